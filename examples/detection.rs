@@ -5,6 +5,11 @@
 
 use nucleus_core::{Config, Server};
 
+// Reports per-subsystem heap usage in `stats` when built with `--features memory-stats`
+#[cfg(feature = "memory-stats")]
+#[global_allocator]
+static ALLOCATOR: nucleus_core::memory::TrackingAllocator = nucleus_core::memory::TrackingAllocator;
+
 #[tokio::main]
 async fn main() {
     let config = Config::default();
@@ -43,6 +43,15 @@ fn print_rag_config(config: &Config) {
         nucleus_core::config::StorageMode::Grpc { url } => {
             println!("  Storage: Remote gRPC @ {}", url);
         }
+        nucleus_core::config::StorageMode::Sqlite { path } => {
+            println!("  Storage: SQLite at {}", path);
+        }
+        nucleus_core::config::StorageMode::Postgres { .. } => {
+            println!("  Storage: Postgres with pgvector");
+        }
+        nucleus_core::config::StorageMode::Chroma { url, .. } => {
+            println!("  Storage: Chroma @ {}", url);
+        }
     }
     println!("  Collection: {}", config.storage.vector_db.collection_name);
     println!("  Embedding: {}", config.rag.embedding_model.name);
@@ -87,6 +96,15 @@ fn print_summary(config: &Config, doc_count: usize) {
         nucleus_core::config::StorageMode::Grpc { url } => {
             println!("Collection '{}' @ {}", config.storage.vector_db.collection_name, url);
         }
+        nucleus_core::config::StorageMode::Sqlite { path } => {
+            println!("Collection '{}' in {}", config.storage.vector_db.collection_name, path);
+        }
+        nucleus_core::config::StorageMode::Postgres { .. } => {
+            println!("Collection '{}' in Postgres", config.storage.vector_db.collection_name);
+        }
+        nucleus_core::config::StorageMode::Chroma { url, .. } => {
+            println!("Collection '{}' @ {}", config.storage.vector_db.collection_name, url);
+        }
     }
     println!("{} documents indexed", doc_count);
     println!("Data persists across restarts");
@@ -22,7 +22,7 @@
 use async_trait::async_trait;
 use nucleus_core::{ChatManager, Config};
 use nucleus_plugin::{Plugin, PluginRegistry, Permission};
-use nucleus_std::{ReadFilePlugin, SearchPlugin, WriteFilePlugin};
+use nucleus_std::{file_plugins, ListDirectoryPlugin, ReadFilePlugin, SearchPlugin, WriteFilePlugin};
 use serde_json::json;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -44,8 +44,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut registry = PluginRegistry::new(Permission::READ_WRITE);
     
     // Step 3: Register plugins (tools the LLM can use)
-    registry.register(Arc::new(ReadFilePlugin::new()));
-    registry.register(Arc::new(WriteFilePlugin::new()));
+    for plugin in file_plugins(&config.plugins) {
+        registry.register(plugin);
+    }
     
     println!("Registered plugins:");
     for plugin in registry.all() {
@@ -157,6 +158,7 @@ pub struct Server {
 fn register_plugins(registry: &mut PluginRegistry) {
     registry.register(Arc::new(ReadFilePlugin::new()));
     registry.register(Arc::new(WriteFilePlugin::new()));
+    registry.register(Arc::new(ListDirectoryPlugin::new()));
     registry.register(Arc::new(SearchPlugin::new()));
     registry.register(Arc::new(MockSemanticSearchPlugin));
     registry.register(Arc::new(MockCommandExecutor));
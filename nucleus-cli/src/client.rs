@@ -0,0 +1,58 @@
+//! Thin wrapper over the core socket client.
+//!
+//! Requests are sent as part of this terminal's session (see
+//! [`terminal_session`]), so private mode and conversation history follow
+//! the terminal.
+
+use anyhow::Result;
+use nucleus_core::client::AiClient;
+use nucleus_core::rag::IndexProgress;
+use nucleus_core::server::{Request, StreamChunk};
+
+/// Sends a request and streams partial chunks to `on_chunk`.
+///
+/// Returns the content of the final `done` chunk.
+pub fn send<F>(request: &Request, on_chunk: F) -> Result<String>
+where
+    F: FnMut(&str),
+{
+    send_for_done(request, on_chunk).map(|chunk| chunk.content)
+}
+
+/// Like [`send`], but returns the whole `done` chunk (response ID, truncation flag).
+pub fn send_for_done<F>(request: &Request, on_chunk: F) -> Result<StreamChunk>
+where
+    F: FnMut(&str),
+{
+    Ok(client().send(&in_session(request), on_chunk)?)
+}
+
+/// Like [`send_for_done`], passing indexing progress to `on_progress`.
+pub fn send_with_progress(request: &Request, on_progress: fn(u64, &IndexProgress)) -> Result<StreamChunk> {
+    Ok(client().with_progress(on_progress).send(&in_session(request), |_| {})?)
+}
+
+/// The session of this terminal: `NUCLEUS_SESSION` if set, else the
+/// shell's process ID.
+fn terminal_session() -> Option<String> {
+    if let Some(session) = std::env::var("NUCLEUS_SESSION").ok().filter(|session| !session.is_empty()) {
+        return Some(session);
+    }
+    #[cfg(unix)]
+    return Some(format!("pid-{}", std::os::unix::process::parent_id()));
+    #[cfg(not(unix))]
+    None
+}
+
+/// `request` in this terminal's session, unless it names one.
+fn in_session(request: &Request) -> Request {
+    let mut request = request.clone();
+    if request.session_id.is_none() {
+        request.session_id = terminal_session();
+    }
+    request
+}
+
+fn client() -> AiClient {
+    AiClient::new().with_warming_up(|warming_up| eprintln!("{}", warming_up))
+}
@@ -0,0 +1,336 @@
+//! `nucleus doctor`: checks every part of the stack and suggests fixes.
+
+use anyhow::Result;
+use colored::Colorize;
+use nucleus_core::client::{AiClient, ClientError};
+use nucleus_core::config::{Config, ProviderKind, StorageMode, UpdateConfig};
+use nucleus_core::provider::Capabilities;
+use nucleus_core::rag::distance;
+use nucleus_core::server::{Component, Request, RequestType, ServerStatus};
+use nucleus_core::shell_integration::Shell;
+use nucleus_core::update::Release;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Result of one check.
+enum Outcome {
+    Pass(String),
+    /// Works, but not as intended
+    Warn { problem: String, fix: String },
+    Fail { problem: String, fix: String },
+    /// Could not be checked because an earlier check failed
+    Skip(String),
+}
+
+fn fail(problem: impl Into<String>, fix: impl Into<String>) -> Outcome {
+    Outcome::Fail {
+        problem: problem.into(),
+        fix: fix.into(),
+    }
+}
+
+fn warn(problem: impl Into<String>, fix: impl Into<String>) -> Outcome {
+    Outcome::Warn {
+        problem: problem.into(),
+        fix: fix.into(),
+    }
+}
+
+fn report(name: &str, outcome: &Outcome) {
+    match outcome {
+        Outcome::Pass(detail) => println!("{} {}: {}", "✓".green(), name.bold(), detail),
+        Outcome::Warn { problem, fix } => {
+            println!("{} {}: {}", "!".yellow(), name.bold(), problem);
+            println!("    {} {}", "→".yellow(), fix);
+        }
+        Outcome::Fail { problem, fix } => {
+            println!("{} {}: {}", "✗".red(), name.bold(), problem);
+            println!("    {} {}", "→".red(), fix);
+        }
+        Outcome::Skip(reason) => println!("{} {}: {}", "-".dimmed(), name.bold(), reason.dimmed()),
+    }
+}
+
+/// Runs all checks, printing each result, and fails if any check failed.
+pub fn run(config_path: &Path) -> Result<()> {
+    let mut outcomes = Vec::new();
+    let mut check = |name: &str, outcome: Outcome| {
+        report(name, &outcome);
+        outcomes.push(outcome);
+    };
+
+    let config = match Config::load(config_path) {
+        Ok(config) => {
+            check("Config", Outcome::Pass(format!("loaded {}", config_path.display())));
+            config
+        }
+        Err(e) => {
+            check("Config", fail(
+                format!("could not load {}: {}", config_path.display(), e),
+                format!("Copy examples/config.yaml to {} or pass --config; checking with defaults", config_path.display()),
+            ));
+            Config::default()
+        }
+    };
+
+    let client = AiClient::new();
+    let mut mismatch = None;
+    let status = match server_status(&client) {
+        Ok(status) => {
+            check("Server", Outcome::Pass(format!("listening on {}", client.socket_path().display())));
+            Some(status)
+        }
+        Err(ClientError::Incompatible(incompatible)) => {
+            check("Server", Outcome::Pass(format!("listening on {}", client.socket_path().display())));
+            mismatch = Some(incompatible);
+            None
+        }
+        Err(ClientError::Connect { path, .. }) => {
+            check("Server", fail(
+                format!("nothing is listening on {}", path),
+                "Start the nucleus server; it creates the socket on startup",
+            ));
+            None
+        }
+        Err(e) => {
+            check("Server", fail(
+                format!("the server did not answer a status request: {}", e),
+                "The server is probably older than this CLI; restart it after upgrading",
+            ));
+            None
+        }
+    };
+
+    match (&status, &mismatch) {
+        (_, Some(mismatch)) => check("Version", fail(
+            format!(
+                "server is {} (protocol {}), CLI is {} (protocol {}), and they cannot talk to each other",
+                mismatch.server.version, mismatch.server.protocol, mismatch.client.version, mismatch.client.protocol
+            ),
+            mismatch.fix(),
+        )),
+        (Some(status), None) if status.version == nucleus_core::VERSION => {
+            check("Version", Outcome::Pass(format!("server and CLI are both {}", status.version)))
+        }
+        (Some(status), None) => check("Version", warn(
+            format!("server is {}, CLI is {}; they are compatible", status.version, nucleus_core::VERSION),
+            "Restart the server so it runs the same version as the CLI",
+        )),
+        (None, None) => check("Version", Outcome::Skip("no server to ask".to_string())),
+    }
+
+    if config.llm.provider == Some(ProviderKind::Ollama) {
+        match ollama_models(&config.llm.base_url) {
+            Ok(models) => {
+                check("Ollama", Outcome::Pass(format!("running at {}", config.llm.base_url)));
+                let missing: Vec<&str> = [config.llm.model.as_str(), config.rag.embedding_model.name.as_str()]
+                    .into_iter()
+                    .filter(|model| !has_model(&models, model))
+                    .collect();
+                if missing.is_empty() {
+                    check("Models", Outcome::Pass(format!("{} and {} are pulled", config.llm.model, config.rag.embedding_model.name)));
+                } else {
+                    let pulls: Vec<String> = missing.iter().map(|model| format!("ollama pull {}", model)).collect();
+                    check("Models", fail(format!("missing {}", missing.join(", ")), format!("Run `{}`", pulls.join(" && "))));
+                }
+            }
+            Err(e) => {
+                check("Ollama", fail(
+                    format!("not reachable at {}: {}", config.llm.base_url, e),
+                    "Start it with `ollama serve`, or fix llm.base_url",
+                ));
+                check("Models", Outcome::Skip("Ollama is not reachable".to_string()));
+            }
+        }
+    }
+
+    match &status {
+        Some(ServerStatus { warming_up, .. }) if warming_up.contains(&Component::Model) => {
+            check("Embeddings", Outcome::Skip("the server is still starting the model".to_string()))
+        }
+        Some(ServerStatus { model_dim: Some(model_dim), store_dim, .. }) if model_dim == store_dim => {
+            check("Embeddings", Outcome::Pass(format!("{} returns {}-dimensional vectors, as the store expects", config.rag.embedding_model.name, model_dim)))
+        }
+        Some(ServerStatus { model_dim: Some(model_dim), store_dim, embedding_model, .. }) => check("Embeddings", fail(
+            format!("{} returns {}-dimensional vectors, but the store expects {}", embedding_model, model_dim, store_dim),
+            format!("Set rag.embedding_model.embedding_dim to {}, clear the knowledge base, and re-index", model_dim),
+        )),
+        Some(ServerStatus { embedding_error, embedding_model, .. }) => check("Embeddings", fail(
+            format!("{} failed: {}", embedding_model, embedding_error.as_deref().unwrap_or("no embedding returned")),
+            "Check that the embedding model is installed and the provider is running",
+        )),
+        None => check("Embeddings", Outcome::Skip("no server to ask".to_string())),
+    }
+
+    check("Distance", distance_metrics(&config));
+
+    match status.as_ref().and_then(|status| status.capabilities) {
+        Some(Capabilities { max_context: Some(max_context), .. }) if config.llm.context_length > max_context => {
+            check("Model", warn(
+                format!("llm.context_length is {}, but {} only has a {}-token window", config.llm.context_length, config.llm.model, max_context),
+                format!("Set llm.context_length to {} or less", max_context),
+            ))
+        }
+        Some(capabilities) => check("Model", Outcome::Pass(describe_capabilities(&config.llm.model, &capabilities))),
+        None => check("Model", Outcome::Skip("no server to ask, or the model is still starting".to_string())),
+    }
+
+    match &status {
+        Some(ServerStatus { warming_up, .. }) if warming_up.contains(&Component::KnowledgeBase) => {
+            check("Store", Outcome::Skip("the server is still connecting to the store".to_string()))
+        }
+        Some(ServerStatus { documents: Some(0), .. }) => check("Store", warn(
+            "healthy, but the knowledge base is empty",
+            "Index a project with an `index` request, e.g. from the Python client",
+        )),
+        Some(ServerStatus { documents: Some(documents), .. }) => {
+            check("Store", Outcome::Pass(format!("healthy, {} documents", documents)))
+        }
+        Some(ServerStatus { store_error, .. }) => check("Store", fail(
+            store_error.clone().unwrap_or_else(|| "not reachable".to_string()),
+            store_fix(&config.storage.storage_mode),
+        )),
+        None => check("Store", Outcome::Skip("no server to ask".to_string())),
+    }
+
+    check("Shell hooks", shell_hooks());
+    check("Updates", updates(&config.updates));
+
+    let failed = outcomes.iter().filter(|outcome| matches!(outcome, Outcome::Fail { .. })).count();
+    println!();
+    if failed > 0 {
+        anyhow::bail!("{} {} failed", failed, if failed == 1 { "check" } else { "checks" });
+    }
+    println!("{}", "Everything looks good.".green());
+    Ok(())
+}
+
+/// Whether the collections compare embeddings the way the embedding model was trained to.
+fn distance_metrics(config: &Config) -> Outcome {
+    let model = &config.rag.embedding_model;
+    let problems = distance::check(&config.storage.vector_db, model);
+    if !problems.is_empty() {
+        return warn(
+            problems.join("; "),
+            "Set the metric the model was trained for, then clear and re-index the affected collections",
+        );
+    }
+    let configured = config.storage.vector_db.distance.as_str();
+    match distance::trained_metric(model) {
+        Some(_) => Outcome::Pass(format!("{} distance, as {} was trained for", configured, model.name)),
+        None => Outcome::Pass(format!("{} distance; not known which metric {} was trained for", configured, model.name)),
+    }
+}
+
+/// e.g. `qwen3 streams, uses tools, 40960-token context`.
+fn describe_capabilities(model: &str, capabilities: &Capabilities) -> String {
+    let mut supports = Vec::new();
+    if capabilities.streaming {
+        supports.push("streams".to_string());
+    }
+    supports.push(if capabilities.tools { "uses tools" } else { "no tools" }.to_string());
+    if capabilities.vision {
+        supports.push("sees images".to_string());
+    }
+    if let Some(max_context) = capabilities.max_context {
+        supports.push(format!("{}-token context", max_context));
+    }
+    format!("{} {}", model, supports.join(", "))
+}
+
+fn server_status(client: &AiClient) -> Result<ServerStatus, ClientError> {
+    let done = client.send(&Request::new(RequestType::Status, ""), |_| {})?;
+    Ok(serde_json::from_str(&done.content)?)
+}
+
+/// Names of the models Ollama has pulled.
+fn ollama_models(base_url: &str) -> Result<Vec<String>> {
+    #[derive(serde::Deserialize)]
+    struct Model {
+        name: String,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Tags {
+        models: Vec<Model>,
+    }
+
+    let tags: Tags = reqwest::blocking::Client::new()
+        .get(format!("{}/api/tags", base_url))
+        .timeout(Duration::from_secs(5))
+        .send()?
+        .error_for_status()?
+        .json()?;
+    Ok(tags.models.into_iter().map(|model| model.name).collect())
+}
+
+/// Ollama names models without a tag `:latest`.
+fn has_model(models: &[String], model: &str) -> bool {
+    models.iter().any(|name| name == model || name.strip_suffix(":latest") == Some(model))
+}
+
+fn updates(config: &UpdateConfig) -> Outcome {
+    if !config.check {
+        return Outcome::Skip("turned off; set updates.check to look for new releases".to_string());
+    }
+    let latest = || -> Result<Release> {
+        Ok(reqwest::blocking::Client::new()
+            .get(&config.url)
+            .header(reqwest::header::USER_AGENT, format!("nucleus/{}", nucleus_core::VERSION))
+            .timeout(Duration::from_secs(10))
+            .send()?
+            .error_for_status()?
+            .json()?)
+    };
+    match latest() {
+        Ok(release) if release.is_newer_than(nucleus_core::VERSION) => warn(
+            format!("nucleus {} is available (running {})", release.version(), nucleus_core::VERSION),
+            format!("See {} for what changed", release.html_url),
+        ),
+        Ok(_) => Outcome::Pass(format!("{} is the latest release", nucleus_core::VERSION)),
+        Err(e) => warn(
+            format!("could not check for a new release: {}", e),
+            format!("Check that {} is reachable, or turn off updates.check", config.url),
+        ),
+    }
+}
+
+fn store_fix(mode: &StorageMode) -> String {
+    match mode {
+        StorageMode::Grpc { url } => format!("Check that Qdrant is running at {}", url),
+        StorageMode::Chroma { url, .. } => format!("Check that Chroma is running at {}", url),
+        // The URL may contain a password
+        StorageMode::Postgres { .. } => {
+            "Check that Postgres is reachable at storage_mode.url and has the pgvector extension".to_string()
+        }
+        StorageMode::Embedded { path } | StorageMode::Sqlite { path } => {
+            format!("Check that {} is readable; if it is corrupt, move it away and re-index", path)
+        }
+    }
+}
+
+fn shell_hooks() -> Outcome {
+    let Some(shell) = Shell::detect() else {
+        return warn("could not detect your shell", "Install the hooks as described by `nucleus shell-init <shell>`");
+    };
+    let (Some(file), Some(line)) = (shell.startup_file(), shell.install_line()) else {
+        return warn(
+            format!("cannot check {} hooks automatically", shell),
+            format!("Make sure your {} profile runs `nucleus shell-init {}`", shell, shell.name()),
+        );
+    };
+
+    let Some(home) = std::env::var_os("HOME").map(PathBuf::from) else {
+        return warn("HOME is not set", format!("Add `{}` to ~/{}", line, file));
+    };
+    let path = home.join(file);
+    match std::fs::read_to_string(&path) {
+        Ok(content) if content.contains("nucleus shell-init") => {
+            Outcome::Pass(format!("{} hooks installed in {}", shell, path.display()))
+        }
+        _ => warn(
+            format!("{} hooks are not installed in {}", shell, path.display()),
+            format!("Add `{}` to {}", line, path.display()),
+        ),
+    }
+}
@@ -1,8 +1,25 @@
+mod client;
+mod doctor;
+
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use colored::Colorize;
-use nucleus_core::config::Config;
-use std::path::PathBuf;
+use nucleus_core::attachment::Attachment;
+use nucleus_core::client::Pacer;
+use nucleus_core::config::{Config, DisplayConfig, Verbosity};
+use nucleus_core::environment::EnvironmentContext;
+use nucleus_core::expression::ExpressionKind;
+use nucleus_core::feedback::Rating;
+use nucleus_core::plugins::PluginInfo;
+use nucleus_core::project_tree::{self, TreeOptions};
+use nucleus_core::rag::{starter, CollectionInfo, ContextPack, IndexProgress, KnowledgeDiff, SnapshotSummary, TrashEntry};
+use nucleus_core::memory;
+use nucleus_core::request_log::{self, LoggedRequest};
+use nucleus_core::render::{BlockRenderer, RawBlock};
+use nucleus_core::response_log::{self, LoggedResponse, Outcome};
+use nucleus_core::server::{ClientVersion, IndexStats, JobInfo, Request, RequestType, SessionInfo};
+use nucleus_core::shell_integration::Shell;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "nucleus")]
@@ -26,6 +43,469 @@ enum Commands {
         #[command(subcommand)]
         command: ModelCommands,
     },
+
+    #[command(about = "Ask the AI a question (requires a running server)")]
+    Ask {
+        #[arg(required = true, num_args = 1.., help = "Question to ask")]
+        question: Vec<String>,
+
+        #[arg(long, help = "Don't attach OS, shell, git, and toolchain details")]
+        no_env: bool,
+
+        #[arg(long, help = "Stop generating after this many milliseconds")]
+        max_time_ms: Option<u64>,
+
+        #[arg(long, help = "Stop generating after this many tokens")]
+        max_tokens: Option<u32>,
+
+        #[arg(long, value_parser = ["terse", "normal", "detailed"], help = "Answer length (default: verbosity.default)")]
+        verbosity: Option<String>,
+
+        #[arg(long, help = "Attach a summarized tree of the current directory")]
+        tree: bool,
+
+        #[arg(short, long = "attach", value_name = "FILE", help = "Attach a file for this question (use - for stdin)")]
+        attachments: Vec<String>,
+
+        #[arg(long, value_name = "CHARS_PER_SEC", help = "Render the answer at most this fast; Enter shows the rest at once (default: display.chars_per_sec, 0 = off)")]
+        pace: Option<u32>,
+
+        #[arg(long, help = "Search every collection (code, team, man pages, dotfiles, conversations) and label sources")]
+        all_collections: bool,
+
+        #[arg(long, help = "Named knowledge base collection to search (default: the one for the current directory)")]
+        collection: Option<String>,
+
+        #[arg(long, help = "Offer to save a table or JSON block of the answer as written (default: display.save_blocks)")]
+        save_block: bool,
+    },
+
+    #[command(about = "Ask the last question again for a shorter answer (requires a running server)")]
+    Shorter,
+
+    #[command(about = "Explain the meaningful differences between two files (requires a running server)")]
+    Diff {
+        #[arg(help = "Old version (use - for stdin)")]
+        old: String,
+
+        #[arg(help = "New version (use - for stdin)")]
+        new: String,
+
+        #[arg(short, long, help = "What to pay particular attention to")]
+        focus: Option<String>,
+    },
+
+    #[command(about = "Summarize uncommitted changes and recent commits (requires a running server)")]
+    WhatsChanged {
+        #[arg(help = "What to pay particular attention to")]
+        focus: Option<String>,
+
+        #[arg(long, default_value_t = nucleus_core::changes::RECENT_COMMITS, help = "Recent commits to include")]
+        commits: usize,
+    },
+
+    #[command(about = "List TODO, FIXME, and HACK comments in indexed files (requires a running server)")]
+    Todos {
+        #[arg(help = "Directory to look in (defaults to the current directory)")]
+        dir: Option<PathBuf>,
+
+        #[arg(long, help = "Have the LLM group and prioritize them")]
+        summarize: bool,
+    },
+
+    #[command(about = "Summarize a log file and cluster its errors (requires a running server)")]
+    AnalyzeLog {
+        #[arg(help = "Log file to analyze")]
+        path: PathBuf,
+    },
+
+    #[command(about = "Generate a regex checked against a sample (requires a running server)")]
+    Regex {
+        #[arg(required = true, num_args = 1.., help = "What the regex should match")]
+        description: Vec<String>,
+
+        #[arg(short, long, value_name = "FILE", help = "Sample input (use - for stdin)")]
+        sample: String,
+    },
+
+    #[command(about = "Generate a jq filter checked against a sample (requires a running server)")]
+    Jq {
+        #[arg(required = true, num_args = 1.., help = "What the filter should do")]
+        description: Vec<String>,
+
+        #[arg(short, long, value_name = "FILE", help = "Sample JSON or JSON lines (use - for stdin)")]
+        sample: String,
+    },
+
+    #[command(about = "Index a directory into the knowledge base, showing progress (requires a running server)")]
+    Index {
+        #[arg(default_value = ".", help = "Directory to index")]
+        dir: PathBuf,
+
+        #[arg(long, help = "Collection to index into instead of the one for the directory")]
+        collection: Option<String>,
+
+        #[arg(long, help = "Return the job ID right away and index in the background")]
+        detach: bool,
+    },
+
+    #[command(about = "Index man pages and --help output of the commands on PATH (requires a running server)")]
+    IndexCommands,
+
+    #[command(about = "Index your shell, editor, and tool configuration files (requires a running server and dotfiles.enabled)")]
+    IndexDotfiles,
+
+    #[command(about = "Fetch a web page and index its readable text (requires a running server)")]
+    IndexUrl {
+        #[arg(help = "URL of the page")]
+        url: String,
+    },
+
+    #[command(about = "Show an annotated tree of a project directory")]
+    Tree {
+        #[arg(default_value = ".", help = "Project directory")]
+        path: PathBuf,
+
+        #[arg(short, long, default_value_t = 3, help = "Directory levels to expand")]
+        depth: usize,
+    },
+
+    #[command(about = "Print shell hooks that mark commands and their output (OSC 133)")]
+    ShellInit {
+        #[arg(help = "Shell to print hooks for (bash, zsh, fish, powershell, or cmd; detected if omitted)")]
+        shell: Option<String>,
+    },
+
+    #[command(about = "Context pack commands (requires a running server)")]
+    Pack {
+        #[command(subcommand)]
+        command: PackCommands,
+    },
+
+    #[command(about = "Manage the shared team knowledge base (requires a running server)")]
+    Team {
+        #[command(subcommand)]
+        command: TeamCommands,
+    },
+
+    #[command(about = "Rate responses and export eval sets (requires a running server)")]
+    Feedback {
+        #[command(subcommand)]
+        command: FeedbackCommands,
+    },
+
+    #[command(about = "List or print again responses from the response log (requires response_log.enabled)")]
+    Responses {
+        #[command(subcommand)]
+        command: ResponsesCommands,
+    },
+
+    #[command(about = "Send a logged request again to reproduce its answer (requires request_log.enabled)")]
+    Replay {
+        #[arg(help = "Request ID (lists recent requests if omitted)")]
+        id: Option<String>,
+    },
+
+    #[command(about = "Show knowledge base statistics (requires a running server)")]
+    Stats {
+        #[arg(long, help = "Collection to report on instead of the one for this directory")]
+        collection: Option<String>,
+        #[arg(long, help = "Print the statistics as JSON")]
+        json: bool,
+        #[arg(long, default_value_t = 10, help = "Number of sources to list, most chunks first")]
+        sources: usize,
+    },
+
+    #[command(about = "List, inspect, or cancel running jobs such as indexing (requires a running server)")]
+    Jobs {
+        #[command(subcommand)]
+        command: JobsCommands,
+    },
+
+    #[command(about = "List, enable, or disable the plugins found in plugins.path (requires a running server)")]
+    Plugins {
+        #[command(subcommand)]
+        command: PluginsCommands,
+    },
+
+    #[command(about = "Manage named knowledge base collections, e.g. one per project (requires a running server)")]
+    Collection {
+        #[command(subcommand)]
+        command: CollectionCommands,
+    },
+
+    #[command(about = "Restore cleared or removed knowledge (requires a running server)")]
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands,
+    },
+
+    #[command(about = "Snapshot the knowledge base and compare it with a snapshot later (requires a running server)")]
+    Kb {
+        #[command(subcommand)]
+        command: KbCommands,
+    },
+
+    #[command(about = "Summarize a conversation into the knowledge base (requires a running server)")]
+    Remember {
+        #[arg(help = "Response ID printed after the answer")]
+        response_id: String,
+    },
+
+    #[command(about = "Check the server, models, store, and shell hooks, and suggest fixes")]
+    Doctor,
+
+    /// Runs the model for a server with `llm.worker.enabled`, over stdin and stdout
+    #[command(hide = true)]
+    ModelWorker,
+
+    #[command(about = "Turn private mode on or off for this terminal (requires a running server)")]
+    Private {
+        #[arg(default_value = "status", value_parser = ["on", "off", "status"])]
+        state: String,
+    },
+
+    #[command(about = "Start a new conversation in this terminal (requires a running server)")]
+    New,
+
+    #[command(about = "List, resume, or delete stored conversations (requires a running server)")]
+    Sessions {
+        #[command(subcommand)]
+        command: SessionsCommands,
+    },
+
+    #[command(about = "Print workspace events as JSON lines while they happen (requires a running server)")]
+    Events {
+        #[arg(help = "Event types to print (file-indexed, chat-completed, edit-applied, plugin-executed); all if none")]
+        types: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum FeedbackCommands {
+    #[command(about = "Mark a response as good")]
+    Up {
+        response_id: String,
+        #[arg(help = "Optional comment")]
+        comment: Option<String>,
+    },
+
+    #[command(about = "Mark a response as bad")]
+    Down {
+        response_id: String,
+        #[arg(help = "What was wrong with the response")]
+        comment: Option<String>,
+    },
+
+    #[command(about = "Export rated responses as a JSONL eval set")]
+    Export {
+        file: PathBuf,
+        #[arg(long, value_parser = ["up", "down"], help = "Only export responses with this rating")]
+        rating: Option<String>,
+    },
+
+    #[command(about = "Compare ratings across experiment variants")]
+    Stats,
+}
+
+#[derive(Subcommand)]
+enum ResponsesCommands {
+    #[command(about = "List the most recent logged responses")]
+    List {
+        #[arg(long, default_value_t = 10, help = "Number of responses to list")]
+        last: usize,
+    },
+
+    #[command(about = "Print a logged response")]
+    Show {
+        #[arg(help = "Log or response ID (defaults to the most recent response)")]
+        id: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CollectionCommands {
+    #[command(about = "List collections and the directories they belong to")]
+    List,
+
+    #[command(about = "Create an empty collection")]
+    Create {
+        #[arg(help = "Name (lowercase letters, digits, '-', and '_')")]
+        name: String,
+
+        #[arg(long, help = "Project directory whose requests use the collection (default: none)")]
+        root: Option<PathBuf>,
+    },
+
+    #[command(about = "Delete a collection and everything indexed into it")]
+    Delete {
+        name: String,
+    },
+
+    #[command(about = "Use a collection outside every collection's project directory")]
+    Switch {
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashCommands {
+    #[command(about = "List removals kept in the trash, newest first")]
+    List,
+
+    #[command(about = "Put a removal's documents back where they were")]
+    Restore {
+        #[arg(help = "Entry ID from `trash list`")]
+        id: String,
+    },
+
+    #[command(about = "Delete trash entries for good")]
+    Purge {
+        #[arg(help = "Entry ID from `trash list`", required_unless_present = "all")]
+        id: Option<String>,
+
+        #[arg(long, conflicts_with = "id", help = "Delete every entry")]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum KbCommands {
+    #[command(about = "Record the sources of this directory's collection and hashes of their content")]
+    Snapshot {
+        #[arg(help = "Snapshot name, e.g. 'before-demo' (named after the time if omitted; replaces one of the same name)")]
+        name: Option<String>,
+
+        #[arg(long, help = "Collection to snapshot instead of the one for this directory")]
+        collection: Option<String>,
+    },
+
+    #[command(about = "List snapshots, newest first")]
+    Snapshots,
+
+    #[command(about = "Show sources added, removed, and changed since a snapshot")]
+    Diff {
+        #[arg(help = "Snapshot name from `kb snapshots`")]
+        snapshot: String,
+
+        #[arg(long, help = "Print the differences as JSON")]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionsCommands {
+    #[command(about = "List stored conversations, most recently used first")]
+    List,
+
+    #[command(about = "Continue a stored conversation in this terminal")]
+    Resume {
+        #[arg(help = "Conversation ID from `sessions list`")]
+        id: String,
+    },
+
+    #[command(about = "Delete a stored conversation")]
+    Delete {
+        #[arg(help = "Conversation ID from `sessions list`")]
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum JobsCommands {
+    #[command(about = "List running jobs")]
+    List,
+
+    #[command(about = "Cancel a job; work it finished is kept")]
+    Cancel {
+        #[arg(help = "Job ID from `jobs list`")]
+        id: u64,
+    },
+
+    #[command(about = "Show a job's progress, or how it ended if it finished recently")]
+    Status {
+        #[arg(help = "Job ID from `jobs list` or `index --detach`")]
+        id: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum PluginsCommands {
+    #[command(about = "List the plugins found, and why any cannot be used")]
+    List,
+
+    #[command(about = "Enable a plugin")]
+    Enable {
+        #[arg(help = "Plugin name from `plugins list`")]
+        name: String,
+    },
+
+    #[command(about = "Disable a plugin; it stays disabled until enabled again")]
+    Disable {
+        #[arg(help = "Plugin name from `plugins list`")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TeamCommands {
+    #[command(about = "Index a directory into the shared knowledge base")]
+    Index {
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+
+    #[command(about = "Remove a file or directory from the shared knowledge base")]
+    Remove {
+        path: PathBuf,
+    },
+
+    #[command(about = "Remove everything from the shared knowledge base")]
+    Clear {
+        #[arg(long, help = "Confirm clearing the shared knowledge base for the whole team")]
+        yes: bool,
+    },
+
+    #[command(about = "Show shared knowledge base statistics")]
+    Stats,
+}
+
+#[derive(Subcommand)]
+enum PackCommands {
+    #[command(about = "Export indexed sources, prompts, and memories to a pack file")]
+    Export {
+        #[arg(help = "Pack file to write (e.g., 'onboarding.pack')")]
+        file: PathBuf,
+
+        #[arg(short, long, help = "Pack name (defaults to the file name)")]
+        name: Option<String>,
+
+        #[arg(short, long)]
+        description: Option<String>,
+
+        #[arg(short, long = "source", help = "Indexed file or directory to include (repeatable)")]
+        sources: Vec<String>,
+
+        #[arg(short, long = "prompt", value_name = "NAME=TEXT", help = "Named prompt to include (repeatable)")]
+        prompts: Vec<String>,
+
+        #[arg(short, long = "memory", help = "Note to include (repeatable)")]
+        memories: Vec<String>,
+    },
+
+    #[command(about = "Import a pack file into the knowledge base")]
+    Import {
+        file: PathBuf,
+    },
+
+    #[command(about = "List imported packs")]
+    List,
+
+    #[command(about = "Download a starter pack of general knowledge into its own collection")]
+    Install {
+        #[arg(value_parser = starter_pack_names(), help = "Starter pack to install")]
+        name: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -58,6 +538,131 @@ fn main() -> Result<()> {
             ModelCommands::Set { model } => set_model(&cli.config, &model),
             ModelCommands::List { url } => list_models(&url),
         },
+        Commands::Ask {
+            question,
+            no_env,
+            max_time_ms,
+            max_tokens,
+            verbosity,
+            tree,
+            attachments,
+            pace,
+            all_collections,
+            collection,
+            save_block,
+        } => {
+            let mut display = Config::load(&cli.config).map(|config| config.display).unwrap_or_default();
+            display.save_blocks |= save_block;
+            let pace = pace.unwrap_or(display.chars_per_sec);
+            let mut request = chat_request(&question.join(" "), !no_env, tree, &attachments, max_time_ms, max_tokens)?;
+            if all_collections {
+                request = request.with_all_collections();
+            }
+            if let Some(collection) = collection {
+                request = request.with_collection(collection);
+            }
+            if let Some(verbosity) = verbosity.as_deref().and_then(Verbosity::from_name) {
+                request = request.with_verbosity(verbosity);
+            }
+            ask(&request, &attachments, pace, &display)
+        }
+        Commands::Shorter => {
+            let display = Config::load(&cli.config).map(|config| config.display).unwrap_or_default();
+            let request = chat_request("/shorter", true, false, &[], None, None)?;
+            ask(&request, &[], display.chars_per_sec, &display)
+        }
+        Commands::Diff { old, new, focus } => diff(&old, &new, focus.as_deref().unwrap_or_default()),
+        Commands::WhatsChanged { focus, commits } => whats_changed(focus.as_deref().unwrap_or_default(), commits),
+        Commands::Todos { dir, summarize } => todos(dir.as_deref(), summarize),
+        Commands::AnalyzeLog { path } => analyze_log(&path),
+        Commands::Regex { description, sample } => {
+            generate_expression(ExpressionKind::Regex, &description.join(" "), &sample)
+        }
+        Commands::Jq { description, sample } => {
+            generate_expression(ExpressionKind::Jq, &description.join(" "), &sample)
+        }
+        Commands::Index { dir, collection, detach } => index_directory(&dir, collection, detach),
+        Commands::IndexCommands => index_commands(),
+        Commands::IndexDotfiles => index_dotfiles(),
+        Commands::IndexUrl { url } => index_url(&url),
+        Commands::Tree { path, depth } => show_tree(&path, depth),
+        Commands::ShellInit { shell } => shell_init(shell.as_deref()),
+        Commands::Pack { command } => match command {
+            PackCommands::Export {
+                file,
+                name,
+                description,
+                sources,
+                prompts,
+                memories,
+            } => export_pack(file, name, description, sources, prompts, memories),
+            PackCommands::Import { file } => import_pack(file),
+            PackCommands::List => list_packs(),
+            PackCommands::Install { name } => install_starter_pack(&name),
+        },
+        Commands::Team { command } => match command {
+            TeamCommands::Index { dir } => team_request(RequestType::TeamIndex, &dir.to_string_lossy()),
+            TeamCommands::Remove { path } => team_request(RequestType::TeamRemove, &path.to_string_lossy()),
+            TeamCommands::Clear { yes } => {
+                if !yes {
+                    anyhow::bail!("This removes the shared knowledge base for the whole team. Re-run with --yes to confirm.");
+                }
+                team_request(RequestType::TeamClear, "")
+            }
+            TeamCommands::Stats => team_request(RequestType::TeamStats, ""),
+        },
+        Commands::Doctor => doctor::run(&cli.config),
+        Commands::ModelWorker => nucleus_core::provider::worker::run(),
+        Commands::Private { state } => set_privacy(&state),
+        Commands::New => new_conversation(),
+        Commands::Remember { response_id } => remember(&response_id),
+        Commands::Feedback { command } => match command {
+            FeedbackCommands::Up { response_id, comment } => send_feedback(response_id, Rating::Up, comment),
+            FeedbackCommands::Down { response_id, comment } => send_feedback(response_id, Rating::Down, comment),
+            FeedbackCommands::Export { file, rating } => export_feedback(file, rating),
+            FeedbackCommands::Stats => feedback_stats(),
+        },
+        Commands::Responses { command } => match command {
+            ResponsesCommands::List { last } => list_responses(&cli.config, last),
+            ResponsesCommands::Show { id } => show_response(&cli.config, id.as_deref()),
+        },
+        Commands::Replay { id } => match id {
+            Some(id) => replay(&cli.config, &id),
+            None => list_requests(&cli.config),
+        },
+        Commands::Stats { collection, json, sources } => show_stats(collection, json, sources),
+        Commands::Jobs { command } => match command {
+            JobsCommands::List => list_jobs(),
+            JobsCommands::Cancel { id } => cancel_job(id),
+            JobsCommands::Status { id } => job_status(id),
+        },
+        Commands::Plugins { command } => match command {
+            PluginsCommands::List => list_plugins(),
+            PluginsCommands::Enable { name } => collection_request(RequestType::PluginsEnable, &name),
+            PluginsCommands::Disable { name } => collection_request(RequestType::PluginsDisable, &name),
+        },
+        Commands::Collection { command } => match command {
+            CollectionCommands::List => list_collections(),
+            CollectionCommands::Create { name, root } => create_collection(&name, root),
+            CollectionCommands::Delete { name } => collection_request(RequestType::CollectionDelete, &name),
+            CollectionCommands::Switch { name } => collection_request(RequestType::CollectionSwitch, &name),
+        },
+        Commands::Trash { command } => match command {
+            TrashCommands::List => list_trash(),
+            TrashCommands::Restore { id } => collection_request(RequestType::TrashRestore, &id),
+            TrashCommands::Purge { id, .. } => collection_request(RequestType::TrashPurge, id.as_deref().unwrap_or_default()),
+        },
+        Commands::Kb { command } => match command {
+            KbCommands::Snapshot { name, collection } => snapshot_knowledge(name, collection),
+            KbCommands::Snapshots => list_snapshots(),
+            KbCommands::Diff { snapshot, json } => diff_knowledge(&snapshot, json),
+        },
+        Commands::Sessions { command } => match command {
+            SessionsCommands::List => list_sessions(),
+            SessionsCommands::Resume { id } => collection_request(RequestType::SessionResume, &id),
+            SessionsCommands::Delete { id } => collection_request(RequestType::SessionDelete, &id),
+        },
+        Commands::Events { types } => follow_events(&types),
     }
 }
 
@@ -173,3 +778,1023 @@ fn list_models(base_url: &str) -> Result<()> {
 
     Ok(())
 }
+
+/// Turns a file argument into an attachment, reading `-` from stdin.
+fn attachment_arg(path: &str) -> Result<Attachment> {
+    use std::io::Read;
+
+    if path != "-" {
+        // Paths are resolved by the server against the working directory
+        return Ok(Attachment::file(path));
+    }
+    let mut content = String::new();
+    std::io::stdin()
+        .read_to_string(&mut content)
+        .context("Failed to read attachment from stdin")?;
+    Ok(Attachment::inline(content).with_name("stdin"))
+}
+
+fn chat_request(
+    question: &str,
+    with_env: bool,
+    with_tree: bool,
+    attachments: &[String],
+    max_time_ms: Option<u64>,
+    max_tokens: Option<u32>,
+) -> Result<Request> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let mut request = Request::new(RequestType::Chat, question).with_pwd(cwd.to_string_lossy());
+
+    if with_env {
+        request = request.with_environment(EnvironmentContext::collect(&cwd));
+    }
+    if with_tree {
+        request = request.with_tree();
+    }
+    for path in attachments {
+        request = request.with_attachment(attachment_arg(path)?);
+    }
+    if let Some(max_time_ms) = max_time_ms {
+        request = request.with_max_time_ms(max_time_ms);
+    }
+    if let Some(max_tokens) = max_tokens {
+        request = request.with_max_tokens(max_tokens);
+    }
+    Ok(request)
+}
+
+fn ask(request: &Request, attachments: &[String], chars_per_sec: u32, display: &DisplayConfig) -> Result<()> {
+    use std::io::{IsTerminal, Write};
+
+    let interactive = std::io::stdout().is_terminal();
+    // Wrap at the terminal width unless configured; piped output is left alone
+    let wrap_width = display.wrap_width.unwrap_or_else(|| {
+        let columns = std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok());
+        columns.filter(|_| interactive).unwrap_or(0)
+    });
+    let color = colored::control::SHOULD_COLORIZE.should_colorize();
+    let mut renderer = BlockRenderer::new(wrap_width, color).with_structured(!display.raw_blocks);
+    let mut pacer = Pacer::new(chars_per_sec, |text: &str| {
+        print!("{}", renderer.push(text));
+        let _ = std::io::stdout().flush();
+    });
+    // Lines typed in the terminal; Enter while the answer streams renders the
+    // rest at once. stdin may be taken by an attachment
+    let keys = (std::io::stdin().is_terminal() && !attachments.iter().any(|path| path == "-")).then(|| {
+        let skip = pacer.skip_handle();
+        let (lines, keys) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut line = String::new();
+            while std::io::stdin().read_line(&mut line).is_ok_and(|read| read > 0) {
+                skip.store(true, std::sync::atomic::Ordering::Relaxed);
+                if lines.send(std::mem::take(&mut line)).is_err() {
+                    break;
+                }
+            }
+        });
+        keys
+    });
+
+    let done = client::send_for_done(request, |chunk| pacer.push(chunk))?;
+    println!("{}", renderer.finish());
+
+    if done.truncated {
+        println!("{}", "(stopped at the request budget)".yellow());
+    }
+    if let Some(response_id) = done.response_id {
+        println!(
+            "{}",
+            format!(
+                "Rate this answer: nucleus feedback up|down {id} · keep it: nucleus remember {id}",
+                id = response_id
+            )
+            .dimmed()
+        );
+    }
+    if let Some(keys) = keys.filter(|_| offers_save(display, interactive, renderer.blocks())) {
+        save_block(renderer.blocks(), &keys)?;
+    }
+
+    Ok(())
+}
+
+/// Whether to offer saving a block after an answer, which waits for input:
+/// only when asked to, in a terminal, and if the answer has blocks.
+fn offers_save(display: &DisplayConfig, interactive: bool, blocks: &[RawBlock]) -> bool {
+    display.save_blocks && interactive && !blocks.is_empty()
+}
+
+/// Offers to save a table or JSON block of an answer as the model wrote it,
+/// e.g. to open it in an editor or a spreadsheet.
+fn save_block(blocks: &[RawBlock], keys: &std::sync::mpsc::Receiver<String>) -> Result<()> {
+    // Lines typed while the answer streamed were for skipping ahead
+    while keys.try_recv().is_ok() {}
+
+    let choices: Vec<String> =
+        blocks.iter().enumerate().map(|(i, block)| format!("{} {}", i + 1, block.kind.as_str())).collect();
+    println!(
+        "{}",
+        format!("Save a block as written: its number and Enter ({}), or Enter to skip", choices.join(", ")).dimmed()
+    );
+    let Ok(line) = keys.recv() else {
+        return Ok(());
+    };
+    let Some((number, block)) = line
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| Some((number, blocks.get(number.checked_sub(1)?)?)))
+    else {
+        return Ok(());
+    };
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let path = format!("nucleus-block-{}-{}.{}", timestamp, number, block.kind.extension());
+    std::fs::write(&path, format!("{}\n", block.raw)).with_context(|| format!("Failed to write {}", path))?;
+    println!("{} Saved the {} to {}", "✓".green().bold(), block.kind.as_str(), path);
+    Ok(())
+}
+
+fn diff(old: &str, new: &str, focus: &str) -> Result<()> {
+    if old == "-" && new == "-" {
+        anyhow::bail!("Only one side of the diff can be read from stdin");
+    }
+
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let request = Request::new(RequestType::Diff, focus)
+        .with_pwd(cwd.to_string_lossy())
+        .with_attachment(attachment_arg(old)?)
+        .with_attachment(attachment_arg(new)?);
+
+    // The explanation is colorized a line at a time as it streams
+    let mut pending = String::new();
+    let mut streamed = false;
+    let done = client::send(&request, |chunk| {
+        streamed = true;
+        pending.push_str(chunk);
+        while let Some(newline) = pending.find('\n') {
+            let line: String = pending.drain(..=newline).collect();
+            println!("{}", colorize_diff_line(line.trim_end()));
+        }
+    })?;
+
+    // Identical inputs are answered without asking the LLM
+    if !streamed {
+        println!("{} {}", "✓".green().bold(), done);
+    } else if !pending.is_empty() {
+        println!("{}", colorize_diff_line(&pending));
+    }
+    Ok(())
+}
+
+/// Colors an explanation line by its `+`/`-`/`~` marker.
+fn colorize_diff_line(line: &str) -> String {
+    let marker = line.trim_start();
+    if marker.starts_with("+ ") {
+        line.green().to_string()
+    } else if marker.starts_with("- ") {
+        line.red().to_string()
+    } else if marker.starts_with("~ ") {
+        line.yellow().to_string()
+    } else if marker.starts_with("Summary:") {
+        line.bold().to_string()
+    } else {
+        line.to_string()
+    }
+}
+
+fn whats_changed(focus: &str, commits: usize) -> Result<()> {
+    use std::io::Write;
+
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let request = Request::new(RequestType::WhatsChanged, focus)
+        .with_pwd(cwd.to_string_lossy())
+        .with_limit(commits);
+
+    client::send(&request, |chunk| {
+        print!("{}", chunk);
+        let _ = std::io::stdout().flush();
+    })?;
+    println!();
+    Ok(())
+}
+
+fn todos(dir: Option<&Path>, summarize: bool) -> Result<()> {
+    use nucleus_core::todos::{Age, TodoFile};
+    use std::io::Write;
+
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let dir = dir.map(|dir| dir.to_string_lossy().to_string()).unwrap_or_default();
+    let request_type = if summarize { RequestType::TodoSummary } else { RequestType::Todos };
+    let request = Request::new(request_type, dir).with_pwd(cwd.to_string_lossy());
+
+    if summarize {
+        client::send(&request, |chunk| {
+            print!("{}", chunk);
+            let _ = std::io::stdout().flush();
+        })?;
+        println!();
+        return Ok(());
+    }
+
+    let response = client::send(&request, |_| {})?;
+    let files: Vec<TodoFile> = serde_json::from_str(&response).context("Invalid response from server")?;
+    if files.is_empty() {
+        println!("{}", "No TODO, FIXME, or HACK comments in the indexed files".yellow());
+        return Ok(());
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let mut ages = [0usize; Age::ALL.len()];
+    for file in &files {
+        let path = Path::new(&file.path);
+        let path = path.strip_prefix(&cwd).unwrap_or(path);
+        println!("{}", path.display().to_string().bold());
+        for todo in &file.todos {
+            let age = Age::of(todo.committed_at, now);
+            ages[Age::ALL.iter().position(|a| *a == age).unwrap_or_default()] += 1;
+
+            let marker = match todo.marker.as_str() {
+                "FIXME" => todo.marker.red().bold(),
+                "HACK" => todo.marker.yellow().bold(),
+                _ => todo.marker.cyan().bold(),
+            };
+            let origin = match &todo.author {
+                Some(author) => format!("{}, {}", age.as_str(), author),
+                None => age.as_str().to_string(),
+            };
+            println!("  {:>5}  {} {} {}", todo.line, marker, todo.text, format!("({})", origin).dimmed());
+        }
+        println!();
+    }
+
+    let total: usize = ages.iter().sum();
+    let by_age: Vec<String> = Age::ALL.iter()
+        .zip(ages)
+        .filter(|(_, count)| *count > 0)
+        .map(|(age, count)| format!("{} {}", count, age.as_str()))
+        .collect();
+    println!("{} {} in {} files ({})", "✓".green().bold(), total, files.len(), by_age.join(", "));
+    Ok(())
+}
+
+fn analyze_log(path: &Path) -> Result<()> {
+    use std::io::Write;
+
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let request = Request::new(RequestType::AnalyzeLog, path.to_string_lossy())
+        .with_pwd(cwd.to_string_lossy());
+
+    client::send(&request, |chunk| {
+        print!("{}", chunk);
+        let _ = std::io::stdout().flush();
+    })?;
+    println!();
+    Ok(())
+}
+
+fn generate_expression(kind: ExpressionKind, description: &str, sample: &str) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let request = Request::new(RequestType::GenerateExpression, description)
+        .with_pwd(cwd.to_string_lossy())
+        .with_attachment(attachment_arg(sample)?)
+        .with_expression_kind(kind);
+
+    // Progress goes to stderr so the expression can be captured from stdout
+    let expression = client::send(&request, |chunk| eprint!("{}", chunk.dimmed()))?;
+
+    println!("{}", expression);
+    Ok(())
+}
+
+fn index_commands() -> Result<()> {
+    let request = Request::new(RequestType::IndexCommands, "");
+
+    let response = client::send(&request, |chunk| print!("{}", chunk))?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn index_dotfiles() -> Result<()> {
+    let request = Request::new(RequestType::IndexDotfiles, "");
+
+    let response = client::send(&request, |chunk| print!("{}", chunk))?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn index_directory(dir: &Path, collection: Option<String>, detach: bool) -> Result<()> {
+    use std::io::IsTerminal;
+
+    let dir = std::path::absolute(dir).with_context(|| format!("Invalid directory: {}", dir.display()))?;
+    let dir = dir.to_string_lossy();
+    let mut request = Request::new(RequestType::Index, dir.as_ref()).with_pwd(dir.as_ref()).with_detach(detach);
+    if let Some(collection) = collection {
+        request = request.with_collection(collection);
+    }
+
+    let on_progress: fn(u64, &IndexProgress) = if std::io::stderr().is_terminal() {
+        |job, progress| eprint!("\r\x1b[2K[job {}] {}", job, format_progress(progress))
+    } else {
+        |_, _| {}
+    };
+    let done = client::send_with_progress(&request, on_progress);
+    if !detach && std::io::stderr().is_terminal() {
+        eprint!("\r\x1b[2K");
+    }
+    let done = done?;
+
+    println!("{} {}", "✓".green().bold(), done.content);
+    if let (true, Some(job)) = (detach, done.job) {
+        println!("Follow it with `nucleus jobs status {}`, or stop it with `nucleus jobs cancel {}`", job, job);
+    }
+    Ok(())
+}
+
+/// e.g. `120/340 files  main.rs`
+fn format_progress(progress: &IndexProgress) -> String {
+    let current = progress
+        .current
+        .as_deref()
+        .and_then(|current| Path::new(current).file_name())
+        .map(|name| format!("  {}", name.to_string_lossy()))
+        .unwrap_or_default();
+    format!("{}/{} files{}", progress.files_done, progress.files_total, current)
+}
+
+fn index_url(url: &str) -> Result<()> {
+    let request = Request::new(RequestType::IndexUrl, url);
+
+    let response = client::send(&request, |chunk| print!("{}", chunk))?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn show_tree(path: &Path, depth: usize) -> Result<()> {
+    let options = TreeOptions {
+        max_depth: depth,
+        ..TreeOptions::default()
+    };
+    let tree = project_tree::build(path, &options)
+        .with_context(|| format!("Failed to read directory: {}", path.display()))?;
+
+    print!("{}", project_tree::render(&tree, &options));
+    Ok(())
+}
+
+fn shell_init(shell: Option<&str>) -> Result<()> {
+    let shell = match shell {
+        Some(name) => Shell::from_name(name).with_context(|| {
+            format!("Unsupported shell '{}'; expected bash, zsh, fish, powershell, or cmd", name)
+        })?,
+        None => Shell::detect().context("Could not detect your shell; pass it explicitly, e.g. `nucleus shell-init zsh`")?,
+    };
+
+    print!("{}", shell.hook());
+    Ok(())
+}
+
+fn export_pack(
+    file: PathBuf,
+    name: Option<String>,
+    description: Option<String>,
+    sources: Vec<String>,
+    prompts: Vec<String>,
+    memories: Vec<String>,
+) -> Result<()> {
+    let name = match name {
+        Some(name) => name,
+        None => file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .context("Pack file has no name")?,
+    };
+
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let mut pack = ContextPack::new(name);
+    pack.description = description;
+
+    for source in sources {
+        // Sources are stored as absolute paths by the server's indexer
+        pack = pack.with_source(cwd.join(source).to_string_lossy());
+    }
+    for prompt in prompts {
+        let (name, content) = prompt
+            .split_once('=')
+            .with_context(|| format!("Invalid prompt '{}', expected NAME=TEXT", prompt))?;
+        pack = pack.with_prompt(name, content);
+    }
+    for memory in memories {
+        pack = pack.with_memory(memory);
+    }
+
+    let request = Request::new(RequestType::PackExport, file.to_string_lossy())
+        .with_pwd(cwd.to_string_lossy())
+        .with_pack(pack);
+    let response = client::send(&request, |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn import_pack(file: PathBuf) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let request = Request::new(RequestType::PackImport, file.to_string_lossy())
+        .with_pwd(cwd.to_string_lossy());
+
+    println!("{} Importing {}...", "→".blue(), file.display());
+    let response = client::send(&request, |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn list_packs() -> Result<()> {
+    let response = client::send(&Request::new(RequestType::PackList, ""), |_| {})?;
+
+    println!("{}", "Imported packs:".bold().green());
+    println!();
+    for line in response.lines() {
+        println!("  {} {}", "•".cyan(), line);
+    }
+
+    Ok(())
+}
+
+/// Starter packs as clap values, described in `pack install --help`.
+fn starter_pack_names() -> clap::builder::PossibleValuesParser {
+    starter::STARTER_PACKS
+        .iter()
+        .map(|pack| clap::builder::PossibleValue::new(pack.name).help(pack.description))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn install_starter_pack(name: &str) -> Result<()> {
+    println!("{} Downloading starter pack {}...", "→".blue(), name);
+    let response = client::send(&Request::new(RequestType::PackInstall, name), |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn team_request(request_type: RequestType, content: &str) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let request = Request::new(request_type, content).with_pwd(cwd.to_string_lossy());
+
+    let response = client::send(&request, |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn list_collections() -> Result<()> {
+    let response = client::send(&Request::new(RequestType::CollectionList, ""), |_| {})?;
+    let collections: Vec<CollectionInfo> = serde_json::from_str(&response).context("Invalid collections response")?;
+
+    println!("{}", "Collections:".bold().green());
+    println!();
+    for collection in collections {
+        let marker = if collection.active { "*".green().bold().to_string() } else { " ".to_string() };
+        let roots: Vec<String> = collection.roots.iter().map(|root| root.display().to_string()).collect();
+        if collection.starter {
+            println!("{} {}  {}", marker, collection.name.cyan(), "starter pack".dimmed());
+        } else if roots.is_empty() {
+            println!("{} {}", marker, collection.name.cyan());
+        } else {
+            println!("{} {}  {}", marker, collection.name.cyan(), roots.join(", ").dimmed());
+        }
+    }
+    Ok(())
+}
+
+fn create_collection(name: &str, root: Option<PathBuf>) -> Result<()> {
+    let mut request = Request::new(RequestType::CollectionCreate, name);
+    if let Some(root) = root {
+        let root = root.canonicalize().with_context(|| format!("Failed to resolve {}", root.display()))?;
+        request = request.with_pwd(root.to_string_lossy());
+    }
+    let response = client::send(&request, |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn collection_request(request_type: RequestType, name: &str) -> Result<()> {
+    let response = client::send(&Request::new(request_type, name), |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn list_trash() -> Result<()> {
+    let response = client::send(&Request::new(RequestType::TrashList, ""), |_| {})?;
+    let entries: Vec<TrashEntry> = serde_json::from_str(&response).context("Invalid trash response")?;
+    if entries.is_empty() {
+        println!("The trash is empty.");
+        return Ok(());
+    }
+
+    println!("{}", "Trash:".bold().green());
+    println!();
+    for entry in entries {
+        let what = match (&entry.source, &entry.roots) {
+            (Some(source), _) => source.clone(),
+            (None, Some(_)) => "deleted".to_string(),
+            (None, None) => "cleared".to_string(),
+        };
+        println!(
+            "  {} {}: {}, {} documents  {}",
+            entry.id.cyan(),
+            entry.origin,
+            what,
+            entry.documents,
+            ago(entry.timestamp).dimmed()
+        );
+    }
+    println!();
+    println!("{}", "Restore one with `nucleus trash restore <ID>`.".dimmed());
+    Ok(())
+}
+
+fn snapshot_knowledge(name: Option<String>, collection: Option<String>) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let mut request = Request::new(RequestType::SnapshotCreate, name.unwrap_or_default()).with_pwd(cwd.to_string_lossy());
+    if let Some(collection) = collection {
+        request = request.with_collection(collection);
+    }
+    let response = client::send(&request, |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn list_snapshots() -> Result<()> {
+    let response = client::send(&Request::new(RequestType::SnapshotList, ""), |_| {})?;
+    let snapshots: Vec<SnapshotSummary> = serde_json::from_str(&response).context("Invalid snapshot list response")?;
+    if snapshots.is_empty() {
+        println!("No snapshots yet; take one with `nucleus kb snapshot <name>`.");
+        return Ok(());
+    }
+
+    println!("{}", "Snapshots:".bold().green());
+    println!();
+    for snapshot in snapshots {
+        println!(
+            "  {} {}: {} sources  {}",
+            snapshot.name.cyan(),
+            snapshot.origin,
+            snapshot.sources,
+            ago(snapshot.timestamp).dimmed()
+        );
+    }
+    println!();
+    println!("{}", "Compare one with the knowledge base now with `nucleus kb diff <name>`.".dimmed());
+    Ok(())
+}
+
+fn diff_knowledge(snapshot: &str, json: bool) -> Result<()> {
+    let response = client::send(&Request::new(RequestType::SnapshotDiff, snapshot), |_| {})?;
+    if json {
+        println!("{}", response);
+        return Ok(());
+    }
+    let diff: KnowledgeDiff = serde_json::from_str(&response).context("Invalid diff response")?;
+
+    println!(
+        "{}",
+        format!("Changes in the {} since snapshot '{}' ({}):", diff.snapshot.origin, diff.snapshot.name, ago(diff.snapshot.timestamp))
+            .bold()
+            .green()
+    );
+    println!();
+    if diff.is_empty() {
+        println!("  No sources added, removed, or changed ({} unchanged).", diff.unchanged);
+        return Ok(());
+    }
+    for source in &diff.added {
+        println!("  {} {}", "+".green().bold(), source);
+    }
+    for source in &diff.removed {
+        println!("  {} {}", "-".red().bold(), source);
+    }
+    for source in &diff.changed {
+        println!("  {} {}", "~".yellow().bold(), source);
+    }
+    println!();
+    println!(
+        "  {} added, {} removed, {} changed, {} unchanged",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len(),
+        diff.unchanged
+    );
+    Ok(())
+}
+
+fn show_stats(collection: Option<String>, json: bool, limit: usize) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let mut request = Request::new(RequestType::Stats, "").with_pwd(cwd.to_string_lossy());
+    if let Some(collection) = collection {
+        request = request.with_collection(collection);
+    }
+    let response = client::send(&request, |_| {})?;
+    if json {
+        println!("{}", response);
+        return Ok(());
+    }
+    let stats: IndexStats = serde_json::from_str(&response).context("Invalid stats response")?;
+
+    println!("{}", format!("Knowledge base ({} collection):", stats.collection).bold().green());
+    println!();
+    println!("  Chunks:        {} from {} sources", stats.chunks, stats.sources.len());
+    if let Some(bytes) = stats.disk_bytes {
+        println!("  On disk:       {}", memory::format_bytes(bytes as usize));
+    }
+    println!("  Embeddings:    {} ({} dimensions)", stats.embedding_model, stats.embedding_dim);
+    if let Some(last_indexed) = stats.last_indexed {
+        println!("  Last indexed:  {}", ago(last_indexed));
+    }
+    for job in &stats.indexing {
+        println!("  {}  {} ({}s)", "Running:".yellow(), job.description, job.elapsed_secs);
+    }
+    if let Some(team) = &stats.team {
+        println!("  Team '{}':     {} shared chunks", team.namespace, team.chunks);
+    }
+
+    let mut sources = stats.sources;
+    sources.sort_by(|a, b| b.chunks.cmp(&a.chunks).then_with(|| a.source.cmp(&b.source)));
+    if limit > 0 && !sources.is_empty() {
+        println!();
+        println!("{}", "Sources with the most chunks:".bold());
+        for source in sources.iter().take(limit) {
+            let indexed = source.indexed_at.map(|secs| format!("  indexed {}", ago(secs))).unwrap_or_default();
+            println!("  {:>6}  {}{}", source.chunks, source.source, indexed.dimmed());
+        }
+    }
+
+    if !stats.stale.is_empty() {
+        println!();
+        println!("{}", format!("{} stale sources, changed since they were indexed:", stats.stale.len()).bold().yellow());
+        let shown = if limit > 0 { limit } else { stats.stale.len() };
+        for stale in stats.stale.iter().take(shown) {
+            let indexed = stale.indexed_at.map(|secs| format!("  indexed {}", ago(secs))).unwrap_or_default();
+            println!("  {:>8}  {}{}", stale.reason, stale.source, indexed.dimmed());
+        }
+        if stats.stale.len() > shown {
+            println!("  … and {} more", stats.stale.len() - shown);
+        }
+        println!("  Re-index them, or set rag.freshness.auto_refresh to let the server do it");
+    }
+
+    if let Some(memory) = stats.memory {
+        println!();
+        println!("{}", memory);
+    }
+    if let Some(version) = stats.latest_version {
+        println!();
+        println!("{}", format!("nucleus {} is available (running {})", version, nucleus_core::VERSION).yellow());
+    }
+    Ok(())
+}
+
+fn logged_responses(config_path: &PathBuf) -> Result<Vec<LoggedResponse>> {
+    let config = Config::load(config_path).context("Failed to load config")?.response_log;
+    let responses = response_log::read_responses(Path::new(&config.path), config.keep)
+        .context("Failed to read the response log")?;
+    if responses.is_empty() && !config.enabled {
+        anyhow::bail!("No responses are logged. Set response_log.enabled in the config and restart the server.");
+    }
+    Ok(responses)
+}
+
+fn list_responses(config_path: &PathBuf, last: usize) -> Result<()> {
+    let responses = logged_responses(config_path)?;
+    if responses.is_empty() {
+        println!("No responses logged yet.");
+        return Ok(());
+    }
+
+    println!("{}", "Recent responses:".bold().green());
+    println!();
+    for response in &responses[responses.len().saturating_sub(last)..] {
+        let outcome = match response.outcome {
+            Some(Outcome::Done) => "✓".green(),
+            Some(Outcome::Error) => "✗".red(),
+            Some(Outcome::Incomplete) | None => "…".yellow(),
+        };
+        let prompt: String = response.prompt.lines().next().unwrap_or_default().chars().take(60).collect();
+        println!("  {} {} {}  {}", outcome, response.id.cyan(), prompt, ago(response.timestamp).dimmed());
+    }
+    Ok(())
+}
+
+fn show_response(config_path: &PathBuf, id: Option<&str>) -> Result<()> {
+    let responses = logged_responses(config_path)?;
+    let response = match id {
+        Some(id) => responses
+            .iter()
+            .rev()
+            .find(|response| response.id == id || response.response_id.as_deref() == Some(id))
+            .with_context(|| format!("No logged response with ID '{}'", id))?,
+        None => responses.last().context("No responses logged yet")?,
+    };
+
+    println!("{} {}", response.id.cyan(), ago(response.timestamp).dimmed());
+    if let Some(pwd) = &response.pwd {
+        println!("{}", pwd.dimmed());
+    }
+    println!("{} {}", ">".bold(), response.prompt);
+    println!();
+    println!("{}", response.response);
+    match response.outcome {
+        Some(Outcome::Done) => {}
+        Some(Outcome::Error) => {
+            eprintln!("{} {}", "Error:".red().bold(), response.error.as_deref().unwrap_or_default());
+        }
+        Some(Outcome::Incomplete) => eprintln!("{}", "(the response ended early)".yellow()),
+        None => eprintln!("{}", "(still streaming, or the server stopped before it ended)".yellow()),
+    }
+    Ok(())
+}
+
+fn logged_requests(config: &Config) -> Result<Vec<LoggedRequest>> {
+    let requests = request_log::read_requests(Path::new(&config.request_log.path))
+        .context("Failed to read the request log")?;
+    if requests.is_empty() && !config.request_log.enabled {
+        anyhow::bail!("No requests are logged. Set request_log.enabled in the config and restart the server.");
+    }
+    Ok(requests)
+}
+
+fn list_requests(config_path: &PathBuf) -> Result<()> {
+    let config = Config::load(config_path).context("Failed to load config")?;
+    let requests = logged_requests(&config)?;
+    if requests.is_empty() {
+        println!("No requests logged yet.");
+        return Ok(());
+    }
+
+    println!("{}", "Recent requests:".bold().green());
+    println!();
+    for logged in &requests[requests.len().saturating_sub(10)..] {
+        let content: String = logged.request.content.lines().next().unwrap_or_default().chars().take(60).collect();
+        println!("  {} {} {}  {}", logged.id.cyan(), request_kind(logged.request.request_type).dimmed(), content, ago(logged.timestamp).dimmed());
+    }
+    println!();
+    println!("{}", "Run one again: nucleus replay <id>".dimmed());
+    Ok(())
+}
+
+fn replay(config_path: &PathBuf, id: &str) -> Result<()> {
+    let config = Config::load(config_path).context("Failed to load config")?;
+    let logged = logged_requests(&config)?
+        .into_iter()
+        .rev()
+        .find(|logged| logged.id == id)
+        .with_context(|| format!("No logged request with ID '{}'", id))?;
+
+    // Replay as this client, outside the original session and without this
+    // terminal's conversation
+    let mut request = logged.request;
+    request.client = Some(ClientVersion::current());
+    request.session_id = None;
+    request.history.get_or_insert_with(Vec::new);
+
+    println!("{}", format!("Replaying {} from {}:", request_kind(request.request_type), ago(logged.timestamp)).dimmed());
+    println!("{} {}", ">".bold(), request.content);
+    println!();
+    ask(&request, &[], config.display.chars_per_sec, &config.display)?;
+
+    if config.response_log.enabled {
+        println!("{}", format!("Original answer: nucleus responses show {}", id).dimmed());
+    }
+    Ok(())
+}
+
+/// The protocol name of `request_type`, e.g. `chat`.
+fn request_kind(request_type: RequestType) -> String {
+    serde_json::to_value(request_type)
+        .ok()
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+/// How long ago `secs` (since the Unix epoch) was, e.g. `3h ago`.
+fn ago(secs: u64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    match now.saturating_sub(secs) {
+        elapsed if elapsed < 60 => "just now".to_string(),
+        elapsed if elapsed < 3600 => format!("{}m ago", elapsed / 60),
+        elapsed if elapsed < 86_400 => format!("{}h ago", elapsed / 3600),
+        elapsed => format!("{}d ago", elapsed / 86_400),
+    }
+}
+
+fn list_jobs() -> Result<()> {
+    let response = client::send(&Request::new(RequestType::JobsList, ""), |_| {})?;
+    let jobs: Vec<JobInfo> = serde_json::from_str(&response).context("Invalid jobs response")?;
+
+    if jobs.is_empty() {
+        println!("No jobs are running.");
+        return Ok(());
+    }
+    println!("{}", "Running jobs:".bold().green());
+    println!();
+    for job in jobs {
+        let state = if job.cancelling { " (cancelling)".yellow().to_string() } else { String::new() };
+        let progress = job.progress.as_ref().map(|progress| format!("  {}", format_progress(progress))).unwrap_or_default();
+        println!("  {} {}  {}s{}{}", job.id.to_string().cyan(), job.description, job.elapsed_secs, progress.dimmed(), state);
+    }
+    Ok(())
+}
+
+fn job_status(id: u64) -> Result<()> {
+    let response = client::send(&Request::new(RequestType::JobsStatus, id.to_string()), |_| {})?;
+    let job: JobInfo = serde_json::from_str(&response).context("Invalid job response")?;
+
+    println!("{} {}", job.id.to_string().cyan(), job.description);
+    match &job.outcome {
+        Some(outcome) if outcome.success => println!("  {} {} ({}s)", "✓".green().bold(), outcome.summary, job.elapsed_secs),
+        Some(outcome) => println!("  {} {} ({}s)", "✗".red().bold(), outcome.summary, job.elapsed_secs),
+        None => {
+            let state = if job.cancelling { "Cancelling".yellow() } else { "Running".green() };
+            println!("  {} for {}s", state, job.elapsed_secs);
+            if let Some(progress) = &job.progress {
+                println!("  {}", format_progress(progress));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cancel_job(id: u64) -> Result<()> {
+    let response = client::send(&Request::new(RequestType::JobsCancel, id.to_string()), |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn list_plugins() -> Result<()> {
+    let response = client::send(&Request::new(RequestType::PluginsList, ""), |_| {})?;
+    let plugins: Vec<PluginInfo> = serde_json::from_str(&response).context("Invalid plugins response")?;
+
+    if plugins.is_empty() {
+        println!("No plugins found. Add one as a directory with a plugin.yaml manifest in plugins.path.");
+        return Ok(());
+    }
+    println!("{}", "Plugins:".bold().green());
+    println!();
+    for plugin in plugins {
+        let state = match (&plugin.problem, plugin.enabled) {
+            (Some(_), _) => "unavailable".red(),
+            (None, true) => "enabled".green(),
+            (None, false) => "disabled".yellow(),
+        };
+        let kind = plugin.kind.map(|kind| format!(" ({})", kind.as_str())).unwrap_or_default();
+        println!("  {}  {}{}  {}", plugin.name.cyan(), state, kind.dimmed(), plugin.description);
+        if let Some(problem) = &plugin.problem {
+            println!("    {}", problem.red());
+        }
+        if !plugin.requires.is_empty() {
+            let requires: Vec<&str> = plugin.requires.iter().map(|capability| capability.as_str()).collect();
+            println!("    {} {}", "requires:".dimmed(), requires.join(", "));
+        }
+        println!("    {}", plugin.path.display().to_string().dimmed());
+    }
+    Ok(())
+}
+
+fn set_privacy(state: &str) -> Result<()> {
+    let response = client::send(&Request::new(RequestType::Privacy, state), |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn new_conversation() -> Result<()> {
+    let response = client::send(&Request::new(RequestType::SessionReset, ""), |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn list_sessions() -> Result<()> {
+    let response = client::send(&Request::new(RequestType::SessionList, ""), |_| {})?;
+    let sessions: Vec<SessionInfo> = serde_json::from_str(&response).context("Invalid sessions response")?;
+    if sessions.is_empty() {
+        println!("No stored conversations.");
+        return Ok(());
+    }
+
+    println!("{}", "Conversations:".bold().green());
+    println!();
+    for session in sessions {
+        let marker = if session.current { " (this terminal)".green().to_string() } else { String::new() };
+        println!(
+            "  {}{}: {}, {} exchanges  {}",
+            session.id.cyan(),
+            marker,
+            session.title,
+            session.turns,
+            ago(session.updated).dimmed()
+        );
+    }
+    println!();
+    println!("{}", "Continue one here with `nucleus sessions resume <ID>`.".dimmed());
+    Ok(())
+}
+
+/// Prints events until the server goes away, one JSON object per line so
+/// scripts can read them as they arrive.
+fn follow_events(types: &[String]) -> Result<()> {
+    use std::io::Write;
+
+    let request = Request::new(RequestType::Subscribe, types.join(","));
+    let mut stdout = std::io::stdout();
+    client::send(&request, |event| {
+        let _ = writeln!(stdout, "{}", event);
+        let _ = stdout.flush();
+    })?;
+    Ok(())
+}
+
+fn remember(response_id: &str) -> Result<()> {
+    let response = client::send(&Request::new(RequestType::Remember, response_id), |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn send_feedback(response_id: String, rating: Rating, comment: Option<String>) -> Result<()> {
+    let request = Request::new(RequestType::Feedback, comment.unwrap_or_default())
+        .with_response_id(response_id)
+        .with_rating(rating);
+
+    let response = client::send(&request, |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn export_feedback(file: PathBuf, rating: Option<String>) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let mut request = Request::new(RequestType::FeedbackExport, file.to_string_lossy())
+        .with_pwd(cwd.to_string_lossy());
+
+    match rating.as_deref() {
+        Some("up") => request = request.with_rating(Rating::Up),
+        Some("down") => request = request.with_rating(Rating::Down),
+        _ => {}
+    }
+
+    let response = client::send(&request, |_| {})?;
+
+    println!("{} {}", "✓".green().bold(), response);
+    Ok(())
+}
+
+fn feedback_stats() -> Result<()> {
+    let response = client::send(&Request::new(RequestType::FeedbackStats, ""), |_| {})?;
+
+    println!("{}", "Feedback by variant:".bold().green());
+    println!();
+    for line in response.lines() {
+        println!("  {} {}", "•".cyan(), line);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_prompt_is_opt_in() {
+        let mut renderer = BlockRenderer::new(0, false);
+        renderer.push("Sizes:\n\n| crate | lines |\n|---|---|\n| core | 900 |\n\nDone.\n");
+        renderer.finish();
+        assert_eq!(renderer.blocks().len(), 1);
+
+        // A normal answer with a table does not wait for input
+        let mut display = DisplayConfig::default();
+        assert!(!offers_save(&display, true, renderer.blocks()));
+        display.save_blocks = true;
+        assert!(offers_save(&display, true, renderer.blocks()));
+        assert!(!offers_save(&display, false, renderer.blocks()));
+        assert!(!offers_save(&display, true, &[]));
+    }
+}
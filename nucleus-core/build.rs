@@ -0,0 +1,46 @@
+//! Generates the gRPC service code when the `grpc` feature is enabled.
+//!
+//! The service is described here instead of being compiled from
+//! `proto/nucleus/v1/nucleus.proto`, so building doesn't need `protoc`. The
+//! message types live in `src/server/grpc/proto.rs`; both must be kept in sync
+//! with the `.proto` file, which is the reference for other languages.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    grpc::generate();
+}
+
+#[cfg(feature = "grpc")]
+mod grpc {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    /// `(method, route, input, output, server streaming)`
+    const METHODS: &[(&str, &str, &str, &str, bool)] = &[
+        ("chat", "Chat", "ChatRequest", "ChatChunk", true),
+        ("embed", "Embed", "EmbedRequest", "EmbedResponse", false),
+        ("search", "Search", "SearchRequest", "SearchResponse", false),
+        ("index", "Index", "IndexRequest", "IndexResponse", false),
+        ("stats", "Stats", "StatsRequest", "StatsResponse", false),
+        ("team_admin", "TeamAdmin", "TeamAdminRequest", "TeamAdminResponse", false),
+    ];
+
+    pub fn generate() {
+        let mut service = Service::builder().name("Nucleus").package("nucleus.v1");
+        for &(name, route, input, output, streaming) in METHODS {
+            let mut method = Method::builder()
+                .name(name)
+                .route_name(route)
+                .input_type(format!("super::{}", input))
+                .output_type(format!("super::{}", output))
+                .codec_path("tonic_prost::ProstCodec");
+            if streaming {
+                method = method.server_streaming();
+            }
+            service = service.method(method.build());
+        }
+
+        Builder::new().compile(&[service.build()]);
+    }
+}
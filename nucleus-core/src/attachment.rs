@@ -0,0 +1,324 @@
+//! Files and snippets attached to a single chat request.
+//!
+//! An attachment is either a file path (resolved against the request's working
+//! directory) or inline content such as a clipboard paste. Attachments are
+//! added to the prompt for that request only; they are never indexed into the
+//! knowledge base.
+//!
+//! When the attachments exceed the configured context budget, they are split
+//! into chunks and only the chunks sharing the most terms with the question
+//! are kept, in their original order.
+
+use crate::config::AttachmentConfig;
+use crate::rag::{chunk_text, terms};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AttachmentError {
+    #[error("Failed to read attachment '{path}': {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Attachment '{name}' is {size} bytes, over the {limit} byte limit")]
+    TooLarge { name: String, size: usize, limit: usize },
+
+    #[error("Attachment '{name}' has unsupported type '{mime_type}'; only text can be attached")]
+    Unsupported { name: String, mime_type: String },
+
+    #[error("Attachment needs either a path or inline content")]
+    Empty,
+}
+
+pub type Result<T> = std::result::Result<T, AttachmentError>;
+
+/// A file path or inline content sent with a chat request.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// Display name (defaults to the file name, or "attachment")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// File to read, relative to the request's `pwd`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Inline content, used instead of `path`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// MIME type (guessed from the file extension if omitted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+impl Attachment {
+    pub fn file(path: impl Into<String>) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn inline(content: impl Into<String>) -> Self {
+        Self {
+            content: Some(content.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    /// Reads and validates the attachment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, the attachment is larger
+    /// than `config.max_bytes`, or it is not text.
+    pub async fn resolve(&self, pwd: Option<&Path>, config: &AttachmentConfig) -> Result<ResolvedAttachment> {
+        let (name, bytes) = match (&self.content, &self.path) {
+            (Some(content), _) => {
+                let name = self.name.clone().unwrap_or_else(|| "attachment".to_string());
+                (name, content.clone().into_bytes())
+            }
+            (None, Some(path)) => {
+                let full_path = match pwd {
+                    Some(pwd) if Path::new(path).is_relative() => pwd.join(path),
+                    _ => PathBuf::from(path),
+                };
+                let io_error = |source| AttachmentError::Io { path: path.clone(), source };
+
+                // Check the size before reading so huge files are rejected cheaply
+                let size = tokio::fs::metadata(&full_path).await.map_err(io_error)?.len() as usize;
+                let name = self.name.clone().unwrap_or_else(|| path.clone());
+                if size > config.max_bytes {
+                    return Err(AttachmentError::TooLarge { name, size, limit: config.max_bytes });
+                }
+
+                (name, tokio::fs::read(&full_path).await.map_err(io_error)?)
+            }
+            (None, None) => return Err(AttachmentError::Empty),
+        };
+
+        if bytes.len() > config.max_bytes {
+            return Err(AttachmentError::TooLarge {
+                name,
+                size: bytes.len(),
+                limit: config.max_bytes,
+            });
+        }
+
+        let mime_type = self
+            .mime_type
+            .clone()
+            .or_else(|| self.path.as_deref().and_then(mime_from_extension).map(str::to_string))
+            .unwrap_or_else(|| "text/plain".to_string());
+
+        let content = match String::from_utf8(bytes) {
+            Ok(content) if is_text(&mime_type) => content,
+            _ => return Err(AttachmentError::Unsupported { name, mime_type }),
+        };
+
+        Ok(ResolvedAttachment { name, mime_type, content })
+    }
+}
+
+/// An attachment whose content has been read and validated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedAttachment {
+    pub name: String,
+    pub mime_type: String,
+    pub content: String,
+}
+
+/// Formats attachments as prompt context, chunking them to fit the budget.
+///
+/// Returns an empty string if there are no attachments.
+pub fn format_context(query: &str, attachments: &[ResolvedAttachment], config: &AttachmentConfig) -> String {
+    if attachments.is_empty() {
+        return String::new();
+    }
+
+    let total: usize = attachments.iter().map(|a| a.content.len()).sum();
+    let excerpts: Vec<Vec<String>> = if total <= config.max_context_bytes {
+        attachments.iter().map(|a| vec![a.content.clone()]).collect()
+    } else {
+        select_chunks(query, attachments, config)
+    };
+
+    let mut context = String::from("\n\nAttached by the user:\n\n");
+    for (attachment, chunks) in attachments.iter().zip(excerpts) {
+        let whole = chunks.len() == 1 && chunks[0].len() == attachment.content.len();
+        if whole {
+            context.push_str(&format!("--- {} ({}) ---\n", attachment.name, attachment.mime_type));
+        } else {
+            context.push_str(&format!(
+                "--- {} ({}, relevant excerpts) ---\n",
+                attachment.name, attachment.mime_type
+            ));
+        }
+
+        if chunks.is_empty() {
+            context.push_str("[omitted: no part matched the question]\n");
+        }
+        for (i, chunk) in chunks.iter().enumerate() {
+            if i > 0 {
+                context.push_str("\n[...]\n");
+            }
+            context.push_str(chunk.trim_end());
+            context.push('\n');
+        }
+        context.push('\n');
+    }
+
+    context.push_str("User question:\n");
+    context
+}
+
+/// Keeps the chunks that best match `query` within the context budget.
+///
+/// Returns the selected chunks per attachment, in document order.
+fn select_chunks(query: &str, attachments: &[ResolvedAttachment], config: &AttachmentConfig) -> Vec<Vec<String>> {
+    let query_terms = terms(query);
+    let overlap = config.chunk_size / 10;
+
+    // (attachment index, chunk index, score, chunk)
+    let mut candidates: Vec<(usize, usize, f32, String)> = Vec::new();
+    for (a, attachment) in attachments.iter().enumerate() {
+        for (c, chunk) in chunk_text(&attachment.content, config.chunk_size, overlap).into_iter().enumerate() {
+            let chunk_terms = terms(&chunk);
+            let matched = query_terms.intersection(&chunk_terms).count();
+            // Earlier chunks win ties; they tend to hold headers and definitions
+            let score = matched as f32 - c as f32 * 1e-3;
+            candidates.push((a, c, score, chunk));
+        }
+    }
+    candidates.sort_by(|x, y| y.2.total_cmp(&x.2));
+
+    let mut used = 0;
+    let mut selected: Vec<(usize, usize, String)> = Vec::new();
+    for (a, c, _, chunk) in candidates {
+        if used + chunk.len() > config.max_context_bytes {
+            continue;
+        }
+        used += chunk.len();
+        selected.push((a, c, chunk));
+    }
+    selected.sort_by_key(|(a, c, _)| (*a, *c));
+
+    let mut excerpts = vec![Vec::new(); attachments.len()];
+    for (a, _, chunk) in selected {
+        excerpts[a].push(chunk);
+    }
+    excerpts
+}
+
+fn is_text(mime_type: &str) -> bool {
+    let essence = mime_type.split(';').next().unwrap_or_default().trim();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence,
+            "application/json"
+                | "application/xml"
+                | "application/yaml"
+                | "application/toml"
+                | "application/javascript"
+                | "application/x-sh"
+                | "application/sql"
+        )
+}
+
+fn mime_from_extension(path: &str) -> Option<&'static str> {
+    let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+    let mime_type = match extension.as_str() {
+        "md" | "markdown" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "toml" => "application/toml",
+        "js" | "mjs" => "application/javascript",
+        "sh" | "bash" | "zsh" => "application/x-sh",
+        "sql" => "application/sql",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        _ => return None,
+    };
+    Some(mime_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(name: &str, content: &str) -> ResolvedAttachment {
+        ResolvedAttachment {
+            name: name.to_string(),
+            mime_type: "text/plain".to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_file_and_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.md"), "# Notes").unwrap();
+        std::fs::write(dir.path().join("logo.png"), "not really a png").unwrap();
+        let config = AttachmentConfig::default();
+
+        let notes = Attachment::file("notes.md").resolve(Some(dir.path()), &config).await.unwrap();
+        assert_eq!(notes.name, "notes.md");
+        assert_eq!(notes.mime_type, "text/markdown");
+        assert_eq!(notes.content, "# Notes");
+
+        let image = Attachment::file("logo.png").resolve(Some(dir.path()), &config).await;
+        assert!(matches!(image, Err(AttachmentError::Unsupported { .. })));
+
+        let small = AttachmentConfig { max_bytes: 4, ..config };
+        let large = Attachment::inline("too long").resolve(None, &small).await;
+        assert!(matches!(large, Err(AttachmentError::TooLarge { size: 8, .. })));
+    }
+
+    #[test]
+    fn test_format_context_within_budget() {
+        let config = AttachmentConfig::default();
+        let context = format_context("why?", &[resolved("clipboard", "error: linker failed")], &config);
+
+        assert!(context.contains("--- clipboard (text/plain) ---\nerror: linker failed\n"));
+        assert!(context.ends_with("User question:\n"));
+    }
+
+    #[test]
+    fn test_format_context_keeps_relevant_chunks() {
+        let config = AttachmentConfig {
+            max_context_bytes: 40,
+            chunk_size: 40,
+            ..AttachmentConfig::default()
+        };
+        let log = format!(
+            "{:<40}{:<40}{:<40}",
+            "starting worker pool", "connection refused by postgres", "shutting down"
+        );
+
+        let context = format_context("why was the postgres connection refused", &[resolved("app.log", &log)], &config);
+        assert!(context.contains("relevant excerpts"));
+        assert!(context.contains("connection refused by postgres"));
+        assert!(!context.contains("starting worker pool"));
+    }
+}
@@ -0,0 +1,188 @@
+//! What changed in a git working tree, for `whats-changed` requests.
+//!
+//! Collects what `git status` and `git diff HEAD` show, plus the latest
+//! commits with their patches, and builds a prompt asking the LLM for a
+//! bullet list of the changes. Diffs are cut to fit one prompt, keeping
+//! the uncommitted changes over older history.
+
+use std::path::Path;
+use std::process::Command;
+use thiserror::Error;
+
+/// Commits included by default.
+pub const RECENT_COMMITS: usize = 3;
+
+/// Characters of diff sent to the LLM; uncommitted changes get two thirds.
+const MAX_DIFF_CHARS: usize = 24_000;
+
+/// Errors that can occur while collecting changes.
+#[derive(Debug, Error)]
+pub enum ChangesError {
+    #[error("{0} is not inside a git repository")]
+    NotARepository(String),
+
+    #[error("git {command} failed: {message}")]
+    Git { command: String, message: String },
+
+    #[error("Failed to run git: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, ChangesError>;
+
+/// Uncommitted changes and recent history of a repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    /// Current branch, empty before the first commit
+    pub branch: String,
+    /// `git status --short` output, including untracked files
+    pub status: String,
+    /// Staged and unstaged changes to tracked files
+    pub diff: String,
+    /// Latest commits, newest first, with their patches
+    pub recent_commits: String,
+}
+
+impl Changes {
+    /// Collects the changes of the repository containing `dir`.
+    ///
+    /// Runs `git`, so call it off the async runtime.
+    pub fn collect(dir: &Path, commits: usize) -> Result<Self> {
+        if git(dir, &["rev-parse", "--show-toplevel"]).is_err() {
+            return Err(ChangesError::NotARepository(dir.display().to_string()));
+        }
+
+        // Before the first commit there is no HEAD to diff against or log
+        let has_commits = git(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok();
+        let branch = if has_commits {
+            git(dir, &["rev-parse", "--abbrev-ref", "HEAD"])?.trim().to_string()
+        } else {
+            String::new()
+        };
+        let status = git(dir, &["status", "--short"])?;
+        let diff = if has_commits {
+            git(dir, &["diff", "HEAD"])?
+        } else {
+            git(dir, &["diff", "--cached"])?
+        };
+        let recent_commits = if has_commits && commits > 0 {
+            let count = format!("-{}", commits);
+            git(dir, &["log", &count, "--patch", "--format=commit %h (%cr) %an%n%s%n"])?
+        } else {
+            String::new()
+        };
+
+        Ok(Self {
+            branch,
+            status,
+            diff,
+            recent_commits,
+        })
+    }
+
+    /// Whether there is nothing to summarize.
+    pub fn is_empty(&self) -> bool {
+        self.status.trim().is_empty() && self.diff.trim().is_empty() && self.recent_commits.trim().is_empty()
+    }
+
+    /// Prompt asking for a bullet list of the changes.
+    pub fn summary_prompt(&self, focus: &str) -> String {
+        let mut prompt = String::from(
+            "Summarize what changed in this git repository as a bullet list.\n\
+             Start with the uncommitted work, then the recent commits, with one `- ` line per \
+             meaningful change naming the files involved and what the change does.\n\
+             Skip formatting-only changes, and mention untracked files that look important.\n",
+        );
+        let focus = focus.trim();
+        if !focus.is_empty() {
+            prompt.push_str(&format!("Pay particular attention to: {}\n", focus));
+        }
+
+        if !self.branch.is_empty() {
+            prompt.push_str(&format!("\nBranch: {}\n", self.branch));
+        }
+        let status = if self.status.trim().is_empty() { "(clean)" } else { self.status.trim_end() };
+        prompt.push_str(&format!("\n`git status --short`:\n```\n{}\n```\n", status));
+
+        let uncommitted_budget = if self.recent_commits.is_empty() { MAX_DIFF_CHARS } else { MAX_DIFF_CHARS * 2 / 3 };
+        let diff = truncate(&self.diff, uncommitted_budget);
+        if !diff.trim().is_empty() {
+            prompt.push_str(&format!("\nUncommitted changes:\n```diff\n{}\n```\n", diff.trim_end()));
+        }
+
+        let commits_budget = MAX_DIFF_CHARS.saturating_sub(diff.chars().count());
+        let commits = truncate(&self.recent_commits, commits_budget);
+        if !commits.trim().is_empty() {
+            prompt.push_str(&format!("\nRecent commits:\n```diff\n{}\n```\n", commits.trim_end()));
+        }
+        prompt
+    }
+}
+
+/// Runs git in `dir`, returning its output.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output()?;
+    if !output.status.success() {
+        return Err(ChangesError::Git {
+            command: args.first().copied().unwrap_or_default().to_string(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Keeps the first `max_chars` characters of `text`, noting how much was cut.
+fn truncate(text: &str, max_chars: usize) -> String {
+    let length = text.chars().count();
+    if length <= max_chars {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(max_chars).collect();
+    format!("{}\n[... {} more characters not shown]", kept, length - max_chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    #[test]
+    fn test_collect_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(matches!(Changes::collect(dir.path(), 1), Err(ChangesError::NotARepository(_))));
+
+        run(dir.path(), &["init", "--quiet"]);
+        std::fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        run(dir.path(), &["add", "main.rs"]);
+        run(dir.path(), &["commit", "--quiet", "-m", "Add main"]);
+        std::fs::write(dir.path().join("main.rs"), "fn main() { run(); }\n").unwrap();
+        std::fs::write(dir.path().join("notes.md"), "todo\n").unwrap();
+
+        let changes = Changes::collect(dir.path(), 1).unwrap();
+        assert!(!changes.branch.is_empty());
+        assert!(changes.status.contains("?? notes.md"));
+        assert!(changes.diff.contains("+fn main() { run(); }"));
+        assert!(changes.recent_commits.contains("Add main"));
+
+        let prompt = changes.summary_prompt("error handling");
+        assert!(prompt.contains("Pay particular attention to: error handling"));
+        assert!(prompt.contains("Uncommitted changes:"));
+        assert!(prompt.contains("Recent commits:"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("abcdef", 3), "abc\n[... 3 more characters not shown]");
+    }
+}
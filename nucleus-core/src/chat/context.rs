@@ -0,0 +1,292 @@
+//! Keeping a conversation within the model's context window.
+//!
+//! Tool results pile up over a conversation: a few file reads can take more
+//! of `llm.context_length` than is left once the answer has its
+//! `llm.answer_tokens`. Before each request, [`ContextWindow`] estimates the
+//! size of the messages (see [`rag::estimate_tokens`]) and, if they no
+//! longer fit, has the model summarize the older turns and puts the summary
+//! in their place.
+//!
+//! The question and the latest turn are kept verbatim as long as they fit
+//! next to the summary. A turn (an assistant message calling tools and the
+//! tool results after it) is never split, so the provider still sees every
+//! tool call answered. Turns too long for one summary request are
+//! summarized piece by piece, each request condensing the next messages
+//! together with the summary so far.
+
+use crate::config::LlmConfig;
+use crate::provider::{ChatRequest, Message, Provider};
+use crate::rag::{self, ContextBudget};
+use crate::text;
+use anyhow::{bail, Context, Result};
+use tracing::info;
+
+/// Estimated tokens for the role and framing of each message.
+const MESSAGE_TOKENS: usize = 4;
+
+/// Tokens set aside for a summary, both next to the messages kept and in
+/// the request summarizing the next piece.
+const SUMMARY_TOKENS: usize = 512;
+
+/// Pieces of the conversation are summarized in at least this many tokens.
+const MIN_PIECE_TOKENS: usize = 256;
+
+/// Starts the message that replaces the summarized turns.
+const SUMMARY_HEADING: &str = "Summary of the conversation so far (earlier messages were summarized to fit the context window):";
+
+const SUMMARY_INSTRUCTIONS: &str = "Summarize the conversation below for the assistant to continue it. \
+Keep the facts, file names, code, commands, tool results, and decisions the rest of the conversation \
+may need; drop pleasantries and repetition. Reply with the summary only.\n\n";
+
+/// The room for messages in the model's context window.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ContextWindow {
+    budget: ContextBudget,
+}
+
+impl ContextWindow {
+    pub(super) fn new(llm: &LlmConfig) -> Self {
+        Self {
+            budget: ContextBudget::new(llm),
+        }
+    }
+
+    /// Tokens available to messages.
+    fn available(&self) -> usize {
+        self.budget.available(&[])
+    }
+
+    /// Summarizes older turns of `messages` if they do not fit, returning
+    /// whether they were.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the first message alone does not fit, or if a
+    /// summary request fails.
+    pub(super) async fn fit(&self, provider: &dyn Provider, model: &str, messages: &mut Vec<Message>) -> Result<bool> {
+        let total = tokens(messages);
+        if total <= self.available() {
+            return Ok(false);
+        }
+        if messages.len() < 2 {
+            bail!(
+                "The prompt takes about {} tokens, more than llm.context_length ({}) leaves after llm.answer_tokens ({})",
+                total,
+                self.budget.context_length,
+                self.budget.answer_tokens
+            );
+        }
+
+        // Keep the latest turn if it fits next to the question and the summary
+        let latest = latest_turn(messages).max(1);
+        let kept = tokens(&messages[..1]) + tokens(&messages[latest..]) + SUMMARY_TOKENS;
+        let end = if kept <= self.available() { latest } else { messages.len() };
+        if end <= 1 {
+            return Ok(false);
+        }
+
+        info!("Conversation of about {} tokens exceeds the context window; summarizing {} messages", total, end - 1);
+        let summary = self.summarize(provider, model, &messages[1..end]).await?;
+        messages.splice(1..end, [Message::user(None, format!("{}\n{}", SUMMARY_HEADING, summary.trim()))]);
+        Ok(true)
+    }
+
+    /// Summarizes `messages` with the model, in as many requests as it takes.
+    async fn summarize(&self, provider: &dyn Provider, model: &str, messages: &[Message]) -> Result<String> {
+        let piece_tokens = self
+            .budget
+            .available(&[SUMMARY_INSTRUCTIONS])
+            .saturating_sub(SUMMARY_TOKENS)
+            .max(MIN_PIECE_TOKENS);
+
+        let mut summary = String::new();
+        for piece in pieces(messages, piece_tokens * rag::CHARS_PER_TOKEN) {
+            let prompt = if summary.is_empty() {
+                format!("{}Conversation:\n{}", SUMMARY_INSTRUCTIONS, piece)
+            } else {
+                format!("{}Summary of the conversation before:\n{}\n\nConversation:\n{}", SUMMARY_INSTRUCTIONS, summary, piece)
+            };
+            let request = ChatRequest::new(model, vec![Message::user(None, prompt)]).with_temperature(0.2);
+
+            let mut content = String::new();
+            provider
+                .chat(request, Box::new(|response| {
+                    // Providers either stream increments and finish with an
+                    // empty done chunk, or send the full text in the done chunk
+                    if !response.done || content.is_empty() {
+                        content.push_str(&response.content);
+                    }
+                }))
+                .await
+                .context("Failed to summarize the conversation")?;
+            summary = content;
+        }
+        Ok(summary)
+    }
+}
+
+/// Estimated tokens of `messages`.
+fn tokens(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .map(|message| {
+            let calls: usize = message
+                .tool_calls
+                .iter()
+                .flatten()
+                .map(|call| rag::estimate_tokens(&call.function.name) + rag::estimate_tokens(&call.function.arguments.to_string()))
+                .sum();
+            MESSAGE_TOKENS + rag::estimate_tokens(&message.content) + calls
+        })
+        .sum()
+}
+
+/// Index of the first message of the latest turn: the last assistant
+/// message calling tools, or else the last message.
+fn latest_turn(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .rposition(|message| message.role == "assistant" && message.tool_calls.is_some())
+        .unwrap_or(messages.len().saturating_sub(1))
+}
+
+/// `messages` as a transcript, in pieces of at most `max_chars` characters.
+fn pieces(messages: &[Message], max_chars: usize) -> Vec<String> {
+    let mut pieces = vec![String::new()];
+    for message in messages {
+        let mut entry = format!("{}: {}\n\n", message.role, message.content);
+        for call in message.tool_calls.iter().flatten() {
+            entry.insert_str(entry.len() - 2, &format!("\n(called {} with {})", call.function.name, call.function.arguments));
+        }
+
+        let mut rest = entry.as_str();
+        while !rest.is_empty() {
+            let current = pieces.last_mut().expect("at least one piece");
+            let room = max_chars.saturating_sub(current.chars().count());
+            let end = rest.char_indices().nth(room).map_or(rest.len(), |(i, _)| text::floor_boundary(rest, i));
+            if end == 0 {
+                // No room left, or not even one grapheme; start a new piece
+                if current.is_empty() {
+                    let end = text::ceil_boundary(rest, rest.chars().next().map_or(0, char::len_utf8));
+                    current.push_str(&rest[..end]);
+                    rest = &rest[end..];
+                }
+                pieces.push(String::new());
+                continue;
+            }
+            current.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+    pieces.retain(|piece| !piece.is_empty());
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::models::EmbeddingModel;
+    use crate::provider::{ChatResponse, ToolCall, ToolCallFunction};
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Summarizes by counting, and records the prompts it was sent.
+    #[derive(Default)]
+    struct Summarizer {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Provider for Summarizer {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> crate::provider::Result<()> {
+            let mut prompts = self.prompts.lock().unwrap();
+            prompts.push(request.messages[0].content.clone());
+            let reply = format!("summary {}", prompts.len());
+            callback(ChatResponse {
+                model: request.model,
+                content: reply.clone(),
+                done: true,
+                truncated: false,
+                message: Message::assistant(None, reply),
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    fn window(context_length: usize) -> ContextWindow {
+        let mut llm = Config::default().llm;
+        llm.context_length = context_length;
+        llm.answer_tokens = 100;
+        ContextWindow::new(&llm)
+    }
+
+    fn tool_turn(name: &str, result: String) -> [Message; 2] {
+        let mut call = Message::assistant(None, "");
+        call.tool_calls = Some(vec![ToolCall {
+            function: ToolCallFunction {
+                name: name.to_string(),
+                arguments: serde_json::json!({ "path": "src/main.rs" }),
+            },
+        }]);
+        [call, Message::tool(None, result)]
+    }
+
+    #[tokio::test]
+    async fn test_fit_summarizes_older_turns() {
+        let provider = Summarizer::default();
+        let mut messages = vec![Message::user(None, "What does main do?")];
+        messages.extend(tool_turn("read_file", "fn main() {}\n".repeat(300)));
+        messages.extend(tool_turn("read_file", "fn helper() {}".to_string()));
+
+        // Fits: nothing happens
+        assert!(!window(10_000).fit(&provider, "model", &mut messages).await.unwrap());
+        assert_eq!(messages.len(), 5);
+
+        // The first turn goes, the latest one stays
+        assert!(window(1_400).fit(&provider, "model", &mut messages).await.unwrap());
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "user", "assistant", "tool"]);
+        assert_eq!(messages[3].content, "fn helper() {}");
+        let prompts = provider.prompts.lock().unwrap().clone();
+        assert!(messages[1].content.starts_with(SUMMARY_HEADING));
+        assert!(messages[1].content.ends_with(&format!("summary {}", prompts.len())));
+        assert!(prompts[0].contains("(called read_file with {\"path\":\"src/main.rs\"})"));
+        assert!(prompts.iter().all(|prompt| !prompt.contains("fn helper")));
+        assert!(tokens(&messages) <= window(1_400).available());
+    }
+
+    #[tokio::test]
+    async fn test_fit_summarizes_long_turns_in_pieces() {
+        let provider = Summarizer::default();
+        let mut messages = vec![Message::user(None, "Explain the logs")];
+        messages.extend(tool_turn("read_file", "error: timeout\n".repeat(2_000)));
+
+        assert!(window(1_500).fit(&provider, "model", &mut messages).await.unwrap());
+        // The only turn does not fit next to the summary, so it is summarized too
+        assert_eq!(messages.len(), 2);
+        let prompts = provider.prompts.lock().unwrap().clone();
+        assert!(prompts.len() > 1);
+        assert!(prompts[1].contains("Summary of the conversation before:\nsummary 1"));
+        assert!(messages[1].content.ends_with(&format!("summary {}", prompts.len())));
+
+        let mut question = vec![Message::user(None, "x".repeat(10_000))];
+        assert!(window(1_500).fit(&provider, "model", &mut question).await.is_err());
+    }
+
+    #[test]
+    fn test_pieces_split_long_messages() {
+        let messages = [Message::user(None, "é".repeat(25)), Message::assistant(None, "ok")];
+        let pieces = pieces(&messages, 10);
+        assert!(pieces.iter().all(|piece| piece.chars().count() <= 10));
+        assert_eq!(pieces.concat(), format!("user: {}\n\nassistant: ok\n\n", "é".repeat(25)));
+    }
+}
@@ -26,16 +26,32 @@
 //! The LLM streams responses in chunks. Tool calls may arrive in early chunks
 //! while the final `done=true` chunk contains no tool calls. The manager
 //! preserves tool calls from any chunk to ensure they're not lost.
+//!
+//! # Context Window
+//!
+//! Tool results can outgrow `llm.context_length`. Before each request the
+//! manager has the LLM summarize older turns that no longer fit and sends
+//! the summary in their place, keeping the question and the latest turn.
 
-use crate::config::Config;
+use super::context::ContextWindow;
+use super::orchestrator::{Orchestrator, ReviewOutcome};
+use super::scheduler::{ChatScheduler, Slot};
+use crate::config::{Config, OperationClass, ProviderKind};
+use crate::egress::{EgressClassifier, EgressConfirmation, Finding};
+use crate::events::{EventBus, EventKind};
 use crate::models::EmbeddingModel;
-use crate::provider::{ChatRequest, ChatResponse, Message, MistralRsProvider, Provider, Tool, ToolCall, ToolFunction};
-use crate::rag::RagEngine;
+use crate::plugins::{Plugins, SharedRegistry};
+use crate::provider::{
+    Capabilities, ChatRequest, ChatResponse, FallbackEntry, FallbackProvider, Message, MistralRsProvider, OllamaProvider,
+    OpenAiProvider, Provider, ResilientProvider, Tool, ToolCall, ToolFunction, WorkerProvider,
+};
+use crate::rag::{self, RagEngine, SearchResult};
 use nucleus_plugin::PluginRegistry;
 use anyhow::{Context, Result};
 use std::path::Path;
 use std::sync::Arc;
-use tracing::{debug, info};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
 /// Manages multi-turn conversations with tool-augmented LLM capabilities.
 ///
@@ -78,10 +94,19 @@ pub struct ChatManager {
     config: Config,
     /// LLM provider for communication
     provider: Arc<dyn Provider>,
-    /// Registry for available plugins/tools
-    registry: Arc<PluginRegistry>,
+    /// Registry for available plugins/tools, shared with whoever enables
+    /// and disables them
+    plugins: Plugins,
     /// RAG manager for knowledge base integration (with persistent storage)
     rag_engine:  Arc<RagEngine>,
+    /// Checks prompts for secrets before they are sent to a remote provider
+    egress: EgressClassifier,
+    /// Asked before sending prompts with findings when `egress.mode` is `confirm`
+    confirm_egress: Option<Arc<EgressConfirmation>>,
+    /// Receives the plugins executed, the edits they apply, and completed chats
+    events: Option<EventBus>,
+    /// Slots for model requests, at most `sessions.max_concurrent_chats` at once
+    chats: ChatScheduler,
 }
 
 impl ChatManager {
@@ -93,8 +118,8 @@ impl ChatManager {
     /// # Arguments
     ///
     /// * `config` - Nucleus configuration including LLM settings
-    /// * `registry` - Plugin registry containing available tools. The registry is wrapped
-    ///   in an `Arc` internally and shared between the manager and provider for tool execution.
+    /// * `registry` - Plugin registry containing available tools. The registry is shared
+    ///   between the manager and provider for tool execution, see [`plugins`](Self::plugins).
     ///
     /// # Examples
     ///
@@ -146,6 +171,21 @@ impl ChatManager {
     pub fn builder(config: Config, registry: PluginRegistry) -> ChatManagerBuilder {
         ChatManagerBuilder::new(config, registry)
     }
+
+    /// Creates a manager from parts its owner already has, e.g. the server's
+    /// provider and knowledge base.
+    pub(crate) fn from_parts(config: Config, provider: Arc<dyn Provider>, rag_engine: Arc<RagEngine>, plugins: Plugins) -> Self {
+        Self {
+            egress: EgressClassifier::new(&config.egress),
+            chats: ChatScheduler::spawn(config.sessions.max_concurrent_chats),
+            config,
+            provider,
+            plugins,
+            rag_engine,
+            confirm_egress: None,
+            events: None,
+        }
+    }
     
     ///
     /// # Examples
@@ -171,6 +211,41 @@ impl ChatManager {
         Ok(self)
     }
     
+    /// Replace the provider with an ordered fallback chain.
+    ///
+    /// Each provider is retried per its policy while unavailable (unreachable,
+    /// model not loaded, overloaded) before the next one is tried. Embeddings
+    /// come from the first provider.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nucleus_core::{ChatManager, Config};
+    /// # use nucleus_core::config::RetryPolicy;
+    /// # use nucleus_core::provider::{FallbackEntry, OllamaProvider, OpenAiProvider};
+    /// # use nucleus_plugin::{PluginRegistry, Permission};
+    /// # use std::sync::Arc;
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let config = Config::load_or_default();
+    /// # let registry = PluginRegistry::new(Permission::READ_ONLY);
+    /// let manager = ChatManager::new(config.clone(), registry).await?
+    ///     .with_fallback_providers(vec![
+    ///         FallbackEntry::new(Arc::new(OllamaProvider::new(&config)))
+    ///             .with_retry(RetryPolicy { max_retries: 2, backoff_ms: 250 }),
+    ///         FallbackEntry::new(Arc::new(OpenAiProvider::new(&config))).with_model("gpt-4o-mini"),
+    ///     ]).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `providers` is empty or the RAG system fails to initialize.
+    pub async fn with_fallback_providers(self, providers: Vec<FallbackEntry>) -> Result<Self> {
+        let chain = FallbackProvider::new(providers).context("No providers given")?;
+        self.with_provider(Arc::new(chain)).await
+    }
+    
     /// Replace the RAG manager.
     ///
     /// # Examples
@@ -196,6 +271,66 @@ impl ChatManager {
         self
     }
     
+    /// Sets the callback asked before a prompt with secrets or personal data
+    /// is sent to a remote provider.
+    ///
+    /// Only used when `egress.mode` is `confirm`; without a callback such
+    /// prompts are blocked.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nucleus_core::{ChatManager, Config};
+    /// # use nucleus_plugin::{PluginRegistry, Permission};
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let config = Config::load_or_default();
+    /// # let registry = PluginRegistry::new(Permission::READ_ONLY);
+    /// let manager = ChatManager::new(config, registry).await?
+    ///     .with_egress_confirmation(|findings| {
+    ///         eprintln!("Prompt contains {} sensitive item(s); sending anyway", findings.len());
+    ///         true
+    ///     });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_egress_confirmation<F>(mut self, confirm: F) -> Self
+    where
+        F: Fn(&[Finding]) -> bool + Send + Sync + 'static,
+    {
+        self.confirm_egress = Some(Arc::new(confirm));
+        self
+    }
+
+    /// Publishes workspace events to `events`: each plugin executed, each
+    /// file a plugin with write permission changed, and each completed chat.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nucleus_core::{ChatManager, Config};
+    /// # use nucleus_core::events::{EventBus, EventKind};
+    /// # use nucleus_plugin::{PluginRegistry, Permission};
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let config = Config::load_or_default();
+    /// # let registry = PluginRegistry::new(Permission::READ_WRITE);
+    /// let events = EventBus::default();
+    /// let mut edits = events.subscribe("edit-applied")?;
+    /// tokio::spawn(async move {
+    ///     while let Some(event) = edits.next().await {
+    ///         if let EventKind::EditApplied { path, .. } = event.kind {
+    ///             println!("{} changed, rebuilding docs", path);
+    ///         }
+    ///     }
+    /// });
+    /// let manager = ChatManager::new(config, registry).await?.with_events(events);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+    
     /// Loads previously indexed documents from persistent storage.
     ///
     /// Should be called after creating the ChatManager to restore the knowledge base.
@@ -225,6 +360,18 @@ impl ChatManager {
     pub async fn knowledge_base_count(&self) -> usize {
         self.rag_engine.count().await
     }
+
+    /// The plugins tools run from; enabling or disabling one through them
+    /// applies from the next tool call.
+    pub fn plugins(&self) -> &Plugins {
+        &self.plugins
+    }
+
+    /// What the provider supports with the configured model (tools, images,
+    /// context window), to leave out what it can't handle.
+    pub async fn capabilities(&self) -> Capabilities {
+        self.provider.capabilities(&self.config.llm.model).await
+    }
     
     /// Indexes a directory into the knowledge base.
     ///
@@ -244,6 +391,53 @@ impl ChatManager {
             .context("Failed to index directory")
     }
 
+    /// Searches the knowledge base without asking the LLM.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Text to find similar documents for
+    /// * `limit` - Maximum number of results (defaults to `storage.top_k`)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if embedding the query or the search fails.
+    pub async fn search(&self, query: &str, limit: Option<usize>) -> Result<Vec<SearchResult>> {
+        let mut options = self.rag_engine.retrieval_options();
+        if let Some(limit) = limit {
+            options.top_k = limit;
+        }
+        self.rag_engine.retrieve_with(query, options).await
+            .context("Failed to search knowledge base")
+    }
+
+    /// Retrieves the knowledge base results [`query`](Self::query) would use
+    /// as context for `query`, without asking the LLM.
+    ///
+    /// With [`generate`](Self::generate), this lets callers rerank or filter
+    /// the results, show their sources, or assemble the prompt themselves
+    /// before generation starts. [`rag::format_context`] formats results
+    /// the way `query` does.
+    ///
+    /// # Returns
+    ///
+    /// The most relevant chunks, or none if the knowledge base is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if embedding the query or the search fails.
+    pub async fn retrieve(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let rag_count = self.rag_engine.count().await;
+        debug!("RAG knowledge base has {} documents", rag_count);
+        if rag_count == 0 {
+            debug!("RAG knowledge base is empty, skipping context retrieval");
+            return Ok(Vec::new());
+        }
+        
+        debug!("Retrieving RAG context for query: {}", query);
+        self.rag_engine.retrieve_with(query, self.rag_engine.retrieval_options()).await
+            .context("Failed to retrieve context")
+    }
+
     /// Sends a query to the LLM and returns the final response.
     ///
     /// This method handles the complete conversation flow including:
@@ -335,27 +529,83 @@ impl ChatManager {
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn query_stream<F>(&self, user_message: &str, mut on_chunk: F) -> Result<String>
+    pub async fn query_stream<F>(&self, user_message: &str, on_chunk: F) -> Result<String>
     where
         F: FnMut(&str) + Send,
     {
-        // Retrieve relevant context from knowledge base if available
-        let rag_count = self.rag_engine.count().await;
-        debug!("RAG knowledge base has {} documents", rag_count);
-        
-        // Context retrieved from RAG
-        let context = if rag_count > 0 {
-            debug!("Retrieving RAG context for query: {}", user_message);
-            self.rag_engine.retrieve_context(user_message).await
-                .unwrap_or_else(|e| {
-                    debug!("Could not retrieve RAG context: {}", e);
+        self.answer(None, user_message, None, on_chunk).await
+    }
+
+    /// Answers `user_message` from the given `context` without searching the
+    /// knowledge base, and returns the final response.
+    ///
+    /// `context` is prepended to the message as [`query`](Self::query) does
+    /// with retrieved context; see [`retrieve`](Self::retrieve). Tools are
+    /// offered and executed as in `query`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the LLM request fails or a requested tool fails
+    /// to execute.
+    pub async fn generate(&self, user_message: &str, context: &str) -> Result<String> {
+        self.generate_stream(user_message, context, |_| {}).await
+    }
+
+    /// Streaming version of [`generate`](Self::generate), calling `on_chunk`
+    /// with each chunk of content as it arrives.
+    pub async fn generate_stream<F>(&self, user_message: &str, context: &str, on_chunk: F) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.answer(None, user_message, Some(context), on_chunk).await
+    }
+
+    /// A handle answering for session `id`: its calls run one at a time, in
+    /// the order they were made, like requests to the server in a session.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nucleus_core::ChatManager;
+    /// # async fn example(manager: &ChatManager) -> anyhow::Result<()> {
+    /// let session = manager.session("editor-1");
+    /// let response = session.query("Summarize the README file").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn session(&self, id: impl Into<String>) -> SessionChat<'_> {
+        SessionChat { manager: self, id: id.into() }
+    }
+
+    /// Waits for a slot for a request of `session`, shared with every other
+    /// caller of this manager.
+    pub(crate) async fn acquire(&self, session: Option<&str>) -> Slot {
+        self.chats.acquire(session).await
+    }
+
+    /// Answers `user_message` once `session` has a slot, from `context` or,
+    /// if none is given, from what the knowledge base finds for it.
+    async fn answer<F>(&self, session: Option<&str>, user_message: &str, context: Option<&str>, mut on_chunk: F) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        // Calls from other threads run at most `sessions.max_concurrent_chats`
+        // at once, as requests to the server do
+        let _slot = self.acquire(session).await;
+        let context = match context {
+            Some(context) => context.to_string(),
+            None => match self.retrieve(user_message).await {
+                Ok(results) => {
+                    let results = rag::ContextBudget::new(&self.config.llm).fit(results, &[user_message]);
+                    rag::format_context(&results, self.rag_engine.context_format())
+                }
+                Err(e) => {
+                    debug!("Could not retrieve RAG context: {:#}", e);
                     String::new()
-                })
-        } else {
-            debug!("RAG knowledge base is empty, skipping context retrieval");
-            String::new()
+                }
+            },
         };
-        
+        let started = Instant::now();
         // Construct user message with context if available
         let enhanced_message = if !context.is_empty() {
             debug!("Enhanced message with {} characters of RAG context", context.len());
@@ -365,14 +615,55 @@ impl ChatManager {
             user_message.to_string()
         };
         
-        let mut messages = vec![Message::user(Some(context.clone()), &enhanced_message)];
+        let request = ChatRequest::new(&self.config.llm.model, vec![Message::user(Some(context.to_string()), &enhanced_message)])
+            .with_temperature(self.config.llm.temperature);
+        let response = self.complete(request, 0, &mut on_chunk).await?;
+        if let Some(events) = &self.events {
+            events.publish(EventKind::ChatCompleted {
+                response_id: None,
+                session_id: session.map(str::to_string),
+                duration_ms: started.elapsed().as_millis() as u64,
+                truncated: response.truncated,
+            });
+        }
+        Ok(response.message.content)
+    }
 
-        let tools = self.build_tools();
+    /// Answers `request`, offering the registry's plugins as tools and
+    /// running the ones the model calls until it answers without any.
+    ///
+    /// The first `checked` messages are not checked for secrets again. The
+    /// final response carries the last answer's content and truncation;
+    /// `on_chunk` also receives what came before tool calls.
+    pub(crate) async fn complete<F>(&self, mut request: ChatRequest, mut checked: usize, on_chunk: &mut F) -> Result<ChatResponse>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let mut messages = std::mem::take(&mut request.messages);
+        let context = messages.last().and_then(|message| message.context.clone());
 
-        loop {
-            let mut request = ChatRequest::new(&self.config.llm.model, messages.clone())
-                .with_temperature(self.config.llm.temperature);
+        let mut tools = self.build_tools().await;
+        if !tools.is_empty() && !self.provider.capabilities(&request.model).await.tools {
+            debug!("{} does not support tools; answering without them", request.model);
+            tools.clear();
+        }
 
+        loop {
+            if self.provider.is_remote() {
+                let outgoing: Vec<&str> = messages[checked..].iter().map(|m| m.content.as_str()).collect();
+                self.egress.check(self.config.egress.mode, &outgoing.join("\n"), self.confirm_egress.as_deref())?;
+                checked = messages.len();
+            }
+            let window = ContextWindow::new(&self.config.llm);
+            if window.fit(self.provider.as_ref(), &request.model, &mut messages).await? {
+                // The summary only restates messages that were already checked
+                checked = messages.len();
+            }
+            
+            let mut request = ChatRequest {
+                messages: messages.clone(),
+                ..request.clone()
+            };
             if !tools.is_empty() {
                 request.tools = Some(tools.clone());
             }
@@ -386,7 +677,7 @@ impl ChatManager {
             self.provider
                 .chat(request, Box::new(|response| {
                     // Call user's streaming callback with incremental content
-                    if !response.done && !response.content.is_empty() {
+                    if !response.content.is_empty() {
                         on_chunk(&response.content);
                     }
                     
@@ -410,15 +701,14 @@ impl ChatManager {
             // Reconstruct the complete message with accumulated content and preserved tool calls
             response.message.content = accumulated_content;
             response.message.tool_calls = tool_calls;
-            let assistant_message = response.message;
 
             // Handle tool calls: execute each tool and add results to conversation
-            if let Some(tool_calls) = &assistant_message.tool_calls {
+            if let Some(tool_calls) = &response.message.tool_calls {
                 // Add the assistant's message with tool calls to conversation history
                 messages.push(Message {
                     role: "assistant".to_string(),
-                    context: Some(context.to_string()),
-                    content: assistant_message.content.clone(),
+                    context: context.clone(),
+                    content: response.message.content.clone(),
                     images: None,
                     tool_calls: Some(tool_calls.clone()),
                 });
@@ -429,16 +719,35 @@ impl ChatManager {
                     let tool_args = &tool_call.function.arguments;
                     info!(tool_name = %tool_name, "Executing tool");
 
-                    let result = self
-                        .registry
-                        .execute(tool_name, tool_args.clone())
-                        .await
-                        .with_context(|| format!("Failed to execute tool: {}", tool_name))?;
+                    // The registry is only locked for the lookup, so enabling or
+                    // disabling plugins doesn't wait for the call
+                    let plugin = self.plugins.registry().read().await.authorize(tool_name, tool_args);
+                    let writes = plugin.as_ref().is_ok_and(|plugin| plugin.required_permission().write);
+                    let executed = Instant::now();
+                    let result = match plugin {
+                        Ok(plugin) => {
+                            let execution = plugin.execute(tool_args.clone());
+                            match self.config.timeouts.limit(OperationClass::Plugin) {
+                                Some(limit) => match tokio::time::timeout(limit, execution).await {
+                                    Ok(result) => result.map_err(anyhow::Error::from),
+                                    Err(_) => Err(anyhow::anyhow!(
+                                        "Tool {} timed out after {}s (raise `timeouts.plugin_secs` to allow more time)",
+                                        tool_name,
+                                        limit.as_secs()
+                                    )),
+                                },
+                                None => execution.await.map_err(anyhow::Error::from),
+                            }
+                        }
+                        Err(e) => Err(anyhow::Error::from(e)),
+                    };
+                    self.publish_execution(tool_call, writes, executed.elapsed(), result.as_ref().err());
+                    let result = result.with_context(|| format!("Failed to execute tool: {}", tool_name))?;
 
                     // Add tool result as a message for the LLM to synthesize
                     messages.push(Message {
                         role: "tool".to_string(),
-                        context: Some(context.to_string()),
+                        context: context.clone(),
                         content: result.content,
                         images: None,
                         tool_calls: None,
@@ -447,11 +756,50 @@ impl ChatManager {
                 // Continue loop to get LLM's response using the tool results
             } else {
                 // No tool calls - this is the final response
-                return Ok(assistant_message.content);
+                return Ok(response);
             }
         }
     }
 
+    /// Announces an executed tool call, and the file it changed if the plugin
+    /// `writes` (has write permission) and was given a `path`.
+    fn publish_execution(&self, tool_call: &ToolCall, writes: bool, duration: Duration, error: Option<&anyhow::Error>) {
+        let Some(events) = &self.events else {
+            return;
+        };
+        let plugin = &tool_call.function.name;
+        events.publish(EventKind::PluginExecuted {
+            plugin: plugin.clone(),
+            success: error.is_none(),
+            duration_ms: duration.as_millis() as u64,
+            error: error.map(|e| format!("{:#}", e)),
+        });
+
+        if let (true, None, Some(path)) = (writes, error, tool_call.function.arguments["path"].as_str()) {
+            events.publish(EventKind::EditApplied {
+                path: path.to_string(),
+                plugin: plugin.clone(),
+            });
+        }
+    }
+
+    /// Runs an implementer/reviewer review loop for a task.
+    ///
+    /// Two personas (configured under `orchestration`) iterate on the task for up
+    /// to `orchestration.max_rounds` rounds and produce a merged final answer.
+    /// Useful for higher-stakes changes where a second opinion is worth the
+    /// extra generations. See [`Orchestrator`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any LLM request fails.
+    pub async fn review_loop(&self, task: &str) -> Result<ReviewOutcome> {
+        let _slot = self.chats.acquire(None).await;
+        Orchestrator::new(self.provider.clone(), &self.config)
+            .review_loop(task, |_| {})
+            .await
+    }
+
     /// Converts registered plugins into Ollama tool definitions.
     ///
     /// Transforms plugins from the registry into the JSON schema format
@@ -466,8 +814,11 @@ impl ChatManager {
     ///
     /// This method is called once at the start of each query. Tools are
     /// included in every LLM request throughout the conversation loop.
-    fn build_tools(&self) -> Vec<Tool> {
-        self.registry
+    async fn build_tools(&self) -> Vec<Tool> {
+        self.plugins
+            .registry()
+            .read()
+            .await
             .all()
             .iter()
             .map(|plugin| {
@@ -485,6 +836,49 @@ impl ChatManager {
     }
 }
 
+/// A [`ChatManager`] answering for one session, from
+/// [`ChatManager::session`].
+///
+/// Its methods are those of the manager, waiting for the session's earlier
+/// calls to finish first.
+pub struct SessionChat<'a> {
+    manager: &'a ChatManager,
+    id: String,
+}
+
+impl SessionChat<'_> {
+    /// The session's ID.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// [`ChatManager::query`] in this session.
+    pub async fn query(&self, user_message: &str) -> Result<String> {
+        self.query_stream(user_message, |_| {}).await
+    }
+
+    /// [`ChatManager::query_stream`] in this session.
+    pub async fn query_stream<F>(&self, user_message: &str, on_chunk: F) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.manager.answer(Some(&self.id), user_message, None, on_chunk).await
+    }
+
+    /// [`ChatManager::generate`] in this session.
+    pub async fn generate(&self, user_message: &str, context: &str) -> Result<String> {
+        self.generate_stream(user_message, context, |_| {}).await
+    }
+
+    /// [`ChatManager::generate_stream`] in this session.
+    pub async fn generate_stream<F>(&self, user_message: &str, context: &str, on_chunk: F) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.manager.answer(Some(&self.id), user_message, Some(context), on_chunk).await
+    }
+}
+
 /// Builder for configuring and creating a `ChatManager`.
 ///
 /// This builder provides a fluent API for customizing LLM and embedding models
@@ -529,16 +923,77 @@ impl ChatManager {
 pub struct ChatManagerBuilder {
     config: Config,
     registry: PluginRegistry,
+    plugins: Option<Plugins>,
     llm_model_override: Option<String>,
     embedding_model_override: Option<EmbeddingModel>,
 }
 
+/// Creates the provider selected by `llm.provider` (mistral.rs by default).
+///
+/// Server-backed providers get retries and a circuit breaker (`resilience`);
+/// with `llm.worker.enabled`, mistral.rs runs in a worker process.
+async fn create_provider(config: &Config, registry: &SharedRegistry) -> Result<Arc<dyn Provider>> {
+    Ok(match config.llm.provider {
+        Some(ProviderKind::OpenAi) => Arc::new(ResilientProvider::new(
+            Arc::new(OpenAiProvider::new(config)),
+            "OpenAI-compatible API",
+            &config.resilience,
+        )),
+        Some(ProviderKind::Ollama) => Arc::new(ResilientProvider::new(
+            Arc::new(OllamaProvider::new(config)),
+            "Ollama",
+            &config.resilience,
+        )),
+        #[cfg(feature = "llama-cpp")]
+        Some(ProviderKind::LlamaCpp) => Arc::new(crate::provider::LlamaCppProvider::new(config).await?),
+        #[cfg(not(feature = "llama-cpp"))]
+        Some(ProviderKind::LlamaCpp) => {
+            anyhow::bail!("llm.provider 'llamacpp' requires building with the `llama-cpp` feature")
+        }
+        None | Some(ProviderKind::MistralRs) if config.llm.worker.enabled => Arc::new(
+            WorkerProvider::start(config).await?
+        ),
+        None | Some(ProviderKind::MistralRs) => Arc::new(
+            MistralRsProvider::new(config, Arc::clone(registry)).await?
+        ),
+    })
+}
+
+/// Chains the primary provider with `llm.fallbacks`.
+///
+/// Providers that fail to load (e.g. a model that does not fit in memory)
+/// are left out of the chain.
+async fn fallback_chain(config: &Config, registry: &SharedRegistry) -> Result<Arc<dyn Provider>> {
+    let mut llm_configs = vec![config.llm.clone()];
+    llm_configs.extend(config.llm.fallbacks.iter().map(|fallback| fallback.apply(&config.llm)));
+
+    let mut entries = Vec::new();
+    for llm in llm_configs {
+        let provider_config = Config { llm, ..config.clone() };
+        match create_provider(&provider_config, registry).await {
+            Ok(provider) => entries.push(
+                FallbackEntry::new(provider)
+                    .with_retry(provider_config.llm.retry)
+                    .with_model(provider_config.llm.model),
+            ),
+            Err(e) => {
+                let kind = provider_config.llm.provider.unwrap_or(ProviderKind::MistralRs);
+                warn!("Skipping {:?} provider that failed to load: {:#}", kind, e);
+            }
+        }
+    }
+
+    let chain = FallbackProvider::new(entries).context("No configured provider could be loaded")?;
+    Ok(Arc::new(chain))
+}
+
 impl ChatManagerBuilder {
     /// Creates a new builder with the given config and registry.
     pub fn new(config: Config, registry: PluginRegistry) -> Self {
         Self {
             config,
             registry,
+            plugins: None,
             llm_model_override: None,
             embedding_model_override: None,
         }
@@ -617,10 +1072,21 @@ impl ChatManagerBuilder {
         self
     }
 
+    /// Runs tools from `plugins` instead of the builder's registry, so that
+    /// enabling or disabling a plugin through another holder of `plugins`
+    /// (e.g. another `ChatManager`) applies to this one too.
+    pub fn with_plugins(mut self, plugins: Plugins) -> Self {
+        self.plugins = Some(plugins);
+        self
+    }
+
     /// Builds the `ChatManager` with the configured settings.
     ///
-    /// This initializes the provider with the (possibly overridden) LLM model,
+    /// This initializes the provider selected by `llm.provider` (mistral.rs by
+    /// default, chained with any `llm.fallbacks`) with the (possibly overridden) LLM model,
     /// and the RAG system with the (possibly overridden) embedding model.
+    /// The enabled plugins found in `plugins.path` join the registry's,
+    /// unless [`with_plugins`](Self::with_plugins) gave plugins to share.
     ///
     /// # Errors
     ///
@@ -637,17 +1103,241 @@ impl ChatManagerBuilder {
             config.rag.embedding_model = embedding_model;
         }
 
-        let registry = Arc::new(self.registry);
-        let provider: Arc<dyn Provider> = Arc::new(
-            MistralRsProvider::new(&config, Arc::clone(&registry)).await?
-        );
+        let plugins = match self.plugins {
+            Some(plugins) => plugins,
+            None => Plugins::new(&config.plugins, self.registry),
+        };
+        let provider = if config.llm.fallbacks.is_empty() {
+            create_provider(&config, plugins.registry()).await?
+        } else {
+            fallback_chain(&config, plugins.registry()).await?
+        };
         let rag_engine = Arc::new(RagEngine::new(&config, provider.clone()).await?);
 
-        Ok(ChatManager {
-            config,
-            provider,
-            registry,
-            rag_engine,
-        })
+        Ok(ChatManager::from_parts(config, provider, rag_engine, plugins))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PluginsConfig;
+    use crate::provider::ToolCallFunction;
+    use async_trait::async_trait;
+    use nucleus_plugin::Permission;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn manager(config: Config, provider: Arc<dyn Provider>, plugins: Plugins) -> ChatManager {
+        let rag_engine = Arc::new(RagEngine::deferred(&config, provider.clone()));
+        ChatManager::from_parts(config, provider, rag_engine, plugins)
+    }
+
+    /// Calls the `echo` tool, then answers with what it returned.
+    struct EchoCaller;
+
+    #[async_trait]
+    impl Provider for EchoCaller {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> crate::provider::Result<()> {
+            let last = request.messages.last().unwrap();
+            let mut message = Message::assistant(None, "");
+            if last.role == "tool" {
+                message.content = last.content.clone();
+            } else {
+                message.tool_calls = Some(vec![ToolCall {
+                    function: ToolCallFunction { name: "echo".to_string(), arguments: serde_json::json!({}) },
+                }]);
+            }
+            callback(ChatResponse {
+                model: request.model,
+                content: message.content.clone(),
+                done: true,
+                truncated: false,
+                message,
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    /// Answers after a pause, counting the most chats it had at once.
+    #[derive(Default)]
+    struct Slow {
+        running: AtomicUsize,
+        most: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Provider for Slow {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> crate::provider::Result<()> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            callback(ChatResponse {
+                model: request.model,
+                content: "done".to_string(),
+                done: true,
+                truncated: false,
+                message: Message::assistant(None, "done"),
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    /// Answers until it hits the token limit.
+    struct Cut;
+
+    #[async_trait]
+    impl Provider for Cut {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> crate::provider::Result<()> {
+            callback(ChatResponse {
+                model: request.model,
+                content: "partial".to_string(),
+                done: true,
+                truncated: true,
+                message: Message::assistant(None, "partial"),
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_completed_event_reports_truncation() {
+        let events = EventBus::new(8);
+        let mut completed = events.subscribe("chat-completed").unwrap();
+        let plugins_config = PluginsConfig { enabled: false, ..PluginsConfig::default() };
+        let plugins = Plugins::new(&plugins_config, PluginRegistry::new(Permission::NONE));
+        let manager = manager(Config::default(), Arc::new(Cut), plugins).with_events(events);
+
+        assert_eq!(manager.generate("hi", "").await.unwrap(), "partial");
+        let event = completed.next().await.unwrap();
+        assert!(matches!(event.kind, EventKind::ChatCompleted { truncated: true, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_chats_are_limited() {
+        let mut config = Config::default();
+        config.sessions.max_concurrent_chats = 2;
+        let provider = Arc::new(Slow::default());
+        let plugins_config = PluginsConfig { enabled: false, ..PluginsConfig::default() };
+        let manager = manager(config, provider.clone(), Plugins::new(&plugins_config, PluginRegistry::new(Permission::NONE)));
+
+        let answers = futures::future::join_all((0..5).map(|_| manager.generate("hi", ""))).await;
+        assert!(answers.into_iter().all(|answer| answer.unwrap() == "done"));
+        assert_eq!(provider.most.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_session_calls_run_one_at_a_time() {
+        let mut config = Config::default();
+        config.sessions.max_concurrent_chats = 0;
+        let provider = Arc::new(Slow::default());
+        let plugins_config = PluginsConfig { enabled: false, ..PluginsConfig::default() };
+        let manager = manager(config, provider.clone(), Plugins::new(&plugins_config, PluginRegistry::new(Permission::NONE)));
+
+        let session = manager.session("a");
+        let answers = futures::future::join_all((0..3).map(|_| session.generate("hi", ""))).await;
+        assert!(answers.into_iter().all(|answer| answer.unwrap() == "done"));
+        assert_eq!(provider.most.load(Ordering::SeqCst), 1);
+
+        // Other sessions run alongside it
+        let other = manager.session("b");
+        let (first, second) = tokio::join!(session.generate("hi", ""), other.generate("hi", ""));
+        assert_eq!((first.unwrap(), second.unwrap()), ("done".to_string(), "done".to_string()));
+        assert_eq!(provider.most.load(Ordering::SeqCst), 2);
+    }
+
+    /// An `echo` tool that takes a while, announcing when it starts.
+    struct SlowEcho(Arc<tokio::sync::Notify>);
+
+    #[async_trait]
+    impl nucleus_plugin::Plugin for SlowEcho {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "Echoes, slowly"
+        }
+
+        fn parameter_schema(&self) -> serde_json::Value {
+            serde_json::json!({})
+        }
+
+        fn required_permission(&self) -> Permission {
+            Permission::NONE
+        }
+
+        async fn execute(&self, _input: serde_json::Value) -> nucleus_plugin::Result<nucleus_plugin::PluginOutput> {
+            self.0.notify_one();
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            Ok(nucleus_plugin::PluginOutput::new("echoed"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_is_not_locked_during_tool_calls() {
+        let started = Arc::new(tokio::sync::Notify::new());
+        let mut registry = PluginRegistry::new(Permission::NONE);
+        assert!(registry.register(Arc::new(SlowEcho(started.clone()))));
+        let plugins_config = PluginsConfig { enabled: false, ..PluginsConfig::default() };
+        let manager = manager(Config::default(), Arc::new(EchoCaller), Plugins::new(&plugins_config, registry));
+
+        let writer = async {
+            started.notified().await;
+            tokio::time::timeout(Duration::from_millis(100), manager.plugins().registry().write()).await.is_ok()
+        };
+        let (answer, locked) = tokio::join!(manager.generate("hi", ""), writer);
+        assert_eq!(answer.unwrap(), "echoed");
+        assert!(locked);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_disabled_plugin_is_refused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("echo");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("plugin.yaml"), "name: echo\ndescription: Echoes\nentry: ./run.sh\n").unwrap();
+        std::fs::write(dir.join("run.sh"), "#!/bin/sh\necho echoed\n").unwrap();
+        std::fs::set_permissions(dir.join("run.sh"), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let plugins_config = PluginsConfig { path: root.path().display().to_string(), ..PluginsConfig::default() };
+        let plugins = Plugins::new(&plugins_config, PluginRegistry::new(Permission::ALL));
+        let manager = manager(Config::default(), Arc::new(EchoCaller), plugins);
+        assert_eq!(manager.generate("hi", "").await.unwrap(), "echoed");
+
+        // Another holder of the plugins disables it, e.g. the server handler
+        manager.plugins().clone().set_enabled("echo", false).await.unwrap();
+        let error = manager.generate("hi", "").await.unwrap_err();
+        assert_eq!(format!("{:#}", error), "Failed to execute tool: echo: Plugin error: Unknown plugin: echo");
+
+        manager.plugins().set_enabled("echo", true).await.unwrap();
+        assert_eq!(manager.generate("hi", "").await.unwrap(), "echoed");
     }
 }
@@ -1,3 +1,7 @@
+mod context;
 mod manager;
+mod orchestrator;
+mod scheduler;
 
-pub use manager::{ChatManager, ChatManagerBuilder};
+pub use manager::{ChatManager, ChatManagerBuilder, SessionChat};
+pub use orchestrator::{Orchestrator, ReviewOutcome, ReviewRound};
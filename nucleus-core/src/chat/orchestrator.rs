@@ -0,0 +1,261 @@
+//! Multi-agent review loop between two personas.
+//!
+//! The orchestrator lets an "implementer" persona draft an answer and a
+//! "reviewer" persona critique it, iterating for a bounded number of rounds.
+//! Once the reviewer approves (or the round limit is reached) the implementer
+//! produces a final answer that incorporates the review feedback.
+//!
+//! ```text
+//! Task → Implementer draft → Reviewer → APPROVED? ──yes──→ Final answer
+//!              ↑                 │ no
+//!              └──── revise ─────┘ (up to max_rounds)
+//! ```
+
+use crate::config::{Config, OrchestrationConfig, PersonaConfig, VerbosityConfig};
+use crate::provider::{ChatRequest, Message, Provider};
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tracing::{debug, info};
+
+/// Marker the reviewer uses to accept a draft.
+const APPROVAL_MARKER: &str = "APPROVED";
+
+/// One implementer/reviewer exchange.
+#[derive(Debug, Clone)]
+pub struct ReviewRound {
+    pub draft: String,
+    pub review: String,
+    pub approved: bool,
+}
+
+/// Result of a completed review loop.
+#[derive(Debug, Clone)]
+pub struct ReviewOutcome {
+    /// The merged final answer
+    pub final_answer: String,
+    /// Every draft and review, in order
+    pub rounds: Vec<ReviewRound>,
+    /// Whether the reviewer approved a draft before the round limit
+    pub approved: bool,
+}
+
+/// Runs implementer/reviewer conversations against a provider.
+///
+/// Each persona may override the model, temperature, and verbosity. Providers that serve
+/// a single loaded model (such as mistral.rs) ignore the model override.
+pub struct Orchestrator {
+    provider: Arc<dyn Provider>,
+    config: OrchestrationConfig,
+    default_model: String,
+    default_temperature: f64,
+    verbosity: VerbosityConfig,
+}
+
+impl Orchestrator {
+    pub fn new(provider: Arc<dyn Provider>, config: &Config) -> Self {
+        Self {
+            provider,
+            config: config.orchestration.clone(),
+            default_model: config.llm.model.clone(),
+            default_temperature: config.llm.temperature,
+            verbosity: config.verbosity.clone(),
+        }
+    }
+
+    /// Runs the review loop for `task`.
+    ///
+    /// `on_chunk` receives progress text as it is produced: a header for each
+    /// persona turn followed by that persona's streamed output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any LLM request fails.
+    pub async fn review_loop<F>(&self, task: &str, mut on_chunk: F) -> Result<ReviewOutcome>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let implementer = &self.config.implementer;
+        let reviewer = &self.config.reviewer;
+        let max_rounds = self.config.max_rounds.max(1);
+
+        let mut rounds: Vec<ReviewRound> = Vec::new();
+        let mut approved = false;
+
+        for round in 1..=max_rounds {
+            let draft_prompt = match rounds.last() {
+                None => task.to_string(),
+                Some(previous) => format!(
+                    "Task:\n{}\n\nYour previous draft:\n{}\n\nReviewer feedback:\n{}\n\n\
+                     Revise the draft to address the feedback.",
+                    task, previous.draft, previous.review
+                ),
+            };
+
+            on_chunk(&format!("\n--- {} (round {}) ---\n", implementer.name, round));
+            let draft = self.complete(implementer, &draft_prompt, &mut on_chunk).await?;
+
+            let review_prompt = format!(
+                "Task:\n{}\n\nProposed answer:\n{}\n\n\
+                 Review the proposed answer for correctness and completeness. \
+                 If it needs no further changes, reply with {} on the first line. \
+                 Otherwise list the concrete problems to fix.",
+                task, draft, APPROVAL_MARKER
+            );
+
+            on_chunk(&format!("\n--- {} (round {}) ---\n", reviewer.name, round));
+            let review = self.complete(reviewer, &review_prompt, &mut on_chunk).await?;
+
+            let round_approved = is_approval(&review);
+            debug!(round, approved = round_approved, "Review round complete");
+            rounds.push(ReviewRound {
+                draft,
+                review,
+                approved: round_approved,
+            });
+
+            if round_approved {
+                approved = true;
+                break;
+            }
+        }
+
+        let last = rounds.last().context("Review loop produced no rounds")?;
+        let final_answer = if approved {
+            last.draft.clone()
+        } else {
+            let merge_prompt = format!(
+                "Task:\n{}\n\nLatest draft:\n{}\n\nOutstanding reviewer feedback:\n{}\n\n\
+                 Write the final answer, incorporating the feedback.",
+                task, last.draft, last.review
+            );
+            on_chunk(&format!("\n--- {} (final) ---\n", implementer.name));
+            self.complete(implementer, &merge_prompt, &mut on_chunk).await?
+        };
+
+        info!(rounds = rounds.len(), approved, "Review loop finished");
+
+        Ok(ReviewOutcome {
+            final_answer,
+            rounds,
+            approved,
+        })
+    }
+
+    /// Sends a single-turn request as `persona` and returns the full response.
+    async fn complete<F>(&self, persona: &PersonaConfig, prompt: &str, on_chunk: &mut F) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let mut system_prompt = persona.system_prompt.clone();
+        if let Some(instructions) = persona.verbosity.and_then(|verbosity| verbosity.instructions()) {
+            system_prompt.push_str(&format!("\n\n{}", instructions));
+        }
+        let messages = vec![
+            Message::system(None, system_prompt),
+            Message::user(None, prompt),
+        ];
+        let model = persona.model.as_deref().unwrap_or(&self.default_model);
+        let mut request = ChatRequest::new(model, messages)
+            .with_temperature(persona.temperature.unwrap_or(self.default_temperature));
+        if let Some(max_tokens) = persona.verbosity.and_then(|verbosity| self.verbosity.max_tokens(verbosity)) {
+            request = request.with_max_tokens(max_tokens);
+        }
+
+        let mut content = String::new();
+        self.provider
+            .chat(request, Box::new(|response| {
+                // Providers either stream increments and finish with an empty
+                // done chunk, or send the full text in the done chunk.
+                if !response.done || content.is_empty() {
+                    on_chunk(&response.content);
+                    content.push_str(&response.content);
+                }
+            }))
+            .await
+            .with_context(|| format!("Failed to get response from persona '{}'", persona.name))?;
+
+        Ok(content)
+    }
+}
+
+fn is_approval(review: &str) -> bool {
+    review
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(|line| line.trim_start_matches(['*', '#', ' ']).starts_with(APPROVAL_MARKER))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EmbeddingModel;
+    use crate::provider::ChatResponse;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Replies with canned responses in order.
+    struct ScriptedProvider {
+        replies: Mutex<Vec<&'static str>>,
+    }
+
+    #[async_trait]
+    impl Provider for ScriptedProvider {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> crate::provider::Result<()> {
+            let reply = self.replies.lock().unwrap().remove(0);
+            callback(ChatResponse {
+                model: request.model,
+                content: reply.to_string(),
+                done: true,
+                truncated: false,
+                message: Message::assistant(None, reply),
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    fn orchestrator(replies: Vec<&'static str>, max_rounds: usize) -> Orchestrator {
+        let mut config = Config::default();
+        config.orchestration.max_rounds = max_rounds;
+        let provider = Arc::new(ScriptedProvider {
+            replies: Mutex::new(replies),
+        });
+        Orchestrator::new(provider, &config)
+    }
+
+    #[tokio::test]
+    async fn test_stops_on_approval() {
+        let orchestrator = orchestrator(vec!["draft one", "Needs tests", "draft two", "APPROVED"], 3);
+        let outcome = orchestrator.review_loop("task", |_| {}).await.unwrap();
+
+        assert!(outcome.approved);
+        assert_eq!(outcome.rounds.len(), 2);
+        assert_eq!(outcome.final_answer, "draft two");
+    }
+
+    #[tokio::test]
+    async fn test_merges_after_round_limit() {
+        let orchestrator = orchestrator(vec!["draft", "Missing error handling", "final"], 1);
+        let outcome = orchestrator.review_loop("task", |_| {}).await.unwrap();
+
+        assert!(!outcome.approved);
+        assert_eq!(outcome.rounds.len(), 1);
+        assert_eq!(outcome.final_answer, "final");
+    }
+
+    #[test]
+    fn test_is_approval() {
+        assert!(is_approval("APPROVED\nLooks good"));
+        assert!(is_approval("\n**APPROVED**"));
+        assert!(!is_approval("Not APPROVED yet"));
+    }
+}
@@ -0,0 +1,216 @@
+//! Scheduling of model requests from every transport.
+//!
+//! The socket and gRPC transports share one server request handler, whose
+//! [`ChatManager`](super::ChatManager) answers their chats, and a manager may
+//! also be called from many threads of an embedding application (e.g.
+//! through the C API), so requests for the model arrive from many places at
+//! once. Each one asks [`ChatScheduler::acquire`] for a [`Slot`] before it
+//! runs and gives it back by dropping it. The slots are handed out by a task
+//! that owns all the scheduling state and hears of requests and finished
+//! ones only through messages:
+//!
+//! - a session runs one request at a time, in the order they arrived, so
+//!   its history is not read and written by two answers at once
+//! - at most `sessions.max_concurrent_chats` requests run in total (0 for
+//!   no limit)
+//! - sessions waiting for a slot take turns, so one sending many requests
+//!   does not hold up the others
+//!
+//! Requests without a session ID are each scheduled on their own.
+use std::collections::{HashMap, HashSet, VecDeque};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+/// Whom a request is scheduled for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Session(String),
+    /// A request without a session, numbered by the scheduler
+    Request(u64),
+}
+
+#[derive(Debug)]
+enum Command {
+    Acquire { session: Option<String>, grant: oneshot::Sender<Slot> },
+    Release(Key),
+}
+
+/// Permission to run one request; dropping it lets the next one run.
+#[derive(Debug)]
+pub(crate) struct Slot {
+    key: Key,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Release(self.key.clone()));
+    }
+}
+
+/// Hands out [`Slot`]s; cloning it shares the same scheduler.
+#[derive(Debug, Clone)]
+pub(crate) struct ChatScheduler {
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl ChatScheduler {
+    /// Starts the scheduler task, running at most `limit` requests at once
+    /// (0 for no limit). It stops once the scheduler and its slots are dropped.
+    pub(crate) fn spawn(limit: usize) -> Self {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run(State::new(limit), commands.downgrade(), receiver));
+        Self { commands }
+    }
+
+    /// Waits for a slot for a request of `session`.
+    ///
+    /// A request dropped while it waits gives up its place.
+    pub(crate) async fn acquire(&self, session: Option<&str>) -> Slot {
+        let (grant, granted) = oneshot::channel();
+        let session = session.map(str::to_string);
+        self.commands
+            .send(Command::Acquire { session, grant })
+            .expect("the chat scheduler runs while the scheduler exists");
+        granted.await.expect("the chat scheduler answers every request")
+    }
+}
+
+async fn run(
+    mut state: State,
+    commands: mpsc::WeakUnboundedSender<Command>,
+    mut receiver: mpsc::UnboundedReceiver<Command>,
+) {
+    while let Some(command) = receiver.recv().await {
+        match command {
+            Command::Acquire { session, grant } => state.enqueue(session, grant),
+            Command::Release(key) => state.release(&key),
+        }
+        // A slot holds a sender, so one exists while there is anything to schedule
+        let Some(commands) = commands.upgrade() else {
+            break;
+        };
+        for (key, grant) in state.schedule() {
+            // A refused slot is dropped, releasing it again
+            let _ = grant.send(Slot { key, commands: commands.clone() });
+        }
+    }
+}
+
+/// What the scheduler task knows; only it touches this.
+#[derive(Debug, Default)]
+struct State {
+    /// Most running at once, 0 for no limit
+    limit: usize,
+    last_request: u64,
+    running: HashSet<Key>,
+    waiting: HashMap<Key, VecDeque<oneshot::Sender<Slot>>>,
+    /// Keys with waiting requests and none running, next to run first
+    turns: VecDeque<Key>,
+}
+
+impl State {
+    fn new(limit: usize) -> Self {
+        Self { limit, ..Self::default() }
+    }
+
+    fn enqueue(&mut self, session: Option<String>, grant: oneshot::Sender<Slot>) {
+        let key = match session {
+            Some(session) => Key::Session(session),
+            None => {
+                self.last_request += 1;
+                Key::Request(self.last_request)
+            }
+        };
+        let waiting = self.waiting.entry(key.clone()).or_default();
+        waiting.push_back(grant);
+        if waiting.len() == 1 && !self.running.contains(&key) {
+            self.turns.push_back(key);
+        }
+    }
+
+    fn release(&mut self, key: &Key) {
+        if self.running.remove(key) && self.waiting.contains_key(key) {
+            self.turns.push_back(key.clone());
+        }
+    }
+
+    /// Starts requests while there is room, each from the next session in turn.
+    fn schedule(&mut self) -> Vec<(Key, oneshot::Sender<Slot>)> {
+        let mut started = Vec::new();
+        while self.limit == 0 || self.running.len() < self.limit {
+            let Some(key) = self.turns.pop_front() else {
+                break;
+            };
+            let Some(waiting) = self.waiting.get_mut(&key) else {
+                continue;
+            };
+            // Requests dropped while they waited
+            while waiting.front().is_some_and(|grant| grant.is_closed()) {
+                waiting.pop_front();
+            }
+            let Some(grant) = waiting.pop_front() else {
+                self.waiting.remove(&key);
+                continue;
+            };
+            if waiting.is_empty() {
+                self.waiting.remove(&key);
+            }
+            debug!("Starting a request for {:?}; {} running", key, self.running.len() + 1);
+            self.running.insert(key.clone());
+            started.push((key, grant));
+        }
+        started
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn granted(slot: &mut oneshot::Receiver<Slot>) -> Option<Slot> {
+        tokio::time::timeout(Duration::from_millis(50), slot).await.ok().map(|slot| slot.unwrap())
+    }
+
+    /// Queues a request through the scheduler's channel, returning where its slot arrives.
+    fn request(scheduler: &ChatScheduler, session: Option<&str>) -> oneshot::Receiver<Slot> {
+        let (grant, granted) = oneshot::channel();
+        let session = session.map(str::to_string);
+        scheduler.commands.send(Command::Acquire { session, grant }).unwrap();
+        granted
+    }
+
+    #[tokio::test]
+    async fn test_one_request_per_session() {
+        let scheduler = ChatScheduler::spawn(0);
+        let first = scheduler.acquire(Some("a")).await;
+        let mut second = request(&scheduler, Some("a"));
+        let other = scheduler.acquire(Some("b")).await;
+        let anonymous = (scheduler.acquire(None).await, scheduler.acquire(None).await);
+        assert!(granted(&mut second).await.is_none());
+
+        drop(first);
+        assert!(granted(&mut second).await.is_some());
+        drop((other, anonymous));
+    }
+
+    #[tokio::test]
+    async fn test_sessions_take_turns() {
+        let scheduler = ChatScheduler::spawn(1);
+        let running = scheduler.acquire(Some("busy")).await;
+        let mut busy: Vec<_> = (0..3).map(|_| request(&scheduler, Some("busy"))).collect();
+        let mut abandoned = request(&scheduler, Some("quiet"));
+        abandoned.close();
+        let mut quiet = request(&scheduler, Some("quiet"));
+
+        drop(running);
+        let next = granted(&mut quiet).await.expect("the waiting session goes next");
+        assert!(granted(&mut busy[0]).await.is_none());
+        drop(next);
+        let next = granted(&mut busy[0]).await.expect("then the busy session again");
+        assert!(granted(&mut busy[1]).await.is_none());
+        drop(next);
+        assert!(granted(&mut busy[1]).await.is_some());
+    }
+}
@@ -0,0 +1,302 @@
+//! Retries and circuit breaking for remote services.
+//!
+//! A backend that hiccups (Ollama restarting, a dropped connection to
+//! Qdrant) should cost one retry, not a hung request, and a backend that is
+//! down should fail requests immediately instead of making each one wait
+//! for its own timeout. [`CircuitBreaker`] does both for the provider and
+//! store wrappers:
+//!
+//! - transient failures are retried with jittered exponential backoff
+//!   (`resilience.retry`);
+//! - after `resilience.failure_threshold` consecutive failures the circuit
+//!   opens, and calls fail at once with [`CircuitOpen`];
+//! - while open, the service is probed in the background every
+//!   `resilience.probe_interval_secs` and the circuit closes as soon as a
+//!   probe succeeds. Without a probe, or once `resilience.open_secs` have
+//!   passed, calls are let through again to test the service.
+
+use crate::config::{ResilienceConfig, RetryPolicy};
+use futures::future::BoxFuture;
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// Returned instead of calling a service whose circuit is open.
+#[derive(Debug, Clone, Error)]
+#[error("{service} is unavailable after repeated failures; retrying in {}s", retry_in.as_secs().max(1))]
+pub struct CircuitOpen {
+    /// Service the circuit protects
+    pub service: String,
+    /// Time until calls are let through again, unless a probe succeeds first
+    pub retry_in: Duration,
+}
+
+/// Checks whether a service has recovered.
+pub type Probe = Arc<dyn Fn() -> BoxFuture<'static, bool> + Send + Sync>;
+
+/// State of a circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls fail immediately
+    Open,
+    /// The open period is over; the next call tests the service
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct State {
+    failures: u32,
+    open_until: Option<Instant>,
+    probing: bool,
+}
+
+/// Retry and circuit breaker state of one service.
+pub struct CircuitBreaker {
+    service: String,
+    config: ResilienceConfig,
+    probe: Option<Probe>,
+    state: Arc<Mutex<State>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(service: impl Into<String>, config: &ResilienceConfig) -> Self {
+        Self {
+            service: service.into(),
+            config: config.clone(),
+            probe: None,
+            state: Arc::new(Mutex::new(State {
+                failures: 0,
+                open_until: None,
+                probing: false,
+            })),
+        }
+    }
+
+    /// Probes the service in the background while the circuit is open.
+    pub fn with_probe(mut self, probe: Probe) -> Self {
+        self.probe = Some(probe);
+        self
+    }
+
+    /// The retry policy for calls through this breaker.
+    pub fn retry(&self) -> RetryPolicy {
+        self.config.retry
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match self.state.lock().unwrap().open_until {
+            None => CircuitState::Closed,
+            Some(until) if Instant::now() < until => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Fails if the circuit is open.
+    pub fn check(&self) -> Result<(), CircuitOpen> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        match self.state.lock().unwrap().open_until {
+            Some(until) if Instant::now() < until => Err(CircuitOpen {
+                service: self.service.clone(),
+                retry_in: until - Instant::now(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records a successful call, closing the circuit.
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.open_until.take().is_some() {
+            info!("{} is available again", self.service);
+        }
+        state.failures = 0;
+    }
+
+    /// Records a failed call, opening the circuit after too many in a row.
+    pub fn record_failure(&self) {
+        if !self.config.enabled {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.failures = state.failures.saturating_add(1);
+        if state.failures < self.config.failure_threshold.max(1) {
+            return;
+        }
+
+        if state.open_until.is_none() {
+            warn!("{} failed {} times in a row; failing fast until it recovers", self.service, state.failures);
+        }
+        state.open_until = Some(Instant::now() + Duration::from_secs(self.config.open_secs));
+        if !state.probing {
+            if let (Some(probe), Ok(runtime)) = (&self.probe, tokio::runtime::Handle::try_current()) {
+                state.probing = true;
+                runtime.spawn(probe_until_recovered(
+                    self.service.clone(),
+                    Arc::clone(probe),
+                    Arc::clone(&self.state),
+                    Duration::from_secs(self.config.probe_interval_secs.max(1)),
+                ));
+            }
+        }
+    }
+
+    /// Runs `operation`, retrying failures for which `is_transient` holds
+    /// while the circuit stays closed.
+    ///
+    /// Only transient failures count against the circuit.
+    pub async fn call<T, E, F, Fut>(&self, is_transient: impl Fn(&E) -> bool, mut operation: F) -> Result<T, E>
+    where
+        E: From<CircuitOpen>,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let retry = self.retry();
+        let mut attempt = 0;
+        loop {
+            self.check()?;
+            match operation().await {
+                Ok(value) => {
+                    self.record_success();
+                    return Ok(value);
+                }
+                Err(e) if is_transient(&e) => {
+                    self.record_failure();
+                    if attempt >= retry.max_retries || self.check().is_err() {
+                        return Err(e);
+                    }
+                    tokio::time::sleep(backoff(&retry, attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Probes until the service answers, then closes the circuit.
+async fn probe_until_recovered(service: String, probe: Probe, state: Arc<Mutex<State>>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        if state.lock().unwrap().open_until.is_none() {
+            break;
+        }
+        if probe().await {
+            info!("{} is available again", service);
+            let mut state = state.lock().unwrap();
+            state.open_until = None;
+            state.failures = 0;
+            break;
+        }
+    }
+    state.lock().unwrap().probing = false;
+}
+
+/// Delay before retry number `retry` (counting from 0): the policy's
+/// backoff doubled per retry, with up to half of it randomly taken off so
+/// clients that failed together do not retry together.
+pub fn backoff(policy: &RetryPolicy, retry: u32) -> Duration {
+    let delay = policy.backoff_ms.saturating_mul(1 << retry.min(16));
+    let jitter = RandomState::new().build_hasher().finish() % (delay / 2 + 1);
+    Duration::from_millis(delay - jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn config(failure_threshold: u32) -> ResilienceConfig {
+        ResilienceConfig {
+            retry: RetryPolicy { max_retries: 1, backoff_ms: 1 },
+            failure_threshold,
+            ..ResilienceConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff_is_jittered_exponential() {
+        let policy = RetryPolicy { max_retries: 3, backoff_ms: 100 };
+        for _ in 0..20 {
+            let first = backoff(&policy, 0).as_millis();
+            let third = backoff(&policy, 2).as_millis();
+            assert!((50..=100).contains(&first), "{}", first);
+            assert!((200..=400).contains(&third), "{}", third);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let breaker = CircuitBreaker::new("test", &config(5));
+        let calls = AtomicU32::new(0);
+
+        let result: Result<u32, String> = breaker
+            .call(|_| true, || async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err("connection refused".to_string()),
+                    n => Ok(n),
+                }
+            })
+            .await;
+        assert_eq!(result, Ok(1));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // Permanent errors are not retried
+        let calls = AtomicU32::new(0);
+        let result: Result<(), String> = breaker
+            .call(|_| false, || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err("bad request".to_string())
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_opens_after_repeated_failures_and_probe_closes() {
+        let healthy = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let probe_health = Arc::clone(&healthy);
+        let mut config = config(2);
+        config.probe_interval_secs = 1;
+        let breaker = CircuitBreaker::new("Ollama", &config)
+            .with_probe(Arc::new(move || {
+                let healthy = probe_health.load(Ordering::SeqCst);
+                Box::pin(async move { healthy })
+            }));
+
+        let failing = || async { Err::<(), String>("timed out".to_string()) };
+        assert!(breaker.call(|_| true, failing).await.is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let open = breaker.check().unwrap_err();
+        assert_eq!(open.service, "Ollama");
+        let calls = AtomicU32::new(0);
+        let result: Result<(), String> = breaker
+            .call(|_| true, || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .await;
+        assert!(result.unwrap_err().contains("Ollama is unavailable"));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        healthy.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    impl From<CircuitOpen> for String {
+        fn from(open: CircuitOpen) -> Self {
+            open.to_string()
+        }
+    }
+}
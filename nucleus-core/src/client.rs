@@ -0,0 +1,526 @@
+//! Blocking client for the server socket (a named pipe on Windows).
+//!
+//! Used by the CLI and the language bindings; every call opens a new
+//! connection, sends one request, and reads the response stream. [`Pacer`]
+//! slows rendering of the stream down to a readable rate.
+
+use crate::rag::{CollectionInfo, IndexProgress};
+use crate::server::{
+    ChunkType, IndexStats, JobInfo, Request, RequestType, SearchHit, StreamChunk, Timeout, VersionMismatch, WarmingUp, SOCKET_PATH,
+};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Failed to connect to {path}. Is the server running?")]
+    Connect {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Failed to talk to the server: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid message: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    Server(String),
+
+    /// The server stopped the request at its time limit.
+    #[error("{0}")]
+    Timeout(Timeout),
+
+    /// The server speaks another protocol version.
+    #[error("{0}")]
+    Incompatible(VersionMismatch),
+
+    #[error("Server closed the connection without a response")]
+    Closed,
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+/// Blocking connection to the server's IPC endpoint.
+#[cfg(unix)]
+type Connection = std::os::unix::net::UnixStream;
+
+#[cfg(windows)]
+type Connection = std::fs::File;
+
+/// How long to wait for a free pipe instance while the server is busy accepting.
+#[cfg(windows)]
+const PIPE_BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Client for a running nucleus server.
+#[derive(Debug, Clone)]
+pub struct AiClient {
+    socket_path: PathBuf,
+    on_warming_up: Option<fn(&WarmingUp)>,
+    on_progress: Option<fn(u64, &IndexProgress)>,
+}
+
+impl Default for AiClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AiClient {
+    /// Creates a client for the default [`SOCKET_PATH`].
+    pub fn new() -> Self {
+        Self {
+            socket_path: PathBuf::from(SOCKET_PATH),
+            on_warming_up: None,
+            on_progress: None,
+        }
+    }
+
+    pub fn with_socket_path(mut self, socket_path: impl Into<PathBuf>) -> Self {
+        self.socket_path = socket_path.into();
+        self
+    }
+
+    /// Calls `on_warming_up` with the progress the server reports while a
+    /// request waits for the model or knowledge base to start.
+    pub fn with_warming_up(mut self, on_warming_up: fn(&WarmingUp)) -> Self {
+        self.on_warming_up = Some(on_warming_up);
+        self
+    }
+
+    /// Calls `on_progress` with the job ID and progress the server reports
+    /// while an index request runs.
+    pub fn with_progress(mut self, on_progress: fn(u64, &IndexProgress)) -> Self {
+        self.on_progress = Some(on_progress);
+        self
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Sends a request and streams partial chunks to `on_chunk`.
+    ///
+    /// Progress while the server is starting goes to the
+    /// [`with_warming_up`](Self::with_warming_up) callback instead, and
+    /// indexing progress to the [`with_progress`](Self::with_progress) one.
+    ///
+    /// # Returns
+    ///
+    /// The final `done` chunk (content, response ID, truncation flag).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClientError::Server`] with the server's message if the
+    /// request failed, [`ClientError::Timeout`] if it ran past its time limit,
+    /// or [`ClientError::Incompatible`] if the server runs an incompatible version.
+    pub fn send<F>(&self, request: &Request, mut on_chunk: F) -> Result<StreamChunk>
+    where
+        F: FnMut(&str),
+    {
+        let mut stream = self.connect()?;
+
+        let json = serde_json::to_string(request)?;
+        stream.write_all(json.as_bytes())?;
+        stream.write_all(b"\n")?;
+        stream.flush()?;
+
+        let reader = BufReader::new(stream);
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let chunk: StreamChunk = serde_json::from_str(&line)?;
+            match chunk.chunk_type {
+                ChunkType::Chunk => match (&chunk.warming_up, &chunk.progress) {
+                    (Some(warming_up), _) => {
+                        if let Some(on_warming_up) = self.on_warming_up {
+                            on_warming_up(warming_up);
+                        }
+                    }
+                    (None, Some(progress)) => {
+                        if let (Some(on_progress), Some(job)) = (self.on_progress, chunk.job) {
+                            on_progress(job, progress);
+                        }
+                    }
+                    (None, None) => on_chunk(&chunk.content),
+                },
+                ChunkType::Done => return Ok(chunk),
+                ChunkType::Error => {
+                    return Err(match (chunk.timeout, chunk.incompatible) {
+                        (Some(timeout), _) => ClientError::Timeout(timeout),
+                        (None, Some(mismatch)) => ClientError::Incompatible(mismatch),
+                        (None, None) => ClientError::Server(
+                            chunk.error.unwrap_or_else(|| "Unknown server error".to_string()),
+                        ),
+                    })
+                }
+            }
+        }
+
+        Err(ClientError::Closed)
+    }
+
+    /// Asks a question, streaming the answer to `on_chunk`, and returns the complete answer.
+    pub fn chat<F>(&self, prompt: &str, pwd: Option<&str>, on_chunk: F) -> Result<String>
+    where
+        F: FnMut(&str),
+    {
+        let mut request = Request::new(RequestType::Chat, prompt);
+        if let Some(pwd) = pwd {
+            request = request.with_pwd(pwd);
+        }
+        self.send(&request, on_chunk).map(|done| done.content)
+    }
+
+    /// Answers `prompt` from `context` without searching the knowledge base,
+    /// streaming the answer to `on_chunk`; `context` is typically built from
+    /// [`search`](Self::search) results.
+    pub fn generate<F>(&self, prompt: &str, context: &str, on_chunk: F) -> Result<String>
+    where
+        F: FnMut(&str),
+    {
+        let request = Request::new(RequestType::Generate, prompt).with_context(context);
+        self.send(&request, on_chunk).map(|done| done.content)
+    }
+
+    /// Searches the knowledge base without asking the LLM.
+    pub fn search(&self, query: &str, limit: Option<usize>) -> Result<Vec<SearchHit>> {
+        let mut request = Request::new(RequestType::Search, query);
+        if let Some(limit) = limit {
+            request = request.with_limit(limit);
+        }
+        let done = self.send(&request, |_| {})?;
+        Ok(serde_json::from_str(&done.content)?)
+    }
+
+    /// Indexes a directory (absolute, or relative to the current directory) into the knowledge base.
+    pub fn index(&self, dir: impl AsRef<Path>) -> Result<String> {
+        let dir = std::path::absolute(dir.as_ref())?;
+        let dir = dir.to_string_lossy();
+        let request = Request::new(RequestType::Index, dir.as_ref()).with_pwd(dir.as_ref());
+        self.send(&request, |_| {}).map(|done| done.content)
+    }
+
+    /// Starts indexing a directory in the background, returning the job ID
+    /// to pass to [`job_status`](Self::job_status) or [`cancel_job`](Self::cancel_job).
+    pub fn index_detached(&self, dir: impl AsRef<Path>) -> Result<u64> {
+        let dir = std::path::absolute(dir.as_ref())?;
+        let dir = dir.to_string_lossy();
+        let request = Request::new(RequestType::Index, dir.as_ref()).with_pwd(dir.as_ref()).with_detach(true);
+        let done = self.send(&request, |_| {})?;
+        done.job.ok_or_else(|| ClientError::Server("The server did not return a job ID".to_string()))
+    }
+
+    /// Fetches a web page and indexes its readable text into the knowledge base.
+    pub fn index_url(&self, url: &str) -> Result<String> {
+        self.send(&Request::new(RequestType::IndexUrl, url), |_| {}).map(|done| done.content)
+    }
+
+    /// Returns statistics of the active knowledge base collection.
+    pub fn stats(&self) -> Result<IndexStats> {
+        let done = self.send(&Request::new(RequestType::Stats, ""), |_| {})?;
+        Ok(serde_json::from_str(&done.content)?)
+    }
+
+    /// Lists the jobs running on the server, such as directory indexing.
+    pub fn jobs(&self) -> Result<Vec<JobInfo>> {
+        let done = self.send(&Request::new(RequestType::JobsList, ""), |_| {})?;
+        Ok(serde_json::from_str(&done.content)?)
+    }
+
+    /// Lists the named knowledge base collections, `default` first.
+    pub fn collections(&self) -> Result<Vec<CollectionInfo>> {
+        let done = self.send(&Request::new(RequestType::CollectionList, ""), |_| {})?;
+        Ok(serde_json::from_str(&done.content)?)
+    }
+
+    /// Progress of job `id`, or how it ended if it finished recently.
+    pub fn job_status(&self, id: u64) -> Result<JobInfo> {
+        let done = self.send(&Request::new(RequestType::JobsStatus, id.to_string()), |_| {})?;
+        Ok(serde_json::from_str(&done.content)?)
+    }
+
+    /// Asks job `id` to stop; it keeps the work it finished.
+    pub fn cancel_job(&self, id: u64) -> Result<String> {
+        self.send(&Request::new(RequestType::JobsCancel, id.to_string()), |_| {}).map(|done| done.content)
+    }
+
+    #[cfg(unix)]
+    fn connect(&self) -> Result<Connection> {
+        Connection::connect(&self.socket_path).map_err(|source| self.connect_error(source))
+    }
+
+    /// Opens the named pipe, retrying while all of its instances are busy.
+    #[cfg(windows)]
+    fn connect(&self) -> Result<Connection> {
+        const ERROR_PIPE_BUSY: i32 = 231;
+
+        let deadline = std::time::Instant::now() + PIPE_BUSY_TIMEOUT;
+        loop {
+            match std::fs::OpenOptions::new().read(true).write(true).open(&self.socket_path) {
+                Ok(pipe) => return Ok(pipe),
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) && std::time::Instant::now() < deadline => {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                }
+                Err(source) => return Err(self.connect_error(source)),
+            }
+        }
+    }
+
+    fn connect_error(&self, source: io::Error) -> ClientError {
+        ClientError::Connect {
+            path: self.socket_path.display().to_string(),
+            source,
+        }
+    }
+}
+
+/// Text is handed on in slices of this many frames per second.
+const PACER_FRAMES_PER_SEC: u32 = 60;
+
+/// Renders streamed text at a readable pace.
+///
+/// Fast local models produce text faster than it can be read; wrapping the
+/// chunk callback passed to [`AiClient::send`] in a pacer hands text on at
+/// no more than `chars_per_sec`, sleeping as needed. Pauses in the stream
+/// are not made up for with a burst afterwards. Setting the flag from
+/// [`Pacer::skip_handle`] (e.g. when the user presses a key) renders the rest
+/// immediately.
+pub struct Pacer<F> {
+    chars_per_sec: u32,
+    on_text: F,
+    /// When the next slice is due
+    next_due: Option<Instant>,
+    skip: Arc<AtomicBool>,
+}
+
+impl<F: FnMut(&str)> Pacer<F> {
+    /// Creates a pacer handing text to `on_text`; a rate of 0 disables pacing.
+    pub fn new(chars_per_sec: u32, on_text: F) -> Self {
+        Self {
+            chars_per_sec,
+            on_text,
+            next_due: None,
+            skip: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Flag that, once set, makes the pacer hand on text without waiting.
+    pub fn skip_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.skip)
+    }
+
+    /// Hands `text` on, blocking until it is due.
+    pub fn push(&mut self, text: &str) {
+        let slice_chars = (self.chars_per_sec / PACER_FRAMES_PER_SEC).max(1) as usize;
+        let mut rest = text;
+
+        while !rest.is_empty() {
+            if self.chars_per_sec == 0 || self.skip.load(Ordering::Relaxed) {
+                (self.on_text)(rest);
+                return;
+            }
+
+            let now = Instant::now();
+            match self.next_due {
+                Some(due) if due > now => std::thread::sleep(due - now),
+                // Behind schedule because the stream paused; don't burst to catch up
+                _ => self.next_due = Some(now),
+            }
+
+            let end = rest.char_indices().nth(slice_chars).map_or(rest.len(), |(i, _)| i);
+            let (slice, remaining) = rest.split_at(end);
+            (self.on_text)(slice);
+            rest = remaining;
+
+            let slice_time = Duration::from_secs_f64(slice.chars().count() as f64 / self.chars_per_sec as f64);
+            self.next_due = self.next_due.map(|due| due + slice_time);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::os::unix::net::UnixListener;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_streams_chunks_until_done() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("nucleus.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let request: Request = serde_json::from_str(&line).unwrap();
+
+            let mut stream = &stream;
+            for chunk in [StreamChunk::chunk("Hel"), StreamChunk::chunk("lo"), StreamChunk::done("Hello")] {
+                writeln!(stream, "{}", serde_json::to_string(&chunk).unwrap()).unwrap();
+            }
+            request
+        });
+
+        let mut streamed = String::new();
+        let answer = AiClient::new()
+            .with_socket_path(&socket)
+            .chat("hi", Some("/src"), |chunk| streamed.push_str(chunk))
+            .unwrap();
+
+        assert_eq!(answer, "Hello");
+        assert_eq!(streamed, "Hello");
+        let request = server.join().unwrap();
+        assert_eq!(request.request_type, RequestType::Chat);
+        assert_eq!(request.pwd.as_deref(), Some("/src"));
+        assert_eq!(request.client, Some(crate::server::ClientVersion::current()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_skips_warming_up_progress() {
+        use crate::server::{Component, WarmingUp};
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("nucleus.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+
+            let mut stream = &stream;
+            let warming_up = WarmingUp { component: Component::KnowledgeBase, elapsed_secs: 3 };
+            for chunk in [StreamChunk::warming_up(warming_up), StreamChunk::chunk("Hi"), StreamChunk::done("Hi")] {
+                writeln!(stream, "{}", serde_json::to_string(&chunk).unwrap()).unwrap();
+            }
+        });
+
+        static WARMING_UP: AtomicBool = AtomicBool::new(false);
+        let mut streamed = Vec::new();
+        let done = AiClient::new()
+            .with_socket_path(&socket)
+            .with_warming_up(|warming_up| {
+                assert_eq!(warming_up.component, Component::KnowledgeBase);
+                WARMING_UP.store(true, Ordering::SeqCst);
+            })
+            .send(&Request::new(RequestType::Chat, "hi"), |chunk| streamed.push(chunk.to_string()))
+            .unwrap();
+
+        assert_eq!(done.content, "Hi");
+        assert_eq!(streamed, ["Hi"]);
+        assert!(WARMING_UP.load(Ordering::SeqCst));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_server_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("nucleus.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            writeln!(stream, "{}", serde_json::to_string(&StreamChunk::error("boom")).unwrap()).unwrap();
+        });
+
+        let error = AiClient::new().with_socket_path(&socket).stats().unwrap_err();
+        assert!(matches!(error, ClientError::Server(message) if message == "boom"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_server_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("nucleus.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let chunk = StreamChunk::timed_out(Timeout {
+                operation: crate::config::OperationClass::Index,
+                limit_secs: 600,
+            });
+            writeln!(stream, "{}", serde_json::to_string(&chunk).unwrap()).unwrap();
+        });
+
+        let error = AiClient::new().with_socket_path(&socket).index("/tmp").unwrap_err();
+        assert!(matches!(error, ClientError::Timeout(Timeout { limit_secs: 600, .. })));
+        assert!(error.to_string().contains("`timeouts.index_secs`"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_incompatible_server() {
+        use crate::server::{ClientVersion, PROTOCOL_VERSION};
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("nucleus.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let request: Request = serde_json::from_str(&line).unwrap();
+            let chunk = StreamChunk::incompatible(VersionMismatch {
+                client: request.client.unwrap(),
+                server: ClientVersion { version: "9.0.0".to_string(), protocol: PROTOCOL_VERSION + 1 },
+            });
+            writeln!(stream, "{}", serde_json::to_string(&chunk).unwrap()).unwrap();
+        });
+
+        let error = AiClient::new().with_socket_path(&socket).stats().unwrap_err();
+        assert!(matches!(&error, ClientError::Incompatible(mismatch) if mismatch.server.version == "9.0.0"));
+        assert!(error.to_string().contains("Upgrade the client"), "{}", error);
+    }
+
+    #[test]
+    fn test_pacer_limits_rate() {
+        let mut rendered = String::new();
+        let started = Instant::now();
+        let mut pacer = Pacer::new(400, |text: &str| rendered.push_str(text));
+        for _ in 0..10 {
+            pacer.push("abcdefghij");
+        }
+        drop(pacer);
+
+        assert_eq!(rendered.len(), 100);
+        assert!(started.elapsed() >= Duration::from_millis(200), "took {:?}", started.elapsed());
+    }
+
+    #[test]
+    fn test_pacer_skip_and_disabled() {
+        let text = "é".repeat(10_000);
+
+        let mut rendered = String::new();
+        let mut pacer = Pacer::new(10, |chunk: &str| rendered.push_str(chunk));
+        pacer.skip_handle().store(true, Ordering::Relaxed);
+        let started = Instant::now();
+        pacer.push(&text);
+        drop(pacer);
+        assert_eq!(rendered, text);
+
+        let mut unpaced = String::new();
+        Pacer::new(0, |chunk: &str| unpaced.push_str(chunk)).push(&text);
+        assert_eq!(unpaced, text);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,444 @@
+//! Man pages and `--help` output of the commands on PATH.
+//!
+//! Documentation is harvested for every executable on the server's PATH and
+//! indexed into a dedicated collection, one source (`command:<name>`) per
+//! command. Suggestions and explanations of terminal commands look up the
+//! documentation of the commands they involve, so flags come from the
+//! installed version rather than from the model's memory.
+//!
+//! Refreshes are incremental: a fingerprint (path, size, modification time)
+//! of each executable is kept in `commands.state_path`, and only commands
+//! whose fingerprint changed are harvested again.
+
+use crate::config::CommandDocsConfig;
+use crate::rag::RagEngine;
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// Prefix of the `source` metadata of command documentation.
+pub const SOURCE_PREFIX: &str = "command:";
+
+/// Commands that run the rest of the line as another command.
+const WRAPPERS: &[&str] = &["sudo", "doas", "env", "nice", "nohup", "time", "timeout", "xargs", "watch", "exec"];
+
+/// An executable found on PATH.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Executable {
+    pub name: String,
+    pub path: PathBuf,
+    /// Changes when the executable is replaced or updated
+    pub fingerprint: String,
+}
+
+/// Where a command's documentation came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocKind {
+    Man,
+    Help,
+}
+
+impl DocKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Man => "man",
+            Self::Help => "help",
+        }
+    }
+}
+
+/// Documentation of one command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandDoc {
+    pub name: String,
+    pub kind: DocKind,
+    pub text: String,
+}
+
+impl CommandDoc {
+    /// Source recorded with the command's documents.
+    pub fn source(&self) -> String {
+        source(&self.name)
+    }
+}
+
+/// Source recorded with the documents of command `name`.
+pub fn source(name: &str) -> String {
+    format!("{}{}", SOURCE_PREFIX, name)
+}
+
+/// Indexed commands and their fingerprints, persisted between refreshes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexState {
+    pub commands: BTreeMap<String, String>,
+}
+
+impl IndexState {
+    /// Loads the state, or returns an empty one if the file does not exist.
+    pub async fn load(path: &Path) -> Result<Self> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Invalid command index state: {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Work needed to bring the index up to date.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RefreshPlan<'a> {
+    /// New or changed commands to harvest
+    pub changed: Vec<&'a Executable>,
+    /// Indexed commands no longer on PATH
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+/// Compares the executables on PATH with what was indexed.
+pub fn plan_refresh<'a>(state: &IndexState, executables: &'a [Executable]) -> RefreshPlan<'a> {
+    let mut plan = RefreshPlan::default();
+    for executable in executables {
+        match state.commands.get(&executable.name) {
+            Some(fingerprint) if *fingerprint == executable.fingerprint => plan.unchanged += 1,
+            _ => plan.changed.push(executable),
+        }
+    }
+
+    let on_path: HashSet<&str> = executables.iter().map(|e| e.name.as_str()).collect();
+    plan.removed = state
+        .commands
+        .keys()
+        .filter(|name| !on_path.contains(name.as_str()))
+        .cloned()
+        .collect();
+    plan
+}
+
+/// Lists the executables in the directories of `path_var` (formatted like
+/// `PATH`), sorted by name.
+///
+/// Like the shell, the first directory providing a name wins.
+pub fn executables_on_path(path_var: &OsStr, exclude: &[String]) -> Vec<Executable> {
+    let mut seen = HashSet::new();
+    let mut executables = Vec::new();
+
+    for dir in std::env::split_paths(path_var) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') || exclude.contains(&name) || seen.contains(&name) {
+                continue;
+            }
+            // Follows symlinks, which many PATH entries are
+            let Ok(metadata) = std::fs::metadata(entry.path()) else {
+                continue;
+            };
+            if !metadata.is_file() || !is_executable(&metadata) {
+                continue;
+            }
+
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_secs());
+            let path = entry.path();
+            executables.push(Executable {
+                fingerprint: format!("{}:{}:{}", path.display(), metadata.len(), modified),
+                name: name.clone(),
+                path,
+            });
+            seen.insert(name);
+        }
+    }
+
+    executables.sort_by(|a, b| a.name.cmp(&b.name));
+    executables
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    true
+}
+
+/// Reads the man page of `executable`, falling back to its `--help` output.
+///
+/// Returns `None` if neither is available.
+pub async fn harvest(executable: &Executable, config: &CommandDocsConfig) -> Option<CommandDoc> {
+    let timeout = Duration::from_millis(config.help_timeout_ms);
+
+    let mut man = Command::new("man");
+    man.arg(&executable.name).env("MANPAGER", "cat").env("PAGER", "cat").env("MANWIDTH", "100");
+    let (kind, text) = match run(man, timeout).await {
+        Some((true, text)) => (DocKind::Man, text),
+        _ => {
+            let mut help = Command::new(&executable.path);
+            help.arg("--help");
+            match run(help, timeout).await {
+                Some((success, text)) if success || text.to_lowercase().contains("usage") => (DocKind::Help, text),
+                _ => return None,
+            }
+        }
+    };
+
+    let text = clean(&text);
+    if text.trim().is_empty() {
+        return None;
+    }
+    Some(CommandDoc {
+        name: executable.name.clone(),
+        kind,
+        text: text.chars().take(config.max_chars).collect(),
+    })
+}
+
+/// Runs `command` without input, returning whether it succeeded and its
+/// combined output, or `None` if it could not start or timed out.
+async fn run(mut command: Command, timeout: Duration) -> Option<(bool, String)> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let output = tokio::time::timeout(timeout, command.output()).await.ok()?.ok()?;
+
+    // Many commands print their help to stderr
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Some((output.status.success(), text))
+}
+
+/// Removes backspace overstrikes (bold and underline in man output) and ANSI
+/// escape sequences.
+pub fn clean(text: &str) -> String {
+    let mut cleaned = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // "X\bX" is bold X, "_\bX" is underlined X
+            '\u{8}' => {
+                cleaned.pop();
+            }
+            '\u{1b}' => {
+                if chars.peek() == Some(&'[') {
+                    chars.next();
+                    // Parameters up to the final letter
+                    for c in chars.by_ref() {
+                        if c.is_ascii_alphabetic() {
+                            break;
+                        }
+                    }
+                }
+            }
+            c => cleaned.push(c),
+        }
+    }
+    cleaned
+}
+
+/// Names of the commands run by a shell command line, in order.
+///
+/// Looks at the first word of each pipeline and list element, skipping
+/// variable assignments and wrappers such as `sudo`.
+pub fn command_names(command_line: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for segment in command_line.split(['|', ';', '&', '\n', '(', ')', '`']) {
+        let name = segment
+            .split_whitespace()
+            // Skips assignments and the options and durations of wrappers
+            .filter(|word| !word.contains('=') && !word.starts_with('-') && !word.starts_with(|c: char| c.is_ascii_digit()))
+            .find(|word| !WRAPPERS.contains(word));
+        // `/usr/bin/git` is documented as `git`
+        if let Some(name) = name.and_then(|name| name.rsplit('/').next()) {
+            if !name.is_empty() && !names.iter().any(|existing| existing == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Result of a refresh.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RefreshSummary {
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    /// Commands without a man page or usable `--help`
+    pub undocumented: usize,
+}
+
+/// Brings the command collection up to date with the server's PATH.
+///
+/// `on_progress` receives a line for every command indexed.
+///
+/// # Errors
+///
+/// Returns an error if the state file cannot be read or written, or if
+/// embedding or storing documentation fails. Commands indexed before the
+/// failure are recorded, so the next refresh continues where this one stopped.
+pub async fn refresh<F>(rag: &RagEngine, config: &CommandDocsConfig, mut on_progress: F) -> Result<RefreshSummary>
+where
+    F: FnMut(&str) + Send,
+{
+    let state_path = PathBuf::from(&config.state_path);
+    let mut state = IndexState::load(&state_path).await?;
+
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let executables = executables_on_path(&path_var, &config.exclude);
+    let plan = plan_refresh(&state, &executables);
+    info!(
+        changed = plan.changed.len(),
+        removed = plan.removed.len(),
+        unchanged = plan.unchanged,
+        "Refreshing command documentation"
+    );
+
+    let mut summary = RefreshSummary {
+        unchanged: plan.unchanged,
+        ..RefreshSummary::default()
+    };
+
+    for name in &plan.removed {
+        rag.remove_command_doc(name).await?;
+        state.commands.remove(name);
+        summary.removed += 1;
+    }
+
+    // Collected first: mapping inside the stream makes the future not `Send`
+    let harvests: Vec<_> = plan
+        .changed
+        .iter()
+        .map(|executable| async move { (*executable, harvest(executable, config).await) })
+        .collect();
+    let mut harvests = futures::stream::iter(harvests).buffer_unordered(config.concurrency.max(1));
+
+    let result = async {
+        while let Some((executable, doc)) = harvests.next().await {
+            match doc {
+                Some(doc) => {
+                    let chunks = rag.index_command_doc(&doc).await?;
+                    on_progress(&format!("✓ {} ({}, {} chunks)\n", doc.name, doc.kind.as_str(), chunks));
+                    summary.indexed += 1;
+                }
+                None => {
+                    debug!(command = %executable.name, "No documentation found");
+                    // Documentation indexed for an earlier version is stale
+                    if state.commands.contains_key(&executable.name) {
+                        rag.remove_command_doc(&executable.name).await?;
+                    }
+                    summary.undocumented += 1;
+                }
+            }
+            // Undocumented commands are recorded too, so they are not run again
+            state.commands.insert(executable.name.clone(), executable.fingerprint.clone());
+        }
+        anyhow::Ok(())
+    }
+    .await;
+
+    if let Err(e) = state.save(&state_path).await {
+        warn!("Failed to save command index state: {:#}", e);
+    }
+    result?;
+
+    info!(?summary, "Command documentation refreshed");
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn executable(name: &str, fingerprint: &str) -> Executable {
+        Executable {
+            name: name.to_string(),
+            path: PathBuf::from("/usr/bin").join(name),
+            fingerprint: fingerprint.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_plan_refresh() {
+        let mut state = IndexState::default();
+        state.commands.insert("git".to_string(), "v1".to_string());
+        state.commands.insert("ls".to_string(), "v1".to_string());
+        state.commands.insert("gone".to_string(), "v1".to_string());
+
+        let executables = vec![executable("git", "v2"), executable("ls", "v1"), executable("rg", "v1")];
+        let plan = plan_refresh(&state, &executables);
+
+        let changed: Vec<&str> = plan.changed.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(changed, vec!["git", "rg"]);
+        assert_eq!(plan.removed, vec!["gone"]);
+        assert_eq!(plan.unchanged, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_executables_on_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let create = |dir: &Path, name: &str, mode: u32| {
+            let path = dir.join(name);
+            std::fs::write(&path, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+        create(first.path(), "tool", 0o755);
+        create(first.path(), "notes.txt", 0o644);
+        create(first.path(), "reboot", 0o755);
+        create(second.path(), "tool", 0o755);
+        create(second.path(), "other", 0o755);
+
+        let path_var = std::env::join_paths([first.path(), second.path()]).unwrap();
+        let executables = executables_on_path(&path_var, &["reboot".to_string()]);
+
+        let names: Vec<&str> = executables.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["other", "tool"]);
+        assert!(executables[1].path.starts_with(first.path()));
+    }
+
+    #[test]
+    fn test_clean() {
+        assert_eq!(clean("N\u{8}NA\u{8}AM\u{8}ME\u{8}E\n_\u{8}f_\u{8}i_\u{8}l_\u{8}e"), "NAME\nfile");
+        assert_eq!(clean("\u{1b}[1m-v\u{1b}[0m verbose"), "-v verbose");
+    }
+
+    #[test]
+    fn test_command_names() {
+        assert_eq!(
+            command_names("FOO=1 sudo /usr/bin/git log --oneline | grep -v fixup && cargo test; (cd x && ls)"),
+            vec!["git", "grep", "cargo", "cd", "ls"]
+        );
+        assert_eq!(command_names("timeout 5 make -j4"), vec!["make"]);
+        assert!(command_names("  ").is_empty());
+    }
+}
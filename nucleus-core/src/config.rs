@@ -1,9 +1,13 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
 use thiserror::Error;
 
+use crate::egress::FindingKind;
 use crate::models::EmbeddingModel;
+use nucleus_plugin::Capability;
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -26,155 +30,2295 @@ pub struct Config {
     pub rag: RagConfig,
     pub storage: StorageConfig,
     pub personalization: PersonalizationConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub orchestration: OrchestrationConfig,
+    /// Shared team knowledge base, searched alongside the local one
+    #[serde(default)]
+    pub team: Option<TeamConfig>,
+    /// Alternate settings tried on a share of chat requests
+    #[serde(default)]
+    pub experiments: Vec<ExperimentConfig>,
+    #[serde(default)]
+    pub attachments: AttachmentConfig,
+    #[serde(default)]
+    pub suggest: SuggestConfig,
+    /// Secret and personal data checks before prompts are sent to a remote LLM
+    #[serde(default)]
+    pub egress: EgressConfig,
+    /// gRPC API served alongside the socket protocol (requires the `grpc` feature)
+    #[serde(default)]
+    pub grpc: GrpcConfig,
+    #[serde(default)]
+    pub log_analysis: LogAnalysisConfig,
+    #[serde(default)]
+    pub expression: ExpressionConfig,
+    /// Man pages and `--help` output of the commands on PATH
+    #[serde(default)]
+    pub commands: CommandDocsConfig,
+    /// Shell, editor, and tool configuration files (opt-in)
+    #[serde(default)]
+    pub dotfiles: DotfilesConfig,
+    /// Summaries of past conversations
+    #[serde(default)]
+    pub conversations: ConversationsConfig,
+    /// How clients render responses
+    #[serde(default)]
+    pub display: DisplayConfig,
+    /// Live index updates while files change
+    #[serde(default)]
+    pub watch: WatchConfig,
+    /// Retries and circuit breaking for the LLM provider and Qdrant
+    #[serde(default)]
+    pub resilience: ResilienceConfig,
+    /// Time limits for chat, embedding, indexing, and plugin execution
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+    /// Checks for newer nucleus releases (opt-in)
+    #[serde(default)]
+    pub updates: UpdateConfig,
+    /// Write-through log of streamed responses (opt-in)
+    #[serde(default)]
+    pub response_log: ResponseLogConfig,
+    /// Recent raw requests, kept for `nucleus replay` (opt-in)
+    #[serde(default)]
+    pub request_log: RequestLogConfig,
+    /// Conversation history kept per client session
+    #[serde(default)]
+    pub sessions: SessionsConfig,
+    /// Stream of workspace activity for external automation
+    #[serde(default)]
+    pub events: EventsConfig,
+    /// How long answers are
+    #[serde(default)]
+    pub verbosity: VerbosityConfig,
+    /// Plugins discovered in a directory
+    #[serde(default)]
+    pub plugins: PluginsConfig,
+
+    #[serde(skip)]
+    pub permission: Permission,
+}
+
+/// Permissions granted to the AI.
+///
+/// **Note**: A permission granted here does not mean it will automatically perform the actions.
+/// However, if false, the functionality will not exist to begin with.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Permission {
+    /// Read directories and files
+    pub read: bool,
+    /// Write to files
+    pub write: bool,
+    /// Run system commands
+    pub command: bool,
+}
+
+impl Default for Permission {
+    fn default() -> Self {
+        Self {
+            read: true,
+            write: true,
+            command: true,
+        }
+    }
+}
+
+/// Configuration for the AI model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmConfig {
+    pub model: String,
+    pub base_url: String,
+    pub temperature: f64,
+    /// Tokens the model takes in; conversations outgrowing it have their
+    /// older turns summarized
+    pub context_length: usize,
+    /// Tokens of `context_length` kept free for the answer when retrieved
+    /// context is fitted into the prompt (a request's `max_tokens` takes
+    /// precedence)
+    #[serde(default = "default_answer_tokens")]
+    pub answer_tokens: usize,
+    /// Backend to use; unset keeps the entry point's default (Ollama for the
+    /// server, mistral.rs for [`crate::ChatManager`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<ProviderKind>,
+    /// API key for the `openai` provider; falls back to `OPENAI_API_KEY`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Retries for the primary provider before falling back
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Providers tried in order when the primary one is unavailable
+    /// (used by [`crate::ChatManager`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<FallbackConfig>,
+    /// Settings for the `llamacpp` provider
+    #[serde(default)]
+    pub llama_cpp: LlamaCppConfig,
+    /// Running the `mistralrs` model in a worker process
+    #[serde(default)]
+    pub worker: WorkerConfig,
+}
+
+/// Model worker settings for the `mistralrs` provider (see
+/// [`crate::provider::WorkerProvider`]).
+///
+/// With a worker, a crash in the model (e.g. a CUDA out-of-memory abort)
+/// ends the worker process rather than the whole application; the worker
+/// is restarted and the conversation state kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Command line of the worker; it must serve
+    /// [`crate::provider::worker::serve`] on its stdin and stdout
+    #[serde(default = "default_worker_command")]
+    pub command: Vec<String>,
+    /// Restarts after consecutive crashes before giving up
+    #[serde(default = "default_worker_max_restarts")]
+    pub max_restarts: u32,
+}
+
+fn default_answer_tokens() -> usize {
+    2048
+}
+
+fn default_worker_command() -> Vec<String> {
+    vec!["nucleus".to_string(), "model-worker".to_string()]
+}
+
+fn default_worker_max_restarts() -> u32 {
+    3
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: default_worker_command(),
+            max_restarts: default_worker_max_restarts(),
+        }
+    }
+}
+
+/// llama.cpp settings (`llm.provider: llamacpp`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlamaCppConfig {
+    /// Layers offloaded to the GPU (0 runs on the CPU)
+    #[serde(default)]
+    pub gpu_layers: u32,
+    /// Prompt tokens processed per step
+    #[serde(default = "default_llama_cpp_batch_size")]
+    pub batch_size: u32,
+    /// Threads used for generation (llama.cpp's default if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<i32>,
+}
+
+fn default_llama_cpp_batch_size() -> u32 {
+    512
+}
+
+impl Default for LlamaCppConfig {
+    fn default() -> Self {
+        Self {
+            gpu_layers: 0,
+            batch_size: default_llama_cpp_batch_size(),
+            threads: None,
+        }
+    }
+}
+
+/// How often to retry a provider that is unavailable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further retry
+    #[serde(default = "default_retry_backoff_ms")]
+    pub backoff_ms: u64,
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            backoff_ms: default_retry_backoff_ms(),
+        }
+    }
+}
+
+/// A fallback provider; unset fields other than `api_key` are taken from `llm`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallbackConfig {
+    pub provider: ProviderKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+impl FallbackConfig {
+    /// Returns `llm` with this fallback's overrides applied.
+    pub fn apply(&self, llm: &LlmConfig) -> LlmConfig {
+        LlmConfig {
+            model: self.model.clone().unwrap_or_else(|| llm.model.clone()),
+            base_url: self.base_url.clone().unwrap_or_else(|| llm.base_url.clone()),
+            provider: Some(self.provider),
+            // Never send the primary's key to another endpoint
+            api_key: self.api_key.clone(),
+            retry: self.retry,
+            fallbacks: Vec::new(),
+            ..llm.clone()
+        }
+    }
+}
+
+/// LLM backend selected by `llm.provider`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// Ollama HTTP API at `base_url`
+    Ollama,
+    /// In-process inference with mistral.rs
+    MistralRs,
+    /// OpenAI-compatible Chat Completions API at `base_url`
+    OpenAi,
+    /// In-process inference with llama.cpp (requires the `llama-cpp` feature)
+    LlamaCpp,
+}
+
+impl LlmConfig {
+    /// Returns true if `base_url` points at this machine.
+    pub fn is_local(&self) -> bool {
+        is_local_url(&self.base_url)
+    }
+}
+
+/// Returns true if the URL's host is a loopback or unspecified address.
+pub(crate) fn is_local_url(url: &str) -> bool {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = without_scheme.split('/').next().unwrap_or_default();
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+
+    let host = match host_port.strip_prefix('[') {
+        // IPv6 literal, e.g. [::1]:11434
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host_port.split(':').next().unwrap_or_default(),
+    };
+
+    matches!(host, "localhost" | "::1" | "0.0.0.0") || host.starts_with("127.")
+}
+
+/// Configuration for RAG processing.
+///
+/// This covers embedding settings and text processing behavior (chunking, indexing).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RagConfig {
+    pub embedding_model: EmbeddingModel,
+    #[serde(default)]
+    pub indexer: IndexerConfig,
+    /// Extra patterns skipped during indexing, added to `indexer.exclude_patterns`
+    /// (so the defaults don't have to be repeated)
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    /// Rerank search results by query term overlap before building context
+    #[serde(default)]
+    pub rerank: bool,
+    /// Combine vector similarity with BM25 keyword matching, so exact
+    /// identifiers and error codes in a query find their chunks
+    #[serde(default)]
+    pub hybrid: bool,
+    /// Reranking (cross-encoder) model that rescores the best candidates
+    /// before the top results are picked, e.g. a Qwen3-Reranker or
+    /// bge-reranker served by the LLM provider
+    #[serde(default)]
+    pub reranker_model: Option<String>,
+    /// Candidates rescored by `reranker_model`
+    #[serde(default = "default_reranker_candidates")]
+    pub reranker_candidates: usize,
+    /// Keep embeddings of indexed chunks on disk (`storage.embedding_cache_path`)
+    /// so unchanged and duplicate chunks are never sent to the model again
+    #[serde(default = "default_cache_embeddings")]
+    pub cache_embeddings: bool,
+    /// Score multipliers for searches across all collections
+    #[serde(default)]
+    pub collection_weights: CollectionWeights,
+    /// How much a file's modification time counts in the ranking of indexed
+    /// files, from 0 (similarity only) to 1
+    #[serde(default)]
+    pub recency_weight: f32,
+    /// Days after which a file counts as half as recent
+    #[serde(default = "default_recency_half_life_days")]
+    pub recency_half_life_days: f32,
+    /// Pick results by maximal marginal relevance, trading relevance (1)
+    /// against covering different content (0), e.g. 0.7; unset keeps the
+    /// plain ranking, which may return several near-identical chunks
+    #[serde(default)]
+    pub mmr_lambda: Option<f32>,
+    /// How retrieved chunks are laid out in the prompt; unset picks what
+    /// suits `llm.model`, see [`ContextFormat::for_model`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_format: Option<ContextFormat>,
+    /// Trust levels of sources and collections, weighting their results and
+    /// shown to the model; the first matching rule applies
+    #[serde(default)]
+    pub source_trust: Vec<SourceTrustRule>,
+    /// Checks for sources that changed since they were indexed
+    #[serde(default)]
+    pub freshness: FreshnessConfig,
+    /// Local ONNX embedding for large indexing runs
+    #[serde(default)]
+    pub bulk_embedding: BulkEmbeddingConfig,
+    /// Reading files mentioned in chat that are not indexed, or changed since
+    #[serde(default)]
+    pub read_through: ReadThroughConfig,
+}
+
+impl RagConfig {
+    /// The configured context format, or the default for `model`.
+    pub fn context_format_for(&self, model: &str) -> ContextFormat {
+        self.context_format.unwrap_or_else(|| ContextFormat::for_model(model))
+    }
+}
+
+/// Layout of the retrieved-context block, see [`crate::rag::format_context`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextFormat {
+    /// `[1] (source) content` entries
+    #[default]
+    Numbered,
+    /// `<doc index="1" source="...">` elements inside `<documents>`
+    Xml,
+    /// A heading with the path and a code fence per chunk
+    Markdown,
+}
+
+impl ContextFormat {
+    /// The format models of this family follow best: XML tags for Claude,
+    /// Markdown for GPT and code models, numbered entries otherwise.
+    pub fn for_model(model: &str) -> Self {
+        let model = model.to_lowercase();
+        // Strip registry or vendor prefixes such as `anthropic/`
+        let name = model.rsplit('/').next().unwrap_or(&model);
+        let is_o_series = name.strip_prefix('o').is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()));
+        if name.starts_with("claude") {
+            Self::Xml
+        } else if name.starts_with("gpt") || is_o_series || ["coder", "codellama", "starcoder", "deepseek", "codestral"].iter().any(|family| name.contains(family)) {
+            Self::Markdown
+        } else {
+            Self::Numbered
+        }
+    }
+}
+
+fn default_reranker_candidates() -> usize {
+    20
+}
+
+fn default_cache_embeddings() -> bool {
+    true
+}
+
+fn default_recency_half_life_days() -> f32 {
+    14.0
+}
+
+/// Score multipliers applied when a query searches every collection.
+///
+/// Results from all collections are merged by weighted score, so a weight
+/// above 1 favors a collection and 0 leaves it out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionWeights {
+    /// Indexed files and added knowledge
+    #[serde(default = "default_collection_weight")]
+    pub knowledge: f32,
+    /// Shared team knowledge base
+    #[serde(default = "default_collection_weight")]
+    pub team: f32,
+    /// Man pages and `--help` output
+    #[serde(default = "default_collection_weight")]
+    pub commands: f32,
+    /// Shell, editor, and tool configuration files
+    #[serde(default = "default_collection_weight")]
+    pub dotfiles: f32,
+    /// Summaries of past conversations
+    #[serde(default = "default_collection_weight")]
+    pub conversations: f32,
+    /// Installed starter packs, also searched with every request; below 1
+    /// so your own files win over general knowledge
+    #[serde(default = "default_starter_weight")]
+    pub starter: f32,
+}
+
+fn default_collection_weight() -> f32 {
+    1.0
+}
+
+fn default_starter_weight() -> f32 {
+    0.8
+}
+
+impl Default for CollectionWeights {
+    fn default() -> Self {
+        Self {
+            knowledge: default_collection_weight(),
+            team: default_collection_weight(),
+            commands: default_collection_weight(),
+            dotfiles: default_collection_weight(),
+            conversations: default_collection_weight(),
+            starter: default_starter_weight(),
+        }
+    }
+}
+
+/// Trust in the sources matching a rule of `rag.source_trust`, see
+/// [`crate::rag::trust`]. A rule with both `source` and `collection` needs
+/// both to match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceTrustRule {
+    /// Pattern for the file path or URL, where `*` matches any characters,
+    /// `?` one, and a leading `~` the home directory, e.g. `https://docs.rs/*`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Collection results come from when searching them all, e.g. `team`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    pub trust: TrustLevel,
+    /// Score multiplier instead of the level's default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f32>,
+}
+
+/// How far a source is trusted when sources disagree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustLevel {
+    /// e.g. random web pages, to be double-checked
+    Low,
+    #[default]
+    Normal,
+    /// e.g. your own notes
+    Trusted,
+    /// Official documentation and specifications
+    Authoritative,
+}
+
+impl TrustLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::Trusted => "trusted",
+            Self::Authoritative => "authoritative",
+        }
+    }
+
+    /// Score multiplier of results at this level, unless a rule sets one.
+    pub fn default_weight(self) -> f32 {
+        match self {
+            Self::Low => 0.8,
+            Self::Normal => 1.0,
+            Self::Trusted => 1.15,
+            Self::Authoritative => 1.3,
+        }
+    }
+}
+
+/// Stale sources in the knowledge base, see [`crate::rag::freshness`].
+///
+/// `nucleus stats` lists them; the server also checks every collection
+/// periodically, logging what it finds or re-indexing it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FreshnessConfig {
+    /// Web pages not fetched again for this many days are stale; 0 never
+    /// expires them
+    #[serde(default = "default_url_max_age_days")]
+    pub url_max_age_days: u64,
+    /// How often the server checks for stale sources; 0 turns the periodic
+    /// check off
+    #[serde(default = "default_freshness_interval_hours")]
+    pub interval_hours: u64,
+    /// Re-index stale sources when the periodic check finds them
+    #[serde(default)]
+    pub auto_refresh: bool,
+}
+
+fn default_url_max_age_days() -> u64 {
+    7
+}
+
+fn default_freshness_interval_hours() -> u64 {
+    24
+}
+
+impl Default for FreshnessConfig {
+    fn default() -> Self {
+        Self {
+            url_max_age_days: default_url_max_age_days(),
+            interval_hours: default_freshness_interval_hours(),
+            auto_refresh: false,
+        }
+    }
+}
+
+/// Embedding large indexing runs with a local ONNX model, see
+/// [`crate::rag`]. Requires the `onnx` feature.
+///
+/// Runs with at least `min_chunks` chunks to embed bypass the provider;
+/// smaller, incremental updates keep using it. The model must be an
+/// export of `rag.embedding_model`, so both produce the same vectors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkEmbeddingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// The ONNX model file
+    #[serde(default)]
+    pub model_path: String,
+    /// The model's `tokenizer.json`
+    #[serde(default)]
+    pub tokenizer_path: String,
+    /// Chunks embedded per run of the model
+    #[serde(default = "default_bulk_batch_size")]
+    pub batch_size: usize,
+    /// Threads per run; 0 uses every core
+    #[serde(default)]
+    pub threads: usize,
+    /// Chunks an indexing run needs to be embedded locally
+    #[serde(default = "default_bulk_min_chunks")]
+    pub min_chunks: usize,
+    /// Tokens a chunk is truncated to
+    #[serde(default = "default_bulk_max_tokens")]
+    pub max_tokens: usize,
+}
+
+fn default_bulk_batch_size() -> usize {
+    256
+}
+
+fn default_bulk_min_chunks() -> usize {
+    2000
+}
+
+fn default_bulk_max_tokens() -> usize {
+    512
+}
+
+impl Default for BulkEmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model_path: String::new(),
+            tokenizer_path: String::new(),
+            batch_size: default_bulk_batch_size(),
+            threads: 0,
+            min_chunks: default_bulk_min_chunks(),
+            max_tokens: default_bulk_max_tokens(),
+        }
+    }
+}
+
+/// Reading files mentioned in a chat message ("look at src/server.rs"),
+/// see [`crate::rag::read_through`].
+///
+/// A mentioned file that is not indexed, or changed since it was, is read
+/// and chunked for the request, so the answer reflects the file as it is.
+/// Its chunks are only kept for that request unless `persist` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadThroughConfig {
+    #[serde(default = "default_read_through_enabled")]
+    pub enabled: bool,
+    /// Files read per message
+    #[serde(default = "default_read_through_max_files")]
+    pub max_files: usize,
+    /// Larger files are left to the index
+    #[serde(default = "default_read_through_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// Chunks of each file added to the context, the most relevant first
+    #[serde(default = "default_read_through_max_chunks")]
+    pub max_chunks: usize,
+    /// Index the files read, outside private sessions
+    #[serde(default)]
+    pub persist: bool,
+}
+
+fn default_read_through_enabled() -> bool {
+    true
+}
+
+fn default_read_through_max_files() -> usize {
+    3
+}
+
+fn default_read_through_max_file_bytes() -> u64 {
+    512 * 1024
+}
+
+fn default_read_through_max_chunks() -> usize {
+    8
+}
+
+impl Default for ReadThroughConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_read_through_enabled(),
+            max_files: default_read_through_max_files(),
+            max_file_bytes: default_read_through_max_file_bytes(),
+            max_chunks: default_read_through_max_chunks(),
+            persist: false,
+        }
+    }
+}
+
+impl FreshnessConfig {
+    /// Age after which web pages are stale, `None` if they never are.
+    pub fn url_max_age(&self) -> Option<std::time::Duration> {
+        (self.url_max_age_days > 0).then(|| std::time::Duration::from_secs(self.url_max_age_days * 24 * 3600))
+    }
+}
+
+/// Configuration for file indexing behavior.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexerConfig {
+    /// File extensions to index (e.g., ["rs", "go", "py"])
+    /// Empty list (default) means index all readable text files
+    #[serde(default)]
+    pub extensions: Vec<String>,
+
+    /// Patterns to exclude - skips directories/files containing these strings
+    /// Default excludes: build artifacts, version control, package managers, temp files
+    #[serde(default = "default_exclude_patterns")]
+    pub exclude_patterns: Vec<String>,
+
+    /// Size of text chunks in bytes for splitting documents
+    pub chunk_size: usize,
+
+    /// Overlap between consecutive chunks in bytes
+    pub chunk_overlap: usize,
+
+    /// Skip files ignored by `.gitignore` and `.ignore` files, the global
+    /// gitignore, and `.git/info/exclude`
+    #[serde(default = "default_respect_gitignore")]
+    pub respect_gitignore: bool,
+
+    /// Batches of chunks embedded at the same time
+    #[serde(default = "default_index_concurrency")]
+    pub concurrency: usize,
+
+    /// How files are split into chunks, by file type
+    #[serde(default)]
+    pub chunking: ChunkingConfig,
+}
+
+/// Chunking strategies by file type, see [`crate::rag::Chunkers`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChunkingConfig {
+    /// Strategy for files not listed in `by_extension` and for text that
+    /// isn't a file, such as notes and web pages
+    #[serde(default)]
+    pub default: ChunkStrategy,
+    /// Strategies by file extension without the dot, e.g. `md: markdown`.
+    /// With the `tree-sitter` feature, source files in supported languages
+    /// use `syntax` unless listed.
+    #[serde(default)]
+    pub by_extension: HashMap<String, ChunkStrategy>,
+}
+
+/// How text is split into chunks, see [`crate::rag::chunking`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStrategy {
+    /// Windows of `chunk_size` bytes overlapping by `chunk_overlap`
+    #[default]
+    Fixed,
+    /// Whole sentences packed up to `chunk_size`
+    Sentence,
+    /// Paragraphs, then lines, then sentences, then words, whichever fit
+    Recursive,
+    /// Syntax nodes of source files (`tree-sitter` feature), `fixed` otherwise
+    Syntax,
+    /// Markdown sections under their headings
+    Markdown,
+}
+
+fn default_exclude_patterns() -> Vec<String> {
+    crate::patterns::default_exclude_patterns()
+}
+
+fn default_respect_gitignore() -> bool {
+    true
+}
+
+fn default_index_concurrency() -> usize {
+    4
+}
+
+fn default_top_k() -> usize {
+    5
+}
+
+fn default_packs_path() -> String {
+    "./data/packs".to_string()
+}
+
+fn default_feedback_path() -> String {
+    "./data/feedback.jsonl".to_string()
+}
+
+fn default_crash_reports_path() -> String {
+    "./data/crashes".to_string()
+}
+
+fn default_embedding_cache_path() -> String {
+    "./data/embedding_cache".to_string()
+}
+
+fn default_collections_path() -> String {
+    "./data/collections.json".to_string()
+}
+
+fn default_snapshots_path() -> String {
+    "./data/snapshots".to_string()
+}
+
+fn default_starter_packs_url() -> String {
+    "https://github.com/Cooksey99/llm-workspace/releases/download/starter-packs".to_string()
+}
+
+impl Default for IndexerConfig {
+    fn default() -> Self {
+        Self {
+            extensions: Vec::new(), // Empty = index all text files
+            exclude_patterns: default_exclude_patterns(),
+            chunk_size: 512,
+            chunk_overlap: 50,
+            respect_gitignore: default_respect_gitignore(),
+            concurrency: default_index_concurrency(),
+            chunking: ChunkingConfig::default(),
+        }
+    }
+}
+
+/// Vector database storage mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum StorageMode {
+    /// Embedded storage - runs in-process with zero setup (default)
+    Embedded { path: String },
+    /// gRPC storage - connect to external vector database server
+    Grpc { url: String },
+    /// Single-file SQLite database with sqlite-vec - a lighter embedded option
+    /// (requires the `sqlite` feature)
+    Sqlite { path: String },
+    /// Postgres with the pgvector extension, e.g. a database a team already
+    /// runs (requires the `postgres` feature)
+    Postgres { url: String },
+    /// A Chroma server, e.g. to search collections built by Python tooling
+    Chroma {
+        url: String,
+        #[serde(default = "default_chroma_tenant")]
+        tenant: String,
+        #[serde(default = "default_chroma_database")]
+        database: String,
+        /// Token for servers with token authentication
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+}
+
+fn default_chroma_tenant() -> String {
+    "default_tenant".to_string()
+}
+
+fn default_chroma_database() -> String {
+    "default_database".to_string()
+}
+
+impl Default for StorageMode {
+    fn default() -> Self {
+        Self::Embedded {
+            path: "./data/nucleus_vectordb".to_string(),
+        }
+    }
+}
+
+impl Default for RagConfig {
+    fn default() -> Self {
+        let embedding_model = EmbeddingModel::default();
+
+        let indexer = IndexerConfig {
+            extensions: Vec::new(),
+            exclude_patterns: default_exclude_patterns(),
+            chunk_size: embedding_model.embedding_dim,
+            chunk_overlap: 50,
+            respect_gitignore: default_respect_gitignore(),
+            concurrency: default_index_concurrency(),
+            chunking: ChunkingConfig::default(),
+        };
+
+        Self {
+            embedding_model,
+            indexer,
+            exclude_patterns: Vec::new(),
+            rerank: false,
+            hybrid: false,
+            reranker_model: None,
+            reranker_candidates: default_reranker_candidates(),
+            cache_embeddings: default_cache_embeddings(),
+            collection_weights: CollectionWeights::default(),
+            recency_weight: 0.0,
+            recency_half_life_days: default_recency_half_life_days(),
+            mmr_lambda: None,
+            context_format: None,
+            source_trust: Vec::new(),
+            freshness: FreshnessConfig::default(),
+            bulk_embedding: BulkEmbeddingConfig::default(),
+            read_through: ReadThroughConfig::default(),
+        }
+    }
+}
+
+/// Storage configuration for all persistence.
+///
+/// This includes chat history, tool state, and vector database storage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageConfig {
+    pub chat_history_path: String,
+    pub tool_state_path: String,
+    /// Vector database storage mode
+    #[serde(default)]
+    pub storage_mode: StorageMode,
+    /// Vector database configuration (collection name, etc.)
+    #[serde(default)]
+    pub vector_db: VectorDbConfig,
+    /// Number of results to return from vector similarity searches
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    /// Directory where manifests of imported context packs are kept
+    #[serde(default = "default_packs_path")]
+    pub packs_path: String,
+    /// JSONL file where rated responses are stored
+    #[serde(default = "default_feedback_path")]
+    pub feedback_path: String,
+    /// Directory where the daemon writes crash reports
+    #[serde(default = "default_crash_reports_path")]
+    pub crash_reports_path: String,
+    /// Directory of the embedding cache, one file per embedding model
+    #[serde(default = "default_embedding_cache_path")]
+    pub embedding_cache_path: String,
+    /// JSON file listing the named knowledge base collections and the active one
+    #[serde(default = "default_collections_path")]
+    pub collections_path: String,
+    /// Directory of knowledge base snapshots, one file per snapshot
+    #[serde(default = "default_snapshots_path")]
+    pub snapshots_path: String,
+    /// Where `nucleus pack install` downloads starter packs from (a URL or a
+    /// local directory holding `<name>.pack` files)
+    #[serde(default = "default_starter_packs_url")]
+    pub starter_packs_url: String,
+    /// In-memory approximate nearest neighbour index for the embedded stores
+    #[serde(default)]
+    pub hnsw: HnswConfig,
+    /// Where cleared and removed documents are kept until purged
+    #[serde(default)]
+    pub trash: TrashConfig,
+}
+
+/// Trash for documents removed from the knowledge base.
+///
+/// Clearing the knowledge base, removing a source, and deleting a collection
+/// move the documents here, embeddings included, so `nucleus trash restore`
+/// brings them back without re-indexing. Entries older than
+/// `retention_days` are purged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashConfig {
+    #[serde(default = "default_trash_enabled")]
+    pub enabled: bool,
+    /// Directory of the trash, one file per entry
+    #[serde(default = "default_trash_path")]
+    pub path: String,
+    #[serde(default = "default_trash_retention_days")]
+    pub retention_days: u64,
+}
+
+fn default_trash_enabled() -> bool {
+    true
+}
+
+fn default_trash_path() -> String {
+    "./data/trash".to_string()
+}
+
+fn default_trash_retention_days() -> u64 {
+    7
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_trash_enabled(),
+            path: default_trash_path(),
+            retention_days: default_trash_retention_days(),
+        }
+    }
+}
+
+/// HNSW graph kept in memory in front of the `embedded` and `sqlite` stores,
+/// which otherwise compare every query against every stored embedding.
+///
+/// Off by default: the graph holds a copy of every embedding and is built
+/// when the store opens. Server stores index on their own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HnswConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Links per node (twice as many on the bottom layer)
+    #[serde(default = "default_hnsw_m")]
+    pub m: usize,
+    /// Candidates examined while inserting
+    #[serde(default = "default_hnsw_ef_construction")]
+    pub ef_construction: usize,
+    /// Candidates examined while searching, at least `top_k`
+    #[serde(default = "default_hnsw_ef_search")]
+    pub ef_search: usize,
+    /// How the graph keeps embeddings in memory
+    #[serde(default)]
+    pub quantization: Quantization,
+    /// Candidates per requested result rescored against the full-precision
+    /// embeddings when `quantization` is not `none`
+    #[serde(default = "default_hnsw_rescore")]
+    pub rescore: usize,
+}
+
+/// Encoding of the embeddings held by the HNSW graph.
+///
+/// Quantized graphs keep the full-precision embeddings in a temporary file
+/// and read them back only to rescore the best candidates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quantization {
+    /// 32-bit floats
+    #[default]
+    None,
+    /// One signed byte per dimension and a scale per embedding (4x smaller)
+    Int8,
+    /// One sign bit per dimension (32x smaller)
+    Binary,
+}
+
+fn default_hnsw_m() -> usize {
+    16
+}
+
+fn default_hnsw_ef_construction() -> usize {
+    200
+}
+
+fn default_hnsw_ef_search() -> usize {
+    64
+}
+
+fn default_hnsw_rescore() -> usize {
+    4
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            m: default_hnsw_m(),
+            ef_construction: default_hnsw_ef_construction(),
+            ef_search: default_hnsw_ef_search(),
+            quantization: Quantization::default(),
+            rescore: default_hnsw_rescore(),
+        }
+    }
+}
+
+/// Vector database configuration (collection/index name, etc.).
+///
+/// Provider-agnostic configuration that works with any vector DB backend
+/// (Qdrant, LanceDB, etc.).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorDbConfig {
+    /// Collection/index name for storing vectors
+    pub collection_name: String,
+    /// How embeddings are compared; use the metric the embedding model was trained for
+    #[serde(default)]
+    pub distance: DistanceMetric,
+    /// Distance metric by collection name as the store sees it (e.g.
+    /// `nucleus_kb_commands`, or `nucleus_kb-docs` for collection `docs`),
+    /// overriding `distance`
+    #[serde(default)]
+    pub distances: HashMap<String, DistanceMetric>,
+}
+
+impl VectorDbConfig {
+    /// The configuration of collection `collection_name`, with the same metrics.
+    pub fn named(&self, collection_name: String) -> Self {
+        Self { collection_name, ..self.clone() }
+    }
+
+    /// The distance metric of this collection.
+    pub fn metric(&self) -> DistanceMetric {
+        self.distances.get(&self.collection_name).copied().unwrap_or(self.distance)
+    }
+}
+
+/// How a collection compares embeddings.
+///
+/// Every metric is reported as a similarity score, higher for closer
+/// embeddings, that equals cosine similarity for unit-length embeddings.
+/// Qdrant and Chroma collections keep the metric they were created with;
+/// the embedded stores apply it per search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceMetric {
+    /// Cosine similarity, ignoring the embeddings' length
+    #[default]
+    Cosine,
+    /// Dot product, for models trained to put relevance in the embeddings' length
+    Dot,
+    /// Euclidean distance, scored as 1 - distance² / 2
+    #[serde(alias = "l2")]
+    Euclidean,
+}
+
+impl DistanceMetric {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cosine => "cosine",
+            Self::Dot => "dot",
+            Self::Euclidean => "euclidean",
+        }
+    }
+}
+
+/// Completion notifications for long-running operations (indexing, agent tasks).
+///
+/// Nothing is sent unless a webhook URL or command is configured, or desktop
+/// notifications are enabled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    /// URL that receives a JSON POST when an operation finishes
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// Shell command to run when an operation finishes.
+    /// Details are passed as `NUCLEUS_*` environment variables.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Only notify for operations that took at least this many seconds
+    #[serde(default)]
+    pub min_duration_secs: u64,
+    /// Show a desktop notification when a long generation or task finishes
+    #[serde(default)]
+    pub desktop: bool,
+    /// Only show desktop notifications for tasks that took at least this many seconds
+    #[serde(default = "default_desktop_min_duration_secs")]
+    pub desktop_min_duration_secs: u64,
+}
+
+fn default_desktop_min_duration_secs() -> u64 {
+    30
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            command: None,
+            min_duration_secs: 0,
+            desktop: false,
+            desktop_min_duration_secs: default_desktop_min_duration_secs(),
+        }
+    }
+}
+
+/// Configuration for multi-agent review loops (`debate` requests).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrchestrationConfig {
+    /// Maximum number of draft/review rounds before the final answer is merged
+    #[serde(default = "default_max_rounds")]
+    pub max_rounds: usize,
+    /// Persona that drafts and revises the answer
+    #[serde(default = "PersonaConfig::implementer")]
+    pub implementer: PersonaConfig,
+    /// Persona that critiques each draft
+    #[serde(default = "PersonaConfig::reviewer")]
+    pub reviewer: PersonaConfig,
+}
+
+/// A named role in a multi-agent conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersonaConfig {
+    pub name: String,
+    pub system_prompt: String,
+    /// Model override (defaults to `llm.model`)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Temperature override (defaults to `llm.temperature`)
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// Length of this persona's answers, with the instructions and token
+    /// budget of `verbosity` (unset leaves it to the system prompt)
+    #[serde(default)]
+    pub verbosity: Option<Verbosity>,
+}
+
+impl PersonaConfig {
+    pub fn implementer() -> Self {
+        Self {
+            name: "implementer".to_string(),
+            system_prompt: "You are a senior software engineer. Produce complete, correct solutions \
+                and revise them carefully when given review feedback."
+                .to_string(),
+            model: None,
+            temperature: None,
+            verbosity: None,
+        }
+    }
+
+    pub fn reviewer() -> Self {
+        Self {
+            name: "reviewer".to_string(),
+            system_prompt: "You are a meticulous code reviewer. Look for bugs, missing edge cases, \
+                and unclear reasoning. Be specific and concise."
+                .to_string(),
+            model: None,
+            temperature: Some(0.2),
+            verbosity: None,
+        }
+    }
+}
+
+fn default_max_rounds() -> usize {
+    3
+}
+
+impl Default for OrchestrationConfig {
+    fn default() -> Self {
+        Self {
+            max_rounds: default_max_rounds(),
+            implementer: PersonaConfig::implementer(),
+            reviewer: PersonaConfig::reviewer(),
+        }
+    }
+}
+
+/// Limits for files and snippets attached to chat requests.
+///
+/// Attachments are added to the prompt for a single request and never indexed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachmentConfig {
+    /// Largest accepted attachment, in bytes
+    #[serde(default = "default_attachment_max_bytes")]
+    pub max_bytes: usize,
+    /// Attachment text added to the prompt across all attachments, in bytes.
+    /// Larger attachments are chunked and only the chunks most relevant to the
+    /// question are kept.
+    #[serde(default = "default_attachment_context_bytes")]
+    pub max_context_bytes: usize,
+    /// Chunk size used when attachments exceed `max_context_bytes`
+    #[serde(default = "default_attachment_chunk_size")]
+    pub chunk_size: usize,
+}
+
+fn default_attachment_max_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_attachment_context_bytes() -> usize {
+    16 * 1024
+}
+
+fn default_attachment_chunk_size() -> usize {
+    2000
+}
+
+impl Default for AttachmentConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: default_attachment_max_bytes(),
+            max_context_bytes: default_attachment_context_bytes(),
+            chunk_size: default_attachment_chunk_size(),
+        }
+    }
+}
+
+/// Low-latency settings for inline command suggestions (`suggest` requests).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuggestConfig {
+    /// Smaller, faster model for suggestions (defaults to `llm.model`)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Hard time budget per suggestion, in milliseconds
+    #[serde(default = "default_suggest_max_time_ms")]
+    pub max_time_ms: u64,
+    #[serde(default = "default_suggest_max_tokens")]
+    pub max_tokens: u32,
+    #[serde(default = "default_suggest_temperature")]
+    pub temperature: f64,
+    /// Add knowledge base context, cached per working directory
+    #[serde(default)]
+    pub use_rag: bool,
+    /// Most frequent shell commands embedded when a session starts, so
+    /// `use_rag` retrieval for input starting one needs no embedding (0 for none)
+    #[serde(default = "default_suggest_standby_commands")]
+    pub standby_commands: usize,
+    /// Top-level files of the working directory embedded likewise (0 for none)
+    #[serde(default = "default_suggest_standby_files")]
+    pub standby_files: usize,
+    /// Shell history the frequent commands are read from (defaults to
+    /// `$HISTFILE`, or the history file of the session's shell)
+    #[serde(default)]
+    pub history_path: Option<String>,
+    /// Checks for dangerous suggested commands
+    #[serde(default)]
+    pub guardrails: GuardrailConfig,
+}
+
+fn default_suggest_max_time_ms() -> u64 {
+    300
+}
+
+fn default_suggest_max_tokens() -> u32 {
+    48
+}
+
+fn default_suggest_temperature() -> f64 {
+    0.2
+}
+
+fn default_suggest_standby_commands() -> usize {
+    50
+}
+
+fn default_suggest_standby_files() -> usize {
+    50
+}
+
+impl Default for SuggestConfig {
+    fn default() -> Self {
+        Self {
+            model: None,
+            max_time_ms: default_suggest_max_time_ms(),
+            max_tokens: default_suggest_max_tokens(),
+            temperature: default_suggest_temperature(),
+            use_rag: false,
+            standby_commands: default_suggest_standby_commands(),
+            standby_files: default_suggest_standby_files(),
+            history_path: None,
+            guardrails: GuardrailConfig::default(),
+        }
+    }
+}
+
+/// How strictly suggested commands matching a guardrail rule are flagged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuardrailProfile {
+    /// Show suggestions unchecked
+    Off,
+    /// Only warn, even for rules asking for confirmation
+    Relaxed,
+    /// Act as each rule says
+    #[default]
+    Standard,
+    /// Ask for confirmation on every match
+    Strict,
+}
+
+/// What a client does with a suggested command matching a guardrail rule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuardrailAction {
+    /// Show the warning next to the suggestion
+    #[default]
+    Warn,
+    /// Show the warning and require an extra keystroke to accept
+    Confirm,
+}
+
+/// A pattern to flag in suggested commands, next to the built-in ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardrailRule {
+    /// Name shown with the warning and used in `disable`
+    pub name: String,
+    /// Regular expression matched against the command
+    pub pattern: String,
+    pub message: String,
+    #[serde(default)]
+    pub action: GuardrailAction,
+}
+
+/// Guardrails for suggested commands (see [`crate::guardrails`]).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GuardrailConfig {
+    #[serde(default)]
+    pub profile: GuardrailProfile,
+    #[serde(default)]
+    pub rules: Vec<GuardrailRule>,
+    /// Built-in rules to turn off, by name
+    #[serde(default)]
+    pub disable: Vec<String>,
+}
+
+/// What to do when a prompt for a remote LLM contains secrets or personal data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EgressMode {
+    /// Send without checking
+    #[default]
+    Off,
+    /// Refuse to send
+    Block,
+    /// Ask the user (library callers); clients that cannot ask are blocked
+    Confirm,
+}
+
+/// Pre-send classifier settings (see [`crate::egress`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EgressConfig {
+    #[serde(default)]
+    pub mode: EgressMode,
+    /// Shannon entropy (bits per character) above which long tokens are treated as secrets
+    #[serde(default = "default_entropy_threshold")]
+    pub entropy_threshold: f64,
+    /// Finding kinds to allow, e.g. `email`
+    #[serde(default)]
+    pub ignore: Vec<FindingKind>,
+}
+
+fn default_entropy_threshold() -> f64 {
+    4.2
+}
+
+impl Default for EgressConfig {
+    fn default() -> Self {
+        Self {
+            mode: EgressMode::default(),
+            entropy_threshold: default_entropy_threshold(),
+            ignore: Vec::new(),
+        }
+    }
+}
+
+/// gRPC API settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrpcConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address to listen on; keep it on loopback unless the network is trusted
+    #[serde(default = "default_grpc_address")]
+    pub address: String,
+}
+
+fn default_grpc_address() -> String {
+    "127.0.0.1:50051".to_string()
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: default_grpc_address(),
+        }
+    }
+}
+
+/// Log analysis settings (`analyze-log` requests, see [`crate::log_analysis`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogAnalysisConfig {
+    /// Characters of log per summarization request
+    #[serde(default = "default_log_chunk_chars")]
+    pub chunk_chars: usize,
+    /// Chunks summarized per log; beyond this, those with the most errors and warnings are kept
+    #[serde(default = "default_log_max_chunks")]
+    pub max_chunks: usize,
+    /// Summarization requests run in parallel
+    #[serde(default = "default_log_concurrency")]
+    pub concurrency: usize,
+    /// Error clusters listed in the report
+    #[serde(default = "default_log_max_clusters")]
+    pub max_clusters: usize,
+}
+
+fn default_log_chunk_chars() -> usize {
+    12000
+}
+
+fn default_log_max_chunks() -> usize {
+    32
+}
+
+fn default_log_concurrency() -> usize {
+    4
+}
+
+fn default_log_max_clusters() -> usize {
+    10
+}
+
+impl Default for LogAnalysisConfig {
+    fn default() -> Self {
+        Self {
+            chunk_chars: default_log_chunk_chars(),
+            max_chunks: default_log_max_chunks(),
+            concurrency: default_log_concurrency(),
+            max_clusters: default_log_max_clusters(),
+        }
+    }
+}
+
+/// Regex and jq generation settings (`generate-expression` requests, see [`crate::expression`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpressionConfig {
+    /// Generations tried before giving up; each retry is told why the last one failed
+    #[serde(default = "default_expression_max_attempts")]
+    pub max_attempts: u32,
+    /// Characters of the sample shown to the LLM (validation always uses the whole sample)
+    #[serde(default = "default_expression_sample_chars")]
+    pub sample_chars: usize,
+}
+
+fn default_expression_max_attempts() -> u32 {
+    3
+}
+
+fn default_expression_sample_chars() -> usize {
+    4000
+}
+
+impl Default for ExpressionConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_expression_max_attempts(),
+            sample_chars: default_expression_sample_chars(),
+        }
+    }
+}
+
+/// Command documentation settings (`index-commands` requests, see [`crate::command_docs`]).
+///
+/// Documentation is kept in its own collection, named after the local one
+/// with a `_commands` suffix.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandDocsConfig {
+    /// JSON file recording which command versions are indexed
+    #[serde(default = "default_commands_state_path")]
+    pub state_path: String,
+    /// How long a command may take to print its `--help`, in milliseconds
+    #[serde(default = "default_commands_help_timeout_ms")]
+    pub help_timeout_ms: u64,
+    /// Characters of documentation kept per command
+    #[serde(default = "default_commands_max_chars")]
+    pub max_chars: usize,
+    /// Commands documented in parallel
+    #[serde(default = "default_commands_concurrency")]
+    pub concurrency: usize,
+    /// Documentation chunks added to a prompt
+    #[serde(default = "default_commands_top_k")]
+    pub top_k: usize,
+    /// Add documentation to suggestions (costs one embedding per suggestion)
+    #[serde(default = "default_commands_suggest")]
+    pub suggest: bool,
+    /// Commands never run with `--help`
+    #[serde(default = "default_commands_exclude")]
+    pub exclude: Vec<String>,
+}
+
+fn default_commands_state_path() -> String {
+    "./data/commands.json".to_string()
+}
+
+fn default_commands_help_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_commands_max_chars() -> usize {
+    20000
+}
+
+fn default_commands_concurrency() -> usize {
+    8
+}
+
+fn default_commands_top_k() -> usize {
+    3
+}
+
+fn default_commands_suggest() -> bool {
+    true
+}
+
+fn default_commands_exclude() -> Vec<String> {
+    ["halt", "poweroff", "reboot", "shutdown", "init", "telinit"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+impl CommandDocsConfig {
+    /// Storage configuration for the command collection, based on the local one.
+    pub fn storage_config(&self, local: &StorageConfig) -> StorageConfig {
+        StorageConfig {
+            vector_db: local.vector_db.named(format!("{}_commands", local.vector_db.collection_name)),
+            ..local.clone()
+        }
+    }
+}
+
+impl Default for CommandDocsConfig {
+    fn default() -> Self {
+        Self {
+            state_path: default_commands_state_path(),
+            help_timeout_ms: default_commands_help_timeout_ms(),
+            max_chars: default_commands_max_chars(),
+            concurrency: default_commands_concurrency(),
+            top_k: default_commands_top_k(),
+            suggest: default_commands_suggest(),
+            exclude: default_commands_exclude(),
+        }
+    }
+}
+
+/// Dotfile settings (`index-dotfiles` requests, see [`crate::dotfiles`]).
+///
+/// Files are kept in their own collection, named after the local one with a
+/// `_dotfiles` suffix.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DotfilesConfig {
+    /// Index the files and add them to prompts
+    #[serde(default)]
+    pub enabled: bool,
+    /// Files and directories to index; `~` is the server's home directory
+    #[serde(default = "default_dotfiles_paths")]
+    pub paths: Vec<String>,
+    /// JSON file recording which file versions are indexed
+    #[serde(default = "default_dotfiles_state_path")]
+    pub state_path: String,
+    /// Larger files are skipped
+    #[serde(default = "default_dotfiles_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// Files indexed per configured directory
+    #[serde(default = "default_dotfiles_max_files")]
+    pub max_files: usize,
+    /// Dotfile chunks added to a prompt
+    #[serde(default = "default_dotfiles_top_k")]
+    pub top_k: usize,
+    /// Add dotfiles to suggestions (costs one embedding per suggestion)
+    #[serde(default = "default_dotfiles_suggest")]
+    pub suggest: bool,
+}
+
+fn default_dotfiles_paths() -> Vec<String> {
+    [
+        "~/.zshrc",
+        "~/.bashrc",
+        "~/.bash_profile",
+        "~/.profile",
+        "~/.aliases",
+        "~/.config/fish/config.fish",
+        "~/.gitconfig",
+        "~/.config/git/config",
+        "~/.vimrc",
+        "~/.config/nvim",
+        "~/.tmux.conf",
+        "~/.inputrc",
+        "~/.config/starship.toml",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+fn default_dotfiles_state_path() -> String {
+    "./data/dotfiles.json".to_string()
+}
+
+fn default_dotfiles_max_file_bytes() -> u64 {
+    256 * 1024
+}
+
+fn default_dotfiles_max_files() -> usize {
+    200
+}
+
+fn default_dotfiles_top_k() -> usize {
+    3
+}
+
+fn default_dotfiles_suggest() -> bool {
+    true
+}
+
+impl DotfilesConfig {
+    /// Storage configuration for the dotfile collection, based on the local one.
+    pub fn storage_config(&self, local: &StorageConfig) -> StorageConfig {
+        StorageConfig {
+            vector_db: local.vector_db.named(format!("{}_dotfiles", local.vector_db.collection_name)),
+            ..local.clone()
+        }
+    }
+}
+
+impl Default for DotfilesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            paths: default_dotfiles_paths(),
+            state_path: default_dotfiles_state_path(),
+            max_file_bytes: default_dotfiles_max_file_bytes(),
+            max_files: default_dotfiles_max_files(),
+            top_k: default_dotfiles_top_k(),
+            suggest: default_dotfiles_suggest(),
+        }
+    }
+}
+
+/// Conversation settings (`remember` requests, see [`crate::conversations`]).
+///
+/// Summaries are kept in their own collection, named after the local one
+/// with a `_conversations` suffix.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationsConfig {
+    /// Summarize and index every chat exchange outside private mode
+    /// (one extra LLM call per response)
+    #[serde(default)]
+    pub auto: bool,
+    /// Model writing the summaries (default: `llm.model`)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Longer transcripts lose their middle before being summarized
+    #[serde(default = "default_conversations_max_transcript_chars")]
+    pub max_transcript_chars: usize,
+    /// Past conversations added to a prompt (0 disables retrieval)
+    #[serde(default = "default_conversations_top_k")]
+    pub top_k: usize,
+}
+
+fn default_conversations_max_transcript_chars() -> usize {
+    12_000
+}
+
+fn default_conversations_top_k() -> usize {
+    2
+}
+
+impl ConversationsConfig {
+    /// Storage configuration for the conversation collection, based on the local one.
+    pub fn storage_config(&self, local: &StorageConfig) -> StorageConfig {
+        StorageConfig {
+            vector_db: local.vector_db.named(format!("{}_conversations", local.vector_db.collection_name)),
+            ..local.clone()
+        }
+    }
+}
+
+impl Default for ConversationsConfig {
+    fn default() -> Self {
+        Self {
+            auto: false,
+            model: None,
+            max_transcript_chars: default_conversations_max_transcript_chars(),
+            top_k: default_conversations_top_k(),
+        }
+    }
+}
+
+/// Client-side rendering settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DisplayConfig {
+    /// Maximum rate streamed responses are rendered at, in characters per
+    /// second (0 renders text as soon as it arrives), see [`crate::client::Pacer`]
+    #[serde(default)]
+    pub chars_per_sec: u32,
+    /// Column streamed responses are wrapped at, counting wide characters
+    /// as two (0 disables wrapping); unset uses `$COLUMNS` when printing to
+    /// a terminal, see [`crate::text::LineWrapper`]
+    #[serde(default)]
+    pub wrap_width: Option<usize>,
+    /// Print markdown tables and JSON blocks as the model wrote them instead
+    /// of drawing them, see [`crate::render::BlockRenderer`]
+    #[serde(default)]
+    pub raw_blocks: bool,
+    /// After an answer with tables or JSON blocks, wait for the number of
+    /// one to save as written; `nucleus ask --save-block` for one answer
+    #[serde(default)]
+    pub save_blocks: bool,
+}
+
+/// Live index updates (see [`crate::server`]).
+///
+/// The server watches every directory it indexes, re-indexing files that
+/// change or appear and removing deleted ones from the knowledge base.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchConfig {
+    /// Watch indexed directories for changes
+    #[serde(default = "default_watch_enabled")]
+    pub enabled: bool,
+    /// Directories indexed and watched from startup, in addition to those
+    /// indexed by requests
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Changes are applied once files have been quiet for this long
+    #[serde(default = "default_watch_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+fn default_watch_enabled() -> bool {
+    true
+}
 
-    #[serde(skip)]
-    pub permission: Permission,
+fn default_watch_debounce_ms() -> u64 {
+    500
 }
 
-/// Permissions granted to the AI.
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_watch_enabled(),
+            paths: Vec::new(),
+            debounce_ms: default_watch_debounce_ms(),
+        }
+    }
+}
+
+/// Retries and circuit breaking for remote services (Ollama and
+/// OpenAI-compatible providers, Qdrant).
 ///
-/// **Note**: A permission granted here does not mean it will automatically perform the actions.
-/// However, if false, the functionality will not exist to begin with.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct Permission {
-    /// Read directories and files
-    pub read: bool,
-    /// Write to files
-    pub write: bool,
-    /// Run system commands
-    pub command: bool,
+/// Transient failures are retried; after `failure_threshold` consecutive
+/// failures calls fail immediately until the service answers again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResilienceConfig {
+    /// Open circuits after repeated failures (retries apply either way)
+    #[serde(default = "default_resilience_enabled")]
+    pub enabled: bool,
+    /// Retries of a failed call, with jittered backoff
+    #[serde(default = "default_resilience_retry")]
+    pub retry: RetryPolicy,
+    /// Consecutive failures that open the circuit
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Seconds calls fail fast before one is let through to test the service
+    #[serde(default = "default_open_secs")]
+    pub open_secs: u64,
+    /// Seconds between background checks of a service whose circuit is open
+    #[serde(default = "default_probe_interval_secs")]
+    pub probe_interval_secs: u64,
 }
 
-impl Default for Permission {
+fn default_resilience_enabled() -> bool {
+    true
+}
+
+fn default_resilience_retry() -> RetryPolicy {
+    RetryPolicy {
+        max_retries: 2,
+        backoff_ms: 250,
+    }
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+fn default_open_secs() -> u64 {
+    30
+}
+
+fn default_probe_interval_secs() -> u64 {
+    5
+}
+
+impl Default for ResilienceConfig {
     fn default() -> Self {
         Self {
-            read: true,
-            write: true,
-            command: true,
+            enabled: default_resilience_enabled(),
+            retry: default_resilience_retry(),
+            failure_threshold: default_failure_threshold(),
+            open_secs: default_open_secs(),
+            probe_interval_secs: default_probe_interval_secs(),
         }
     }
 }
 
-/// Configuration for the AI model
+/// Class of operation a time limit applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationClass {
+    /// Requests that generate text with the LLM
+    Chat,
+    /// Searches and additions to the knowledge base
+    Embed,
+    /// Indexing files, packs, and documentation
+    Index,
+    /// A plugin run as a tool call
+    Plugin,
+}
+
+impl OperationClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Chat => "chat",
+            Self::Embed => "embed",
+            Self::Index => "index",
+            Self::Plugin => "plugin",
+        }
+    }
+}
+
+/// Time limits per class of operation, in seconds; 0 means no limit.
+///
+/// The server stops a request that runs past its limit and answers with a
+/// timeout error naming the limit, so clients never wait on a stuck backend.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LlmConfig {
-    pub model: String,
-    pub base_url: String,
-    pub temperature: f64,
-    pub context_length: usize,
+pub struct TimeoutConfig {
+    /// Chat, edit, debate, and other requests answered by the LLM
+    #[serde(default = "default_chat_timeout_secs")]
+    pub chat_secs: u64,
+    /// Searches and additions to the knowledge base
+    #[serde(default = "default_embed_timeout_secs")]
+    pub embed_secs: u64,
+    /// Indexing directories, packs, command docs, and dotfiles
+    #[serde(default = "default_index_timeout_secs")]
+    pub index_secs: u64,
+    /// A single plugin execution
+    #[serde(default = "default_plugin_timeout_secs")]
+    pub plugin_secs: u64,
 }
 
-/// Configuration for RAG processing.
+fn default_chat_timeout_secs() -> u64 {
+    300
+}
+
+fn default_embed_timeout_secs() -> u64 {
+    60
+}
+
+fn default_index_timeout_secs() -> u64 {
+    3600
+}
+
+fn default_plugin_timeout_secs() -> u64 {
+    60
+}
+
+impl TimeoutConfig {
+    /// The limit for `class`, or `None` if it has none.
+    pub fn limit(&self, class: OperationClass) -> Option<Duration> {
+        let secs = match class {
+            OperationClass::Chat => self.chat_secs,
+            OperationClass::Embed => self.embed_secs,
+            OperationClass::Index => self.index_secs,
+            OperationClass::Plugin => self.plugin_secs,
+        };
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            chat_secs: default_chat_timeout_secs(),
+            embed_secs: default_embed_timeout_secs(),
+            index_secs: default_index_timeout_secs(),
+            plugin_secs: default_plugin_timeout_secs(),
+        }
+    }
+}
+
+/// Checks for newer releases, reported by `stats` and `nucleus doctor`.
 ///
-/// This covers embedding settings and text processing behavior (chunking, indexing).
+/// Off by default, since it contacts the release feed over the network.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RagConfig {
-    pub embedding_model: EmbeddingModel,
+pub struct UpdateConfig {
     #[serde(default)]
-    pub indexer: IndexerConfig,
+    pub check: bool,
+    /// Latest release in the GitHub releases API format (`tag_name`, `html_url`)
+    #[serde(default = "default_update_url")]
+    pub url: String,
+    /// How often the server checks again
+    #[serde(default = "default_update_interval_hours")]
+    pub interval_hours: u64,
 }
 
-/// Configuration for file indexing behavior.
+fn default_update_url() -> String {
+    "https://api.github.com/repos/Cooksey99/llm-workspace/releases/latest".to_string()
+}
+
+fn default_update_interval_hours() -> u64 {
+    24
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            check: false,
+            url: default_update_url(),
+            interval_hours: default_update_interval_hours(),
+        }
+    }
+}
+
+/// Copies every streamed response, with the request that produced it, to a
+/// rotating JSONL file as it is generated.
+///
+/// Responses of private sessions are never logged.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct IndexerConfig {
-    /// File extensions to index (e.g., ["rs", "go", "py"])
-    /// Empty list (default) means index all readable text files
+pub struct ResponseLogConfig {
     #[serde(default)]
-    pub extensions: Vec<String>,
+    pub enabled: bool,
+    #[serde(default = "default_response_log_path")]
+    pub path: String,
+    /// Size at which the file is rotated
+    #[serde(default = "default_response_log_max_bytes")]
+    pub max_bytes: u64,
+    /// Number of rotated files kept (`path.1` is the newest)
+    #[serde(default = "default_response_log_keep")]
+    pub keep: usize,
+}
 
-    /// Patterns to exclude - skips directories/files containing these strings
-    /// Default excludes: build artifacts, version control, package managers, temp files
-    #[serde(default = "default_exclude_patterns")]
-    pub exclude_patterns: Vec<String>,
+fn default_response_log_path() -> String {
+    "./data/responses.jsonl".to_string()
+}
 
-    /// Size of text chunks in bytes for splitting documents
-    pub chunk_size: usize,
+fn default_response_log_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
 
-    /// Overlap between consecutive chunks in bytes
-    pub chunk_overlap: usize,
+fn default_response_log_keep() -> usize {
+    4
 }
 
-fn default_exclude_patterns() -> Vec<String> {
-    crate::patterns::default_exclude_patterns()
+impl Default for ResponseLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_response_log_path(),
+            max_bytes: default_response_log_max_bytes(),
+            keep: default_response_log_keep(),
+        }
+    }
 }
 
-fn default_top_k() -> usize {
-    5
+/// Keeps the most recent chat-like requests as the client sent them, so they
+/// can be replayed against a changed configuration or model.
+///
+/// Requests of private sessions are never logged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestLogConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_request_log_path")]
+    pub path: String,
+    /// Number of recent requests kept
+    #[serde(default = "default_request_log_keep")]
+    pub keep: usize,
 }
 
-impl Default for IndexerConfig {
+fn default_request_log_path() -> String {
+    "./data/requests.jsonl".to_string()
+}
+
+fn default_request_log_keep() -> usize {
+    200
+}
+
+impl Default for RequestLogConfig {
     fn default() -> Self {
         Self {
-            extensions: Vec::new(), // Empty = index all text files
-            exclude_patterns: default_exclude_patterns(),
-            chunk_size: 512,
-            chunk_overlap: 50,
+            enabled: false,
+            path: default_request_log_path(),
+            keep: default_request_log_keep(),
         }
     }
 }
 
-/// Vector database storage mode
+/// Recent exchanges the server keeps for each client session (a request's
+/// `session_id`, e.g. one per terminal) and adds to the session's next chat
+/// prompts, so follow-up questions have their context.
+///
+/// Conversations are also stored on disk under `path`, one JSONL file per
+/// session, so they survive server restarts and can be listed, resumed, and
+/// deleted; private sessions never are. Requests without a session have no
+/// history.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "mode", rename_all = "lowercase")]
-pub enum StorageMode {
-    /// Embedded storage - runs in-process with zero setup (default)
-    Embedded { path: String },
-    /// gRPC storage - connect to external vector database server
-    Grpc { url: String },
+pub struct SessionsConfig {
+    /// Question and answer pairs kept per session (0 disables history)
+    #[serde(default = "default_sessions_max_turns")]
+    pub max_turns: usize,
+    /// Sessions idle this long are forgotten; stored ones can still be resumed
+    #[serde(default = "default_sessions_idle_minutes")]
+    pub idle_minutes: u64,
+    /// Store conversations on disk
+    #[serde(default = "default_sessions_persist")]
+    pub persist: bool,
+    /// Directory of the stored conversations
+    #[serde(default = "default_sessions_path")]
+    pub path: String,
+    /// Model requests run at once across all sessions and transports of the
+    /// server, and by each `ChatManager` (0 for no limit); a session always
+    /// runs one at a time
+    #[serde(default = "default_max_concurrent_chats")]
+    pub max_concurrent_chats: usize,
 }
 
-impl Default for StorageMode {
+fn default_sessions_max_turns() -> usize {
+    10
+}
+
+fn default_sessions_idle_minutes() -> u64 {
+    60
+}
+
+fn default_sessions_persist() -> bool {
+    true
+}
+
+fn default_sessions_path() -> String {
+    "./data/sessions".to_string()
+}
+
+fn default_max_concurrent_chats() -> usize {
+    4
+}
+
+impl Default for SessionsConfig {
     fn default() -> Self {
-        Self::Embedded {
-            path: "./data/nucleus_vectordb".to_string(),
+        Self {
+            max_turns: default_sessions_max_turns(),
+            idle_minutes: default_sessions_idle_minutes(),
+            persist: default_sessions_persist(),
+            path: default_sessions_path(),
+            max_concurrent_chats: default_max_concurrent_chats(),
         }
     }
 }
 
-impl Default for RagConfig {
+/// Plugins discovered in a directory at startup (see [`crate::plugins`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginsConfig {
+    /// Look for plugins in `path`
+    #[serde(default = "default_plugins_enabled")]
+    pub enabled: bool,
+    /// Directory with a subdirectory and `plugin.yaml` manifest per plugin
+    #[serde(default = "default_plugins_path")]
+    pub path: String,
+    /// Plugin name -> what it may do (read, write, network, execute), in
+    /// place of the default grant; applies to built-in plugins too
+    #[serde(default)]
+    pub grants: HashMap<String, Vec<Capability>>,
+    /// Directory the file plugins (read_file, write_file, list_dir) are
+    /// confined to; the current directory if unset
+    #[serde(default)]
+    pub workspace_root: Option<String>,
+}
+
+fn default_plugins_enabled() -> bool {
+    true
+}
+
+fn default_plugins_path() -> String {
+    "~/.config/llm-workspace/plugins".to_string()
+}
+
+impl Default for PluginsConfig {
     fn default() -> Self {
-        let embedding_model = EmbeddingModel::default();
+        Self {
+            enabled: default_plugins_enabled(),
+            path: default_plugins_path(),
+            grants: HashMap::new(),
+            workspace_root: None,
+        }
+    }
+}
 
-        let indexer = IndexerConfig {
-            extensions: Vec::new(),
-            exclude_patterns: default_exclude_patterns(),
-            chunk_size: embedding_model.embedding_dim,
-            chunk_overlap: 50,
-        };
+/// Workspace events (files indexed, chats completed, edits applied, plugins
+/// executed) streamed to `subscribe` requests and, with `sse_enabled`, as
+/// Server-Sent Events from `GET /events` (see [`crate::events`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventsConfig {
+    /// Events buffered per subscriber; one falling further behind misses the oldest
+    #[serde(default = "default_events_buffer")]
+    pub buffer: usize,
+    #[serde(default)]
+    pub sse_enabled: bool,
+    /// Address the Server-Sent Events endpoint listens on; event payloads
+    /// include file paths, so keep it on loopback unless the network is trusted
+    #[serde(default = "default_events_sse_address")]
+    pub sse_address: String,
+}
+
+fn default_events_buffer() -> usize {
+    256
+}
 
+fn default_events_sse_address() -> String {
+    "127.0.0.1:7879".to_string()
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
         Self {
-            embedding_model,
-            indexer,
+            buffer: default_events_buffer(),
+            sse_enabled: false,
+            sse_address: default_events_sse_address(),
         }
     }
 }
 
-/// Storage configuration for all persistence.
+/// How long answers are.
 ///
-/// This includes chat history, tool state, and vector database storage.
+/// Requests pick a [`Verbosity`] (see `Request::verbosity`), falling back
+/// to `default`. Terse and detailed answers get instructions added to the
+/// system prompt and their own token budget; a request's `max_tokens` still
+/// takes precedence.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct StorageConfig {
-    pub chat_history_path: String,
-    pub tool_state_path: String,
-    /// Vector database storage mode
+pub struct VerbosityConfig {
     #[serde(default)]
-    pub storage_mode: StorageMode,
-    /// Vector database configuration (collection name, etc.)
+    pub default: Verbosity,
+    /// Token budget of terse answers
+    #[serde(default = "default_terse_max_tokens")]
+    pub terse_max_tokens: Option<u32>,
+    /// Token budget of detailed answers
     #[serde(default)]
-    pub vector_db: VectorDbConfig,
-    /// Number of results to return from vector similarity searches
-    #[serde(default = "default_top_k")]
-    pub top_k: usize,
+    pub detailed_max_tokens: Option<u32>,
 }
 
-/// Vector database configuration (collection/index name, etc.).
+fn default_terse_max_tokens() -> Option<u32> {
+    Some(256)
+}
+
+impl VerbosityConfig {
+    /// The token budget of answers at `verbosity`, `None` for no limit.
+    pub fn max_tokens(&self, verbosity: Verbosity) -> Option<u32> {
+        match verbosity {
+            Verbosity::Terse => self.terse_max_tokens,
+            Verbosity::Normal => None,
+            Verbosity::Detailed => self.detailed_max_tokens,
+        }
+    }
+}
+
+impl Default for VerbosityConfig {
+    fn default() -> Self {
+        Self {
+            default: Verbosity::default(),
+            terse_max_tokens: default_terse_max_tokens(),
+            detailed_max_tokens: None,
+        }
+    }
+}
+
+/// Length and level of detail of an answer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    /// Just the answer: a sentence or two, or only the command or code
+    Terse,
+    #[default]
+    Normal,
+    /// Reasoning, edge cases, alternatives, and examples
+    Detailed,
+}
+
+impl Verbosity {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "terse" => Some(Self::Terse),
+            "normal" => Some(Self::Normal),
+            "detailed" => Some(Self::Detailed),
+            _ => None,
+        }
+    }
+
+    /// Added to the system prompt for answers at this verbosity.
+    pub fn instructions(&self) -> Option<&'static str> {
+        match self {
+            Self::Terse => Some(
+                "Answer as briefly as possible: a sentence or two, or just the command or code asked for. \
+                 No preamble, recap, or closing remarks.",
+            ),
+            Self::Normal => None,
+            Self::Detailed => Some(
+                "Answer thoroughly: explain the reasoning, cover edge cases and alternatives, \
+                 and include examples where they help.",
+            ),
+        }
+    }
+}
+
+/// Shared knowledge base for team mode.
 ///
-/// Provider-agnostic configuration that works with any vector DB backend
-/// (Qdrant, LanceDB, etc.).
+/// Retrieval searches both the local and the shared knowledge base, while chat
+/// still runs against the local LLM. Each team gets its own collection on the
+/// shared server, named after its namespace.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct VectorDbConfig {
-    /// Collection/index name for storing vectors
-    pub collection_name: String,
+pub struct TeamConfig {
+    /// Where the shared knowledge base lives (usually `mode: grpc` with a Qdrant URL)
+    pub storage_mode: StorageMode,
+    /// Team namespace, used to derive the shared collection name
+    pub namespace: String,
+    /// Only admins should disable this; it allows indexing into the shared knowledge base
+    #[serde(default = "default_team_read_only")]
+    pub read_only: bool,
+}
+
+fn default_team_read_only() -> bool {
+    true
+}
+
+impl TeamConfig {
+    /// Collection name on the shared server, e.g. `team_platform` for namespace `platform`.
+    pub fn collection_name(&self) -> String {
+        let namespace: String = self
+            .namespace
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        format!("team_{}", namespace)
+    }
+
+    /// Storage configuration for the shared knowledge base, based on the local one.
+    pub fn storage_config(&self, local: &StorageConfig) -> StorageConfig {
+        StorageConfig {
+            storage_mode: self.storage_mode.clone(),
+            vector_db: local.vector_db.named(self.collection_name()),
+            ..local.clone()
+        }
+    }
+}
+
+/// An A/B experiment arm: alternate settings for a percentage of chat requests.
+///
+/// Unset fields fall back to the regular configuration. Requests not routed
+/// to any experiment use the regular configuration and are tagged `control`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentConfig {
+    /// Tag recorded with every response produced by this arm
+    pub name: String,
+    /// Share of chat requests routed to this arm (0-100)
+    pub percentage: f64,
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    #[serde(default)]
+    pub rerank: Option<bool>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -197,6 +2341,8 @@ impl Default for VectorDbConfig {
     fn default() -> Self {
         Self {
             collection_name: "nucleus_kb".to_string(),
+            distance: DistanceMetric::default(),
+            distances: HashMap::new(),
         }
     }
 }
@@ -209,6 +2355,15 @@ impl Default for StorageConfig {
             storage_mode: StorageMode::default(),
             vector_db: VectorDbConfig::default(),
             top_k: default_top_k(),
+            packs_path: default_packs_path(),
+            feedback_path: default_feedback_path(),
+            crash_reports_path: default_crash_reports_path(),
+            embedding_cache_path: default_embedding_cache_path(),
+            collections_path: default_collections_path(),
+            snapshots_path: default_snapshots_path(),
+            starter_packs_url: default_starter_packs_url(),
+            hnsw: HnswConfig::default(),
+            trash: TrashConfig::default(),
         }
     }
 }
@@ -220,6 +2375,13 @@ impl Default for LlmConfig {
             base_url: "http://localhost:11434".to_string(), // For Ollama provider (if used)
             temperature: 0.6,
             context_length: 32768,
+            answer_tokens: default_answer_tokens(),
+            provider: None,
+            api_key: None,
+            retry: RetryPolicy::default(),
+            fallbacks: Vec::new(),
+            llama_cpp: LlamaCppConfig::default(),
+            worker: WorkerConfig::default(),
         }
     }
 }
@@ -234,6 +2396,30 @@ impl Default for Config {
             rag: RagConfig::default(),
             storage: StorageConfig::default(),
             personalization: PersonalizationConfig::default(),
+            notifications: NotificationConfig::default(),
+            orchestration: OrchestrationConfig::default(),
+            team: None,
+            experiments: Vec::new(),
+            attachments: AttachmentConfig::default(),
+            suggest: SuggestConfig::default(),
+            egress: EgressConfig::default(),
+            grpc: GrpcConfig::default(),
+            log_analysis: LogAnalysisConfig::default(),
+            expression: ExpressionConfig::default(),
+            commands: CommandDocsConfig::default(),
+            dotfiles: DotfilesConfig::default(),
+            conversations: ConversationsConfig::default(),
+            display: DisplayConfig::default(),
+            watch: WatchConfig::default(),
+            resilience: ResilienceConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            updates: UpdateConfig::default(),
+            response_log: ResponseLogConfig::default(),
+            request_log: RequestLogConfig::default(),
+            sessions: SessionsConfig::default(),
+            events: EventsConfig::default(),
+            verbosity: VerbosityConfig::default(),
+            plugins: PluginsConfig::default(),
             permission: Permission::default(),
         }
     }
@@ -270,8 +2456,14 @@ mod tests {
 
     #[test]
     fn test_vector_db_config_default() {
-        let config = VectorDbConfig::default();
+        let mut config = VectorDbConfig::default();
         assert_eq!(config.collection_name, "nucleus_kb");
+        assert_eq!(config.metric(), DistanceMetric::Cosine);
+
+        config.distances = serde_yaml::from_str("nucleus_kb_commands: dot\nnucleus_kb-docs: l2").unwrap();
+        assert_eq!(config.named("nucleus_kb_commands".to_string()).metric(), DistanceMetric::Dot);
+        assert_eq!(config.named("nucleus_kb-docs".to_string()).metric(), DistanceMetric::Euclidean);
+        assert_eq!(config.metric(), DistanceMetric::Cosine);
     }
 
     #[test]
@@ -281,6 +2473,69 @@ mod tests {
         assert_eq!(config.tool_state_path, "./data/tool_state");
         assert_eq!(config.vector_db.collection_name, "nucleus_kb");
         assert_eq!(config.top_k, 5);
+        assert_eq!(config.packs_path, "./data/packs");
+        assert_eq!(config.feedback_path, "./data/feedback.jsonl");
+    }
+
+    #[test]
+    fn test_notification_config_defaults() {
+        let config = NotificationConfig::default();
+        assert!(config.webhook_url.is_none());
+        assert!(config.command.is_none());
+        assert_eq!(config.min_duration_secs, 0);
+        assert!(!config.desktop);
+        assert_eq!(config.desktop_min_duration_secs, 30);
+    }
+
+    #[test]
+    fn test_team_config() {
+        let yaml = "storage_mode:\n  mode: grpc\n  url: http://kb.internal:6334\nnamespace: platform team\n";
+        let team: TeamConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(team.read_only);
+        assert_eq!(team.collection_name(), "team_platform_team");
+
+        let storage = team.storage_config(&StorageConfig::default());
+        assert_eq!(storage.vector_db.collection_name, "team_platform_team");
+        assert!(matches!(storage.storage_mode, StorageMode::Grpc { .. }));
+    }
+
+    #[test]
+    fn test_llm_is_local() {
+        let mut llm = Config::default().llm;
+        for (url, local) in [
+            ("http://localhost:11434", true),
+            ("http://127.0.0.1:11434/", true),
+            ("http://[::1]:11434", true),
+            ("https://ollama.example.com", false),
+            ("http://10.0.0.5:11434", false),
+        ] {
+            llm.base_url = url.to_string();
+            assert_eq!(llm.is_local(), local, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_llm_fallbacks() {
+        let yaml = "provider: openai\nbase_url: https://api.openai.com/v1\napi_key: sk-primary\nmodel: gpt-4o-mini\n\
+                    temperature: 0.6\ncontext_length: 32768\nretry:\n  max_retries: 2\n\
+                    fallbacks:\n  - provider: ollama\n    base_url: http://localhost:11434\n    model: qwen3:0.6b\n";
+        let llm: LlmConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(llm.retry, RetryPolicy { max_retries: 2, backoff_ms: 500 });
+
+        let fallback = llm.fallbacks[0].apply(&llm);
+        assert_eq!(fallback.provider, Some(ProviderKind::Ollama));
+        assert_eq!(fallback.model, "qwen3:0.6b");
+        assert_eq!(fallback.temperature, 0.6);
+        assert_eq!(fallback.api_key, None);
+        assert_eq!(fallback.retry, RetryPolicy::default());
+    }
+
+    #[test]
+    fn test_suggest_config_defaults() {
+        let config: SuggestConfig = serde_yaml::from_str("model: qwen2.5-coder:0.5b").unwrap();
+        assert_eq!(config.model.as_deref(), Some("qwen2.5-coder:0.5b"));
+        assert_eq!(config.max_time_ms, 300);
+        assert!(!config.use_rag);
     }
 
     #[test]
@@ -288,4 +2543,28 @@ mod tests {
         let config = RagConfig::default();
         assert_eq!(config.embedding_model.name, EmbeddingModel::default().name);
     }
+
+    #[test]
+    fn test_context_format_for_model() {
+        assert_eq!(ContextFormat::for_model("anthropic/claude-sonnet-4"), ContextFormat::Xml);
+        assert_eq!(ContextFormat::for_model("gpt-4o-mini"), ContextFormat::Markdown);
+        assert_eq!(ContextFormat::for_model("o3-mini"), ContextFormat::Markdown);
+        assert_eq!(ContextFormat::for_model("qwen2.5-coder:7b"), ContextFormat::Markdown);
+        assert_eq!(ContextFormat::for_model("llama3.2"), ContextFormat::Numbered);
+        assert_eq!(ContextFormat::for_model("olmo-2"), ContextFormat::Numbered);
+
+        let config = RagConfig {
+            context_format: Some(serde_yaml::from_str("xml").unwrap()),
+            ..RagConfig::default()
+        };
+        assert_eq!(config.context_format_for("llama3.2"), ContextFormat::Xml);
+    }
+
+    #[test]
+    fn test_timeout_limits() {
+        let config: TimeoutConfig = serde_yaml::from_str("chat_secs: 0\nindex_secs: 600").unwrap();
+        assert_eq!(config.limit(OperationClass::Chat), None);
+        assert_eq!(config.limit(OperationClass::Index), Some(Duration::from_secs(600)));
+        assert_eq!(config.limit(OperationClass::Embed), Some(Duration::from_secs(60)));
+    }
 }
@@ -0,0 +1,86 @@
+//! Past conversations as a knowledge source.
+//!
+//! A chat exchange can be summarized by the LLM (the problem with its exact
+//! error messages, the cause, and the fix that worked) and indexed into a
+//! dedicated collection, one source (`conversation:<response ID>`) per
+//! exchange, so a solution worked out weeks ago is retrieved the next time
+//! the same error shows up.
+//!
+//! Exchanges are saved on request (`remember` with the response ID) or, with
+//! `conversations.auto`, after every chat response outside private mode. The
+//! automatic mode is off by default: transcripts contain whatever was
+//! discussed, so turning it on is the user's consent to keep them.
+
+/// Prefix of the `source` metadata of conversation documents.
+pub const SOURCE_PREFIX: &str = "conversation:";
+
+/// Instructions for the summarizing model.
+pub const SYSTEM_PROMPT: &str = "You write short notes about technical conversations so they can be found again later. \
+    Describe the problem, quoting error messages, commands, and file names exactly; the cause, if it was found; \
+    and the solution or workaround that was reached. Leave out greetings and dead ends. \
+    If no problem was solved, summarize what was learned. Reply with the note only, in plain text.";
+
+/// Marks where the middle of a long transcript was left out.
+const OMITTED: &str = "\n[...]\n";
+
+/// Source recorded with a conversation's documents.
+pub fn source(response_id: &str) -> String {
+    format!("{}{}", SOURCE_PREFIX, response_id)
+}
+
+/// Formats conversation turns as `role: text` paragraphs.
+///
+/// Transcripts longer than `max_chars` keep their beginning (the problem)
+/// and end (the solution), dropping the middle.
+pub fn transcript<'a>(turns: impl IntoIterator<Item = (&'a str, &'a str)>, max_chars: usize) -> String {
+    let text = turns
+        .into_iter()
+        .filter(|(_, content)| !content.trim().is_empty())
+        .map(|(role, content)| format!("{}: {}", role, content.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let length = text.chars().count();
+    if length <= max_chars {
+        return text;
+    }
+
+    let keep = max_chars.saturating_sub(OMITTED.len()) / 2;
+    let head: String = text.chars().take(keep).collect();
+    let tail: String = text.chars().skip(length - keep).collect();
+    format!("{}{}{}", head, OMITTED, tail)
+}
+
+/// Asks for a retrieval note about `transcript`.
+pub fn summary_prompt(transcript: &str) -> String {
+    format!("Write the note for this conversation:\n\n{}", transcript)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript() {
+        let turns = [
+            ("user", "cargo build fails with E0599"),
+            ("assistant", ""),
+            ("assistant", "Import the trait."),
+        ];
+        assert_eq!(
+            transcript(turns, 1000),
+            "user: cargo build fails with E0599\n\nassistant: Import the trait."
+        );
+    }
+
+    #[test]
+    fn test_transcript_keeps_beginning_and_end() {
+        let long = format!("error E0599{}fixed by importing Write", "x".repeat(500));
+        let text = transcript([("user", long.as_str())], 100);
+
+        assert!(text.chars().count() <= 100);
+        assert!(text.starts_with("user: error E0599"));
+        assert!(text.ends_with("fixed by importing Write"));
+        assert!(text.contains("[...]"));
+    }
+}
@@ -0,0 +1,229 @@
+//! Line diffs and prompts for explaining them.
+//!
+//! Diff requests send the LLM a unified diff rather than both texts, so long
+//! files that differ in a few places fit in the context. The LLM is asked to
+//! mark each difference with `+`, `-`, or `~` so clients can colorize them.
+
+/// Lines of unchanged context around each change.
+pub const CONTEXT_LINES: usize = 3;
+
+/// Above this many line comparisons, a changed region is shown as a whole
+/// removal and addition instead of being aligned line by line.
+const MAX_ALIGN_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+/// Returns a unified diff of `old` and `new`, or an empty string if they have
+/// the same lines.
+pub fn unified(old_name: &str, new_name: &str, old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&old_lines, &new_lines);
+    if ops.iter().all(|op| matches!(op, Op::Equal(_))) {
+        return String::new();
+    }
+
+    let mut output = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for hunk in hunks(&ops, CONTEXT_LINES) {
+        output.push_str(&hunk);
+    }
+    output
+}
+
+/// Aligns the lines, trimming the common prefix and suffix first.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Op<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut ops: Vec<Op> = old[..prefix].iter().map(|line| Op::Equal(line)).collect();
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_ALIGN_CELLS {
+        ops.extend(old_middle.iter().map(|line| Op::Delete(line)));
+        ops.extend(new_middle.iter().map(|line| Op::Insert(line)));
+    } else {
+        ops.extend(align(old_middle, new_middle));
+    }
+    ops.extend(old[old.len() - suffix..].iter().map(|line| Op::Equal(line)));
+    ops
+}
+
+/// Longest common subsequence alignment.
+fn align<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Op<'a>> {
+    let width = new.len() + 1;
+    // lengths[i * width + j]: LCS length of old[i..] and new[j..]
+    let mut lengths = vec![0usize; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::with_capacity(old.len() + new.len());
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            ops.push(Op::Equal(old[i]));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            ops.push(Op::Delete(old[i]));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(new[j]));
+            j += 1;
+        }
+    }
+    ops.extend(old[i..].iter().map(|line| Op::Delete(line)));
+    ops.extend(new[j..].iter().map(|line| Op::Insert(line)));
+    ops
+}
+
+/// Groups changes with their surrounding context into `@@` hunks.
+fn hunks(ops: &[Op], context: usize) -> Vec<String> {
+    let changes: Vec<usize> = (0..ops.len()).filter(|&i| !matches!(ops[i], Op::Equal(_))).collect();
+
+    // Merge changes whose context overlaps into (start, end) ranges of ops
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &i in &changes {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(ops.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    // Line numbers at the start of each op, counting from 1
+    let mut old_line = 1;
+    let mut new_line = 1;
+    let mut positions = Vec::with_capacity(ops.len());
+    for op in ops {
+        positions.push((old_line, new_line));
+        match op {
+            Op::Equal(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Op::Delete(_) => old_line += 1,
+            Op::Insert(_) => new_line += 1,
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let slice = &ops[start..end];
+            let old_count = slice.iter().filter(|op| !matches!(op, Op::Insert(_))).count();
+            let new_count = slice.iter().filter(|op| !matches!(op, Op::Delete(_))).count();
+            let (old_start, new_start) = positions[start];
+
+            let mut hunk = format!(
+                "@@ -{} +{} @@\n",
+                hunk_range(old_start, old_count),
+                hunk_range(new_start, new_count)
+            );
+            for op in slice {
+                let (marker, line) = match op {
+                    Op::Equal(line) => (' ', line),
+                    Op::Delete(line) => ('-', line),
+                    Op::Insert(line) => ('+', line),
+                };
+                hunk.push(marker);
+                hunk.push_str(line);
+                hunk.push('\n');
+            }
+            hunk
+        })
+        .collect()
+}
+
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        // An empty range names the line before it
+        0 => format!("{},0", start - 1),
+        1 => start.to_string(),
+        _ => format!("{},{}", start, count),
+    }
+}
+
+/// Builds the prompt asking the LLM to explain `diff`.
+///
+/// `focus` is an optional question from the user, e.g. "does this change
+/// the retry behavior?".
+pub fn explain_prompt(diff: &str, focus: &str) -> String {
+    let mut prompt = String::from(
+        "Explain the meaningful differences between the two versions in this diff.\n\
+         Put each difference on its own line, starting with `+ ` if it adds something, \
+         `- ` if it removes something, or `~ ` if it changes something, followed by \
+         what changed and its effect.\n\
+         Ignore formatting, reordering, and comment changes unless they change behavior.\n\
+         Finish with a line starting with `Summary: `.\n",
+    );
+    let focus = focus.trim();
+    if !focus.is_empty() {
+        prompt.push_str(&format!("Pay particular attention to: {}\n", focus));
+    }
+    prompt.push_str(&format!("\n```diff\n{}```", diff));
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_texts() {
+        assert_eq!(unified("a", "b", "x\ny\n", "x\ny"), "");
+    }
+
+    #[test]
+    fn test_unified_hunks() {
+        let old = "timeout: 30\nretries: 3\nhost: a\n";
+        let new = "timeout: 60\nretries: 3\nhost: a\nport: 80\n";
+
+        let expected = "--- old.yaml\n+++ new.yaml\n\
+                        @@ -1,3 +1,4 @@\n\
+                        -timeout: 30\n\
+                        +timeout: 60\n \
+                        retries: 3\n \
+                        host: a\n\
+                        +port: 80\n";
+        assert_eq!(unified("old.yaml", "new.yaml", old, new), expected);
+    }
+
+    #[test]
+    fn test_distant_changes_get_separate_hunks() {
+        let old: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+        let mut new = old.clone();
+        new[1] = "two".to_string();
+        new.remove(17);
+
+        let diff = unified("a", "b", &old.join("\n"), &new.join("\n"));
+        let headers: Vec<&str> = diff.lines().filter(|line| line.starts_with("@@")).collect();
+        assert_eq!(headers, vec!["@@ -1,5 +1,5 @@", "@@ -15,6 +15,5 @@"]);
+    }
+
+    #[test]
+    fn test_explain_prompt_focus() {
+        let prompt = explain_prompt("-a\n+b\n", "  retries ");
+        assert!(prompt.contains("Pay particular attention to: retries\n"));
+        assert!(prompt.ends_with("```diff\n-a\n+b\n```"));
+        assert!(!explain_prompt("-a\n", "").contains("attention"));
+    }
+}
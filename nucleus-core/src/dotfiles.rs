@@ -0,0 +1,466 @@
+//! The user's shell, editor, and tool configuration files.
+//!
+//! Opt-in (`dotfiles.enabled`): the files and directories listed in
+//! `dotfiles.paths` are indexed into a dedicated collection, one source
+//! (`dotfile:<path>`) per file, so suggestions and answers respect the user's
+//! aliases, plugins, and settings instead of proposing commands their setup
+//! shadows. Secrets are redacted with the egress classifier before anything
+//! is embedded or stored.
+//!
+//! Like [`crate::command_docs`], refreshes are incremental: a fingerprint
+//! (size, modification time) of each file is kept in `dotfiles.state_path`,
+//! and only changed files are read again.
+
+use crate::config::DotfilesConfig;
+use crate::egress::EgressClassifier;
+use crate::rag::RagEngine;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::{debug, info, warn};
+
+/// Prefix of the `source` metadata of dotfile documents.
+pub const SOURCE_PREFIX: &str = "dotfile:";
+
+/// Directories never descended into, such as plugin checkouts' history.
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "__pycache__"];
+
+/// A configuration file found under `dotfiles.paths`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dotfile {
+    pub path: PathBuf,
+    /// Path shown to the model, with the home directory abbreviated to `~`
+    pub name: String,
+    /// Changes when the file is edited
+    pub fingerprint: String,
+}
+
+/// An alias defined in a configuration file, e.g. `ll` for `ls -la`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    /// What the user types, e.g. `ll` or `git co`
+    pub name: String,
+    pub expansion: String,
+}
+
+/// A configuration file ready to be indexed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DotfileDoc {
+    pub name: String,
+    /// Contents with secrets redacted
+    pub text: String,
+    pub aliases: Vec<Alias>,
+    /// Number of secrets redacted from the contents
+    pub redactions: usize,
+}
+
+impl DotfileDoc {
+    /// Source recorded with the file's documents.
+    pub fn source(&self) -> String {
+        source(&self.name)
+    }
+}
+
+/// Source recorded with the documents of the file named `name`.
+pub fn source(name: &str) -> String {
+    format!("{}{}", SOURCE_PREFIX, name)
+}
+
+/// Indexed files and their fingerprints, persisted between refreshes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexState {
+    pub files: BTreeMap<String, String>,
+}
+
+impl IndexState {
+    /// Loads the state, or returns an empty one if the file does not exist.
+    pub async fn load(path: &Path) -> Result<Self> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Invalid dotfile index state: {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Work needed to bring the index up to date.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RefreshPlan<'a> {
+    /// New or edited files to read
+    pub changed: Vec<&'a Dotfile>,
+    /// Indexed files that no longer exist
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+/// Compares the files found with what was indexed.
+pub fn plan_refresh<'a>(state: &IndexState, dotfiles: &'a [Dotfile]) -> RefreshPlan<'a> {
+    let mut plan = RefreshPlan::default();
+    for dotfile in dotfiles {
+        match state.files.get(&dotfile.name) {
+            Some(fingerprint) if *fingerprint == dotfile.fingerprint => plan.unchanged += 1,
+            _ => plan.changed.push(dotfile),
+        }
+    }
+
+    let found: HashSet<&str> = dotfiles.iter().map(|d| d.name.as_str()).collect();
+    plan.removed = state
+        .files
+        .keys()
+        .filter(|name| !found.contains(name.as_str()))
+        .cloned()
+        .collect();
+    plan
+}
+
+/// Expands a leading `~` to `home`.
+pub fn expand_home(path: &str, home: &Path) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) => home.join(rest.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Abbreviates `home` at the start of `path` to `~`.
+fn display_name(path: &Path, home: &Path) -> String {
+    match path.strip_prefix(home) {
+        Ok(rest) if !home.as_os_str().is_empty() => format!("~/{}", rest.display()),
+        _ => path.display().to_string(),
+    }
+}
+
+/// Lists the files under the configured paths, sorted by name.
+///
+/// Directories are walked recursively, up to `max_files` files each. Missing
+/// paths are skipped, as are files over `max_file_bytes`.
+pub fn discover(config: &DotfilesConfig, home: &Path) -> Vec<Dotfile> {
+    let mut seen = HashSet::new();
+    let mut dotfiles = Vec::new();
+
+    for configured in &config.paths {
+        let root = expand_home(configured, home);
+        let mut files = Vec::new();
+        if root.is_dir() {
+            walk(&root, config.max_files, &mut files);
+        } else {
+            files.push(root);
+        }
+
+        for path in files {
+            // Follows symlinks, which dotfile managers create
+            let Ok(metadata) = std::fs::metadata(&path) else {
+                continue;
+            };
+            if !metadata.is_file() || metadata.len() > config.max_file_bytes {
+                continue;
+            }
+            let name = display_name(&path, home);
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_secs());
+            dotfiles.push(Dotfile {
+                fingerprint: format!("{}:{}", metadata.len(), modified),
+                name,
+                path,
+            });
+        }
+    }
+
+    dotfiles.sort_by(|a, b| a.name.cmp(&b.name));
+    dotfiles
+}
+
+/// Collects up to `limit` files below `dir`, in a stable order.
+fn walk(dir: &Path, limit: usize, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    entries.sort();
+
+    for path in entries {
+        if files.len() >= limit {
+            return;
+        }
+        let skipped = path
+            .file_name()
+            .is_some_and(|name| SKIPPED_DIRS.iter().any(|skipped| name == *skipped));
+        if path.is_dir() {
+            if !skipped {
+                walk(&path, limit, files);
+            }
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// Reads a configuration file, redacting secrets.
+///
+/// Returns `None` for unreadable, binary, and empty files.
+pub async fn read(dotfile: &Dotfile, classifier: &EgressClassifier) -> Option<DotfileDoc> {
+    let bytes = tokio::fs::read(&dotfile.path).await.ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    let (text, redactions) = classifier.redact(&text);
+    Some(DotfileDoc {
+        name: dotfile.name.clone(),
+        aliases: aliases(&text),
+        text,
+        redactions,
+    })
+}
+
+/// Aliases defined in a configuration file.
+///
+/// Understands shell `alias name=value` (and fish's `alias name value` and
+/// `abbr`) lines, and the `[alias]` section of a git configuration.
+pub fn aliases(text: &str) -> Vec<Alias> {
+    let mut aliases = Vec::new();
+    let mut in_git_aliases = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_git_aliases = line.trim_end_matches(']').trim_start_matches('[').trim() == "alias";
+            continue;
+        }
+        if line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if in_git_aliases {
+            if let Some((name, expansion)) = line.split_once('=') {
+                aliases.push(Alias {
+                    name: format!("git {}", name.trim()),
+                    expansion: unquote(expansion.trim()).to_string(),
+                });
+            }
+        } else if let Some(definition) = line.strip_prefix("alias ") {
+            // `alias -g` and friends
+            let definition = definition.trim_start();
+            let definition = match definition.split_once(' ') {
+                Some((flag, rest)) if flag.starts_with('-') => rest.trim_start(),
+                _ => definition,
+            };
+            let split = definition.split_once('=').or_else(|| definition.split_once(' '));
+            if let Some((name, expansion)) = split {
+                aliases.push(Alias {
+                    name: name.trim().to_string(),
+                    expansion: unquote(expansion.trim()).to_string(),
+                });
+            }
+        } else if let Some(definition) = line.strip_prefix("abbr ") {
+            let words: Vec<&str> = definition.split_whitespace().skip_while(|word| word.starts_with('-')).collect();
+            if let [name, expansion @ ..] = words.as_slice() {
+                if !expansion.is_empty() {
+                    aliases.push(Alias {
+                        name: name.to_string(),
+                        expansion: unquote(&expansion.join(" ")).to_string(),
+                    });
+                }
+            }
+        }
+    }
+    aliases
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['\'', '"'] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|value| value.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    value
+}
+
+/// Result of a refresh.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RefreshSummary {
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    /// Secrets redacted from the files indexed
+    pub redactions: usize,
+}
+
+/// Brings the dotfile collection up to date with the configured paths.
+///
+/// `on_progress` receives a line for every file indexed.
+///
+/// # Errors
+///
+/// Returns an error if the state file cannot be read or written, or if
+/// embedding or storing a file fails. Files indexed before the failure are
+/// recorded, so the next refresh continues where this one stopped.
+pub async fn refresh<F>(
+    rag: &RagEngine,
+    config: &DotfilesConfig,
+    classifier: &EgressClassifier,
+    mut on_progress: F,
+) -> Result<RefreshSummary>
+where
+    F: FnMut(&str) + Send,
+{
+    let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    let state_path = PathBuf::from(&config.state_path);
+    let mut state = IndexState::load(&state_path).await?;
+
+    let dotfiles = discover(config, &home);
+    let plan = plan_refresh(&state, &dotfiles);
+    info!(
+        changed = plan.changed.len(),
+        removed = plan.removed.len(),
+        unchanged = plan.unchanged,
+        "Refreshing dotfiles"
+    );
+
+    let mut summary = RefreshSummary {
+        unchanged: plan.unchanged,
+        ..RefreshSummary::default()
+    };
+
+    let result = async {
+        for name in &plan.removed {
+            rag.remove_dotfile(name).await?;
+            state.files.remove(name);
+            summary.removed += 1;
+        }
+
+        for dotfile in &plan.changed {
+            match read(dotfile, classifier).await {
+                Some(doc) => {
+                    let chunks = rag.index_dotfile(&doc).await?;
+                    let mut line = format!("✓ {} ({} chunks", doc.name, chunks);
+                    if doc.redactions > 0 {
+                        line.push_str(&format!(", {} secret(s) redacted", doc.redactions));
+                    }
+                    line.push_str(")\n");
+                    on_progress(&line);
+                    summary.indexed += 1;
+                    summary.redactions += doc.redactions;
+                }
+                None => {
+                    debug!(file = %dotfile.name, "Skipping unreadable or binary file");
+                    if state.files.contains_key(&dotfile.name) {
+                        rag.remove_dotfile(&dotfile.name).await?;
+                    }
+                }
+            }
+            state.files.insert(dotfile.name.clone(), dotfile.fingerprint.clone());
+        }
+        anyhow::Ok(())
+    }
+    .await;
+
+    if let Err(e) = state.save(&state_path).await {
+        warn!("Failed to save dotfile index state: {:#}", e);
+    }
+    result?;
+
+    info!(?summary, "Dotfiles refreshed");
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases() {
+        let zshrc = "# aliases\nalias ll='ls -la'\nalias -g G=\"| grep\"\nexport EDITOR=nvim\n";
+        assert_eq!(
+            aliases(zshrc),
+            vec![
+                Alias { name: "ll".to_string(), expansion: "ls -la".to_string() },
+                Alias { name: "G".to_string(), expansion: "| grep".to_string() },
+            ]
+        );
+
+        let gitconfig = "[user]\n\tname = Jane\n[alias]\n\tco = checkout\n\tlg = \"log --oneline --graph\"\n[core]\n\tpager = delta\n";
+        assert_eq!(
+            aliases(gitconfig),
+            vec![
+                Alias { name: "git co".to_string(), expansion: "checkout".to_string() },
+                Alias { name: "git lg".to_string(), expansion: "log --oneline --graph".to_string() },
+            ]
+        );
+
+        let fish = "alias vim nvim\nabbr -a gs git status\n";
+        assert_eq!(
+            aliases(fish),
+            vec![
+                Alias { name: "vim".to_string(), expansion: "nvim".to_string() },
+                Alias { name: "gs".to_string(), expansion: "git status".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discover() {
+        let home = tempfile::tempdir().unwrap();
+        std::fs::write(home.path().join(".zshrc"), "alias ll='ls -la'\n").unwrap();
+        std::fs::write(home.path().join(".huge"), "x".repeat(64)).unwrap();
+        let nvim = home.path().join(".config/nvim");
+        std::fs::create_dir_all(nvim.join("lua")).unwrap();
+        std::fs::create_dir_all(nvim.join(".git")).unwrap();
+        std::fs::write(nvim.join("init.lua"), "require('plugins')\n").unwrap();
+        std::fs::write(nvim.join("lua/plugins.lua"), "return {}\n").unwrap();
+        std::fs::write(nvim.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+
+        let config = DotfilesConfig {
+            paths: ["~/.zshrc", "~/.huge", "~/.missing", "~/.config/nvim", "~/.zshrc"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            max_file_bytes: 32,
+            ..DotfilesConfig::default()
+        };
+        let names: Vec<String> = discover(&config, home.path()).into_iter().map(|d| d.name).collect();
+        assert_eq!(names, vec!["~/.config/nvim/init.lua", "~/.config/nvim/lua/plugins.lua", "~/.zshrc"]);
+    }
+
+    #[test]
+    fn test_plan_refresh() {
+        let dotfile = |name: &str, fingerprint: &str| Dotfile {
+            path: PathBuf::from(name),
+            name: name.to_string(),
+            fingerprint: fingerprint.to_string(),
+        };
+        let mut state = IndexState::default();
+        state.files.insert("~/.zshrc".to_string(), "1".to_string());
+        state.files.insert("~/.vimrc".to_string(), "1".to_string());
+
+        let dotfiles = vec![dotfile("~/.gitconfig", "1"), dotfile("~/.zshrc", "2")];
+        let plan = plan_refresh(&state, &dotfiles);
+
+        let changed: Vec<&str> = plan.changed.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(changed, vec!["~/.gitconfig", "~/.zshrc"]);
+        assert_eq!(plan.removed, vec!["~/.vimrc"]);
+        assert_eq!(plan.unchanged, 0);
+    }
+}
@@ -0,0 +1,197 @@
+//! Lightweight description of the user's shell environment.
+//!
+//! Clients collect this next to the working directory and attach it to
+//! requests, so answers match the user's OS, shell, and toolchains instead of
+//! assuming a default Linux + bash setup.
+//!
+//! Collection only runs cheap commands (`git`, `--version` probes for
+//! toolchains whose project files are present in the working directory).
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+/// Project marker files and the toolchain version command they imply.
+const TOOLCHAIN_PROBES: &[(&str, &str, &[&str])] = &[
+    ("Cargo.toml", "rustc", &["--version"]),
+    ("package.json", "node", &["--version"]),
+    ("go.mod", "go", &["version"]),
+    ("pyproject.toml", "python3", &["--version"]),
+    ("requirements.txt", "python3", &["--version"]),
+    ("Gemfile", "ruby", &["--version"]),
+    ("pom.xml", "java", &["-version"]),
+    ("build.gradle", "java", &["-version"]),
+];
+
+/// Git state of the working directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitContext {
+    pub branch: String,
+    /// True if there are uncommitted changes
+    pub dirty: bool,
+}
+
+/// Environment details attached to a request.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvironmentContext {
+    /// Operating system, including distro or version when known (e.g. "macOS 14.5")
+    pub os: String,
+    pub arch: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitContext>,
+    /// Versions of toolchains relevant to the working directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub toolchains: Vec<String>,
+}
+
+impl EnvironmentContext {
+    /// Collects the environment for a working directory.
+    pub fn collect(pwd: &Path) -> Self {
+        Self {
+            os: os_description(),
+            arch: std::env::consts::ARCH.to_string(),
+            shell: shell_name(),
+            git: git_context(pwd),
+            toolchains: toolchain_versions(pwd),
+        }
+    }
+
+    /// Formats the environment as a system prompt section.
+    pub fn to_prompt(&self) -> String {
+        let mut prompt = String::from("User environment:\n");
+        prompt.push_str(&format!("- OS: {} ({})\n", self.os, self.arch));
+
+        if let Some(shell) = &self.shell {
+            prompt.push_str(&format!("- Shell: {}\n", shell));
+        }
+        if let Some(git) = &self.git {
+            let state = if git.dirty { "uncommitted changes" } else { "clean" };
+            prompt.push_str(&format!("- Git: branch {} ({})\n", git.branch, state));
+        }
+        if !self.toolchains.is_empty() {
+            prompt.push_str(&format!("- Toolchains: {}\n", self.toolchains.join(", ")));
+        }
+
+        prompt.push_str("Tailor commands and paths to this environment.");
+        prompt
+    }
+}
+
+/// Runs a command and returns its trimmed output (stdout, or stderr if stdout is empty).
+fn command_output(program: &str, args: &[&str], dir: Option<&Path>) -> Option<String> {
+    let mut command = Command::new(program);
+    command.args(args);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    // Some tools (e.g. `java -version`) print their version to stderr
+    let text = if output.stdout.is_empty() { output.stderr } else { output.stdout };
+    let text = String::from_utf8_lossy(&text);
+    text.lines().next().map(|line| line.trim().to_string())
+}
+
+fn os_description() -> String {
+    match std::env::consts::OS {
+        "macos" => command_output("sw_vers", &["-productVersion"], None)
+            .map(|version| format!("macOS {}", version))
+            .unwrap_or_else(|| "macOS".to_string()),
+        "linux" => std::fs::read_to_string("/etc/os-release")
+            .ok()
+            .and_then(|content| parse_os_release(&content))
+            .unwrap_or_else(|| "Linux".to_string()),
+        os => os.to_string(),
+    }
+}
+
+/// Extracts `PRETTY_NAME` from `/etc/os-release` content.
+fn parse_os_release(content: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|value| value.trim_matches('"').to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn shell_name() -> Option<String> {
+    if let Some(shell) = crate::shell_integration::Shell::detect() {
+        return Some(shell.name().to_string());
+    }
+
+    std::env::var("SHELL")
+        .ok()
+        .and_then(|shell| Path::new(&shell).file_name().map(|name| name.to_string_lossy().to_string()))
+}
+
+fn git_context(pwd: &Path) -> Option<GitContext> {
+    let branch = command_output("git", &["rev-parse", "--abbrev-ref", "HEAD"], Some(pwd))?;
+
+    let dirty = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .current_dir(pwd)
+        .output()
+        .map(|output| !output.stdout.is_empty())
+        .unwrap_or(false);
+
+    Some(GitContext { branch, dirty })
+}
+
+fn toolchain_versions(pwd: &Path) -> Vec<String> {
+    let mut versions: Vec<String> = Vec::new();
+    let mut probed: Vec<&str> = Vec::new();
+
+    for (marker, program, args) in TOOLCHAIN_PROBES {
+        if probed.contains(program) || !pwd.join(marker).exists() {
+            continue;
+        }
+        probed.push(program);
+
+        if let Some(version) = command_output(program, args, Some(pwd)) {
+            versions.push(version);
+        }
+    }
+
+    versions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_os_release() {
+        let content = "NAME=\"Fedora Linux\"\nPRETTY_NAME=\"Fedora Linux 40 (Workstation Edition)\"\n";
+        assert_eq!(
+            parse_os_release(content).as_deref(),
+            Some("Fedora Linux 40 (Workstation Edition)")
+        );
+        assert_eq!(parse_os_release("NAME=Arch"), None);
+    }
+
+    #[test]
+    fn test_to_prompt() {
+        let context = EnvironmentContext {
+            os: "macOS 14.5".to_string(),
+            arch: "aarch64".to_string(),
+            shell: Some("zsh".to_string()),
+            git: Some(GitContext {
+                branch: "main".to_string(),
+                dirty: true,
+            }),
+            toolchains: vec!["rustc 1.80.0".to_string()],
+        };
+
+        let prompt = context.to_prompt();
+        assert!(prompt.contains("- OS: macOS 14.5 (aarch64)"));
+        assert!(prompt.contains("- Shell: zsh"));
+        assert!(prompt.contains("- Git: branch main (uncommitted changes)"));
+        assert!(prompt.contains("- Toolchains: rustc 1.80.0"));
+    }
+}
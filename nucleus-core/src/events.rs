@@ -0,0 +1,375 @@
+//! Workspace events for external automation.
+//!
+//! The [`EventBus`] broadcasts what happens in the workspace as it happens:
+//! files indexed, chats completed, edits applied, and plugins executed.
+//! Scripts subscribe to react to it, e.g. rebuilding docs whenever the agent
+//! edits a file, instead of polling the file system or the server.
+//!
+//! The server streams events to `subscribe` requests on the socket, one JSON
+//! event per chunk, and with `events.sse_enabled` serves them as Server-Sent
+//! Events from `GET /events` on `events.sse_address`:
+//!
+//! ```text
+//! curl -N 'http://127.0.0.1:7879/events?types=edit-applied,file-indexed'
+//! ```
+//!
+//! [`ChatManager`](crate::ChatManager) publishes the plugins it executes and
+//! the edits they apply to the bus passed to
+//! [`ChatManager::with_events`](crate::ChatManager::with_events).
+//!
+//! Delivery is best effort: subscribers that fall more than `events.buffer`
+//! events behind miss the oldest ones, and events published while nobody
+//! listens are dropped.
+
+use crate::config::EventsConfig;
+use crate::feedback::unix_timestamp;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+/// How often an idle Server-Sent Events stream sends a comment, so proxies
+/// and clients don't time it out.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// Largest HTTP request head accepted by the Server-Sent Events endpoint.
+const MAX_HEAD_BYTES: usize = 8 * 1024;
+
+#[derive(Debug, Error)]
+pub enum EventError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Unknown event type '{0}' (expected one of: {known})", known = EventKind::NAMES.join(", "))]
+    UnknownKind(String),
+}
+
+pub type Result<T> = std::result::Result<T, EventError>;
+
+/// What happened.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum EventKind {
+    /// All chunks of a file (or page) were stored in the knowledge base
+    FileIndexed { source: String },
+    /// A chat-like request was answered
+    ChatCompleted {
+        /// ID to rate the response with; absent for private sessions and
+        /// library conversations
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        response_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_id: Option<String>,
+        duration_ms: u64,
+        /// Generation stopped at the request's time or token budget
+        #[serde(default)]
+        truncated: bool,
+    },
+    /// A plugin with write permission changed a file
+    EditApplied { path: String, plugin: String },
+    /// A plugin ran as a tool call
+    PluginExecuted {
+        plugin: String,
+        success: bool,
+        duration_ms: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+impl EventKind {
+    /// Names of the event types, as used in filters and the `event` field.
+    pub const NAMES: &'static [&'static str] = &["file-indexed", "chat-completed", "edit-applied", "plugin-executed"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::FileIndexed { .. } => "file-indexed",
+            EventKind::ChatCompleted { .. } => "chat-completed",
+            EventKind::EditApplied { .. } => "edit-applied",
+            EventKind::PluginExecuted { .. } => "plugin-executed",
+        }
+    }
+}
+
+/// An event as delivered to subscribers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkspaceEvent {
+    /// Increases by one per event published on the bus
+    pub id: u64,
+    /// Unix timestamp (seconds)
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// Broadcasts workspace events to every subscriber. Clones share the bus.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<WorkspaceEvent>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl EventBus {
+    /// A bus keeping up to `buffer` events for each subscriber.
+    pub fn new(buffer: usize) -> Self {
+        let (sender, _) = broadcast::channel(buffer.max(1));
+        Self {
+            sender,
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    pub fn from_config(config: &EventsConfig) -> Self {
+        Self::new(config.buffer)
+    }
+
+    /// Sends `kind` to the current subscribers, if any.
+    pub fn publish(&self, kind: EventKind) {
+        let event = WorkspaceEvent {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: unix_timestamp(),
+            kind,
+        };
+        debug!(event = event.kind.name(), id = event.id, "Publishing workspace event");
+        // Fails only when nobody listens
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribes to the events named in `filter`, a comma-separated list of
+    /// [`EventKind::NAMES`]; an empty filter receives every event.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EventError::UnknownKind`] for a name that is not an event type.
+    pub fn subscribe(&self, filter: &str) -> Result<Subscription> {
+        let kinds = filter
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                EventKind::NAMES
+                    .iter()
+                    .find(|&&known| known == name)
+                    .copied()
+                    .ok_or_else(|| EventError::UnknownKind(name.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Subscription {
+            receiver: self.sender.subscribe(),
+            kinds,
+        })
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::from_config(&EventsConfig::default())
+    }
+}
+
+/// Events published after [`EventBus::subscribe`], in order.
+pub struct Subscription {
+    receiver: broadcast::Receiver<WorkspaceEvent>,
+    /// Event types received; empty for all
+    kinds: Vec<&'static str>,
+}
+
+impl Subscription {
+    /// Waits for the next event, or `None` once the bus is gone.
+    pub async fn next(&mut self) -> Option<WorkspaceEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.kinds.is_empty() || self.kinds.contains(&event.kind.name()) => return Some(event),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Event subscriber fell behind and missed {} events (raise events.buffer)", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Serves `bus` as Server-Sent Events from `GET /events` on `address` until
+/// the listener fails.
+///
+/// `GET /events?types=edit-applied,file-indexed` receives only those event
+/// types. Each event is sent with its `id`, its type as the SSE event name,
+/// and the JSON event as data.
+///
+/// Requests whose `Host` is neither `address` nor a loopback name are
+/// refused, so a web page cannot read the stream by rebinding its own domain
+/// to this address.
+pub async fn serve_sse(bus: EventBus, address: &str) -> Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let bus = bus.clone();
+        let address = address.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = serve_client(stream, &bus, &address).await {
+                debug!("Event stream to {} ended: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn serve_client(mut stream: TcpStream, bus: &EventBus, address: &str) -> Result<()> {
+    let head = read_head(&mut stream).await?;
+    if !host_allowed(&head, address) {
+        return respond(&mut stream, "403 Forbidden", "unexpected Host").await;
+    }
+    let filter = match event_filter(&head) {
+        Ok(filter) => filter,
+        Err(status) => return respond(&mut stream, status, "").await,
+    };
+    let mut subscription = match bus.subscribe(filter) {
+        Ok(subscription) => subscription,
+        Err(e) => return respond(&mut stream, "400 Bad Request", &e.to_string()).await,
+    };
+
+    stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n")
+        .await?;
+    stream.flush().await?;
+
+    let mut keepalive = tokio::time::interval(KEEPALIVE);
+    keepalive.tick().await;
+    loop {
+        let frame = tokio::select! {
+            event = subscription.next() => match event {
+                Some(event) => sse_frame(&event),
+                None => return Ok(()),
+            },
+            _ = keepalive.tick() => ": keep-alive\n\n".to_string(),
+        };
+        stream.write_all(frame.as_bytes()).await?;
+        stream.flush().await?;
+    }
+}
+
+/// Reads the request line and headers; the endpoint takes no body.
+async fn read_head(stream: &mut TcpStream) -> Result<String> {
+    let mut head = Vec::new();
+    let mut buffer = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_HEAD_BYTES {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// The event filter of a `GET /events` request, or the status to answer
+/// any other request with.
+fn event_filter(head: &str) -> std::result::Result<&str, &'static str> {
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let (method, target) = (request_line.next(), request_line.next().unwrap_or_default());
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/events" {
+        return Err("404 Not Found");
+    }
+    if method != Some("GET") {
+        return Err("405 Method Not Allowed");
+    }
+    Ok(query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("types="))
+        .unwrap_or_default())
+}
+
+/// Whether the `Host` header of a request names `address` or the loopback
+/// interface; requests without one are refused.
+fn host_allowed(head: &str, address: &str) -> bool {
+    let Some(host) = head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("host").then(|| value.trim())
+    }) else {
+        return false;
+    };
+    if host.eq_ignore_ascii_case(address) {
+        return true;
+    }
+    // Strip the port, keeping IPv6 brackets
+    let name = match host.rfind(':') {
+        Some(colon) if !host[colon..].contains(']') => &host[..colon],
+        _ => host,
+    };
+    ["localhost", "127.0.0.1", "[::1]"].iter().any(|loopback| name.eq_ignore_ascii_case(loopback))
+}
+
+/// `event` in the Server-Sent Events format.
+fn sse_frame(event: &WorkspaceEvent) -> String {
+    let data = serde_json::to_string(event).unwrap_or_default();
+    format!("id: {}\nevent: {}\ndata: {}\n\n", event.id, event.kind.name(), data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribers_receive_filtered_events() {
+        let bus = EventBus::new(8);
+        // Nobody listens yet: dropped
+        bus.publish(EventKind::FileIndexed { source: "/src/old.rs".to_string() });
+
+        let mut all = bus.subscribe("").unwrap();
+        let mut edits = bus.subscribe("edit-applied, plugin-executed").unwrap();
+        assert!(matches!(bus.subscribe("file-changed"), Err(EventError::UnknownKind(name)) if name == "file-changed"));
+
+        bus.publish(EventKind::FileIndexed { source: "/src/main.rs".to_string() });
+        bus.publish(EventKind::EditApplied { path: "docs/index.md".to_string(), plugin: "write_file".to_string() });
+
+        let indexed = all.next().await.unwrap();
+        assert_eq!((indexed.id, indexed.kind.name()), (2, "file-indexed"));
+        assert_eq!(all.next().await.unwrap().id, 3);
+        let edit = edits.next().await.unwrap();
+        assert_eq!(edit.id, 3);
+
+        let json = serde_json::to_value(&edit).unwrap();
+        assert_eq!(json["event"], "edit-applied");
+        assert_eq!(json["path"], "docs/index.md");
+        assert!(sse_frame(&edit).starts_with("id: 3\nevent: edit-applied\ndata: {"));
+    }
+
+    #[test]
+    fn test_event_filter_of_requests() {
+        let head = "GET /events?types=edit-applied,file-indexed HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert_eq!(event_filter(head), Ok("edit-applied,file-indexed"));
+        assert_eq!(event_filter("GET /events HTTP/1.1\r\n\r\n"), Ok(""));
+        assert_eq!(event_filter("GET / HTTP/1.1\r\n\r\n"), Err("404 Not Found"));
+        assert_eq!(event_filter("POST /events HTTP/1.1\r\n\r\n"), Err("405 Method Not Allowed"));
+    }
+
+    #[test]
+    fn test_host_allowed() {
+        let head = |host: &str| format!("GET /events HTTP/1.1\r\n{}\r\n\r\n", host);
+        let address = "192.168.1.20:7879";
+        assert!(host_allowed(&head("Host: 192.168.1.20:7879"), address));
+        assert!(host_allowed(&head("host: localhost:7879"), address));
+        assert!(host_allowed(&head("Host: 127.0.0.1"), address));
+        assert!(host_allowed(&head("Host: [::1]:7879"), address));
+        assert!(!host_allowed(&head("Host: attacker.example:7879"), address));
+        assert!(!host_allowed(&head("Host: localhost.attacker.example"), address));
+        assert!(!host_allowed(&head("Accept: */*"), address));
+    }
+}
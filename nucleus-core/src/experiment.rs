@@ -0,0 +1,134 @@
+//! A/B experiments over chat settings.
+//!
+//! Each configured experiment receives a percentage of chat requests and
+//! overrides some settings (retrieval `top_k`, reranking, model, temperature).
+//! Responses are tagged with the experiment name so feedback can be compared
+//! per configuration; unrouted requests are tagged [`CONTROL`].
+
+use crate::config::{Config, ExperimentConfig};
+use crate::rag::RetrievalOptions;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use tracing::warn;
+
+/// Tag for requests that use the regular configuration.
+pub const CONTROL: &str = "control";
+
+/// Settings resolved for a single request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    /// Experiment name, or [`CONTROL`]
+    pub name: String,
+    pub model: String,
+    pub temperature: f64,
+    pub retrieval: RetrievalOptions,
+}
+
+/// Assigns requests to experiment arms.
+#[derive(Debug, Clone)]
+pub struct ExperimentRouter {
+    experiments: Vec<ExperimentConfig>,
+}
+
+impl ExperimentRouter {
+    pub fn new(experiments: Vec<ExperimentConfig>) -> Self {
+        let total: f64 = experiments.iter().map(|e| e.percentage.max(0.0)).sum();
+        if total > 100.0 {
+            warn!("Experiment percentages add up to {}%; later experiments will receive less traffic", total);
+        }
+
+        Self { experiments }
+    }
+
+    /// Returns true if any experiment is configured.
+    pub fn is_active(&self) -> bool {
+        self.experiments.iter().any(|e| e.percentage > 0.0)
+    }
+
+    /// Picks the settings for a new request.
+    ///
+    /// `defaults` are the regular retrieval settings used by the control arm.
+    pub fn assign(&self, config: &Config, defaults: RetrievalOptions) -> Variant {
+        let roll = if self.is_active() { random_percentage() } else { 100.0 };
+        self.assign_with_roll(config, defaults, roll)
+    }
+
+    /// Picks settings for a roll in `[0, 100)`; experiments claim consecutive ranges.
+    fn assign_with_roll(&self, config: &Config, defaults: RetrievalOptions, roll: f64) -> Variant {
+        let mut threshold = 0.0;
+        for experiment in &self.experiments {
+            threshold += experiment.percentage.max(0.0);
+            if roll < threshold {
+                return Variant {
+                    name: experiment.name.clone(),
+                    model: experiment.model.clone().unwrap_or_else(|| config.llm.model.clone()),
+                    temperature: experiment.temperature.unwrap_or(config.llm.temperature),
+                    retrieval: RetrievalOptions {
+                        top_k: experiment.top_k.unwrap_or(defaults.top_k),
+                        rerank: experiment.rerank.unwrap_or(defaults.rerank),
+                        ..defaults
+                    },
+                };
+            }
+        }
+
+        Variant {
+            name: CONTROL.to_string(),
+            model: config.llm.model.clone(),
+            temperature: config.llm.temperature,
+            retrieval: defaults,
+        }
+    }
+}
+
+/// Returns a pseudo-random value in `[0, 100)`.
+///
+/// `RandomState` is seeded randomly per instance, which is plenty for traffic splitting.
+fn random_percentage() -> f64 {
+    let value = RandomState::new().build_hasher().finish();
+    (value % 10_000) as f64 / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn experiment(name: &str, percentage: f64) -> ExperimentConfig {
+        ExperimentConfig {
+            name: name.to_string(),
+            percentage,
+            top_k: Some(10),
+            rerank: Some(true),
+            model: None,
+            temperature: None,
+        }
+    }
+
+    #[test]
+    fn test_assign_with_roll() {
+        let config = Config::default();
+        let defaults = RetrievalOptions { top_k: 5, rerank: false, include_team: true, hybrid: false, cross_encoder: true, diversify: true, filter: None };
+        let router = ExperimentRouter::new(vec![experiment("wide", 20.0), experiment("narrow", 30.0)]);
+
+        let wide = router.assign_with_roll(&config, defaults.clone(), 10.0);
+        assert_eq!(wide.name, "wide");
+        assert_eq!(wide.retrieval, RetrievalOptions { top_k: 10, rerank: true, include_team: true, hybrid: false, cross_encoder: true, diversify: true, filter: None });
+        assert_eq!(wide.model, config.llm.model);
+
+        assert_eq!(router.assign_with_roll(&config, defaults.clone(), 35.0).name, "narrow");
+
+        let control = router.assign_with_roll(&config, defaults.clone(), 75.0);
+        assert_eq!(control.name, CONTROL);
+        assert_eq!(control.retrieval, defaults);
+    }
+
+    #[test]
+    fn test_no_experiments_is_control() {
+        let config = Config::default();
+        let defaults = RetrievalOptions { top_k: 5, rerank: false, include_team: true, hybrid: false, cross_encoder: true, diversify: true, filter: None };
+        let router = ExperimentRouter::new(Vec::new());
+
+        assert!(!router.is_active());
+        assert_eq!(router.assign(&config, defaults).name, CONTROL);
+    }
+}
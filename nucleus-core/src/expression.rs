@@ -0,0 +1,419 @@
+//! Regex and jq expressions generated from a description.
+//!
+//! The LLM writes an expression for a natural-language description and a
+//! sample of the input. Before it is returned, the expression is compiled and
+//! run on the sample here (with the `regex` crate or jaq), so callers never
+//! get an expression that does not parse or does not match. When validation
+//! fails, the error is sent back and the LLM tries again.
+
+use crate::config::{Config, ExpressionConfig};
+use crate::provider::{ChatRequest, Message, Provider};
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{debug, info};
+
+/// Outputs kept from running an expression on the sample.
+pub const MAX_OUTPUTS: usize = 20;
+
+/// Language of a generated expression.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpressionKind {
+    /// Rust `regex` crate syntax
+    #[default]
+    Regex,
+    /// jq filter (run with jaq)
+    Jq,
+}
+
+impl fmt::Display for ExpressionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Regex => write!(f, "regex"),
+            Self::Jq => write!(f, "jq"),
+        }
+    }
+}
+
+/// Reason an expression was rejected.
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("Invalid regex: {0}")]
+    InvalidRegex(String),
+
+    #[error("The regex does not match anything in the sample")]
+    NoMatch,
+
+    #[error("The sample is not JSON: {0}")]
+    InvalidSample(String),
+
+    #[error("Invalid jq filter: {0}")]
+    InvalidJq(String),
+
+    #[error("The jq filter failed on the sample: {0}")]
+    JqRuntime(String),
+
+    #[error("The jq filter produced no output for the sample")]
+    NoOutput,
+}
+
+/// Validates `expression` against `sample`, returning what it produced: the
+/// matches of a regex, or the outputs of a jq filter as JSON.
+///
+/// At most [`MAX_OUTPUTS`] outputs are returned.
+///
+/// # Errors
+///
+/// Returns an error if the expression does not compile, fails on the sample,
+/// or produces nothing from it.
+pub fn validate(kind: ExpressionKind, expression: &str, sample: &str) -> Result<Vec<String>, ValidationError> {
+    match kind {
+        ExpressionKind::Regex => validate_regex(expression, sample),
+        ExpressionKind::Jq => validate_jq(expression, sample),
+    }
+}
+
+fn validate_regex(expression: &str, sample: &str) -> Result<Vec<String>, ValidationError> {
+    let regex = Regex::new(expression).map_err(|e| ValidationError::InvalidRegex(e.to_string()))?;
+    let matches: Vec<String> = regex
+        .find_iter(sample)
+        .filter(|m| !m.is_empty())
+        .take(MAX_OUTPUTS)
+        .map(|m| m.as_str().to_string())
+        .collect();
+
+    if matches.is_empty() {
+        return Err(ValidationError::NoMatch);
+    }
+    Ok(matches)
+}
+
+fn validate_jq(expression: &str, sample: &str) -> Result<Vec<String>, ValidationError> {
+    use jaq_core::load::{Arena, File, Loader};
+    use jaq_core::{Compiler, Ctx, RcIter};
+    use jaq_json::Val;
+
+    // A single document or JSON lines
+    let inputs = serde_json::Deserializer::from_str(sample)
+        .into_iter::<serde_json::Value>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ValidationError::InvalidSample(e.to_string()))?;
+    if inputs.is_empty() {
+        return Err(ValidationError::InvalidSample("the sample is empty".to_string()));
+    }
+
+    let arena = Arena::default();
+    let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+    let modules = loader
+        .load(&arena, File { code: expression, path: () })
+        .map_err(|errors| ValidationError::InvalidJq(describe_load_errors(&errors)))?;
+    let filter = Compiler::default()
+        .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+        .compile(modules)
+        .map_err(|errors| {
+            let undefined: Vec<String> = errors
+                .iter()
+                .flat_map(|(_, errors)| errors)
+                .map(|(name, undefined)| format!("undefined {} `{}`", undefined.as_str(), name))
+                .collect();
+            ValidationError::InvalidJq(undefined.join(", "))
+        })?;
+
+    let mut outputs = Vec::new();
+    let no_inputs = RcIter::new(core::iter::empty());
+    for input in inputs {
+        let remaining = MAX_OUTPUTS - outputs.len();
+        for output in filter.run((Ctx::new([], &no_inputs), Val::from(input))).take(remaining) {
+            let output = output.map_err(|e| ValidationError::JqRuntime(e.to_string()))?;
+            outputs.push(output.to_string());
+        }
+        if outputs.len() == MAX_OUTPUTS {
+            break;
+        }
+    }
+
+    if outputs.is_empty() {
+        return Err(ValidationError::NoOutput);
+    }
+    Ok(outputs)
+}
+
+/// Describes jq syntax errors as "expected X at `Y`".
+fn describe_load_errors(errors: &jaq_core::load::Errors<&str, ()>) -> String {
+    use jaq_core::load::Error;
+
+    // Only the start of the unparsed input is worth showing
+    let near = |rest: &str| rest.chars().take(20).collect::<String>();
+    let messages: Vec<String> = errors
+        .iter()
+        .flat_map(|(_, error)| match error {
+            Error::Io(errors) => errors.iter().map(|(path, e)| format!("{}: {}", path, e)).collect(),
+            Error::Lex(errors) => errors
+                .iter()
+                .map(|(expected, rest)| format!("expected {} at `{}`", expected.as_str(), near(rest)))
+                .collect(),
+            Error::Parse(errors) => errors
+                .iter()
+                .map(|(expected, rest)| format!("expected {} at `{}`", expected.as_str(), near(rest)))
+                .collect::<Vec<_>>(),
+        })
+        .collect();
+    messages.join(", ")
+}
+
+/// Takes the expression out of an LLM response: the first fenced code block,
+/// or else the first non-empty line.
+pub fn extract_expression(response: &str) -> Option<String> {
+    let expression = match response.split_once("```") {
+        Some((_, rest)) => {
+            // Skip the language tag on the opening fence
+            let (_, code) = rest.split_once('\n').unwrap_or(("", rest));
+            code.split("```").next().unwrap_or(code).trim()
+        }
+        None => response.lines().map(str::trim).find(|line| !line.is_empty())?,
+    };
+    let expression = expression.trim_matches('`').trim();
+    (!expression.is_empty()).then(|| expression.to_string())
+}
+
+/// An expression that passed validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generated {
+    pub expression: String,
+    /// What the expression produced from the sample (see [`validate`])
+    pub outputs: Vec<String>,
+    /// Generations it took, counting from 1
+    pub attempts: u32,
+}
+
+/// Generates expressions, retrying until one validates.
+pub struct ExpressionGenerator {
+    provider: Arc<dyn Provider>,
+    config: ExpressionConfig,
+    model: String,
+    temperature: f64,
+}
+
+impl ExpressionGenerator {
+    pub fn new(provider: Arc<dyn Provider>, config: &Config) -> Self {
+        Self {
+            provider,
+            config: config.expression.clone(),
+            model: config.llm.model.clone(),
+            temperature: config.llm.temperature,
+        }
+    }
+
+    /// Generates a `kind` expression for `description` that works on `sample`.
+    ///
+    /// `on_failure` is called with each rejected expression and the reason.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an LLM request fails, or if no expression validates
+    /// within `max_attempts`.
+    pub async fn generate<F>(
+        &self,
+        kind: ExpressionKind,
+        description: &str,
+        sample: &str,
+        mut on_failure: F,
+    ) -> Result<Generated>
+    where
+        F: FnMut(&str, &str) + Send,
+    {
+        let mut messages = vec![
+            Message::system(None, system_prompt(kind)),
+            Message::user(None, generation_prompt(kind, description, sample, self.config.sample_chars)),
+        ];
+
+        let mut last_error = String::new();
+        for attempt in 1..=self.config.max_attempts.max(1) {
+            let response = self.complete(messages.clone()).await?;
+            messages.push(Message::assistant(None, &response));
+
+            let Some(expression) = extract_expression(&response) else {
+                last_error = "the response contained no expression".to_string();
+                on_failure("", &last_error);
+                messages.push(Message::user(None, "Reply with only the expression in a code block."));
+                continue;
+            };
+
+            match validate(kind, &expression, sample) {
+                Ok(outputs) => {
+                    info!(%kind, attempt, "Generated expression");
+                    return Ok(Generated {
+                        expression,
+                        outputs,
+                        attempts: attempt,
+                    });
+                }
+                Err(e) => {
+                    debug!(%kind, attempt, expression, "Expression rejected: {}", e);
+                    last_error = e.to_string();
+                    on_failure(&expression, &last_error);
+                    messages.push(Message::user(
+                        None,
+                        format!("{}\nFix the expression and reply with only the corrected expression in a code block.", e),
+                    ));
+                }
+            }
+        }
+
+        bail!(
+            "No valid {} after {} attempt(s); last error: {}",
+            kind,
+            self.config.max_attempts.max(1),
+            last_error
+        )
+    }
+
+    async fn complete(&self, messages: Vec<Message>) -> Result<String> {
+        let request = ChatRequest::new(&self.model, messages).with_temperature(self.temperature);
+
+        let mut content = String::new();
+        self.provider
+            .chat(request, Box::new(|response| content.push_str(&response.content)))
+            .await
+            .context("Failed to generate expression")?;
+        Ok(content)
+    }
+}
+
+fn system_prompt(kind: ExpressionKind) -> &'static str {
+    match kind {
+        ExpressionKind::Regex => {
+            "You write regular expressions for the Rust `regex` crate. It has no lookaround or \
+             backreferences; use inline flags such as (?m) or (?i) when needed. \
+             Reply with only the regex in a single code block, without delimiters or quotes."
+        }
+        ExpressionKind::Jq => {
+            "You write jq filters. Prefer standard jq builtins. \
+             Reply with only the filter in a single code block, without shell quoting."
+        }
+    }
+}
+
+/// Builds the first request, with the sample cut to `sample_chars`.
+fn generation_prompt(kind: ExpressionKind, description: &str, sample: &str, sample_chars: usize) -> String {
+    let shown: String = sample.chars().take(sample_chars).collect();
+    let note = if shown.len() < sample.len() { " (truncated)" } else { "" };
+    format!(
+        "Write a {} that does this: {}\n\nSample input{}:\n```\n{}\n```",
+        kind,
+        description.trim(),
+        note,
+        shown.trim_end()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EmbeddingModel;
+    use crate::provider::ChatResponse;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Replies with canned responses in order, recording the last prompt.
+    struct ScriptedProvider {
+        replies: Mutex<Vec<&'static str>>,
+        last_prompt: Mutex<String>,
+    }
+
+    #[async_trait]
+    impl Provider for ScriptedProvider {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> crate::provider::Result<()> {
+            *self.last_prompt.lock().unwrap() = request.messages.last().unwrap().content.clone();
+            let reply = self.replies.lock().unwrap().remove(0);
+            callback(ChatResponse {
+                model: request.model,
+                content: reply.to_string(),
+                done: true,
+                truncated: false,
+                message: Message::assistant(None, reply),
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    fn generator(replies: Vec<&'static str>) -> (Arc<ScriptedProvider>, ExpressionGenerator) {
+        let provider = Arc::new(ScriptedProvider {
+            replies: Mutex::new(replies),
+            last_prompt: Mutex::new(String::new()),
+        });
+        (provider.clone(), ExpressionGenerator::new(provider, &Config::default()))
+    }
+
+    #[tokio::test]
+    async fn test_retries_with_validation_error() {
+        let (provider, generator) = generator(vec!["```\n(?<=id=)\\d+\n```", "```\nid=(\\d+)\n```"]);
+        let mut failures = Vec::new();
+        let generated = generator
+            .generate(ExpressionKind::Regex, "user ids", "id=7 id=42", |expression, _| {
+                failures.push(expression.to_string())
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(generated.expression, r"id=(\d+)");
+        assert_eq!(generated.outputs, vec!["id=7", "id=42"]);
+        assert_eq!(generated.attempts, 2);
+        assert_eq!(failures, vec![r"(?<=id=)\d+"]);
+        assert!(provider.last_prompt.lock().unwrap().starts_with("Invalid regex"));
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let (_, generator) = generator(vec!["`.missing[`", "`.missing[`", "`.missing[`"]);
+        let error = generator
+            .generate(ExpressionKind::Jq, "names", r#"{"name": "a"}"#, |_, _| {})
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("No valid jq after 3 attempt(s)"));
+    }
+
+    #[test]
+    fn test_validate_regex() {
+        let sample = "GET /a 200\nPOST /b 500\nGET /c 503\n";
+        let matches = validate(ExpressionKind::Regex, r"\b5\d\d\b", sample).unwrap();
+        assert_eq!(matches, vec!["500", "503"]);
+
+        assert!(matches!(validate(ExpressionKind::Regex, r"(?<=GET )/\w", sample), Err(ValidationError::InvalidRegex(_))));
+        assert!(matches!(validate(ExpressionKind::Regex, r"\b404\b", sample), Err(ValidationError::NoMatch)));
+    }
+
+    #[test]
+    fn test_validate_jq() {
+        let sample = r#"{"name": "a", "ok": true}
+{"name": "b", "ok": false}"#;
+        let outputs = validate(ExpressionKind::Jq, "select(.ok | not) | .name", sample).unwrap();
+        assert_eq!(outputs, vec![r#""b""#]);
+
+        assert!(matches!(validate(ExpressionKind::Jq, ".name |", sample), Err(ValidationError::InvalidJq(_))));
+        assert!(matches!(validate(ExpressionKind::Jq, "nosuchfn", sample), Err(ValidationError::InvalidJq(_))));
+        assert!(matches!(validate(ExpressionKind::Jq, ".name + 1", sample), Err(ValidationError::JqRuntime(_))));
+        assert!(matches!(validate(ExpressionKind::Jq, "empty", sample), Err(ValidationError::NoOutput)));
+        assert!(matches!(validate(ExpressionKind::Jq, ".", "not json"), Err(ValidationError::InvalidSample(_))));
+    }
+
+    #[test]
+    fn test_extract_expression() {
+        assert_eq!(extract_expression("```regex\n^\\d+$\n```").as_deref(), Some("^\\d+$"));
+        assert_eq!(extract_expression("Here you go:\n```\n.items[]\n```\nThis lists items.").as_deref(), Some(".items[]"));
+        assert_eq!(extract_expression("\n`.name`\n").as_deref(), Some(".name"));
+        assert_eq!(extract_expression("  \n"), None);
+    }
+}
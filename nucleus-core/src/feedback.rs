@@ -0,0 +1,355 @@
+//! Response feedback capture for building evaluation sets.
+//!
+//! Every chat response is assigned an ID and remembered for a while together
+//! with its prompt, the retrieved knowledge base chunks, and the model
+//! settings. When a user rates a response (thumbs up or down), the full
+//! interaction is appended to a JSONL file. The file can later be exported,
+//! optionally filtered by rating, to build eval sets from real failures.
+//!
+//! Interactions are tagged with the experiment variant that produced them, so
+//! ratings can be compared per configuration (see [`crate::experiment`]).
+
+use crate::experiment::CONTROL;
+use crate::rag::SearchResult;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+
+/// Number of recent interactions that can still receive feedback.
+const MAX_RECENT_INTERACTIONS: usize = 256;
+
+#[derive(Debug, Error)]
+pub enum FeedbackError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Unknown or expired response ID '{0}'")]
+    UnknownResponse(String),
+}
+
+pub type Result<T> = std::result::Result<T, FeedbackError>;
+
+/// Thumbs up or down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rating {
+    Up,
+    Down,
+}
+
+/// A knowledge base chunk that was added to the prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievedChunk {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub score: f32,
+    pub content: String,
+}
+
+impl From<&SearchResult> for RetrievedChunk {
+    fn from(result: &SearchResult) -> Self {
+        Self {
+            source: result.document.metadata.get("source").cloned(),
+            score: result.score,
+            content: result.document.content.clone(),
+        }
+    }
+}
+
+/// Everything needed to reproduce a response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub response_id: String,
+    /// Unix timestamp (seconds) of the response
+    pub timestamp: u64,
+    pub prompt: String,
+    pub response: String,
+    pub model: String,
+    pub temperature: f64,
+    pub embedding_model: String,
+    #[serde(default)]
+    pub retrieved: Vec<RetrievedChunk>,
+    /// Experiment variant that produced the response
+    #[serde(default = "default_variant")]
+    pub variant: String,
+}
+
+fn default_variant() -> String {
+    CONTROL.to_string()
+}
+
+/// Rating counts for one experiment variant.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VariantSummary {
+    pub variant: String,
+    pub up: usize,
+    pub down: usize,
+}
+
+impl VariantSummary {
+    /// Share of ratings that were thumbs up, from 0.0 to 1.0.
+    pub fn approval_rate(&self) -> f64 {
+        let total = self.up + self.down;
+        if total == 0 {
+            0.0
+        } else {
+            self.up as f64 / total as f64
+        }
+    }
+}
+
+/// A rated interaction, as stored in the feedback file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackRecord {
+    pub rating: Rating,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Unix timestamp (seconds) of the feedback
+    pub rated_at: u64,
+    #[serde(flatten)]
+    pub interaction: Interaction,
+}
+
+/// Remembers recent interactions and persists feedback for them.
+#[derive(Debug)]
+pub struct FeedbackStore {
+    path: PathBuf,
+    recent: Mutex<VecDeque<Interaction>>,
+}
+
+impl FeedbackStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Remembers an interaction so it can be rated later.
+    ///
+    /// Only the most recent interactions are kept; nothing is written to disk
+    /// until feedback is given.
+    pub fn record(&self, interaction: Interaction) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() >= MAX_RECENT_INTERACTIONS {
+            recent.pop_front();
+        }
+        recent.push_back(interaction);
+    }
+
+    /// Looks up a recent interaction by its response ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the response ID is unknown or has been evicted.
+    pub fn find(&self, response_id: &str) -> Result<Interaction> {
+        self.recent
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|interaction| interaction.response_id == response_id)
+            .cloned()
+            .ok_or_else(|| FeedbackError::UnknownResponse(response_id.to_string()))
+    }
+
+    /// Rates a recent response and appends it to the feedback file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the response ID is unknown or has been evicted,
+    /// or if the feedback file cannot be written.
+    pub async fn submit(
+        &self,
+        response_id: &str,
+        rating: Rating,
+        comment: Option<String>,
+    ) -> Result<FeedbackRecord> {
+        let interaction = self.find(response_id)?;
+
+        let record = FeedbackRecord {
+            rating,
+            comment: comment.filter(|c| !c.trim().is_empty()),
+            rated_at: unix_timestamp(),
+            interaction,
+        };
+
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+
+        Ok(record)
+    }
+
+    /// Reads all feedback records, optionally keeping only one rating.
+    pub async fn load(&self, rating: Option<Rating>) -> Result<Vec<FeedbackRecord>> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut records = Vec::new();
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let record: FeedbackRecord = serde_json::from_str(line)?;
+            if rating.is_none_or(|rating| record.rating == rating) {
+                records.push(record);
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Counts ratings per experiment variant, sorted by variant name.
+    pub async fn summarize(&self) -> Result<Vec<VariantSummary>> {
+        let mut summaries: BTreeMap<String, VariantSummary> = BTreeMap::new();
+
+        for record in self.load(None).await? {
+            let variant = record.interaction.variant;
+            let summary = summaries.entry(variant.clone()).or_insert_with(|| VariantSummary {
+                variant,
+                ..VariantSummary::default()
+            });
+            match record.rating {
+                Rating::Up => summary.up += 1,
+                Rating::Down => summary.down += 1,
+            }
+        }
+
+        Ok(summaries.into_values().collect())
+    }
+
+    /// Writes feedback records as an eval set (one JSON object per line).
+    ///
+    /// # Returns
+    ///
+    /// The number of records exported.
+    pub async fn export(&self, path: &Path, rating: Option<Rating>) -> Result<usize> {
+        let records = self.load(rating).await?;
+
+        let mut output = String::new();
+        for record in &records {
+            output.push_str(&serde_json::to_string(record)?);
+            output.push('\n');
+        }
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, output).await?;
+
+        Ok(records.len())
+    }
+}
+
+/// Generates a short, unique response ID.
+pub fn new_response_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let sequence = COUNTER.fetch_add(1, Ordering::Relaxed) & 0xffff;
+
+    format!("r{:x}{:04x}", millis, sequence)
+}
+
+pub(crate) fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interaction(response_id: &str) -> Interaction {
+        Interaction {
+            response_id: response_id.to_string(),
+            timestamp: 0,
+            prompt: "How do I list files?".to_string(),
+            response: "Use rm -rf".to_string(),
+            model: "test-model".to_string(),
+            temperature: 0.6,
+            embedding_model: "test-embed".to_string(),
+            retrieved: vec![],
+            variant: CONTROL.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit_and_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FeedbackStore::new(dir.path().join("feedback.jsonl"));
+        store.record(interaction("a"));
+        store.record(interaction("b"));
+
+        store.submit("a", Rating::Down, Some("Wrong command".to_string())).await.unwrap();
+        store.submit("b", Rating::Up, None).await.unwrap();
+
+        let down = store.load(Some(Rating::Down)).await.unwrap();
+        assert_eq!(down.len(), 1);
+        assert_eq!(down[0].interaction.response_id, "a");
+        assert_eq!(down[0].comment.as_deref(), Some("Wrong command"));
+
+        let export_path = dir.path().join("evals/failures.jsonl");
+        assert_eq!(store.export(&export_path, None).await.unwrap(), 2);
+        let exported = std::fs::read_to_string(export_path).unwrap();
+        assert_eq!(exported.lines().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_summarize_by_variant() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FeedbackStore::new(dir.path().join("feedback.jsonl"));
+        store.record(interaction("a"));
+        store.record(Interaction {
+            variant: "rerank".to_string(),
+            ..interaction("b")
+        });
+
+        store.submit("a", Rating::Down, None).await.unwrap();
+        store.submit("b", Rating::Up, None).await.unwrap();
+        store.submit("b", Rating::Up, None).await.unwrap();
+
+        let summaries = store.summarize().await.unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].variant, CONTROL);
+        assert_eq!(summaries[0].approval_rate(), 0.0);
+        assert_eq!(summaries[1], VariantSummary { variant: "rerank".to_string(), up: 2, down: 0 });
+    }
+
+    #[tokio::test]
+    async fn test_unknown_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FeedbackStore::new(dir.path().join("feedback.jsonl"));
+
+        let result = store.submit("missing", Rating::Up, None).await;
+        assert!(matches!(result, Err(FeedbackError::UnknownResponse(_))));
+    }
+
+    #[test]
+    fn test_response_ids_are_unique() {
+        assert_ne!(new_response_id(), new_response_id());
+    }
+}
@@ -0,0 +1,154 @@
+//! Tauri command handlers for desktop GUIs.
+//!
+//! Exposes [`ChatManager`] operations to a Tauri frontend, so a GUI runs on
+//! the same session and RAG logic as every other client. Put the manager in
+//! Tauri's state and register the commands:
+//!
+//! ```ignore
+//! let manager = ChatManager::new(config, registry).await?;
+//!
+//! tauri::Builder::default()
+//!     .manage(manager)
+//!     .invoke_handler(nucleus_core::gui::invoke_handler(tauri::generate_handler![my_command]))
+//!     .run(tauri::generate_context!())?;
+//! ```
+//!
+//! From the frontend, stream a chat by listening for [`CHUNK_EVENT`] before
+//! invoking `nucleus_chat`:
+//!
+//! ```js
+//! const requestId = crypto.randomUUID();
+//! const unlisten = await listen("nucleus://chunk", (e) => {
+//!   if (e.payload.requestId === requestId) append(e.payload.chunk);
+//! });
+//! const answer = await invoke("nucleus_chat", { prompt, requestId });
+//! unlisten();
+//! ```
+//!
+//! Commands reject with the error message as a string.
+
+use crate::rag::SearchResult;
+use crate::server::SearchHit;
+use crate::ChatManager;
+use serde::{Serialize, Serializer};
+use std::path::Path;
+use tauri::ipc::{Invoke, InvokeHandler};
+use tauri::{AppHandle, Emitter, Runtime, State};
+use thiserror::Error;
+use tracing::warn;
+
+/// Event carrying partial chat output, with a [`ChunkEvent`] payload.
+pub const CHUNK_EVENT: &str = "nucleus://chunk";
+
+/// Names of the commands registered by [`invoke_handler`].
+pub const COMMANDS: &[&str] = &["nucleus_chat", "nucleus_search", "nucleus_index", "nucleus_count"];
+
+/// Payload of [`CHUNK_EVENT`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkEvent {
+    /// ID passed to `nucleus_chat` by the frontend
+    pub request_id: String,
+    pub chunk: String,
+}
+
+/// Error returned to the frontend, serialized as its message.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct CommandError(String);
+
+impl From<anyhow::Error> for CommandError {
+    fn from(error: anyhow::Error) -> Self {
+        Self(format!("{:#}", error))
+    }
+}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, CommandError>;
+
+/// Returns an invoke handler serving the nucleus commands, passing every
+/// other command to `app_handler`.
+///
+/// Tauri accepts a single invoke handler, so the app's own commands go
+/// through this one.
+pub fn invoke_handler<R, F>(app_handler: F) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static
+where
+    R: Runtime,
+    F: Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+{
+    // Boxing gives the generated closure its argument type
+    let nucleus_handler: Box<InvokeHandler<R>> =
+        Box::new(tauri::generate_handler![nucleus_chat, nucleus_search, nucleus_index, nucleus_count]);
+
+    move |invoke| {
+        if COMMANDS.contains(&invoke.message.command()) {
+            nucleus_handler(invoke)
+        } else {
+            app_handler(invoke)
+        }
+    }
+}
+
+/// Asks a question, emitting [`CHUNK_EVENT`]s as the answer is generated.
+///
+/// Resolves with the complete answer.
+#[tauri::command]
+pub async fn nucleus_chat<R: Runtime>(
+    app: AppHandle<R>,
+    manager: State<'_, ChatManager>,
+    prompt: String,
+    request_id: String,
+) -> Result<String> {
+    let answer = manager
+        .query_stream(&prompt, |chunk| {
+            let event = ChunkEvent {
+                request_id: request_id.clone(),
+                chunk: chunk.to_string(),
+            };
+            if let Err(e) = app.emit(CHUNK_EVENT, event) {
+                warn!("Failed to emit chat chunk: {}", e);
+            }
+        })
+        .await?;
+
+    Ok(answer)
+}
+
+/// Searches the knowledge base without asking the LLM, best match first.
+#[tauri::command]
+pub async fn nucleus_search(
+    manager: State<'_, ChatManager>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>> {
+    let results: Vec<SearchResult> = manager.search(&query, limit).await?;
+    Ok(results.into_iter().map(SearchHit::from).collect())
+}
+
+/// Indexes a directory, resolving with the number of files indexed.
+#[tauri::command]
+pub async fn nucleus_index(manager: State<'_, ChatManager>, path: String) -> Result<usize> {
+    Ok(manager.index_directory(Path::new(&path)).await?)
+}
+
+/// Resolves with the number of documents in the knowledge base.
+#[tauri::command]
+pub async fn nucleus_count(manager: State<'_, ChatManager>) -> Result<usize> {
+    Ok(manager.knowledge_base_count().await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_serialize_as_message() {
+        let error = CommandError::from(anyhow::anyhow!("not found").context("indexing /tmp/x"));
+        assert_eq!(serde_json::to_string(&error).unwrap(), r#""indexing /tmp/x: not found""#);
+    }
+}
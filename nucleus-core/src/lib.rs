@@ -11,20 +11,56 @@
 //! Users should interact with nucleus via the `Server` API.
 
 // Public modules
+pub mod attachment;
+pub mod changes;
 pub mod chat;
+pub mod circuit;
+pub mod client;
+pub mod command_docs;
 pub mod config;
+pub mod conversations;
 pub mod detection;
+pub mod diff;
+pub mod dotfiles;
+pub mod egress;
+pub mod environment;
+pub mod events;
+pub mod experiment;
+pub mod expression;
+pub mod feedback;
+#[cfg(feature = "tauri")]
+pub mod gui;
+pub mod guardrails;
+pub mod log_analysis;
+pub mod memory;
 pub mod models;
+pub mod notify;
 pub mod patterns;
+pub mod plugins;
+pub mod project_tree;
 pub mod provider;
 pub mod qdrant_helper;
 pub mod rag;
+pub mod render;
+pub mod request_log;
+pub mod response_log;
 pub mod server;
+pub mod shell_integration;
+pub mod text;
+pub mod todos;
+pub mod update;
+pub mod warmup;
+
+/// Version of nucleus, reported by the server so clients can detect a mismatch.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 // Public exports
-pub use chat::{ChatManager, ChatManagerBuilder};
-pub use config::{Config, IndexerConfig};
+pub use chat::{ChatManager, ChatManagerBuilder, SessionChat};
+pub use client::AiClient;
+pub use config::{Config, IndexerConfig, ProviderKind};
 pub use detection::{check_ollama_silent, detect_ollama, DetectionError, OllamaInfo};
+pub use feedback::{FeedbackStore, Rating};
+pub use notify::{Notifier, OperationEvent, OperationKind};
 pub use rag::RagEngine;
 pub use server::Server;
 
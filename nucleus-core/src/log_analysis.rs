@@ -0,0 +1,473 @@
+//! Map-reduce analysis of large log files.
+//!
+//! A log too large for one prompt is split into chunks at line boundaries.
+//! Each chunk is summarized on its own (map), then the summaries are merged
+//! into one report (reduce). Error lines are also clustered without the LLM,
+//! so counts and timestamps in the report are exact.
+//!
+//! ```text
+//! log → chunks → summaries (in parallel) → merged batches → final report
+//!    └→ error clusters ─────────────────────────────────────────┘
+//! ```
+
+use crate::config::{Config, LogAnalysisConfig};
+use crate::provider::{ChatRequest, Message, Provider};
+use anyhow::{Context, Result};
+use futures::{StreamExt, TryStreamExt};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use tracing::{debug, info};
+
+/// System prompt for every analysis request.
+const ANALYST_PROMPT: &str = "You are an experienced site reliability engineer analyzing logs. \
+                              Be concise and factual; quote timestamps exactly as they appear.";
+
+/// Longest example line shown for an error cluster.
+const MAX_EXAMPLE_CHARS: usize = 160;
+
+static TIMESTAMP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        // ISO 8601 / RFC 3339
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+        // syslog
+        r"|[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}",
+        // Apache/nginx access logs
+        r"|\d{2}/[A-Z][a-z]{2}/\d{4}:\d{2}:\d{2}:\d{2}(?: [+-]\d{4})?",
+    ))
+    .expect("valid timestamp pattern")
+});
+
+static ERROR_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:error|fatal|critical|panic(?:ked)?|exception|traceback|failed|failure)\b")
+        .expect("valid error pattern")
+});
+
+static WARNING_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bwarn(?:ing)?\b").expect("valid warning pattern"));
+
+/// IDs, addresses, and numbers that differ between occurrences of the same error.
+static VARIABLE_PART: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[0-9a-fA-F]{8}(?:-[0-9a-fA-F]{4}){3}-[0-9a-fA-F]{12}|0x[0-9a-fA-F]+|\b[0-9a-fA-F]{12,}\b|\d+")
+        .expect("valid variable pattern")
+});
+
+/// A run of consecutive log lines summarized in one request.
+#[derive(Debug, Clone)]
+pub struct LogChunk {
+    /// First line number, counting from 1
+    pub first_line: usize,
+    pub last_line: usize,
+    pub text: String,
+    /// Error and warning lines in the chunk
+    pub problems: usize,
+}
+
+/// Error lines that differ only in timestamps, IDs, and numbers.
+#[derive(Debug, Clone)]
+pub struct ErrorCluster {
+    /// The message with variable parts replaced by `#`
+    pub signature: String,
+    pub count: usize,
+    /// First occurrence, trimmed
+    pub example: String,
+    pub first_line: usize,
+    pub first_seen: Option<String>,
+    pub last_seen: Option<String>,
+}
+
+/// What to send to the LLM for a log, and what was found without it.
+#[derive(Debug, Clone)]
+pub struct LogPlan {
+    pub total_lines: usize,
+    /// Chunks to summarize, in log order
+    pub chunks: Vec<LogChunk>,
+    /// Chunks left out because the log exceeds `max_chunks`
+    pub skipped_chunks: usize,
+    /// Most frequent error clusters first
+    pub clusters: Vec<ErrorCluster>,
+}
+
+/// Splits a log into chunks and clusters its error lines.
+///
+/// When there are more than `max_chunks` chunks, the ones with the most error
+/// and warning lines are kept.
+pub fn plan(content: &str, config: &LogAnalysisConfig) -> LogPlan {
+    let chunk_chars = config.chunk_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut current = LogChunk {
+        first_line: 1,
+        last_line: 0,
+        text: String::new(),
+        problems: 0,
+    };
+    let mut clusters: HashMap<String, ErrorCluster> = HashMap::new();
+    let mut total_lines = 0;
+
+    for (index, line) in content.lines().enumerate() {
+        let number = index + 1;
+        total_lines = number;
+        let line = truncate(line, chunk_chars);
+
+        if !current.text.is_empty() && current.text.len() + line.len() > chunk_chars {
+            let next = LogChunk {
+                first_line: number,
+                last_line: number - 1,
+                text: String::new(),
+                problems: 0,
+            };
+            chunks.push(std::mem::replace(&mut current, next));
+        }
+        current.text.push_str(line);
+        current.text.push('\n');
+        current.last_line = number;
+
+        let is_error = ERROR_LINE.is_match(line);
+        if is_error || WARNING_LINE.is_match(line) {
+            current.problems += 1;
+        }
+        if is_error {
+            add_to_cluster(&mut clusters, line, number);
+        }
+    }
+    if !current.text.is_empty() {
+        chunks.push(current);
+    }
+
+    let skipped_chunks = chunks.len().saturating_sub(config.max_chunks.max(1));
+    if skipped_chunks > 0 {
+        let mut ranked: Vec<usize> = (0..chunks.len()).collect();
+        ranked.sort_by_key(|&i| std::cmp::Reverse(chunks[i].problems));
+        ranked.truncate(config.max_chunks.max(1));
+        ranked.sort_unstable();
+        chunks = ranked.into_iter().map(|i| chunks[i].clone()).collect();
+    }
+
+    let mut clusters: Vec<ErrorCluster> = clusters.into_values().collect();
+    clusters.sort_by(|a, b| b.count.cmp(&a.count).then(a.first_line.cmp(&b.first_line)));
+    clusters.truncate(config.max_clusters);
+
+    LogPlan {
+        total_lines,
+        chunks,
+        skipped_chunks,
+        clusters,
+    }
+}
+
+fn add_to_cluster(clusters: &mut HashMap<String, ErrorCluster>, line: &str, number: usize) {
+    let timestamp = TIMESTAMP.find(line).map(|m| m.as_str().to_string());
+    let message = TIMESTAMP.replace(line, "");
+    let signature = VARIABLE_PART
+        .replace_all(&message, "#")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let cluster = clusters.entry(signature.clone()).or_insert_with(|| ErrorCluster {
+        signature,
+        count: 0,
+        example: truncate(line.trim(), MAX_EXAMPLE_CHARS).to_string(),
+        first_line: number,
+        first_seen: timestamp.clone(),
+        last_seen: None,
+    });
+    cluster.count += 1;
+    if timestamp.is_some() {
+        cluster.first_seen = cluster.first_seen.take().or(timestamp.clone());
+        cluster.last_seen = timestamp;
+    }
+}
+
+/// Formats error clusters as a plain text list.
+pub fn format_clusters(name: &str, plan: &LogPlan) -> String {
+    if plan.clusters.is_empty() {
+        return format!("No error lines found in {} ({} lines).\n", name, plan.total_lines);
+    }
+
+    let mut output = format!("Error clusters in {} ({} lines):\n", name, plan.total_lines);
+    for cluster in &plan.clusters {
+        output.push_str(&format!("  {}× line {}: {}\n", cluster.count, cluster.first_line, cluster.example));
+        match (&cluster.first_seen, &cluster.last_seen) {
+            (Some(first), Some(last)) if cluster.count > 1 && first != last => {
+                output.push_str(&format!("      first {}, last {}\n", first, last));
+            }
+            (Some(first), _) => output.push_str(&format!("      at {}\n", first)),
+            _ => {}
+        }
+    }
+    output
+}
+
+fn truncate(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// Summarizes log plans with an LLM.
+pub struct LogAnalyzer {
+    provider: Arc<dyn Provider>,
+    config: LogAnalysisConfig,
+    model: String,
+    temperature: f64,
+}
+
+impl LogAnalyzer {
+    pub fn new(provider: Arc<dyn Provider>, config: &Config) -> Self {
+        Self {
+            provider,
+            config: config.log_analysis.clone(),
+            model: config.llm.model.clone(),
+            temperature: config.llm.temperature,
+        }
+    }
+
+    /// Analyzes the log named `name`, returning the full report.
+    ///
+    /// `on_chunk` receives the report as it is produced: the error clusters,
+    /// a progress line, then the streamed final summary.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any LLM request fails.
+    pub async fn analyze<F>(&self, name: &str, plan: &LogPlan, mut on_chunk: F) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let mut report = format_clusters(name, plan);
+        on_chunk(&report);
+        if plan.chunks.is_empty() {
+            return Ok(report);
+        }
+
+        let progress = match plan.skipped_chunks {
+            0 => format!("\nSummarizing {} chunk(s)...\n\n", plan.chunks.len()),
+            skipped => format!(
+                "\nSummarizing {} of {} chunks (those with the most errors and warnings)...\n\n",
+                plan.chunks.len(),
+                plan.chunks.len() + skipped
+            ),
+        };
+        on_chunk(&progress);
+        report.push_str(&progress);
+
+        // Map: summarize chunks in parallel, keeping log order
+        // Collected first: mapping inside the stream makes the future not `Send`
+        let requests: Vec<_> = plan.chunks.iter().map(|chunk| self.summarize_chunk(name, chunk)).collect();
+        let mut summaries: Vec<String> = futures::stream::iter(requests)
+            .buffered(self.config.concurrency.max(1))
+            .try_collect()
+            .await?;
+        debug!(chunks = summaries.len(), "Summarized log chunks");
+
+        // Reduce: merge batches of summaries until they fit in one request
+        while summaries.len() > 1 && summaries.iter().map(String::len).sum::<usize>() > self.config.chunk_chars {
+            let batches = batches(&summaries, self.config.chunk_chars);
+            if batches.len() == summaries.len() {
+                // Every summary is too long to merge with another
+                break;
+            }
+
+            let mut merged = Vec::with_capacity(batches.len());
+            for batch in batches {
+                let prompt = format!(
+                    "These are summaries of consecutive parts of the log file {}.\n\
+                     Merge them into one summary of the same form, keeping every distinct problem \
+                     with its timestamps and line numbers.\n\n{}",
+                    name,
+                    batch.join("\n\n")
+                );
+                merged.push(self.complete(&prompt, &mut |_| {}).await?);
+            }
+            debug!(from = summaries.len(), to = merged.len(), "Merged log summaries");
+            summaries = merged;
+        }
+
+        let clusters = if plan.clusters.is_empty() { "None".to_string() } else { format_clusters(name, plan) };
+        let prompt = format!(
+            "These are summaries of parts of the log file {}, in order.\n\n{}\n\n\
+             Exact error counts found by pattern matching:\n{}\n\n\
+             Write the final analysis: the main problems (with how often and when they occur), \
+             their likely root causes, and the sequence of notable events.",
+            name,
+            summaries.join("\n\n"),
+            clusters
+        );
+        let summary = self
+            .complete(&prompt, &mut |chunk| {
+                on_chunk(chunk);
+            })
+            .await?;
+        report.push_str(&summary);
+
+        info!(chunks = plan.chunks.len(), skipped = plan.skipped_chunks, "Log analysis finished");
+        Ok(report)
+    }
+
+    async fn summarize_chunk(&self, name: &str, chunk: &LogChunk) -> Result<String> {
+        let prompt = format!(
+            "This is lines {}-{} of the log file {}.\n\
+             List its errors, warnings, and notable events, with their timestamps. \
+             Be brief; if nothing is notable, say so in one line.\n\n```\n{}```",
+            chunk.first_line, chunk.last_line, name, chunk.text
+        );
+        let summary = self
+            .complete(&prompt, &mut |_| {})
+            .await
+            .with_context(|| format!("Failed to summarize lines {}-{}", chunk.first_line, chunk.last_line))?;
+        Ok(format!("Lines {}-{}:\n{}", chunk.first_line, chunk.last_line, summary.trim()))
+    }
+
+    /// Sends a single-turn request and returns the full response.
+    async fn complete(&self, prompt: &str, on_chunk: &mut (dyn FnMut(&str) + Send)) -> Result<String> {
+        let messages = vec![Message::system(None, ANALYST_PROMPT), Message::user(None, prompt)];
+        let request = ChatRequest::new(&self.model, messages).with_temperature(self.temperature);
+
+        let mut content = String::new();
+        self.provider
+            .chat(request, Box::new(|response| {
+                // Providers either stream increments and finish with an empty
+                // done chunk, or send the full text in the done chunk.
+                if !response.done || content.is_empty() {
+                    on_chunk(&response.content);
+                    content.push_str(&response.content);
+                }
+            }))
+            .await
+            .context("Failed to get response from the LLM")?;
+
+        Ok(content)
+    }
+}
+
+/// Groups consecutive texts into batches of at most `max_chars` (a longer
+/// text gets a batch of its own).
+fn batches(texts: &[String], max_chars: usize) -> Vec<Vec<&str>> {
+    let mut batches: Vec<Vec<&str>> = Vec::new();
+    let mut size = 0;
+    for text in texts {
+        match batches.last_mut() {
+            Some(batch) if size + text.len() <= max_chars => batch.push(text),
+            _ => {
+                batches.push(vec![text]);
+                size = 0;
+            }
+        }
+        size += text.len();
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EmbeddingModel;
+    use crate::provider::ChatResponse;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    fn config(chunk_chars: usize, max_chunks: usize) -> LogAnalysisConfig {
+        LogAnalysisConfig {
+            chunk_chars,
+            max_chunks,
+            ..LogAnalysisConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_error_clusters() {
+        let log = "2024-05-01T10:00:00Z INFO started\n\
+                   2024-05-01T10:00:05Z ERROR connection to 10.0.0.1:5432 refused\n\
+                   2024-05-01T10:01:00Z WARN slow query\n\
+                   2024-05-01T10:02:30Z ERROR connection to 10.0.0.2:5432 refused\n\
+                   2024-05-01T10:03:00Z ERROR disk full on /var\n";
+
+        let plan = plan(log, &LogAnalysisConfig::default());
+        assert_eq!(plan.total_lines, 5);
+        assert_eq!(plan.clusters.len(), 2);
+
+        let refused = &plan.clusters[0];
+        assert_eq!(refused.signature, "ERROR connection to #.#.#.#:# refused");
+        assert_eq!(refused.count, 2);
+        assert_eq!(refused.first_line, 2);
+        assert_eq!(refused.first_seen.as_deref(), Some("2024-05-01T10:00:05Z"));
+        assert_eq!(refused.last_seen.as_deref(), Some("2024-05-01T10:02:30Z"));
+        assert_eq!(plan.clusters[1].count, 1);
+    }
+
+    #[test]
+    fn test_chunks_prefer_problems() {
+        let mut lines = vec!["info: fine"; 40];
+        lines[25] = "error: bad";
+        lines[26] = "warn: hot!";
+        let log = lines.join("\n");
+
+        // 11 chars per line, so each chunk holds 3 lines
+        let plan = plan(&log, &config(33, 2));
+        assert_eq!(plan.skipped_chunks, 12);
+        let ranges: Vec<(usize, usize)> = plan.chunks.iter().map(|c| (c.first_line, c.last_line)).collect();
+        assert_eq!(ranges, vec![(1, 3), (25, 27)]);
+        assert_eq!(plan.chunks[1].problems, 2);
+        assert_eq!(plan.chunks[1].text, "info: fine\nerror: bad\nwarn: hot!\n");
+    }
+
+    #[test]
+    fn test_batches() {
+        let texts: Vec<String> = ["aaaa", "bb", "cccccc", "d"].iter().map(|t| t.to_string()).collect();
+        assert_eq!(batches(&texts, 6), vec![vec!["aaaa", "bb"], vec!["cccccc"], vec!["d"]]);
+    }
+
+    /// Answers with the kind of request it received.
+    struct EchoProvider {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Provider for EchoProvider {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> crate::provider::Result<()> {
+            let prompt = request.messages.last().unwrap().content.clone();
+            let reply = if prompt.starts_with("This is lines") { "chunk summary" } else { "final analysis" };
+            self.prompts.lock().unwrap().push(prompt);
+            callback(ChatResponse {
+                model: request.model,
+                content: reply.to_string(),
+                done: true,
+                truncated: false,
+                message: Message::assistant(None, reply),
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_analyze_maps_then_reduces() {
+        let provider = Arc::new(EchoProvider { prompts: Mutex::new(Vec::new()) });
+        let mut config = Config::default();
+        config.log_analysis = self::config(20, 8);
+        let analyzer = LogAnalyzer::new(provider.clone(), &config);
+
+        let log = "ERROR a\nINFO b\nINFO c\nERROR a\nINFO d\n";
+        let plan = plan(log, &config.log_analysis);
+        let mut streamed = String::new();
+        let report = analyzer.analyze("app.log", &plan, |chunk| streamed.push_str(chunk)).await.unwrap();
+
+        assert_eq!(report, streamed);
+        assert!(report.starts_with("Error clusters in app.log (5 lines):\n  2× line 1: ERROR a\n"));
+        assert!(report.ends_with("final analysis"));
+
+        let prompts = provider.prompts.lock().unwrap();
+        let map_requests = prompts.iter().filter(|p| p.starts_with("This is lines")).count();
+        assert_eq!(map_requests, plan.chunks.len());
+        assert!(prompts.last().unwrap().contains("Lines 1-2:\nchunk summary"));
+    }
+}
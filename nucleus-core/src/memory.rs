@@ -0,0 +1,323 @@
+//! Per-subsystem heap usage, for diagnosing a growing daemon.
+//!
+//! With the `memory-stats` feature, [`TrackingAllocator`] counts live heap
+//! bytes by the subsystem that allocated them. An application opts in by
+//! installing it as the global allocator:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: nucleus_core::memory::TrackingAllocator = nucleus_core::memory::TrackingAllocator;
+//! ```
+//!
+//! The server's `stats` request then reports the counts. Allocations are
+//! attributed to the subsystem active on the allocating thread: vector store
+//! operations, provider calls (model), and session state are marked with
+//! [`enter`] and [`track`]; everything else counts as `other`. Memory
+//! allocated outside Rust's allocator (e.g. by llama.cpp) and on blocking
+//! threads is not attributed to a subsystem.
+//!
+//! Without the feature, marking subsystems costs nothing and [`snapshot`]
+//! returns `None`.
+
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::fmt;
+use std::future::Future;
+
+/// Part of the daemon memory is attributed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Subsystem {
+    Other,
+    /// Vector store operations
+    Store,
+    /// Per-session server state
+    Sessions,
+    /// LLM and embedding provider calls
+    Model,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 4] = [Self::Store, Self::Sessions, Self::Model, Self::Other];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Other => "other",
+            Self::Store => "store",
+            Self::Sessions => "sessions",
+            Self::Model => "model",
+        }
+    }
+}
+
+thread_local! {
+    // Const-initialized without a destructor, so the allocator can read it
+    static CURRENT: Cell<Subsystem> = const { Cell::new(Subsystem::Other) };
+}
+
+/// Subsystem allocations on this thread are currently attributed to.
+pub fn current() -> Subsystem {
+    CURRENT.try_with(Cell::get).unwrap_or(Subsystem::Other)
+}
+
+/// Attributes allocations on this thread to `subsystem` until the scope is dropped.
+///
+/// Do not hold a scope across an `.await`; use [`track`] for futures.
+pub fn enter(subsystem: Subsystem) -> Scope {
+    let previous = CURRENT.try_with(|current| current.replace(subsystem)).unwrap_or(Subsystem::Other);
+    Scope { previous }
+}
+
+/// Restores the previous subsystem when dropped, see [`enter`].
+#[must_use = "the subsystem is only active while the scope is alive"]
+pub struct Scope {
+    previous: Subsystem,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let _ = CURRENT.try_with(|current| current.set(self.previous));
+    }
+}
+
+/// Attributes the allocations made while polling `future` to `subsystem`.
+pub async fn track<F: Future>(subsystem: Subsystem, future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    std::future::poll_fn(|cx| {
+        let _scope = enter(subsystem);
+        future.as_mut().poll(cx)
+    })
+    .await
+}
+
+/// Live heap usage of one subsystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubsystemUsage {
+    pub subsystem: Subsystem,
+    pub bytes: usize,
+    pub allocations: usize,
+}
+
+/// Heap usage at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    /// Live bytes across subsystems
+    pub bytes: usize,
+    /// Highest number of live bytes seen
+    pub peak_bytes: usize,
+    pub subsystems: Vec<SubsystemUsage>,
+}
+
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Heap: {} live (peak {})", format_bytes(self.bytes), format_bytes(self.peak_bytes))?;
+        for usage in &self.subsystems {
+            write!(
+                f,
+                "\n  {:<9} {:>10} in {} allocation(s)",
+                usage.subsystem.as_str(),
+                format_bytes(usage.bytes),
+                usage.allocations
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 MiB`.
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+/// Current heap usage, or `None` if [`TrackingAllocator`] is not the global
+/// allocator (or the `memory-stats` feature is disabled).
+pub fn snapshot() -> Option<MemoryStats> {
+    #[cfg(feature = "memory-stats")]
+    {
+        tracking::snapshot()
+    }
+    #[cfg(not(feature = "memory-stats"))]
+    {
+        None
+    }
+}
+
+#[cfg(feature = "memory-stats")]
+pub use tracking::TrackingAllocator;
+
+#[cfg(feature = "memory-stats")]
+mod tracking {
+    use super::{current, MemoryStats, Subsystem, SubsystemUsage};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Bytes in front of every allocation recording its subsystem.
+    const HEADER: usize = 16;
+
+    static INSTALLED: AtomicBool = AtomicBool::new(false);
+    static BYTES: [AtomicUsize; 4] = [const { AtomicUsize::new(0) }; 4];
+    static ALLOCATIONS: [AtomicUsize; 4] = [const { AtomicUsize::new(0) }; 4];
+    static TOTAL: AtomicUsize = AtomicUsize::new(0);
+    static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    /// Global allocator counting live bytes per [`Subsystem`].
+    ///
+    /// Wraps the system allocator, storing the allocating subsystem in a
+    /// small header in front of each allocation.
+    pub struct TrackingAllocator;
+
+    fn index(subsystem: Subsystem) -> usize {
+        match subsystem {
+            Subsystem::Other => 0,
+            Subsystem::Store => 1,
+            Subsystem::Sessions => 2,
+            Subsystem::Model => 3,
+        }
+    }
+
+    /// Header size keeping the returned pointer aligned for `layout`.
+    fn header_size(layout: Layout) -> usize {
+        HEADER.max(layout.align())
+    }
+
+    fn outer_layout(layout: Layout) -> Option<Layout> {
+        let size = layout.size().checked_add(header_size(layout))?;
+        Layout::from_size_align(size, layout.align().max(HEADER)).ok()
+    }
+
+    // SAFETY: allocation is delegated to `System` with a layout extended by
+    // the header; the pointer handed out is offset by the header size, which
+    // is a multiple of the requested alignment, and `dealloc` undoes exactly
+    // that offset for the same layout.
+    unsafe impl GlobalAlloc for TrackingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let Some(outer) = outer_layout(layout) else {
+                return std::ptr::null_mut();
+            };
+            let base = System.alloc(outer);
+            if base.is_null() {
+                return base;
+            }
+
+            let slot = index(current());
+            base.write(slot as u8);
+            BYTES[slot].fetch_add(layout.size(), Ordering::Relaxed);
+            ALLOCATIONS[slot].fetch_add(1, Ordering::Relaxed);
+            let total = TOTAL.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(total, Ordering::Relaxed);
+            if !INSTALLED.load(Ordering::Relaxed) {
+                INSTALLED.store(true, Ordering::Relaxed);
+            }
+
+            base.add(header_size(layout))
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let base = ptr.sub(header_size(layout));
+            let slot = usize::from(base.read());
+            BYTES[slot].fetch_sub(layout.size(), Ordering::Relaxed);
+            ALLOCATIONS[slot].fetch_sub(1, Ordering::Relaxed);
+            TOTAL.fetch_sub(layout.size(), Ordering::Relaxed);
+
+            if let Some(outer) = outer_layout(layout) {
+                System.dealloc(base, outer);
+            }
+        }
+    }
+
+    pub(super) fn snapshot() -> Option<MemoryStats> {
+        if !INSTALLED.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let subsystems = Subsystem::ALL
+            .iter()
+            .map(|&subsystem| SubsystemUsage {
+                subsystem,
+                bytes: BYTES[index(subsystem)].load(Ordering::Relaxed),
+                allocations: ALLOCATIONS[index(subsystem)].load(Ordering::Relaxed),
+            })
+            .collect();
+        Some(MemoryStats {
+            bytes: TOTAL.load(Ordering::Relaxed),
+            peak_bytes: PEAK.load(Ordering::Relaxed),
+            subsystems,
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_allocations_are_attributed() {
+            let allocator = TrackingAllocator;
+            let layout = Layout::from_size_align(100, 64).unwrap();
+            let before = BYTES[index(Subsystem::Store)].load(Ordering::Relaxed);
+
+            let ptr = {
+                let _scope = super::super::enter(Subsystem::Store);
+                unsafe { allocator.alloc(layout) }
+            };
+            assert!(!ptr.is_null());
+            assert_eq!(ptr as usize % 64, 0);
+            assert_eq!(BYTES[index(Subsystem::Store)].load(Ordering::Relaxed), before + 100);
+            assert!(snapshot().is_some());
+
+            // Freed from another subsystem, still credited back to the store
+            unsafe { allocator.dealloc(ptr, layout) };
+            assert_eq!(BYTES[index(Subsystem::Store)].load(Ordering::Relaxed), before);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enter_restores_previous() {
+        assert_eq!(current(), Subsystem::Other);
+        {
+            let _store = enter(Subsystem::Store);
+            {
+                let _sessions = enter(Subsystem::Sessions);
+                assert_eq!(current(), Subsystem::Sessions);
+            }
+            assert_eq!(current(), Subsystem::Store);
+        }
+        assert_eq!(current(), Subsystem::Other);
+    }
+
+    #[tokio::test]
+    async fn test_track_only_while_polled() {
+        let observed = track(Subsystem::Model, async {
+            let before = current();
+            tokio::task::yield_now().await;
+            (before, current())
+        })
+        .await;
+        assert_eq!(observed, (Subsystem::Model, Subsystem::Model));
+        assert_eq!(current(), Subsystem::Other);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
@@ -0,0 +1,376 @@
+//! Completion notifications for long-running operations.
+//!
+//! Indexing a large repository or running a batch job can take a long time.
+//! The [`Notifier`] reports when such an operation finishes, either by POSTing
+//! a JSON payload to a webhook or by running a local command.
+//!
+//! The webhook payload includes a pre-formatted `text` field, so it can be sent
+//! directly to Slack-style incoming webhooks.
+//!
+//! Long generations and tasks can also raise a desktop notification
+//! (`osascript` on macOS, `notify-send` on Linux, a tray balloon on Windows),
+//! so finishing work is noticed while the terminal is in the background.
+
+use crate::config::NotificationConfig;
+use serde::Serialize;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+/// Errors that can occur while delivering a notification.
+#[derive(Debug, Error)]
+pub enum NotifyError {
+    #[error("Webhook request failed: {0}")]
+    Webhook(#[from] reqwest::Error),
+
+    #[error("Webhook returned status {0}")]
+    WebhookStatus(u16),
+
+    #[error("Notify command failed: {0}")]
+    Command(String),
+}
+
+pub type Result<T> = std::result::Result<T, NotifyError>;
+
+/// Kind of long-running operation that completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+    /// Directory or file indexing
+    Index,
+    /// Multi-step agent task
+    Agent,
+    /// Batch summarization
+    Summarize,
+    /// A single chat generation
+    Generation,
+}
+
+impl OperationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationKind::Index => "index",
+            OperationKind::Agent => "agent",
+            OperationKind::Summarize => "summarize",
+            OperationKind::Generation => "generation",
+        }
+    }
+
+    /// Returns true for background jobs, which are reported to the webhook and
+    /// command targets. Generations only raise desktop notifications.
+    pub fn is_job(&self) -> bool {
+        !matches!(self, OperationKind::Generation)
+    }
+}
+
+/// Payload describing a finished operation.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationEvent {
+    pub operation: OperationKind,
+    pub success: bool,
+    pub duration_ms: u64,
+    /// Short description of the outcome (e.g. "Indexed 120 files from ./src")
+    pub summary: String,
+    /// Human-readable message, rendered by Slack-compatible webhooks
+    pub text: String,
+}
+
+impl OperationEvent {
+    pub fn new(
+        operation: OperationKind,
+        success: bool,
+        duration: Duration,
+        summary: impl Into<String>,
+    ) -> Self {
+        let summary = summary.into();
+        let status = if success { "finished" } else { "failed" };
+        let text = format!(
+            "nucleus: {} {} after {}: {}",
+            operation.as_str(),
+            status,
+            format_duration(duration),
+            summary
+        );
+
+        Self {
+            operation,
+            success,
+            duration_ms: duration.as_millis() as u64,
+            summary,
+            text,
+        }
+    }
+}
+
+/// Delivers operation completion notifications.
+///
+/// Webhook and command notifications are sent for jobs that ran for at least
+/// `min_duration_secs`. Desktop notifications are sent for jobs and generations
+/// that ran for at least `desktop_min_duration_secs`.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    config: NotificationConfig,
+    http_client: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(config: NotificationConfig) -> Self {
+        Self {
+            config,
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// Returns true if any notification target is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.config.webhook_url.is_some() || self.config.command.is_some() || self.config.desktop
+    }
+
+    /// Returns true if the event is long enough to be worth reporting.
+    pub fn should_notify(&self, event: &OperationEvent) -> bool {
+        self.should_notify_job(event) || self.should_notify_desktop(event)
+    }
+
+    fn should_notify_job(&self, event: &OperationEvent) -> bool {
+        let has_target = self.config.webhook_url.is_some() || self.config.command.is_some();
+        has_target
+            && event.operation.is_job()
+            && event.duration_ms >= self.config.min_duration_secs * 1000
+    }
+
+    fn should_notify_desktop(&self, event: &OperationEvent) -> bool {
+        self.config.desktop && event.duration_ms >= self.config.desktop_min_duration_secs * 1000
+    }
+
+    /// Sends the event to every configured target.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first delivery failure. Targets are attempted independently,
+    /// so a failing webhook does not prevent the command from running.
+    pub async fn notify(&self, event: &OperationEvent) -> Result<()> {
+        if !self.should_notify(event) {
+            debug!(operation = event.operation.as_str(), "Skipping notification");
+            return Ok(());
+        }
+
+        let mut first_error = None;
+
+        if self.should_notify_job(event) {
+            if let Some(url) = &self.config.webhook_url {
+                if let Err(e) = self.post_webhook(url, event).await {
+                    first_error.get_or_insert(e);
+                }
+            }
+
+            if let Some(command) = &self.config.command {
+                if let Err(e) = run_command(command, event).await {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        if self.should_notify_desktop(event) {
+            if let Err(e) = show_desktop_notification(event).await {
+                first_error.get_or_insert(e);
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => {
+                info!(operation = event.operation.as_str(), "Sent completion notification");
+                Ok(())
+            }
+        }
+    }
+
+    async fn post_webhook(&self, url: &str, event: &OperationEvent) -> Result<()> {
+        let response = self.http_client.post(url).json(event).send().await?;
+
+        if !response.status().is_success() {
+            return Err(NotifyError::WebhookStatus(response.status().as_u16()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the notify command through the platform shell.
+///
+/// Event details are passed as `NUCLEUS_*` environment variables rather than
+/// interpolated into the command line.
+async fn run_command(command: &str, event: &OperationEvent) -> Result<()> {
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    };
+
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+
+    let status = cmd
+        .env("NUCLEUS_OPERATION", event.operation.as_str())
+        .env("NUCLEUS_SUCCESS", event.success.to_string())
+        .env("NUCLEUS_DURATION_MS", event.duration_ms.to_string())
+        .env("NUCLEUS_SUMMARY", &event.summary)
+        .env("NUCLEUS_MESSAGE", &event.text)
+        .status()
+        .await
+        .map_err(|e| NotifyError::Command(e.to_string()))?;
+
+    if !status.success() {
+        return Err(NotifyError::Command(format!(
+            "'{}' exited with {}",
+            command, status
+        )));
+    }
+
+    Ok(())
+}
+
+/// Shows a desktop notification with the event summary.
+///
+/// Title and body are passed through environment variables so they never need
+/// shell or script escaping.
+async fn show_desktop_notification(event: &OperationEvent) -> Result<()> {
+    let title = format!("nucleus: {} {}", event.operation.as_str(), if event.success { "finished" } else { "failed" });
+
+    #[cfg(target_os = "macos")]
+    let mut cmd = {
+        let mut cmd = Command::new("osascript");
+        cmd.arg("-e")
+            .arg("display notification (system attribute \"NUCLEUS_BODY\") with title (system attribute \"NUCLEUS_TITLE\")");
+        cmd
+    };
+
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("powershell");
+        cmd.args(["-NoProfile", "-Command"]).arg(
+            "[void][System.Reflection.Assembly]::LoadWithPartialName('System.Windows.Forms'); \
+             $n = New-Object System.Windows.Forms.NotifyIcon; \
+             $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+             $n.ShowBalloonTip(5000, $env:NUCLEUS_TITLE, $env:NUCLEUS_BODY, 'Info'); \
+             Start-Sleep -Seconds 6; $n.Dispose()",
+        );
+        cmd
+    };
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg("notify-send --app-name=nucleus \"$NUCLEUS_TITLE\" \"$NUCLEUS_BODY\"");
+        cmd
+    };
+
+    let status = cmd
+        .env("NUCLEUS_TITLE", &title)
+        .env("NUCLEUS_BODY", headline(&event.summary, 200))
+        .status()
+        .await
+        .map_err(|e| NotifyError::Command(format!("Desktop notification failed: {}", e)))?;
+
+    if !status.success() {
+        return Err(NotifyError::Command(format!(
+            "Desktop notification exited with {}",
+            status
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the first non-empty line of `text`, truncated to `max_chars` characters.
+pub fn headline(text: &str, max_chars: usize) -> String {
+    let line = text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+
+    if line.chars().count() <= max_chars {
+        return line.to_string();
+    }
+
+    let mut truncated: String = line.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_text() {
+        let event = OperationEvent::new(
+            OperationKind::Index,
+            true,
+            Duration::from_secs(3720),
+            "Indexed 42 files",
+        );
+        assert_eq!(event.duration_ms, 3_720_000);
+        assert_eq!(event.text, "nucleus: index finished after 1h 2m: Indexed 42 files");
+    }
+
+    #[test]
+    fn test_should_notify_respects_min_duration() {
+        let notifier = Notifier::new(NotificationConfig {
+            webhook_url: Some("http://localhost:9/hook".to_string()),
+            min_duration_secs: 60,
+            ..NotificationConfig::default()
+        });
+
+        let short = OperationEvent::new(OperationKind::Index, true, Duration::from_secs(5), "");
+        let long = OperationEvent::new(OperationKind::Index, true, Duration::from_secs(90), "");
+        assert!(!notifier.should_notify(&short));
+        assert!(notifier.should_notify(&long));
+    }
+
+    #[test]
+    fn test_generations_only_notify_desktop() {
+        let webhook_only = Notifier::new(NotificationConfig {
+            webhook_url: Some("http://localhost:9/hook".to_string()),
+            ..NotificationConfig::default()
+        });
+        let desktop = Notifier::new(NotificationConfig {
+            desktop: true,
+            desktop_min_duration_secs: 10,
+            ..NotificationConfig::default()
+        });
+
+        let event = OperationEvent::new(OperationKind::Generation, true, Duration::from_secs(45), "");
+        assert!(!webhook_only.should_notify(&event));
+        assert!(desktop.should_notify(&event));
+    }
+
+    #[test]
+    fn test_headline() {
+        assert_eq!(headline("\n  First line\nSecond", 80), "First line");
+        assert_eq!(headline("abcdef", 4), "abc…");
+        assert_eq!(headline("", 10), "");
+    }
+
+    #[test]
+    fn test_disabled_without_targets() {
+        let notifier = Notifier::new(NotificationConfig::default());
+        let event = OperationEvent::new(OperationKind::Agent, false, Duration::from_secs(600), "");
+        assert!(!notifier.should_notify(&event));
+    }
+}
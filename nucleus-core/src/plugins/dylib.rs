@@ -0,0 +1,115 @@
+//! Plugins built as dynamic libraries, loaded into the daemon.
+//!
+//! A library exports two C functions:
+//!
+//! ```c
+//! int nucleus_plugin_call(const char *input, char **output);
+//! void nucleus_plugin_free(char *output);
+//! ```
+//!
+//! `nucleus_plugin_call` takes the arguments as JSON and sets `output` to
+//! the result, which is then handed back to `nucleus_plugin_free`; a
+//! non-zero return fails the call, with `output` as the message.
+//!
+//! Nothing sandboxes a library: it runs with the daemon's rights, so these
+//! plugins need the `execute` permission, and it is only loaded on its first
+//! call, since loading runs its initializers. Calls run on tokio's blocking
+//! threads; one that does not return is abandoned at the tool timeout, but
+//! keeps its thread.
+
+use anyhow::{anyhow, bail, Result};
+use libloading::{Library, Symbol};
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+type Call = unsafe extern "C" fn(*const c_char, *mut *mut c_char) -> c_int;
+type Free = unsafe extern "C" fn(*mut c_char);
+
+/// A plugin library, loaded on its first call.
+#[derive(Debug, Clone)]
+pub struct DynamicLibrary {
+    path: PathBuf,
+    library: Arc<OnceLock<std::result::Result<Library, String>>>,
+}
+
+impl DynamicLibrary {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, library: Arc::new(OnceLock::new()) }
+    }
+
+    /// Runs `nucleus_plugin_call` on `input` on a blocking thread.
+    pub async fn call(&self, input: String) -> Result<String> {
+        let plugin = self.clone();
+        tokio::task::spawn_blocking(move || plugin.call_blocking(&input)).await?
+    }
+
+    fn call_blocking(&self, input: &str) -> Result<String> {
+        let library = self
+            .library
+            // SAFETY: loading runs the library's initializers, which is what
+            // enabling a dynamic library plugin trusts it with
+            .get_or_init(|| unsafe { Library::new(&self.path) }.map_err(|e| format!("loading {}: {}", self.path.display(), e)))
+            .as_ref()
+            .map_err(|e| anyhow!("{}", e))?;
+        // SAFETY: the plugin ABI above gives these symbols these types
+        let (call, free): (Symbol<Call>, Symbol<Free>) =
+            unsafe { (library.get(b"nucleus_plugin_call\0")?, library.get(b"nucleus_plugin_free\0")?) };
+
+        let input = CString::new(input)?;
+        let mut output: *mut c_char = std::ptr::null_mut();
+        // SAFETY: `input` is a valid C string for the call, and `output` is
+        // only read if the library set it, then freed by the library
+        let (status, output) = unsafe {
+            let status = call(input.as_ptr(), &mut output);
+            if output.is_null() {
+                (status, String::new())
+            } else {
+                let text = CStr::from_ptr(output).to_string_lossy().into_owned();
+                free(output);
+                (status, text)
+            }
+        };
+        if status != 0 {
+            bail!("nucleus_plugin_call returned {}: {}", status, output);
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    const PLUGIN: &str = r#"
+#include <stdlib.h>
+#include <string.h>
+int nucleus_plugin_call(const char *input, char **output) {
+    *output = strdup(input);
+    return strcmp(input, "{}") == 0;
+}
+void nucleus_plugin_free(char *output) { free(output); }
+"#;
+
+    #[tokio::test]
+    async fn test_call_library() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("plugin.c"), PLUGIN).unwrap();
+        let path = dir.path().join("plugin.so");
+        let built = Command::new("cc")
+            .args(["-shared", "-fPIC", "-o"])
+            .arg(&path)
+            .arg(dir.path().join("plugin.c"))
+            .status();
+        if !built.is_ok_and(|status| status.success()) {
+            eprintln!("No C compiler, skipping");
+            return;
+        }
+
+        let library = DynamicLibrary::new(path);
+        assert_eq!(library.call(r#"{"x":1}"#.to_string()).await.unwrap(), r#"{"x":1}"#);
+        let error = library.call("{}".to_string()).await.unwrap_err();
+        assert_eq!(error.to_string(), "nucleus_plugin_call returned 1: {}");
+    }
+}
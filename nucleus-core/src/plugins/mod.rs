@@ -0,0 +1,560 @@
+//! Plugins discovered in the plugins directory (`plugins.path`).
+//!
+//! Each plugin is a subdirectory with a `plugin.yaml` manifest, which names
+//! the plugin, describes it to the model, gives the JSON schema of its
+//! parameters and the permissions it needs, and points at its entry:
+//!
+//! ```yaml
+//! name: jira
+//! description: Looks up a Jira issue by its key
+//! parameters: {type: object, required: [key], properties: {key: {type: string}}}
+//! permission: {read: true, network: true}
+//! entry: ./jira.sh
+//! ```
+//!
+//! An executable entry is run in the plugin's directory for every call,
+//! with the arguments as JSON on stdin; what it prints is the result, and a
+//! non-zero exit status fails the call. Since it runs code of its own, it
+//! also needs the `execute` permission.
+//!
+//! A `.wasm` entry is compiled when the directory is scanned and run in a
+//! sandbox, see [`wasm`]; having nothing to reach outside it with, it needs
+//! no `execute`. A `.so`, `.dylib` or `.dll` entry is loaded into the daemon
+//! on its first call, see [`dylib`]. Each needs its feature, `wasm-plugins`
+//! or `dylib-plugins`; without it the plugin is listed with that problem.
+//!
+//! [`PluginDirectory::scan`] reads the manifests, and
+//! [`PluginDirectory::register`] adds the enabled plugins to a
+//! [`PluginRegistry`]; [`Plugins`] does both once and holds the registry
+//! the [`ChatManager`](crate::ChatManager) tool loop runs tools from, which
+//! the server's chats go through too, registering and removing plugins as
+//! they are enabled and disabled. Disabled plugins are kept in
+//! `disabled.json` in the directory.
+//!
+//! What each plugin may do is granted in `plugins.grants`, see
+//! [`apply_grants`]; plugins without a grant there get the registry's default.
+
+use crate::config::PluginsConfig;
+use async_trait::async_trait;
+use nucleus_plugin::{Capability, Permission, Plugin, PluginError, PluginOutput, PluginRegistry};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+#[cfg(feature = "dylib-plugins")]
+pub mod dylib;
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm;
+
+const MANIFEST: &str = "plugin.yaml";
+const DISABLED: &str = "disabled.json";
+
+#[derive(Debug, Error)]
+pub enum PluginsError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid list of disabled plugins: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("No plugin '{0}'; `nucleus plugins list` shows them")]
+    NotFound(String),
+}
+
+pub type Result<T> = std::result::Result<T, PluginsError>;
+
+/// What a plugin's entry is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PluginKind {
+    Executable,
+    Wasm,
+    DynamicLibrary,
+}
+
+impl PluginKind {
+    fn of(entry: &Path) -> Self {
+        match entry.extension().and_then(|extension| extension.to_str()) {
+            Some("wasm") => Self::Wasm,
+            Some("so" | "dylib" | "dll") => Self::DynamicLibrary,
+            _ => Self::Executable,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Executable => "executable",
+            Self::Wasm => "WASM",
+            Self::DynamicLibrary => "dynamic library",
+        }
+    }
+}
+
+/// A discovered plugin, as listed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginInfo {
+    pub name: String,
+    pub description: String,
+    /// Directory of the plugin
+    pub path: PathBuf,
+    /// Unknown if the manifest could not be read
+    pub kind: Option<PluginKind>,
+    pub enabled: bool,
+    /// What the plugin needs to be granted
+    #[serde(default)]
+    pub requires: Vec<Capability>,
+    /// Why the plugin cannot be used, whether or not it is enabled
+    #[serde(default)]
+    pub problem: Option<String>,
+}
+
+/// What a plugin asks for besides `execute`, which every plugin but a WASM
+/// one needs.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ManifestPermission {
+    #[serde(default)]
+    read: bool,
+    #[serde(default)]
+    write: bool,
+    #[serde(default)]
+    network: bool,
+}
+
+impl Manifest {
+    fn required_permission(&self) -> Permission {
+        Permission {
+            read: self.permission.read,
+            write: self.permission.write,
+            network: self.permission.network,
+            execute: PluginKind::of(&self.entry) != PluginKind::Wasm,
+        }
+    }
+}
+
+/// Grants the plugins named in `plugins.grants` exactly what is listed there.
+///
+/// Applies to plugins registered afterwards, and to the next call of those
+/// already registered.
+pub fn apply_grants(config: &PluginsConfig, registry: &mut PluginRegistry) {
+    for (name, capabilities) in &config.grants {
+        registry.set_grant(name.clone(), capabilities.iter().copied().collect());
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Manifest {
+    name: String,
+    description: String,
+    #[serde(default = "empty_schema")]
+    parameters: Value,
+    #[serde(default)]
+    permission: ManifestPermission,
+    /// Relative to the plugin's directory
+    entry: PathBuf,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+fn empty_schema() -> Value {
+    serde_json::json!({"type": "object", "properties": {}})
+}
+
+#[derive(Debug)]
+struct Discovered {
+    dir: PathBuf,
+    /// The manifest, or why it could not be used
+    manifest: std::result::Result<Manifest, String>,
+    /// How its entry runs, or why it cannot
+    runner: std::result::Result<Runner, String>,
+}
+
+impl Discovered {
+    fn name(&self) -> String {
+        match &self.manifest {
+            Ok(manifest) => manifest.name.clone(),
+            Err(_) => self.dir.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        }
+    }
+
+    fn problem(&self) -> Option<String> {
+        self.manifest.as_ref().err().or(self.runner.as_ref().err()).cloned()
+    }
+}
+
+/// How a plugin's entry is run.
+#[derive(Debug, Clone)]
+enum Runner {
+    Process,
+    #[cfg(feature = "wasm-plugins")]
+    Wasm(wasm::WasmModule),
+    #[cfg(feature = "dylib-plugins")]
+    Library(dylib::DynamicLibrary),
+}
+
+impl Runner {
+    /// Prepares the entry of `manifest`, or says why it cannot be run.
+    fn load(dir: &Path, manifest: &Manifest) -> std::result::Result<Self, String> {
+        let entry = dir.join(&manifest.entry);
+        if !entry.is_file() {
+            return Err(format!("Entry {} not found", manifest.entry.display()));
+        }
+        match PluginKind::of(&manifest.entry) {
+            PluginKind::Executable => Ok(Self::Process),
+            #[cfg(feature = "wasm-plugins")]
+            PluginKind::Wasm => wasm::WasmModule::load(&entry).map(Self::Wasm),
+            #[cfg(not(feature = "wasm-plugins"))]
+            PluginKind::Wasm => Err(missing_feature(PluginKind::Wasm, "wasm-plugins")),
+            #[cfg(feature = "dylib-plugins")]
+            PluginKind::DynamicLibrary => Ok(Self::Library(dylib::DynamicLibrary::new(entry))),
+            #[cfg(not(feature = "dylib-plugins"))]
+            PluginKind::DynamicLibrary => Err(missing_feature(PluginKind::DynamicLibrary, "dylib-plugins")),
+        }
+    }
+}
+
+#[cfg(not(all(feature = "wasm-plugins", feature = "dylib-plugins")))]
+fn missing_feature(kind: PluginKind, feature: &str) -> String {
+    format!("{} plugins need the `{}` feature, which this build does not include", kind.as_str(), feature)
+}
+
+/// The plugins found in a directory, and which of them are disabled.
+#[derive(Debug)]
+pub struct PluginDirectory {
+    path: PathBuf,
+    plugins: Vec<Discovered>,
+    disabled: BTreeSet<String>,
+}
+
+impl PluginDirectory {
+    /// The directory `config` names, scanned; none if discovery is off.
+    pub fn from_config(config: &PluginsConfig) -> Self {
+        let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+        let path = crate::dotfiles::expand_home(&config.path, &home);
+        if config.enabled {
+            Self::scan(path)
+        } else {
+            Self { path, plugins: Vec::new(), disabled: BTreeSet::new() }
+        }
+    }
+
+    /// Reads the manifests in the subdirectories of `path`.
+    ///
+    /// A missing directory has no plugins; unreadable manifests are listed
+    /// with their problem, and an unreadable directory is logged.
+    pub fn scan(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut directory = Self { path, plugins: Vec::new(), disabled: BTreeSet::new() };
+        match directory.read() {
+            Ok(()) => info!("Found {} plugin(s) in {}", directory.plugins.len(), directory.path.display()),
+            Err(PluginsError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to read plugins in {}: {}", directory.path.display(), e),
+        }
+        directory
+    }
+
+    fn read(&mut self) -> Result<()> {
+        let mut dirs = Vec::new();
+        for entry in std::fs::read_dir(&self.path)? {
+            let dir = entry?.path();
+            if dir.join(MANIFEST).is_file() {
+                dirs.push(dir);
+            }
+        }
+        dirs.sort();
+
+        let mut names = BTreeSet::new();
+        for dir in dirs {
+            let manifest = std::fs::read_to_string(dir.join(MANIFEST))
+                .map_err(|e| e.to_string())
+                .and_then(|yaml| serde_yaml::from_str::<Manifest>(&yaml).map_err(|e| format!("Invalid {}: {}", MANIFEST, e)))
+                .and_then(|manifest| {
+                    if names.insert(manifest.name.clone()) {
+                        Ok(manifest)
+                    } else {
+                        Err(format!("Another plugin is named '{}'", manifest.name))
+                    }
+                });
+            let runner = manifest.as_ref().map_err(Clone::clone).and_then(|manifest| Runner::load(&dir, manifest));
+            self.plugins.push(Discovered { dir, manifest, runner });
+        }
+
+        match std::fs::read(self.path.join(DISABLED)) {
+            Ok(json) => self.disabled = serde_json::from_slice(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    /// The plugins found, by name.
+    pub fn list(&self) -> Vec<PluginInfo> {
+        let mut plugins: Vec<PluginInfo> = self
+            .plugins
+            .iter()
+            .map(|plugin| {
+                let name = plugin.name();
+                PluginInfo {
+                    description: plugin.manifest.as_ref().map(|m| m.description.clone()).unwrap_or_default(),
+                    path: plugin.dir.clone(),
+                    kind: plugin.manifest.as_ref().ok().map(|m| PluginKind::of(&m.entry)),
+                    enabled: !self.disabled.contains(&name),
+                    requires: plugin.manifest.as_ref().map(|m| m.required_permission().capabilities()).unwrap_or_default(),
+                    problem: plugin.problem(),
+                    name,
+                }
+            })
+            .collect();
+        plugins.sort_by(|a, b| a.name.cmp(&b.name));
+        plugins
+    }
+
+    /// Enables or disables plugin `name`, remembering it in the directory.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        if !self.plugins.iter().any(|plugin| plugin.name() == name) {
+            return Err(PluginsError::NotFound(name.to_string()));
+        }
+        let changed = if enabled { self.disabled.remove(name) } else { self.disabled.insert(name.to_string()) };
+        if changed {
+            let partial = self.path.join(DISABLED).with_extension("partial");
+            std::fs::write(&partial, serde_json::to_vec_pretty(&self.disabled)?)?;
+            std::fs::rename(&partial, self.path.join(DISABLED))?;
+        }
+        Ok(())
+    }
+
+    /// Registers the enabled plugins that can be used, returning how many
+    /// were; those needing more than `registry` grants them are logged.
+    pub fn register(&self, registry: &mut PluginRegistry) -> usize {
+        self.usable().filter(|plugin| registry.register(Arc::new(plugin.clone()))).count()
+    }
+
+    /// The enabled plugins without a problem.
+    fn usable(&self) -> impl Iterator<Item = ExternalPlugin> + '_ {
+        self.plugins.iter().filter_map(|plugin| {
+            let (manifest, runner) = (plugin.manifest.as_ref().ok()?, plugin.runner.as_ref().ok()?);
+            (!self.disabled.contains(&manifest.name)).then(|| ExternalPlugin {
+                manifest: manifest.clone(),
+                dir: plugin.dir.clone(),
+                runner: runner.clone(),
+            })
+        })
+    }
+}
+
+/// A plugin registry shared by everything that runs or lists plugins.
+pub type SharedRegistry = Arc<RwLock<PluginRegistry>>;
+
+/// The registry tools run from, with the enabled plugins of `plugins.path`
+/// in it, and the directory they were found in.
+///
+/// Clones share both: enabling or disabling a plugin through one changes
+/// the registry in place, so the next tool call of every
+/// [`ChatManager`](crate::ChatManager) and handler holding a clone sees it.
+#[derive(Clone)]
+pub struct Plugins {
+    directory: Arc<Mutex<PluginDirectory>>,
+    registry: SharedRegistry,
+}
+
+impl Plugins {
+    /// Scans the directory `config` names and registers its enabled plugins
+    /// in `registry`, after granting them `plugins.grants`.
+    pub fn new(config: &PluginsConfig, mut registry: PluginRegistry) -> Self {
+        let directory = PluginDirectory::from_config(config);
+        apply_grants(config, &mut registry);
+        let registered = directory.register(&mut registry);
+        if registered > 0 {
+            info!("Registered {} plugin(s) from {}", registered, directory.path.display());
+        }
+        Self {
+            directory: Arc::new(Mutex::new(directory)),
+            registry: Arc::new(RwLock::new(registry)),
+        }
+    }
+
+    /// The registry tools run from.
+    pub fn registry(&self) -> &SharedRegistry {
+        &self.registry
+    }
+
+    /// The plugins found, by name; an enabled plugin that could not be
+    /// registered for lack of a grant says so in its `problem`.
+    pub async fn list(&self) -> Vec<PluginInfo> {
+        let registry = self.registry.read().await;
+        let directory = self.directory.lock().unwrap();
+        directory
+            .list()
+            .into_iter()
+            .map(|mut plugin| {
+                if plugin.enabled && plugin.problem.is_none() && registry.get(&plugin.name).is_none() {
+                    let required: Permission = plugin.requires.iter().copied().collect();
+                    let missing = registry.grant(&plugin.name).missing(&required);
+                    plugin.problem = Some(format!("Not granted {}; see plugins.grants", missing));
+                }
+                plugin
+            })
+            .collect()
+    }
+
+    /// Enables or disables plugin `name`, remembering it in the directory,
+    /// and registers it or removes it from the registry right away.
+    pub async fn set_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        let mut registry = self.registry.write().await;
+        let mut directory = self.directory.lock().unwrap();
+        directory.set_enabled(name, enabled)?;
+        if !enabled {
+            registry.unregister(name);
+        } else if let Some(plugin) = directory.usable().find(|plugin| plugin.manifest.name == name) {
+            registry.register(Arc::new(plugin));
+        }
+        Ok(())
+    }
+}
+
+/// A plugin from the directory, run by its [`Runner`].
+#[derive(Clone)]
+struct ExternalPlugin {
+    manifest: Manifest,
+    dir: PathBuf,
+    runner: Runner,
+}
+
+#[async_trait]
+impl Plugin for ExternalPlugin {
+    fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    fn description(&self) -> &str {
+        &self.manifest.description
+    }
+
+    fn parameter_schema(&self) -> Value {
+        self.manifest.parameters.clone()
+    }
+
+    fn required_permission(&self) -> Permission {
+        self.manifest.required_permission()
+    }
+
+    async fn execute(&self, input: Value) -> nucleus_plugin::Result<PluginOutput> {
+        match &self.runner {
+            Runner::Process => self.run_process(input).await,
+            #[cfg(feature = "wasm-plugins")]
+            Runner::Wasm(module) => module
+                .call(&input.to_string())
+                .await
+                .map(PluginOutput::new)
+                .map_err(|e| PluginError::ExecutionFailed(format!("{} failed: {:#}", self.manifest.name, e))),
+            #[cfg(feature = "dylib-plugins")]
+            Runner::Library(library) => library
+                .call(input.to_string())
+                .await
+                .map(PluginOutput::new)
+                .map_err(|e| PluginError::ExecutionFailed(format!("{} failed: {:#}", self.manifest.name, e))),
+        }
+    }
+}
+
+impl ExternalPlugin {
+    /// Runs the entry as a separate process, with `input` on its stdin.
+    async fn run_process(&self, input: Value) -> nucleus_plugin::Result<PluginOutput> {
+        let entry = self.dir.join(&self.manifest.entry);
+        let mut child = Command::new(&entry)
+            .args(&self.manifest.args)
+            .current_dir(&self.dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| PluginError::ExecutionFailed(format!("Failed to start {}: {}", entry.display(), e)))?;
+
+        // The exit status tells whether a plugin that does not read its input failed
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(input.to_string().as_bytes()).await;
+        }
+        let output = child.wait_with_output().await.map_err(|e| PluginError::ExecutionFailed(e.to_string()))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(PluginError::ExecutionFailed(format!(
+                "{} exited with {}: {}",
+                self.manifest.name,
+                output.status,
+                stderr.trim()
+            )));
+        }
+        Ok(PluginOutput::new(String::from_utf8_lossy(&output.stdout).trim_end()))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn plugin(root: &Path, dir: &str, manifest: &str) -> PathBuf {
+        let dir = root.join(dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(MANIFEST), manifest).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_discover_and_run_plugins() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = plugin(root.path(), "echo", "name: echo\ndescription: Echoes its input\nentry: ./run.sh\n");
+        std::fs::write(dir.join("run.sh"), "#!/bin/sh\necho \"got $(cat)\"\n").unwrap();
+        std::fs::set_permissions(dir.join("run.sh"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        plugin(root.path(), "wasm", "name: fast\ndescription: A WASM plugin\nentry: fast.wasm\n");
+        plugin(root.path(), "broken", "name: [\n");
+
+        let mut directory = PluginDirectory::scan(root.path());
+        let listed: Vec<(String, Option<PluginKind>, bool)> = directory
+            .list()
+            .into_iter()
+            .map(|plugin| (plugin.name, plugin.kind, plugin.problem.is_none()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("broken".to_string(), None, false),
+                ("echo".to_string(), Some(PluginKind::Executable), true),
+                ("fast".to_string(), Some(PluginKind::Wasm), false),
+            ]
+        );
+
+        let mut registry = PluginRegistry::new(Permission::ALL);
+        assert_eq!(directory.register(&mut registry), 1);
+        let output = registry.execute("echo", serde_json::json!({"x": 1})).await.unwrap();
+        assert_eq!(output.content, r#"got {"x":1}"#);
+        assert_eq!(directory.register(&mut PluginRegistry::new(Permission::READ_ONLY)), 0);
+
+        // A grant of its own replaces the default
+        let config = PluginsConfig {
+            grants: [("echo".to_string(), vec![Capability::Execute])].into_iter().collect(),
+            ..PluginsConfig::default()
+        };
+        let mut registry = PluginRegistry::new(Permission::NONE);
+        apply_grants(&config, &mut registry);
+        assert_eq!(directory.register(&mut registry), 1);
+        assert_eq!(directory.list()[1].requires, [Capability::Execute]);
+        // A WASM plugin runs sandboxed
+        assert!(directory.list()[2].requires.is_empty());
+
+        // Disabling is remembered in the directory
+        directory.set_enabled("echo", false).unwrap();
+        assert!(matches!(directory.set_enabled("missing", false), Err(PluginsError::NotFound(_))));
+        let directory = PluginDirectory::scan(root.path());
+        assert!(!directory.list()[1].enabled);
+        assert_eq!(directory.register(&mut PluginRegistry::new(Permission::ALL)), 0);
+    }
+}
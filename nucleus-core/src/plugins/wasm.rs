@@ -0,0 +1,143 @@
+//! WASM plugins, run by wasmtime with nothing imported into them.
+//!
+//! A module exports its `memory` and two functions: `alloc(len: i32) -> i32`
+//! returns where to write `len` bytes of input, and `call(ptr: i32, len: i32)
+//! -> i64` takes the arguments as JSON there and returns where its UTF-8
+//! result is, as `ptr << 32 | len`. A trap fails the call.
+//!
+//! Every call gets a fresh instance, limited to [`MEMORY_LIMIT`] bytes of
+//! memory. Running code yields to tokio every [`TICK`], so a call that does
+//! not return is stopped by the tool timeout like any other.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+use wasmtime::{Config, Engine, ExternType, Instance, Module, Store, StoreLimits, StoreLimitsBuilder, UpdateDeadline};
+
+/// Memory one call may grow to.
+pub const MEMORY_LIMIT: usize = 64 << 20;
+
+/// How long code runs before yielding.
+pub const TICK: Duration = Duration::from_millis(10);
+
+/// The engine every module is compiled for, with a thread ticking its epoch.
+fn engine() -> std::result::Result<&'static Engine, String> {
+    static ENGINE: OnceLock<std::result::Result<Engine, String>> = OnceLock::new();
+    ENGINE
+        .get_or_init(|| {
+            let mut config = Config::new();
+            config.async_support(true).epoch_interruption(true);
+            let engine = Engine::new(&config).map_err(|e| format!("Failed to start the WASM runtime: {:#}", e))?;
+            let ticking = engine.clone();
+            std::thread::Builder::new()
+                .name("wasm-epoch".to_string())
+                .spawn(move || loop {
+                    std::thread::sleep(TICK);
+                    ticking.increment_epoch();
+                })
+                .map_err(|e| format!("Failed to start the WASM runtime: {}", e))?;
+            Ok(engine)
+        })
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+/// A compiled WASM plugin.
+#[derive(Debug, Clone)]
+pub struct WasmModule {
+    module: Module,
+}
+
+impl WasmModule {
+    /// Compiles the module at `path`, or says why it cannot be a plugin.
+    pub fn load(path: &Path) -> std::result::Result<Self, String> {
+        let module = Module::from_file(engine()?, path).map_err(|e| format!("Invalid WASM module: {:#}", e))?;
+        if let Some(import) = module.imports().next() {
+            return Err(format!(
+                "Imports {}::{}, but WASM plugins are given nothing to import",
+                import.module(),
+                import.name()
+            ));
+        }
+        for (name, is_memory) in [("memory", true), ("alloc", false), ("call", false)] {
+            match module.get_export(name) {
+                Some(ExternType::Memory(_)) if is_memory => {}
+                Some(ExternType::Func(_)) if !is_memory => {}
+                _ => return Err(format!("Does not export `{}`", name)),
+            }
+        }
+        Ok(Self { module })
+    }
+
+    /// Runs `call` on `input` in a fresh instance.
+    pub async fn call(&self, input: &str) -> Result<String> {
+        let limits = StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build();
+        let mut store = Store::new(self.module.engine(), limits);
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store.epoch_deadline_callback(|_| Ok(UpdateDeadline::Yield(1)));
+        store.set_epoch_deadline(1);
+
+        let instance = Instance::new_async(&mut store, &self.module, &[]).await?;
+        let memory = instance.get_memory(&mut store, "memory").context("No `memory` export")?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let call = instance.get_typed_func::<(i32, i32), i64>(&mut store, "call")?;
+
+        let len = i32::try_from(input.len()).context("Input too large")?;
+        let ptr = alloc.call_async(&mut store, len).await?;
+        memory.write(&mut store, ptr as u32 as usize, input.as_bytes()).map_err(|_| anyhow!("`alloc` returned memory out of bounds"))?;
+        let result = call.call_async(&mut store, (ptr, len)).await? as u64;
+
+        let (start, len) = ((result >> 32) as usize, (result & 0xffff_ffff) as usize);
+        let Some(output) = memory.data(&store).get(start..start + len) else {
+            bail!("`call` returned memory out of bounds");
+        };
+        String::from_utf8(output.to_vec()).context("`call` returned invalid UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A module exporting `alloc`, which always answers 1024, and `call`
+    /// with the given body.
+    fn module(call: &[u8]) -> Vec<u8> {
+        let alloc: &[u8] = &[0x00, 0x41, 0x80, 0x08, 0x0b];
+        let mut code = vec![0x02, alloc.len() as u8];
+        code.extend_from_slice(alloc);
+        code.push(call.len() as u8);
+        code.extend_from_slice(call);
+
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        // (i32) -> i32 and (i32, i32) -> i64
+        wasm.extend_from_slice(&[0x01, 0x0c, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7e]);
+        wasm.extend_from_slice(&[0x03, 0x03, 0x02, 0x00, 0x01]);
+        wasm.extend_from_slice(&[0x05, 0x03, 0x01, 0x00, 0x01]);
+        wasm.extend_from_slice(&[0x07, 0x19, 0x03, 0x06]);
+        wasm.extend_from_slice(b"memory\x02\x00\x05alloc\x00\x00\x04call\x00\x01");
+        wasm.extend_from_slice(&[0x0a, code.len() as u8]);
+        wasm.extend_from_slice(&code);
+        wasm
+    }
+
+    fn load(wasm: &[u8]) -> std::result::Result<WasmModule, String> {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("plugin.wasm"), wasm).unwrap();
+        WasmModule::load(&dir.path().join("plugin.wasm"))
+    }
+
+    #[tokio::test]
+    async fn test_call_and_stop_wasm() {
+        // Returns its input: ptr << 32 | len
+        let echo = load(&module(&[0x00, 0x20, 0x00, 0xad, 0x42, 0x20, 0x86, 0x20, 0x01, 0xad, 0x84, 0x0b])).unwrap();
+        assert_eq!(echo.call(r#"{"x":1}"#).await.unwrap(), r#"{"x":1}"#);
+
+        // Loops forever, until the timeout drops it
+        let spin = load(&module(&[0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x42, 0x00, 0x0b])).unwrap();
+        let stopped = tokio::time::timeout(Duration::from_millis(200), spin.call("{}")).await;
+        assert!(stopped.is_err());
+
+        assert!(load(b"not wasm").unwrap_err().starts_with("Invalid WASM module"));
+    }
+}
@@ -0,0 +1,341 @@
+//! Annotated, depth-limited project tree summaries.
+//!
+//! Gives the model a compact picture of a repository's layout (directory
+//! sizes, dominant languages, and notable files like manifests and entry
+//! points) so questions such as "where should I add a new provider?" don't
+//! require guessing.
+//!
+//! ```text
+//! nucleus/ (142 files, 1.1 MB; Rust 81%, Markdown 12%)
+//! ├── Cargo.toml (2.1 KB) [manifest]
+//! ├── nucleus-core/ (64 files, 620 KB; Rust)
+//! │   ├── src/ (58 files, 590 KB; Rust)
+//! ...
+//! ```
+
+use crate::patterns;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Options controlling how much of the tree is shown.
+#[derive(Debug, Clone)]
+pub struct TreeOptions {
+    /// Directory levels to expand below the root
+    pub max_depth: usize,
+    /// Entries listed per directory before the rest are summarized
+    pub max_entries: usize,
+    /// File or directory names to skip (`*.ext` globs are supported)
+    pub exclude_patterns: Vec<String>,
+}
+
+impl Default for TreeOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 3,
+            max_entries: 25,
+            exclude_patterns: patterns::default_exclude_patterns(),
+        }
+    }
+}
+
+/// A file or directory in the summarized tree.
+#[derive(Debug, Clone)]
+pub struct TreeNode {
+    pub name: String,
+    pub is_dir: bool,
+    /// Total size in bytes (recursive for directories)
+    pub size: u64,
+    /// Number of files (recursive for directories, 1 for files)
+    pub file_count: usize,
+    /// Bytes per language (recursive for directories)
+    pub languages: HashMap<&'static str, u64>,
+    /// Why the file is notable (e.g. "manifest"), if it is
+    pub note: Option<&'static str>,
+    /// Children, only populated within `max_depth`
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// Languages by share of bytes, largest first, as `(language, percent)`.
+    pub fn language_shares(&self) -> Vec<(&'static str, u8)> {
+        let total: u64 = self.languages.values().sum();
+        if total == 0 {
+            return Vec::new();
+        }
+
+        let mut shares: Vec<(&'static str, u8)> = self
+            .languages
+            .iter()
+            .map(|(language, bytes)| (*language, ((bytes * 100) / total) as u8))
+            .collect();
+        shares.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        shares
+    }
+}
+
+/// Builds a summarized tree rooted at `root`.
+///
+/// # Errors
+///
+/// Returns an error if `root` cannot be read.
+pub fn build(root: &Path, options: &TreeOptions) -> std::io::Result<TreeNode> {
+    let name = root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| root.display().to_string());
+
+    build_dir(root, name, 0, options)
+}
+
+fn build_dir(path: &Path, name: String, depth: usize, options: &TreeOptions) -> std::io::Result<TreeNode> {
+    let mut node = TreeNode {
+        name,
+        is_dir: true,
+        size: 0,
+        file_count: 0,
+        languages: HashMap::new(),
+        note: None,
+        children: Vec::new(),
+    };
+
+    let mut entries: Vec<_> = fs::read_dir(path)?.filter_map(|e| e.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let entry_name = entry.file_name().to_string_lossy().to_string();
+        if is_excluded(&entry_name, &options.exclude_patterns) {
+            continue;
+        }
+
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        let child = if file_type.is_dir() {
+            // Unreadable subdirectories are skipped rather than failing the whole tree
+            match build_dir(&entry.path(), entry_name, depth + 1, options) {
+                Ok(child) => child,
+                Err(_) => continue,
+            }
+        } else if file_type.is_file() {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            file_node(entry_name, size)
+        } else {
+            continue;
+        };
+
+        node.size += child.size;
+        node.file_count += child.file_count;
+        for (language, bytes) in &child.languages {
+            *node.languages.entry(language).or_insert(0) += bytes;
+        }
+
+        if depth < options.max_depth {
+            node.children.push(child);
+        }
+    }
+
+    // Directories first, then files
+    node.children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+    Ok(node)
+}
+
+fn file_node(name: String, size: u64) -> TreeNode {
+    let mut languages = HashMap::new();
+    if let Some(language) = language_for(&name) {
+        languages.insert(language, size);
+    }
+
+    TreeNode {
+        note: notable_file(&name),
+        name,
+        is_dir: false,
+        size,
+        file_count: 1,
+        languages,
+        children: Vec::new(),
+    }
+}
+
+/// Renders the tree as indented text with size, language, and note annotations.
+pub fn render(root: &TreeNode, options: &TreeOptions) -> String {
+    let mut output = format!("{}\n", annotate(root));
+    render_children(root, "", options, &mut output);
+    output
+}
+
+fn render_children(node: &TreeNode, prefix: &str, options: &TreeOptions, output: &mut String) {
+    let shown = node.children.len().min(options.max_entries);
+    let hidden = &node.children[shown..];
+
+    for (i, child) in node.children[..shown].iter().enumerate() {
+        let last = i + 1 == shown && hidden.is_empty();
+        let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+
+        output.push_str(&format!("{}{}{}\n", prefix, branch, annotate(child)));
+        render_children(child, &format!("{}{}", prefix, indent), options, output);
+    }
+
+    if !hidden.is_empty() {
+        let files: usize = hidden.iter().map(|c| c.file_count).sum();
+        let size: u64 = hidden.iter().map(|c| c.size).sum();
+        output.push_str(&format!(
+            "{}└── … {} more entries ({} files, {})\n",
+            prefix,
+            hidden.len(),
+            files,
+            format_size(size)
+        ));
+    }
+}
+
+fn annotate(node: &TreeNode) -> String {
+    if !node.is_dir {
+        let mut line = format!("{} ({})", node.name, format_size(node.size));
+        if let Some(note) = node.note {
+            line.push_str(&format!(" [{}]", note));
+        }
+        return line;
+    }
+
+    let mut line = format!("{}/ ({} files, {}", node.name, node.file_count, format_size(node.size));
+    let shares = node.language_shares();
+    match shares.as_slice() {
+        [] => {}
+        [(language, _)] => line.push_str(&format!("; {}", language)),
+        shares => {
+            let top: Vec<String> = shares
+                .iter()
+                .take(3)
+                .filter(|(_, percent)| *percent > 0)
+                .map(|(language, percent)| format!("{} {}%", language, percent))
+                .collect();
+            line.push_str(&format!("; {}", top.join(", ")));
+        }
+    }
+    line.push(')');
+    line
+}
+
+/// Matches entry names against exclude patterns and binary extensions.
+///
+/// Unlike [`patterns::should_exclude`], this compares whole names, so
+/// `database.rs` is not hidden by the `data` pattern.
+fn is_excluded(name: &str, exclude_patterns: &[String]) -> bool {
+    let excluded = exclude_patterns.iter().any(|pattern| match pattern.strip_prefix('*') {
+        Some(suffix) => name.ends_with(suffix),
+        None => name == pattern,
+    });
+
+    excluded
+        || Path::new(name)
+            .extension()
+            .map(|ext| patterns::binary_extensions().contains(&ext.to_string_lossy().to_lowercase().as_str()))
+            .unwrap_or(false)
+}
+
+pub(crate) fn language_for(name: &str) -> Option<&'static str> {
+    let extension = Path::new(name).extension()?.to_str()?.to_lowercase();
+    let language = match extension.as_str() {
+        "rs" => "Rust",
+        "go" => "Go",
+        "py" => "Python",
+        "js" | "mjs" | "cjs" | "jsx" => "JavaScript",
+        "ts" | "tsx" => "TypeScript",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "cxx" | "hpp" => "C++",
+        "cs" => "C#",
+        "rb" => "Ruby",
+        "swift" => "Swift",
+        "sh" | "bash" | "zsh" => "Shell",
+        "md" | "markdown" => "Markdown",
+        "toml" | "yaml" | "yml" | "json" => "Config",
+        "html" | "css" | "scss" => "Web",
+        "sql" => "SQL",
+        _ => return None,
+    };
+    Some(language)
+}
+
+fn notable_file(name: &str) -> Option<&'static str> {
+    let lower = name.to_lowercase();
+    let note = match lower.as_str() {
+        "cargo.toml" | "package.json" | "go.mod" | "pyproject.toml" | "setup.py" | "pom.xml"
+        | "build.gradle" | "gemfile" => "manifest",
+        "main.rs" | "lib.rs" | "main.go" | "main.py" | "__main__.py" | "index.js" | "index.ts" => "entry point",
+        "mod.rs" | "__init__.py" => "module root",
+        "makefile" | "justfile" | "dockerfile" | "build.rs" => "build",
+        "license" | "license.md" | "license-mit" | "license-apache" => "license",
+        _ if lower.starts_with("readme") => "docs",
+        _ if lower.starts_with("contributing") => "docs",
+        _ => return None,
+    };
+    Some(note)
+}
+
+fn format_size(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = KB * 1024.0;
+
+    let bytes_f = bytes as f64;
+    if bytes_f >= MB {
+        format!("{:.1} MB", bytes_f / MB)
+    } else if bytes_f >= KB {
+        format!("{:.1} KB", bytes_f / KB)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_and_render() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/provider")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+        fs::write(root.join("README.md"), "# Project").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("src/provider/ollama.rs"), "pub struct Ollama;").unwrap();
+        fs::write(root.join("target/debug/app"), "binary").unwrap();
+
+        let options = TreeOptions {
+            max_depth: 2,
+            ..TreeOptions::default()
+        };
+        let tree = build(root, &options).unwrap();
+        assert_eq!(tree.file_count, 4);
+
+        let rendered = render(&tree, &options);
+        assert!(rendered.contains("Cargo.toml (9 B) [manifest]"));
+        assert!(rendered.contains("README.md (9 B) [docs]"));
+        assert!(rendered.contains("src/ (2 files, 30 B; Rust)"));
+        assert!(rendered.contains("main.rs (12 B) [entry point]"));
+        // Beyond max_depth, directories are summarized but not expanded
+        assert!(!rendered.contains("ollama.rs"));
+        assert!(!rendered.contains("target"));
+    }
+
+    #[test]
+    fn test_is_excluded_matches_whole_names() {
+        let patterns = patterns::default_exclude_patterns();
+        assert!(is_excluded("node_modules", &patterns));
+        assert!(is_excluded("foo.egg-info", &patterns));
+        assert!(is_excluded("logo.png", &patterns));
+        assert!(!is_excluded("database.rs", &patterns));
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(2048), "2.0 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
+}
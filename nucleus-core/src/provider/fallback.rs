@@ -0,0 +1,292 @@
+//! Provider that fails over between backends.
+//!
+//! Tries an ordered list of providers (e.g. local mistral.rs, then Ollama,
+//! then a remote API), retrying each according to its [`RetryPolicy`] while
+//! it is unavailable. A request only moves on before any output has been
+//! streamed, so callers never see two partial answers.
+
+use crate::config::RetryPolicy;
+use crate::models::EmbeddingModel;
+use super::types::*;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// A provider in a fallback chain.
+#[derive(Clone)]
+pub struct FallbackEntry {
+    pub provider: Arc<dyn Provider>,
+    pub retry: RetryPolicy,
+    /// Model to request from this provider (defaults to the request's model)
+    pub model: Option<String>,
+}
+
+impl FallbackEntry {
+    pub fn new(provider: Arc<dyn Provider>) -> Self {
+        Self {
+            provider,
+            retry: RetryPolicy::default(),
+            model: None,
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+}
+
+/// Tries providers in order until one answers.
+///
+/// Embeddings always come from the first provider, since vectors from
+/// different models cannot be compared.
+pub struct FallbackProvider {
+    entries: Vec<FallbackEntry>,
+}
+
+impl FallbackProvider {
+    /// Creates a chain; returns `None` if `entries` is empty.
+    pub fn new(entries: Vec<FallbackEntry>) -> Option<Self> {
+        (!entries.is_empty()).then_some(Self { entries })
+    }
+}
+
+/// Delay before retry number `retry` (counting from 0).
+fn backoff(policy: &RetryPolicy, retry: u32) -> Duration {
+    Duration::from_millis(policy.backoff_ms.saturating_mul(1 << retry.min(16)))
+}
+
+#[async_trait]
+impl Provider for FallbackProvider {
+    async fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+        mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+    ) -> Result<()> {
+        let mut last_error = None;
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            let mut request = request.clone();
+            if let Some(model) = &entry.model {
+                request.model = model.clone();
+            }
+
+            for attempt in 0..=entry.retry.max_retries {
+                if attempt > 0 {
+                    tokio::time::sleep(backoff(&entry.retry, attempt - 1)).await;
+                }
+
+                let mut streamed = false;
+                let result = entry
+                    .provider
+                    .chat(request.clone(), Box::new(|response: ChatResponse| {
+                        streamed |= !response.content.is_empty() || response.message.tool_calls.is_some();
+                        callback(response);
+                    }))
+                    .await;
+
+                match result {
+                    Ok(()) => return Ok(()),
+                    // Retrying now would repeat output the caller already has
+                    Err(e) if streamed || !e.is_unavailable() => return Err(e),
+                    Err(e) => {
+                        warn!(provider = index, attempt, "Provider unavailable: {}", e);
+                        last_error = Some(e);
+                    }
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| ProviderError::Other("No providers configured".to_string())))
+    }
+
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>> {
+        self.embed_batch(&[text], model)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ProviderError::Other("No embeddings returned".to_string()))
+    }
+
+    async fn embed_batch(&self, texts: &[&str], model: &EmbeddingModel) -> Result<Vec<Vec<f32>>> {
+        let entry = &self.entries[0];
+        let mut attempt = 0;
+        loop {
+            match entry.provider.embed_batch(texts, model).await {
+                Err(e) if e.is_unavailable() && attempt < entry.retry.max_retries => {
+                    warn!(attempt, "Embedding provider unavailable: {}", e);
+                    tokio::time::sleep(backoff(&entry.retry, attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn rerank(&self, query: &str, documents: &[&str], model: &str) -> Result<Vec<f32>> {
+        let entry = &self.entries[0];
+        let mut attempt = 0;
+        loop {
+            match entry.provider.rerank(query, documents, model).await {
+                Err(e) if e.is_unavailable() && attempt < entry.retry.max_retries => {
+                    warn!(attempt, "Reranking provider unavailable: {}", e);
+                    tokio::time::sleep(backoff(&entry.retry, attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Healthy if any provider in the chain is.
+    async fn health_check(&self) -> Result<()> {
+        let mut last_error = None;
+        for entry in &self.entries {
+            match entry.provider.health_check().await {
+                Ok(()) => return Ok(()),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        last_error.map_or(Ok(()), Err)
+    }
+
+    /// True if any provider in the chain is remote, since any of them may
+    /// receive the prompt.
+    fn is_remote(&self) -> bool {
+        self.entries.iter().any(|entry| entry.provider.is_remote())
+    }
+
+    /// What every provider in the chain supports, since any of them may answer.
+    async fn capabilities(&self, model: &str) -> Capabilities {
+        let mut capabilities: Option<Capabilities> = None;
+        for entry in &self.entries {
+            let entry_capabilities = entry.provider.capabilities(entry.model.as_deref().unwrap_or(model)).await;
+            capabilities = Some(match capabilities {
+                Some(capabilities) => capabilities.intersect(entry_capabilities),
+                None => entry_capabilities,
+            });
+        }
+        capabilities.unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Fails with the scripted errors, then answers with its name.
+    struct FlakyProvider {
+        name: &'static str,
+        failures: Mutex<Vec<ProviderError>>,
+        /// Streams this text before failing
+        partial: Option<&'static str>,
+        models: Mutex<Vec<String>>,
+    }
+
+    impl FlakyProvider {
+        fn new(name: &'static str, failures: Vec<ProviderError>) -> Self {
+            Self {
+                name,
+                failures: Mutex::new(failures),
+                partial: None,
+                models: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Provider for FlakyProvider {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> Result<()> {
+            self.models.lock().unwrap().push(request.model.clone());
+            let reply = |content: &str, done| ChatResponse {
+                model: request.model.clone(),
+                content: content.to_string(),
+                done,
+                truncated: false,
+                message: Message::assistant(None, content),
+            };
+
+            if let Some(partial) = self.partial {
+                callback(reply(partial, false));
+            }
+            let mut failures = self.failures.lock().unwrap();
+            if !failures.is_empty() {
+                return Err(failures.remove(0));
+            }
+            callback(reply(self.name, true));
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    fn no_backoff(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            backoff_ms: 0,
+        }
+    }
+
+    async fn answer(provider: &FallbackProvider) -> (Result<()>, String) {
+        let mut answer = String::new();
+        let request = ChatRequest::new("primary-model", vec![Message::user(None, "hi")]);
+        let result = provider.chat(request, Box::new(|response| answer.push_str(&response.content))).await;
+        (result, answer)
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_fails_over() {
+        let unavailable = || ProviderError::Other("model not loaded".to_string());
+        let primary = Arc::new(FlakyProvider::new("primary", vec![unavailable(), unavailable()]));
+        let backup = Arc::new(FlakyProvider::new("backup", vec![]));
+        let chain = FallbackProvider::new(vec![
+            FallbackEntry::new(primary.clone()).with_retry(no_backoff(1)),
+            FallbackEntry::new(backup.clone()).with_model("backup-model"),
+        ])
+        .unwrap();
+
+        let (result, answer) = answer(&chain).await;
+        assert!(result.is_ok());
+        assert_eq!(answer, "backup");
+        assert_eq!(primary.models.lock().unwrap().len(), 2);
+        assert_eq!(*backup.models.lock().unwrap(), vec!["backup-model"]);
+    }
+
+    #[tokio::test]
+    async fn test_no_fail_over_after_output() {
+        let primary = Arc::new(FlakyProvider {
+            partial: Some("Hel"),
+            ..FlakyProvider::new("primary", vec![ProviderError::Api("overloaded".to_string())])
+        });
+        let backup = Arc::new(FlakyProvider::new("backup", vec![]));
+        let chain = FallbackProvider::new(vec![FallbackEntry::new(primary), FallbackEntry::new(backup.clone())]).unwrap();
+
+        let (result, answer) = answer(&chain).await;
+        assert!(matches!(result, Err(ProviderError::Api(_))));
+        assert_eq!(answer, "Hel");
+        assert!(backup.models.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            backoff_ms: 100,
+        };
+        assert_eq!(backoff(&policy, 0), Duration::from_millis(100));
+        assert_eq!(backoff(&policy, 2), Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,398 @@
+//! llama.cpp provider implementation.
+//!
+//! An in-process alternative to mistral.rs built on the `llama-cpp-2`
+//! bindings. Many quantized GGUFs load faster and use less memory under
+//! llama.cpp. Requires the `llama-cpp` feature.
+//!
+//! Generation runs on a blocking thread, one llama.cpp context per request;
+//! pieces are streamed back as they are decoded. Tool calling is not
+//! supported: tool definitions in a request are ignored.
+
+use crate::config::LlamaCppConfig;
+use crate::models::EmbeddingModel;
+use crate::Config;
+
+use super::types::*;
+use async_trait::async_trait;
+use llama_cpp_2::context::params::{LlamaContextParams, LlamaPoolingType};
+use llama_cpp_2::context::LlamaContext;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::{AddBos, LlamaChatMessage, LlamaModel, Special};
+use llama_cpp_2::sampling::LlamaSampler;
+use llama_cpp_2::token::LlamaToken;
+use tracing::{debug, info};
+
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, OnceCell};
+
+/// llama.cpp may only be initialized once per process.
+static BACKEND: OnceCell<LlamaBackend> = OnceCell::const_new();
+
+async fn backend() -> Result<&'static LlamaBackend> {
+    BACKEND
+        .get_or_try_init(|| async {
+            LlamaBackend::init().map_err(|e| ProviderError::Other(format!("Failed to initialize llama.cpp: {}", e)))
+        })
+        .await
+}
+
+/// llama.cpp in-process provider.
+///
+/// Note: Use async `new()` - the model may need to be downloaded.
+pub struct LlamaCppProvider {
+    model: Arc<LlamaModel>,
+    model_name: String,
+    settings: LlamaCppConfig,
+    context_length: u32,
+    embedding_model: OnceCell<Arc<LlamaModel>>,
+}
+
+impl LlamaCppProvider {
+    /// Creates a new llama.cpp provider, loading `llm.model`.
+    ///
+    /// # Model Resolution
+    ///
+    /// - `"/path/file.gguf"` - Local GGUF file (`~` is expanded)
+    /// - `"repo:file.gguf"` - GGUF file from a HuggingFace repository, downloaded on first use
+    pub async fn new(config: &Config) -> Result<Self> {
+        let settings = config.llm.llama_cpp.clone();
+        let path = resolve_gguf(&config.llm.model).await?;
+        let model = load_model(path, &settings).await?;
+
+        Ok(Self {
+            model,
+            model_name: config.llm.model.clone(),
+            settings,
+            context_length: u32::try_from(config.llm.context_length).unwrap_or(u32::MAX),
+            embedding_model: OnceCell::new(),
+        })
+    }
+}
+
+/// Context parameters; built on the thread that uses them, since they hold
+/// raw pointers.
+fn context_params(settings: &LlamaCppConfig, n_ctx: u32) -> LlamaContextParams {
+    let mut params = LlamaContextParams::default()
+        .with_n_ctx(NonZeroU32::new(n_ctx))
+        .with_n_batch(settings.batch_size);
+    if let Some(threads) = settings.threads {
+        params = params.with_n_threads(threads).with_n_threads_batch(threads);
+    }
+    params
+}
+
+/// Finds the GGUF file for a model spec, downloading it if needed.
+async fn resolve_gguf(spec: &str) -> Result<PathBuf> {
+    let expanded = match spec.strip_prefix('~') {
+        Some(rest) => {
+            let home = std::env::var("HOME")
+                .map_err(|_| ProviderError::Other("HOME environment variable not set".to_string()))?;
+            format!("{}{}", home, rest)
+        }
+        None => spec.to_string(),
+    };
+    if Path::new(&expanded).is_file() {
+        return Ok(PathBuf::from(expanded));
+    }
+
+    // HuggingFace GGUF format: "Repo/Model-GGUF:filename.gguf"
+    let Some((repo, file)) = spec.split_once(':') else {
+        return Err(ProviderError::Other(format!(
+            "llama.cpp needs a GGUF file: expected a local path or 'Repo/Model-GGUF:file.gguf', got '{}'",
+            spec
+        )));
+    };
+    info!(repo, file, "Fetching GGUF from HuggingFace");
+    let api = hf_hub::api::tokio::Api::new()
+        .map_err(|e| ProviderError::Other(format!("Failed to set up HuggingFace downloads: {}", e)))?;
+    api.model(repo.to_string())
+        .get(file)
+        .await
+        .map_err(|e| ProviderError::Other(format!("Failed to download '{}' from HuggingFace: {}", spec, e)))
+}
+
+async fn load_model(path: PathBuf, settings: &LlamaCppConfig) -> Result<Arc<LlamaModel>> {
+    let backend = backend().await?;
+    let gpu_layers = settings.gpu_layers;
+
+    info!(path = %path.display(), gpu_layers, "Loading GGUF with llama.cpp");
+    let model = tokio::task::spawn_blocking(move || {
+        let params = LlamaModelParams::default().with_n_gpu_layers(gpu_layers);
+        LlamaModel::load_from_file(backend, &path, &params)
+            .map_err(|e| ProviderError::Other(format!("Failed to load GGUF '{}': {}", path.display(), e)))
+    })
+    .await
+    .map_err(|e| ProviderError::Other(e.to_string()))??;
+    Ok(Arc::new(model))
+}
+
+/// Formats the conversation with the model's chat template.
+fn render_prompt(model: &LlamaModel, messages: &[Message]) -> Result<String> {
+    let template = model
+        .chat_template(None)
+        .map_err(|e| ProviderError::Other(format!("Model has no usable chat template: {}", e)))?;
+    let messages = messages
+        .iter()
+        .map(|message| LlamaChatMessage::new(message.role.clone(), message.content.clone()))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| ProviderError::Other(format!("Invalid chat message: {}", e)))?;
+
+    model
+        .apply_chat_template(&template, &messages, true)
+        .map_err(|e| ProviderError::Other(format!("Failed to apply chat template: {}", e)))
+}
+
+#[allow(deprecated)]
+fn token_bytes(model: &LlamaModel, token: LlamaToken) -> Result<Vec<u8>> {
+    model
+        .token_to_bytes(token, Special::Tokenize)
+        .map_err(|e| ProviderError::Other(format!("Failed to decode token: {}", e)))
+}
+
+/// Splits off the longest valid UTF-8 prefix of `pending`, keeping a
+/// multi-byte character split across tokens for the next piece.
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(text) => text.len(),
+        // An invalid sequence (rather than an incomplete one) is replaced
+        Err(e) if e.error_len().is_some() => pending.len(),
+        Err(e) => e.valid_up_to(),
+    };
+    let piece: Vec<u8> = pending.drain(..valid).collect();
+    String::from_utf8_lossy(&piece).into_owned()
+}
+
+/// Generates a completion for `prompt`, sending text pieces to `pieces`.
+///
+/// Stops early when the receiver is dropped (the request was abandoned).
+/// Returns whether generation was cut off at the request's `max_tokens`.
+fn generate(
+    model: &LlamaModel,
+    mut context: LlamaContext,
+    prompt: &str,
+    request: &ChatRequest,
+    pieces: mpsc::UnboundedSender<String>,
+) -> Result<bool> {
+    let tokens = model
+        .str_to_token(prompt, AddBos::Always)
+        .map_err(|e| ProviderError::Other(format!("Failed to tokenize prompt: {}", e)))?;
+    let n_ctx = context.n_ctx() as usize;
+    if tokens.len() >= n_ctx {
+        return Err(ProviderError::Other(format!(
+            "Prompt is {} tokens, but the context holds {}",
+            tokens.len(),
+            n_ctx
+        )));
+    }
+
+    let batch_size = (context.n_batch() as usize).max(1);
+    let mut batch = LlamaBatch::new(batch_size, 1);
+    let last = tokens.len().saturating_sub(1);
+    for (start, chunk) in tokens.chunks(batch_size).enumerate() {
+        batch.clear();
+        for (offset, token) in chunk.iter().enumerate() {
+            let position = start * batch_size + offset;
+            batch
+                .add(*token, position as i32, &[0], position == last)
+                .map_err(|e| ProviderError::Other(format!("Failed to queue prompt: {}", e)))?;
+        }
+        context
+            .decode(&mut batch)
+            .map_err(|e| ProviderError::Other(format!("Failed to process prompt: {}", e)))?;
+    }
+
+    let mut sampler = if request.temperature <= 0.0 {
+        LlamaSampler::greedy()
+    } else {
+        LlamaSampler::chain_simple([LlamaSampler::temp(request.temperature as f32), LlamaSampler::dist(rand_seed())])
+    };
+
+    let max_tokens = request.max_tokens.map_or(usize::MAX, |max| max as usize);
+    let mut position = tokens.len();
+    let mut pending = Vec::new();
+    let mut generated = 0;
+    let mut truncated = false;
+    while position < n_ctx {
+        let token = sampler.sample(&context, batch.n_tokens() - 1);
+        sampler.accept(token);
+        if model.is_eog_token(token) {
+            break;
+        }
+        // Only a token past the limit, not the end of generation, truncates
+        if generated == max_tokens {
+            truncated = true;
+            break;
+        }
+
+        pending.extend(token_bytes(model, token)?);
+        let piece = take_utf8(&mut pending);
+        if !piece.is_empty() && pieces.send(piece).is_err() {
+            debug!("Generation abandoned by the caller");
+            return Ok(false);
+        }
+
+        batch.clear();
+        batch
+            .add(token, position as i32, &[0], true)
+            .map_err(|e| ProviderError::Other(format!("Failed to queue token: {}", e)))?;
+        context
+            .decode(&mut batch)
+            .map_err(|e| ProviderError::Other(format!("Failed to generate: {}", e)))?;
+        position += 1;
+        generated += 1;
+    }
+
+    if !pending.is_empty() {
+        let _ = pieces.send(String::from_utf8_lossy(&pending).into_owned());
+    }
+    Ok(truncated)
+}
+
+fn rand_seed() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.subsec_nanos())
+}
+
+#[async_trait]
+impl Provider for LlamaCppProvider {
+    async fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+        mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+    ) -> Result<()> {
+        if request.tools.is_some() {
+            debug!("llama.cpp provider ignores tool definitions");
+        }
+        let backend = backend().await?;
+        let model = Arc::clone(&self.model);
+        let settings = self.settings.clone();
+        let n_ctx = self.context_length;
+
+        // Chunks follow the Ollama provider's protocol: each carries only the
+        // new text, and the stream ends with an empty `done` chunk.
+        let (sender, mut pieces) = mpsc::unbounded_channel();
+        let generation = tokio::task::spawn_blocking(move || {
+            let prompt = render_prompt(&model, &request.messages)?;
+            let context = model
+                .new_context(backend, context_params(&settings, n_ctx))
+                .map_err(|e| ProviderError::Other(format!("Failed to create llama.cpp context: {}", e)))?;
+            generate(&model, context, &prompt, &request, sender)
+        });
+
+        while let Some(piece) = pieces.recv().await {
+            callback(ChatResponse {
+                model: self.model_name.clone(),
+                content: piece.clone(),
+                done: false,
+                truncated: false,
+                message: Message::assistant(None, piece),
+            });
+        }
+        let truncated = generation.await.map_err(|e| ProviderError::Other(format!("Generation failed: {}", e)))??;
+
+        callback(ChatResponse {
+            model: self.model_name.clone(),
+            content: String::new(),
+            done: true,
+            truncated,
+            message: Message::assistant(None, ""),
+        });
+        Ok(())
+    }
+
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>> {
+        self.embed_batch(&[text], model)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ProviderError::Other("No embeddings returned".to_string()))
+    }
+
+    async fn embed_batch(&self, texts: &[&str], model: &EmbeddingModel) -> Result<Vec<Vec<f32>>> {
+        // Lazy load the embedding model on first use
+        let embedding_model = self.embedding_model
+            .get_or_try_init(|| async {
+                let spec = match &model.path {
+                    Some(path) => path.to_string_lossy().into_owned(),
+                    None => model.hf_repo.clone().unwrap_or_else(|| model.id.clone()),
+                };
+                let path = resolve_gguf(&spec).await.map_err(|e| ProviderError::Other(format!(
+                    "{}\n\nSet rag.embedding_model.path to a local GGUF embedding model, or \
+                     rag.embedding_model.hf_repo to 'Repo/Model-GGUF:file.gguf'.", e
+                )))?;
+                load_model(path, &self.settings).await
+            })
+            .await?;
+
+        let backend = backend().await?;
+        let model = Arc::clone(embedding_model);
+        let texts: Vec<String> = texts.iter().map(|text| text.to_string()).collect();
+        let settings = self.settings.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let params = context_params(&settings, model.n_ctx_train())
+                .with_n_ubatch(settings.batch_size)
+                .with_embeddings(true)
+                .with_pooling_type(LlamaPoolingType::Mean);
+            let mut context = model
+                .new_context(backend, params)
+                .map_err(|e| ProviderError::Other(format!("Failed to create llama.cpp context: {}", e)))?;
+
+            let mut embeddings = Vec::with_capacity(texts.len());
+            for text in &texts {
+                let mut tokens = model
+                    .str_to_token(text, AddBos::Always)
+                    .map_err(|e| ProviderError::Other(format!("Failed to tokenize: {}", e)))?;
+                // Longer texts are truncated to one batch
+                tokens.truncate(context.n_batch() as usize);
+
+                let mut batch = LlamaBatch::new(tokens.len().max(1), 1);
+                batch
+                    .add_sequence(&tokens, 0, false)
+                    .map_err(|e| ProviderError::Other(format!("Failed to queue text: {}", e)))?;
+                context.clear_kv_cache();
+                context
+                    .decode(&mut batch)
+                    .map_err(|e| ProviderError::Other(format!("Failed to embed: {}", e)))?;
+
+                let embedding = context
+                    .embeddings_seq_ith(0)
+                    .map_err(|e| ProviderError::Other(format!("Failed to read embedding: {}", e)))?;
+                embeddings.push(embedding.to_vec());
+            }
+            Ok(embeddings)
+        })
+        .await
+        .map_err(|e| ProviderError::Other(format!("Embedding failed: {}", e)))?
+    }
+
+    /// Tool definitions are ignored; the context is the one every request gets.
+    async fn capabilities(&self, _model: &str) -> Capabilities {
+        Capabilities {
+            max_context: Some(self.context_length as usize),
+            ..Capabilities::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_utf8_keeps_split_characters() {
+        // "é" is 0xC3 0xA9, split across two tokens
+        let mut pending = vec![b'c', b'a', b'f', 0xC3];
+        assert_eq!(take_utf8(&mut pending), "caf");
+        assert_eq!(pending, vec![0xC3]);
+
+        pending.push(0xA9);
+        assert_eq!(take_utf8(&mut pending), "é");
+        assert!(pending.is_empty());
+    }
+}
@@ -12,7 +12,7 @@ use async_trait::async_trait;
 use mistralrs::{
     EmbeddingModelBuilder, Function, GgufModelBuilder, IsqType, Model, PagedAttentionMetaBuilder, RequestBuilder, Response, TextMessageRole, TextMessages, TextModelBuilder, Tool as MistralTool, ToolChoice, ToolType
 };
-use nucleus_plugin::PluginRegistry;
+use crate::plugins::SharedRegistry;
 use tracing::{debug, info, warn};
 
 use std::path::Path;
@@ -29,7 +29,7 @@ use tokio::sync::OnceCell;
 pub struct MistralRsProvider {
     model: Arc<Model>,
     model_name: String,
-    registry: Arc<PluginRegistry>,
+    registry: SharedRegistry,
     config: Config,
     embedding_model: OnceCell<Arc<Model>>,
 }
@@ -61,7 +61,7 @@ impl MistralRsProvider {
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn new(config: &Config, registry: Arc<PluginRegistry>) -> Result<Self> {
+    pub async fn new(config: &Config, registry: SharedRegistry) -> Result<Self> {
         let model_name = config.llm.model.clone();
         
         // Log which backend we're using
@@ -81,7 +81,7 @@ impl MistralRsProvider {
         })
     }
 
-    async fn build_model(config: Config, registry: Arc<PluginRegistry>) -> Result<Model> {
+    async fn build_model(config: Config, registry: SharedRegistry) -> Result<Model> {
         let model_name = config.llm.model;
 
         // Expand tilde in path if present
@@ -187,55 +187,28 @@ impl Provider for MistralRsProvider {
 
         // Convert to RequestBuilder
         let mut builder = RequestBuilder::from(messages);
+        
+        if let Some(max_tokens) = request.max_tokens {
+            builder = builder.set_sampler_max_len(max_tokens as usize);
+        }
 
-        // Convert plugins to mistral.rs tool definitions
-        // Tool calls are returned in the response for nucleus to execute
-        if self.registry.get_count() > 0 {
-            let plugins = self.registry.all();
-            info!(plugin_count = plugins.len(), "Converting plugins to mistral.rs tools");
-            
-            let mistral_tools: Vec<MistralTool> = plugins
+        // Convert the request's tools (or else the registry's plugins) to
+        // mistral.rs tool definitions. Tool calls are returned in the response
+        // for nucleus to execute; in a model worker only the request has tools.
+        let mistral_tools: Vec<MistralTool> = match &request.tools {
+            Some(tools) if !tools.is_empty() => tools
                 .iter()
-                .map(|plugin| {
-                    let schema = plugin.parameter_schema();
-                    debug!(
-                        tool_name = %plugin.name(),
-                        description = %plugin.description(),
-                        "Processing plugin"
-                    );
-                    debug!(parameters = ?schema, "Plugin parameter schema");
-                    
-                    // Extract properties from JSON Schema format
-                    // Input: {"type": "object", "properties": {"path": {...}}, "required": [...]}
-                    // Output: HashMap<String, Value> of just the properties
-                    let parameters = if let Some(props) = schema.get("properties") {
-                        if let Some(obj) = props.as_object() {
-                            let extracted = obj.clone().into_iter().collect();
-                            debug!(
-                                properties = ?obj.keys().collect::<Vec<_>>(),
-                                "Extracted tool properties"
-                            );
-                            Some(extracted)
-                        } else {
-                            warn!("Plugin properties field is not an object");
-                            None
-                        }
-                    } else {
-                        debug!("No properties field in schema, using as-is");
-                        serde_json::from_value(schema).ok()
-                    };
-                    
-                    MistralTool {
-                        tp: ToolType::Function,
-                        function: Function {
-                            name: plugin.name().to_string(),
-                            description: Some(plugin.description().to_string()),
-                            parameters,
-                        },
-                    }
-                })
-                .collect();
-            
+                .map(|tool| mistral_tool(&tool.function.name, &tool.function.description, tool.function.parameters.clone()))
+                .collect(),
+            _ => self.registry
+                .read()
+                .await
+                .all()
+                .iter()
+                .map(|plugin| mistral_tool(plugin.name(), plugin.description(), plugin.parameter_schema()))
+                .collect(),
+        };
+        if !mistral_tools.is_empty() {
             info!(tool_count = mistral_tools.len(), "Setting tools with ToolChoice::Auto");
             builder = builder.set_tools(mistral_tools).set_tool_choice(ToolChoice::Auto);
         }
@@ -255,9 +228,11 @@ impl Provider for MistralRsProvider {
         })?
         .map_err(|e| ProviderError::Other(format!("Failed to create stream: {:?}", e)))?;
         
-        let mut accumulated_content = String::new();
-        let mut final_tool_calls = None;
+        // Chunks follow the Ollama provider's protocol: each carries only the new
+        // text, tool calls are sent in the chunk they arrive in, and the stream
+        // ends with an empty `done` chunk.
         let mut message_role = String::from("assistant"); // Default, will be updated from stream
+        let mut truncated = false;
 
         // Process stream chunks with timeout per chunk to avoid hangs
         let chunk_timeout = std::time::Duration::from_secs(30);
@@ -274,81 +249,85 @@ impl Provider for MistralRsProvider {
             let Some(chunk) = chunk_opt else { break; };
             match chunk {
                 Response::Chunk(resp) => {
-                    if let Some(choice) = resp.choices.first() {
-                        // Capture role from stream
-                        message_role = choice.delta.role.clone();
-                        
-                        // Stream content incrementally
-                        if let Some(content) = &choice.delta.content {
-                            accumulated_content.push_str(content);
-                            
-                            // Send incremental update to callback
-                            callback(ChatResponse {
-                                model: self.model_name.clone(),
-                                content: content.clone(),
-                                done: false,
-                                message: Message {
-                                    role: message_role.clone(),
-                                    content: accumulated_content.clone(),
-                                    context: None,
-                                    images: None,
-                                    tool_calls: None,
+                    let Some(choice) = resp.choices.first() else { continue; };
+                    truncated |= choice.finish_reason.as_deref() == Some("length");
+                    
+                    // Capture role from stream
+                    message_role = choice.delta.role.clone();
+                    
+                    let content = choice.delta.content.clone().unwrap_or_default();
+                    let tool_calls = choice.delta.tool_calls.as_ref().map(|tcs| {
+                        tcs.iter()
+                            .map(|tc| super::types::ToolCall {
+                                function: super::types::ToolCallFunction {
+                                    name: tc.function.name.clone(),
+                                    arguments: serde_json::from_str(&tc.function.arguments)
+                                        .unwrap_or(serde_json::json!({})),
                                 },
-                            });
-                        }
-                        
-                        // Capture tool calls if present
-                        if let Some(tcs) = &choice.delta.tool_calls {
-                            final_tool_calls = Some(
-                                tcs.iter()
-                                    .map(|tc| super::types::ToolCall {
-                                        function: super::types::ToolCallFunction {
-                                            name: tc.function.name.clone(),
-                                            arguments: serde_json::from_str(&tc.function.arguments)
-                                                .unwrap_or(serde_json::json!({})),
-                                        },
-                                    })
-                                    .collect(),
-                            );
-                        }
+                            })
+                            .collect()
+                    });
+                    if content.is_empty() && tool_calls.is_none() {
+                        continue;
                     }
+                    
+                    // Send each token to the callback as soon as it arrives
+                    callback(ChatResponse {
+                        model: self.model_name.clone(),
+                        content: content.clone(),
+                        done: false,
+                        truncated: false,
+                        message: Message {
+                            role: message_role.clone(),
+                            content,
+                            context: None,
+                            images: None,
+                            tool_calls,
+                        },
+                    });
                 }
                 Response::Done(_) => {
                     break;
                 }
+                Response::ModelError(message, _) => {
+                    return Err(ProviderError::Other(format!("Model error: {}", message)));
+                }
+                Response::InternalError(e) => {
+                    return Err(ProviderError::Other(format!("Internal error: {}", e)));
+                }
+                Response::ValidationError(e) => {
+                    return Err(ProviderError::Other(format!("Invalid request: {}", e)));
+                }
                 _ => {}
             }
         }
 
-        // Send final done=true message with captured role
         callback(ChatResponse {
             model: self.model_name.clone(),
-            content: accumulated_content.clone(),
+            content: String::new(),
             done: true,
+            truncated,
             message: Message {
                 role: message_role,
-                content: accumulated_content,
+                content: String::new(),
                 context: None,
                 images: None,
-                tool_calls: final_tool_calls,
+                tool_calls: None,
             },
         });
 
         Ok(())
     }
 
-    async fn embed(&self, text: &str, _model: &EmbeddingModel) -> Result<Vec<f32>> {
-        // Lazy load embedding model on first use
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>> {
+        // Lazy load embedding model on first use; it runs in-process, so RAG
+        // works offline once the weights are available
         let embedding_model = self.embedding_model
             .get_or_try_init(|| async {
-                let model_path: String  = match &self.config.rag.embedding_model.path {
-                    Some(path) => path.to_string_lossy().into(),
-                    None => self.config.rag.embedding_model.hf_repo.clone().unwrap_or("Nucleus Registry".to_string())
-                };
-
-                info!("Loading embedding model from: {}", model_path);
+                let model_source = embedding_source(model)?;
+                info!("Loading embedding model from: {}", model_source);
                 
-                let model = EmbeddingModelBuilder::new(model_path.clone())
+                let loaded = EmbeddingModelBuilder::new(model_source.clone())
                     .with_logging()
                     .with_throughput_logging()
                     .with_token_source(mistralrs::TokenSource::None)
@@ -357,11 +336,12 @@ impl Provider for MistralRsProvider {
                     .map_err(|e| {
                         ProviderError::Other(
                             format!("Failed to load embedding model from '{}': {:?}\n\n\
-                                Make sure the model exists at that path.", model_path, e)
+                                Set rag.embedding_model.path to a local model directory, or \
+                                rag.embedding_model.hf_repo to a HuggingFace repository.", model_source, e)
                         )
                     })?;
                 
-                Ok::<Arc<Model>, ProviderError>(Arc::new(model))
+                Ok::<Arc<Model>, ProviderError>(Arc::new(loaded))
             })
             .await?;
         
@@ -375,4 +355,60 @@ impl Provider for MistralRsProvider {
         
         Ok(embedding)
     }
+    
+    async fn capabilities(&self, _model: &str) -> Capabilities {
+        Capabilities { tools: true, ..Capabilities::default() }
+    }
+}
+
+/// Where to load an embedding model from: its local path (with `~`
+/// expanded), else its HuggingFace repository, else its ID as a repository.
+/// Builds a mistral.rs tool definition from a tool's JSON Schema.
+fn mistral_tool(name: &str, description: &str, schema: serde_json::Value) -> MistralTool {
+    debug!(tool_name = %name, description = %description, parameters = ?schema, "Processing tool");
+
+    // Extract properties from JSON Schema format
+    // Input: {"type": "object", "properties": {"path": {...}}, "required": [...]}
+    // Output: HashMap<String, Value> of just the properties
+    let parameters = if let Some(props) = schema.get("properties") {
+        if let Some(obj) = props.as_object() {
+            let extracted = obj.clone().into_iter().collect();
+            debug!(
+                properties = ?obj.keys().collect::<Vec<_>>(),
+                "Extracted tool properties"
+            );
+            Some(extracted)
+        } else {
+            warn!("Tool properties field is not an object");
+            None
+        }
+    } else {
+        debug!("No properties field in schema, using as-is");
+        serde_json::from_value(schema).ok()
+    };
+
+    MistralTool {
+        tp: ToolType::Function,
+        function: Function {
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            parameters,
+        },
+    }
+}
+
+fn embedding_source(model: &EmbeddingModel) -> Result<String> {
+    let Some(path) = &model.path else {
+        return Ok(model.hf_repo.clone().unwrap_or_else(|| model.id.clone()));
+    };
+
+    let path = path.to_string_lossy();
+    match path.strip_prefix('~') {
+        Some(rest) => {
+            let home = std::env::var("HOME")
+                .map_err(|_| ProviderError::Other("HOME environment variable not set".to_string()))?;
+            Ok(format!("{}{}", home, rest))
+        }
+        None => Ok(path.into_owned()),
+    }
 }
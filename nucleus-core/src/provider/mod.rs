@@ -1,19 +1,32 @@
 //! LLM provider abstraction layer.
 //!
 //! This module defines a common interface for different LLM backends
-//! (Ollama, mistral.rs, etc.) to provide chat completions and embeddings.
+//! (Ollama, mistral.rs, llama.cpp, OpenAI-compatible APIs) to provide chat completions and embeddings.
 
+pub mod fallback;
+#[cfg(feature = "llama-cpp")]
+pub mod llamacpp;
 pub mod mistralrs;
 pub mod ollama;
+pub mod openai;
+mod rerank;
+pub mod resilient;
 mod types;
 mod utils;
+pub mod worker;
 
 // Re-export common types
 pub use types::{
-    ChatRequest, ChatResponse, EmbedRequest, EmbedResponse, Message, Provider, ProviderError,
+    Capabilities, ChatRequest, ChatResponse, EmbedRequest, EmbedResponse, Message, Provider, ProviderError,
     Result, Tool, ToolCall, ToolCallFunction, ToolFunction,
 };
 
 // Re-export provider implementations
+pub use fallback::{FallbackEntry, FallbackProvider};
+#[cfg(feature = "llama-cpp")]
+pub use llamacpp::LlamaCppProvider;
 pub use mistralrs::MistralRsProvider;
 pub use ollama::OllamaProvider;
+pub use openai::OpenAiProvider;
+pub use resilient::ResilientProvider;
+pub use worker::WorkerProvider;
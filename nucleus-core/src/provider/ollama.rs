@@ -64,6 +64,9 @@ impl Provider for OllamaProvider {
             options: {
                 let mut opts = HashMap::new();
                 opts.insert("temperature".to_string(), serde_json::json!(request.temperature));
+                if let Some(max_tokens) = request.max_tokens {
+                    opts.insert("num_predict".to_string(), serde_json::json!(max_tokens));
+                }
                 Some(opts)
             },
             stream: true,
@@ -112,6 +115,7 @@ impl Provider for OllamaProvider {
                         model: ollama_response.model.clone(),
                         content: ollama_response.message.content.clone(),
                         done: ollama_response.done,
+                        truncated: ollama_response.done_reason.as_deref() == Some("length"),
                         message: Message {
                             role: ollama_response.message.role.clone(),
                             content: ollama_response.message.content.clone(),
@@ -134,12 +138,24 @@ impl Provider for OllamaProvider {
         Ok(())
     }
     
-    async fn embed(&self, text: &str, _model: &EmbeddingModel) -> Result<Vec<f32>> {
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>> {
+        self.embed_batch(&[text], model)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ProviderError::Other("No embeddings returned".to_string()))
+    }
+    
+    /// Embeds all texts in one request; `/api/embed` takes a list of inputs.
+    async fn embed_batch(&self, texts: &[&str], _model: &EmbeddingModel) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
         let url = format!("{}/api/embed", self.base_url);
         
-        let embed_request = EmbedRequest {
-            model: self.config.rag.embedding_model.name.clone(),
-            input: text.to_string(),
+        let embed_request = OllamaEmbedRequest {
+            model: &self.config.rag.embedding_model.name,
+            input: texts,
         };
         
         let response = self.http_client
@@ -154,16 +170,101 @@ impl Provider for OllamaProvider {
         }
         
         let embed_response = response.json::<EmbedResponse>().await?;
+        if embed_response.embeddings.len() != texts.len() {
+            return Err(ProviderError::Other(format!(
+                "Expected {} embeddings, got {}",
+                texts.len(),
+                embed_response.embeddings.len()
+            )));
+        }
+        Ok(embed_response.embeddings)
+    }
+    
+    async fn health_check(&self) -> Result<()> {
+        let url = format!("{}/api/version", self.base_url);
+        let response = self.http_client
+            .get(&url)
+            .timeout(std::time::Duration::from_secs(5))
+            .send()
+            .await?;
         
-        embed_response.embeddings
-            .into_iter()
-            .next()
-            .ok_or_else(|| ProviderError::Other("No embeddings returned".to_string()))
+        if !response.status().is_success() {
+            return Err(ProviderError::Api(format!("Ollama health check returned {}", response.status())));
+        }
+        Ok(())
+    }
+    
+    fn is_remote(&self) -> bool {
+        !crate::config::is_local_url(&self.base_url)
+    }
+    
+    /// Reads the model's capabilities from `/api/show`; servers too old to
+    /// list them are assumed to support tools but not images.
+    async fn capabilities(&self, model: &str) -> Capabilities {
+        let url = format!("{}/api/show", self.base_url);
+        let show = async {
+            let response = self.http_client
+                .post(&url)
+                .json(&serde_json::json!({ "model": model }))
+                .timeout(std::time::Duration::from_secs(5))
+                .send()
+                .await?
+                .error_for_status()?;
+            response.json::<OllamaShowResponse>().await
+        };
+        match show.await {
+            Ok(show) => show.capabilities(),
+            Err(e) => {
+                tracing::debug!("Failed to read the capabilities of {}: {}", model, e);
+                Capabilities { tools: true, ..Capabilities::default() }
+            }
+        }
+    }
+}
+
+/// The parts of an `/api/show` response that describe what a model can do.
+#[derive(Debug, Deserialize)]
+struct OllamaShowResponse {
+    /// e.g. `completion`, `tools`, `vision`, `embedding`; missing before Ollama 0.6.4
+    #[serde(default)]
+    capabilities: Option<Vec<String>>,
+    /// Architecture details, including `<architecture>.context_length`
+    #[serde(default)]
+    model_info: HashMap<String, serde_json::Value>,
+}
+
+impl OllamaShowResponse {
+    fn capabilities(&self) -> Capabilities {
+        let has = |capability: &str| {
+            self.capabilities
+                .as_ref()
+                .is_none_or(|capabilities| capabilities.iter().any(|c| c == capability))
+        };
+        let max_context = self
+            .model_info
+            .iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64())
+            .map(|tokens| tokens as usize);
+        Capabilities {
+            streaming: true,
+            tools: has("tools"),
+            vision: self.capabilities.is_some() && has("vision"),
+            // Embeddings come from `rag.embedding_model`, not this model
+            embeddings: true,
+            max_context,
+        }
     }
 }
 
 // Ollama-specific request/response types (internal)
 
+#[derive(Debug, Serialize)]
+struct OllamaEmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [&'a str],
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct OllamaChatRequest {
     model: String,
@@ -226,3 +327,27 @@ struct OllamaToolCallFunction {
     name: String,
     arguments: serde_json::Value,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show_capabilities() {
+        let show: OllamaShowResponse = serde_json::from_str(r#"{
+            "capabilities": ["completion", "vision"],
+            "model_info": {"general.architecture": "gemma3", "gemma3.context_length": 131072}
+        }"#).unwrap();
+        let capabilities = show.capabilities();
+        assert!(!capabilities.tools);
+        assert!(capabilities.vision);
+        assert_eq!(capabilities.max_context, Some(131072));
+
+        // Servers from before capabilities were listed
+        let show: OllamaShowResponse = serde_json::from_str(r#"{"model_info": {}}"#).unwrap();
+        let capabilities = show.capabilities();
+        assert!(capabilities.tools);
+        assert!(!capabilities.vision);
+        assert_eq!(capabilities.max_context, None);
+    }
+}
@@ -0,0 +1,559 @@
+//! OpenAI-compatible provider implementation.
+//!
+//! Speaks the Chat Completions and Embeddings APIs, so it works with OpenAI
+//! and compatible endpoints (OpenRouter, Groq, vLLM, LM Studio, ...). Set
+//! `llm.base_url` to the API root including the version, e.g.
+//! `https://api.openai.com/v1`.
+
+use crate::models::EmbeddingModel;
+use super::types::*;
+use async_trait::async_trait;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Environment variable read when `llm.api_key` is not set.
+pub const API_KEY_ENV: &str = "OPENAI_API_KEY";
+
+/// OpenAI-compatible HTTP API provider.
+#[derive(Debug, Clone)]
+pub struct OpenAiProvider {
+    base_url: String,
+    api_key: Option<String>,
+    http_client: reqwest::Client,
+    config: crate::Config,
+}
+
+impl OpenAiProvider {
+    /// Creates a provider for `llm.base_url`, authenticating with `llm.api_key`
+    /// or the `OPENAI_API_KEY` environment variable.
+    ///
+    /// Local servers usually need no key.
+    pub fn new(config: &crate::Config) -> Self {
+        let api_key = config.llm.api_key.clone()
+            .or_else(|| std::env::var(API_KEY_ENV).ok())
+            .filter(|key| !key.is_empty());
+
+        Self {
+            base_url: config.llm.base_url.trim_end_matches('/').to_string(),
+            api_key,
+            http_client: reqwest::Client::new(),
+            config: config.clone(),
+        }
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        let request = self.http_client.post(format!("{}/{}", self.base_url, path));
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for OpenAiProvider {
+    async fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+        mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+    ) -> Result<()> {
+        let openai_request = OpenAiChatRequest {
+            model: request.model.clone(),
+            messages: to_openai_messages(&request.messages),
+            temperature: request.temperature,
+            max_tokens: request.max_tokens,
+            stream: true,
+            tools: request.tools.clone(),
+        };
+
+        let response = self.post("chat/completions")
+            .json(&openai_request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(ProviderError::Api(error_text));
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut state = StreamState::new(&request.model);
+
+        while let Some(chunk_result) = stream.next().await {
+            let chunk = chunk_result?;
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(newline_pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = buffer.drain(..=newline_pos).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&line);
+
+                for response in state.feed_line(line.trim_end())? {
+                    callback(response);
+                }
+                if state.finished {
+                    return Ok(());
+                }
+            }
+        }
+
+        // The server closed the stream without `[DONE]`
+        for response in state.finish() {
+            callback(response);
+        }
+        Ok(())
+    }
+
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>> {
+        self.embed_batch(&[text], model)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ProviderError::Other("No embeddings returned".to_string()))
+    }
+
+    async fn embed_batch(&self, texts: &[&str], _model: &EmbeddingModel) -> Result<Vec<Vec<f32>>> {
+        let embed_request = OpenAiEmbedRequest {
+            model: self.config.rag.embedding_model.name.clone(),
+            input: texts.iter().map(|text| text.to_string()).collect(),
+        };
+
+        let response = self.post("embeddings")
+            .json(&embed_request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(ProviderError::Api(error_text));
+        }
+
+        let mut embed_response = response.json::<OpenAiEmbedResponse>().await?;
+        embed_response.data.sort_by_key(|data| data.index);
+        Ok(embed_response.data.into_iter().map(|data| data.embedding).collect())
+    }
+
+    /// Uses the `rerank` endpoint of servers that have one (llama.cpp, vLLM,
+    /// and other Jina/Cohere-compatible APIs), grading through chat otherwise.
+    async fn rerank(&self, query: &str, documents: &[&str], model: &str) -> Result<Vec<f32>> {
+        let rerank_request = OpenAiRerankRequest {
+            model: model.to_string(),
+            query: query.to_string(),
+            documents: documents.iter().map(|document| document.to_string()).collect(),
+        };
+
+        let response = self.post("rerank")
+            .json(&rerank_request)
+            .send()
+            .await?;
+
+        if matches!(response.status(), reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::METHOD_NOT_ALLOWED) {
+            return super::rerank::grade(self, query, documents, model).await;
+        }
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(ProviderError::Api(error_text));
+        }
+
+        let rerank_response = response.json::<OpenAiRerankResponse>().await?;
+        Ok(relevance_scores(rerank_response, documents.len()))
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        let request = self.http_client
+            .get(format!("{}/models", self.base_url))
+            .timeout(std::time::Duration::from_secs(5));
+        let request = match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        };
+
+        let response = request.send().await?;
+        // Any answer but a server error means the API is up
+        if response.status().is_server_error() {
+            return Err(ProviderError::Api(format!("Health check returned {}", response.status())));
+        }
+        Ok(())
+    }
+
+    fn is_remote(&self) -> bool {
+        !crate::config::is_local_url(&self.base_url)
+    }
+
+    /// The API doesn't describe models; tools are sent, images are not.
+    async fn capabilities(&self, _model: &str) -> Capabilities {
+        Capabilities { tools: true, ..Capabilities::default() }
+    }
+}
+
+/// Orders rerank results by document, from 0 to 1.
+///
+/// Some servers return raw logits; those are squashed with a sigmoid.
+fn relevance_scores(response: OpenAiRerankResponse, count: usize) -> Vec<f32> {
+    let logits = response.results.iter().any(|result| !(0.0..=1.0).contains(&result.relevance_score));
+    let mut scores = vec![0.0; count];
+    for result in response.results {
+        if let Some(score) = scores.get_mut(result.index) {
+            *score = if logits { 1.0 / (1.0 + (-result.relevance_score).exp()) } else { result.relevance_score };
+        }
+    }
+    scores
+}
+
+/// Converts messages, linking tool results to the calls they answer.
+///
+/// nucleus messages carry no tool call IDs, so IDs are assigned to each
+/// assistant tool call and tool results take them in order.
+fn to_openai_messages(messages: &[Message]) -> Vec<OpenAiMessage> {
+    let mut pending_ids = VecDeque::new();
+
+    messages
+        .iter()
+        .enumerate()
+        .map(|(i, message)| {
+            let tool_calls = message.tool_calls.as_ref().map(|calls| {
+                calls
+                    .iter()
+                    .enumerate()
+                    .map(|(j, call)| {
+                        let id = format!("call_{}_{}", i, j);
+                        pending_ids.push_back(id.clone());
+                        OpenAiToolCall {
+                            id,
+                            call_type: "function".to_string(),
+                            function: OpenAiFunctionCall {
+                                name: call.function.name.clone(),
+                                arguments: call.function.arguments.to_string(),
+                            },
+                        }
+                    })
+                    .collect()
+            });
+            let tool_call_id = (message.role == "tool").then(|| pending_ids.pop_front()).flatten();
+
+            OpenAiMessage {
+                role: message.role.clone(),
+                content: message.content.clone(),
+                tool_calls,
+                tool_call_id,
+            }
+        })
+        .collect()
+}
+
+/// Turns server-sent events into `ChatResponse` chunks.
+///
+/// Tool call arguments arrive in fragments, so calls are collected and sent
+/// in one chunk once the model finishes them.
+struct StreamState {
+    model: String,
+    role: String,
+    /// Tool calls being streamed, by index: (name, argument fragments)
+    tool_calls: BTreeMap<usize, (String, String)>,
+    /// The model was stopped at `max_tokens`
+    truncated: bool,
+    finished: bool,
+}
+
+impl StreamState {
+    fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            role: "assistant".to_string(),
+            tool_calls: BTreeMap::new(),
+            truncated: false,
+            finished: false,
+        }
+    }
+
+    fn feed_line(&mut self, line: &str) -> Result<Vec<ChatResponse>> {
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            // Comments, event names, and blank separators
+            return Ok(Vec::new());
+        };
+        if data == "[DONE]" {
+            return Ok(self.finish());
+        }
+
+        let chunk: OpenAiChunk = serde_json::from_str(data)?;
+        if let Some(error) = chunk.error {
+            return Err(ProviderError::Api(error.message));
+        }
+
+        let mut responses = Vec::new();
+        for choice in chunk.choices {
+            if let Some(role) = choice.delta.role {
+                self.role = role;
+            }
+            if let Some(content) = choice.delta.content.filter(|content| !content.is_empty()) {
+                responses.push(self.response(content, false, None));
+            }
+            for call in choice.delta.tool_calls.unwrap_or_default() {
+                let (name, arguments) = self.tool_calls.entry(call.index).or_default();
+                if let Some(function) = call.function {
+                    name.push_str(&function.name.unwrap_or_default());
+                    arguments.push_str(&function.arguments.unwrap_or_default());
+                }
+            }
+            if let Some(reason) = choice.finish_reason {
+                self.truncated |= reason == "length";
+                responses.extend(self.take_tool_calls());
+            }
+        }
+        Ok(responses)
+    }
+
+    /// Ends the stream, flushing unfinished tool calls.
+    fn finish(&mut self) -> Vec<ChatResponse> {
+        if self.finished {
+            return Vec::new();
+        }
+        self.finished = true;
+
+        let mut responses: Vec<ChatResponse> = self.take_tool_calls().into_iter().collect();
+        responses.push(self.response(String::new(), true, None));
+        responses
+    }
+
+    fn take_tool_calls(&mut self) -> Option<ChatResponse> {
+        if self.tool_calls.is_empty() {
+            return None;
+        }
+
+        let calls = std::mem::take(&mut self.tool_calls)
+            .into_values()
+            .map(|(name, arguments)| ToolCall {
+                function: ToolCallFunction {
+                    name,
+                    arguments: serde_json::from_str(&arguments).unwrap_or(serde_json::json!({})),
+                },
+            })
+            .collect();
+        Some(self.response(String::new(), false, Some(calls)))
+    }
+
+    fn response(&self, content: String, done: bool, tool_calls: Option<Vec<ToolCall>>) -> ChatResponse {
+        ChatResponse {
+            model: self.model.clone(),
+            content: content.clone(),
+            done,
+            truncated: done && self.truncated,
+            message: Message {
+                role: self.role.clone(),
+                content,
+                context: None,
+                images: None,
+                tool_calls,
+            },
+        }
+    }
+}
+
+// OpenAI-specific request/response types (internal)
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiChatRequest {
+    model: String,
+    messages: Vec<OpenAiMessage>,
+    temperature: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Tool>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiMessage {
+    role: String,
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<OpenAiToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiToolCall {
+    id: String,
+    #[serde(rename = "type")]
+    call_type: String,
+    function: OpenAiFunctionCall,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiFunctionCall {
+    name: String,
+    /// JSON-encoded arguments
+    arguments: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiChunk {
+    #[serde(default)]
+    choices: Vec<OpenAiChoice>,
+    /// Some compatible servers report errors inside the stream
+    #[serde(default)]
+    error: Option<OpenAiError>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiChoice {
+    delta: OpenAiDelta,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiDelta {
+    #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<OpenAiToolCallDelta>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiToolCallDelta {
+    #[serde(default)]
+    index: usize,
+    #[serde(default)]
+    function: Option<OpenAiFunctionDelta>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiFunctionDelta {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    arguments: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiError {
+    message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiEmbedRequest {
+    model: String,
+    input: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiRerankRequest {
+    model: String,
+    query: String,
+    documents: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiRerankResponse {
+    results: Vec<OpenAiRerankResult>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiRerankResult {
+    index: usize,
+    relevance_score: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiEmbedResponse {
+    data: Vec<OpenAiEmbedding>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiEmbedding {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_results_reference_calls() {
+        let mut assistant = Message::assistant(None, "");
+        assistant.tool_calls = Some(vec![ToolCall {
+            function: ToolCallFunction {
+                name: "read_file".to_string(),
+                arguments: serde_json::json!({"path": "README.md"}),
+            },
+        }]);
+        let messages = vec![Message::user(None, "summarize"), assistant, Message::tool(None, "# nucleus")];
+
+        let converted = to_openai_messages(&messages);
+        let call = &converted[1].tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call.function.arguments, r#"{"path":"README.md"}"#);
+        assert_eq!(converted[2].tool_call_id.as_ref(), Some(&call.id));
+        assert_eq!(converted[0].tool_call_id, None);
+    }
+
+    #[test]
+    fn test_stream_parsing() {
+        let mut state = StreamState::new("gpt-4o-mini");
+        let lines = [
+            r#"data: {"choices":[{"delta":{"role":"assistant","content":"Hel"}}]}"#,
+            "",
+            ": keep-alive",
+            r#"data: {"choices":[{"delta":{"content":"lo"}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"name":"read_file","arguments":"{\"pa"}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"th\":\"a.rs\"}"}}]},"finish_reason":"tool_calls"}]}"#,
+            "data: [DONE]",
+        ];
+
+        let responses: Vec<ChatResponse> = lines
+            .iter()
+            .flat_map(|line| state.feed_line(line).unwrap())
+            .collect();
+
+        let contents: Vec<&str> = responses.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, vec!["Hel", "lo", "", ""]);
+
+        let calls = responses[2].message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].function.name, "read_file");
+        assert_eq!(calls[0].function.arguments, serde_json::json!({"path": "a.rs"}));
+        assert!(responses[3].done);
+        assert!(state.finish().is_empty());
+    }
+
+    #[test]
+    fn test_relevance_scores() {
+        let response: OpenAiRerankResponse = serde_json::from_str(
+            r#"{"results": [{"index": 1, "relevance_score": 0.9}, {"index": 0, "relevance_score": 0.2}]}"#,
+        ).unwrap();
+        assert_eq!(relevance_scores(response, 2), [0.2, 0.9]);
+
+        let response: OpenAiRerankResponse = serde_json::from_str(
+            r#"{"results": [{"index": 0, "relevance_score": 0.0}, {"index": 1, "relevance_score": -3.5}]}"#,
+        ).unwrap();
+        let scores = relevance_scores(response, 2);
+        assert_eq!(scores[0], 0.5);
+        assert!(scores[1] < 0.1);
+    }
+
+    #[test]
+    fn test_stream_stopped_at_max_tokens() {
+        let mut state = StreamState::new("m");
+        state.feed_line(r#"data: {"choices":[{"delta":{"content":"Hel"},"finish_reason":"length"}]}"#).unwrap();
+        let done = state.feed_line("data: [DONE]").unwrap();
+        assert!(done.last().unwrap().done && done.last().unwrap().truncated);
+
+        let mut state = StreamState::new("m");
+        state.feed_line(r#"data: {"choices":[{"delta":{"content":"Hi"},"finish_reason":"stop"}]}"#).unwrap();
+        assert!(!state.feed_line("data: [DONE]").unwrap()[0].truncated);
+    }
+
+    #[test]
+    fn test_stream_error() {
+        let mut state = StreamState::new("m");
+        let error = state.feed_line(r#"data: {"error":{"message":"rate limited"}}"#).unwrap_err();
+        assert!(error.to_string().contains("rate limited"));
+    }
+}
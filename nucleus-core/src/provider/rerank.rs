@@ -0,0 +1,83 @@
+//! Relevance grading with a chat model, the default [`Provider::rerank`].
+//!
+//! Most local backends have no reranking endpoint, but reranker models
+//! (e.g. Qwen3-Reranker on Ollama) and general chat models can still judge
+//! a query and passage together, which is what makes a cross-encoder more
+//! accurate than comparing embeddings. Each passage is graded from 0 to 10
+//! in its own request.
+
+use super::types::{ChatRequest, Message, Provider, ProviderError, Result};
+use futures::StreamExt;
+
+/// Passages graded at the same time.
+const CONCURRENCY: usize = 4;
+
+/// Characters of a passage sent for grading.
+const MAX_PASSAGE_CHARS: usize = 4_000;
+
+const SYSTEM_PROMPT: &str = "You judge search results. Rate how well the passage answers the query, \
+    from 0 (unrelated) to 10 (answers it directly). Reply with the number only.";
+
+/// Scores `documents` against `query` by asking `model`, from 0 to 1.
+pub(crate) async fn grade<P: Provider + ?Sized>(
+    provider: &P,
+    query: &str,
+    documents: &[&str],
+    model: &str,
+) -> Result<Vec<f32>> {
+    let grades: Vec<_> = documents.iter()
+        .map(|document| grade_one(provider, query, document, model))
+        .collect();
+    futures::stream::iter(grades)
+        .buffered(CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+async fn grade_one<P: Provider + ?Sized>(provider: &P, query: &str, document: &str, model: &str) -> Result<f32> {
+    let passage: String = document.chars().take(MAX_PASSAGE_CHARS).collect();
+    let messages = vec![
+        Message::system(None, SYSTEM_PROMPT),
+        Message::user(None, format!("Query: {}\n\nPassage:\n{}", query, passage)),
+    ];
+    let request = ChatRequest::new(model, messages)
+        .with_temperature(0.0)
+        .with_max_tokens(8);
+
+    let mut reply = String::new();
+    provider.chat(request, Box::new(|response| reply.push_str(&response.message.content))).await?;
+    parse_grade(&reply).ok_or_else(|| ProviderError::Other(format!("Unexpected reranker reply: {}", reply.trim())))
+}
+
+/// Reads a 0-10 grade (or a reranker's yes/no) as a score from 0 to 1.
+fn parse_grade(reply: &str) -> Option<f32> {
+    let reply = reply.trim().to_lowercase();
+    if reply.starts_with("yes") {
+        return Some(1.0);
+    }
+    if reply.starts_with("no") {
+        return Some(0.0);
+    }
+
+    let number: String = reply.chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    number.parse::<f32>().ok().map(|grade| (grade / 10.0).clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_grade() {
+        assert_eq!(parse_grade("7"), Some(0.7));
+        assert_eq!(parse_grade(" Score: 10/10"), Some(1.0));
+        assert_eq!(parse_grade("Yes"), Some(1.0));
+        assert_eq!(parse_grade("no"), Some(0.0));
+        assert_eq!(parse_grade("unsure"), None);
+    }
+}
@@ -0,0 +1,175 @@
+//! Retries and circuit breaking around a provider.
+//!
+//! Wraps a provider that talks to a server (Ollama, OpenAI-compatible APIs)
+//! with a [`CircuitBreaker`], see [`crate::circuit`]. Chat requests are only
+//! retried before any output has been streamed, so callers never see two
+//! partial answers; once the circuit is open every call fails immediately
+//! with [`ProviderError::CircuitOpen`], which a fallback chain treats as
+//! unavailable and skips.
+
+use crate::circuit::{backoff, CircuitBreaker};
+use crate::config::ResilienceConfig;
+use crate::models::EmbeddingModel;
+use super::types::*;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Provider with retries and a circuit breaker.
+pub struct ResilientProvider {
+    inner: Arc<dyn Provider>,
+    breaker: CircuitBreaker,
+}
+
+impl ResilientProvider {
+    /// Wraps `inner`; `service` names it in errors and logs, e.g. "Ollama".
+    ///
+    /// While the circuit is open, `inner` is health-checked in the background.
+    pub fn new(inner: Arc<dyn Provider>, service: impl Into<String>, config: &ResilienceConfig) -> Self {
+        let probed = Arc::clone(&inner);
+        let breaker = CircuitBreaker::new(service, config).with_probe(Arc::new(move || {
+            let provider = Arc::clone(&probed);
+            Box::pin(async move { provider.health_check().await.is_ok() })
+        }));
+        Self { inner, breaker }
+    }
+}
+
+#[async_trait]
+impl Provider for ResilientProvider {
+    async fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+        mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+    ) -> Result<()> {
+        let retry = self.breaker.retry();
+        let mut attempt = 0;
+        loop {
+            self.breaker.check()?;
+
+            let mut streamed = false;
+            let result = self.inner
+                .chat(request.clone(), Box::new(|response: ChatResponse| {
+                    streamed |= !response.content.is_empty() || response.message.tool_calls.is_some();
+                    callback(response);
+                }))
+                .await;
+
+            match result {
+                Ok(()) => {
+                    self.breaker.record_success();
+                    return Ok(());
+                }
+                Err(e) if e.is_unavailable() => {
+                    self.breaker.record_failure();
+                    // Retrying now would repeat output the caller already has
+                    if streamed || attempt >= retry.max_retries || self.breaker.check().is_err() {
+                        return Err(e);
+                    }
+                    tracing::debug!(attempt, "Retrying chat request: {}", e);
+                    tokio::time::sleep(backoff(&retry, attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>> {
+        self.breaker.call(ProviderError::is_unavailable, || self.inner.embed(text, model)).await
+    }
+
+    async fn embed_batch(&self, texts: &[&str], model: &EmbeddingModel) -> Result<Vec<Vec<f32>>> {
+        self.breaker.call(ProviderError::is_unavailable, || self.inner.embed_batch(texts, model)).await
+    }
+
+    async fn rerank(&self, query: &str, documents: &[&str], model: &str) -> Result<Vec<f32>> {
+        self.breaker.call(ProviderError::is_unavailable, || self.inner.rerank(query, documents, model)).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.inner.health_check().await
+    }
+
+    fn is_remote(&self) -> bool {
+        self.inner.is_remote()
+    }
+
+    async fn capabilities(&self, model: &str) -> Capabilities {
+        self.inner.capabilities(model).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RetryPolicy;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails its first `failures` chat requests as unreachable.
+    struct FlakyProvider {
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl Provider for FlakyProvider {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> Result<()> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(ProviderError::Other("connection refused".to_string()));
+            }
+            callback(ChatResponse {
+                model: request.model,
+                content: "hi".to_string(),
+                done: true,
+                truncated: false,
+                message: Message::assistant(None, "hi"),
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    fn config(max_retries: u32, failure_threshold: u32) -> ResilienceConfig {
+        ResilienceConfig {
+            retry: RetryPolicy { max_retries, backoff_ms: 0 },
+            failure_threshold,
+            ..ResilienceConfig::default()
+        }
+    }
+
+    async fn chat(provider: &ResilientProvider) -> Result<String> {
+        let mut answer = String::new();
+        let request = ChatRequest::new("model", vec![Message::user(None, "hello")]);
+        provider.chat(request, Box::new(|response| answer.push_str(&response.content))).await?;
+        Ok(answer)
+    }
+
+    #[tokio::test]
+    async fn test_hiccup_is_retried() {
+        let flaky = Arc::new(FlakyProvider { failures: 1, calls: AtomicU32::new(0) });
+        let provider = ResilientProvider::new(flaky.clone(), "Ollama", &config(2, 3));
+
+        assert_eq!(chat(&provider).await.unwrap(), "hi");
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_fails_fast() {
+        let flaky = Arc::new(FlakyProvider { failures: u32::MAX, calls: AtomicU32::new(0) });
+        let provider = ResilientProvider::new(flaky.clone(), "Ollama", &config(0, 2));
+
+        assert!(matches!(chat(&provider).await, Err(ProviderError::Other(_))));
+        assert!(matches!(chat(&provider).await, Err(ProviderError::Other(_))));
+        let error = chat(&provider).await.unwrap_err();
+        assert!(matches!(error, ProviderError::CircuitOpen(_)));
+        assert!(error.is_unavailable());
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -20,6 +20,22 @@ pub enum ProviderError {
     
     #[error("Provider error: {0}")]
     Other(String),
+    
+    #[error(transparent)]
+    CircuitOpen(#[from] crate::circuit::CircuitOpen),
+}
+
+impl ProviderError {
+    /// Returns true if the backend could not serve the request (unreachable,
+    /// timed out, model not loaded, overloaded), so another attempt or
+    /// provider may succeed.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            ProviderError::Request(e) => e.is_connect() || e.is_timeout(),
+            ProviderError::Api(_) | ProviderError::Other(_) | ProviderError::CircuitOpen(_) => true,
+            ProviderError::Json(_) => false,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, ProviderError>;
@@ -32,7 +48,10 @@ pub type Result<T> = std::result::Result<T, ProviderError>;
 pub trait Provider: Send + Sync {
     /// Stream a chat completion.
     ///
-    /// The callback is invoked for each chunk of the response.
+    /// The callback is invoked for each chunk of the response as it is
+    /// generated. Each chunk carries only the new text (in both `content` and
+    /// `message.content`), tool calls arrive in the chunk they were produced
+    /// in, and the last chunk has `done` set.
     async fn chat<'a>(
         &'a self,
         request: ChatRequest,
@@ -51,6 +70,83 @@ pub trait Provider: Send + Sync {
         }
         Ok(embeddings)
     }
+    
+    /// Score how relevant each of `documents` is to `query`, from 0 to 1,
+    /// with a reranking (cross-encoder) model.
+    ///
+    /// The default implementation has `model` grade each passage through
+    /// [`chat`](Self::chat), which works with any backend.
+    async fn rerank(&self, query: &str, documents: &[&str], model: &str) -> Result<Vec<f32>> {
+        super::rerank::grade(self, query, documents, model).await
+    }
+    
+    /// Check that the backend is reachable, cheaply and without generating.
+    ///
+    /// Used to probe for recovery after repeated failures. The default
+    /// reports success, so calls are let through to test the backend.
+    async fn health_check(&self) -> Result<()> {
+        Ok(())
+    }
+    
+    /// Returns true if requests leave this machine.
+    ///
+    /// Prompts for remote providers are checked by the egress classifier.
+    fn is_remote(&self) -> bool {
+        false
+    }
+    
+    /// What the backend can do with `model`, so callers can leave out what it
+    /// can't (e.g. tool definitions) instead of failing at request time.
+    ///
+    /// The default claims only streaming and embeddings.
+    async fn capabilities(&self, _model: &str) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// What a provider and model support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Responses arrive in pieces as they are generated
+    pub streaming: bool,
+    /// Tool definitions in chat requests are used
+    pub tools: bool,
+    /// Images in messages are seen
+    pub vision: bool,
+    /// Embeddings can be generated
+    pub embeddings: bool,
+    /// Context window in tokens, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_context: Option<usize>,
+}
+
+impl Capabilities {
+    /// What both `self` and `other` support, e.g. for a chain of providers
+    /// any of which may answer.
+    pub fn intersect(self, other: Self) -> Self {
+        Self {
+            streaming: self.streaming && other.streaming,
+            tools: self.tools && other.tools,
+            vision: self.vision && other.vision,
+            embeddings: self.embeddings && other.embeddings,
+            max_context: match (self.max_context, other.max_context) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            streaming: true,
+            tools: false,
+            vision: false,
+            embeddings: true,
+            max_context: None,
+        }
+    }
 }
 
 /// Request for chat completion.
@@ -60,6 +156,9 @@ pub struct ChatRequest {
     pub messages: Vec<Message>,
     pub temperature: f64,
     pub tools: Option<Vec<Tool>>,
+    /// Maximum number of tokens to generate (provider default if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
 }
 
 impl ChatRequest {
@@ -69,6 +168,7 @@ impl ChatRequest {
             messages,
             temperature: 0.7,
             tools: None,
+            max_tokens: None,
         }
     }
     
@@ -81,6 +181,11 @@ impl ChatRequest {
         self.tools = Some(tools);
         self
     }
+    
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
 }
 
 /// Response from chat completion (streaming chunk).
@@ -89,6 +194,10 @@ pub struct ChatResponse {
     pub model: String,
     pub content: String,
     pub done: bool,
+    /// Generation stopped at the request's `max_tokens` rather than where
+    /// the model ended it; set on the final chunk
+    #[serde(default)]
+    pub truncated: bool,
     pub message: Message,
 }
 
@@ -0,0 +1,389 @@
+//! Model worker process for the mistral.rs provider (`llm.worker`).
+//!
+//! mistral.rs runs the model inside the process that loads it, so a CUDA
+//! out-of-memory abort or a crash in native code would take down the whole
+//! server, with its sessions and in-memory state. [`WorkerProvider`] loads
+//! the model in a child process instead (`nucleus model-worker`, which runs
+//! [`serve`]) and talks to it over its stdin and stdout, one JSON message
+//! per line. When the worker dies, the call in flight fails and the worker
+//! is restarted in the background.
+
+use super::types::*;
+use super::MistralRsProvider;
+use crate::models::EmbeddingModel;
+use crate::Config;
+
+use async_trait::async_trait;
+use nucleus_plugin::{Permission, PluginRegistry};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, Mutex, MutexGuard, RwLock};
+use tracing::{debug, error, info, warn};
+
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A message from the server to the worker.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Call {
+    /// Load the model; always the first message
+    Init { config: Box<Config> },
+    Chat { request: ChatRequest },
+    Embed { texts: Vec<String>, model: EmbeddingModel },
+}
+
+/// A message from the worker to the server.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Reply {
+    /// The model is loaded
+    Ready,
+    Chunk { response: ChatResponse },
+    /// The chat response is complete
+    Done,
+    Embeddings { embeddings: Vec<Vec<f32>> },
+    Error { message: String },
+}
+
+/// A running worker process.
+struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    replies: Lines<BufReader<ChildStdout>>,
+    /// Replies of an abandoned call are still to be read
+    pending: bool,
+    /// The process has exited
+    dead: bool,
+}
+
+impl Worker {
+    /// Starts the worker and waits for it to load the model.
+    async fn spawn(config: &Config) -> Result<Self> {
+        let (program, args) = config.llm.worker.command.split_first()
+            .ok_or_else(|| ProviderError::Other("llm.worker.command is empty".to_string()))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| ProviderError::Other(format!("Failed to start model worker '{}': {}", program, e)))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let mut worker = Self {
+            child,
+            stdin,
+            replies: BufReader::new(stdout).lines(),
+            pending: false,
+            dead: false,
+        };
+
+        worker.send(&Call::Init { config: Box::new(config.clone()) }).await?;
+        match worker.recv().await? {
+            Reply::Ready => {
+                info!(pid = ?worker.child.id(), "Model worker ready");
+                Ok(worker)
+            }
+            Reply::Error { message } => Err(ProviderError::Other(format!("Model worker failed to load the model: {}", message))),
+            reply => Err(unexpected(&reply)),
+        }
+    }
+
+    async fn send(&mut self, call: &Call) -> Result<()> {
+        // A cancelled call leaves its replies behind; skip past them first
+        while self.pending {
+            self.recv().await?;
+        }
+
+        let mut line = serde_json::to_string(call)?;
+        line.push('\n');
+        let written = match self.stdin.write_all(line.as_bytes()).await {
+            Ok(()) => self.stdin.flush().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            return Err(self.crashed(&e.to_string()).await);
+        }
+        self.pending = true;
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Result<Reply> {
+        loop {
+            let line = match self.replies.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => return Err(self.crashed("closed its output").await),
+                Err(e) => return Err(self.crashed(&e.to_string()).await),
+            };
+            match serde_json::from_str::<Reply>(&line) {
+                Ok(reply) => {
+                    self.pending = matches!(reply, Reply::Chunk { .. });
+                    return Ok(reply);
+                }
+                // The model libraries may print to stdout too
+                Err(_) => debug!(output = %line, "Ignoring model worker output"),
+            }
+        }
+    }
+
+    /// Marks the worker dead, describing how the process ended.
+    async fn crashed(&mut self, reason: &str) -> ProviderError {
+        self.dead = true;
+        let status = match tokio::time::timeout(Duration::from_secs(5), self.child.wait()).await {
+            Ok(Ok(status)) => status.to_string(),
+            _ => {
+                let _ = self.child.start_kill();
+                format!("killed after it {}", reason)
+            }
+        };
+        error!(status = %status, "Model worker died");
+        ProviderError::Other(format!("Model worker exited ({})", status))
+    }
+}
+
+fn unexpected(reply: &Reply) -> ProviderError {
+    ProviderError::Other(format!("Unexpected reply from model worker: {:?}", reply))
+}
+
+/// Runs the mistral.rs model in a worker process (`llm.worker.enabled`).
+///
+/// Calls are handled one at a time. A worker that dies is restarted, up to
+/// `llm.worker.max_restarts` times in a row without a successful call in
+/// between; after that calls fail until the server is restarted.
+pub struct WorkerProvider {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: Config,
+    worker: Mutex<Option<Worker>>,
+    /// Crashes since the last successful call
+    crashes: AtomicU32,
+}
+
+impl WorkerProvider {
+    /// Starts the worker and waits for the model to load.
+    pub async fn start(config: &Config) -> Result<Self> {
+        let worker = Worker::spawn(config).await?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                config: config.clone(),
+                worker: Mutex::new(Some(worker)),
+                crashes: AtomicU32::new(0),
+            }),
+        })
+    }
+
+    /// The worker, started again if it died and the restart has not finished.
+    async fn worker(&self) -> Result<MutexGuard<'_, Option<Worker>>> {
+        let mut guard = self.inner.worker.lock().await;
+        if guard.is_none() {
+            let crashes = self.inner.crashes.load(Ordering::SeqCst);
+            if crashes > self.inner.config.llm.worker.max_restarts {
+                return Err(ProviderError::Other(format!(
+                    "Model worker crashed {} times in a row and was not restarted",
+                    crashes
+                )));
+            }
+            *guard = Some(Worker::spawn(&self.inner.config).await?);
+        }
+        Ok(guard)
+    }
+
+    /// Passes on the result of a call, replacing the worker if it died.
+    fn finish<T>(&self, mut guard: MutexGuard<'_, Option<Worker>>, result: Result<T>) -> Result<T> {
+        if !guard.as_ref().is_some_and(|worker| worker.dead) {
+            if result.is_ok() {
+                self.inner.crashes.store(0, Ordering::SeqCst);
+            }
+            return result;
+        }
+
+        *guard = None;
+        drop(guard);
+        let crashes = self.inner.crashes.fetch_add(1, Ordering::SeqCst) + 1;
+        if crashes > self.inner.config.llm.worker.max_restarts {
+            error!(crashes, "Model worker keeps crashing; not restarting it");
+        } else {
+            warn!(crashes, "Restarting model worker");
+            tokio::spawn(restart(Arc::clone(&self.inner)));
+        }
+        result
+    }
+}
+
+async fn restart(inner: Arc<Inner>) {
+    let mut guard = inner.worker.lock().await;
+    // A call may have restarted it already
+    if guard.is_some() {
+        return;
+    }
+    match Worker::spawn(&inner.config).await {
+        Ok(worker) => *guard = Some(worker),
+        Err(e) => {
+            inner.crashes.fetch_add(1, Ordering::SeqCst);
+            error!(error = %e, "Failed to restart model worker");
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for WorkerProvider {
+    async fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+        mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+    ) -> Result<()> {
+        let mut guard = self.worker().await?;
+        let worker = guard.as_mut().expect("worker is running");
+        let result = async {
+            worker.send(&Call::Chat { request }).await?;
+            loop {
+                match worker.recv().await? {
+                    Reply::Chunk { response } => callback(response),
+                    Reply::Done => return Ok(()),
+                    Reply::Error { message } => return Err(ProviderError::Other(message)),
+                    reply => return Err(unexpected(&reply)),
+                }
+            }
+        }
+        .await;
+        self.finish(guard, result)
+    }
+
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>> {
+        self.embed_batch(&[text], model)
+            .await?
+            .pop()
+            .ok_or_else(|| ProviderError::Other("Model worker returned no embedding".to_string()))
+    }
+
+    async fn embed_batch(&self, texts: &[&str], model: &EmbeddingModel) -> Result<Vec<Vec<f32>>> {
+        let mut guard = self.worker().await?;
+        let worker = guard.as_mut().expect("worker is running");
+        let call = Call::Embed {
+            texts: texts.iter().map(|text| text.to_string()).collect(),
+            model: model.clone(),
+        };
+        let result = async {
+            worker.send(&call).await?;
+            match worker.recv().await? {
+                Reply::Embeddings { embeddings } => Ok(embeddings),
+                Reply::Error { message } => Err(ProviderError::Other(message)),
+                reply => Err(unexpected(&reply)),
+            }
+        }
+        .await;
+        self.finish(guard, result)
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.worker().await.map(drop)
+    }
+
+    /// Those of mistral.rs, which the worker runs.
+    async fn capabilities(&self, _model: &str) -> Capabilities {
+        Capabilities { tools: true, ..Capabilities::default() }
+    }
+}
+
+/// Serves a [`WorkerProvider`] on stdin and stdout (`nucleus model-worker`).
+pub async fn serve() -> anyhow::Result<()> {
+    let mut calls = BufReader::new(tokio::io::stdin()).lines();
+    let (replies, mut outbox) = mpsc::unbounded_channel::<Reply>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(reply) = outbox.recv().await {
+            let mut line = serde_json::to_string(&reply)?;
+            line.push('\n');
+            stdout.write_all(line.as_bytes()).await?;
+            stdout.flush().await?;
+        }
+        anyhow::Ok(())
+    });
+
+    let Some(line) = calls.next_line().await? else {
+        return Ok(());
+    };
+    let Call::Init { config } = serde_json::from_str(&line)? else {
+        anyhow::bail!("expected an init message first");
+    };
+    // Tools come with each chat request, so the registry stays empty
+    let registry = Arc::new(RwLock::new(PluginRegistry::new(Permission {
+        network: true,
+        ..Permission::READ_ONLY
+    })));
+    let provider = match MistralRsProvider::new(&config, registry).await {
+        Ok(provider) => {
+            let _ = replies.send(Reply::Ready);
+            provider
+        }
+        Err(e) => {
+            let _ = replies.send(Reply::Error { message: e.to_string() });
+            drop(replies);
+            writer.await??;
+            return Err(e.into());
+        }
+    };
+
+    while let Some(line) = calls.next_line().await? {
+        let reply = match serde_json::from_str(&line)? {
+            Call::Chat { request } => {
+                let chunks = replies.clone();
+                let callback = Box::new(move |response| {
+                    let _ = chunks.send(Reply::Chunk { response });
+                });
+                match provider.chat(request, callback).await {
+                    Ok(()) => Reply::Done,
+                    Err(e) => Reply::Error { message: e.to_string() },
+                }
+            }
+            Call::Embed { texts, model } => {
+                let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+                match provider.embed_batch(&texts, &model).await {
+                    Ok(embeddings) => Reply::Embeddings { embeddings },
+                    Err(e) => Reply::Error { message: e.to_string() },
+                }
+            }
+            Call::Init { .. } => Reply::Error { message: "the model is already loaded".to_string() },
+        };
+        let _ = replies.send(reply);
+    }
+
+    drop(replies);
+    writer.await?
+}
+
+/// Runs [`serve`] on a new Tokio runtime, for the `nucleus` binary.
+pub fn run() -> anyhow::Result<()> {
+    tokio::runtime::Runtime::new()?.block_on(serve())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_worker_restarts_after_crash() {
+        // Each worker answers one call, printing a stray line first, then exits
+        let script = r#"read init; echo "loading model"; echo '{"type":"ready"}'
+            read call; echo '{"type":"embeddings","embeddings":[[1.0,0.5]]}'"#;
+        let mut config = Config::default();
+        config.llm.worker.command = vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+        let model = EmbeddingModel::default();
+
+        let provider = WorkerProvider::start(&config).await.unwrap();
+        assert_eq!(provider.embed("hello", &model).await.unwrap(), vec![1.0, 0.5]);
+
+        let error = provider.embed("hello", &model).await.unwrap_err();
+        assert!(error.to_string().contains("exited"), "{}", error);
+
+        assert_eq!(provider.embed("again", &model).await.unwrap(), vec![1.0, 0.5]);
+    }
+}
@@ -0,0 +1,145 @@
+//! Fitting retrieved context into the model's context window.
+//!
+//! `storage.top_k` chunks of a few kilobytes each can take up more of the
+//! window than is left once the system prompt, history, and question are
+//! in, and the answer needs room too. [`ContextBudget`] estimates what the
+//! rest of the prompt costs and keeps as many results as fit into
+//! `llm.context_length` minus the tokens reserved for the answer, trimming
+//! the last one that only fits partly.
+//!
+//! Tokens are estimated from the length of the text rather than counted
+//! with the model's tokenizer, which the providers don't expose.
+
+use super::types::SearchResult;
+use crate::config::LlmConfig;
+use tracing::debug;
+
+/// Characters per token used for estimates. Real tokenizers average closer
+/// to four for English, so estimates err on the side of a shorter prompt.
+pub(crate) const CHARS_PER_TOKEN: usize = 3;
+
+/// Estimated tokens for the heading of the context and the label of each
+/// result, see [`format_context`](super::format_context).
+const HEADING_TOKENS: usize = 16;
+const LABEL_TOKENS: usize = 16;
+
+/// A result is trimmed to fit only if at least this many tokens of it remain.
+const MIN_TRIMMED_TOKENS: usize = 64;
+
+/// Estimated number of tokens in `text`.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// The space in the context window available to retrieved context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextBudget {
+    /// `llm.context_length`
+    pub context_length: usize,
+    /// Tokens kept free for the answer
+    pub answer_tokens: usize,
+}
+
+impl ContextBudget {
+    pub fn new(llm: &LlmConfig) -> Self {
+        Self {
+            context_length: llm.context_length,
+            answer_tokens: llm.answer_tokens,
+        }
+    }
+
+    /// Reserves `answer_tokens` for the answer instead, e.g. a request's `max_tokens`.
+    pub fn with_answer_tokens(mut self, answer_tokens: usize) -> Self {
+        self.answer_tokens = answer_tokens;
+        self
+    }
+
+    /// Tokens left for retrieved context next to `prompt`, everything else
+    /// sent to the model (system prompt, history, question, other context).
+    pub fn available(&self, prompt: &[&str]) -> usize {
+        let used: usize = prompt.iter().map(|part| estimate_tokens(part)).sum();
+        self.context_length.saturating_sub(self.answer_tokens + used)
+    }
+
+    /// Keeps the leading `results` that fit next to `prompt`, trimming the
+    /// first one that doesn't fit completely if enough of it does.
+    pub fn fit(&self, mut results: Vec<SearchResult>, prompt: &[&str]) -> Vec<SearchResult> {
+        if results.is_empty() {
+            return results;
+        }
+        let mut left = self.available(prompt).saturating_sub(HEADING_TOKENS);
+
+        let retrieved = results.len();
+        let mut kept = 0;
+        let mut trimmed = false;
+        for result in &mut results {
+            let tokens = estimate_tokens(&result.document.content) + LABEL_TOKENS;
+            if tokens <= left {
+                left -= tokens;
+                kept += 1;
+                continue;
+            }
+            let room = left.saturating_sub(LABEL_TOKENS);
+            if room >= MIN_TRIMMED_TOKENS {
+                trim(&mut result.document.content, room * CHARS_PER_TOKEN);
+                kept += 1;
+                trimmed = true;
+            }
+            break;
+        }
+
+        if kept < retrieved || trimmed {
+            debug!(kept, retrieved, trimmed, "Fitted retrieved context into the context window");
+        }
+        results.truncate(kept);
+        results
+    }
+}
+
+/// Cuts `content` down to `max_chars` characters (including a trailing `…`),
+/// at a line break if there is one in the second half.
+fn trim(content: &mut String, max_chars: usize) {
+    let Some((end, _)) = content.char_indices().nth(max_chars.saturating_sub(1)) else {
+        return;
+    };
+    let end = match content[..end].rfind('\n') {
+        Some(line_end) if line_end >= end / 2 => line_end,
+        _ => end,
+    };
+    content.truncate(end);
+    content.push('…');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag::Document;
+
+    fn result(content: &str) -> SearchResult {
+        SearchResult {
+            document: Document::new(content, content, Vec::new()),
+            score: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_fit_keeps_trims_and_drops() {
+        let budget = ContextBudget { context_length: 1_000, answer_tokens: 400 };
+        let prompt = "q".repeat(300);
+        // 600 tokens besides the answer, minus 100 for the prompt
+        assert_eq!(budget.available(&[&prompt]), 500);
+
+        let results = vec![result(&"a".repeat(600)), result(&"b".repeat(900)), result("c")];
+        let fitted = budget.fit(results, &[&prompt]);
+
+        assert_eq!(fitted.len(), 2);
+        assert_eq!(fitted[0].document.content.len(), 600);
+        // 484 - 216 - 16 tokens of room left for the second
+        let trimmed = &fitted[1].document.content;
+        assert!(trimmed.ends_with('…'));
+        assert_eq!(trimmed.chars().count(), 252 * CHARS_PER_TOKEN);
+
+        let tight = ContextBudget { context_length: 500, answer_tokens: 400 };
+        assert!(tight.fit(vec![result(&"a".repeat(600))], &[&prompt]).is_empty());
+    }
+}
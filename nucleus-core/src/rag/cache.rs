@@ -0,0 +1,184 @@
+//! Persistent cache of embeddings keyed by content hash.
+//!
+//! Re-indexing a changed file re-embeds all of its chunks, most of which
+//! usually did not change, and identical files (vendored code, licenses,
+//! copies across projects) are embedded once per location. The cache maps
+//! the SHA-256 of a chunk to its embedding so each distinct chunk is only
+//! sent to the embedding model once.
+//!
+//! Each embedding model gets its own append-only file of fixed-size records
+//! (32-byte hash followed by the vector as little-endian `f32`s). Only the
+//! hash to offset index is kept in memory; vectors are read on a hit. A
+//! record cut short by a crash is dropped when the file is opened.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Hash identifying a chunk.
+pub type Key = [u8; 32];
+
+const KEY_LEN: u64 = 32;
+
+/// Returns the cache key of `text`.
+pub fn key(text: &str) -> Key {
+    Sha256::digest(text.as_bytes()).into()
+}
+
+/// On-disk cache of the embeddings of one model.
+///
+/// The methods do blocking file I/O; call them off the async runtime.
+pub struct EmbeddingCache {
+    path: PathBuf,
+    dim: usize,
+    /// Opened on first use
+    state: Mutex<Option<State>>,
+}
+
+struct State {
+    file: File,
+    offsets: HashMap<Key, u64>,
+    len: u64,
+}
+
+impl EmbeddingCache {
+    /// Cache for `model_id` embeddings of `dim` dimensions in `dir`.
+    pub fn new(dir: impl AsRef<Path>, model_id: &str, dim: usize) -> Self {
+        let name: String = model_id.chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect();
+        Self {
+            path: dir.as_ref().join(format!("{}-{}.bin", name, dim)),
+            dim,
+            state: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn record_len(&self) -> u64 {
+        KEY_LEN + 4 * self.dim as u64
+    }
+
+    /// Looks up `keys`, returning the cached embedding of each, if any.
+    pub fn get(&self, keys: &[Key]) -> io::Result<Vec<Option<Vec<f32>>>> {
+        let mut guard = self.state.lock().unwrap();
+        let state = self.open(&mut guard)?;
+
+        let mut bytes = vec![0u8; 4 * self.dim];
+        keys.iter()
+            .map(|key| {
+                let Some(&offset) = state.offsets.get(key) else {
+                    return Ok(None);
+                };
+                state.file.seek(SeekFrom::Start(offset + KEY_LEN))?;
+                state.file.read_exact(&mut bytes)?;
+                Ok(Some(bytes.chunks_exact(4)
+                    .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                    .collect()))
+            })
+            .collect()
+    }
+
+    /// Stores embeddings; ones already cached or of the wrong size are skipped.
+    pub fn insert(&self, entries: &[(Key, &[f32])]) -> io::Result<()> {
+        let mut guard = self.state.lock().unwrap();
+        let state = self.open(&mut guard)?;
+
+        let mut buffer = Vec::new();
+        let mut added = Vec::new();
+        for (key, embedding) in entries {
+            if embedding.len() != self.dim || state.offsets.contains_key(key) || added.contains(key) {
+                continue;
+            }
+            buffer.extend_from_slice(key);
+            for value in *embedding {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+            added.push(*key);
+        }
+        if added.is_empty() {
+            return Ok(());
+        }
+
+        state.file.seek(SeekFrom::Start(state.len))?;
+        state.file.write_all(&buffer)?;
+        for key in added {
+            state.offsets.insert(key, state.len);
+            state.len += self.record_len();
+        }
+        Ok(())
+    }
+
+    /// Opens the cache file and reads its index, if not done yet.
+    fn open<'a>(&self, state: &'a mut Option<State>) -> io::Result<&'a mut State> {
+        if state.is_none() {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&self.path)?;
+
+            let record_len = self.record_len();
+            let len = file.metadata()?.len() / record_len * record_len;
+            file.set_len(len)?;
+
+            let mut offsets = HashMap::new();
+            let mut reader = BufReader::new(&file);
+            let mut key = [0u8; KEY_LEN as usize];
+            let mut offset = 0;
+            while offset < len {
+                reader.read_exact(&mut key)?;
+                reader.seek_relative(record_len as i64 - KEY_LEN as i64)?;
+                offsets.insert(key, offset);
+                offset += record_len;
+            }
+            tracing::debug!("Opened embedding cache {} with {} entries", self.path.display(), offsets.len());
+
+            *state = Some(State { file, offsets, len });
+        }
+        Ok(state.as_mut().expect("opened above"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persists_across_opens() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = EmbeddingCache::new(dir.path(), "nomic-embed-text:latest", 3);
+        let (a, b) = (key("fn main() {}"), key("struct Config;"));
+
+        cache.insert(&[(a, &[1.0, 2.0, 3.0][..]), (b, &[0.5][..])]).unwrap();
+        assert_eq!(cache.get(&[a, b]).unwrap(), vec![Some(vec![1.0, 2.0, 3.0]), None]);
+
+        let reopened = EmbeddingCache::new(dir.path(), "nomic-embed-text:latest", 3);
+        assert_eq!(reopened.path(), dir.path().join("nomic-embed-text_latest-3.bin"));
+        assert_eq!(reopened.get(&[a]).unwrap(), vec![Some(vec![1.0, 2.0, 3.0])]);
+    }
+
+    #[test]
+    fn test_drops_partial_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = EmbeddingCache::new(dir.path(), "model", 2);
+        cache.insert(&[(key("a"), &[1.0, 2.0][..])]).unwrap();
+        drop(cache);
+
+        let path = dir.path().join("model-2.bin");
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&[7; 10]).unwrap();
+
+        let cache = EmbeddingCache::new(dir.path(), "model", 2);
+        cache.insert(&[(key("b"), &[3.0, 4.0][..])]).unwrap();
+        assert_eq!(
+            cache.get(&[key("a"), key("b")]).unwrap(),
+            vec![Some(vec![1.0, 2.0]), Some(vec![3.0, 4.0])]
+        );
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 2 * (32 + 8));
+    }
+}
@@ -0,0 +1,500 @@
+//! Chroma vector database storage over its HTTP API.
+//!
+//! Lets nucleus search collections that other tools, typically Python
+//! scripts, have built in a Chroma server (0.6 or later), and add to them.
+//! The collection named `vector_db.collection_name` is created with its
+//! configured distance metric if it does not exist; an existing one keeps
+//! its distance function, which scores are derived from. Its embeddings must come from
+//! the configured embedding model, or searches compare vectors from
+//! different spaces.
+//!
+//! Chroma filters have no `LIKE`, so those conditions are checked against
+//! a larger set of nearest neighbours after the search.
+
+use super::filter::{Condition, SearchFilter};
+use super::store::{source_matches, VectorStore};
+use super::types::{Document, SearchResult};
+use crate::config::{DistanceMetric, StorageConfig, StorageMode};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Records fetched per request when reading the whole collection.
+const PAGE_SIZE: usize = 500;
+
+/// Records added or deleted per request, below Chroma's batch limit.
+const WRITE_BATCH_SIZE: usize = 1000;
+
+/// Nearest neighbours fetched per result wanted when `LIKE` conditions are
+/// checked afterwards.
+const LIKE_CANDIDATES: usize = 4;
+
+/// Distance function of a Chroma collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Space {
+    Cosine,
+    /// Squared Euclidean distance, Chroma's default
+    L2,
+    InnerProduct,
+}
+
+impl Space {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "cosine" => Some(Self::Cosine),
+            "l2" => Some(Self::L2),
+            "ip" => Some(Self::InnerProduct),
+            _ => None,
+        }
+    }
+
+    fn of(metric: DistanceMetric) -> Self {
+        match metric {
+            DistanceMetric::Cosine => Self::Cosine,
+            DistanceMetric::Dot => Self::InnerProduct,
+            DistanceMetric::Euclidean => Self::L2,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Cosine => "cosine",
+            Self::L2 => "l2",
+            Self::InnerProduct => "ip",
+        }
+    }
+
+    /// Similarity score of a distance, 1 for identical vectors.
+    ///
+    /// For normalized embeddings all three agree with cosine similarity.
+    fn score(self, distance: f32) -> f32 {
+        match self {
+            Self::Cosine | Self::InnerProduct => 1.0 - distance,
+            Self::L2 => 1.0 - distance / 2.0,
+        }
+    }
+}
+
+/// Metadata of a record or collection, as Chroma stores it.
+type Metadata = Map<String, Value>;
+
+/// A collection as returned by the collections endpoint.
+#[derive(Debug, Deserialize)]
+struct Collection {
+    id: String,
+    #[serde(default)]
+    metadata: Option<Metadata>,
+    #[serde(default)]
+    configuration_json: Option<Value>,
+    #[serde(default)]
+    dimension: Option<u64>,
+}
+
+impl Collection {
+    fn space(&self) -> Space {
+        let configured = self.configuration_json.as_ref().and_then(|configuration| {
+            configuration
+                .pointer("/hnsw/space")
+                .or_else(|| configuration.pointer("/spann/space"))
+        });
+        let legacy = self.metadata.as_ref().and_then(|metadata| metadata.get("hnsw:space"));
+        configured
+            .or(legacy)
+            .and_then(Value::as_str)
+            .and_then(Space::parse)
+            .unwrap_or(Space::L2)
+    }
+}
+
+/// Records returned by the get endpoint, field by field.
+#[derive(Debug, Default, Deserialize)]
+struct Records {
+    ids: Vec<String>,
+    #[serde(default)]
+    documents: Option<Vec<Option<String>>>,
+    #[serde(default)]
+    metadatas: Option<Vec<Option<Metadata>>>,
+    #[serde(default)]
+    embeddings: Option<Vec<Option<Vec<f32>>>>,
+    #[serde(default)]
+    distances: Option<Vec<Option<f32>>>,
+}
+
+/// Results of the query endpoint, one list per query embedding.
+#[derive(Debug, Deserialize)]
+struct QueryResults {
+    ids: Vec<Vec<String>>,
+    #[serde(default)]
+    documents: Option<Vec<Vec<Option<String>>>>,
+    #[serde(default)]
+    metadatas: Option<Vec<Vec<Option<Metadata>>>>,
+    #[serde(default)]
+    distances: Option<Vec<Vec<Option<f32>>>>,
+}
+
+impl QueryResults {
+    /// Results of the first query embedding.
+    fn first(self) -> Records {
+        fn first<T>(lists: Option<Vec<Vec<T>>>) -> Option<Vec<T>> {
+            lists.and_then(|lists| lists.into_iter().next())
+        }
+        Records {
+            ids: self.ids.into_iter().next().unwrap_or_default(),
+            documents: first(self.documents),
+            metadatas: first(self.metadatas),
+            embeddings: None,
+            distances: first(self.distances),
+        }
+    }
+}
+
+impl Records {
+    /// The records as documents, each with its distance if returned.
+    fn into_documents(self) -> Vec<(Document, Option<f32>)> {
+        let mut contents = self.documents.unwrap_or_default().into_iter();
+        let mut metadatas = self.metadatas.unwrap_or_default().into_iter();
+        let mut embeddings = self.embeddings.unwrap_or_default().into_iter();
+        let mut distances = self.distances.unwrap_or_default().into_iter();
+        self.ids
+            .into_iter()
+            .map(|id| {
+                let document = Document {
+                    id,
+                    content: contents.next().flatten().unwrap_or_default(),
+                    embedding: embeddings.next().flatten().unwrap_or_default(),
+                    metadata: metadata_strings(metadatas.next().flatten().unwrap_or_default()),
+                };
+                (document, distances.next().flatten())
+            })
+            .collect()
+    }
+}
+
+/// Metadata as strings; Chroma also stores numbers and booleans, which
+/// tools other than nucleus write.
+fn metadata_strings(metadata: Metadata) -> HashMap<String, String> {
+    metadata
+        .into_iter()
+        .filter_map(|(key, value)| match value {
+            Value::String(value) => Some((key, value)),
+            Value::Number(_) | Value::Bool(_) => Some((key, value.to_string())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Chroma server storing one collection.
+pub struct ChromaStore {
+    http: reqwest::Client,
+    base_url: String,
+    /// Endpoint of the collection, `…/collections/{id}`
+    collection_url: String,
+    token: Option<String>,
+    space: Space,
+}
+
+#[async_trait]
+impl VectorStore for ChromaStore {
+    async fn add(&self, documents: Vec<Document>) -> Result<()> {
+        for batch in documents.chunks(WRITE_BATCH_SIZE) {
+            let metadatas: Vec<Value> = batch
+                .iter()
+                // Chroma rejects empty metadata
+                .map(|document| if document.metadata.is_empty() { Value::Null } else { json!(document.metadata) })
+                .collect();
+            let body = json!({
+                "ids": batch.iter().map(|document| &document.id).collect::<Vec<_>>(),
+                "embeddings": batch.iter().map(|document| &document.embedding).collect::<Vec<_>>(),
+                "documents": batch.iter().map(|document| &document.content).collect::<Vec<_>>(),
+                "metadatas": metadatas,
+            });
+            self.post::<Value>("upsert", body, "add documents").await?;
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query_embedding: &[f32], top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        if top_k == 0 {
+            return Ok(Vec::new());
+        }
+
+        let checked_after = filter.is_some_and(|filter| {
+            filter.conditions().iter().any(|condition| matches!(condition, Condition::Like { .. }))
+        });
+        let mut body = json!({
+            "query_embeddings": [query_embedding],
+            "n_results": if checked_after { top_k * LIKE_CANDIDATES } else { top_k },
+            "include": ["documents", "metadatas", "distances"],
+        });
+        if let Some(clause) = filter.and_then(chroma_where) {
+            body["where"] = clause;
+        }
+        let results: QueryResults = self.post("query", body, "search").await?;
+
+        Ok(results
+            .first()
+            .into_documents()
+            .into_iter()
+            .filter(|(document, _)| filter.is_none_or(|filter| filter.matches(document)))
+            .take(top_k)
+            .map(|(document, distance)| SearchResult {
+                document,
+                score: distance.map_or(0.0, |distance| self.space.score(distance)),
+            })
+            .collect())
+    }
+
+    async fn count(&self) -> Result<usize> {
+        let response = self
+            .send(self.http.get(format!("{}/count", self.collection_url)), "count documents")
+            .await?;
+        response.json().await.context("Invalid count from Chroma")
+    }
+
+    async fn clear(&self) -> Result<()> {
+        let mut ids = Vec::new();
+        self.scan(&[], |records| ids.extend(records.ids)).await?;
+        self.delete(ids).await?;
+        Ok(())
+    }
+
+    async fn get_indexed_paths(&self) -> Result<Vec<String>> {
+        let mut sources = std::collections::BTreeSet::new();
+        self.scan(&["metadatas"], |records| {
+            for (document, _) in records.into_documents() {
+                if let Some(source) = document.metadata.get("source") {
+                    sources.insert(source.clone());
+                }
+            }
+        })
+        .await?;
+        Ok(sources.into_iter().collect())
+    }
+
+    async fn remove_by_source(&self, source_path: &str) -> Result<usize> {
+        let mut ids = Vec::new();
+        self.scan(&["metadatas"], |records| {
+            for (document, _) in records.into_documents() {
+                if document.metadata.get("source").is_some_and(|source| source_matches(source, source_path)) {
+                    ids.push(document.id);
+                }
+            }
+        })
+        .await?;
+        self.delete(ids).await
+    }
+
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>> {
+        let mut documents = Vec::new();
+        self.scan(&["documents", "metadatas", "embeddings"], |records| {
+            documents.extend(records.into_documents().into_iter().map(|(document, _)| document).filter(|document| {
+                source_path.is_none_or(|path| {
+                    document.metadata.get("source").is_some_and(|source| source_matches(source, path))
+                })
+            }));
+        })
+        .await?;
+        Ok(documents)
+    }
+
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>> {
+        let mut hashes = Vec::new();
+        self.scan(&["metadatas"], |records| {
+            for (mut document, _) in records.into_documents() {
+                if let Some(source) = document.metadata.remove("source") {
+                    if source_matches(&source, source_path) {
+                        hashes.push((source, document.metadata.remove("hash")));
+                    }
+                }
+            }
+        })
+        .await?;
+        Ok(hashes)
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.send(self.http.get(format!("{}/api/v2/heartbeat", self.base_url)), "check the server")
+            .await?;
+        Ok(())
+    }
+}
+
+impl ChromaStore {
+    /// Connects to the server and opens the collection, creating it if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage_config` - Storage configuration with a `chroma` storage
+    ///   mode and the collection name
+    /// * `vector_size` - Dimension of the embedding vectors
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server cannot be reached or the collection
+    /// holds embeddings of another dimension.
+    pub async fn new(storage_config: StorageConfig, vector_size: u64) -> Result<Self> {
+        let StorageMode::Chroma { url, tenant, database, token } = &storage_config.storage_mode else {
+            anyhow::bail!("ChromaStore only supports Chroma mode");
+        };
+        let base_url = url.trim_end_matches('/').to_string();
+        let collections_url = format!("{}/api/v2/tenants/{}/databases/{}/collections", base_url, tenant, database);
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .context("Failed to create HTTP client")?;
+        let mut store = Self {
+            http,
+            base_url,
+            collection_url: String::new(),
+            token: token.clone(),
+            space: Space::Cosine,
+        };
+
+        let collection_name = &storage_config.vector_db.collection_name;
+        let request = store.http.post(&collections_url).json(&json!({
+            "name": collection_name,
+            "get_or_create": true,
+            "metadata": { "hnsw:space": Space::of(storage_config.vector_db.metric()).name() },
+        }));
+        let collection: Collection = store
+            .send(request, "open the collection")
+            .await?
+            .json()
+            .await
+            .context("Invalid collection from Chroma")?;
+        if let Some(dimension) = collection.dimension.filter(|&dimension| dimension != vector_size) {
+            anyhow::bail!(
+                "Chroma collection '{}' stores {}-dimensional embeddings but the embedding model produces {}; \
+                 configure the model the collection was built with",
+                collection_name, dimension, vector_size
+            );
+        }
+
+        store.space = collection.space();
+        store.collection_url = format!("{}/{}", collections_url, collection.id);
+        Ok(store)
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder, action: &str) -> Result<reqwest::Response> {
+        let request = match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        let response = request.send().await.with_context(|| format!("Failed to {} in Chroma", action))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("Failed to {} in Chroma: {} {}", action, status, body.trim());
+        }
+        Ok(response)
+    }
+
+    /// Posts `body` to the collection's `endpoint`, e.g. `query`.
+    async fn post<T: DeserializeOwned>(&self, endpoint: &str, body: Value, action: &str) -> Result<T> {
+        let request = self.http.post(format!("{}/{}", self.collection_url, endpoint)).json(&body);
+        self.send(request, action)
+            .await?
+            .json()
+            .await
+            .with_context(|| format!("Invalid response from Chroma to {}", endpoint))
+    }
+
+    /// Reads the whole collection page by page, returning `include`d fields
+    /// besides the IDs.
+    async fn scan(&self, include: &[&str], mut visit: impl FnMut(Records)) -> Result<()> {
+        let mut offset = 0;
+        loop {
+            let body = json!({ "include": include, "limit": PAGE_SIZE, "offset": offset });
+            let records: Records = self.post("get", body, "read documents").await?;
+            let count = records.ids.len();
+            visit(records);
+            if count < PAGE_SIZE {
+                return Ok(());
+            }
+            offset += count;
+        }
+    }
+
+    /// Deletes the records with `ids`, returning how many there were.
+    async fn delete(&self, ids: Vec<String>) -> Result<usize> {
+        for batch in ids.chunks(WRITE_BATCH_SIZE) {
+            self.post::<Value>("delete", json!({ "ids": batch }), "delete documents").await?;
+        }
+        Ok(ids.len())
+    }
+}
+
+/// Translates the equality conditions of `filter` to a Chroma `where`
+/// clause; `LIKE` conditions are checked on the results instead.
+fn chroma_where(filter: &SearchFilter) -> Option<Value> {
+    let mut clauses: Vec<Value> = filter
+        .conditions()
+        .iter()
+        .filter_map(|condition| match condition {
+            Condition::Equals { key, value } => {
+                let mut clause = Map::new();
+                clause.insert(key.clone(), json!({ "$eq": value }));
+                Some(Value::Object(clause))
+            }
+            Condition::Like { .. } => None,
+        })
+        .collect();
+    match clauses.len() {
+        0 => None,
+        // `$and` needs at least two clauses
+        1 => clauses.pop(),
+        _ => Some(json!({ "$and": clauses })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chroma_where() {
+        assert_eq!(chroma_where(&SearchFilter::new().with_like("source", "src/%")), None);
+        assert_eq!(
+            chroma_where(&SearchFilter::new().with_equals("language", "rust").with_like("source", "src/%")),
+            Some(json!({ "language": { "$eq": "rust" } }))
+        );
+        assert_eq!(
+            chroma_where(&SearchFilter::new().with_equals("language", "rust").with_equals("page", "2")),
+            Some(json!({ "$and": [{ "language": { "$eq": "rust" } }, { "page": { "$eq": "2" } }] }))
+        );
+    }
+
+    #[test]
+    fn test_records_from_other_tools() {
+        let results: QueryResults = serde_json::from_value(json!({
+            "ids": [["a", "b"]],
+            "documents": [["First", null]],
+            "metadatas": [[{ "source": "notes.md", "page": 3, "draft": false, "tags": ["x"] }, null]],
+            "distances": [[0.5, 1.0]],
+            "embeddings": null,
+        }))
+        .unwrap();
+        let documents = results.first().into_documents();
+
+        let (first, distance) = &documents[0];
+        assert_eq!(first.content, "First");
+        assert_eq!(first.metadata.len(), 3);
+        assert_eq!(first.metadata["page"], "3");
+        assert_eq!(first.metadata["draft"], "false");
+        assert_eq!(Space::L2.score(distance.unwrap()), 0.75);
+        assert_eq!((documents[1].0.content.as_str(), documents[1].0.metadata.len()), ("", 0));
+
+        let collection: Collection = serde_json::from_value(json!({
+            "id": "4f1c",
+            "name": "docs",
+            "metadata": { "hnsw:space": "ip" },
+            "configuration_json": { "hnsw": { "space": "cosine" } },
+        }))
+        .unwrap();
+        assert_eq!(collection.space(), Space::Cosine);
+    }
+}
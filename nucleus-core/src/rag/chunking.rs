@@ -0,0 +1,365 @@
+//! Chunking strategies, selected per file type.
+//!
+//! A [`Chunker`] splits text into the chunks that are embedded and stored.
+//! [`Chunkers`] picks one per file by extension from
+//! `indexer.chunking.by_extension`, using `indexer.chunking.default` for
+//! other files and for text that isn't a file. The built-in strategies are:
+//!
+//! - [`FixedSizeChunker`]: overlapping windows of `chunk_size` bytes
+//! - [`SentenceChunker`]: whole sentences packed up to the chunk size
+//! - [`RecursiveChunker`]: paragraphs, then lines, then sentences, then words
+//! - [`SyntaxChunker`]: syntax nodes of source files (`tree-sitter` feature)
+//! - [`MarkdownChunker`]: sections under their headings
+//!
+//! All of them cut only on grapheme boundaries and keep chunks within
+//! `chunk_size` bytes unless a single grapheme cluster is larger. Other
+//! strategies can be registered with [`Chunkers::with_chunker`].
+
+use super::indexer::chunk_text;
+use crate::config::{ChunkStrategy, IndexerConfig};
+use crate::text;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Splits text into chunks for embedding.
+pub trait Chunker: fmt::Debug + Send + Sync {
+    /// Splits `text`, the contents of the file at `path` if it is one.
+    fn chunk(&self, text: &str, path: Option<&Path>) -> Vec<String>;
+}
+
+/// Byte windows of `size` overlapping by `overlap`, see [`chunk_text`].
+#[derive(Debug, Clone, Copy)]
+pub struct FixedSizeChunker {
+    pub size: usize,
+    pub overlap: usize,
+}
+
+impl Chunker for FixedSizeChunker {
+    fn chunk(&self, text: &str, _path: Option<&Path>) -> Vec<String> {
+        chunk_text(text, self.size, self.overlap)
+    }
+}
+
+/// Sentences packed into chunks of up to `size` bytes, repeating up to
+/// `overlap` bytes of trailing sentences at the start of the next chunk.
+///
+/// Sentences end at `.`, `!`, or `?` followed by whitespace, at their CJK
+/// counterparts, and at blank lines.
+#[derive(Debug, Clone, Copy)]
+pub struct SentenceChunker {
+    pub size: usize,
+    pub overlap: usize,
+}
+
+impl Chunker for SentenceChunker {
+    fn chunk(&self, text: &str, _path: Option<&Path>) -> Vec<String> {
+        let mut pieces = Vec::new();
+        for sentence in sentences(text) {
+            split_recursive(text, sentence, self.size, &[" "], &mut pieces);
+        }
+        pack(text, &pieces, self.size, self.overlap)
+    }
+}
+
+/// Splits on the coarsest separator that makes pieces fit: blank lines,
+/// then line breaks, then sentences, then spaces, then byte windows.
+/// Pieces are packed like [`SentenceChunker`] does.
+#[derive(Debug, Clone, Copy)]
+pub struct RecursiveChunker {
+    pub size: usize,
+    pub overlap: usize,
+}
+
+const RECURSIVE_SEPARATORS: &[&str] = &["\n\n", "\n", ". ", " "];
+
+impl Chunker for RecursiveChunker {
+    fn chunk(&self, text: &str, _path: Option<&Path>) -> Vec<String> {
+        let mut pieces = Vec::new();
+        split_recursive(text, 0..text.len(), self.size, RECURSIVE_SEPARATORS, &mut pieces);
+        pack(text, &pieces, self.size, self.overlap)
+    }
+}
+
+/// Splits source files on syntax boundaries with the `tree-sitter` feature,
+/// see [`syntax`](super::syntax); other text is chunked like [`FixedSizeChunker`].
+#[derive(Debug, Clone, Copy)]
+pub struct SyntaxChunker {
+    pub size: usize,
+    pub overlap: usize,
+}
+
+impl Chunker for SyntaxChunker {
+    fn chunk(&self, text: &str, path: Option<&Path>) -> Vec<String> {
+        #[cfg(feature = "tree-sitter")]
+        if let Some(chunks) = path.and_then(|path| super::syntax::chunk_source(path, text, self.size, self.overlap)) {
+            return chunks;
+        }
+        #[cfg(not(feature = "tree-sitter"))]
+        let _ = path;
+
+        chunk_text(text, self.size, self.overlap)
+    }
+}
+
+/// Markdown sections, each starting at a heading, packed into chunks; a
+/// section too large for one chunk is split like [`RecursiveChunker`] does.
+/// Lines starting with `#` inside code fences are not headings.
+#[derive(Debug, Clone, Copy)]
+pub struct MarkdownChunker {
+    pub size: usize,
+    pub overlap: usize,
+}
+
+impl Chunker for MarkdownChunker {
+    fn chunk(&self, text: &str, _path: Option<&Path>) -> Vec<String> {
+        let mut pieces = Vec::new();
+        for section in sections(text) {
+            split_recursive(text, section, self.size, RECURSIVE_SEPARATORS, &mut pieces);
+        }
+        pack(text, &pieces, self.size, self.overlap)
+    }
+}
+
+/// Creates the built-in chunker for `strategy`.
+pub fn chunker(strategy: ChunkStrategy, size: usize, overlap: usize) -> Arc<dyn Chunker> {
+    match strategy {
+        ChunkStrategy::Fixed => Arc::new(FixedSizeChunker { size, overlap }),
+        ChunkStrategy::Sentence => Arc::new(SentenceChunker { size, overlap }),
+        ChunkStrategy::Recursive => Arc::new(RecursiveChunker { size, overlap }),
+        ChunkStrategy::Syntax => Arc::new(SyntaxChunker { size, overlap }),
+        ChunkStrategy::Markdown => Arc::new(MarkdownChunker { size, overlap }),
+    }
+}
+
+/// The chunker of each file type.
+#[derive(Debug, Clone)]
+pub struct Chunkers {
+    default: Arc<dyn Chunker>,
+    by_extension: HashMap<String, Arc<dyn Chunker>>,
+}
+
+impl Chunkers {
+    /// Chunkers configured by `indexer.chunking`. With the `tree-sitter`
+    /// feature, source files in supported languages use [`SyntaxChunker`]
+    /// unless configured otherwise.
+    pub fn new(config: &IndexerConfig) -> Self {
+        let (size, overlap) = (config.chunk_size, config.chunk_overlap);
+        let mut by_extension = HashMap::new();
+
+        #[cfg(feature = "tree-sitter")]
+        for extension in super::syntax::EXTENSIONS {
+            by_extension.insert(extension.to_string(), chunker(ChunkStrategy::Syntax, size, overlap));
+        }
+        for (extension, strategy) in &config.chunking.by_extension {
+            by_extension.insert(extension.trim_start_matches('.').to_string(), chunker(*strategy, size, overlap));
+        }
+
+        Self {
+            default: chunker(config.chunking.default, size, overlap),
+            by_extension,
+        }
+    }
+
+    /// Uses `chunker` for files with `extension` (without the dot).
+    pub fn with_chunker(mut self, extension: impl Into<String>, chunker: impl Chunker + 'static) -> Self {
+        self.by_extension.insert(extension.into(), Arc::new(chunker));
+        self
+    }
+
+    /// The chunker for the file at `path`, or for text that isn't a file.
+    pub fn for_path(&self, path: Option<&Path>) -> &dyn Chunker {
+        path.and_then(|path| path.extension()?.to_str())
+            .and_then(|extension| self.by_extension.get(extension))
+            .unwrap_or(&self.default)
+            .as_ref()
+    }
+
+    /// Chunks `text`, the contents of the file at `path` if it is one.
+    pub fn chunk(&self, text: &str, path: Option<&Path>) -> Vec<String> {
+        self.for_path(path).chunk(text, path)
+    }
+}
+
+/// Splits `text` into sentences, each with the whitespace after it.
+fn sentences(text: &str) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let ends = match c {
+            '。' | '！' | '？' => true,
+            '.' | '!' | '?' => chars.peek().is_some_and(|&(_, next)| next.is_whitespace()),
+            '\n' => chars.peek().is_some_and(|&(_, next)| next == '\n'),
+            _ => false,
+        };
+        if !ends {
+            continue;
+        }
+        let mut end = i + c.len_utf8();
+        while let Some(&(j, next)) = chars.peek().filter(|(_, next)| next.is_whitespace()) {
+            end = j + next.len_utf8();
+            chars.next();
+        }
+        pieces.push(start..end);
+        start = end;
+    }
+    if start < text.len() {
+        pieces.push(start..text.len());
+    }
+    pieces
+}
+
+/// Splits markdown into sections starting at headings outside code fences.
+fn sections(text: &str) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut fence: Option<&str> = None;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let indented = line.len() - trimmed.len() > 3;
+        match fence {
+            Some(marker) if trimmed.starts_with(marker) => fence = None,
+            Some(_) => {}
+            None if !indented && (trimmed.starts_with("```") || trimmed.starts_with("~~~")) => {
+                fence = Some(&trimmed[..3]);
+            }
+            None if !indented && is_heading(trimmed) && offset > start => {
+                pieces.push(start..offset);
+                start = offset;
+            }
+            None => {}
+        }
+        offset += line.len();
+    }
+    if start < text.len() {
+        pieces.push(start..text.len());
+    }
+    pieces
+}
+
+fn is_heading(line: &str) -> bool {
+    let level = line.bytes().take_while(|&b| b == b'#').count();
+    (1..=6).contains(&level) && line[level..].starts_with([' ', '\t', '\n', '\r'])
+}
+
+/// Splits `range` of `text` into contiguous pieces of at most `size` bytes,
+/// on the first of `separators` that occurs in it, then the next for pieces
+/// still too large, and finally on grapheme boundaries.
+fn split_recursive(text: &str, range: Range<usize>, size: usize, separators: &[&str], pieces: &mut Vec<Range<usize>>) {
+    if range.len() <= size {
+        pieces.push(range);
+        return;
+    }
+
+    let slice = &text[range.clone()];
+    let Some(position) = separators.iter().position(|separator| slice.contains(separator)) else {
+        let mut start = range.start;
+        while start < range.end {
+            let mut end = text::floor_boundary(text, (start + size).min(range.end));
+            if end <= start {
+                end = text::ceil_boundary(text, start + 1);
+            }
+            pieces.push(start..end);
+            start = end;
+        }
+        return;
+    };
+
+    let separator = separators[position];
+    let mut start = range.start;
+    for (i, _) in slice.match_indices(separator) {
+        let end = range.start + i + separator.len();
+        split_recursive(text, start..end, size, &separators[position + 1..], pieces);
+        start = end;
+    }
+    if start < range.end {
+        split_recursive(text, start..range.end, size, &separators[position + 1..], pieces);
+    }
+}
+
+/// Packs consecutive pieces into chunks of at most `size` bytes.
+///
+/// Each chunk after the first starts with the trailing pieces of the one
+/// before that fit in `overlap` bytes. Whitespace-only chunks are dropped.
+fn pack(text: &str, pieces: &[Range<usize>], size: usize, overlap: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut push = |first: &Range<usize>, last: &Range<usize>| {
+        let chunk = &text[first.start..last.end];
+        if !chunk.trim().is_empty() {
+            chunks.push(chunk.to_string());
+        }
+    };
+
+    let mut first = 0;
+    for i in 1..pieces.len() {
+        if pieces[i].end - pieces[first].start <= size {
+            continue;
+        }
+        push(&pieces[first], &pieces[i - 1]);
+        first = (first + 1..i)
+            .find(|&k| pieces[i].start - pieces[k].start <= overlap && pieces[i].end - pieces[k].start <= size)
+            .unwrap_or(i);
+    }
+    if let Some(last) = pieces.last() {
+        push(&pieces[first], last);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChunkingConfig;
+
+    #[test]
+    fn test_sentence_and_recursive_chunkers() {
+        let text = "First sentence here. Second one is longer! Third? 日本語です。終わり";
+        let chunks = SentenceChunker { size: 45, overlap: 0 }.chunk(text, None);
+        assert_eq!(chunks, ["First sentence here. Second one is longer! ", "Third? 日本語です。終わり"]);
+
+        // The trailing sentence is repeated when it fits in the overlap
+        let chunks = SentenceChunker { size: 30, overlap: 10 }.chunk(text, None);
+        assert_eq!(chunks[1], "Second one is longer! Third? ");
+        assert_eq!(chunks[2], "Third? 日本語です。");
+
+        let text = "para one\nline two\n\npara two is here";
+        let chunks = RecursiveChunker { size: 20, overlap: 0 }.chunk(text, None);
+        assert_eq!(chunks, ["para one\nline two\n\n", "para two is here"]);
+        assert!(RecursiveChunker { size: 4, overlap: 0 }.chunk(text, None).iter().all(|chunk| chunk.len() <= 4));
+    }
+
+    #[test]
+    fn test_markdown_sections() {
+        let text = "# Intro\nHello.\n\n```sh\n# not a heading\n```\n## Usage\nRun it.\n## Notes\nNone.\n";
+        let chunks = MarkdownChunker { size: 48, overlap: 0 }.chunk(text, None);
+        assert_eq!(chunks, [
+            "# Intro\nHello.\n\n```sh\n# not a heading\n```\n",
+            "## Usage\nRun it.\n## Notes\nNone.\n",
+        ]);
+    }
+
+    #[test]
+    fn test_chunkers_by_extension() {
+        let config = IndexerConfig {
+            chunk_size: 20,
+            chunk_overlap: 0,
+            chunking: ChunkingConfig {
+                default: ChunkStrategy::Sentence,
+                by_extension: HashMap::from([("md".to_string(), ChunkStrategy::Markdown)]),
+            },
+            ..IndexerConfig::default()
+        };
+        let chunkers = Chunkers::new(&config).with_chunker("log", FixedSizeChunker { size: 5, overlap: 0 });
+
+        let text = "# A\nOne. Two.\n# B\nThree.";
+        assert_eq!(chunkers.chunk(text, Some(Path::new("README.md"))), ["# A\nOne. Two.\n", "# B\nThree."]);
+        assert_eq!(chunkers.chunk("First one. Second one. Third.", None), ["First one. ", "Second one. Third."]);
+        assert_eq!(chunkers.chunk("abcdefgh", Some(Path::new("app.log"))), ["abcde", "fgh"]);
+    }
+}
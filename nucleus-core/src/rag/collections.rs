@@ -0,0 +1,357 @@
+//! Named collections: separate knowledge bases, e.g. one per project.
+//!
+//! The `default` collection is the knowledge base configured in
+//! `storage.vector_db`. Others are created on request and live in the same
+//! database as `<collection_name>-<name>`. Which collections exist, the
+//! project directories each one belongs to, and the active one are kept in
+//! `storage.collections_path`.
+//!
+//! A request uses, in order: the collection it names, the collection whose
+//! root is the deepest directory containing its working directory, the
+//! active collection, and `default`. Collections holding a starter pack
+//! (see [`starter`](super::starter)) are searched by every request as well.
+
+use super::keyword::KeywordStore;
+use super::store::{create_vector_store, VectorStore};
+use super::{RagError, Result};
+use crate::config::{DistanceMetric, ResilienceConfig, StorageConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tracing::warn;
+
+/// Name of the collection configured in `storage.vector_db`.
+pub const DEFAULT_COLLECTION: &str = "default";
+
+/// A collection, as listed by [`RagEngine::list_collections`](super::RagEngine::list_collections).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionInfo {
+    pub name: String,
+    /// Project directories whose requests use the collection
+    #[serde(default)]
+    pub roots: Vec<PathBuf>,
+    /// Whether requests outside every root use the collection
+    #[serde(default)]
+    pub active: bool,
+    /// Whether the collection holds an installed starter pack
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub starter: bool,
+}
+
+/// Contents of `storage.collections_path`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Registry {
+    #[serde(default)]
+    collections: Vec<Entry>,
+    /// `None` for the default collection
+    #[serde(default)]
+    active: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    name: String,
+    #[serde(default)]
+    roots: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    starter: bool,
+}
+
+/// The collections of a knowledge base and their stores, opened on first use.
+pub(crate) struct Collections {
+    path: PathBuf,
+    storage: StorageConfig,
+    vector_size: u64,
+    resilience: ResilienceConfig,
+    registry: RwLock<Registry>,
+    stores: tokio::sync::Mutex<HashMap<String, Arc<KeywordStore>>>,
+}
+
+impl Collections {
+    /// Loads the collections listed at `storage.collections_path`, with
+    /// `default` as the default collection's store.
+    pub(crate) fn load(
+        storage: &StorageConfig,
+        vector_size: u64,
+        resilience: &ResilienceConfig,
+        default: Arc<KeywordStore>,
+    ) -> Self {
+        let path = PathBuf::from(&storage.collections_path);
+        let registry = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable collections file {}: {}", path.display(), e);
+                Registry::default()
+            }),
+            Err(_) => Registry::default(),
+        };
+
+        Self {
+            path,
+            storage: storage.clone(),
+            vector_size,
+            resilience: resilience.clone(),
+            registry: RwLock::new(registry),
+            stores: tokio::sync::Mutex::new(HashMap::from([(DEFAULT_COLLECTION.to_string(), default)])),
+        }
+    }
+
+    pub(crate) fn list(&self) -> Vec<CollectionInfo> {
+        let registry = self.registry.read().unwrap();
+        let default = CollectionInfo {
+            name: DEFAULT_COLLECTION.to_string(),
+            roots: Vec::new(),
+            active: registry.active.is_none(),
+            starter: false,
+        };
+        let named = registry.collections.iter().map(|entry| CollectionInfo {
+            name: entry.name.clone(),
+            roots: entry.roots.clone(),
+            active: registry.active.as_ref() == Some(&entry.name),
+            starter: entry.starter,
+        });
+        std::iter::once(default).chain(named).collect()
+    }
+
+    /// Adds collection `name`, used by requests from within `roots`.
+    pub(crate) fn create(&self, name: &str, roots: Vec<PathBuf>) -> Result<()> {
+        validate(name)?;
+        let mut registry = self.registry.write().unwrap();
+        if name == DEFAULT_COLLECTION || registry.collections.iter().any(|entry| entry.name == name) {
+            return Err(RagError::Collection(format!("'{}' already exists", name)));
+        }
+        registry.collections.push(Entry { name: name.to_string(), roots, starter: false });
+        self.save(&registry)
+    }
+
+    /// Adds collection `name` for a starter pack, unless it already holds one.
+    pub(crate) fn create_starter(&self, name: &str) -> Result<()> {
+        validate(name)?;
+        let mut registry = self.registry.write().unwrap();
+        match registry.collections.iter().find(|entry| entry.name == name) {
+            Some(entry) if entry.starter => return Ok(()),
+            Some(_) => return Err(RagError::Collection(format!("'{}' already exists and is not a starter pack", name))),
+            None if name == DEFAULT_COLLECTION => return Err(RagError::Collection(format!("'{}' already exists", name))),
+            None => {}
+        }
+        registry.collections.push(Entry { name: name.to_string(), roots: Vec::new(), starter: true });
+        self.save(&registry)
+    }
+
+    /// Names of the collections holding a starter pack.
+    pub(crate) fn starters(&self) -> Vec<String> {
+        let registry = self.registry.read().unwrap();
+        registry.collections.iter().filter(|entry| entry.starter).map(|entry| entry.name.clone()).collect()
+    }
+
+    /// Removes collection `name` and everything in it.
+    pub(crate) async fn delete(&self, name: &str) -> Result<()> {
+        if name == DEFAULT_COLLECTION {
+            return Err(RagError::Collection("the default collection cannot be deleted".to_string()));
+        }
+        let store = self.store(name).await?;
+        store.clear().await.map_err(|e| RagError::Retrieval(e.to_string()))?;
+        self.stores.lock().await.remove(name);
+
+        let mut registry = self.registry.write().unwrap();
+        registry.collections.retain(|entry| entry.name != name);
+        if registry.active.as_deref() == Some(name) {
+            registry.active = None;
+        }
+        self.save(&registry)
+    }
+
+    /// Makes `name` the collection of requests outside every root.
+    pub(crate) fn switch(&self, name: &str) -> Result<()> {
+        let mut registry = self.registry.write().unwrap();
+        registry.active = match name {
+            DEFAULT_COLLECTION => None,
+            name if registry.collections.iter().any(|entry| entry.name == name) => Some(name.to_string()),
+            name => return Err(no_such_collection(name)),
+        };
+        self.save(&registry)
+    }
+
+    /// The collection a request naming `requested` from `cwd` uses.
+    pub(crate) fn resolve(&self, requested: Option<&str>, cwd: Option<&Path>) -> String {
+        if let Some(requested) = requested.filter(|name| !name.is_empty()) {
+            return requested.to_string();
+        }
+        let registry = self.registry.read().unwrap();
+        let project = cwd.and_then(|cwd| {
+            registry
+                .collections
+                .iter()
+                .flat_map(|entry| entry.roots.iter().map(move |root| (root, &entry.name)))
+                .filter(|(root, _)| cwd.starts_with(root))
+                .max_by_key(|(root, _)| root.components().count())
+                .map(|(_, name)| name.clone())
+        });
+        project
+            .or_else(|| registry.active.clone())
+            .unwrap_or_else(|| DEFAULT_COLLECTION.to_string())
+    }
+
+    /// The store of collection `name`, connecting to it on first use.
+    pub(crate) async fn store(&self, name: &str) -> Result<Arc<KeywordStore>> {
+        let mut stores = self.stores.lock().await;
+        if let Some(store) = stores.get(name) {
+            return Ok(Arc::clone(store));
+        }
+        if !self.registry.read().unwrap().collections.iter().any(|entry| entry.name == name) {
+            return Err(no_such_collection(name));
+        }
+
+        let store = create_vector_store(storage_config(&self.storage, name), self.vector_size, &self.resilience)
+            .await
+            .map_err(|e| RagError::Retrieval(format!("Collection '{}': {}", name, e)))?;
+        let store = Arc::new(KeywordStore::new(store));
+        stores.insert(name.to_string(), Arc::clone(&store));
+        Ok(store)
+    }
+
+    /// The distance metric of collection `name`.
+    pub(crate) fn metric(&self, name: &str) -> DistanceMetric {
+        storage_config(&self.storage, name).vector_db.metric()
+    }
+
+    fn save(&self, registry: &Registry) -> Result<()> {
+        let write = || -> std::io::Result<()> {
+            if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&self.path, serde_json::to_string_pretty(registry)?)
+        };
+        write().map_err(|e| RagError::Collection(format!("failed to save {}: {}", self.path.display(), e)))
+    }
+}
+
+/// Storage configuration for collection `name`, based on the default one.
+fn storage_config(local: &StorageConfig, name: &str) -> StorageConfig {
+    StorageConfig {
+        vector_db: local.vector_db.named(format!("{}-{}", local.vector_db.collection_name, name)),
+        ..local.clone()
+    }
+}
+
+/// Collection names end up in table names, so they are kept to lowercase
+/// letters, digits, `-`, and `_`.
+fn validate(name: &str) -> Result<()> {
+    let valid_char = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_';
+    if name.is_empty() || name.len() > 64 || !name.chars().all(valid_char) {
+        return Err(RagError::Collection(format!(
+            "invalid name '{}'; use up to 64 lowercase letters, digits, '-', and '_'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn no_such_collection(name: &str) -> RagError {
+    RagError::Collection(format!("no collection named '{}'; create it first", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag::types::{Document, SearchResult};
+    use crate::rag::SearchFilter;
+    use async_trait::async_trait;
+
+    struct EmptyStore;
+
+    #[async_trait]
+    impl VectorStore for EmptyStore {
+        async fn add(&self, _: Vec<Document>) -> anyhow::Result<()> {
+            Ok(())
+        }
+        async fn search(&self, _: &[f32], _: usize, _: Option<&SearchFilter>) -> anyhow::Result<Vec<SearchResult>> {
+            Ok(Vec::new())
+        }
+        async fn count(&self) -> anyhow::Result<usize> {
+            Ok(0)
+        }
+        async fn clear(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+        async fn get_indexed_paths(&self) -> anyhow::Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+        async fn remove_by_source(&self, _: &str) -> anyhow::Result<usize> {
+            Ok(0)
+        }
+        async fn get_documents(&self, _: Option<&str>) -> anyhow::Result<Vec<Document>> {
+            Ok(Vec::new())
+        }
+        async fn source_hashes(&self, _: &str) -> anyhow::Result<Vec<(String, Option<String>)>> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn open(dir: &Path) -> Collections {
+        let storage = StorageConfig {
+            collections_path: dir.join("collections.json").to_string_lossy().to_string(),
+            ..StorageConfig::default()
+        };
+        let default = Arc::new(KeywordStore::new(Arc::new(EmptyStore)));
+        Collections::load(&storage, 4, &ResilienceConfig::default(), default)
+    }
+
+    #[test]
+    fn test_resolve_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let collections = open(dir.path());
+        collections.create("work", vec![PathBuf::from("/src/work")]).unwrap();
+        collections.create("api", vec![PathBuf::from("/src/work/api")]).unwrap();
+        assert!(collections.create("work", Vec::new()).is_err());
+        assert!(collections.create("Bad Name", Vec::new()).is_err());
+        assert!(collections.switch("missing").is_err());
+
+        assert_eq!(collections.resolve(None, Some(Path::new("/src/work/api/src"))), "api");
+        assert_eq!(collections.resolve(None, Some(Path::new("/src/work/web"))), "work");
+        assert_eq!(collections.resolve(None, Some(Path::new("/tmp"))), DEFAULT_COLLECTION);
+        assert_eq!(collections.resolve(Some("api"), Some(Path::new("/tmp"))), "api");
+
+        collections.switch("work").unwrap();
+        assert_eq!(collections.resolve(None, None), "work");
+
+        // Another server sees the same collections
+        let reloaded = open(dir.path());
+        let listed = reloaded.list();
+        assert_eq!(listed.iter().map(|info| info.name.as_str()).collect::<Vec<_>>(), ["default", "work", "api"]);
+        assert!(listed[1].active && !listed[0].active);
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let collections = open(dir.path());
+        collections.create("scratch", Vec::new()).unwrap();
+        collections.switch("scratch").unwrap();
+        assert!(collections.delete(DEFAULT_COLLECTION).await.is_err());
+        assert!(collections.store("missing").await.is_err());
+
+        let store = Arc::new(KeywordStore::new(Arc::new(EmptyStore)));
+        collections.stores.lock().await.insert("scratch".to_string(), store);
+        collections.delete("scratch").await.unwrap();
+        assert_eq!(collections.list().len(), 1);
+        assert_eq!(collections.resolve(None, None), DEFAULT_COLLECTION);
+        assert!(collections.store("scratch").await.is_err());
+    }
+
+    #[test]
+    fn test_starter_collections() {
+        let dir = tempfile::tempdir().unwrap();
+        let collections = open(dir.path());
+        collections.create("work", Vec::new()).unwrap();
+        collections.create_starter("git").unwrap();
+        // Installing again reuses the collection
+        collections.create_starter("git").unwrap();
+        assert!(collections.create_starter("work").is_err());
+        assert!(collections.create_starter(DEFAULT_COLLECTION).is_err());
+
+        let reloaded = open(dir.path());
+        assert_eq!(reloaded.starters(), ["git"]);
+        assert!(reloaded.list().iter().any(|info| info.name == "git" && info.starter));
+    }
+}
@@ -0,0 +1,141 @@
+//! Distance metrics of the collections (`vector_db.distance`).
+//!
+//! Embedding models are trained to compare embeddings one way: most by
+//! cosine similarity, some (e.g. TAS-B, Contriever, DPR) by dot product,
+//! so an embedding's length carries relevance too. Every store compares a
+//! collection's embeddings with the metric configured for it and reports a
+//! similarity score, see [`DistanceMetric`].
+//!
+//! The embedding model registry does not record which metric its models
+//! were trained for, so [`trained_metric`] knows it for the common model
+//! families; collections configured with another metric are reported when
+//! the RAG engine starts and by `nucleus doctor`.
+
+use crate::config::{DistanceMetric, VectorDbConfig};
+use crate::models::{EmbeddingModel, ModelRegistry};
+
+/// Name fragments of embedding models and the metric they were trained
+/// for, checked in order against the model's ID and Hugging Face repository.
+const TRAINED_METRICS: [(&str, DistanceMetric); 15] = [
+    ("msmarco-distilbert-base-tas-b", DistanceMetric::Dot),
+    ("-dot-v", DistanceMetric::Dot),
+    ("contriever", DistanceMetric::Dot),
+    ("dpr-", DistanceMetric::Dot),
+    ("qwen3-embedding", DistanceMetric::Cosine),
+    ("nomic-embed-text", DistanceMetric::Cosine),
+    ("all-minilm", DistanceMetric::Cosine),
+    ("all-mpnet", DistanceMetric::Cosine),
+    ("bge-", DistanceMetric::Cosine),
+    ("e5-", DistanceMetric::Cosine),
+    ("gte-", DistanceMetric::Cosine),
+    ("mxbai-embed", DistanceMetric::Cosine),
+    ("snowflake-arctic-embed", DistanceMetric::Cosine),
+    ("text-embedding-3", DistanceMetric::Cosine),
+    ("text-embedding-ada", DistanceMetric::Cosine),
+];
+
+/// The metric `model` was trained for, if it is a known one.
+///
+/// The model is also looked up in the [`ModelRegistry`] by ID, so a
+/// configuration naming just the ID is recognized by its repository.
+pub fn trained_metric(model: &EmbeddingModel) -> Option<DistanceMetric> {
+    let registered = ModelRegistry::new().get_embedding(&model.id);
+    let names: Vec<String> = [Some(&model.id), model.hf_repo.as_ref()]
+        .into_iter()
+        .flatten()
+        .chain(registered.iter().filter_map(|model| model.hf_repo.as_ref()))
+        .map(|name| name.to_lowercase())
+        .collect();
+    TRAINED_METRICS
+        .iter()
+        .find(|(fragment, _)| names.iter().any(|name| name.contains(fragment)))
+        .map(|&(_, metric)| metric)
+}
+
+/// Problems with the metrics of `vector_db` for embeddings from `model`:
+/// each configured metric other than the one the model was trained for.
+pub fn check(vector_db: &VectorDbConfig, model: &EmbeddingModel) -> Vec<String> {
+    let Some(trained) = trained_metric(model) else {
+        return Vec::new();
+    };
+    let mismatch = |setting: String, metric: DistanceMetric| {
+        format!(
+            "{} is {} but embedding model '{}' was trained for {} distance; results will be ranked differently than the model intends",
+            setting,
+            metric.as_str(),
+            model.id,
+            trained.as_str()
+        )
+    };
+
+    let mut problems = Vec::new();
+    if vector_db.distance != trained {
+        problems.push(mismatch("vector_db.distance".to_string(), vector_db.distance));
+    }
+    let mut overrides: Vec<_> = vector_db.distances.iter().filter(|(_, &metric)| metric != trained).collect();
+    overrides.sort_by_key(|(collection, _)| *collection);
+    for (collection, &metric) in overrides {
+        problems.push(mismatch(format!("vector_db.distances.{}", collection), metric));
+    }
+    problems
+}
+
+/// Similarity score of `a` and `b` under `metric`.
+pub(crate) fn similarity(metric: DistanceMetric, a: &[f32], b: &[f32]) -> f32 {
+    score(metric, dot(a, b), dot(a, a).sqrt(), dot(b, b).sqrt())
+}
+
+/// Similarity score under `metric` of two embeddings with dot product
+/// `dot` and lengths `a_norm` and `b_norm`.
+pub(crate) fn score(metric: DistanceMetric, dot: f32, a_norm: f32, b_norm: f32) -> f32 {
+    match metric {
+        DistanceMetric::Cosine => {
+            let norms = a_norm * b_norm;
+            if norms > 0.0 { dot / norms } else { 0.0 }
+        }
+        DistanceMetric::Dot => dot,
+        DistanceMetric::Euclidean => euclidean_score(a_norm * a_norm + b_norm * b_norm - 2.0 * dot),
+    }
+}
+
+/// Similarity score of a squared Euclidean distance, which equals cosine
+/// similarity for unit-length embeddings.
+pub(crate) fn euclidean_score(squared_distance: f32) -> f32 {
+    1.0 - squared_distance / 2.0
+}
+
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scores_and_trained_metrics() {
+        let (a, b) = ([3.0, 4.0], [6.0, 8.0]);
+        assert!((similarity(DistanceMetric::Cosine, &a, &b) - 1.0).abs() < 1e-6);
+        assert_eq!(similarity(DistanceMetric::Dot, &a, &b), 50.0);
+        assert_eq!(similarity(DistanceMetric::Euclidean, &a, &b), 1.0 - 25.0 / 2.0);
+        // All three agree on unit-length embeddings
+        let (a, b) = ([0.6, 0.8], [1.0, 0.0]);
+        for metric in [DistanceMetric::Dot, DistanceMetric::Euclidean] {
+            assert!((similarity(metric, &a, &b) - 0.6).abs() < 1e-6);
+        }
+
+        let tas_b = EmbeddingModel::from("sentence-transformers/msmarco-distilbert-base-tas-b");
+        assert_eq!(trained_metric(&tas_b), Some(DistanceMetric::Dot));
+        assert_eq!(trained_metric(&EmbeddingModel::default()), Some(DistanceMetric::Cosine));
+        assert_eq!(trained_metric(&EmbeddingModel::from("my-own-embedder")), None);
+
+        let mut vector_db = VectorDbConfig::default();
+        assert!(check(&vector_db, &EmbeddingModel::default()).is_empty());
+        assert_eq!(check(&vector_db, &tas_b).len(), 1);
+        vector_db.distance = DistanceMetric::Dot;
+        vector_db.distances.insert("nucleus_kb-notes".to_string(), DistanceMetric::Euclidean);
+        let problems = check(&vector_db, &tas_b);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("vector_db.distances.nucleus_kb-notes is euclidean"));
+    }
+}
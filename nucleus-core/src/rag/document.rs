@@ -0,0 +1,97 @@
+//! Text extraction from PDF and DOCX files (`documents` feature).
+//!
+//! Design docs and specs are rarely plain text. Their text is extracted
+//! page by page so chunks can record the page they came from: PDFs with
+//! `pdf-extract`, DOCX files by reading `word/document.xml` out of the
+//! archive. DOCX has no fixed pages; breaks are taken from explicit page
+//! breaks and the ones Word recorded when the file was last saved.
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::io::{self, Read, Seek};
+use std::path::Path;
+
+/// Text of each page of the PDF or DOCX file at `path`.
+pub(crate) fn extract_pages(path: &Path) -> io::Result<Vec<String>> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("pdf") => pdf_pages(path),
+        Some("docx") => docx_pages(std::fs::File::open(path)?),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("not a document: {}", path.display()))),
+    }
+}
+
+fn pdf_pages(path: &Path) -> io::Result<Vec<String>> {
+    // pdf-extract panics on some malformed fonts; one bad file should not take down indexing
+    std::panic::catch_unwind(|| pdf_extract::extract_text_by_pages(path))
+        .map_err(|_| io::Error::other(format!("could not parse {}", path.display())))?
+        .map_err(io::Error::other)
+}
+
+fn docx_pages(file: impl Read + Seek) -> io::Result<Vec<String>> {
+    let mut archive = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+    let mut xml = String::new();
+    archive.by_name("word/document.xml").map_err(io::Error::other)?.read_to_string(&mut xml)?;
+
+    let mut reader = Reader::from_str(&xml);
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    let mut in_text = false;
+    loop {
+        match reader.read_event().map_err(io::Error::other)? {
+            Event::Start(element) if element.local_name().as_ref() == b"t" => in_text = true,
+            Event::End(element) if element.local_name().as_ref() == b"t" => in_text = false,
+            Event::End(element) if element.local_name().as_ref() == b"p" => page.push('\n'),
+            Event::Text(text) if in_text => page.push_str(&text.unescape().map_err(io::Error::other)?),
+            Event::Empty(element) => match element.local_name().as_ref() {
+                b"tab" => page.push('\t'),
+                b"br" if is_page_break(&element) => pages.push(std::mem::take(&mut page)),
+                b"br" | b"cr" => page.push('\n'),
+                // Follows an explicit break too, so only counts on a page with text
+                b"lastRenderedPageBreak" if !page.trim().is_empty() => pages.push(std::mem::take(&mut page)),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    pages.push(page);
+    Ok(pages)
+}
+
+/// Whether a `<w:br>` element breaks the page rather than the line.
+fn is_page_break(element: &BytesStart) -> bool {
+    element.attributes()
+        .flatten()
+        .any(|attribute| attribute.key.local_name().as_ref() == b"type" && attribute.value.as_ref() == b"page")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn docx(body: &str) -> Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("word/document.xml", zip::write::SimpleFileOptions::default()).unwrap();
+        write!(
+            writer,
+            r#"<?xml version="1.0"?><w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>{}</w:body></w:document>"#,
+            body
+        ).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_docx_pages() {
+        let file = docx(concat!(
+            r#"<w:p><w:r><w:t>Design &amp; scope</w:t></w:r></w:p>"#,
+            r#"<w:p><w:r><w:t xml:space="preserve">Goals: </w:t><w:tab/><w:t>fast</w:t></w:r></w:p>"#,
+            r#"<w:p><w:r><w:br w:type="page"/></w:r></w:p>"#,
+            r#"<w:p><w:r><w:lastRenderedPageBreak/><w:t>Appendix</w:t></w:r></w:p>"#,
+            r#"<w:p><w:r><w:lastRenderedPageBreak/><w:t>Glossary</w:t></w:r></w:p>"#,
+        ));
+
+        let pages = docx_pages(file).unwrap();
+        assert_eq!(pages, ["Design & scope\nGoals: \tfast\n", "\nAppendix\n", "Glossary\n"]);
+    }
+}
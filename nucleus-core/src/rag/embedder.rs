@@ -3,9 +3,11 @@
 //! This module provides functionality to convert text into vector embeddings
 //! using provider embedding models.
 
+use super::cache::{self, EmbeddingCache};
 use crate::{models::EmbeddingModel, provider::{Provider, ProviderError}};
 use std::sync::Arc;
 use thiserror::Error;
+use tracing::{debug, warn};
 
 /// Errors that can occur during embedding generation.
 #[derive(Debug, Error)]
@@ -19,6 +21,11 @@ pub enum EmbedderError {
     /// This typically indicates a problem with the model or request format.
     #[error("No embeddings returned")]
     NoEmbeddings,
+
+    /// The local ONNX model failed (see [`Embedder::with_onnx`]).
+    #[cfg(feature = "onnx")]
+    #[error("ONNX embedding error: {0}")]
+    Onnx(#[from] super::onnx::OnnxError),
 }
 
 /// Result type for embedding operations.
@@ -40,6 +47,10 @@ pub type Result<T> = std::result::Result<T, EmbedderError>;
 pub struct Embedder {
     provider: Arc<dyn Provider>,
     model: EmbeddingModel,
+    cache: Option<Arc<EmbeddingCache>>,
+    /// Local model used instead of the provider
+    #[cfg(feature = "onnx")]
+    onnx: Option<Arc<super::onnx::OnnxEmbedder>>,
 }
 
 impl Embedder {
@@ -47,9 +58,34 @@ impl Embedder {
         Self {
             provider,
             model: model.into(),
+            cache: None,
+            #[cfg(feature = "onnx")]
+            onnx: None,
+        }
+    }
+    
+    /// An embedder for bulk indexing that runs `onnx` locally instead of
+    /// calling the provider, sharing this one's cache.
+    #[cfg(feature = "onnx")]
+    pub(crate) fn with_onnx(&self, onnx: super::onnx::OnnxEmbedder) -> Self {
+        Self {
+            onnx: Some(Arc::new(onnx)),
+            ..self.clone()
         }
     }
     
+    /// Reuses embeddings of previously seen texts in [`embed_batch`](Self::embed_batch),
+    /// persisted in `dir`.
+    pub fn with_cache(mut self, dir: impl AsRef<std::path::Path>) -> Self {
+        self.cache = Some(Arc::new(EmbeddingCache::new(dir, &self.model.id, self.model.embedding_dim)));
+        self
+    }
+    
+    /// Returns the embedding model used by this embedder.
+    pub fn model(&self) -> &EmbeddingModel {
+        &self.model
+    }
+    
     /// Generates a vector embedding for the given text.
     ///
     /// The embedding is a high-dimensional vector (typically 768 or 1024 dimensions)
@@ -73,6 +109,10 @@ impl Embedder {
     /// - The API returns no embeddings
     ///
     pub async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        #[cfg(feature = "onnx")]
+        if self.onnx.is_some() {
+            return self.embed_uncached(&[text]).await?.pop().ok_or(EmbedderError::NoEmbeddings);
+        }
         self.provider
             .embed(text, &self.model)
             .await
@@ -83,6 +123,7 @@ impl Embedder {
     ///
     /// This is more efficient than calling `embed()` repeatedly, as it can
     /// process multiple texts in a single request or pipeline them efficiently.
+    /// With a cache, only texts not embedded before are sent to the model.
     ///
     /// # Arguments
     ///
@@ -94,17 +135,83 @@ impl Embedder {
     ///
     /// # Errors
     ///
-    /// Returns an error if any embedding generation fails.
+    /// Returns an error if any embedding generation fails. Cache failures
+    /// are logged and fall back to the model.
     pub async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
-        use tracing::info;
+        let Some(cache) = &self.cache else {
+            return self.embed_uncached(texts).await;
+        };
         
-        info!("Embedder::embed_batch called with {} texts", texts.len());
-        let result = self.provider
+        let keys: Vec<cache::Key> = texts.iter().map(|text| cache::key(text)).collect();
+        let lookup = {
+            let (cache, keys) = (Arc::clone(cache), keys.clone());
+            tokio::task::spawn_blocking(move || cache.get(&keys)).await
+        };
+        let mut embeddings = match lookup {
+            Ok(Ok(embeddings)) => embeddings,
+            Ok(Err(e)) => {
+                warn!("Embedding cache {} unreadable: {}", cache.path().display(), e);
+                vec![None; texts.len()]
+            }
+            Err(e) => {
+                warn!("Embedding cache lookup failed: {}", e);
+                vec![None; texts.len()]
+            }
+        };
+        
+        let missing: Vec<usize> = (0..texts.len()).filter(|&i| embeddings[i].is_none()).collect();
+        debug!("Embedding cache: {} of {} texts cached", texts.len() - missing.len(), texts.len());
+        if !missing.is_empty() {
+            let missing_texts: Vec<&str> = missing.iter().map(|&i| texts[i]).collect();
+            let computed = self.embed_uncached(&missing_texts).await?;
+            
+            let entries: Vec<(cache::Key, Vec<f32>)> = missing.iter()
+                .zip(&computed)
+                .map(|(&i, embedding)| (keys[i], embedding.clone()))
+                .collect();
+            let store = Arc::clone(cache);
+            let stored = tokio::task::spawn_blocking(move || {
+                let entries: Vec<(cache::Key, &[f32])> = entries.iter()
+                    .map(|(key, embedding)| (*key, embedding.as_slice()))
+                    .collect();
+                store.insert(&entries)
+            }).await;
+            if let Ok(Err(e)) = stored {
+                warn!("Failed to write embedding cache {}: {}", cache.path().display(), e);
+            }
+            
+            for (i, embedding) in missing.into_iter().zip(computed) {
+                embeddings[i] = Some(embedding);
+            }
+        }
+        
+        embeddings.into_iter()
+            .map(|embedding| embedding.ok_or(EmbedderError::NoEmbeddings))
+            .collect()
+    }
+    
+    async fn embed_uncached(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        #[cfg(feature = "onnx")]
+        if let Some(onnx) = &self.onnx {
+            let (onnx, owned) = (Arc::clone(onnx), texts.iter().map(|text| text.to_string()).collect::<Vec<_>>());
+            let embeddings = tokio::task::spawn_blocking(move || {
+                let texts: Vec<&str> = owned.iter().map(String::as_str).collect();
+                onnx.embed(&texts)
+            })
+            .await
+            .map_err(|_| EmbedderError::NoEmbeddings)??;
+            if embeddings.len() != texts.len() {
+                return Err(EmbedderError::NoEmbeddings);
+            }
+            return Ok(embeddings);
+        }
+        let embeddings = self.provider
             .embed_batch(texts, &self.model)
             .await
             .map_err(EmbedderError::Provider)?;
-        info!("Embedder::embed_batch completed, got {} embeddings", result.len());
-        
-        Ok(result)
+        if embeddings.len() != texts.len() {
+            return Err(EmbedderError::NoEmbeddings);
+        }
+        Ok(embeddings)
     }
 }
@@ -0,0 +1,284 @@
+//! Metadata filters scoping a search to part of the index.
+//!
+//! A filter is a list of conditions on document metadata that must all
+//! hold, written like `source LIKE 'src/%' AND language = rust`. Stores
+//! apply it in the database where they can, so the nearest neighbours are
+//! picked among matching documents rather than thinned out afterwards.
+
+use super::types::Document;
+use super::RagError;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Conditions on document metadata that all must hold.
+///
+/// # Example
+///
+/// ```
+/// # use nucleus_core::rag::SearchFilter;
+/// let filter: SearchFilter = "source LIKE 'src/%' AND language = rust".parse().unwrap();
+/// assert_eq!(filter, SearchFilter::new().with_like("source", "src/%").with_equals("language", "rust"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchFilter {
+    conditions: Vec<Condition>,
+}
+
+/// A condition on one metadata value; documents without the key never match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// The value is exactly `value`
+    Equals { key: String, value: String },
+    /// The value matches a SQL `LIKE` pattern, where `%` matches any run of
+    /// characters and `_` any single character (case-sensitive)
+    Like { key: String, pattern: String },
+}
+
+impl SearchFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_equals(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.conditions.push(Condition::Equals { key: key.into(), value: value.into() });
+        self
+    }
+
+    pub fn with_like(mut self, key: impl Into<String>, pattern: impl Into<String>) -> Self {
+        self.conditions.push(Condition::Like { key: key.into(), pattern: pattern.into() });
+        self
+    }
+
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Whether `document` meets every condition.
+    pub fn matches(&self, document: &Document) -> bool {
+        self.conditions.iter().all(|condition| condition.matches(&document.metadata))
+    }
+}
+
+impl Condition {
+    pub fn key(&self) -> &str {
+        match self {
+            Condition::Equals { key, .. } | Condition::Like { key, .. } => key,
+        }
+    }
+
+    pub fn matches(&self, metadata: &HashMap<String, String>) -> bool {
+        let Some(actual) = metadata.get(self.key()) else {
+            return false;
+        };
+        match self {
+            Condition::Equals { value, .. } => actual == value,
+            Condition::Like { pattern, .. } => like(actual, pattern),
+        }
+    }
+}
+
+/// Whether `text` matches the `LIKE` `pattern`.
+pub(crate) fn like(text: &str, pattern: &str) -> bool {
+    wildcard(text, pattern, '%', '_')
+}
+
+/// Whether `text` matches the shell-style `pattern` (`*` and `?`).
+pub(crate) fn glob(text: &str, pattern: &str) -> bool {
+    wildcard(text, pattern, '*', '?')
+}
+
+/// Matches `pattern`, where `many` stands for any characters and `one` for
+/// a single one.
+fn wildcard(text: &str, pattern: &str, many: char, one: char) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    // Position after the last `many` and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(&c) if c == many => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == one || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `many` swallow one more character
+                Some((after, start)) => {
+                    p = after;
+                    t = start + 1;
+                    backtrack = Some((after, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == many)
+}
+
+impl FromStr for SearchFilter {
+    type Err = RagError;
+
+    /// Parses conditions of the form `key = value` or `key LIKE pattern`,
+    /// joined by `AND`. Values may be quoted with `'` or `"` (doubling the
+    /// quote inside), and must be if they contain spaces.
+    fn from_str(filter: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| RagError::Filter(format!("{} in '{}'", reason, filter));
+        let mut tokens = tokenize(filter).map_err(invalid)?.into_iter();
+        let mut parsed = SearchFilter::new();
+
+        loop {
+            let key = match tokens.next() {
+                Some(Token::Word(key)) => key,
+                Some(token) => return Err(invalid(format!("expected a metadata key, found {}", token))),
+                None => return Err(invalid("expected a condition".to_string())),
+            };
+            let operator = tokens.next();
+            let value = match tokens.next() {
+                Some(Token::Word(value) | Token::Quoted(value)) => value,
+                Some(token) => return Err(invalid(format!("expected a value for '{}', found {}", key, token))),
+                None => return Err(invalid(format!("expected a value for '{}'", key))),
+            };
+            parsed = match operator {
+                Some(Token::Equals) => parsed.with_equals(key, value),
+                Some(Token::Word(word)) if word.eq_ignore_ascii_case("like") => parsed.with_like(key, value),
+                _ => return Err(invalid(format!("expected = or LIKE after '{}'", key))),
+            };
+
+            match tokens.next() {
+                None => return Ok(parsed),
+                Some(Token::Word(word)) if word.eq_ignore_ascii_case("and") => {}
+                Some(token) => return Err(invalid(format!("expected AND, found {}", token))),
+            }
+        }
+    }
+}
+
+impl fmt::Display for SearchFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, condition) in self.conditions.iter().enumerate() {
+            if i > 0 {
+                write!(f, " AND ")?;
+            }
+            match condition {
+                Condition::Equals { key, value } => write!(f, "{} = '{}'", key, value.replace('\'', "''"))?,
+                Condition::Like { key, pattern } => write!(f, "{} LIKE '{}'", key, pattern.replace('\'', "''"))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Equals,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Quoted(text) => write!(f, "\"{}\"", text),
+            Token::Equals => write!(f, "'='"),
+        }
+    }
+}
+
+fn tokenize(filter: &str) -> std::result::Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = filter.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '=' => {
+                chars.next();
+                tokens.push(Token::Equals);
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        // A doubled quote stands for the quote itself
+                        Some(q) if q == c && chars.peek() == Some(&c) => {
+                            chars.next();
+                            text.push(c);
+                        }
+                        Some(q) if q == c => break,
+                        Some(other) => text.push(other),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '=' | '\'' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_like() {
+        assert!(like("src/rag/mod.rs", "src/%"));
+        assert!(like("src/rag/mod.rs", "%mod.rs"));
+        assert!(like("src/rag/mod.rs", "src/%/%.rs"));
+        assert!(like("a.rs", "_.rs"));
+        assert!(like("", "%"));
+        assert!(!like("docs/src/a.rs", "src/%"));
+        assert!(!like("ab.rs", "_.rs"));
+        assert!(!like("Src/a.rs", "src/%"));
+    }
+
+    #[test]
+    fn test_parse_and_match() {
+        let filter: SearchFilter = r#"source like 'src/%' AND language = rust and tag = "it's""#.parse().unwrap();
+        assert_eq!(
+            filter,
+            SearchFilter::new()
+                .with_like("source", "src/%")
+                .with_equals("language", "rust")
+                .with_equals("tag", "it's")
+        );
+        assert_eq!(filter.to_string(), "source LIKE 'src/%' AND language = 'rust' AND tag = 'it''s'");
+        assert_eq!(filter.to_string().parse::<SearchFilter>().unwrap(), filter);
+
+        let document = Document::new("a", "", vec![])
+            .with_metadata("source", "src/main.rs")
+            .with_metadata("language", "rust")
+            .with_metadata("tag", "it's");
+        assert!(filter.matches(&document));
+        assert!(!filter.matches(&Document { metadata: HashMap::new(), ..document }));
+
+        for invalid in ["", "source", "source LIKE", "source ~ x", "a = b OR c = d", "a = 'open"] {
+            assert!(invalid.parse::<SearchFilter>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Stale sources in the knowledge base (`rag.freshness`).
+//!
+//! Live updates only cover directories the running server indexed, and web
+//! pages are never watched, so stored chunks can fall behind their sources
+//! and retrieval quietly serves outdated code. A file is stale once it was
+//! modified or deleted after it was indexed, a web page once it was not
+//! fetched again for `url_max_age_days`. Sources without a recorded
+//! modification time (notes, or files indexed before it was recorded) are
+//! never reported.
+
+use super::indexer::{modified_secs, now_secs};
+use super::types::SourceStats;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Why a source is stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Staleness {
+    /// The file changed on disk since it was indexed
+    Modified,
+    /// The file no longer exists
+    Missing,
+    /// The web page was fetched longer ago than `url_max_age_days`
+    Expired,
+}
+
+impl fmt::Display for Staleness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Modified => "modified",
+            Self::Missing => "missing",
+            Self::Expired => "expired",
+        })
+    }
+}
+
+/// A source whose stored chunks are out of date.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StaleSource {
+    pub source: String,
+    pub reason: Staleness,
+    /// When the source was last indexed, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexed_at: Option<u64>,
+}
+
+/// Whether `source` is a web page rather than a file.
+pub(crate) fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// The stale ones among `sources` at `now` (seconds since the Unix epoch).
+///
+/// Reads the metadata of every indexed file, so call it off the async
+/// runtime for large knowledge bases.
+pub fn stale_sources(sources: &[SourceStats], now: u64, url_max_age: Option<Duration>) -> Vec<StaleSource> {
+    sources
+        .iter()
+        .filter_map(|stats| {
+            let reason = if is_url(&stats.source) {
+                let max_age = url_max_age?.as_secs();
+                let indexed_at = stats.indexed_at?;
+                (now.saturating_sub(indexed_at) > max_age).then_some(Staleness::Expired)?
+            } else {
+                let indexed_mtime = stats.modified?;
+                match std::fs::metadata(&stats.source) {
+                    Ok(metadata) if modified_secs(&metadata) > indexed_mtime => Staleness::Modified,
+                    Ok(_) => return None,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Staleness::Missing,
+                    Err(_) => return None,
+                }
+            };
+            Some(StaleSource {
+                source: stats.source.clone(),
+                reason,
+                indexed_at: stats.indexed_at,
+            })
+        })
+        .collect()
+}
+
+/// [`stale_sources`] now, off the async runtime.
+pub async fn find_stale(sources: Vec<SourceStats>, url_max_age: Option<Duration>) -> Vec<StaleSource> {
+    tokio::task::spawn_blocking(move || stale_sources(&sources, now_secs(), url_max_age))
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(source: &str, indexed_at: Option<u64>, modified: Option<u64>) -> SourceStats {
+        SourceStats {
+            source: source.to_string(),
+            chunks: 1,
+            indexed_at,
+            modified,
+        }
+    }
+
+    #[test]
+    fn test_stale_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        std::fs::write(&file, "fn main() {}").unwrap();
+        let mtime = modified_secs(&std::fs::metadata(&file).unwrap());
+        let file = file.to_string_lossy().to_string();
+        let missing = dir.path().join("gone.rs").to_string_lossy().to_string();
+
+        let day = 24 * 3600;
+        let now = mtime + 10 * day;
+        let sources = [
+            stats(&file, Some(mtime), Some(mtime)),
+            stats(&file, Some(mtime - 60), Some(mtime - 60)),
+            stats(&missing, Some(mtime), Some(mtime)),
+            stats("notes", Some(mtime), None),
+            stats("https://docs.rs/tokio", Some(now - 8 * day), Some(now - 8 * day)),
+            stats("https://docs.rs/serde", Some(now - day), Some(now - day)),
+        ];
+
+        let stale: Vec<(String, Staleness)> = stale_sources(&sources, now, Some(Duration::from_secs(7 * day)))
+            .into_iter()
+            .map(|stale| (stale.source, stale.reason))
+            .collect();
+        assert_eq!(stale, [
+            (file, Staleness::Modified),
+            (missing, Staleness::Missing),
+            ("https://docs.rs/tokio".to_string(), Staleness::Expired),
+        ]);
+        assert_eq!(stale_sources(&sources[4..], now, None), []);
+    }
+}
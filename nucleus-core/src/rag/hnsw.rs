@@ -0,0 +1,800 @@
+//! In-memory HNSW index in front of an embedded store (`storage.hnsw`).
+//!
+//! The embedded stores compare the query against every stored embedding, so
+//! search time grows with the knowledge base. With the index enabled, the
+//! embeddings are also kept in a hierarchical navigable small world graph
+//! (Malkov & Yashunin), which finds the approximate nearest neighbours in a
+//! few hundred comparisons, keeping search well under a millisecond at 100k+
+//! chunks. The store stays the source of truth: the graph is built from it
+//! when the store opens and follows every write. `m` sets the links per node
+//! and `ef_construction`/`ef_search` the candidates examined while inserting
+//! and searching; higher values trade speed and memory for recall. Nodes
+//! are compared with the collection's distance metric (`vector_db.distance`).
+//!
+//! On big indexes the graph's copy of the embeddings dominates memory.
+//! `quantization: int8` keeps a byte per dimension and `binary` a bit,
+//! cutting it 4x or 32x; the full-precision embeddings go to a temporary
+//! file, and the `rescore` × `top_k` best candidates are ranked again
+//! against them, so results lose little recall.
+
+use super::distance::{self, dot};
+use super::filter::SearchFilter;
+use super::store::{source_matches, VectorStore};
+use super::types::{Document, SearchResult};
+use crate::config::{DistanceMetric, HnswConfig, Quantization};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::info;
+
+/// Candidates fetched per requested result when a filter discards some.
+const FILTER_OVERFETCH: usize = 4;
+
+/// A node and its similarity to the query, ordered by similarity.
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    similarity: f32,
+    node: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity.total_cmp(&other.similarity).then(other.node.cmp(&self.node))
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    /// Neighbours on each layer the node is on, from layer 0 up
+    links: Vec<Vec<usize>>,
+    deleted: bool,
+}
+
+/// An embedding as compared with the nodes.
+#[derive(Debug)]
+struct Query {
+    /// Unit length for cosine
+    vector: Vec<f32>,
+    norm: f32,
+}
+
+/// The nodes' embeddings, encoded as set by `quantization`.
+#[derive(Debug)]
+struct Vectors {
+    metric: DistanceMetric,
+    /// Dimensions, taken from the first embedding
+    dim: usize,
+    codes: Codes,
+    /// Length of each node's embedding
+    norms: Vec<f32>,
+}
+
+#[derive(Debug)]
+enum Codes {
+    Full(Vec<f32>),
+    /// `dim` bytes per node, and each node's largest magnitude
+    Int8(Vec<i8>, Vec<f32>),
+    /// One sign bit per dimension, `dim.div_ceil(64)` words per node
+    Binary(Vec<u64>),
+}
+
+impl Vectors {
+    fn new(quantization: Quantization, metric: DistanceMetric) -> Self {
+        let codes = match quantization {
+            Quantization::None => Codes::Full(Vec::new()),
+            Quantization::Int8 => Codes::Int8(Vec::new(), Vec::new()),
+            Quantization::Binary => Codes::Binary(Vec::new()),
+        };
+        Self {
+            metric,
+            dim: 0,
+            codes,
+            norms: Vec::new(),
+        }
+    }
+
+    /// Appends an embedding, padded or cut to the dimensions of the first.
+    fn push(&mut self, vector: &[f32]) {
+        if self.dim == 0 {
+            self.dim = vector.len();
+        }
+        let dim = self.dim;
+        let value = |i: usize| vector.get(i).copied().unwrap_or(0.0);
+        self.norms.push((0..dim).map(|i| value(i) * value(i)).sum::<f32>().sqrt());
+        match &mut self.codes {
+            Codes::Full(values) => values.extend((0..dim).map(value)),
+            Codes::Int8(bytes, scales) => {
+                let scale = (0..dim).map(|i| value(i).abs()).fold(0.0, f32::max);
+                let step = if scale == 0.0 { 0.0 } else { 127.0 / scale };
+                bytes.extend((0..dim).map(|i| (value(i) * step).round() as i8));
+                scales.push(scale);
+            }
+            Codes::Binary(bits) => {
+                let start = bits.len();
+                bits.resize(start + dim.div_ceil(64), 0);
+                for i in (0..dim).filter(|&i| value(i) > 0.0) {
+                    bits[start + i / 64] |= 1 << (i % 64);
+                }
+            }
+        }
+    }
+
+    /// Approximate similarity score of `query` to `node`.
+    fn similarity_to(&self, query: &Query, node: usize) -> f32 {
+        let dim = self.dim;
+        let query_norm = query.norm;
+        let query = &query.vector;
+        let dot = match &self.codes {
+            Codes::Full(values) => dot(query, &values[node * dim..(node + 1) * dim]),
+            Codes::Int8(bytes, scales) => {
+                let codes = &bytes[node * dim..(node + 1) * dim];
+                let sum: f32 = query.iter().zip(codes).map(|(q, &c)| q * c as f32).sum();
+                sum * scales[node] / 127.0
+            }
+            Codes::Binary(bits) => {
+                let words = &bits[node * dim.div_ceil(64)..];
+                let sum: f32 = query
+                    .iter()
+                    .take(dim)
+                    .enumerate()
+                    .map(|(i, q)| if words[i / 64] >> (i % 64) & 1 == 1 { *q } else { -q })
+                    .sum();
+                sum / (dim as f32).sqrt() * self.norms[node]
+            }
+        };
+        distance::score(self.metric, dot, query_norm, self.norms[node])
+    }
+
+    /// Approximate similarity score between two nodes.
+    fn similarity(&self, a: usize, b: usize) -> f32 {
+        let dim = self.dim;
+        let dot = match &self.codes {
+            Codes::Full(values) => dot(&values[a * dim..(a + 1) * dim], &values[b * dim..(b + 1) * dim]),
+            Codes::Int8(bytes, scales) => {
+                let sum: i32 = bytes[a * dim..(a + 1) * dim]
+                    .iter()
+                    .zip(&bytes[b * dim..(b + 1) * dim])
+                    .map(|(&x, &y)| x as i32 * y as i32)
+                    .sum();
+                sum as f32 * scales[a] * scales[b] / (127.0 * 127.0)
+            }
+            Codes::Binary(bits) => {
+                let words = dim.div_ceil(64);
+                let differing: u32 = bits[a * words..(a + 1) * words]
+                    .iter()
+                    .zip(&bits[b * words..(b + 1) * words])
+                    .map(|(x, y)| (x ^ y).count_ones())
+                    .sum();
+                (1.0 - 2.0 * differing as f32 / dim.max(1) as f32) * self.norms[a] * self.norms[b]
+            }
+        };
+        distance::score(self.metric, dot, self.norms[a], self.norms[b])
+    }
+}
+
+/// HNSW graph over embeddings, scored by a [`DistanceMetric`].
+///
+/// Removed nodes stay in the graph as stepping stones but are never
+/// returned; rebuild the graph once they make up a large share of it.
+/// With quantization, the similarities it returns are approximate.
+#[derive(Debug)]
+pub(crate) struct Hnsw {
+    m: usize,
+    ef_construction: usize,
+    level_factor: f64,
+    vectors: Vectors,
+    nodes: Vec<Node>,
+    entry: Option<usize>,
+    deleted: usize,
+    rng: u64,
+}
+
+impl Hnsw {
+    pub(crate) fn new(m: usize, ef_construction: usize, quantization: Quantization, metric: DistanceMetric) -> Self {
+        let m = m.max(2);
+        Self {
+            m,
+            ef_construction: ef_construction.max(m),
+            level_factor: 1.0 / (m as f64).ln(),
+            vectors: Vectors::new(quantization, metric),
+            nodes: Vec::new(),
+            entry: None,
+            deleted: 0,
+            rng: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+
+    /// Number of nodes that have not been removed.
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.len() - self.deleted
+    }
+
+    /// Number of removed nodes still in the graph.
+    pub(crate) fn deleted(&self) -> usize {
+        self.deleted
+    }
+
+    /// Adds `vector` and returns its node.
+    pub(crate) fn insert(&mut self, vector: &[f32]) -> usize {
+        let query = self.query(vector);
+        let level = self.random_level();
+        let node = self.nodes.len();
+        self.vectors.push(&query.vector);
+        self.nodes.push(Node {
+            links: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(node);
+            return node;
+        };
+        let top = self.nodes[entry].links.len() - 1;
+
+        let mut entry_points = vec![self.candidate(&query, entry)];
+        for layer in (level + 1..=top).rev() {
+            entry_points = self.search_layer(&query, &entry_points, 1, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            let candidates = self.search_layer(&query, &entry_points, self.ef_construction, layer);
+            let neighbours = self.select_neighbours(node, &candidates, self.m);
+            for &neighbour in &neighbours {
+                self.link(neighbour, node, layer);
+            }
+            self.nodes[node].links[layer] = neighbours;
+            entry_points = candidates;
+        }
+
+        if level > top {
+            self.entry = Some(node);
+        }
+        node
+    }
+
+    /// Stops returning `node` from searches.
+    pub(crate) fn remove(&mut self, node: usize) {
+        if let Some(node) = self.nodes.get_mut(node).filter(|node| !node.deleted) {
+            node.deleted = true;
+            self.deleted += 1;
+        }
+    }
+
+    /// The (at most) `k` nodes most similar to `query` and their similarity
+    /// score, most similar first, examining at least `ef` candidates.
+    pub(crate) fn search(&self, query: &[f32], k: usize, ef: usize) -> Vec<(usize, f32)> {
+        let Some(entry) = self.entry.filter(|_| k > 0) else {
+            return Vec::new();
+        };
+        let query = self.query(query);
+        let top = self.nodes[entry].links.len() - 1;
+
+        let mut entry_points = vec![self.candidate(&query, entry)];
+        for layer in (1..=top).rev() {
+            entry_points = self.search_layer(&query, &entry_points, 1, layer);
+        }
+        // Removed nodes take up candidate slots, so look further the more there are
+        let ef = ef.max(k) * self.nodes.len() / self.len().max(1);
+        self.search_layer(&query, &entry_points, ef, 0)
+            .into_iter()
+            .filter(|candidate| !self.nodes[candidate.node].deleted)
+            .take(k)
+            .map(|candidate| (candidate.node, candidate.similarity))
+            .collect()
+    }
+
+    fn query(&self, vector: &[f32]) -> Query {
+        let vector = match self.vectors.metric {
+            DistanceMetric::Cosine => normalized(vector),
+            DistanceMetric::Dot | DistanceMetric::Euclidean => vector.to_vec(),
+        };
+        Query {
+            norm: dot(&vector, &vector).sqrt(),
+            vector,
+        }
+    }
+
+    fn candidate(&self, query: &Query, node: usize) -> Candidate {
+        Candidate {
+            similarity: self.vectors.similarity_to(query, node),
+            node,
+        }
+    }
+
+    /// The `ef` nodes on `layer` closest to `query` reachable from
+    /// `entry_points`, most similar first.
+    fn search_layer(&self, query: &Query, entry_points: &[Candidate], ef: usize, layer: usize) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry_points.iter().map(|candidate| candidate.node).collect();
+        let mut candidates: BinaryHeap<Candidate> = entry_points.iter().copied().collect();
+        let mut nearest: BinaryHeap<Reverse<Candidate>> = entry_points.iter().copied().map(Reverse).collect();
+        while nearest.len() > ef {
+            nearest.pop();
+        }
+
+        while let Some(current) = candidates.pop() {
+            let furthest = nearest.peek().map_or(f32::NEG_INFINITY, |Reverse(furthest)| furthest.similarity);
+            if current.similarity < furthest && nearest.len() >= ef {
+                break;
+            }
+            for &neighbour in self.nodes[current.node].links.get(layer).into_iter().flatten() {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let candidate = self.candidate(query, neighbour);
+                let furthest = nearest.peek().map_or(f32::NEG_INFINITY, |Reverse(furthest)| furthest.similarity);
+                if nearest.len() < ef || candidate.similarity > furthest {
+                    candidates.push(candidate);
+                    nearest.push(Reverse(candidate));
+                    if nearest.len() > ef {
+                        nearest.pop();
+                    }
+                }
+            }
+        }
+
+        let mut nearest: Vec<Candidate> = nearest.into_iter().map(|Reverse(candidate)| candidate).collect();
+        nearest.sort_by(|a, b| b.cmp(a));
+        nearest
+    }
+
+    /// Picks up to `m` of `candidates` (most similar first), preferring ones
+    /// that are not closer to an already picked neighbour than to `base`,
+    /// so links spread out instead of all pointing into one cluster.
+    fn select_neighbours(&self, base: usize, candidates: &[Candidate], m: usize) -> Vec<usize> {
+        let mut selected: Vec<usize> = Vec::with_capacity(m);
+        let mut pruned = Vec::new();
+        for candidate in candidates {
+            if selected.len() >= m {
+                break;
+            }
+            let to_base = self.vectors.similarity(base, candidate.node);
+            if selected.iter().all(|&other| self.vectors.similarity(candidate.node, other) < to_base) {
+                selected.push(candidate.node);
+            } else {
+                pruned.push(candidate.node);
+            }
+        }
+        let missing = m.saturating_sub(selected.len());
+        selected.extend(pruned.into_iter().take(missing));
+        selected
+    }
+
+    /// Links `from` to `to` on `layer`, pruning `from`'s links if it has too many.
+    fn link(&mut self, from: usize, to: usize, layer: usize) {
+        let max = if layer == 0 { 2 * self.m } else { self.m };
+        self.nodes[from].links[layer].push(to);
+        if self.nodes[from].links[layer].len() <= max {
+            return;
+        }
+
+        let mut candidates: Vec<Candidate> = self.nodes[from].links[layer]
+            .iter()
+            .map(|&node| Candidate {
+                similarity: self.vectors.similarity(from, node),
+                node,
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.cmp(a));
+        self.nodes[from].links[layer] = self.select_neighbours(from, &candidates, max);
+    }
+
+    /// Draws the top layer of a new node from an exponential distribution.
+    fn random_level(&mut self) -> usize {
+        // xorshift64*, deterministic so builds are reproducible
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let random = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        let uniform = ((random >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        (-uniform.ln() * self.level_factor) as usize
+    }
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = dot(vector, vector).sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|value| value / norm).collect()
+}
+
+/// Embeddings by node, in an anonymous temporary file, for rescoring the
+/// candidates of a quantized graph.
+#[derive(Debug)]
+struct FullVectors {
+    file: Mutex<File>,
+    /// Dimensions, taken from the first embedding
+    dim: usize,
+}
+
+impl FullVectors {
+    fn new() -> io::Result<Self> {
+        Ok(Self {
+            file: Mutex::new(tempfile::tempfile()?),
+            dim: 0,
+        })
+    }
+
+    /// Appends the next node's embedding, padded or cut to `dim`.
+    fn push(&mut self, vector: &[f32]) -> io::Result<()> {
+        if self.dim == 0 {
+            self.dim = vector.len();
+        }
+        let bytes: Vec<u8> = (0..self.dim)
+            .flat_map(|i| vector.get(i).copied().unwrap_or(0.0).to_le_bytes())
+            .collect();
+        let file = self.file.get_mut().unwrap();
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(&bytes)
+    }
+
+    fn get(&self, node: usize) -> io::Result<Vec<f32>> {
+        let mut bytes = vec![0; self.dim * 4];
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start((node * bytes.len()) as u64))?;
+        file.read_exact(&mut bytes)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect())
+    }
+}
+
+/// The graph and the documents of its nodes.
+#[derive(Debug)]
+struct Indexed {
+    graph: Hnsw,
+    /// Documents by node, without their embeddings
+    documents: Vec<Document>,
+    nodes: HashMap<String, usize>,
+    /// Full-precision embeddings when the graph is quantized
+    full: Option<FullVectors>,
+}
+
+impl Indexed {
+    fn new(config: &HnswConfig, metric: DistanceMetric) -> io::Result<Self> {
+        let full = match config.quantization {
+            Quantization::None => None,
+            Quantization::Int8 | Quantization::Binary => Some(FullVectors::new()?),
+        };
+        Ok(Self {
+            graph: Hnsw::new(config.m, config.ef_construction, config.quantization, metric),
+            documents: Vec::new(),
+            nodes: HashMap::new(),
+            full,
+        })
+    }
+
+    fn insert(&mut self, mut document: Document) -> io::Result<()> {
+        if let Some(old) = self.nodes.remove(&document.id) {
+            self.graph.remove(old);
+        }
+        if let Some(full) = &mut self.full {
+            full.push(&document.embedding)?;
+        }
+        let node = self.graph.insert(&document.embedding);
+        document.embedding = Vec::new();
+        self.nodes.insert(document.id.clone(), node);
+        self.documents.push(document);
+        debug_assert_eq!(self.documents.len(), node + 1);
+        Ok(())
+    }
+
+    /// Replaces the graph's similarities with exact ones, most similar first.
+    fn rescore(&self, query: &[f32], mut hits: Vec<(usize, f32)>) -> io::Result<Vec<(usize, f32)>> {
+        let Some(full) = &self.full else {
+            return Ok(hits);
+        };
+        let metric = self.graph.vectors.metric;
+        for (node, similarity) in &mut hits {
+            *similarity = distance::similarity(metric, query, &full.get(*node)?);
+        }
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(hits)
+    }
+
+    fn remove_source(&mut self, source_path: &str) {
+        let documents = &self.documents;
+        let removed: Vec<usize> = self
+            .nodes
+            .values()
+            .copied()
+            .filter(|&node| {
+                documents[node]
+                    .metadata
+                    .get("source")
+                    .is_some_and(|source| source_matches(source, source_path))
+            })
+            .collect();
+        for node in removed {
+            self.nodes.remove(&self.documents[node].id);
+            self.graph.remove(node);
+        }
+    }
+}
+
+/// An embedded store searched through an in-memory [`Hnsw`] graph.
+///
+/// Writes go to the store first and then to the graph; everything but
+/// search is answered by the store.
+pub(crate) struct HnswStore {
+    inner: Arc<dyn VectorStore>,
+    config: HnswConfig,
+    metric: DistanceMetric,
+    index: Arc<RwLock<Indexed>>,
+    /// Held by writes, so a rebuild cannot miss one
+    writes: tokio::sync::Mutex<()>,
+}
+
+impl HnswStore {
+    /// Builds the graph from the documents in `inner`, compared by `metric`.
+    pub(crate) async fn new(inner: Arc<dyn VectorStore>, config: HnswConfig, metric: DistanceMetric) -> Result<Self> {
+        let documents = inner.get_documents(None).await?;
+        let count = documents.len();
+        let index = build(&config, metric, documents).await?;
+        info!("Built the HNSW index over {} documents", count);
+        Ok(Self {
+            inner,
+            config,
+            metric,
+            index: Arc::new(RwLock::new(index)),
+            writes: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Rebuilds the graph without removed nodes once they outnumber the
+    /// others (and there are more than a few); callers hold `writes`.
+    async fn compact(&self) -> Result<()> {
+        let documents = {
+            let index = self.index.read().unwrap();
+            if index.graph.deleted() <= index.graph.len().max(1024) {
+                return Ok(());
+            }
+            index.nodes.len()
+        };
+        let rebuilt = build(&self.config, self.metric, self.inner.get_documents(None).await?).await?;
+        *self.index.write().unwrap() = rebuilt;
+        info!("Rebuilt the HNSW index over {} documents", documents);
+        Ok(())
+    }
+}
+
+async fn build(config: &HnswConfig, metric: DistanceMetric, documents: Vec<Document>) -> Result<Indexed> {
+    let mut index = Indexed::new(config, metric)?;
+    tokio::task::spawn_blocking(move || {
+        for document in documents {
+            index.insert(document)?;
+        }
+        Ok(index)
+    })
+    .await?
+}
+
+#[async_trait]
+impl VectorStore for HnswStore {
+    async fn add(&self, documents: Vec<Document>) -> Result<()> {
+        let _writes = self.writes.lock().await;
+        self.inner.add(documents.clone()).await?;
+        let index = Arc::clone(&self.index);
+        tokio::task::spawn_blocking(move || {
+            let mut index = index.write().unwrap();
+            documents.into_iter().try_for_each(|document| index.insert(document))
+        })
+        .await??;
+        Ok(())
+    }
+
+    async fn search(&self, query_embedding: &[f32], top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        let filter = filter.filter(|filter| !filter.is_empty());
+        let fetch = if filter.is_some() { top_k * FILTER_OVERFETCH } else { top_k };
+        let (results, exhausted) = {
+            let index = self.index.read().unwrap();
+            // Quantized similarities are rough, so rank more candidates exactly
+            let fetch = match index.full {
+                Some(_) => fetch * self.config.rescore.max(1),
+                None => fetch,
+            };
+            let hits = index.graph.search(query_embedding, fetch, self.config.ef_search.max(fetch));
+            let exhausted = hits.len() < fetch;
+            let hits = index.rescore(query_embedding, hits)?;
+            let results: Vec<SearchResult> = hits
+                .into_iter()
+                .filter(|&(node, _)| filter.is_none_or(|filter| filter.matches(&index.documents[node])))
+                .take(top_k)
+                .map(|(node, similarity)| SearchResult {
+                    document: index.documents[node].clone(),
+                    score: similarity,
+                })
+                .collect();
+            (results, exhausted)
+        };
+
+        // A narrow filter can discard every candidate; the store searches
+        // just the matching documents
+        if results.len() < top_k && !exhausted {
+            return self.inner.search(query_embedding, top_k, filter).await;
+        }
+        Ok(results)
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.inner.count().await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        let _writes = self.writes.lock().await;
+        self.inner.clear().await?;
+        *self.index.write().unwrap() = Indexed::new(&self.config, self.metric)?;
+        Ok(())
+    }
+
+    async fn get_indexed_paths(&self) -> Result<Vec<String>> {
+        self.inner.get_indexed_paths().await
+    }
+
+    async fn remove_by_source(&self, source_path: &str) -> Result<usize> {
+        let _writes = self.writes.lock().await;
+        let removed = self.inner.remove_by_source(source_path).await?;
+        self.index.write().unwrap().remove_source(source_path);
+        self.compact().await?;
+        Ok(removed)
+    }
+
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>> {
+        self.inner.get_documents(source_path).await
+    }
+
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>> {
+        self.inner.source_hashes(source_path).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.inner.health_check().await
+    }
+
+    async fn disk_usage(&self) -> Result<Option<u64>> {
+        self.inner.disk_usage().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_vectors(count: usize, dim: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                (0..dim)
+                    .map(|_| {
+                        state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+                        ((state >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn exact(metric: DistanceMetric, vectors: &[Vec<f32>], removed: &HashSet<usize>, query: &[f32], k: usize) -> Vec<usize> {
+        let mut scored: Vec<Candidate> = (0..vectors.len())
+            .filter(|node| !removed.contains(node))
+            .map(|node| Candidate {
+                similarity: distance::similarity(metric, query, &vectors[node]),
+                node,
+            })
+            .collect();
+        scored.sort_by(|a, b| b.cmp(a));
+        scored.into_iter().take(k).map(|candidate| candidate.node).collect()
+    }
+
+    #[test]
+    fn test_hnsw_recall() {
+        let vectors = random_vectors(1000, 16, 7);
+        let queries = random_vectors(50, 16, 11);
+        let mut graph = Hnsw::new(12, 64, Quantization::None, DistanceMetric::Cosine);
+        for vector in &vectors {
+            graph.insert(vector);
+        }
+        let removed: HashSet<usize> = (0..vectors.len()).step_by(3).collect();
+        for &node in &removed {
+            graph.remove(node);
+        }
+        assert_eq!(graph.len(), vectors.len() - removed.len());
+
+        let k = 10;
+        let mut found = 0;
+        for query in &queries {
+            let hits = graph.search(query, k, 64);
+            assert!(hits.iter().all(|(node, _)| !removed.contains(node)));
+            assert!(hits.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+            let expected = exact(DistanceMetric::Cosine, &vectors, &removed, query, k);
+            found += hits.iter().filter(|(node, _)| expected.contains(node)).count();
+        }
+        let recall = found as f64 / (queries.len() * k) as f64;
+        assert!(recall > 0.9, "recall {}", recall);
+
+        assert!(Hnsw::new(16, 100, Quantization::None, DistanceMetric::Cosine).search(&queries[0], k, 64).is_empty());
+    }
+
+    #[test]
+    fn test_dot_and_euclidean_recall() {
+        // Lengths vary, so the metrics rank differently from cosine
+        let vectors: Vec<Vec<f32>> = random_vectors(1000, 16, 3)
+            .into_iter()
+            .enumerate()
+            .map(|(n, vector)| vector.iter().map(|value| value * (0.5 + (n % 7) as f32 / 3.0)).collect())
+            .collect();
+        let queries = random_vectors(50, 16, 17);
+        let k = 10;
+        for metric in [DistanceMetric::Dot, DistanceMetric::Euclidean] {
+            let mut graph = Hnsw::new(12, 64, Quantization::None, metric);
+            for vector in &vectors {
+                graph.insert(vector);
+            }
+
+            let mut found = 0;
+            for query in &queries {
+                let hits = graph.search(query, k, 64);
+                let (node, similarity) = hits[0];
+                assert!((similarity - distance::similarity(metric, query, &vectors[node])).abs() < 1e-4);
+                let expected = exact(metric, &vectors, &HashSet::new(), query, k);
+                found += hits.iter().filter(|(node, _)| expected.contains(node)).count();
+            }
+            let recall = found as f64 / (queries.len() * k) as f64;
+            assert!(recall > 0.9, "{:?} recall {}", metric, recall);
+            let cosine = exact(DistanceMetric::Cosine, &vectors, &HashSet::new(), &queries[0], k);
+            assert_ne!(exact(metric, &vectors, &HashSet::new(), &queries[0], k), cosine);
+        }
+    }
+
+    #[test]
+    fn test_quantized_recall_with_rescoring() {
+        let vectors = random_vectors(500, 32, 5);
+        let queries = random_vectors(20, 32, 13);
+        let config = |quantization| HnswConfig {
+            enabled: true,
+            m: 12,
+            ef_construction: 64,
+            quantization,
+            ..HnswConfig::default()
+        };
+        let k = 10;
+        for quantization in [Quantization::Int8, Quantization::Binary] {
+            let mut index = Indexed::new(&config(quantization), DistanceMetric::Cosine).unwrap();
+            for (n, vector) in vectors.iter().enumerate() {
+                index.insert(Document::new(format!("doc{}", n), "", vector.clone())).unwrap();
+            }
+
+            let mut found = 0;
+            for query in &queries {
+                let hits = index.graph.search(query, k * 8, 128);
+                let hits = index.rescore(query, hits).unwrap();
+                let expected = exact(DistanceMetric::Cosine, &vectors, &HashSet::new(), query, k);
+                found += hits.iter().take(k).filter(|(node, _)| expected.contains(node)).count();
+
+                let (node, similarity) = hits[0];
+                let exact = dot(&normalized(query), &normalized(&vectors[node]));
+                assert!((similarity - exact).abs() < 1e-5);
+            }
+            let recall = found as f64 / (queries.len() * k) as f64;
+            assert!(recall > 0.85, "{:?} recall {}", quantization, recall);
+        }
+    }
+}
@@ -2,13 +2,26 @@
 //!
 //! This module provides functionality to:
 //! - Recursively collect code files from directories
-//! - Split large text into overlapping chunks
-//! - Filter files by extension and exclude patterns
+//! - Split large text into chunks with the strategy configured for its file
+//!   type (see [`chunking`](super::chunking))
+//! - Extract the text of PDF and DOCX files page by page (`documents` feature)
+//! - Filter files by extension, exclude patterns, and ignore files (`.gitignore`)
+//!
+//! Files are indexed the same on every platform: CRLF line endings are read
+//! as LF, so a checkout with either has the same content hash, chunks, and
+//! chunk IDs, and sources are recorded with `/` separators (see [`source_key`]).
 
+use super::chunking::{Chunker, Chunkers};
 use crate::config::IndexerConfig;
+use crate::text;
+use ignore::gitignore::GitignoreBuilder;
+use ignore::{Match, WalkBuilder};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use tokio::fs;
 use thiserror::Error;
+use tracing::warn;
 
 /// Errors that can occur during file indexing.
 #[derive(Debug, Error)]
@@ -31,28 +44,73 @@ pub type Result<T> = std::result::Result<T, IndexerError>;
 #[derive(Debug, Clone)]
 pub struct Indexer {
     config: IndexerConfig,
+    chunkers: Chunkers,
 }
 
 impl Indexer {
     /// Creates a new Indexer with the given configuration.
     pub fn new(config: IndexerConfig) -> Self {
-        Self { config }
+        let chunkers = Chunkers::new(&config);
+        Self { config, chunkers }
+    }
+
+    /// Chunks files with `extension` (without the dot) with `chunker`.
+    pub fn with_chunker(mut self, extension: impl Into<String>, chunker: impl Chunker + 'static) -> Self {
+        self.chunkers = self.chunkers.with_chunker(extension, chunker);
+        self
     }
 
     /// Collects all indexable files from the specified directory.
     ///
-    /// Walks the directory tree recursively, applying extension and exclude filters.
+    /// Walks the directory tree recursively, applying extension, exclude, and ignore file filters.
     pub async fn collect_files(&self, dir_path: impl AsRef<Path>) -> Result<Vec<IndexedFile>> {
         collect_files(dir_path, &self.config).await
     }
 
-    /// Chunks text according to the indexer's configuration.
-    ///
-    /// Splits text into overlapping chunks using the configured chunk_size and chunk_overlap.
+    /// Number of chunk batches embedded at the same time, at least 1.
+    pub fn concurrency(&self) -> usize {
+        self.config.concurrency.max(1)
+    }
+
+    /// Chunks text that isn't a file with the `indexer.chunking.default` strategy.
     pub fn chunk_text(&self, text: &str) -> Vec<String> {
-        chunk_text(text, self.config.chunk_size, self.config.chunk_overlap)
+        self.chunkers.chunk(text, None)
+    }
+
+    /// Chunks the contents of the file at `path` with the strategy
+    /// configured for its extension.
+    pub fn chunk_file(&self, path: &Path, text: &str) -> Vec<String> {
+        self.chunkers.chunk(text, Some(path))
+    }
+
+    /// Chunks the contents of the file at `path` like
+    /// [`chunk_file`](Self::chunk_file), with the page number (from 1) of
+    /// each chunk of a PDF or DOCX file.
+    ///
+    /// Document pages are chunked separately, so no chunk spans two pages.
+    pub fn chunk_pages(&self, path: &Path, text: &str) -> Vec<(String, Option<usize>)> {
+        if !is_document(path) {
+            return self.chunk_file(path, text).into_iter().map(|chunk| (chunk, None)).collect();
+        }
+        
+        text.split(PAGE_BREAK)
+            .enumerate()
+            .filter(|(_, page)| !page.trim().is_empty())
+            .flat_map(|(i, page)| self.chunk_file(path, page).into_iter().map(move |chunk| (chunk, Some(i + 1))))
+            .collect()
+    }
+    
+    /// Checks if `path` matches an exclude pattern or, with
+    /// `respect_gitignore`, is ignored by an ignore file.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        should_exclude(path, &self.config.exclude_patterns)
+            || (self.config.respect_gitignore && is_gitignored(path, path.is_dir()))
     }
 
+    /// Checks if a file at `path` would be collected by [`collect_files`](Self::collect_files).
+    pub fn accepts_file(&self, path: &Path) -> bool {
+        !self.is_excluded(path) && is_indexable(path, &self.config.extensions)
+    }
 }
 
 /// Splits text into overlapping chunks for better context preservation.
@@ -62,10 +120,13 @@ impl Indexer {
 /// - Overlapping chunks preserve context across boundaries
 /// - Smaller chunks produce more focused embeddings
 ///
-/// # UTF-8 Safety
+/// # Unicode Safety
 ///
-/// This function respects UTF-8 character boundaries by finding the nearest
-/// valid boundary when chunk sizes would split multi-byte characters.
+/// `chunk_size` and `overlap` are in bytes, but chunks start and end only on
+/// grapheme cluster boundaries (see [`crate::text`]), so neither multi-byte
+/// characters nor emoji sequences or accented letters are split. A cluster
+/// longer than `chunk_size` gets a chunk of its own, and an `overlap` of
+/// `chunk_size` or more is ignored.
 pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
     if text.is_empty() {
         eprintln!("WARNING: chunk_text called with empty text");
@@ -80,32 +141,20 @@ pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String>
     let mut start = 0;
     
     while start < text.len() {
-        let mut end = (start + chunk_size).min(text.len());
-        
-        // Find the nearest character boundary at or before 'end'
-        while end > start && !text.is_char_boundary(end) {
-            end -= 1;
-        }
-        
-        let chunk = text[start..end].to_string();
-        if chunk.is_empty() {
-            eprintln!("WARNING: Empty chunk created at start={}, end={}", start, end);
-        } else {
-            chunks.push(chunk);
+        let mut end = text::floor_boundary(text, start + chunk_size);
+        if end <= start {
+            end = text::ceil_boundary(text, start + 1);
         }
+        chunks.push(text[start..end].to_string());
         
         if end == text.len() {
             break;
         }
         
-        // Calculate next start position, ensuring it's on a char boundary
+        // Step back by the overlap, but always make progress
         let step = chunk_size.saturating_sub(overlap);
-        start += step;
-        
-        // Adjust start to nearest char boundary
-        while start < text.len() && !text.is_char_boundary(start) {
-            start += 1;
-        }
+        let next = text::floor_boundary(text, (start + step).min(end));
+        start = if next > start { next } else { end };
     }
     
     chunks
@@ -116,12 +165,132 @@ pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String>
 pub struct IndexedFile {
     pub path: PathBuf,
     pub content: String,
+    /// Modification time in seconds since the Unix epoch, 0 if unknown
+    pub modified: u64,
+}
+
+impl IndexedFile {
+    /// SHA-256 of the file content, stored with its chunks to skip unchanged files on re-index.
+    pub fn content_hash(&self) -> String {
+        content_hash(&self.content)
+    }
+}
+
+/// Separates pages in the text of documents read by [`read_file`].
+const PAGE_BREAK: char = '\x0c';
+
+/// Checks if `path` is a PDF or DOCX file, whose text is extracted rather than read.
+fn is_document(path: &Path) -> bool {
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some("pdf" | "docx"))
+}
+
+/// Reads the text of the file at `path`, with LF line endings.
+///
+/// With the `documents` feature, the text of PDF and DOCX files is
+/// extracted, with pages separated by form feeds; without it they fail to
+/// read like any other binary file.
+pub(crate) async fn read_file(path: &Path) -> std::io::Result<String> {
+    #[cfg(feature = "documents")]
+    if is_document(path) {
+        let path = path.to_path_buf();
+        let pages = tokio::task::spawn_blocking(move || super::document::extract_pages(&path))
+            .await
+            .map_err(std::io::Error::other)??;
+        return Ok(normalize_newlines(pages.join(&PAGE_BREAK.to_string())));
+    }
+    
+    fs::read_to_string(path).await.map(normalize_newlines)
+}
+
+/// Replaces CRLF (and lone CR) line endings in `text` with LF.
+pub(crate) fn normalize_newlines(text: String) -> String {
+    if !text.contains('\r') {
+        return text;
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// The source recorded for the file at `path`: its path with `/` separators.
+///
+/// Windows paths are stored like Unix ones (`C:/src/app/main.rs`, without the
+/// `\\?\` prefix of canonicalized paths), so stores that match sources by
+/// prefix, source trust rules, and filters treat them the same. Elsewhere a
+/// backslash is part of the file name and kept.
+pub(crate) fn source_key(path: &Path) -> String {
+    let source = path.to_string_lossy();
+    if cfg!(windows) {
+        windows_source_key(&source)
+    } else {
+        source.into_owned()
+    }
+}
+
+fn windows_source_key(path: &str) -> String {
+    let source = path.replace('\\', "/");
+    match source.strip_prefix("//?/") {
+        Some(unc) if unc.starts_with("UNC/") => format!("//{}", &unc["UNC/".len()..]),
+        Some(local) => local.to_string(),
+        None => source,
+    }
+}
+
+/// Hex-encoded SHA-256 of `content`.
+pub(crate) fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Stable IDs for the chunks of one source, derived from the source and
+/// each chunk's content rather than its position.
+///
+/// Re-indexing a changed file keeps the IDs of the chunks that did not
+/// change, so citations, feedback records, and exclusions referring to them
+/// stay valid. Repeated identical chunks are numbered by occurrence.
+pub(crate) struct ChunkIds<'a> {
+    source: &'a str,
+    seen: HashMap<String, usize>,
+}
+
+impl<'a> ChunkIds<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        Self { source, seen: HashMap::new() }
+    }
+
+    /// The ID of the next chunk, e.g. `src/main.rs#9f86d081884c7d65`.
+    pub(crate) fn next(&mut self, chunk: &str) -> String {
+        let mut hash = content_hash(chunk);
+        hash.truncate(16);
+        let occurrence = self.seen.entry(hash.clone()).or_insert(0);
+        *occurrence += 1;
+        match *occurrence {
+            1 => format!("{}#{}", self.source, hash),
+            n => format!("{}#{}.{}", self.source, hash, n - 1),
+        }
+    }
+}
+
+/// Modification time of `metadata` in seconds since the Unix epoch, 0 if unknown.
+pub(crate) fn modified_secs(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// The current time in seconds since the Unix epoch.
+pub(crate) fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
 }
 
 /// Recursively collects all indexable files from a directory.
 ///
 /// Walks the directory tree starting from `dir_path`, filtering files based on
-/// the provided configuration. Binary files and unreadable files are silently skipped.
+/// the provided configuration. Binary files and unreadable files are silently skipped;
+/// see [`read_file`] for PDF and DOCX files.
 ///
 /// # Filtering
 ///
@@ -130,44 +299,99 @@ pub struct IndexedFile {
 ///   If empty, all readable text files are indexed.
 /// - **Exclude patterns**: Directories or files matching patterns in `config.exclude_patterns`
 ///   are skipped (e.g., "node_modules", ".git").
+/// - **Ignore files**: With `config.respect_gitignore`, paths ignored by
+///   `.gitignore` and `.ignore` files (in the tree and its parents), the global
+///   gitignore, and `.git/info/exclude` are skipped, whether or not the tree is
+///   a git repository.
 ///
 /// This function is internal to the RAG system. Use [`Rag::index_directory`](crate::rag::Rag::index_directory)
 /// for public-facing directory indexing.
 pub(crate) async fn collect_files(dir_path: impl AsRef<Path>, config: &IndexerConfig) -> Result<Vec<IndexedFile>> {
+    let dir = dir_path.as_ref().to_path_buf();
+    
+    // A missing or unreadable root is an error rather than an empty walk
+    let _ = fs::read_dir(&dir).await?;
+    
+    let walk_config = config.clone();
+    let paths = tokio::task::spawn_blocking(move || walk(&dir, &walk_config))
+        .await
+        .map_err(std::io::Error::other)?;
+    
     let mut files = Vec::new();
-    collect_files_recursive(dir_path.as_ref(), &mut files, config).await?;
+    for path in paths {
+        if let Ok(content) = read_file(&path).await {
+            let modified = fs::metadata(&path).await.map(|metadata| modified_secs(&metadata)).unwrap_or(0);
+            files.push(IndexedFile {
+                path,
+                content,
+                modified,
+            });
+        }
+    }
+    
     Ok(files)
 }
 
-fn collect_files_recursive<'a>(
-    dir: &'a Path, 
-    files: &'a mut Vec<IndexedFile>,
-    config: &'a IndexerConfig,
-) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
-    Box::pin(async move {
-        let mut entries = fs::read_dir(dir).await?;
-        
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            
-            if should_exclude(&path, &config.exclude_patterns) {
-                continue;
+/// Paths of the indexable files under `dir`.
+fn walk(dir: &Path, config: &IndexerConfig) -> Vec<PathBuf> {
+    let patterns = config.exclude_patterns.clone();
+    let walker = WalkBuilder::new(dir)
+        .standard_filters(false)
+        .git_ignore(config.respect_gitignore)
+        .git_global(config.respect_gitignore)
+        .git_exclude(config.respect_gitignore)
+        .ignore(config.respect_gitignore)
+        .parents(config.respect_gitignore)
+        .require_git(false)
+        .follow_links(true)
+        .filter_entry(move |entry| !should_exclude(entry.path(), &patterns))
+        .build();
+    
+    walker
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Skipping during indexing: {}", e);
+                None
             }
-            
-            if path.is_dir() {
-                collect_files_recursive(&path, files, config).await?;
-            } else if is_indexable(&path, &config.extensions) {
-                if let Ok(content) = fs::read_to_string(&path).await {
-                    files.push(IndexedFile {
-                        path: path.clone(),
-                        content,
-                    });
+        })
+        .filter(|entry| entry.file_type().is_some_and(|file_type| file_type.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(|path| is_indexable(path, &config.extensions))
+        .collect()
+}
+
+/// Checks if ignore files in the ancestors of `path` exclude it.
+///
+/// Used for single paths (e.g. from the file watcher); the nearest ignore
+/// file with a matching rule decides, and `.ignore` rules take precedence
+/// over `.gitignore` ones in the same directory. The search stops at the
+/// repository root.
+fn is_gitignored(path: &Path, is_dir: bool) -> bool {
+    for dir in path.ancestors().skip(1).filter(|dir| !dir.as_os_str().is_empty()) {
+        let mut builder = GitignoreBuilder::new(dir);
+        for name in [".gitignore", ".ignore"] {
+            let file = dir.join(name);
+            if file.is_file() {
+                if let Some(e) = builder.add(&file) {
+                    warn!("Invalid ignore file {}: {}", file.display(), e);
                 }
             }
         }
         
-        Ok(())
-    })
+        if let Ok(ignore) = builder.build() {
+            match ignore.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    false
 }
 
 /// Checks if a file should be indexed based on its extension.
@@ -222,6 +446,118 @@ mod tests {
         assert_eq!(chunks[0], "0123456789");
         assert_eq!(chunks[1], "89ABCDEF");
     }
+
+    #[test]
+    fn test_chunk_text_keeps_graphemes_whole() {
+        let text = "日本語のテキスト👩\u{200D}💻 cafe\u{301} 🇯🇵🇫🇷".repeat(3);
+        let chunks = chunk_text(&text, 10, 0);
+        assert_eq!(chunks.concat(), text);
+        for chunk in &chunks {
+            assert!(chunk.len() <= 11, "{:?}", chunk);
+            assert!(!chunk.starts_with(['\u{200D}', '\u{301}', '💻']) && !chunk.ends_with('\u{200D}'), "{:?}", chunk);
+        }
+        // Flags are pairs of regional indicators
+        let regional_indicators = |chunk: &String| chunk.chars().filter(|c| ('🇦'..='🇿').contains(c)).count();
+        assert!(chunks.iter().all(|chunk| regional_indicators(chunk) % 2 == 0));
+
+        // Clusters longer than a chunk, and overlap not smaller than the chunk size
+        assert_eq!(chunk_text("👩\u{200D}💻👩\u{200D}💻", 3, 0), ["👩\u{200D}💻", "👩\u{200D}💻"]);
+        assert_eq!(chunk_text("abcdef", 2, 5), ["ab", "cd", "ef"]);
+    }
+
+    #[test]
+    fn test_chunk_pages() {
+        let indexer = Indexer::new(IndexerConfig {
+            chunk_size: 10,
+            chunk_overlap: 0,
+            ..IndexerConfig::default()
+        });
+
+        let chunks = indexer.chunk_pages(Path::new("spec.pdf"), "Overview\x0c\n\x0cRequirements");
+        assert_eq!(chunks, [
+            ("Overview".to_string(), Some(1)),
+            ("Requiremen".to_string(), Some(3)),
+            ("ts".to_string(), Some(3)),
+        ]);
+        assert_eq!(indexer.chunk_pages(Path::new("notes.txt"), "a\x0cb"), [("a\x0cb".to_string(), None)]);
+    }
+
+    #[tokio::test]
+    async fn test_collect_files_records_hash_and_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let config = IndexerConfig {
+            extensions: vec!["rs".to_string()],
+            exclude_patterns: Vec::new(),
+            ..IndexerConfig::default()
+        };
+        let files = collect_files(dir.path(), &config).await.unwrap();
+
+        assert_eq!(files.len(), 1);
+        assert!(files[0].modified > 0);
+        assert_eq!(files[0].content_hash(), content_hash("fn main() {}"));
+        assert_ne!(files[0].content_hash(), content_hash("fn main() { }"));
+        assert_eq!(files[0].content_hash().len(), 64);
+
+        // A CRLF checkout of the same file is indexed identically
+        std::fs::write(dir.path().join("main.rs"), "fn main() {\r\n}\r\n").unwrap();
+        let files = collect_files(dir.path(), &config).await.unwrap();
+        assert_eq!(files[0].content, "fn main() {\n}\n");
+    }
+
+    #[test]
+    fn test_windows_source_keys() {
+        assert_eq!(windows_source_key(r"C:\src\app\main.rs"), "C:/src/app/main.rs");
+        assert_eq!(windows_source_key(r"\\?\C:\src\app"), "C:/src/app");
+        assert_eq!(windows_source_key(r"\\?\UNC\server\share\notes.md"), "//server/share/notes.md");
+        assert_eq!(windows_source_key("/home/me/main.rs"), "/home/me/main.rs");
+        assert_eq!(source_key(Path::new("/src/app/main.rs")), "/src/app/main.rs");
+        assert_eq!(normalize_newlines("a\r\nb\rc\n".to_string()), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn test_chunk_ids_depend_on_content_not_position() {
+        let mut before = ChunkIds::new("src/lib.rs");
+        let before: Vec<String> = ["a", "b", "a"].into_iter().map(|chunk| before.next(chunk)).collect();
+        let mut after = ChunkIds::new("src/lib.rs");
+        let after: Vec<String> = ["new", "b", "a"].into_iter().map(|chunk| after.next(chunk)).collect();
+
+        assert!(before[0].starts_with("src/lib.rs#"));
+        assert_eq!(before[2], format!("{}.1", before[0]));
+        // Inserting a chunk keeps the IDs of the others
+        assert_eq!(after[1], before[1]);
+        assert_eq!(after[2], before[0]);
+        assert_ne!(ChunkIds::new("src/main.rs").next("b"), before[1]);
+    }
+    
+    #[tokio::test]
+    async fn test_collect_files_respects_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "generated/\n*.log\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("generated")).unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("generated/schema.rs"), "struct Schema;").unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.path().join("src/debug.log"), "trace").unwrap();
+
+        let mut config = IndexerConfig {
+            extensions: vec!["rs".to_string(), "log".to_string()],
+            exclude_patterns: Vec::new(),
+            ..IndexerConfig::default()
+        };
+        let files = collect_files(dir.path(), &config).await.unwrap();
+        let paths: Vec<_> = files.iter().map(|file| file.path.strip_prefix(dir.path()).unwrap()).collect();
+        assert_eq!(paths, vec![Path::new("src/main.rs")]);
+
+        let indexer = Indexer::new(config.clone());
+        assert!(indexer.is_excluded(&dir.path().join("generated/schema.rs")));
+        assert!(indexer.is_excluded(&dir.path().join("src/debug.log")));
+        assert!(!indexer.is_excluded(&dir.path().join("src/main.rs")));
+
+        config.respect_gitignore = false;
+        assert_eq!(collect_files(dir.path(), &config).await.unwrap().len(), 3);
+    }
     
     #[test]
     fn test_is_indexable() {
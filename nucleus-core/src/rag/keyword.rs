@@ -0,0 +1,335 @@
+//! BM25 keyword search over the knowledge base, for hybrid retrieval.
+//!
+//! Vector similarity finds chunks about the same topic but can miss exact
+//! identifiers: a query for `load_config` or `E0599` may rank a chunk that
+//! merely talks about configuration or errors above the one containing the
+//! name. [`KeywordStore`] keeps a BM25 index of the documents in a vector
+//! store, and [`fuse`] merges the keyword and vector rankings with reciprocal
+//! rank fusion, which only looks at positions so the two kinds of scores
+//! never have to be compared.
+//!
+//! The index lives in memory. It is built from the store on the first
+//! keyword search and kept up to date by the writes going through the
+//! wrapper, so a knowledge base that is never searched in hybrid mode costs
+//! nothing.
+
+use super::filter::SearchFilter;
+use super::store::{source_matches, VectorStore};
+use super::types::{Document, SearchResult};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Term frequency saturation.
+const K1: f32 = 1.2;
+
+/// How much scores are normalized by document length.
+const B: f32 = 0.75;
+
+/// Reciprocal rank fusion constant; higher values flatten the rank curve.
+const RRF_K: f32 = 60.0;
+
+/// Lowercased words and identifiers of at least two characters.
+fn tokens(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|token| token.chars().count() >= 2)
+        .map(str::to_lowercase)
+}
+
+/// An indexed document.
+struct Entry {
+    /// The document without its embedding
+    document: Document,
+    terms: HashMap<String, u32>,
+    length: usize,
+}
+
+/// Inverted index scoring documents with BM25.
+#[derive(Default)]
+pub(crate) struct KeywordIndex {
+    entries: HashMap<String, Entry>,
+    /// Documents containing each term
+    postings: HashMap<String, HashSet<String>>,
+    total_length: usize,
+}
+
+impl KeywordIndex {
+    /// Adds documents, replacing any with the same ID.
+    pub(crate) fn insert(&mut self, documents: impl IntoIterator<Item = Document>) {
+        for mut document in documents {
+            self.remove(&document.id);
+            document.embedding = Vec::new();
+
+            let mut terms = HashMap::new();
+            let mut length = 0;
+            for token in tokens(&document.content) {
+                *terms.entry(token).or_insert(0) += 1;
+                length += 1;
+            }
+            for term in terms.keys() {
+                self.postings.entry(term.clone()).or_default().insert(document.id.clone());
+            }
+            self.total_length += length;
+            self.entries.insert(document.id.clone(), Entry { document, terms, length });
+        }
+    }
+
+    /// Removes the document with `id`, if indexed.
+    fn remove(&mut self, id: &str) {
+        let Some(entry) = self.entries.remove(id) else {
+            return;
+        };
+        self.total_length -= entry.length;
+        for term in entry.terms.keys() {
+            if let Some(ids) = self.postings.get_mut(term) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    /// Removes documents whose source is `source_path` or lies under it.
+    pub(crate) fn remove_source(&mut self, source_path: &str) {
+        let ids: Vec<String> = self.entries.values()
+            .filter(|entry| {
+                let source = entry.document.metadata.get("source").map(String::as_str).unwrap_or_default();
+                source_matches(source, source_path)
+            })
+            .map(|entry| entry.document.id.clone())
+            .collect();
+        for id in ids {
+            self.remove(&id);
+        }
+    }
+
+    /// Returns the `top_k` best matches for `query` by BM25 score, among
+    /// the documents matching `filter`.
+    pub(crate) fn search(&self, query: &str, top_k: usize, filter: Option<&SearchFilter>) -> Vec<SearchResult> {
+        if self.entries.is_empty() {
+            return Vec::new();
+        }
+
+        let count = self.entries.len() as f32;
+        let average_length = (self.total_length as f32 / count).max(1.0);
+        let query_terms: HashSet<String> = tokens(query).collect();
+
+        let mut scores: HashMap<&str, f32> = HashMap::new();
+        for term in &query_terms {
+            let Some(ids) = self.postings.get(term) else {
+                continue;
+            };
+            let frequency = ids.len() as f32;
+            let idf = (1.0 + (count - frequency + 0.5) / (frequency + 0.5)).ln();
+
+            for id in ids {
+                let entry = &self.entries[id];
+                let tf = entry.terms[term] as f32;
+                let norm = K1 * (1.0 - B + B * entry.length as f32 / average_length);
+                *scores.entry(id).or_insert(0.0) += idf * tf * (K1 + 1.0) / (tf + norm);
+            }
+        }
+
+        let mut results: Vec<SearchResult> = scores.into_iter()
+            .filter(|(id, _)| filter.is_none_or(|filter| filter.matches(&self.entries[*id].document)))
+            .map(|(id, score)| SearchResult {
+                document: self.entries[id].document.clone(),
+                score,
+            })
+            .collect();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.document.id.cmp(&b.document.id)));
+        results.truncate(top_k);
+        results
+    }
+}
+
+/// Merges rankings with reciprocal rank fusion and keeps the best `top_k`.
+///
+/// A document scores `1 / (60 + rank)` in every ranking it appears in,
+/// scaled so a document ranked first everywhere scores 1. The fused score
+/// replaces each result's `score`.
+pub(crate) fn fuse(rankings: Vec<Vec<SearchResult>>, top_k: usize) -> Vec<SearchResult> {
+    let max_score = rankings.len() as f32 / (RRF_K + 1.0);
+    let mut fused: Vec<SearchResult> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for ranking in rankings {
+        for (rank, result) in ranking.into_iter().enumerate() {
+            let score = 1.0 / (RRF_K + rank as f32 + 1.0) / max_score;
+            match positions.get(&result.document.id) {
+                Some(&position) => fused[position].score += score,
+                None => {
+                    positions.insert(result.document.id.clone(), fused.len());
+                    fused.push(SearchResult { score, ..result });
+                }
+            }
+        }
+    }
+
+    fused.sort_by(|a, b| b.score.total_cmp(&a.score));
+    fused.truncate(top_k);
+    fused
+}
+
+/// Vector store keeping a [`KeywordIndex`] of its documents.
+///
+/// Writes go to the wrapped store and then to the index, once it is built.
+pub(crate) struct KeywordStore {
+    inner: Arc<dyn VectorStore>,
+    /// `None` until the first keyword search
+    index: RwLock<Option<KeywordIndex>>,
+}
+
+impl KeywordStore {
+    pub(crate) fn new(inner: Arc<dyn VectorStore>) -> Self {
+        Self {
+            inner,
+            index: RwLock::new(None),
+        }
+    }
+
+    /// Returns the `top_k` best keyword matches for `query` among the
+    /// documents matching `filter`, building the index from the wrapped
+    /// store first if needed.
+    pub(crate) async fn keyword_search(&self, query: &str, top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        if let Some(index) = self.index.read().await.as_ref() {
+            return Ok(index.search(query, top_k, filter));
+        }
+
+        // Writes wait for the lock, so none are missed while loading
+        let mut guard = self.index.write().await;
+        if guard.is_none() {
+            let mut index = KeywordIndex::default();
+            index.insert(self.inner.get_documents(None).await?);
+            tracing::debug!("Built keyword index of {} documents", index.entries.len());
+            *guard = Some(index);
+        }
+        Ok(guard.as_ref().map(|index| index.search(query, top_k, filter)).unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl VectorStore for KeywordStore {
+    async fn add(&self, documents: Vec<Document>) -> Result<()> {
+        let indexed: Vec<Document> = documents.iter()
+            .map(|document| Document {
+                embedding: Vec::new(),
+                ..document.clone()
+            })
+            .collect();
+        self.inner.add(documents).await?;
+        if let Some(index) = self.index.write().await.as_mut() {
+            index.insert(indexed);
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query_embedding: &[f32], top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        self.inner.search(query_embedding, top_k, filter).await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.inner.count().await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.inner.clear().await?;
+        if let Some(index) = self.index.write().await.as_mut() {
+            *index = KeywordIndex::default();
+        }
+        Ok(())
+    }
+
+    async fn get_indexed_paths(&self) -> Result<Vec<String>> {
+        self.inner.get_indexed_paths().await
+    }
+
+    async fn remove_by_source(&self, source_path: &str) -> Result<usize> {
+        let removed = self.inner.remove_by_source(source_path).await?;
+        if let Some(index) = self.index.write().await.as_mut() {
+            index.remove_source(source_path);
+        }
+        Ok(removed)
+    }
+
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>> {
+        self.inner.get_documents(source_path).await
+    }
+
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>> {
+        self.inner.source_hashes(source_path).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.inner.health_check().await
+    }
+
+    async fn disk_usage(&self) -> Result<Option<u64>> {
+        self.inner.disk_usage().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(id: &str, content: &str, source: &str) -> Document {
+        Document::new(id, content, vec![0.5]).with_metadata("source", source)
+    }
+
+    fn result(id: &str) -> SearchResult {
+        SearchResult {
+            document: Document::new(id, id, vec![]),
+            score: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_exact_identifier_ranks_first() {
+        let mut index = KeywordIndex::default();
+        index.insert([
+            document("a", "Configuration is read at startup from the config file", "notes.md"),
+            document("b", "fn load_config(path: &Path) -> Result<Config>", "src/config.rs"),
+            document("c", "error[E0599]: no method named `flush` found", "build.log"),
+        ]);
+
+        let results = index.search("where is load_config", 3, None);
+        assert_eq!(results[0].document.id, "b");
+        assert!(results[0].document.embedding.is_empty());
+        assert_eq!(index.search("E0599", 3, None)[0].document.id, "c");
+        assert!(index.search("unrelated words", 3, None).is_empty());
+
+        let in_src = SearchFilter::new().with_like("source", "src/%");
+        assert_eq!(index.search("config", 3, Some(&in_src))[0].document.id, "b");
+        assert_eq!(index.search("config", 3, Some(&in_src)).len(), 1);
+    }
+
+    #[test]
+    fn test_replace_and_remove_source() {
+        let mut index = KeywordIndex::default();
+        index.insert([document("a", "old_name", "src/lib.rs"), document("b", "other", "docs/a.md")]);
+        index.insert([document("a", "new_name", "src/lib.rs")]);
+        assert!(index.search("old_name", 5, None).is_empty());
+        assert_eq!(index.search("new_name", 5, None).len(), 1);
+
+        index.remove_source("src");
+        assert!(index.search("new_name", 5, None).is_empty());
+        assert_eq!(index.entries.len(), 1);
+        assert_eq!(index.total_length, 1);
+    }
+
+    #[test]
+    fn test_fuse_rewards_agreement() {
+        let vector = vec![result("a"), result("b"), result("c"), result("d")];
+        let keyword = vec![result("d")];
+
+        let fused = fuse(vec![vector, keyword], 3);
+        let ids: Vec<&str> = fused.iter().map(|result| result.document.id.as_str()).collect();
+        assert_eq!(ids, ["d", "a", "b"]);
+        assert!(fused[0].score <= 1.0);
+        assert_eq!(fuse(vec![vec![result("a")], vec![result("a")]], 1)[0].score, 1.0);
+    }
+}
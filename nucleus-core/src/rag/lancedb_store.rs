@@ -1,10 +1,17 @@
 //! LanceDB vector database storage implementation.
 //!
 //! This module provides integration with LanceDB for embedded, in-process vector storage.
+//! Each collection is a table in the configured directory. Documents are
+//! upserted by ID, so re-indexing replaces old versions, and searched by
+//! the collection's distance metric, scored like the Qdrant store. Tables
+//! have no vector index; every search compares the query with each row, so
+//! a collection can change its metric without re-indexing.
 
-use crate::config::StorageConfig;
+use crate::config::{DistanceMetric, StorageConfig};
 
-use super::store::VectorStore;
+use super::distance;
+use super::filter::{Condition, SearchFilter};
+use super::store::{dir_size, source_matches, VectorStore};
 use super::types::{Document, SearchResult};
 use anyhow::{Context, Result};
 use lancedb::arrow::arrow_schema::{DataType, Field, Schema};
@@ -14,18 +21,30 @@ use arrow_array::{
 };
 use futures::stream::TryStreamExt;
 use async_trait::async_trait;
-use lancedb::query::{ExecutableQuery, QueryBase};
-use lancedb::{connect, Connection, Table};
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use lancedb::table::NewColumnTransform;
+use lancedb::{connect, DistanceType, Table};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use tracing::debug;
+
+/// IDs deleted per delete statement.
+const DELETE_BATCH_SIZE: usize = 500;
 
 /// LanceDB-based vector store for embedded deployment.
 ///
 /// Provides zero-setup, in-process vector storage using LanceDB.
+///
+/// Rows hold the document ID, content, and embedding, the `source` metadata
+/// in a column of its own (for removal by path), and the remaining metadata
+/// as a JSON object.
 pub struct LanceDbStore {
-    storage_config: StorageConfig, 
-    conn: Connection,
     table: Table,
     vector_size: u64,
+    metric: DistanceMetric,
+    /// Directory of the table's files
+    dir: PathBuf,
 }
 
 #[async_trait]
@@ -35,91 +54,80 @@ impl VectorStore for LanceDbStore {
             return Ok(());
         }
 
+        // Merge inserts reject batches that match the same row twice; the last version wins
+        let mut seen = HashSet::new();
+        let mut documents: Vec<Document> = documents
+            .into_iter()
+            .rev()
+            .filter(|doc| seen.insert(doc.id.clone()))
+            .collect();
+        documents.reverse();
+
         let batch = self.create_record_batch(&documents)?;
         let schema_ref = batch.schema();
         let reader = RecordBatchIterator::new(vec![Ok(batch)], schema_ref);
-        
-        self.table
-            .add(reader)
-            .execute()
+
+        let mut merge = self.table.merge_insert(&["id"]);
+        merge.when_matched_update_all(None).when_not_matched_insert_all();
+        merge
+            .execute(Box::new(reader))
             .await
             .map_err(|e| anyhow::anyhow!("Failed to add documents to LanceDB: {:?}", e))?;
 
         Ok(())
     }
 
-    async fn search(&self, query_embedding: &[f32]) -> Result<Vec<SearchResult>> {
-        use tracing::{debug, info};
-        
-        debug!("LanceDB search: opening table '{}'", self.table.name());
-        let table = self.conn.open_table(self.table.name()).execute().await?;
-        
-        debug!("LanceDB search: querying with embedding of size {}, limit={}", 
-            query_embedding.len(), self.storage_config.top_k);
-        let results = table
-            .query()
-            .limit(self.storage_config.top_k)
+    async fn search(&self, query_embedding: &[f32], top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        if top_k == 0 {
+            return Ok(Vec::new());
+        }
+
+        debug!("LanceDB search: querying '{}' with embedding of size {}, limit={}",
+            self.table.name(), query_embedding.len(), top_k);
+        let mut query = self.table.query();
+        if let Some(filter) = filter.filter(|filter| !filter.is_empty()) {
+            // Filters apply before the nearest neighbours are picked
+            query = query.only_if(predicate(filter));
+        }
+        let batches: Vec<RecordBatch> = query
             .nearest_to(query_embedding)?
+            .distance_type(match self.metric {
+                DistanceMetric::Cosine => DistanceType::Cosine,
+                DistanceMetric::Dot => DistanceType::Dot,
+                DistanceMetric::Euclidean => DistanceType::L2,
+            })
+            .limit(top_k)
             .execute()
             .await
-            .context("Failed to execute LanceDB query")?;
-
-        let batches: Vec<RecordBatch> = results.try_collect().await
+            .context("Failed to execute LanceDB query")?
+            .try_collect()
+            .await
             .context("Failed to collect query results")?;
-        
-        debug!("LanceDB search: received {} batches", batches.len());
 
         let mut search_results = Vec::new();
-        
         for batch in batches {
-            let num_rows = batch.num_rows();
-            debug!("Processing batch with {} rows", num_rows);
-            
-            let id_col = batch.column_by_name("id")
-                .context("Missing 'id' column")?;
-            let content_col = batch.column_by_name("content")
-                .context("Missing 'content' column")?;
-            let source_col = batch.column_by_name("source")
-                .context("Missing 'source' column")?;
-            let distance_col = batch.column_by_name("_distance")
-                .context("Missing '_distance' column")?;
-            
-            let id_array = id_col.as_any().downcast_ref::<StringArray>()
-                .context("Failed to cast 'id' to StringArray")?;
-            let content_array = content_col.as_any().downcast_ref::<StringArray>()
-                .context("Failed to cast 'content' to StringArray")?;
-            let source_array = source_col.as_any().downcast_ref::<StringArray>()
-                .context("Failed to cast 'source' to StringArray")?;
-            let distance_array = distance_col.as_any().downcast_ref::<Float32Array>()
+            let distances = batch.column_by_name("_distance")
+                .context("Missing '_distance' column")?
+                .as_any()
+                .downcast_ref::<Float32Array>()
                 .context("Failed to cast '_distance' to Float32Array")?;
-            
-            for i in 0..num_rows {
-                let id = id_array.value(i).to_string();
-                let content = content_array.value(i).to_string();
-                let distance = distance_array.value(i);
-                
-                let mut metadata = std::collections::HashMap::new();
-                if !source_col.is_null(i) {
-                    metadata.insert("source".to_string(), source_array.value(i).to_string());
-                }
-                
-                let document = Document {
-                    id,
-                    content,
-                    embedding: vec![],
-                    metadata,
+
+            // Don't return embeddings in search results
+            for (i, document) in documents_from_batch(&batch, false)?.into_iter().enumerate() {
+                // Cosine and dot distances are 1 - similarity, L2 is squared
+                let score = match self.metric {
+                    DistanceMetric::Cosine | DistanceMetric::Dot => 1.0 - distances.value(i),
+                    DistanceMetric::Euclidean => distance::euclidean_score(distances.value(i)),
                 };
-                
-                let score = 1.0 - distance;
-                
-                search_results.push(SearchResult {
-                    document,
-                    score,
-                });
+                search_results.push(SearchResult { document, score });
             }
         }
-        
-        info!("LanceDB search complete: found {} results", search_results.len());
+
+        if let Some(filter) = filter {
+            search_results.retain(|result| filter.matches(&result.document));
+        }
+
+        debug!("LanceDB search complete: found {} results", search_results.len());
         Ok(search_results)
     }
 
@@ -129,103 +137,101 @@ impl VectorStore for LanceDbStore {
     }
 
     async fn clear(&self) -> Result<()> {
-        self.conn
-            .drop_table(self.table.name(), &[])
-            .await
-            .context("Failed to drop table")?;
-        
-        let schema = Self::create_schema(self.vector_size);
-        self.conn
-            .create_empty_table(self.table.name(), schema)
-            .execute()
+        // Deleting rows rather than dropping the table keeps this handle valid
+        self.table
+            .delete("id IS NOT NULL")
             .await
-            .context("Failed to recreate table")?;
-        
+            .context("Failed to clear LanceDB table")?;
+
         Ok(())
     }
 
     async fn get_indexed_paths(&self) -> Result<Vec<String>> {
-        use std::collections::HashSet;
-        
-        let table = self.conn.open_table(self.table.name()).execute().await?;
-        let results = table
-            .query()
-            .execute()
-            .await
-            .context("Failed to query all documents")?;
-        
-        let batches: Vec<RecordBatch> = results.try_collect().await
-            .context("Failed to collect query results")?;
-        
         let mut unique_paths = HashSet::new();
-        
-        for batch in batches {
-            let source_col = batch.column_by_name("source")
-                .context("Missing 'source' column")?;
-            let source_array = source_col.as_any().downcast_ref::<StringArray>()
-                .context("Failed to cast 'source' to StringArray")?;
-            
+
+        for batch in self.scan(Some(&["source"])).await? {
+            let source_array = string_column(&batch, "source")?;
             for i in 0..batch.num_rows() {
                 if !source_array.is_null(i) {
                     unique_paths.insert(source_array.value(i).to_string());
                 }
             }
         }
-        
+
         Ok(unique_paths.into_iter().collect())
     }
 
     async fn remove_by_source(&self, source_path: &str) -> Result<usize> {
-        use std::path::Path;
-        
-        let normalized_path = Path::new(source_path)
-            .to_string_lossy()
-            .replace("\\", "/");
-        
-        let table = self.conn.open_table(self.table.name()).execute().await?;
-        let results = table
-            .query()
-            .execute()
-            .await
-            .context("Failed to query all documents")?;
-        
-        let batches: Vec<RecordBatch> = results.try_collect().await
-            .context("Failed to collect query results")?;
-        
         let mut ids_to_delete = Vec::new();
-        
-        for batch in batches {
-            let id_col = batch.column_by_name("id")
-                .context("Missing 'id' column")?;
-            let source_col = batch.column_by_name("source")
-                .context("Missing 'source' column")?;
-            
-            let id_array = id_col.as_any().downcast_ref::<StringArray>()
-                .context("Failed to cast 'id' to StringArray")?;
-            let source_array = source_col.as_any().downcast_ref::<StringArray>()
-                .context("Failed to cast 'source' to StringArray")?;
-            
+
+        for batch in self.scan(Some(&["id", "source"])).await? {
+            let id_array = string_column(&batch, "id")?;
+            let source_array = string_column(&batch, "source")?;
+
             for i in 0..batch.num_rows() {
-                if !source_array.is_null(i) {
-                    let point_source = source_array.value(i).replace("\\", "/");
-                    if point_source == normalized_path || point_source.starts_with(&format!("{}/", normalized_path)) {
-                        ids_to_delete.push(id_array.value(i).to_string());
-                    }
+                if !source_array.is_null(i) && source_matches(source_array.value(i), source_path) {
+                    ids_to_delete.push(id_array.value(i).to_string());
                 }
             }
         }
-        
-        let count = ids_to_delete.len();
-        
-        if !ids_to_delete.is_empty() {
-            let delete_expr = format!("id IN ('{}')", ids_to_delete.join("', '"));
-            table
-                .delete(&delete_expr)
+
+        for ids in ids_to_delete.chunks(DELETE_BATCH_SIZE) {
+            let quoted: Vec<String> = ids.iter().map(|id| sql_string(id)).collect();
+            self.table
+                .delete(&format!("id IN ({})", quoted.join(", ")))
                 .await
                 .context("Failed to delete documents by source")?;
         }
-        
-        Ok(count)
+
+        Ok(ids_to_delete.len())
+    }
+
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>> {
+        let mut documents = Vec::new();
+
+        for batch in self.scan(None).await? {
+            for document in documents_from_batch(&batch, true)? {
+                if let Some(source_path) = source_path {
+                    let source = document.metadata.get("source");
+                    if !source.is_some_and(|s| source_matches(s, source_path)) {
+                        continue;
+                    }
+                }
+                documents.push(document);
+            }
+        }
+
+        Ok(documents)
+    }
+
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>> {
+        let mut hashes = Vec::new();
+
+        for batch in self.scan(Some(&["source", "metadata"])).await? {
+            let source_array = string_column(&batch, "source")?;
+            let metadata_array = string_column(&batch, "metadata")?;
+            for i in 0..batch.num_rows() {
+                if source_array.is_null(i) || !source_matches(source_array.value(i), source_path) {
+                    continue;
+                }
+                let hash = if metadata_array.is_null(i) {
+                    None
+                } else {
+                    let mut metadata: HashMap<String, String> = serde_json::from_str(metadata_array.value(i))
+                        .context("Invalid document metadata")?;
+                    metadata.remove("hash")
+                };
+                hashes.push((source_array.value(i).to_string(), hash));
+            }
+        }
+
+        Ok(hashes)
+    }
+
+    async fn disk_usage(&self) -> Result<Option<u64>> {
+        let dir = self.dir.clone();
+        let bytes = tokio::task::spawn_blocking(move || dir_size(&dir)).await?;
+        Ok(Some(bytes))
     }
 }
 
@@ -243,6 +249,7 @@ impl LanceDbStore {
                 false,
             ),
             Field::new("source", DataType::Utf8, true),
+            Field::new("metadata", DataType::Utf8, true),
         ]))
     }
 
@@ -266,6 +273,15 @@ impl LanceDbStore {
         let sources: Vec<Option<&str>> = documents.iter()
             .map(|doc| doc.metadata.get("source").map(|s| s.as_str()))
             .collect();
+        let metadata: Vec<Option<String>> = documents.iter()
+            .map(|doc| {
+                let rest: HashMap<&String, &String> = doc.metadata.iter()
+                    .filter(|(key, _)| key.as_str() != "source")
+                    .collect();
+                (!rest.is_empty()).then(|| serde_json::to_string(&rest)).transpose()
+            })
+            .collect::<Result<_, _>>()
+            .context("Failed to serialize document metadata")?;
 
         let all_vector_values: Vec<f32> = documents.iter()
             .flat_map(|doc| doc.embedding.iter().copied())
@@ -274,6 +290,7 @@ impl LanceDbStore {
         let id_array = StringArray::from(ids);
         let content_array = StringArray::from(contents);
         let source_array = StringArray::from(sources);
+        let metadata_array = StringArray::from(metadata);
 
         let vector_values = Float32Array::from(all_vector_values);
         let vector_array = FixedSizeListArray::new(
@@ -290,18 +307,48 @@ impl LanceDbStore {
                 Arc::new(content_array) as ArrayRef,
                 Arc::new(vector_array) as ArrayRef,
                 Arc::new(source_array) as ArrayRef,
+                Arc::new(metadata_array) as ArrayRef,
             ],
         )
         .context("Failed to create record batch")
     }
 
+    /// Reads every row, optionally only some columns.
+    async fn scan(&self, columns: Option<&[&str]>) -> Result<Vec<RecordBatch>> {
+        let rows = self.table.count_rows(None).await?;
+        if rows == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Plain queries are limited to a few rows unless told otherwise
+        let mut query = self.table.query().limit(rows);
+        if let Some(columns) = columns {
+            query = query.select(Select::columns(columns));
+        }
+
+        query
+            .execute()
+            .await
+            .context("Failed to query all documents")?
+            .try_collect()
+            .await
+            .context("Failed to collect query results")
+    }
+
     /// Creates a new LanceDB store and ensures the table exists.
     ///
+    /// Tables created before metadata was stored gain the column on open.
+    ///
     /// # Arguments
     ///
-    /// * `storage_config` - Storage configuration including collection name and top_k
+    /// * `storage_config` - Storage configuration including collection name
     /// * `path` - Directory path where LanceDB should store data
     /// * `vector_size` - Dimension of the embedding vectors
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened, or if the existing
+    /// table stores vectors of another dimension (the embedding model changed).
     pub async fn new(storage_config: StorageConfig, path: &str, vector_size: u64) -> Result<Self> {
         let conn = connect(path)
             .execute()
@@ -310,7 +357,7 @@ impl LanceDbStore {
 
         let table_names = conn.table_names().execute().await?;
         let collection_name = &storage_config.vector_db.collection_name;
-        
+
         let table = if table_names.contains(&collection_name.to_string()) {
             conn.open_table(collection_name)
                 .execute()
@@ -325,11 +372,206 @@ impl LanceDbStore {
                 .context("Failed to create LanceDB table")?
         };
 
+        let schema = table.schema().await.context("Failed to read LanceDB table schema")?;
+        if let Ok(field) = schema.field_with_name("vector") {
+            if let DataType::FixedSizeList(_, size) = field.data_type() {
+                if *size as u64 != vector_size {
+                    anyhow::bail!(
+                        "Collection '{}' stores {}-dimensional vectors but the embedding model produces {}; \
+                         clear it or use another collection",
+                        collection_name, size, vector_size
+                    );
+                }
+            }
+        }
+        if schema.field_with_name("metadata").is_err() {
+            table
+                .add_columns(
+                    NewColumnTransform::SqlExpressions(vec![(
+                        "metadata".to_string(),
+                        "CAST(NULL AS STRING)".to_string(),
+                    )]),
+                    None,
+                )
+                .await
+                .context("Failed to add the metadata column")?;
+        }
+
         Ok(Self {
-            storage_config,
-            conn,
             table,
             vector_size,
+            metric: storage_config.vector_db.metric(),
+            dir: Path::new(path).join(format!("{}.lance", collection_name)),
+        })
+    }
+}
+
+/// Returns a string column of a query result.
+fn string_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a StringArray> {
+    batch.column_by_name(name)
+        .with_context(|| format!("Missing '{}' column", name))?
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .with_context(|| format!("Failed to cast '{}' to StringArray", name))
+}
+
+/// Rebuilds the documents of a query result written by [`LanceDbStore::add`].
+fn documents_from_batch(batch: &RecordBatch, with_embedding: bool) -> Result<Vec<Document>> {
+    let id_array = string_column(batch, "id")?;
+    let content_array = string_column(batch, "content")?;
+    let source_array = string_column(batch, "source")?;
+    let metadata_array = string_column(batch, "metadata")?;
+    let vector_array = if with_embedding {
+        let vector_col = batch.column_by_name("vector")
+            .context("Missing 'vector' column")?;
+        Some(vector_col.as_any().downcast_ref::<FixedSizeListArray>()
+            .context("Failed to cast 'vector' to FixedSizeListArray")?)
+    } else {
+        None
+    };
+
+    let mut documents = Vec::with_capacity(batch.num_rows());
+    for i in 0..batch.num_rows() {
+        let mut metadata: HashMap<String, String> = if metadata_array.is_null(i) {
+            HashMap::new()
+        } else {
+            serde_json::from_str(metadata_array.value(i))
+                .context("Invalid document metadata")?
+        };
+        if !source_array.is_null(i) {
+            metadata.insert("source".to_string(), source_array.value(i).to_string());
+        }
+
+        let embedding = match vector_array {
+            Some(vector_array) => {
+                let row_vector = vector_array.value(i);
+                row_vector.as_any().downcast_ref::<Float32Array>()
+                    .context("Failed to cast vector values to Float32Array")?
+                    .values()
+                    .to_vec()
+            }
+            None => Vec::new(),
+        };
+
+        documents.push(Document {
+            id: id_array.value(i).to_string(),
+            content: content_array.value(i).to_string(),
+            embedding,
+            metadata,
+        });
+    }
+
+    Ok(documents)
+}
+
+/// Quotes a string literal for a LanceDB filter.
+fn sql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Translates a search filter to a LanceDB predicate.
+///
+/// Metadata other than `source` is a JSON object in the `metadata` column,
+/// matched with `LIKE` on its `"key":"value"` text. Characters that would
+/// need escaping there match any character instead, so the predicate can
+/// admit a few documents too many and results are checked against the
+/// filter afterwards.
+fn predicate(filter: &SearchFilter) -> String {
+    filter.conditions()
+        .iter()
+        .map(|condition| match condition {
+            Condition::Equals { key, value } if key == "source" => format!("source = {}", sql_string(value)),
+            Condition::Like { key, pattern } if key == "source" => format!("source LIKE {}", sql_string(pattern)),
+            Condition::Equals { key, value } => {
+                format!("metadata LIKE {}", sql_string(&format!("%{}:{}%", json_like(key, false), json_like(value, false))))
+            }
+            Condition::Like { key, pattern } => {
+                format!("metadata LIKE {}", sql_string(&format!("%{}:{}%", json_like(key, false), json_like(pattern, true))))
+            }
         })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// A `LIKE` pattern for `text` as a JSON string. Backslashes match any
+/// character, and so do `%` and `_` unless they are `wildcards`.
+fn json_like(text: &str, wildcards: bool) -> String {
+    serde_json::Value::from(text)
+        .to_string()
+        .chars()
+        .map(|c| match c {
+            '\\' => '_',
+            '%' | '_' if !wildcards => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(id: &str, content: &str, embedding: Vec<f32>, source: &str) -> Document {
+        Document::new(id, content, embedding).with_metadata("source", source)
+    }
+
+    #[tokio::test]
+    async fn test_lancedb_store_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage_config = StorageConfig::default();
+        storage_config.vector_db.collection_name = "test_collection".to_string();
+        let path = dir.path().to_string_lossy().to_string();
+        let store = LanceDbStore::new(storage_config.clone(), &path, 3).await.unwrap();
+
+        store.add(vec![
+            document("a_0", "alpha", vec![1.0, 0.0, 0.0], "/src/a.rs").with_metadata("chunk", "0"),
+            document("b_0", "beta", vec![0.0, 1.0, 0.0], "/src/b.rs"),
+            document("it's", "quoted", vec![0.0, 0.0, 1.0], "/other/it's.rs"),
+        ]).await.unwrap();
+        // Re-adding an ID replaces the document
+        store.add(vec![
+            document("a_0", "alpha v2", vec![1.0, 0.0, 0.0], "/src/a.rs").with_metadata("chunk", "0"),
+        ]).await.unwrap();
+        assert_eq!(store.count().await.unwrap(), 3);
+
+        let results = store.search(&[0.9, 0.1, 0.0], 2, None).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].document.content, "alpha v2");
+        assert_eq!(results[0].document.metadata.get("chunk").map(String::as_str), Some("0"));
+        assert_eq!(results[0].document.metadata.get("source").map(String::as_str), Some("/src/a.rs"));
+        assert!(results[0].score > results[1].score);
+        assert!(results[0].score > 0.9);
+
+        // Filters pick the neighbours among matching documents only
+        let other = SearchFilter::new().with_like("source", "/other/%");
+        let results = store.search(&[0.9, 0.1, 0.0], 1, Some(&other)).await.unwrap();
+        assert_eq!(results[0].document.content, "quoted");
+        let chunk = SearchFilter::new().with_equals("chunk", "0");
+        let results = store.search(&[0.0, 1.0, 0.0], 3, Some(&chunk)).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document.content, "alpha v2");
+
+        let mut paths = store.get_indexed_paths().await.unwrap();
+        paths.sort();
+        assert_eq!(paths, vec!["/other/it's.rs", "/src/a.rs", "/src/b.rs"]);
+        assert_eq!(store.get_documents(Some("/src")).await.unwrap().len(), 2);
+
+        assert_eq!(store.remove_by_source("/src").await.unwrap(), 2);
+        assert_eq!(store.remove_by_source("/other/it's.rs").await.unwrap(), 1);
+        assert_eq!(store.count().await.unwrap(), 0);
+
+        store.add(vec![document("c_0", "gamma", vec![0.0, 1.0, 0.0], "/c.rs")]).await.unwrap();
+        store.clear().await.unwrap();
+        assert_eq!(store.count().await.unwrap(), 0);
+        store.add(vec![document("c_0", "gamma", vec![0.0, 1.0, 0.0], "/c.rs")]).await.unwrap();
+
+        // Reopening keeps the documents and rejects another dimension
+        let reopened = LanceDbStore::new(storage_config.clone(), &path, 3).await.unwrap();
+        assert_eq!(reopened.count().await.unwrap(), 1);
+        storage_config.vector_db.distance = DistanceMetric::Dot;
+        let dot = LanceDbStore::new(storage_config.clone(), &path, 3).await.unwrap();
+        let results = dot.search(&[0.0, 2.0, 0.0], 1, None).await.unwrap();
+        assert!((results[0].score - 2.0).abs() < 1e-5);
+        assert!(LanceDbStore::new(storage_config, &path, 4).await.is_err());
     }
 }
@@ -18,45 +18,120 @@
 //!
 //! - [`Manager`]: Orchestrates the entire RAG pipeline
 //! - [`embedder`]: Converts text to vector embeddings via Ollama
+//! - `onnx`: Local ONNX embedding model for large indexing runs (`onnx` feature)
+//! - [`cache`]: Persistent embeddings by content hash, so unchanged chunks are never re-embedded
 //! - [`store`]: In-memory vector database with similarity search
+//! - [`hnsw`]: In-memory approximate nearest neighbour index for the embedded stores
+//! - [`distance`]: Distance metrics of the collections, checked against the embedding model
 //! - [`indexer`]: File collection and text chunking utilities
+//! - [`chunking`]: Chunking strategies (fixed-size, sentence, recursive, syntax, markdown) by file type
+//! - [`keyword`]: BM25 keyword index for hybrid search
+//! - [`filter`]: Metadata filters scoping a search to part of the index
+//! - [`trust`]: Trust levels of sources, weighting results and shown to the model
+//! - [`freshness`]: Sources that changed since they were indexed
+//! - [`read_through`]: Reading files mentioned in chat that are not indexed, or changed since
+//! - [`budget`]: Fitting retrieved context into the model's context window
+//! - [`collections`]: Named collections, separate knowledge bases such as one per project
+//! - [`pack`]: Export and import of shareable context packs
+//! - [`starter`]: Starter packs of general knowledge, installed into their own collections
+//! - [`trash`]: Removed documents, kept for a while so they can be restored
+//! - [`snapshot`]: Sources and content hashes at a point in time, to diff the knowledge base against
+//! - [`web`]: Fetching web pages and extracting their readable text
 //!
 //!
 //! # How It Works
 //!
 //! 1. **Indexing Phase**:
 //!    - Documents are split into chunks (default: 512 bytes with 50 byte overlap)
-//!    - Each chunk is converted to a vector embedding
+//!    - Each chunk is converted to a vector embedding (or taken from the cache)
 //!    - Embeddings are stored in the vector database
 //!
 //! 2. **Retrieval Phase**:
 //!    - User query is converted to a vector embedding  
 //!    - Vector database finds the top-k most similar document chunks
+//!    - With `rag.hybrid`, a BM25 keyword search runs alongside and the two
+//!      rankings are merged with reciprocal rank fusion
+//!    - With `rag.mmr_lambda`, near-duplicate chunks give way to ones that
+//!      cover something else
 //!    - Similar chunks are returned as context
 //!
 //! 3. **Generation Phase** (handled by chat manager):
 //!    - Context is added to the LLM prompt
 //!    - LLM generates response using the context
 
+mod cache;
+mod budget;
+mod chroma_store;
+pub mod chunking;
+#[cfg(feature = "documents")]
+mod document;
+mod collections;
+pub mod distance;
 mod embedder;
+mod filter;
+pub mod freshness;
+mod hnsw;
 mod indexer;
+mod keyword;
 mod lancedb_store;
+#[cfg(feature = "onnx")]
+mod onnx;
+mod pack;
+#[cfg(feature = "postgres")]
+mod pgvector_store;
 mod qdrant_store;
+pub mod read_through;
+mod rerank;
+pub mod snapshot;
+#[cfg(feature = "sqlite")]
+mod sqlite_store;
+pub mod starter;
 mod store;
+#[cfg(feature = "tree-sitter")]
+mod syntax;
+mod trash;
+pub mod trust;
 mod types;
 pub mod utils;
+mod web;
 
 #[allow(unused)]
-pub use types::{Document, SearchResult};
+pub use types::{Document, IndexProgress, RetrievedContext, SearchResult, Source, SourceStats};
+pub use filter::{Condition, SearchFilter};
+pub use freshness::{StaleSource, Staleness};
+pub use budget::{estimate_tokens, ContextBudget};
+pub(crate) use budget::CHARS_PER_TOKEN;
+pub(crate) use indexer::source_key;
+pub use chunking::{Chunker, Chunkers};
+pub use collections::{CollectionInfo, DEFAULT_COLLECTION};
+pub use pack::{ContextPack, PackError, PackPrompt};
+pub use snapshot::{KnowledgeDiff, SnapshotError, SnapshotSummary};
+pub use trash::{TrashEntry, TrashError, TrashOrigin};
+pub(crate) use indexer::chunk_text;
+pub(crate) use rerank::terms;
 
-use crate::config::Config;
+use crate::command_docs::{self, CommandDoc};
+use crate::conversations;
+use crate::dotfiles::{self, DotfileDoc};
+use crate::config::{BulkEmbeddingConfig, CollectionWeights, Config, ContextFormat, DistanceMetric, ReadThroughConfig};
 use crate::provider::Provider;
+use crate::warmup::Warmup;
+use futures::TryStreamExt;
 use embedder::Embedder;
 use indexer::Indexer;
-use store::{create_vector_store, VectorStore};
-use std::path::Path;
-use std::sync::Arc;
+use keyword::KeywordStore;
+use rerank::CrossEncoder;
+use trust::SourceTrust;
+use trash::Trash;
+use snapshot::{Snapshot, Snapshots};
+use collections::Collections;
+use store::{create_vector_store, DeferredStore, VectorStore};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Error)]
 pub enum RagError {
@@ -68,6 +143,35 @@ pub enum RagError {
     
     #[error("Failed to retrieve context: {0}")]
     Retrieval(String),
+    
+    #[error("Context pack error: {0}")]
+    Pack(#[from] pack::PackError),
+    
+    #[error("Failed to fetch {url}: {reason}")]
+    Fetch { url: String, reason: String },
+
+    #[error("Invalid search filter: {0}")]
+    Filter(String),
+
+    #[error("Collection error: {0}")]
+    Collection(String),
+
+    #[error("Trash error: {0}")]
+    Trash(#[from] trash::TrashError),
+
+    #[error("Snapshot error: {0}")]
+    Snapshot(#[from] snapshot::SnapshotError),
+    
+    #[error("No shared team knowledge base is configured")]
+    TeamNotConfigured,
+    
+    #[error("The shared team knowledge base is read-only")]
+    TeamReadOnly,
+
+    /// Indexing stopped at a cancellation request; `indexed` files were
+    /// stored completely and are kept.
+    #[error("Cancelled after indexing {indexed} files")]
+    Cancelled { indexed: usize },
 }
 
 pub type Result<T> = std::result::Result<T, RagError>;
@@ -94,7 +198,164 @@ pub type Result<T> = std::result::Result<T, RagError>;
 pub struct RagEngine {
     embedder: Embedder,
     store: Arc<dyn VectorStore>,
+    /// Keyword index of the local knowledge base, which `store` writes through
+    keywords: Option<Arc<KeywordStore>>,
+    /// Metric of `store`, which files read for a request are scored with too
+    distance: DistanceMetric,
     indexer: Indexer,
+    packs_path: PathBuf,
+    /// Where starter packs are downloaded from, see [`starter`]
+    starter_packs_url: String,
+    team: Option<TeamStore>,
+    /// Man pages and `--help` output, see [`crate::command_docs`]
+    commands: Arc<dyn VectorStore>,
+    command_top_k: usize,
+    /// Shell, editor, and tool configuration, see [`crate::dotfiles`]
+    dotfiles: Arc<dyn VectorStore>,
+    dotfile_top_k: usize,
+    /// Summaries of past conversations, see [`crate::conversations`]
+    conversations: Arc<dyn VectorStore>,
+    conversation_top_k: usize,
+    collection_weights: CollectionWeights,
+    /// Weight of file recency in the ranking, see [`rerank::boost_recent`]
+    recency_weight: f32,
+    recency_half_life_days: f32,
+    top_k: usize,
+    rerank: bool,
+    hybrid: bool,
+    cross_encoder: Option<CrossEncoder>,
+    /// Relevance against diversity, see [`rerank::diversify`]
+    mmr_lambda: Option<f32>,
+    /// Weights of trusted and untrusted sources (`rag.source_trust`)
+    trust: SourceTrust,
+    /// Room for context in the model's context window
+    budget: ContextBudget,
+    /// Layout of retrieved context, see [`format_context`]
+    context_format: ContextFormat,
+    /// Done once the stores have connected, see [`RagEngine::deferred`]
+    ready: Warmup<()>,
+    /// Named collections of the knowledge base, see [`RagEngine::collection`]
+    collections: Arc<Collections>,
+    /// Where removed documents go, unless `storage.trash` is disabled
+    trash: Option<Trash>,
+    /// The knowledge base `store` holds, as recorded in the trash
+    origin: TrashOrigin,
+    /// Snapshots of the sources in each collection, see [`RagEngine::snapshot`]
+    snapshots: Snapshots,
+    /// Local embedding of large indexing runs, see [`RagEngine::bulk_embedder`]
+    bulk_embedding: BulkEmbeddingConfig,
+    /// Files mentioned in chat, see [`RagEngine::read_through`]
+    read_through: ReadThroughConfig,
+}
+
+/// A chunk of a file or web page waiting to be embedded.
+struct PendingChunk {
+    id: String,
+    content: String,
+    source: String,
+    index: usize,
+    /// Page of a PDF or DOCX file the chunk is on, from 1
+    page: Option<usize>,
+    hash: String,
+    /// File modification time, seconds since the Unix epoch
+    modified: u64,
+}
+
+/// Per-request retrieval settings.
+///
+/// Defaults come from the configuration (`storage.top_k` and `rag.rerank`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetrievalOptions {
+    /// Number of results to return
+    pub top_k: usize,
+    /// Whether to rerank results by query term overlap
+    pub rerank: bool,
+    /// Whether to also search the shared team knowledge base, if configured
+    pub include_team: bool,
+    /// Whether to fuse BM25 keyword matches into the vector ranking
+    pub hybrid: bool,
+    /// Whether to rescore the best candidates with `rag.reranker_model`, if configured
+    pub cross_encoder: bool,
+    /// Whether to pick diverse results by maximal marginal relevance
+    /// (`rag.mmr_lambda`), if configured
+    pub diversify: bool,
+    /// Only search documents whose metadata matches this filter
+    pub filter: Option<SearchFilter>,
+}
+
+/// Collection a search result came from.
+///
+/// [`RagEngine::retrieve_all`] records it as `collection` metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collection {
+    /// Indexed files and added knowledge
+    Knowledge,
+    /// Shared team knowledge base
+    Team,
+    /// Man pages and `--help` output
+    Commands,
+    /// Shell, editor, and tool configuration files
+    Dotfiles,
+    /// Summaries of past conversations
+    Conversations,
+    /// Installed starter packs
+    Starter,
+}
+
+impl Collection {
+    pub const ALL: [Collection; 6] = [
+        Self::Knowledge,
+        Self::Team,
+        Self::Commands,
+        Self::Dotfiles,
+        Self::Conversations,
+        Self::Starter,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Knowledge => "knowledge",
+            Self::Team => "team",
+            Self::Commands => "commands",
+            Self::Dotfiles => "dotfiles",
+            Self::Conversations => "conversations",
+            Self::Starter => "starter",
+        }
+    }
+
+    /// Collection recorded in the metadata of `result`, if any.
+    pub fn of(result: &SearchResult) -> Option<Self> {
+        let name = result.document.metadata.get("collection")?;
+        Self::ALL.into_iter().find(|collection| collection.as_str() == name)
+    }
+
+    fn weight(&self, weights: &CollectionWeights) -> f32 {
+        match self {
+            Self::Knowledge => weights.knowledge,
+            Self::Team => weights.team,
+            Self::Commands => weights.commands,
+            Self::Dotfiles => weights.dotfiles,
+            Self::Conversations => weights.conversations,
+            Self::Starter => weights.starter,
+        }
+    }
+}
+
+/// The vector stores of each collection.
+struct Stores {
+    knowledge: Arc<dyn VectorStore>,
+    team: Option<Arc<dyn VectorStore>>,
+    commands: Arc<dyn VectorStore>,
+    dotfiles: Arc<dyn VectorStore>,
+    conversations: Arc<dyn VectorStore>,
+}
+
+/// Shared team knowledge base searched alongside the local store.
+#[derive(Clone)]
+struct TeamStore {
+    store: Arc<dyn VectorStore>,
+    namespace: String,
+    read_only: bool,
 }
 
 impl RagEngine {
@@ -112,25 +373,216 @@ impl RagEngine {
     /// # }
     /// ```
     pub async fn new(config: &Config, provider: Arc<dyn Provider>) -> Result<Self> {
-        let embedder = Embedder::new(provider, config.rag.embedding_model.clone());
-                
-        let store = create_vector_store(
-            config.storage.clone(),
+        let dim = config.rag.embedding_model.embedding_dim.try_into().unwrap_or_default();
+
+        let knowledge = create_vector_store(config.storage.clone(), dim, &config.resilience)
+            .await.map_err(|e| RagError::Retrieval(e.to_string()))?;
+
+        let team = match &config.team {
+            Some(team_config) => Some(
+                create_vector_store(team_config.storage_config(&config.storage), dim, &config.resilience)
+                    .await.map_err(|e| RagError::Retrieval(format!("Shared knowledge base: {}", e)))?
+            ),
+            None => None,
+        };
+
+        let commands = create_vector_store(config.commands.storage_config(&config.storage), dim, &config.resilience)
+            .await.map_err(|e| RagError::Retrieval(format!("Command documentation: {}", e)))?;
+
+        let dotfiles = create_vector_store(config.dotfiles.storage_config(&config.storage), dim, &config.resilience)
+            .await.map_err(|e| RagError::Retrieval(format!("Dotfiles: {}", e)))?;
+
+        let conversations = create_vector_store(config.conversations.storage_config(&config.storage), dim, &config.resilience)
+            .await.map_err(|e| RagError::Retrieval(format!("Conversations: {}", e)))?;
+
+        let stores = Stores { knowledge, team, commands, dotfiles, conversations };
+        Ok(Self::with_stores(config, provider, stores, Warmup::ready(())))
+    }
+
+    /// Creates a RAG manager that connects to its vector stores in the
+    /// background, so it is usable at once.
+    ///
+    /// Operations wait for the stores they use; [`is_ready`](Self::is_ready)
+    /// tells whether they would. Must be called from within a Tokio runtime.
+    pub fn deferred(config: &Config, provider: Arc<dyn Provider>) -> Self {
+        let dim = config.rag.embedding_model.embedding_dim.try_into().unwrap_or_default();
+        let spawn = |storage, name| DeferredStore::spawn(storage, dim, config.resilience.clone(), name);
+
+        let mut deferred = vec![spawn(config.storage.clone(), "Knowledge base")];
+        if let Some(team_config) = &config.team {
+            deferred.push(spawn(team_config.storage_config(&config.storage), "Shared knowledge base"));
+        }
+        deferred.push(spawn(config.commands.storage_config(&config.storage), "Command documentation"));
+        deferred.push(spawn(config.dotfiles.storage_config(&config.storage), "Dotfiles"));
+        deferred.push(spawn(config.conversations.storage_config(&config.storage), "Conversations"));
+
+        let warmups: Vec<_> = deferred.iter().map(|store| store.warmup().clone()).collect();
+        let ready = Warmup::spawn(async move {
+            for warmup in warmups {
+                warmup.get().await?;
+            }
+            Ok(())
+        });
+
+        let mut deferred = deferred.into_iter().map(|store| Arc::new(store) as Arc<dyn VectorStore>);
+        let mut next = || deferred.next().expect("a store for every collection");
+        let knowledge = next();
+        let team = config.team.as_ref().map(|_| next());
+        let stores = Stores { knowledge, team, commands: next(), dotfiles: next(), conversations: next() };
+        Self::with_stores(config, provider, stores, ready)
+    }
+
+    fn with_stores(config: &Config, provider: Arc<dyn Provider>, stores: Stores, ready: Warmup<()>) -> Self {
+        for problem in distance::check(&config.storage.vector_db, &config.rag.embedding_model) {
+            tracing::warn!("{}", problem);
+        }
+        let cross_encoder = config.rag.reranker_model.as_ref()
+            .map(|model| CrossEncoder::new(provider.clone(), model, config.rag.reranker_candidates));
+        let mut embedder = Embedder::new(provider, config.rag.embedding_model.clone());
+        if config.rag.cache_embeddings {
+            embedder = embedder.with_cache(&config.storage.embedding_cache_path);
+        }
+
+        let keywords = Arc::new(KeywordStore::new(stores.knowledge));
+        let collections = Collections::load(
+            &config.storage,
             config.rag.embedding_model.embedding_dim.try_into().unwrap_or_default(),
-        ).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
-        
+            &config.resilience,
+            keywords.clone(),
+        );
+
         let mut indexer_config = config.rag.indexer.clone();
-        
+
         indexer_config.chunk_size = config.rag.indexer.chunk_size;
         indexer_config.chunk_overlap = config.rag.indexer.chunk_overlap;
+        indexer_config.exclude_patterns.extend(config.rag.exclude_patterns.iter().cloned());
         let indexer = Indexer::new(indexer_config);
-        
-        Ok(Self {
-            embedder,
+
+        let team = stores.team.zip(config.team.as_ref()).map(|(store, team_config)| TeamStore {
             store,
+            namespace: team_config.namespace.clone(),
+            read_only: team_config.read_only,
+        });
+
+        Self {
+            embedder,
+            store: keywords.clone(),
+            keywords: Some(keywords),
+            distance: config.storage.vector_db.metric(),
             indexer,
+            packs_path: PathBuf::from(&config.storage.packs_path),
+            starter_packs_url: config.storage.starter_packs_url.clone(),
+            team,
+            commands: stores.commands,
+            command_top_k: config.commands.top_k,
+            dotfiles: stores.dotfiles,
+            dotfile_top_k: config.dotfiles.top_k,
+            conversations: stores.conversations,
+            conversation_top_k: config.conversations.top_k,
+            collection_weights: config.rag.collection_weights.clone(),
+            recency_weight: config.rag.recency_weight.clamp(0.0, 1.0),
+            recency_half_life_days: config.rag.recency_half_life_days,
+            top_k: config.storage.top_k,
+            rerank: config.rag.rerank,
+            hybrid: config.rag.hybrid,
+            cross_encoder,
+            mmr_lambda: config.rag.mmr_lambda.map(|lambda| lambda.clamp(0.0, 1.0)),
+            trust: SourceTrust::new(&config.rag.source_trust, &std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default()),
+            budget: ContextBudget::new(&config.llm),
+            context_format: config.rag.context_format_for(&config.llm.model),
+            ready,
+            collections: Arc::new(collections),
+            trash: config.storage.trash.enabled.then(|| Trash::new(&config.storage.trash)),
+            origin: TrashOrigin::Collection(DEFAULT_COLLECTION.to_string()),
+            snapshots: Snapshots::new(&config.storage.snapshots_path),
+            bulk_embedding: config.rag.bulk_embedding.clone(),
+            read_through: config.rag.read_through.clone(),
+        }
+    }
+
+    /// Chunks indexed files with `extension` (without the dot) with
+    /// `chunker` instead of the configured strategy.
+    pub fn with_chunker(mut self, extension: impl Into<String>, chunker: impl Chunker + 'static) -> Self {
+        self.indexer = self.indexer.with_chunker(extension, chunker);
+        self
+    }
+
+    /// The manager for collection `name`: the same one, searching and
+    /// indexing that collection's knowledge base instead.
+    ///
+    /// The team knowledge base and the command, dotfile, and conversation
+    /// collections are shared by every collection.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RagError::Collection`] if there is no such collection.
+    pub async fn collection(&self, name: &str) -> Result<RagEngine> {
+        let keywords = self.collections.store(name).await?;
+        Ok(Self {
+            store: keywords.clone(),
+            keywords: Some(keywords),
+            distance: self.collections.metric(name),
+            origin: TrashOrigin::Collection(name.to_string()),
+            ..self.clone()
         })
     }
+
+    /// The collection for a request naming `requested` (if any) from the
+    /// working directory `cwd`, see [`collections`].
+    pub fn resolve_collection(&self, requested: Option<&str>, cwd: Option<&Path>) -> String {
+        self.collections.resolve(requested, cwd)
+    }
+
+    /// Lists the collections, `default` first.
+    pub fn list_collections(&self) -> Vec<CollectionInfo> {
+        self.collections.list()
+    }
+
+    /// Creates an empty collection, used by requests from within `roots`
+    /// (project directories) unless they name another.
+    pub fn create_collection(&self, name: &str, roots: Vec<PathBuf>) -> Result<()> {
+        self.collections.create(name, roots)
+    }
+
+    /// Deletes a collection and all documents in it.
+    ///
+    /// The documents go to the trash, from which restoring recreates the
+    /// collection.
+    pub async fn delete_collection(&self, name: &str) -> Result<()> {
+        let roots = self.list_collections()
+            .into_iter()
+            .find(|collection| collection.name == name)
+            .map(|collection| collection.roots)
+            .unwrap_or_default();
+        let collection = match self.collection(name).await {
+            Ok(collection) if name != DEFAULT_COLLECTION => collection,
+            // Let the registry explain why it cannot be deleted
+            _ => return self.collections.delete(name).await,
+        };
+        collection.trashing(None, Some(roots), self.collections.delete(name)).await
+    }
+
+    /// Makes `name` the active collection, used by requests that neither
+    /// name one nor come from a collection's root.
+    pub fn switch_collection(&self, name: &str) -> Result<()> {
+        self.collections.switch(name)
+    }
+
+    /// Whether every vector store is connected (or failed to), so no
+    /// operation has to wait for one.
+    pub fn is_ready(&self) -> bool {
+        self.ready.is_done()
+    }
+
+    /// Waits until every vector store is connected.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RagError::Retrieval`] if a store failed to connect.
+    pub async fn ready(&self) -> Result<()> {
+        self.ready.get().await.map_err(RagError::Retrieval)
+    }
+
     /// Adds a single piece of text to the knowledge base.
     ///
     /// The text is embedded and stored as a single document. For large texts,
@@ -149,43 +601,45 @@ impl RagEngine {
     pub async fn add_knowledge(&self, content: &str, source: &str) -> Result<()> {
         let embedding = self.embedder.embed(content).await?;
         
-        let count = self.store.count().await.unwrap_or(0);
-        let id = format!("{}_{}", source, count);
+        // Adding the same text again replaces it
+        let id = indexer::ChunkIds::new(source).next(content);
         let document = Document::new(id, content, embedding)
-            .with_metadata("source", source);
+            .with_metadata("source", source)
+            .with_metadata("indexed", indexer::now_secs().to_string());
         
         self.store.add(vec![document]).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
         Ok(())
     }
     
-    async fn process_batch(
-        &self,
-        chunk_batch: &mut Vec<String>,
-        chunk_metadata: &mut Vec<(String, String, String, usize)>,
-    ) -> Result<()> {
-        use tracing::info;
-        
-        info!("Processing batch of {} chunks", chunk_batch.len());
-        let chunk_refs: Vec<&str> = chunk_batch.iter().map(|s| s.as_str()).collect();
+    /// Embeds a batch of chunks from [`index_directory`](Self::index_directory)
+    /// with `embedder` and stores them.
+    async fn process_batch(&self, batch: Vec<PendingChunk>, embedder: &Embedder) -> Result<()> {
+        use tracing::debug;
         
-        info!("Calling embed_batch for {} texts", chunk_refs.len());
-        let embeddings = self.embedder.embed_batch(&chunk_refs).await?;
-        info!("Received {} embeddings", embeddings.len());
+        let texts: Vec<&str> = batch.iter().map(|chunk| chunk.content.as_str()).collect();
+        let embeddings = embedder.embed_batch(&texts).await?;
+        debug!("Embedded batch of {} chunks", embeddings.len());
         
+        let indexed = indexer::now_secs().to_string();
         let documents: Vec<Document> = embeddings.into_iter()
-            .zip(chunk_metadata.drain(..))
-            .map(|(embedding, (id, content, source, chunk_idx))| {
-                Document::new(id, content, embedding)
-                    .with_metadata("source", source)
-                    .with_metadata("chunk", chunk_idx.to_string())
+            .zip(batch)
+            .map(|(embedding, chunk)| {
+                let mut document = Document::new(chunk.id, chunk.content, embedding)
+                    .with_metadata("chunk", chunk.index.to_string())
+                    .with_metadata("hash", chunk.hash)
+                    .with_metadata("mtime", chunk.modified.to_string())
+                    .with_metadata("indexed", indexed.as_str());
+                if let Some(page) = chunk.page {
+                    document = document.with_metadata("page", page.to_string());
+                }
+                if let Some(language) = language_of(&chunk.source) {
+                    document = document.with_metadata("language", language);
+                }
+                document.with_metadata("source", chunk.source)
             })
             .collect();
         
-        self.store.add(documents).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
-        
-        info!("Batch processed successfully");
-        chunk_batch.clear();
-        Ok(())
+        self.store.add(documents).await.map_err(|e| RagError::Retrieval(e.to_string()))
     }
     
     /// Recursively indexes all code files in a directory.
@@ -193,8 +647,13 @@ impl RagEngine {
     /// Walks the directory tree, collecting indexable files (see [`indexer`] for
     /// supported extensions). Each file is:
     /// 1. Read and split into chunks
-    /// 2. Each chunk is embedded
-    /// 3. Chunks are stored with file path and chunk index metadata
+    /// 2. Chunks are embedded in batches of 32, `rag.indexer.concurrency` batches at a time
+    ///    (or by a local model in larger batches, see [`bulk_embedder`](Self::bulk_embedder))
+    /// 3. Chunks are stored with file path, chunk index, content hash, and mtime metadata
+    ///
+    /// Re-indexing is incremental: files whose content hash matches the stored
+    /// one are skipped, changed files have their old chunks replaced, and
+    /// chunks of files no longer in the directory are removed.
     ///
     /// Progress is printed to stdout as files are indexed.
     ///
@@ -204,7 +663,7 @@ impl RagEngine {
     ///
     /// # Returns
     ///
-    /// The number of files successfully indexed.
+    /// The number of files (re-)indexed; unchanged files are not counted.
     ///
     /// # Errors
     ///
@@ -213,61 +672,279 @@ impl RagEngine {
     /// - Embedding generation fails for any chunk
     ///
     pub async fn index_directory(&self, dir_path: &Path) -> Result<usize> {
+        self.index_directory_until(dir_path, &CancellationToken::new()).await
+    }
+
+    /// Indexes a directory like [`index_directory`](Self::index_directory)
+    /// until `cancel` is cancelled.
+    ///
+    /// Batches already being embedded finish, and files whose chunks were
+    /// all stored are kept. Files that were only partly stored are removed
+    /// again (also when indexing fails), so the next run indexes them afresh
+    /// instead of taking them for up to date.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RagError::Cancelled`] with the number of files kept if
+    /// indexing was cancelled, and otherwise fails like `index_directory`.
+    pub async fn index_directory_until(&self, dir_path: &Path, cancel: &CancellationToken) -> Result<usize> {
+        self.index_directory_with(dir_path, cancel, |_| {}).await
+    }
+
+    /// Indexes a directory like [`index_directory_until`](Self::index_directory_until),
+    /// calling `on_progress` once the files have been read and chunked and
+    /// again after each embedded batch.
+    pub async fn index_directory_with<F>(&self, dir_path: &Path, cancel: &CancellationToken, on_progress: F) -> Result<usize>
+    where
+        F: Fn(&IndexProgress) + Sync,
+    {
         let files = self.indexer.collect_files(dir_path).await?;
+        let files_total = files.len();
         
         use tracing::{info, debug};
         info!("Found {} files to index", files.len());
         for file in &files {
             debug!(target: "nucleus_core::rag", file = %file.path.display(), "File queued for indexing");
         }
+        
+        let mut stored = self.stored_hashes(dir_path).await?;
         info!("Starting indexing...");
         
         let mut indexed_count = 0;
+        let mut unchanged_count = 0;
         
         const BATCH_SIZE: usize = 32;
-        let mut chunk_batch = Vec::new();
-        let mut chunk_metadata = Vec::new();
-        
+        let mut batches: Vec<Vec<PendingChunk>> = Vec::new();
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        // Chunks of each queued file
+        let mut chunk_counts: HashMap<String, usize> = HashMap::new();
+
         for file in files {
+            if cancel.is_cancelled() {
+                return Err(RagError::Cancelled { indexed: 0 });
+            }
+            let source = indexer::source_key(&file.path);
+            let stored_hash = stored.remove(&source);
+            
             if file.content.is_empty() {
                 eprintln!("WARNING: File has empty content: {}", file.path.display());
+                if stored_hash.is_some() {
+                    self.remove_source(&source).await?;
+                }
                 continue;
             }
             
-            let chunks = self.indexer.chunk_text(&file.content);
+            let hash = file.content_hash();
+            match stored_hash {
+                Some(Some(stored_hash)) if stored_hash == hash => {
+                    unchanged_count += 1;
+                    continue;
+                }
+                // Changed, or indexed before hashes were stored
+                Some(_) => self.remove_source(&source).await?,
+                None => {}
+            }
+            
+            let chunks = self.indexer.chunk_pages(&file.path, &file.content);
             
             if chunks.is_empty() {
                 eprintln!("WARNING: No chunks created for file: {}", file.path.display());
                 continue;
             }
             
-            for (i, chunk) in chunks.into_iter().enumerate() {
-                chunk_batch.push(chunk.clone());
-                chunk_metadata.push((
-                    format!("{}_chunk_{}", file.path.display(), i),
-                    chunk,
-                    file.path.to_string_lossy().to_string(),
-                    i,
-                ));
-                
-                // Process batch when it reaches BATCH_SIZE
-                if chunk_batch.len() >= BATCH_SIZE {
-                    self.process_batch(&mut chunk_batch, &mut chunk_metadata).await?;
+            chunk_counts.insert(source.clone(), chunks.len());
+            let mut ids = indexer::ChunkIds::new(&source);
+            for (i, (chunk, page)) in chunks.into_iter().enumerate() {
+                batch.push(PendingChunk {
+                    id: ids.next(&chunk),
+                    content: chunk,
+                    source: source.clone(),
+                    index: i,
+                    page,
+                    hash: hash.clone(),
+                    modified: file.modified,
+                });
+                if batch.len() >= BATCH_SIZE {
+                    batches.push(std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE)));
                 }
             }
             
             indexed_count += 1;
             println!("✓ Indexed: {}", file.path.display());
         }
+        if !batch.is_empty() {
+            batches.push(batch);
+        }
+        let chunks_total: usize = batches.iter().map(Vec::len).sum();
+        let bulk = self.bulk_embedder(chunks_total).await;
+        let embedder = bulk.as_ref().unwrap_or(&self.embedder);
+        if bulk.is_some() {
+            let batch_size = self.bulk_embedding.batch_size.max(1);
+            let mut chunks = batches.into_iter().flatten().peekable();
+            batches = Vec::new();
+            while chunks.peek().is_some() {
+                batches.push(chunks.by_ref().take(batch_size).collect());
+            }
+        }
+        // Unchanged and skipped files are done already
+        let files_done = AtomicUsize::new(files_total - chunk_counts.len());
+        on_progress(&IndexProgress {
+            files_done: files_done.load(Ordering::Relaxed),
+            files_total,
+            ..IndexProgress::default()
+        });
+        
+        // Embedding dominates indexing time, so keep several requests in flight
+        info!("Embedding {} batches, {} at a time", batches.len(), self.indexer.concurrency());
+        // Chunks left to store per file, and the files batches were started on
+        let progress = Mutex::new((chunk_counts, HashSet::new()));
+        let skipped = AtomicBool::new(false);
+        let embedded: Result<()> = futures::stream::iter(batches.into_iter().map(Ok))
+            .try_for_each_concurrent(self.indexer.concurrency(), |batch| {
+                let (progress, skipped, files_done, on_progress) = (&progress, &skipped, &files_done, &on_progress);
+                async move {
+                    // Batches in flight finish, so only whole batches are stored
+                    if cancel.is_cancelled() {
+                        skipped.store(true, Ordering::Relaxed);
+                        return Ok(());
+                    }
+                    let sources: Vec<String> = batch.iter().map(|chunk| chunk.source.clone()).collect();
+                    progress.lock().unwrap().1.extend(sources.iter().cloned());
+                    self.process_batch(batch, embedder).await?;
+                    let finished = {
+                        let remaining = &mut progress.lock().unwrap().0;
+                        let mut finished = Vec::new();
+                        for source in &sources {
+                            if let Some(count) = remaining.get_mut(source) {
+                                *count -= 1;
+                                if *count == 0 {
+                                    finished.push(source.clone());
+                                }
+                            }
+                        }
+                        finished
+                    };
+                    on_progress(&IndexProgress {
+                        files_done: files_done.fetch_add(finished.len(), Ordering::Relaxed) + finished.len(),
+                        files_total,
+                        current: sources.last().cloned(),
+                        finished,
+                    });
+                    Ok(())
+                }
+            })
+            .await;
+
+        let (remaining, started) = progress.into_inner().unwrap();
+        let skipped = skipped.into_inner();
+        if embedded.is_err() || skipped {
+            for source in started.iter().filter(|source| remaining[*source] > 0) {
+                self.remove_source(source).await?;
+                debug!(target: "nucleus_core::rag", file = %source, "Removed partly stored file");
+            }
+        }
+        embedded?;
+        if skipped {
+            let indexed = remaining.values().filter(|&&left| left == 0).count();
+            info!("Indexing cancelled after {} files", indexed);
+            return Err(RagError::Cancelled { indexed });
+        }
         
-        // Process remaining chunks
-        if !chunk_batch.is_empty() {
-            self.process_batch(&mut chunk_batch, &mut chunk_metadata).await?;
+        // Whatever is left was deleted or is now excluded
+        for source in stored.keys() {
+            self.remove_source(source).await?;
+            println!("✓ Removed: {}", source);
         }
         
+        info!("Indexed {} files, {} unchanged, {} removed", indexed_count, unchanged_count, stored.len());
         Ok(indexed_count)
     }
     
+    /// The embedder for an indexing run with `chunks` chunks to embed: a
+    /// local ONNX model if `rag.bulk_embedding` is enabled and the run is
+    /// large enough, loaded for this run only. Smaller runs, and runs the
+    /// model fails to load for, use the provider.
+    async fn bulk_embedder(&self, chunks: usize) -> Option<Embedder> {
+        use tracing::warn;
+        
+        let config = &self.bulk_embedding;
+        if !config.enabled || chunks < config.min_chunks {
+            return None;
+        }
+        #[cfg(feature = "onnx")]
+        {
+            let (config, dim) = (config.clone(), self.embedder.model().embedding_dim);
+            let path = config.model_path.clone();
+            match tokio::task::spawn_blocking(move || onnx::OnnxEmbedder::load(&config, dim)).await {
+                Ok(Ok(model)) => {
+                    tracing::info!("Embedding {} chunks locally with {}", chunks, path);
+                    return Some(self.embedder.with_onnx(model));
+                }
+                Ok(Err(e)) => warn!("Failed to load {}, embedding through the provider: {}", path, e),
+                Err(e) => warn!("Failed to load {}, embedding through the provider: {}", path, e),
+            }
+            None
+        }
+        #[cfg(not(feature = "onnx"))]
+        {
+            warn!("rag.bulk_embedding requires building with the `onnx` feature; embedding {} chunks through the provider", chunks);
+            None
+        }
+    }
+    
+    /// Content hash stored for each source under `dir_path` (`None` for
+    /// sources indexed before hashes were recorded).
+    async fn stored_hashes(&self, dir_path: &Path) -> Result<HashMap<String, Option<String>>> {
+        let chunks = self.store
+            .source_hashes(&indexer::source_key(dir_path))
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        
+        let mut hashes = HashMap::new();
+        for (source, hash) in chunks {
+            // Chunks disagreeing on the hash mean a partial update; re-index the file
+            hashes
+                .entry(source)
+                .and_modify(|stored: &mut Option<String>| {
+                    if *stored != hash {
+                        *stored = None;
+                    }
+                })
+                .or_insert(hash);
+        }
+        Ok(hashes)
+    }
+    
+    async fn remove_source(&self, source: &str) -> Result<()> {
+        self.store.remove_by_source(source).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
+        Ok(())
+    }
+    
+    /// Records that the unchanged `source` was checked now, with `modified`
+    /// as its modification time if set, so it no longer counts as stale.
+    async fn mark_fresh(&self, source: &str, modified: Option<u64>) -> Result<()> {
+        let documents = self.store
+            .get_documents(Some(source))
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        let indexed = indexer::now_secs().to_string();
+        let documents: Vec<Document> = documents
+            .into_iter()
+            .filter(|document| document.metadata.get("source").map(String::as_str) == Some(source))
+            .map(|mut document| {
+                document.metadata.insert("indexed".to_string(), indexed.clone());
+                if let Some(modified) = modified {
+                    document.metadata.insert("mtime".to_string(), modified.to_string());
+                }
+                document
+            })
+            .collect();
+        if !documents.is_empty() {
+            self.store.add(documents).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
+        }
+        Ok(())
+    }
+    
     /// Indexes multiple directories in batch.
     ///
     /// This is a convenience method for indexing multiple directories at once.
@@ -331,19 +1008,34 @@ impl RagEngine {
     pub async fn index_file(&self, file_path: &str) -> Result<usize> {
         use tokio::fs;
         
-        let content = fs::read_to_string(file_path).await
+        let content = indexer::read_file(Path::new(file_path)).await
             .map_err(|e| RagError::Indexer(indexer::IndexerError::Io(e)))?;
         
-        let chunks = self.indexer.chunk_text(&content);
+        let modified = fs::metadata(file_path).await.map(|metadata| indexer::modified_secs(&metadata)).unwrap_or(0);
+        let hash = indexer::content_hash(&content);
+        let indexed = indexer::now_secs().to_string();
+        
+        let chunks = self.indexer.chunk_pages(Path::new(file_path), &content);
         let chunk_count = chunks.len();
+        let source = indexer::source_key(Path::new(file_path));
+        let mut ids = indexer::ChunkIds::new(&source);
         
-        for (i, chunk) in chunks.into_iter().enumerate() {
+        for (i, (chunk, page)) in chunks.into_iter().enumerate() {
             let embedding = self.embedder.embed(&chunk).await?;
             
-            let id = format!("{}_chunk_{}", file_path, i);
-            let document = Document::new(id, chunk, embedding)
-                .with_metadata("source", file_path)
-                .with_metadata("chunk", i.to_string());
+            let id = ids.next(&chunk);
+            let mut document = Document::new(id, chunk, embedding)
+                .with_metadata("source", source.as_str())
+                .with_metadata("chunk", i.to_string())
+                .with_metadata("hash", hash.as_str())
+                .with_metadata("mtime", modified.to_string())
+                .with_metadata("indexed", indexed.as_str());
+            if let Some(page) = page {
+                document = document.with_metadata("page", page.to_string());
+            }
+            if let Some(language) = language_of(file_path) {
+                document = document.with_metadata("language", language);
+            }
             
             self.store.add(vec![document]).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
         }
@@ -352,114 +1044,707 @@ impl RagEngine {
         Ok(chunk_count)
     }
     
-    /// Retrieves relevant context from the knowledge base for a query.
+    /// Fetches a web page and indexes its text, with the URL as source.
     ///
-    /// Converts the query to an embedding, searches for the top-k most similar
-    /// documents, and formats them as context that can be added to an LLM prompt.
-    ///
-    /// # Arguments
-    ///
-    /// * `query` - The question or text to find relevant context for
+    /// HTML pages are reduced to their title and main content, dropping
+    /// navigation, scripts, and other boilerplate; other text responses are
+    /// indexed as they are. Re-indexing a URL whose text did not change only
+    /// records when it was fetched, otherwise its chunks are replaced. As with directories,
+    /// replacing them also removes the chunks of pages under the URL (those
+    /// of `…/docs/setup` when re-indexing `…/docs`).
     ///
     /// # Returns
     ///
-    /// A formatted string containing the most relevant document chunks, or an
-    /// empty string if the knowledge base is empty or no relevant documents exist.
-    ///
-    /// The format is:
-    /// ```text
-    /// 
-    /// Relevant context from your knowledge base:
-    ///
-    /// [1] <first most relevant chunk>
-    /// [2] <second most relevant chunk>
-    /// ...
-    /// ```
+    /// The number of chunks stored, 0 if the page was unchanged.
     ///
     /// # Errors
     ///
-    /// Returns an error if embedding generation fails.
-    ///
-    pub async fn retrieve_context(&self, query: &str) -> Result<String> {
-        use tracing::{debug, info};
+    /// Returns [`RagError::Fetch`] if the page cannot be fetched, is not
+    /// text, or has no text, or an error if embedding fails.
+    pub async fn index_url(&self, url: &str) -> Result<usize> {
+        use tracing::info;
         
-        let count = self.store.count().await.unwrap_or(0);
-        debug!("Knowledge base count: {}", count);
-        if count == 0 {
-            debug!("Knowledge base is empty, returning empty context");
-            return Ok(String::new());
+        let fetch_error = |reason: String| RagError::Fetch { url: url.to_string(), reason };
+        let text = indexer::normalize_newlines(web::fetch_text(url).await.map_err(fetch_error)?);
+        if text.trim().is_empty() {
+            return Err(fetch_error("the page has no text".to_string()));
         }
         
-        debug!("Generating query embedding for: {}", query);
-        let query_embedding = self.embedder.embed(query).await?;
-        debug!("Query embedding generated, dimension: {}", query_embedding.len());
+        let hash = indexer::content_hash(&text);
+        let stored_hash = self.stored_hashes(Path::new(url)).await?.remove(url);
+        if stored_hash.as_ref().and_then(|stored| stored.as_deref()) == Some(hash.as_str()) {
+            self.mark_fresh(url, None).await?;
+            return Ok(0);
+        }
+        if stored_hash.is_some() {
+            self.remove_source(url).await?;
+        }
         
-        debug!("Searching vector store...");
-        let results = self.store.search(&query_embedding)
-            .await
-            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        let modified = indexer::now_secs();
+        let mut ids = indexer::ChunkIds::new(url);
+        let chunks: Vec<PendingChunk> = self.indexer.chunk_text(&text)
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| PendingChunk {
+                id: ids.next(&chunk),
+                content: chunk,
+                source: url.to_string(),
+                index: i,
+                page: None,
+                hash: hash.clone(),
+                modified,
+            })
+            .collect();
+        let count = chunks.len();
         
-        info!("Found {} results from RAG search", results.len());
+        let mut batches = Vec::new();
+        let mut chunks = chunks.into_iter().peekable();
+        while chunks.peek().is_some() {
+            batches.push(chunks.by_ref().take(32).collect::<Vec<_>>());
+        }
+        futures::stream::iter(batches.into_iter().map(Ok))
+            .try_for_each_concurrent(self.indexer.concurrency(), |batch| self.process_batch(batch, &self.embedder))
+            .await?;
+        
+        info!("Indexed {} ({} chunks)", url, count);
+        Ok(count)
+    }
+    
+    /// Brings the knowledge base up to date with `path` after it changed on disk.
+    ///
+    /// Files are re-indexed if their content hash changed, directories (e.g.
+    /// moved into an indexed tree) are indexed incrementally, and paths that
+    /// no longer exist are removed. Excluded and non-indexable paths are
+    /// ignored.
+    ///
+    /// # Returns
+    ///
+    /// Whether the knowledge base changed.
+    pub async fn refresh_path(&self, path: &Path) -> Result<bool> {
+        if self.indexer.is_excluded(path) {
+            return Ok(false);
+        }
+        
+        let source = indexer::source_key(path);
+        let metadata = match tokio::fs::metadata(path).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let removed = self.store.remove_by_source(&source).await
+                    .map_err(|e| RagError::Retrieval(e.to_string()))?;
+                return Ok(removed > 0);
+            }
+            Err(e) => return Err(RagError::Indexer(indexer::IndexerError::Io(e))),
+        };
         
-        if results.is_empty() {
-            debug!("No results found, returning empty context");
-            return Ok(String::new());
+        if metadata.is_dir() {
+            return Ok(self.index_directory(path).await? > 0);
+        }
+        if !self.indexer.accepts_file(path) {
+            return Ok(false);
         }
         
-        let mut context = String::from("\n\nRelevant context from your knowledge base:\n");
+        let stored_hash = self.stored_hashes(path).await?.remove(&source);
+        let content = match indexer::read_file(path).await {
+            Ok(content) if !content.is_empty() => content,
+            // Emptied, or no longer readable as text
+            _ => {
+                if stored_hash.is_some() {
+                    self.remove_source(&source).await?;
+                }
+                return Ok(stored_hash.is_some());
+            }
+        };
         
-        for (i, result) in results.iter().enumerate() {
-            debug!("Result {}: score={}, source={:?}", 
-                i + 1, 
-                result.score, 
-                result.document.metadata.get("source"));
-            context.push_str(&format!("\n[{}] {}\n", i + 1, result.document.content));
+        let hash = indexer::content_hash(&content);
+        let modified = indexer::modified_secs(&metadata);
+        if stored_hash.as_ref().and_then(|stored| stored.as_deref()) == Some(hash.as_str()) {
+            // Touched but unchanged; no longer stale
+            self.mark_fresh(&source, Some(modified)).await?;
+            return Ok(false);
+        }
+        if stored_hash.is_some() {
+            self.remove_source(&source).await?;
         }
         
-        info!("Generated context with {} results", results.len());
-        Ok(context)
+        let mut ids = indexer::ChunkIds::new(&source);
+        let batch: Vec<PendingChunk> = self.indexer.chunk_pages(path, &content)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (chunk, page))| PendingChunk {
+                id: ids.next(&chunk),
+                content: chunk,
+                source: source.clone(),
+                index: i,
+                page,
+                hash: hash.clone(),
+                modified,
+            })
+            .collect();
+        if !batch.is_empty() {
+            self.process_batch(batch, &self.embedder).await?;
+        }
+        Ok(true)
     }
     
-    /// Returns the total number of documents (chunks) in the knowledge base.
+    /// Brings a source reported by [`freshness::stale_sources`] up to date:
+    /// web pages are fetched and indexed again, files refreshed like
+    /// [`refresh_path`](Self::refresh_path).
     ///
-    /// Note: each indexed file is split into multiple chunks, so this represents
-    /// chunk count, not file count.
-    pub async fn count(&self) -> usize {
-        self.store.count().await.unwrap_or(0)
-    }
-    
-    /// Removes all documents from the knowledge base.
-    pub async fn clear(&self) -> Result<()> {
-        self.store.clear().await.map_err(|e| RagError::Retrieval(e.to_string()))?;
-        Ok(())
+    /// # Returns
+    ///
+    /// Whether the source's content changed.
+    pub async fn refresh_source(&self, source: &str) -> Result<bool> {
+        if freshness::is_url(source) {
+            Ok(self.index_url(source).await? > 0)
+        } else {
+            self.refresh_path(Path::new(source)).await
+        }
     }
     
-    /// Returns all unique file paths that have been indexed in the knowledge base.
+    /// Reads the files mentioned in `query` that are not indexed, or changed
+    /// since they were, see [`read_through`].
     ///
-    /// This method queries Qdrant to retrieve all unique source file paths
-    /// from indexed documents. Useful for displaying indexing status in UIs.
-    pub async fn get_indexed_paths(&self) -> Result<Vec<String>> {
-        self.store.get_indexed_paths().await
-            .map_err(|e| RagError::Retrieval(e.to_string()))
-    }
-
-    /// Removes documents from the knowledge base by source path.
+    /// Relative paths are resolved against `cwd`. Up to
+    /// `rag.read_through.max_files` files are read; each is chunked like
+    /// for indexing and its `max_chunks` chunks most similar to the query
+    /// are returned in file order. Nothing is stored: with
+    /// `rag.read_through.persist`, the caller indexes the files with
+    /// [`refresh_path`](Self::refresh_path) when it may write.
     ///
-    /// This method removes all documents that match the given source path.
-    /// If the path is a directory, all files within that directory are removed.
-    /// If the path is a file, only that specific file is removed.
+    /// # Returns
     ///
-    /// # Arguments
+    /// The chunks, scored by similarity to the query; empty if
+    /// `rag.read_through.enabled` is off or every mentioned file is current.
+    pub async fn read_through(&self, query: &str, cwd: Option<&Path>) -> Result<Vec<SearchResult>> {
+        use tracing::debug;
+
+        if !self.read_through.enabled {
+            return Ok(Vec::new());
+        }
+        let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+        let mut query_embedding = None;
+        let mut results = Vec::new();
+        let mut files = 0;
+        for path in read_through::mentioned_paths(query, cwd, &home) {
+            if files >= self.read_through.max_files {
+                break;
+            }
+            let Ok(metadata) = tokio::fs::metadata(&path).await else {
+                continue;
+            };
+            if !metadata.is_file()
+                || metadata.len() > self.read_through.max_file_bytes
+                || self.indexer.is_excluded(&path)
+                || !self.indexer.accepts_file(&path)
+            {
+                continue;
+            }
+            let content = match indexer::read_file(&path).await {
+                Ok(content) if !content.is_empty() => content,
+                _ => continue,
+            };
+            files += 1;
+
+            let source = indexer::source_key(&path);
+            let hash = indexer::content_hash(&content);
+            let stored_hash = self.stored_hashes(&path).await?.remove(&source).flatten();
+            if stored_hash.as_deref() == Some(hash.as_str()) {
+                // Indexed and current; retrieval covers it
+                continue;
+            }
+            debug!("Reading {} for the request ({})", source, if stored_hash.is_some() { "changed" } else { "not indexed" });
+
+            let chunks = self.indexer.chunk_pages(&path, &content);
+            let texts: Vec<&str> = chunks.iter().map(|(chunk, _)| chunk.as_str()).collect();
+            let embeddings = self.embedder.embed_batch(&texts).await?;
+            let query_embedding = match &query_embedding {
+                Some(embedding) => embedding,
+                None => query_embedding.insert(self.embedder.embed(query).await?),
+            };
+
+            let modified = indexer::modified_secs(&metadata);
+            let mut ids = indexer::ChunkIds::new(&source);
+            let mut scored: Vec<(usize, SearchResult)> = chunks
+                .into_iter()
+                .zip(embeddings)
+                .enumerate()
+                .map(|(i, ((chunk, page), embedding))| {
+                    let score = distance::similarity(self.distance, query_embedding, &embedding);
+                    let mut document = Document::new(ids.next(&chunk), chunk, embedding)
+                        .with_metadata("chunk", i.to_string())
+                        .with_metadata("hash", hash.as_str())
+                        .with_metadata("mtime", modified.to_string());
+                    if let Some(page) = page {
+                        document = document.with_metadata("page", page.to_string());
+                    }
+                    if let Some(language) = language_of(&source) {
+                        document = document.with_metadata("language", language);
+                    }
+                    (i, SearchResult { document: document.with_metadata("source", source.as_str()), score })
+                })
+                .collect();
+            scored.sort_by(|(_, a), (_, b)| b.score.total_cmp(&a.score));
+            scored.truncate(self.read_through.max_chunks);
+            scored.sort_by_key(|(i, _)| *i);
+            results.extend(scored.into_iter().map(|(_, result)| result));
+        }
+        Ok(results)
+    }
+    
+    /// Whether files read by [`read_through`](Self::read_through) should be indexed.
+    pub fn persists_read_through(&self) -> bool {
+        self.read_through.enabled && self.read_through.persist
+    }
+    
+    /// Retrieves the most relevant documents from the knowledge base for a query.
     ///
-    /// * `source_path` - The file or directory path to remove from the knowledge base
+    /// Converts the query to an embedding and searches for the top-k most similar
+    /// documents. In team mode, results from the shared knowledge base are merged
+    /// in by score, as are those of installed starter packs, weighted by
+    /// `rag.collection_weights.starter`.
     ///
     /// # Returns
     ///
-    /// The number of document chunks removed.
+    /// Search results sorted by descending score, or an empty vector if the
+    /// knowledge base is empty.
     ///
     /// # Errors
     ///
-    /// Returns an error if the removal operation fails.
+    /// Returns an error if embedding generation or the local search fails.
+    pub async fn retrieve(&self, query: &str) -> Result<Vec<SearchResult>> {
+        self.retrieve_with(query, self.retrieval_options()).await
+    }
+    
+    /// Layout of retrieved context: `rag.context_format`, or the default
+    /// for `llm.model`.
+    pub fn context_format(&self) -> ContextFormat {
+        self.context_format
+    }
+
+    /// Returns the configured retrieval settings.
+    pub fn retrieval_options(&self) -> RetrievalOptions {
+        RetrievalOptions {
+            top_k: self.top_k,
+            rerank: self.rerank,
+            include_team: true,
+            hybrid: self.hybrid,
+            cross_encoder: true,
+            diversify: true,
+            filter: None,
+        }
+    }
+    
+    /// Retrieves documents like [`retrieve`](Self::retrieve), with per-request settings.
+    pub async fn retrieve_with(&self, query: &str, options: RetrievalOptions) -> Result<Vec<SearchResult>> {
+        self.search(query, None, options).await
+    }
+
+    /// Retrieves documents like [`retrieve_with`](Self::retrieve_with), with
+    /// an embedding of `query` computed beforehand, see [`embed_queries`](Self::embed_queries).
+    ///
+    /// `query` is still used for keyword matching and reranking.
+    pub async fn retrieve_embedded(
+        &self,
+        query: &str,
+        query_embedding: &[f32],
+        options: RetrievalOptions,
+    ) -> Result<Vec<SearchResult>> {
+        self.search(query, Some(query_embedding), options).await
+    }
+
+    /// Embeds `queries` for [`retrieve_embedded`](Self::retrieve_embedded).
+    pub async fn embed_queries(&self, queries: &[&str]) -> Result<Vec<Vec<f32>>> {
+        Ok(self.embedder.embed_batch(queries).await?)
+    }
+
+    /// Searches for `query`, embedding it unless `query_embedding` is given.
+    async fn search(
+        &self,
+        query: &str,
+        query_embedding: Option<&[f32]>,
+        options: RetrievalOptions,
+    ) -> Result<Vec<SearchResult>> {
+        use tracing::{debug, info};
+        
+        let team = self.team.as_ref().filter(|_| options.include_team);
+        let team_count = match team {
+            Some(_) => self.team_count().await,
+            None => 0,
+        };
+        let starters = self.starter_stores().await;
+        let mut count = self.store.count().await.unwrap_or(0) + team_count;
+        for (_, store) in &starters {
+            count += store.count().await.unwrap_or(0);
+        }
+        debug!("Knowledge base count: {}", count);
+        if count == 0 {
+            debug!("Knowledge base is empty, skipping search");
+            return Ok(Vec::new());
+        }
+        
+        let query_embedding = match query_embedding {
+            Some(embedding) => embedding.to_vec(),
+            None => {
+                debug!("Generating query embedding for: {}", query);
+                let embedding = self.embedder.embed(query).await?;
+                debug!("Query embedding generated, dimension: {}", embedding.len());
+                embedding
+            }
+        };
+        
+        let limit = self.candidate_limit(&options);
+        let filter = options.filter.as_ref();
+
+        debug!("Searching vector store...");
+        let mut results = self.store.search(&query_embedding, limit, filter)
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+
+        if let Some(team) = team {
+            // The shared knowledge base is best-effort: chat keeps working offline
+            match team.store.search(&query_embedding, limit, filter).await {
+                Ok(team_results) => {
+                    debug!("Found {} results in team '{}'", team_results.len(), team.namespace);
+                    results.extend(team_results);
+                    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+                }
+                Err(e) => tracing::warn!("Shared knowledge base search failed: {}", e),
+            }
+        }
+        for (name, store) in &starters {
+            match store.search(&query_embedding, limit, filter).await {
+                Ok(found) => {
+                    let weight = self.collection_weights.starter;
+                    results.extend(found.into_iter().map(|mut result| {
+                        result.score *= weight;
+                        result
+                    }));
+                    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+                }
+                Err(e) => tracing::warn!("Search of starter pack '{}' failed: {}", name, e),
+            }
+        }
+        self.boost_recent(&mut results);
+        
+        if options.hybrid {
+            results = self.fuse_keywords(query, results, limit, filter).await;
+        }
+
+        let results = self.select(query, results, &options).await;
+        
+        info!("Found {} results from RAG search", results.len());
+        Ok(results)
+    }
+    
+    /// Results to fetch per search, with extra candidates when reranking,
+    /// recency, trust, or diversifying can promote lower-ranked matches, and
+    /// at least as many as the cross-encoder rescores.
+    fn candidate_limit(&self, options: &RetrievalOptions) -> usize {
+        let limit = if options.rerank || self.recency_weight > 0.0 || !self.trust.is_empty() || self.mmr_lambda(options).is_some() {
+            options.top_k * rerank::CANDIDATE_MULTIPLIER
+        } else {
+            options.top_k
+        };
+        match self.cross_encoder.as_ref().filter(|_| options.cross_encoder) {
+            Some(cross_encoder) => limit.max(cross_encoder.candidates),
+            None => limit,
+        }
+    }
+    
+    /// Merges BM25 keyword matches for `query` among the documents matching
+    /// `filter` into the vector `results` with reciprocal rank fusion.
+    ///
+    /// Falls back to the vector results if the keyword search fails.
+    async fn fuse_keywords(
+        &self,
+        query: &str,
+        results: Vec<SearchResult>,
+        limit: usize,
+        filter: Option<&SearchFilter>,
+    ) -> Vec<SearchResult> {
+        let Some(keywords) = &self.keywords else {
+            return results;
+        };
+        match keywords.keyword_search(query, limit, filter).await {
+            Ok(matches) => {
+                tracing::debug!("Found {} keyword matches", matches.len());
+                keyword::fuse(vec![results, matches], limit)
+            }
+            Err(e) => {
+                tracing::warn!("Keyword search failed: {}", e);
+                results
+            }
+        }
+    }
+    
+    /// Blends file recency into the scores of indexed files (`rag.recency_weight`).
+    fn boost_recent(&self, results: &mut [SearchResult]) {
+        rerank::boost_recent(
+            results,
+            self.recency_weight,
+            self.recency_half_life_days,
+            crate::feedback::unix_timestamp(),
+        );
+    }
+    
+    /// Searches every collection for `query` in parallel.
+    ///
+    /// Results are merged by score multiplied by their collection's weight
+    /// (`rag.collection_weights`, where 0 skips a collection) and record the
+    /// collection as `collection` metadata, see [`Collection::of`]. The team
+    /// knowledge base is only searched with `options.include_team`, and
+    /// `options.filter` applies to every collection.
+    /// `options.hybrid` is ignored: fused ranks cannot be weighed against
+    /// the similarity scores of the other collections.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if embedding generation or the local search fails;
+    /// the other collections are best-effort.
+    pub async fn retrieve_all(&self, query: &str, options: RetrievalOptions) -> Result<Vec<SearchResult>> {
+        use futures::future::join_all;
+        use tracing::{debug, warn};
+        
+        let mut stores = vec![
+            (Collection::Knowledge, &self.store),
+            (Collection::Commands, &self.commands),
+            (Collection::Dotfiles, &self.dotfiles),
+            (Collection::Conversations, &self.conversations),
+        ];
+        if let Some(team) = self.team.as_ref().filter(|_| options.include_team) {
+            stores.push((Collection::Team, &team.store));
+        }
+        let starters = self.starter_stores().await;
+        stores.extend(starters.iter().map(|(_, store)| (Collection::Starter, store)));
+        stores.retain(|(collection, _)| collection.weight(&self.collection_weights) > 0.0);
+        
+        let counts = join_all(stores.iter().map(|(_, store)| store.count())).await;
+        let stores: Vec<_> = stores.into_iter()
+            .zip(counts)
+            .filter(|(_, count)| count.as_ref().is_ok_and(|count| *count > 0))
+            .map(|(store, _)| store)
+            .collect();
+        if stores.is_empty() {
+            debug!("All collections are empty, skipping search");
+            return Ok(Vec::new());
+        }
+        
+        let query_embedding = self.embedder.embed(query).await?;
+        let limit = self.candidate_limit(&options);
+        let filter = options.filter.as_ref();
+
+        let searches = join_all(stores.iter().map(|(_, store)| store.search(&query_embedding, limit, filter))).await;
+        let mut results = Vec::new();
+        for ((collection, _), found) in stores.iter().zip(searches) {
+            let weight = collection.weight(&self.collection_weights);
+            match found {
+                Ok(mut found) => {
+                    if matches!(collection, Collection::Knowledge | Collection::Team) {
+                        self.boost_recent(&mut found);
+                    }
+                    results.extend(found.into_iter().map(|mut result| {
+                        result.score *= weight;
+                        result.document.metadata.insert("collection".to_string(), collection.as_str().to_string());
+                        result
+                    }))
+                }
+                Err(e) if *collection == Collection::Knowledge => return Err(RagError::Retrieval(e.to_string())),
+                Err(e) => warn!("Search of the {} collection failed: {}", collection.as_str(), e),
+            }
+        }
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        
+        Ok(self.select(query, results, &options).await)
+    }
+    
+    /// Picks the best `options.top_k` of the ranked candidates, weighting
+    /// them by trust and reranking them lexically and with the
+    /// cross-encoder as configured.
+    ///
+    /// If the cross-encoder fails, the candidates keep their ranking.
+    async fn select(&self, query: &str, mut results: Vec<SearchResult>, options: &RetrievalOptions) -> Vec<SearchResult> {
+        self.trust.weigh(&mut results);
+        let cross_encoder = self.cross_encoder.as_ref().filter(|_| options.cross_encoder);
+        let mmr_lambda = self.mmr_lambda(options);
+        let mut keep = match cross_encoder {
+            Some(cross_encoder) => cross_encoder.candidates.max(options.top_k),
+            None => options.top_k,
+        };
+        if mmr_lambda.is_some() {
+            keep = keep.max(options.top_k * rerank::CANDIDATE_MULTIPLIER);
+        }
+        
+        let mut results = if options.rerank {
+            rerank::rerank(query, results, keep)
+        } else {
+            results.into_iter().take(keep).collect()
+        };
+        if let Some(cross_encoder) = cross_encoder {
+            match cross_encoder.rerank(query, &mut results).await {
+                // The model's scores replace the weighted ones
+                Ok(()) => self.trust.weigh(&mut results),
+                Err(e) => tracing::warn!("Reranking model failed, keeping the vector ranking: {}", e),
+            }
+        }
+        if let Some(lambda) = mmr_lambda {
+            results = self.diversify(results, options.top_k, lambda).await;
+        }
+        results.truncate(options.top_k);
+        results
+    }
+
+    fn mmr_lambda(&self, options: &RetrievalOptions) -> Option<f32> {
+        self.mmr_lambda.filter(|_| options.diversify)
+    }
+
+    /// Picks `top_k` of `results` by maximal marginal relevance, see
+    /// [`rerank::diversify`].
+    ///
+    /// Search results carry no embeddings, so the candidates are embedded
+    /// again; the embedding cache answers that for indexed chunks. Keeps the
+    /// ranking if embedding fails.
+    async fn diversify(&self, results: Vec<SearchResult>, top_k: usize, lambda: f32) -> Vec<SearchResult> {
+        if results.len() <= top_k {
+            return results;
+        }
+        let texts: Vec<&str> = results.iter().map(|result| result.document.content.as_str()).collect();
+        match self.embedder.embed_batch(&texts).await {
+            Ok(embeddings) => rerank::diversify(results, &embeddings, top_k, lambda),
+            Err(e) => {
+                tracing::warn!("Failed to embed candidates for diversifying, keeping the ranking: {}", e);
+                results
+            }
+        }
+    }
+    
+    /// Retrieves relevant context from the knowledge base for a query.
+    ///
+    /// Searches like [`retrieve`](Self::retrieve) and formats the results as
+    /// context that can be added to an LLM prompt, listing the files and
+    /// chunks it used so responses can cite them. The results are cut down
+    /// to what fits into `llm.context_length` next to the query and
+    /// `llm.answer_tokens`, see [`ContextBudget`].
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The question or text to find relevant context for
+    /// * `filter` - If set, only documents whose metadata matches it are
+    ///   searched, e.g. `source LIKE 'src/%'` to stay within a directory
+    ///
+    /// # Returns
+    ///
+    /// The most relevant document chunks and their sources; the text is empty
+    /// if the knowledge base is empty or no relevant documents exist.
+    ///
+    /// The text is laid out in the [`context_format`](Self::context_format),
+    /// by default:
+    /// ```text
+    /// 
+    /// Relevant context from your knowledge base:
+    ///
+    /// [1] <first most relevant chunk>
+    /// [2] <second most relevant chunk>
+    /// ...
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if embedding generation fails.
+    ///
+    pub async fn retrieve_context(&self, query: &str, filter: Option<SearchFilter>) -> Result<RetrievedContext> {
+        let options = RetrievalOptions {
+            filter,
+            ..self.retrieval_options()
+        };
+        let results = self.retrieve_with(query, options).await?;
+        let results = self.budget.fit(results, &[query]);
+        Ok(RetrievedContext {
+            text: format_context(&results, self.context_format),
+            sources: cite(&results),
+        })
+    }
+    
+    /// Returns the total number of documents (chunks) in the knowledge base.
+    ///
+    /// Note: each indexed file is split into multiple chunks, so this represents
+    /// chunk count, not file count.
+    pub async fn count(&self) -> usize {
+        self.store.count().await.unwrap_or(0)
+    }
+    
+    /// Chunks stored for each source in the knowledge base, by source.
+    ///
+    /// Reads every stored document, so this takes a while for large
+    /// knowledge bases.
+    pub async fn source_stats(&self) -> Result<Vec<SourceStats>> {
+        let documents = self.store
+            .get_documents(None)
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        Ok(summarize_sources(&documents))
+    }
+    
+    /// Bytes the knowledge base takes up on disk; `None` if it is stored on
+    /// a server or the store cannot tell.
+    pub async fn disk_usage(&self) -> Option<u64> {
+        self.store.disk_usage().await.ok().flatten()
+    }
+    
+    /// Checks that the knowledge base store is reachable and searchable
+    /// with embeddings of the configured dimension.
+    ///
+    /// # Returns
+    ///
+    /// The number of documents in the store.
+    pub async fn check_store(&self) -> Result<usize> {
+        let retrieval = |e: anyhow::Error| RagError::Retrieval(format!("{:#}", e));
+        self.store.health_check().await.map_err(retrieval)?;
+        let count = self.store.count().await.map_err(retrieval)?;
+        if count > 0 {
+            let probe = vec![0.0; self.embedder.model().embedding_dim];
+            self.store.search(&probe, 1, None).await.map_err(|e| RagError::Retrieval(format!(
+                "Search with {}-dimensional embeddings failed: {:#}",
+                probe.len(),
+                e
+            )))?;
+        }
+        Ok(count)
+    }
+    
+    /// Removes all documents from the knowledge base, moving them to the trash.
+    pub async fn clear(&self) -> Result<()> {
+        self.trashing(None, None, async {
+            self.store.clear().await.map_err(|e| RagError::Retrieval(e.to_string()))
+        }).await
+    }
+    
+    /// Returns all unique file paths that have been indexed in the knowledge base.
+    ///
+    /// This method queries Qdrant to retrieve all unique source file paths
+    /// from indexed documents. Useful for displaying indexing status in UIs.
+    pub async fn get_indexed_paths(&self) -> Result<Vec<String>> {
+        self.store.get_indexed_paths().await
+            .map_err(|e| RagError::Retrieval(e.to_string()))
+    }
+
+    /// Removes documents from the knowledge base by source path.
+    ///
+    /// This method removes all documents that match the given source path.
+    /// If the path is a directory, all files within that directory are removed.
+    /// If the path is a file, only that specific file is removed. The removed
+    /// documents go to the trash.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_path` - The file or directory path to remove from the knowledge base
+    ///
+    /// # Returns
+    ///
+    /// The number of document chunks removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the removal operation fails.
     ///
     /// # Example
     ///
@@ -476,8 +1761,9 @@ impl RagEngine {
     /// # }
     /// ```
     pub async fn remove_from_knowledge_base(&self, source_path: &str) -> Result<usize> {
-        let removed = self.store.remove_by_source(source_path).await
-            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        let removed = self.trashing(Some(source_path), None, async {
+            self.store.remove_by_source(source_path).await.map_err(|e| RagError::Retrieval(e.to_string()))
+        }).await?;
         
         if removed > 0 {
             println!("Removed {} document chunks from: {}", removed, source_path);
@@ -487,4 +1773,878 @@ impl RagEngine {
         
         Ok(removed)
     }
+    
+    /// Moves the documents of `source` (every document if `None`) to the
+    /// trash, then runs `remove`; the entry is dropped again if that fails.
+    ///
+    /// `roots` are recorded when the collection itself is removed.
+    async fn trashing<T>(
+        &self,
+        source: Option<&str>,
+        roots: Option<Vec<PathBuf>>,
+        remove: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let Some(trash) = self.trash.clone() else {
+            return remove.await;
+        };
+        let documents = self.store.get_documents(source).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        if documents.is_empty() && roots.is_none() {
+            return remove.await;
+        }
+
+        let mut entry = TrashEntry::new(self.origin.clone(), source, documents.len());
+        if let Some(roots) = roots {
+            entry = entry.with_roots(roots);
+        }
+        let id = entry.id.clone();
+        let writer = trash.clone();
+        tokio::task::spawn_blocking(move || writer.put(&entry, &documents))
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))??;
+
+        let result = remove.await;
+        if result.is_err() {
+            let _ = tokio::task::spawn_blocking(move || trash.purge(Some(&id))).await;
+        }
+        result
+    }
+
+    /// Lists the removals kept in the trash, newest first.
+    pub async fn list_trash(&self) -> Result<Vec<TrashEntry>> {
+        let trash = self.trash.clone().ok_or(TrashError::Disabled)?;
+        tokio::task::spawn_blocking(move || trash.list())
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?
+            .map_err(Into::into)
+    }
+
+    /// Adds the documents of trash entry `id` back to where they were
+    /// removed from, recreating a deleted collection, and drops the entry.
+    pub async fn restore_trash(&self, id: &str) -> Result<TrashEntry> {
+        let trash = self.trash.clone().ok_or(TrashError::Disabled)?;
+        let reader = trash.clone();
+        let entry_id = id.to_string();
+        let (entry, documents) = tokio::task::spawn_blocking(move || reader.read(&entry_id))
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))??;
+
+        let target = match &entry.origin {
+            TrashOrigin::Team => self.team_admin()?,
+            TrashOrigin::Collection(name) => {
+                let exists = self.list_collections().iter().any(|collection| &collection.name == name);
+                if let (false, Some(roots)) = (exists, &entry.roots) {
+                    self.create_collection(name, roots.clone())?;
+                }
+                self.collection(name).await?
+            }
+        };
+        for batch in documents.chunks(32) {
+            target.store.add(batch.to_vec()).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
+        }
+
+        let entry_id = id.to_string();
+        tokio::task::spawn_blocking(move || trash.purge(Some(&entry_id)))
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))??;
+        Ok(entry)
+    }
+
+    /// Deletes trash entry `id`, or every entry if `None`, for good.
+    ///
+    /// # Returns
+    ///
+    /// The number of entries deleted.
+    pub async fn purge_trash(&self, id: Option<&str>) -> Result<usize> {
+        let trash = self.trash.clone().ok_or(TrashError::Disabled)?;
+        let id = id.map(str::to_string);
+        tokio::task::spawn_blocking(move || trash.purge(id.as_deref()))
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?
+            .map_err(Into::into)
+    }
+
+    /// Records the sources of this knowledge base and a hash of each one's
+    /// content as snapshot `name`, or one named after the time; a snapshot
+    /// of the same name is replaced.
+    pub async fn snapshot(&self, name: Option<&str>) -> Result<SnapshotSummary> {
+        let documents = self.store.get_documents(None).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        let snapshot = Snapshot::new(name, self.origin.clone(), &documents);
+        let summary = snapshot.summary();
+        let snapshots = self.snapshots.clone();
+        tokio::task::spawn_blocking(move || snapshots.save(&snapshot))
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))??;
+        Ok(summary)
+    }
+
+    /// Lists the snapshots, newest first.
+    pub async fn list_snapshots(&self) -> Result<Vec<SnapshotSummary>> {
+        let snapshots = self.snapshots.clone();
+        tokio::task::spawn_blocking(move || snapshots.list())
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?
+            .map_err(Into::into)
+    }
+
+    /// Compares snapshot `name` with the knowledge base it was taken of, as
+    /// it is now.
+    pub async fn diff_snapshot(&self, name: &str) -> Result<KnowledgeDiff> {
+        let snapshots = self.snapshots.clone();
+        let name = name.to_string();
+        let snapshot = tokio::task::spawn_blocking(move || snapshots.load(&name))
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))??;
+
+        let store = match &snapshot.origin {
+            TrashOrigin::Team => self.team.as_ref().ok_or(RagError::TeamNotConfigured)?.store.clone(),
+            TrashOrigin::Collection(name) => self.collection(name).await?.store,
+        };
+        let documents = store.get_documents(None).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        Ok(KnowledgeDiff::new(&snapshot, &snapshot::manifest(&documents)))
+    }
+
+    /// Stores of the installed starter packs other than this collection,
+    /// or none if `rag.collection_weights.starter` is 0.
+    ///
+    /// Starter packs that fail to open are left out.
+    async fn starter_stores(&self) -> Vec<(String, Arc<dyn VectorStore>)> {
+        if self.collection_weights.starter <= 0.0 {
+            return Vec::new();
+        }
+        let mut stores: Vec<(String, Arc<dyn VectorStore>)> = Vec::new();
+        for name in self.collections.starters() {
+            if self.origin == TrashOrigin::Collection(name.clone()) {
+                continue;
+            }
+            match self.collections.store(&name).await {
+                Ok(store) => stores.push((name, store as Arc<dyn VectorStore>)),
+                Err(e) => tracing::warn!("Skipping starter pack '{}': {}", name, e),
+            }
+        }
+        stores
+    }
+
+    /// Downloads starter pack `name` from `storage.starter_packs_url` and
+    /// imports it into a collection of the same name, replacing what an
+    /// earlier install put there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such starter pack, a collection of
+    /// that name holds something else, or the download or import fails.
+    pub async fn install_starter_pack(&self, name: &str) -> Result<ContextPack> {
+        if starter::find(name).is_none() {
+            return Err(pack::PackError::UnknownStarter { name: name.to_string() }.into());
+        }
+        let location = starter::location(&self.starter_packs_url, name);
+        let bytes = starter::fetch(&location)
+            .await
+            .map_err(|reason| RagError::Fetch { url: location.clone(), reason })?;
+
+        tokio::fs::create_dir_all(&self.packs_path).await.map_err(pack::PackError::from)?;
+        let download = self.packs_path.join(format!("{}.pack.download", name));
+        tokio::fs::write(&download, bytes).await.map_err(pack::PackError::from)?;
+
+        let installed = async {
+            self.collections.create_starter(name)?;
+            let collection = self.collection(name).await?;
+            collection.store.clear().await.map_err(|e| RagError::Retrieval(e.to_string()))?;
+            collection.import_pack(&download).await
+        };
+        let result = installed.await;
+        let _ = tokio::fs::remove_file(&download).await;
+        result
+    }
+
+    /// Exports a context pack to `path`.
+    ///
+    /// Every document indexed from the pack's sources is included with its
+    /// embedding, so importers don't need to re-index. The pack records the
+    /// embedding model used, so importers with a different model re-embed.
+    ///
+    /// # Arguments
+    ///
+    /// * `pack` - Pack manifest (name, sources, prompts, memories)
+    /// * `path` - File to write the pack to
+    ///
+    /// # Returns
+    ///
+    /// The number of document chunks written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pack name is invalid, the store cannot be read,
+    /// or the file cannot be written.
+    pub async fn export_pack(&self, mut pack: ContextPack, path: &Path) -> Result<usize> {
+        pack.validate_name()?;
+        
+        let model = self.embedder.model();
+        pack.embedding_model = model.id.clone();
+        pack.embedding_dim = model.embedding_dim;
+        
+        let mut seen = HashSet::new();
+        pack.documents.clear();
+        for source in &pack.sources {
+            let documents = self.store.get_documents(Some(source)).await
+                .map_err(|e| RagError::Retrieval(e.to_string()))?;
+            pack.documents.extend(documents.into_iter().filter(|doc| seen.insert(doc.id.clone())));
+        }
+        
+        let count = pack.documents.len();
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || pack.write_to(&path))
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))??;
+        
+        Ok(count)
+    }
+    
+    /// Imports a context pack from `path` into the knowledge base.
+    ///
+    /// Documents are added as-is when the pack was built with the same
+    /// embedding model, and re-embedded otherwise. Memories are added with
+    /// a `pack:<name>` source. The pack manifest (without documents) is kept
+    /// in the packs directory so its prompts can be listed later.
+    ///
+    /// # Returns
+    ///
+    /// The imported pack's manifest.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is not a valid pack, or if embedding or
+    /// storing the documents fails.
+    pub async fn import_pack(&self, path: &Path) -> Result<ContextPack> {
+        use tracing::info;
+        
+        let path = path.to_path_buf();
+        let mut pack = tokio::task::spawn_blocking(move || ContextPack::read_from(&path))
+            .await
+            .map_err(|e| RagError::Retrieval(e.to_string()))??;
+        
+        let model = self.embedder.model();
+        let needs_reembed = pack.embedding_model != model.id || pack.embedding_dim != model.embedding_dim;
+        let mut documents = std::mem::take(&mut pack.documents);
+        
+        if needs_reembed {
+            info!(
+                "Re-embedding {} documents from pack '{}' ({} -> {})",
+                documents.len(), pack.name, pack.embedding_model, model.id
+            );
+            for batch in documents.chunks_mut(32) {
+                let texts: Vec<&str> = batch.iter().map(|doc| doc.content.as_str()).collect();
+                let embeddings = self.embedder.embed_batch(&texts).await?;
+                for (doc, embedding) in batch.iter_mut().zip(embeddings) {
+                    doc.embedding = embedding;
+                }
+            }
+        }
+        
+        for batch in documents.chunks(32) {
+            self.store.add(batch.to_vec()).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
+        }
+        
+        let label = pack.source_label();
+        for memory in &pack.memories {
+            self.add_knowledge(memory, &label).await?;
+        }
+        
+        let manifest_path = self.packs_path.join(format!("{}.json", pack.name));
+        tokio::fs::create_dir_all(&self.packs_path).await
+            .map_err(pack::PackError::from)?;
+        let manifest = serde_json::to_vec_pretty(&pack).map_err(pack::PackError::from)?;
+        tokio::fs::write(&manifest_path, manifest).await
+            .map_err(pack::PackError::from)?;
+        
+        info!("Imported pack '{}' with {} documents", pack.name, documents.len());
+        Ok(pack)
+    }
+    
+    /// Returns the manifests of all imported context packs.
+    pub async fn list_packs(&self) -> Result<Vec<ContextPack>> {
+        let mut packs = Vec::new();
+        
+        let mut entries = match tokio::fs::read_dir(&self.packs_path).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(packs),
+            Err(e) => return Err(pack::PackError::from(e).into()),
+        };
+        
+        while let Some(entry) = entries.next_entry().await
+            .map_err(pack::PackError::from)? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            
+            let content = tokio::fs::read(&path).await
+                .map_err(pack::PackError::from)?;
+            packs.push(serde_json::from_slice(&content).map_err(pack::PackError::from)?);
+        }
+        
+        packs.sort_by(|a: &ContextPack, b| a.name.cmp(&b.name));
+        Ok(packs)
+    }
+    
+    /// Replaces the indexed documentation of a command.
+    ///
+    /// # Returns
+    ///
+    /// The number of chunks stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if embedding or storing the documentation fails.
+    pub async fn index_command_doc(&self, doc: &CommandDoc) -> Result<usize> {
+        let source = doc.source();
+        self.commands.remove_by_source(&source).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        
+        // The command name keeps chunks from the middle of a page findable
+        let chunks: Vec<String> = self.indexer.chunk_text(&doc.text)
+            .into_iter()
+            .map(|chunk| format!("{} ({}):\n{}", doc.name, doc.kind.as_str(), chunk))
+            .collect();
+        let mut ids = indexer::ChunkIds::new(&source);
+        
+        for (batch_index, batch) in chunks.chunks(32).enumerate() {
+            let texts: Vec<&str> = batch.iter().map(String::as_str).collect();
+            let embeddings = self.embedder.embed_batch(&texts).await?;
+            let documents: Vec<Document> = batch.iter()
+                .zip(embeddings)
+                .enumerate()
+                .map(|(i, (chunk, embedding))| {
+                    let index = batch_index * 32 + i;
+                    Document::new(ids.next(chunk), chunk.as_str(), embedding)
+                        .with_metadata("source", source.as_str())
+                        .with_metadata("command", doc.name.as_str())
+                        .with_metadata("kind", doc.kind.as_str())
+                        .with_metadata("chunk", index.to_string())
+                })
+                .collect();
+            self.commands.add(documents).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
+        }
+        
+        Ok(chunks.len())
+    }
+    
+    /// Removes the documentation of a command, returning the number of chunks removed.
+    pub async fn remove_command_doc(&self, name: &str) -> Result<usize> {
+        self.commands.remove_by_source(&command_docs::source(name)).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))
+    }
+    
+    /// Returns the number of command documentation chunks.
+    pub async fn command_count(&self) -> usize {
+        self.commands.count().await.unwrap_or(0)
+    }
+    
+    /// Retrieves documentation for the commands run by `command_line`.
+    ///
+    /// Only chunks of those commands are returned, best match first, so a
+    /// similar-looking page of another command is never mistaken for theirs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if embedding generation or the search fails.
+    pub async fn command_docs(&self, command_line: &str) -> Result<Vec<SearchResult>> {
+        let names = command_docs::command_names(command_line);
+        if names.is_empty() || self.command_count().await == 0 {
+            return Ok(Vec::new());
+        }
+        
+        let query_embedding = self.embedder.embed(command_line).await?;
+        // Other commands' chunks are filtered out, so fetch extra candidates
+        let limit = self.command_top_k * rerank::CANDIDATE_MULTIPLIER * names.len();
+        let results = self.commands.search(&query_embedding, limit, None).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        
+        Ok(results.into_iter()
+            .filter(|result| result.document.metadata.get("command").is_some_and(|name| names.contains(name)))
+            .take(self.command_top_k)
+            .collect())
+    }
+    
+    /// Replaces the indexed contents of a configuration file.
+    ///
+    /// Aliases defined in the file are stored as a chunk of their own, so a
+    /// command line using one finds it.
+    ///
+    /// # Returns
+    ///
+    /// The number of chunks stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if embedding or storing the file fails.
+    pub async fn index_dotfile(&self, doc: &DotfileDoc) -> Result<usize> {
+        let source = doc.source();
+        self.dotfiles.remove_by_source(&source).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        
+        let mut chunks = Vec::new();
+        if !doc.aliases.is_empty() {
+            let aliases: String = doc.aliases.iter()
+                .map(|alias| format!("{} = {}\n", alias.name, alias.expansion))
+                .collect();
+            chunks.push(format!("Aliases defined in {}:\n{}", doc.name, aliases));
+        }
+        chunks.extend(self.indexer.chunk_text(&doc.text)
+            .into_iter()
+            .map(|chunk| format!("{}:\n{}", doc.name, chunk)));
+        let mut ids = indexer::ChunkIds::new(&source);
+        
+        for (batch_index, batch) in chunks.chunks(32).enumerate() {
+            let texts: Vec<&str> = batch.iter().map(String::as_str).collect();
+            let embeddings = self.embedder.embed_batch(&texts).await?;
+            let documents: Vec<Document> = batch.iter()
+                .zip(embeddings)
+                .enumerate()
+                .map(|(i, (chunk, embedding))| {
+                    let index = batch_index * 32 + i;
+                    Document::new(ids.next(chunk), chunk.as_str(), embedding)
+                        .with_metadata("source", source.as_str())
+                        .with_metadata("file", doc.name.as_str())
+                        .with_metadata("chunk", index.to_string())
+                })
+                .collect();
+            self.dotfiles.add(documents).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
+        }
+        
+        Ok(chunks.len())
+    }
+    
+    /// Removes a configuration file, returning the number of chunks removed.
+    pub async fn remove_dotfile(&self, name: &str) -> Result<usize> {
+        self.dotfiles.remove_by_source(&dotfiles::source(name)).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))
+    }
+    
+    /// Returns the number of dotfile chunks.
+    pub async fn dotfile_count(&self) -> usize {
+        self.dotfiles.count().await.unwrap_or(0)
+    }
+    
+    /// Retrieves the parts of the user's configuration files relevant to `query`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if embedding generation or the search fails.
+    pub async fn dotfile_context(&self, query: &str) -> Result<Vec<SearchResult>> {
+        if query.trim().is_empty() || self.dotfile_count().await == 0 {
+            return Ok(Vec::new());
+        }
+        
+        let query_embedding = self.embedder.embed(query).await?;
+        self.dotfiles.search(&query_embedding, self.dotfile_top_k, None).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))
+    }
+    
+    /// Indexes the summary of a conversation, replacing an earlier one for the same response.
+    ///
+    /// # Returns
+    ///
+    /// The number of chunks stored.
+    pub async fn index_conversation(&self, response_id: &str, summary: &str, timestamp: u64) -> Result<usize> {
+        let source = conversations::source(response_id);
+        self.conversations.remove_by_source(&source).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))?;
+        
+        let chunks = self.indexer.chunk_text(summary);
+        let texts: Vec<&str> = chunks.iter().map(String::as_str).collect();
+        let embeddings = self.embedder.embed_batch(&texts).await?;
+        let mut ids = indexer::ChunkIds::new(&source);
+        let documents: Vec<Document> = chunks.iter()
+            .zip(embeddings)
+            .enumerate()
+            .map(|(index, (chunk, embedding))| {
+                Document::new(ids.next(chunk), chunk.as_str(), embedding)
+                    .with_metadata("source", source.as_str())
+                    .with_metadata("response_id", response_id)
+                    .with_metadata("timestamp", timestamp.to_string())
+                    .with_metadata("chunk", index.to_string())
+            })
+            .collect();
+        self.conversations.add(documents).await.map_err(|e| RagError::Retrieval(e.to_string()))?;
+        
+        Ok(chunks.len())
+    }
+    
+    /// Returns the number of conversation summary chunks.
+    pub async fn conversation_count(&self) -> usize {
+        self.conversations.count().await.unwrap_or(0)
+    }
+    
+    /// Retrieves summaries of past conversations relevant to `query`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if embedding generation or the search fails.
+    pub async fn conversation_context(&self, query: &str) -> Result<Vec<SearchResult>> {
+        if self.conversation_top_k == 0 || query.trim().is_empty() || self.conversation_count().await == 0 {
+            return Ok(Vec::new());
+        }
+        
+        let query_embedding = self.embedder.embed(query).await?;
+        self.conversations.search(&query_embedding, self.conversation_top_k, None).await
+            .map_err(|e| RagError::Retrieval(e.to_string()))
+    }
+    
+    /// Returns the number of documents in the shared team knowledge base,
+    /// or 0 if team mode is not configured or the server is unreachable.
+    pub async fn team_count(&self) -> usize {
+        match &self.team {
+            Some(team) => team.store.count().await.unwrap_or(0),
+            None => 0,
+        }
+    }
+    
+    /// Returns the configured team namespace, if team mode is enabled.
+    pub fn team_namespace(&self) -> Option<&str> {
+        self.team.as_ref().map(|team| team.namespace.as_str())
+    }
+    
+    /// Returns an engine that reads and writes only the shared team knowledge base.
+    ///
+    /// Used by admin operations such as indexing into or clearing the shared
+    /// index. The returned engine's [`index_directory`](Self::index_directory),
+    /// [`remove_from_knowledge_base`](Self::remove_from_knowledge_base),
+    /// [`count`](Self::count), and [`clear`](Self::clear) all target the team
+    /// collection.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if team mode is not configured, or if the shared
+    /// knowledge base is configured as read-only.
+    pub fn team_admin(&self) -> Result<RagEngine> {
+        let team = self.team.as_ref().ok_or(RagError::TeamNotConfigured)?;
+        if team.read_only {
+            return Err(RagError::TeamReadOnly);
+        }
+        
+        Ok(Self {
+            store: team.store.clone(),
+            keywords: None,
+            team: None,
+            origin: TrashOrigin::Team,
+            ..self.clone()
+        })
+    }
+}
+
+/// The language of a file, lowercased for `language = rust` filters.
+fn language_of(source: &str) -> Option<String> {
+    crate::project_tree::language_for(source).map(str::to_lowercase)
+}
+
+/// Counts the chunks of each source, with the latest indexing and
+/// modification times recorded on them.
+fn summarize_sources(documents: &[Document]) -> Vec<SourceStats> {
+    let mut sources: BTreeMap<&str, SourceStats> = BTreeMap::new();
+    for document in documents {
+        let Some(source) = document.metadata.get("source") else {
+            continue;
+        };
+        let time = |key: &str| document.metadata.get(key).and_then(|secs| secs.parse::<u64>().ok());
+        let stats = sources.entry(source).or_insert_with(|| SourceStats {
+            source: source.clone(),
+            chunks: 0,
+            indexed_at: None,
+            modified: None,
+        });
+        stats.chunks += 1;
+        stats.indexed_at = stats.indexed_at.max(time("indexed"));
+        stats.modified = stats.modified.max(time("mtime").filter(|&mtime| mtime > 0));
+    }
+    sources.into_values().collect()
+}
+
+/// Formats search results as context for an LLM prompt.
+///
+/// Returns an empty string if there are no results. See
+/// [`RagEngine::retrieve_context`] for the numbered format; `Xml` wraps
+/// each result in a `<doc>` element with its source as attributes, and
+/// `Markdown` puts it in a code fence under a heading with its path.
+///
+/// Results weighted by `rag.source_trust` are labeled with their trust
+/// level, and the model is asked to prefer the more trusted ones.
+pub fn format_context(results: &[SearchResult], format: ContextFormat) -> String {
+    use tracing::debug;
+    
+    if results.is_empty() {
+        debug!("No results found, returning empty context");
+        return String::new();
+    }
+    
+    let mut context = String::from("\n\nRelevant context from your knowledge base:\n");
+    if results.iter().any(|result| trust::trust_label(result).is_some()) {
+        context.push_str(TRUST_NOTE);
+    }
+    if format == ContextFormat::Xml {
+        context.push_str("<documents>\n");
+    }
+    
+    for (i, result) in results.iter().enumerate() {
+        debug!("Result {}: score={}, source={:?}", 
+            i + 1, 
+            result.score, 
+            result.document.metadata.get("source"));
+        let content = &result.document.content;
+        let trust = trust::trust_label(result).map(|trust| format!("trust: {}", trust));
+        match format {
+            ContextFormat::Numbered => {
+                let label: Vec<String> = origin(result).into_iter().chain(trust).collect();
+                if label.is_empty() {
+                    context.push_str(&format!("\n[{}] {}\n", i + 1, content));
+                } else {
+                    context.push_str(&format!("\n[{}] ({}) {}\n", i + 1, label.join(", "), content));
+                }
+            }
+            ContextFormat::Xml => {
+                let metadata = &result.document.metadata;
+                let mut attributes = format!("index=\"{}\"", i + 1);
+                if let Some(collection) = Collection::of(result) {
+                    attributes.push_str(&format!(" collection=\"{}\"", collection.as_str()));
+                }
+                for key in ["source", "page", "trust"] {
+                    if let Some(value) = metadata.get(key) {
+                        attributes.push_str(&format!(" {}=\"{}\"", key, escape_attribute(value)));
+                    }
+                }
+                context.push_str(&format!("<doc {}>\n{}\n</doc>\n", attributes, content));
+            }
+            ContextFormat::Markdown => {
+                let label = origin(result).or_else(|| result.document.metadata.get("source").cloned());
+                let trust = trust.map(|trust| format!(" ({})", trust)).unwrap_or_default();
+                match label {
+                    Some(label) => context.push_str(&format!("\n### {}. {}{}\n\n", i + 1, label, trust)),
+                    None => context.push_str(&format!("\n### {}.{}\n\n", i + 1, trust)),
+                }
+                let fence = fence_for(content);
+                let info = result
+                    .document
+                    .metadata
+                    .get("source")
+                    .and_then(|source| Path::new(source).extension())
+                    .map(|extension| extension.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                context.push_str(&format!("{}{}\n{}\n{}\n", fence, info, content.trim_end_matches('\n'), fence));
+            }
+        }
+    }
+    
+    if format == ContextFormat::Xml {
+        context.push_str("</documents>\n");
+    }
+    context
+}
+
+/// Tells the model how to weigh sources labeled by [`trust`].
+const TRUST_NOTE: &str = "Some sources are labeled with how far to trust them. When sources \
+conflict, prefer authoritative ones over trusted ones over unlabeled ones, and double-check \
+anything only low-trust sources say.\n";
+
+/// Escapes `value` for a double-quoted XML attribute.
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A backtick fence longer than any run of backticks in `content`, so
+/// fences inside a chunk (from Markdown files) don't close it early.
+fn fence_for(content: &str) -> String {
+    let longest = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Label of the collection and source a result came from, e.g.
+/// `knowledge: src/main.rs` or `knowledge: docs/spec.pdf, page 3`, for
+/// results of [`RagEngine::retrieve_all`].
+pub fn origin(result: &SearchResult) -> Option<String> {
+    let collection = Collection::of(result)?;
+    let metadata = &result.document.metadata;
+    Some(match (metadata.get("source"), metadata.get("page")) {
+        (Some(source), Some(page)) => format!("{}: {}, page {}", collection.as_str(), source, page),
+        (Some(source), None) => format!("{}: {}", collection.as_str(), source),
+        (None, _) => collection.as_str().to_string(),
+    })
+}
+
+/// The distinct sources of `results`, in order of relevance.
+pub fn cite(results: &[SearchResult]) -> Vec<Source> {
+    let mut sources: Vec<Source> = Vec::new();
+    for source in results.iter().filter_map(Source::of) {
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+    sources
+}
+
+/// Formats `sources` as a footer for a response, e.g.
+/// `Sources: src/main.rs:0, README.md:2`.
+///
+/// Returns an empty string if there are no sources.
+pub fn format_sources(sources: &[Source]) -> String {
+    if sources.is_empty() {
+        return String::new();
+    }
+    let sources: Vec<String> = sources.iter().map(Source::to_string).collect();
+    format!("\n\nSources: {}", sources.join(", "))
+}
+
+/// Formats command documentation for an LLM prompt.
+///
+/// Returns an empty string if there are no results.
+pub fn format_command_docs(results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return String::new();
+    }
+    
+    let mut context = String::from("\n\nDocumentation of the installed commands:\n");
+    for result in results {
+        context.push_str(&format!("\n{}\n", result.document.content));
+    }
+    context
+}
+
+/// Formats dotfile excerpts for an LLM prompt.
+///
+/// Returns an empty string if there are no results.
+pub fn format_dotfiles(results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return String::new();
+    }
+    
+    let mut context = String::from(
+        "\n\nFrom the user's configuration files (respect these aliases and settings):\n",
+    );
+    for result in results {
+        context.push_str(&format!("\n{}\n", result.document.content));
+    }
+    context
+}
+
+/// Formats summaries of past conversations for an LLM prompt.
+///
+/// Returns an empty string if there are no results.
+pub fn format_conversations(results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return String::new();
+    }
+    
+    let mut context = String::from("\n\nNotes from earlier conversations with the user (reuse solutions that worked):\n");
+    for result in results {
+        context.push_str(&format!("\n{}\n", result.document.content));
+    }
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(content: &str, metadata: &[(&str, &str)]) -> SearchResult {
+        let document = metadata
+            .iter()
+            .fold(Document::new("id", content, Vec::new()), |document, (key, value)| document.with_metadata(*key, *value));
+        SearchResult { document, score: 0.5 }
+    }
+
+    #[test]
+    fn test_format_context_labels_collections() {
+        let results = [
+            result("fn main() {}", &[("source", "src/main.rs"), ("collection", "knowledge")]),
+            result("Restart the VPN", &[("collection", "conversations")]),
+            result("plain", &[("source", "notes.md")]),
+        ];
+
+        let context = format_context(&results, ContextFormat::Numbered);
+        assert!(context.contains("[1] (knowledge: src/main.rs) fn main() {}"));
+        assert!(context.contains("[2] (conversations) Restart the VPN"));
+        assert!(context.contains("[3] plain"));
+        assert_eq!(Collection::of(&results[1]), Some(Collection::Conversations));
+        assert_eq!(Collection::of(&results[2]), None);
+    }
+
+    #[test]
+    fn test_format_context_xml_and_markdown() {
+        let results = [
+            result("fn main() {}", &[("source", "src/main.rs"), ("collection", "knowledge")]),
+            result("Run:\n```sh\nmake\n```", &[("source", "docs/\"setup\".md"), ("page", "2")]),
+        ];
+
+        let xml = format_context(&results, ContextFormat::Xml);
+        assert!(xml.contains("<documents>\n<doc index=\"1\" collection=\"knowledge\" source=\"src/main.rs\">\nfn main() {}\n</doc>"));
+        assert!(xml.contains("<doc index=\"2\" source=\"docs/&quot;setup&quot;.md\" page=\"2\">"));
+        assert!(xml.trim_end().ends_with("</documents>"));
+
+        let markdown = format_context(&results, ContextFormat::Markdown);
+        assert!(markdown.contains("### 1. knowledge: src/main.rs\n\n```rs\nfn main() {}\n```\n"));
+        // The chunk's own fence stays inside a longer one
+        assert!(markdown.contains("### 2. docs/\"setup\".md\n\n````md\nRun:\n```sh\nmake\n```\n````\n"));
+        assert!(format_context(&[], ContextFormat::Markdown).is_empty());
+        assert!(!markdown.contains(TRUST_NOTE));
+    }
+
+    #[test]
+    fn test_format_context_labels_trust() {
+        let results = [
+            result("Use spawn_blocking", &[("source", "https://docs.rs/tokio"), ("trust", "authoritative")]),
+            result("Never block", &[("source", "notes.md"), ("collection", "knowledge"), ("trust", "low")]),
+            result("plain", &[]),
+        ];
+
+        let numbered = format_context(&results, ContextFormat::Numbered);
+        assert!(numbered.contains(TRUST_NOTE));
+        assert!(numbered.contains("[1] (trust: authoritative) Use spawn_blocking"));
+        assert!(numbered.contains("[2] (knowledge: notes.md, trust: low) Never block"));
+        assert!(numbered.contains("[3] plain"));
+        assert!(format_context(&results, ContextFormat::Xml).contains("source=\"https://docs.rs/tokio\" trust=\"authoritative\">"));
+        assert!(format_context(&results, ContextFormat::Markdown).contains("### 1. https://docs.rs/tokio (trust: authoritative)"));
+    }
+
+    #[test]
+    fn test_cite_sources() {
+        let results = [
+            result("fn main() {}", &[("source", "src/main.rs"), ("chunk", "0")]),
+            result("fn main() {}", &[("source", "src/main.rs"), ("chunk", "0")]),
+            result("Installation", &[("source", "docs/guide.pdf"), ("chunk", "4"), ("page", "2")]),
+            result("Restart the VPN", &[("collection", "conversations")]),
+        ];
+
+        let sources = cite(&results);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(format_sources(&sources), "\n\nSources: src/main.rs:0, docs/guide.pdf:4 (page 2)");
+        assert_eq!(format_sources(&[]), "");
+    }
+
+    #[test]
+    fn test_summarize_sources() {
+        let documents: Vec<Document> = [
+            result("a", &[("source", "src/main.rs"), ("indexed", "100"), ("mtime", "50")]),
+            result("b", &[("source", "src/main.rs"), ("indexed", "120"), ("mtime", "50")]),
+            result("c", &[("source", "notes")]),
+            result("d", &[]),
+        ]
+        .into_iter()
+        .map(|result| result.document)
+        .collect();
+
+        let stats = summarize_sources(&documents);
+        assert_eq!(stats, [
+            SourceStats { source: "notes".to_string(), chunks: 1, indexed_at: None, modified: None },
+            SourceStats { source: "src/main.rs".to_string(), chunks: 2, indexed_at: Some(120), modified: Some(50) },
+        ]);
+    }
 }
@@ -0,0 +1,188 @@
+//! Local ONNX embedding for bulk indexing (`rag.bulk_embedding`).
+//!
+//! Embedding every chunk of a large tree through the provider means one
+//! HTTP round trip per batch of 32 and a model server tuned for chat. For
+//! runs with many chunks, [`OnnxEmbedder`] instead runs an ONNX export of
+//! the embedding model in-process, on large batches and all cores. The
+//! export must be of `rag.embedding_model` (e.g. the `onnx/model.onnx` and
+//! `tokenizer.json` of `nomic-ai/nomic-embed-text-v1.5`), or its vectors
+//! will not be comparable with the ones queries are embedded with.
+//!
+//! Models with a `sentence_embedding` output (or any rank-2 output) are
+//! taken as is; otherwise the token embeddings of the first output are
+//! mean-pooled over the attention mask. Embeddings are L2-normalized.
+
+use crate::config::BulkEmbeddingConfig;
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use ort::value::Tensor;
+use std::sync::Mutex;
+use thiserror::Error;
+use tokenizers::{PaddingParams, PaddingStrategy, Tokenizer, TruncationParams};
+
+#[derive(Debug, Error)]
+pub enum OnnxError {
+    #[error("ONNX Runtime error: {0}")]
+    Runtime(#[from] ort::Error),
+
+    #[error("Tokenizer error: {0}")]
+    Tokenizer(String),
+
+    #[error("Unexpected model output: {0}")]
+    Output(String),
+}
+
+pub type Result<T> = std::result::Result<T, OnnxError>;
+
+/// An ONNX embedding model with its tokenizer.
+pub(crate) struct OnnxEmbedder {
+    // Runs take the session mutably; each already uses every thread
+    session: Mutex<Session>,
+    tokenizer: Tokenizer,
+    /// Whether the model takes `token_type_ids` (BERT-style models do)
+    token_types: bool,
+    dim: usize,
+}
+
+impl OnnxEmbedder {
+    /// Loads the model and tokenizer of `config`, which must produce
+    /// embeddings of `dim` dimensions.
+    pub(crate) fn load(config: &BulkEmbeddingConfig, dim: usize) -> Result<Self> {
+        let threads = match config.threads {
+            0 => std::thread::available_parallelism().map_or(1, usize::from),
+            threads => threads,
+        };
+        let session = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .with_intra_threads(threads)?
+            .commit_from_file(&config.model_path)?;
+        let token_types = session.inputs.iter().any(|input| input.name == "token_type_ids");
+
+        let mut tokenizer = Tokenizer::from_file(&config.tokenizer_path)
+            .map_err(|e| OnnxError::Tokenizer(format!("{}: {}", config.tokenizer_path, e)))?;
+        // Pad each batch to its longest text only, keeping the tokenizer's pad token
+        let padding = tokenizer.get_padding().cloned().unwrap_or_default();
+        tokenizer.with_padding(Some(PaddingParams {
+            strategy: PaddingStrategy::BatchLongest,
+            ..padding
+        }));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_tokens,
+                ..TruncationParams::default()
+            }))
+            .map_err(|e| OnnxError::Tokenizer(e.to_string()))?;
+
+        Ok(Self {
+            session: Mutex::new(session),
+            tokenizer,
+            token_types,
+            dim,
+        })
+    }
+
+    /// Embeds `texts` in one run of the model.
+    ///
+    /// Blocks for as long as the run takes; call it off the async runtime.
+    pub(crate) fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(|e| OnnxError::Tokenizer(e.to_string()))?;
+        let length = encodings.first().map_or(0, |encoding| encoding.len());
+        let shape = [encodings.len() as i64, length as i64];
+
+        let tensor = |values: &dyn Fn(&tokenizers::Encoding) -> &[u32]| -> Result<Tensor<i64>> {
+            let data: Vec<i64> = encodings.iter().flat_map(|e| values(e).iter().map(|&v| i64::from(v))).collect();
+            Ok(Tensor::from_array((shape, data))?)
+        };
+        let mask: Vec<u32> = encodings.iter().flat_map(|e| e.get_attention_mask().iter().copied()).collect();
+        let mut inputs = ort::inputs! {
+            "input_ids" => tensor(&|e| e.get_ids())?,
+            "attention_mask" => tensor(&|e| e.get_attention_mask())?,
+        };
+        if self.token_types {
+            inputs.push(("token_type_ids".into(), tensor(&|e| e.get_type_ids())?.into()));
+        }
+
+        let mut session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        let outputs = session.run(inputs)?;
+        let output = match outputs.get("sentence_embedding") {
+            Some(output) => output,
+            None => &outputs[0],
+        };
+        let (output_shape, values) = output.try_extract_tensor::<f32>()?;
+
+        let mut embeddings = match **output_shape {
+            // Already pooled
+            [batch, dim] if batch as usize == texts.len() && dim as usize == self.dim => {
+                values.chunks(self.dim).map(<[f32]>::to_vec).collect::<Vec<_>>()
+            }
+            [batch, tokens, dim] if batch as usize == texts.len() && tokens as usize == length && dim as usize == self.dim => {
+                mean_pool(values, &mask, length, self.dim)
+            }
+            _ => {
+                return Err(OnnxError::Output(format!(
+                    "shape {:?}, expected [{}, {}] or [{}, {}, {}] (is rag.embedding_model.embedding_dim right?)",
+                    &**output_shape,
+                    texts.len(),
+                    self.dim,
+                    texts.len(),
+                    length,
+                    self.dim
+                )))
+            }
+        };
+        for embedding in &mut embeddings {
+            normalize(embedding);
+        }
+        Ok(embeddings)
+    }
+}
+
+/// Averages the token embeddings of each text over its unmasked tokens.
+fn mean_pool(values: &[f32], mask: &[u32], length: usize, dim: usize) -> Vec<Vec<f32>> {
+    values
+        .chunks(length * dim)
+        .zip(mask.chunks(length))
+        .map(|(tokens, mask)| {
+            let mut sum = vec![0.0; dim];
+            let mut count = 0.0;
+            for (token, _) in tokens.chunks(dim).zip(mask).filter(|(_, &m)| m != 0) {
+                for (s, v) in sum.iter_mut().zip(token) {
+                    *s += v;
+                }
+                count += 1.0;
+            }
+            sum.iter_mut().for_each(|s| *s /= f32::max(count, 1.0));
+            sum
+        })
+        .collect()
+}
+
+fn normalize(embedding: &mut [f32]) {
+    let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        embedding.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mean_pool_skips_padding() {
+        // Two texts of up to three tokens with two dimensions; the second is padded
+        let values = [1.0, 0.0, 3.0, 2.0, 5.0, 4.0, 2.0, 2.0, 4.0, 0.0, 100.0, 100.0];
+        let mask = [1, 1, 1, 1, 1, 0];
+        let pooled = mean_pool(&values, &mask, 3, 2);
+        assert_eq!(pooled, vec![vec![3.0, 2.0], vec![3.0, 1.0]]);
+
+        let mut embedding = pooled[0].clone();
+        normalize(&mut embedding);
+        assert!((embedding.iter().map(|v| v * v).sum::<f32>() - 1.0).abs() < 1e-6);
+    }
+}
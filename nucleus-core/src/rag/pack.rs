@@ -0,0 +1,218 @@
+//! Context packs: shareable bundles of indexed knowledge.
+//!
+//! A context pack is a named set of sources, prompts, and memories exported as
+//! a single gzip-compressed JSON file. Teammates import the pack to get the
+//! same pre-indexed knowledge base for a codebase without re-indexing it.
+//!
+//! Documents are stored with their embeddings. If the importing side uses a
+//! different embedding model, the documents are re-embedded on import.
+
+use super::types::Document;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use thiserror::Error;
+
+/// Current version of the pack file format.
+pub const PACK_FORMAT_VERSION: u32 = 1;
+
+/// Errors that can occur while reading or writing a pack file.
+#[derive(Debug, Error)]
+pub enum PackError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid pack file: {0}")]
+    Format(#[from] serde_json::Error),
+
+    #[error("Unsupported pack format version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("Invalid pack name '{0}'")]
+    InvalidName(String),
+
+    #[error("No starter pack '{name}'; available: {}", super::starter::names())]
+    UnknownStarter { name: String },
+}
+
+pub type Result<T> = std::result::Result<T, PackError>;
+
+/// A named prompt shipped with a pack.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackPrompt {
+    pub name: String,
+    pub content: String,
+}
+
+/// A named bundle of sources, prompts, and memories.
+///
+/// Without `documents` this is also the pack manifest: the form sent to the
+/// server to describe an export, and the form kept after an import.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextPack {
+    #[serde(default = "default_format_version")]
+    pub format_version: u32,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Embedding model the documents were embedded with
+    #[serde(default)]
+    pub embedding_model: String,
+    #[serde(default)]
+    pub embedding_dim: usize,
+    /// Indexed files or directories included in the pack
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub prompts: Vec<PackPrompt>,
+    /// Free-form notes added to the knowledge base on import
+    #[serde(default)]
+    pub memories: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<Document>,
+}
+
+fn default_format_version() -> u32 {
+    PACK_FORMAT_VERSION
+}
+
+impl ContextPack {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            format_version: PACK_FORMAT_VERSION,
+            name: name.into(),
+            description: None,
+            embedding_model: String::new(),
+            embedding_dim: 0,
+            sources: Vec::new(),
+            prompts: Vec::new(),
+            memories: Vec::new(),
+            documents: Vec::new(),
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.sources.push(source.into());
+        self
+    }
+
+    pub fn with_prompt(mut self, name: impl Into<String>, content: impl Into<String>) -> Self {
+        self.prompts.push(PackPrompt {
+            name: name.into(),
+            content: content.into(),
+        });
+        self
+    }
+
+    pub fn with_memory(mut self, memory: impl Into<String>) -> Self {
+        self.memories.push(memory.into());
+        self
+    }
+
+    /// Returns a copy of the pack without its documents.
+    pub fn manifest(&self) -> Self {
+        Self {
+            documents: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Source label used for documents and memories imported from this pack.
+    pub fn source_label(&self) -> String {
+        format!("pack:{}", self.name)
+    }
+
+    /// Checks that the pack name can be used as a file name.
+    pub fn validate_name(&self) -> Result<()> {
+        let valid = !self.name.is_empty()
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            && !self.name.starts_with('.');
+
+        if valid {
+            Ok(())
+        } else {
+            Err(PackError::InvalidName(self.name.clone()))
+        }
+    }
+
+    /// Writes the pack to `path` as gzip-compressed JSON.
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = File::create(path)?;
+        let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish()?.flush()?;
+        Ok(())
+    }
+
+    /// Reads a pack written by [`write_to`](Self::write_to).
+    pub fn read_from(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let pack: ContextPack = serde_json::from_reader(GzDecoder::new(BufReader::new(file)))?;
+
+        if pack.format_version > PACK_FORMAT_VERSION {
+            return Err(PackError::UnsupportedVersion(pack.format_version));
+        }
+
+        pack.validate_name()?;
+        Ok(pack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("onboarding.pack");
+
+        let mut pack = ContextPack::new("onboarding")
+            .with_source("./src")
+            .with_prompt("review", "Review this change")
+            .with_memory("We use tabs");
+        pack.documents.push(
+            Document::new("src/main.rs_chunk_0", "fn main() {}", vec![0.1, 0.2])
+                .with_metadata("source", "src/main.rs"),
+        );
+        pack.write_to(&path).unwrap();
+
+        let loaded = ContextPack::read_from(&path).unwrap();
+        assert_eq!(loaded.name, "onboarding");
+        assert_eq!(loaded.sources, vec!["./src"]);
+        assert_eq!(loaded.prompts[0].name, "review");
+        assert_eq!(loaded.memories, vec!["We use tabs"]);
+        assert_eq!(loaded.documents.len(), 1);
+        assert_eq!(loaded.documents[0].embedding, vec![0.1, 0.2]);
+    }
+
+    #[test]
+    fn test_manifest_drops_documents() {
+        let mut pack = ContextPack::new("docs");
+        pack.documents.push(Document::new("a", "text", vec![1.0]));
+
+        let json = serde_json::to_string(&pack.manifest()).unwrap();
+        assert!(!json.contains("documents"));
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(ContextPack::new("rust-std_v1.2").validate_name().is_ok());
+        assert!(ContextPack::new("../etc").validate_name().is_err());
+        assert!(ContextPack::new("").validate_name().is_err());
+    }
+}
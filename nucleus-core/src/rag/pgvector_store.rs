@@ -0,0 +1,385 @@
+//! Postgres vector storage using the pgvector extension.
+//!
+//! For teams that already run Postgres: a central database can hold the
+//! shared knowledge base without standing up Qdrant. Each collection is a
+//! table of documents with a `vector` column and an HNSW index for cosine
+//! distance. Requires the `postgres` feature and pgvector 0.5 or later on
+//! the server.
+//!
+//! Connections are made without TLS; reach a remote server through a
+//! private network or a TLS-terminating proxy.
+
+use crate::config::{StorageConfig, StorageMode};
+
+use super::filter::{Condition, SearchFilter};
+use super::store::VectorStore;
+use super::types::{Document, SearchResult};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use pgvector::Vector;
+use std::collections::HashMap;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, NoTls, Row};
+
+/// Query parameters built up alongside the SQL that binds them.
+type Params = Vec<Box<dyn ToSql + Sync + Send>>;
+
+/// Documents whose source, with `\` read as `/`, is `$1` or lies under `$2`,
+/// like [`source_matches`](super::store::source_matches).
+const SOURCE_UNDER: &str = "(replace(source, '\\', '/') = $1 OR starts_with(replace(source, '\\', '/'), $2))";
+
+/// Names of the table and indexes backing a collection.
+#[derive(Debug, Clone)]
+struct Tables {
+    documents: String,
+    source_index: String,
+    embedding_index: String,
+}
+
+impl Tables {
+    fn new(collection_name: &str) -> Self {
+        // Table names cannot be bound as parameters
+        let base: String = collection_name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        Self {
+            documents: format!("\"{}_documents\"", base),
+            source_index: format!("\"{}_documents_source\"", base),
+            embedding_index: format!("\"{}_documents_embedding\"", base),
+        }
+    }
+}
+
+/// Postgres-based vector store for a database shared by several machines.
+///
+/// Documents are upserted by ID and searched by cosine distance, like the
+/// other stores. The connection is reopened if the server closed it.
+pub struct PgVectorStore {
+    client: Mutex<Client>,
+    url: String,
+    tables: Tables,
+    vector_size: u64,
+}
+
+#[async_trait]
+impl VectorStore for PgVectorStore {
+    async fn add(&self, documents: Vec<Document>) -> Result<()> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+
+        for doc in &documents {
+            if doc.embedding.len() != self.vector_size as usize {
+                anyhow::bail!(
+                    "Document {} has embedding size {} but expected {}",
+                    doc.id,
+                    doc.embedding.len(),
+                    self.vector_size
+                );
+            }
+        }
+
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        let statement = tx
+            .prepare(&format!(
+                "INSERT INTO {} (id, content, source, metadata, embedding)
+                 VALUES ($1, $2, $3, $4::text::jsonb, $5)
+                 ON CONFLICT (id) DO UPDATE SET
+                     content = EXCLUDED.content,
+                     source = EXCLUDED.source,
+                     metadata = EXCLUDED.metadata,
+                     embedding = EXCLUDED.embedding",
+                self.tables.documents
+            ))
+            .await?;
+        for doc in documents {
+            let metadata = serde_json::to_string(&doc.metadata)?;
+            let source = doc.metadata.get("source");
+            let embedding = Vector::from(doc.embedding);
+            tx.execute(&statement, &[&doc.id, &doc.content, &source, &metadata, &embedding]).await?;
+        }
+        tx.commit().await.context("Failed to add documents to Postgres")
+    }
+
+    async fn search(&self, query_embedding: &[f32], top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        if top_k == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut params: Params = vec![Box::new(Vector::from(query_embedding.to_vec())), Box::new(top_k as i64)];
+        let conditions = filter.map(|filter| sql_conditions(filter, &mut params));
+        let sql = format!(
+            "SELECT id, content, metadata::text, embedding <=> $1 AS distance
+             FROM {}
+             WHERE {}
+             ORDER BY distance LIMIT $2",
+            self.tables.documents,
+            conditions.as_deref().unwrap_or("TRUE")
+        );
+        let params: Vec<&(dyn ToSql + Sync)> = params.iter().map(|param| param.as_ref() as _).collect();
+
+        let mut client = self.client().await?;
+        let rows = if conditions.is_some() {
+            // The HNSW index picks the neighbours before the filter applies,
+            // so filtered searches compare against the matching documents
+            let tx = client.transaction().await?;
+            tx.batch_execute("SET LOCAL enable_indexscan = off").await?;
+            let rows = tx.query(&sql, &params).await?;
+            tx.commit().await?;
+            rows
+        } else {
+            client.query(&sql, &params).await?
+        };
+
+        rows.iter()
+            .map(|row| {
+                let distance: f64 = row.get(3);
+                // Don't return embeddings in search results; cosine distance is 1 - similarity
+                Ok(SearchResult {
+                    document: document(row, Vec::new())?,
+                    score: 1.0 - distance as f32,
+                })
+            })
+            .collect()
+    }
+
+    async fn count(&self) -> Result<usize> {
+        let client = self.client().await?;
+        let row = client.query_one(&format!("SELECT COUNT(*) FROM {}", self.tables.documents), &[]).await?;
+        Ok(row.get::<_, i64>(0) as usize)
+    }
+
+    async fn clear(&self) -> Result<()> {
+        let client = self.client().await?;
+        client
+            .execute(&format!("DELETE FROM {}", self.tables.documents), &[])
+            .await
+            .context("Failed to clear Postgres collection")?;
+        Ok(())
+    }
+
+    async fn get_indexed_paths(&self) -> Result<Vec<String>> {
+        let client = self.client().await?;
+        let rows = client
+            .query(
+                &format!("SELECT DISTINCT source FROM {} WHERE source IS NOT NULL", self.tables.documents),
+                &[],
+            )
+            .await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn remove_by_source(&self, source_path: &str) -> Result<usize> {
+        let (path, prefix) = source_prefix(source_path);
+        let client = self.client().await?;
+        let removed = client
+            .execute(&format!("DELETE FROM {} WHERE {}", self.tables.documents, SOURCE_UNDER), &[&path, &prefix])
+            .await
+            .context("Failed to delete documents by source")?;
+        Ok(removed as usize)
+    }
+
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>> {
+        let client = self.client().await?;
+        let columns = "id, content, metadata::text, embedding";
+        let rows = match source_path {
+            Some(source_path) => {
+                let (path, prefix) = source_prefix(source_path);
+                let sql = format!("SELECT {} FROM {} WHERE {}", columns, self.tables.documents, SOURCE_UNDER);
+                client.query(&sql, &[&path, &prefix]).await?
+            }
+            None => client.query(&format!("SELECT {} FROM {}", columns, self.tables.documents), &[]).await?,
+        };
+
+        rows.iter()
+            .map(|row| {
+                let embedding: Vector = row.get(3);
+                document(row, embedding.into())
+            })
+            .collect()
+    }
+
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>> {
+        let client = self.client().await?;
+        let (path, prefix) = source_prefix(source_path);
+        let sql = format!("SELECT source, metadata->>'hash' FROM {} WHERE {}", self.tables.documents, SOURCE_UNDER);
+        let rows = client.query(&sql, &[&path, &prefix]).await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        let client = self.client().await?;
+        client.batch_execute("SELECT 1").await.context("Postgres is not reachable")
+    }
+}
+
+impl PgVectorStore {
+    /// Connects to the database and ensures the collection's table exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage_config` - Storage configuration with a `postgres` storage
+    ///   mode and the collection name
+    /// * `vector_size` - Dimension of the embedding vectors
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server cannot be reached, the pgvector
+    /// extension is not available, or the collection stores vectors of
+    /// another dimension (the embedding model changed).
+    pub async fn new(storage_config: StorageConfig, vector_size: u64) -> Result<Self> {
+        let StorageMode::Postgres { url } = &storage_config.storage_mode else {
+            anyhow::bail!("PgVectorStore only supports Postgres mode");
+        };
+        let url = url.clone();
+        let collection_name = storage_config.vector_db.collection_name.clone();
+        let tables = Tables::new(&collection_name);
+
+        let client = connect(&url).await?;
+        client
+            .batch_execute(&format!(
+                "CREATE EXTENSION IF NOT EXISTS vector;
+                 CREATE TABLE IF NOT EXISTS nucleus_collections (
+                     name TEXT PRIMARY KEY,
+                     dimension INTEGER NOT NULL
+                 );
+                 CREATE TABLE IF NOT EXISTS {} (
+                     id TEXT PRIMARY KEY,
+                     content TEXT NOT NULL,
+                     source TEXT,
+                     metadata JSONB NOT NULL,
+                     embedding vector({}) NOT NULL
+                 );
+                 CREATE INDEX IF NOT EXISTS {} ON {} (source);
+                 CREATE INDEX IF NOT EXISTS {} ON {} USING hnsw (embedding vector_cosine_ops);",
+                tables.documents,
+                vector_size,
+                tables.source_index,
+                tables.documents,
+                tables.embedding_index,
+                tables.documents
+            ))
+            .await
+            .context("Failed to create Postgres tables (is the pgvector extension installed?)")?;
+
+        client
+            .execute(
+                "INSERT INTO nucleus_collections (name, dimension) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING",
+                &[&collection_name, &(vector_size as i32)],
+            )
+            .await?;
+        let dimension: i32 = client
+            .query_one("SELECT dimension FROM nucleus_collections WHERE name = $1", &[&collection_name])
+            .await?
+            .get(0);
+        if dimension as u64 != vector_size {
+            anyhow::bail!(
+                "Collection '{}' stores {}-dimensional vectors but the embedding model produces {}; \
+                 clear it or use another collection",
+                collection_name, dimension, vector_size
+            );
+        }
+
+        Ok(Self {
+            client: Mutex::new(client),
+            url,
+            tables,
+            vector_size,
+        })
+    }
+
+    /// The connection, reopened first if the server closed it.
+    async fn client(&self) -> Result<MutexGuard<'_, Client>> {
+        let mut client = self.client.lock().await;
+        if client.is_closed() {
+            *client = connect(&self.url).await?;
+        }
+        Ok(client)
+    }
+}
+
+/// Opens a connection, driving it on a background task.
+async fn connect(url: &str) -> Result<Client> {
+    // The error leaves out the URL, which may contain a password
+    let (client, connection) = tokio_postgres::connect(url, NoTls)
+        .await
+        .context("Failed to connect to Postgres")?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::warn!("Postgres connection closed: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+/// `source_path` with `/` separators and without a trailing one, and the
+/// prefix of sources under it.
+fn source_prefix(source_path: &str) -> (String, String) {
+    let path = source_path.replace('\\', "/").trim_end_matches('/').to_string();
+    let prefix = format!("{}/", path);
+    (path, prefix)
+}
+
+/// Rebuilds a document from the `id`, `content`, and `metadata` columns of `row`.
+fn document(row: &Row, embedding: Vec<f32>) -> Result<Document> {
+    let metadata: String = row.get(2);
+    let metadata: HashMap<String, String> = serde_json::from_str(&metadata)
+        .context("Invalid document metadata")?;
+    Ok(Document {
+        id: row.get(0),
+        content: row.get(1),
+        embedding,
+        metadata,
+    })
+}
+
+/// Translates a search filter to SQL conditions on the documents table,
+/// appending the values they bind to `params`.
+///
+/// `LIKE` has no escape character, so `\` in a pattern matches itself as
+/// in the other stores.
+fn sql_conditions(filter: &SearchFilter, params: &mut Params) -> String {
+    let mut conditions = Vec::new();
+    for condition in filter.conditions() {
+        let column = match condition.key() {
+            "source" => "source".to_string(),
+            key => {
+                params.push(Box::new(key.to_string()));
+                format!("metadata ->> ${}", params.len())
+            }
+        };
+        let (operator, value, escape) = match condition {
+            Condition::Equals { value, .. } => ("=", value.clone(), ""),
+            Condition::Like { pattern, .. } => ("LIKE", pattern.clone(), " ESCAPE ''"),
+        };
+        params.push(Box::new(value));
+        conditions.push(format!("{} {} ${}{}", column, operator, params.len(), escape));
+    }
+    if conditions.is_empty() {
+        "TRUE".to_string()
+    } else {
+        conditions.join(" AND ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sql_conditions() {
+        let filter = SearchFilter::new().with_like("source", "src/%").with_equals("language", "rust");
+        let mut params: Params = vec![Box::new(Vector::from(vec![1.0])), Box::new(5i64)];
+        assert_eq!(
+            sql_conditions(&filter, &mut params),
+            "source LIKE $3 ESCAPE '' AND metadata ->> $4 = $5"
+        );
+        assert_eq!(params.len(), 5);
+
+        assert_eq!(Tables::new("team_platform-docs").documents, "\"team_platform_docs_documents\"");
+        assert_eq!(source_prefix("C:\\src\\"), ("C:/src".to_string(), "C:/src/".to_string()));
+    }
+}
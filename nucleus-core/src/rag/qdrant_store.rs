@@ -3,23 +3,24 @@
 //! This module provides integration with Qdrant, a high-performance vector database
 //! that offers automatic deduplication, persistence, and scalability.
 
-use super::store::VectorStore;
+use super::distance;
+use super::filter::{Condition as MetadataCondition, SearchFilter};
+use super::store::{source_matches, VectorStore};
 use super::types::{Document, SearchResult};
-use crate::config::{StorageConfig, StorageMode};
+use crate::config::{DistanceMetric, StorageConfig, StorageMode};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use qdrant_client::{
     Qdrant,
     qdrant::{
-        vectors_config::Config, CreateCollectionBuilder, DeletePointsBuilder,
-        Distance, PointStruct, ScrollPointsBuilder, SearchPointsBuilder, UpsertPointsBuilder,
-        VectorParamsBuilder, VectorsConfig,
+        vector_output::Vector, vectors_config::Config, Condition, CreateCollectionBuilder,
+        DeletePointsBuilder, Distance, Filter, PointStruct, ScrollPointsBuilder, SearchPointsBuilder,
+        UpsertPointsBuilder, Value as QdrantValue, VectorParamsBuilder, VectorsConfig,
     },
 };
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 /// Qdrant-based vector store for document embeddings.
@@ -37,10 +38,10 @@ use std::sync::Arc;
 ///
 #[derive(Clone)]
 pub struct QdrantStore {
-    storage_config: StorageConfig,
     client: Arc<Qdrant>,
     collection_name: String,
     vector_size: u64,
+    metric: DistanceMetric,
 }
 
 #[async_trait]
@@ -51,9 +52,7 @@ impl VectorStore for QdrantStore {
         }
 
         let points: Vec<PointStruct> = documents.into_iter().map(|document| {
-            let mut hasher = DefaultHasher::new();
-            document.id.hash(&mut hasher);
-            let numeric_id = hasher.finish();
+            let numeric_id = point_id(&document.id);
             
             let payload: HashMap<String, serde_json::Value> = document
                 .metadata
@@ -76,23 +75,26 @@ impl VectorStore for QdrantStore {
         Ok(())
     }
 
-    /// Searches for the most similar documents using cosine similarity.
+    /// Searches for the most similar documents by the collection's distance metric.
     ///
     /// # Arguments
     ///
     /// * `query_embedding` - The embedding vector to search for
     /// * `top_k` - Maximum number of results to return
+    /// * `filter` - If set, only documents whose metadata matches it are searched
     ///
     /// # Returns
     ///
     /// A vector of search results, sorted by descending similarity score.
-    async fn search(&self, query_embedding: &[f32]) -> Result<Vec<SearchResult>> {
+    async fn search(&self, query_embedding: &[f32], top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        let mut search = SearchPointsBuilder::new(&self.collection_name, query_embedding.to_vec(), top_k as u64)
+            .with_payload(true);
+        if let Some(filter) = filter {
+            search = search.filter(qdrant_filter(filter));
+        }
         let search_result = self
             .client
-            .search_points(
-                SearchPointsBuilder::new(&self.collection_name, query_embedding.to_vec(), self.storage_config.top_k as u64)
-                    .with_payload(true)
-            )
+            .search_points(search)
             .await
             .context("Failed to search points")?;
 
@@ -100,40 +102,17 @@ impl VectorStore for QdrantStore {
             .result
             .into_iter()
             .map(|point| {
-                let payload = point.payload;
-                let content = payload
-                    .get("content")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .unwrap_or_default();
-                
-                // Get the original ID from metadata
-                let id = payload
-                    .get("id")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .unwrap_or_default();
-
-                let metadata: HashMap<String, String> = payload
-                    .iter()
-                    .filter(|(k, _)| k.as_str() != "content" && k.as_str() != "id")
-                    .filter_map(|(k, v)| {
-                        v.as_str().map(|s| (k.clone(), s.to_string()))
-                    })
-                    .collect();
+                // Don't return embeddings in search results
+                let document = document_from_payload(&point.payload, vec![]);
 
-                let document = Document {
-                    id,
-                    content,
-                    embedding: vec![], // Don't return embeddings in search results
-                    metadata,
+                // Qdrant scores Euclidean collections by the distance itself
+                let score = match self.metric {
+                    DistanceMetric::Euclidean => distance::euclidean_score(point.score * point.score),
+                    DistanceMetric::Cosine | DistanceMetric::Dot => point.score,
                 };
-
-                SearchResult {
-                    document,
-                    score: point.score,
-                }
+                SearchResult { document, score }
             })
+            .filter(|result| filter.is_none_or(|filter| filter.matches(&result.document)))
             .collect();
 
         Ok(results)
@@ -254,9 +233,8 @@ impl VectorStore for QdrantStore {
                     let payload = &point.payload;
                     if let Some(source_value) = payload.get("source") {
                         if let Some(source_str) = source_value.as_str() {
-                            let point_source = source_str.replace("\\", "/");
                             // Match exact file or any file under directory
-                            if point_source == normalized_path || point_source.starts_with(&format!("{}/", normalized_path)) {
+                            if source_matches(source_str, &normalized_path) {
                                 points_to_delete.push(point_id.clone());
                             }
                         }
@@ -287,6 +265,141 @@ impl VectorStore for QdrantStore {
         
         Ok(count)
     }
+
+    /// Returns stored documents with their embeddings by scrolling the collection.
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>> {
+        let mut documents = Vec::new();
+        let mut offset: Option<qdrant_client::qdrant::PointId> = None;
+        
+        loop {
+            let mut builder = ScrollPointsBuilder::new(&self.collection_name)
+                .limit(100)
+                .with_payload(true)
+                .with_vectors(true);
+            
+            if let Some(off) = offset {
+                builder = builder.offset(off);
+            }
+            
+            let scroll_result = self.client
+                .scroll(builder)
+                .await
+                .context("Failed to scroll points")?;
+            
+            for point in scroll_result.result {
+                if let Some(source_path) = source_path {
+                    let source = point.payload.get("source").and_then(|v| v.as_str());
+                    if !source.is_some_and(|s| source_matches(s, source_path)) {
+                        continue;
+                    }
+                }
+                
+                let embedding = point.vectors
+                    .as_ref()
+                    .and_then(|vectors| vectors.get_vector())
+                    .and_then(|vector| match vector {
+                        Vector::Dense(dense) => Some(dense.data),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                
+                documents.push(document_from_payload(&point.payload, embedding));
+            }
+            
+            if let Some(next_offset) = scroll_result.next_page_offset {
+                offset = Some(next_offset);
+            } else {
+                break;
+            }
+        }
+        
+        Ok(documents)
+    }
+
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>> {
+        let mut hashes = Vec::new();
+        let mut offset: Option<qdrant_client::qdrant::PointId> = None;
+        
+        loop {
+            let mut builder = ScrollPointsBuilder::new(&self.collection_name)
+                .limit(100)
+                .with_payload(qdrant_client::qdrant::PayloadIncludeSelector {
+                    fields: vec!["source".to_string(), "hash".to_string()],
+                });
+            
+            if let Some(off) = offset {
+                builder = builder.offset(off);
+            }
+            
+            let scroll_result = self.client
+                .scroll(builder)
+                .await
+                .context("Failed to scroll points")?;
+            
+            for point in &scroll_result.result {
+                let Some(source) = point.payload.get("source").and_then(|v| v.as_str()) else {
+                    continue;
+                };
+                if source_matches(source, source_path) {
+                    let hash = point.payload.get("hash").and_then(|v| v.as_str()).map(|s| s.to_string());
+                    hashes.push((source.to_string(), hash));
+                }
+            }
+            
+            if let Some(next_offset) = scroll_result.next_page_offset {
+                offset = Some(next_offset);
+            } else {
+                break;
+            }
+        }
+        
+        Ok(hashes)
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.client.health_check().await.context("Qdrant health check failed")?;
+        Ok(())
+    }
+}
+
+/// Point ID of the document with `id`, so adding it again overwrites it.
+///
+/// Derived from SHA-256 rather than `DefaultHasher`, whose output may change
+/// between Rust releases and would then duplicate every re-added document.
+fn point_id(id: &str) -> u64 {
+    let digest = Sha256::digest(id.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().expect("SHA-256 digests are 32 bytes"))
+}
+
+/// Rebuilds a document from a point payload written by [`QdrantStore::add`].
+fn document_from_payload(payload: &HashMap<String, QdrantValue>, embedding: Vec<f32>) -> Document {
+    let content = payload
+        .get("content")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_default();
+    
+    // Get the original ID from metadata
+    let id = payload
+        .get("id")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_default();
+
+    let metadata: HashMap<String, String> = payload
+        .iter()
+        .filter(|(k, _)| k.as_str() != "content" && k.as_str() != "id")
+        .filter_map(|(k, v)| {
+            v.as_str().map(|s| (k.clone(), s.to_string()))
+        })
+        .collect();
+
+    Document {
+        id,
+        content,
+        embedding,
+        metadata,
+    }
 }
 
 impl QdrantStore {
@@ -316,10 +429,10 @@ impl QdrantStore {
         let collection_name = storage_config.vector_db.collection_name.clone();
         
         let store = Self {
-            storage_config,
             client,
             collection_name,
             vector_size,
+            metric: storage_config.vector_db.metric(),
         };
 
         store.ensure_collection().await?;
@@ -327,6 +440,8 @@ impl QdrantStore {
         Ok(store)
     }
 
+    /// Creates the collection with the configured distance metric, or
+    /// checks that an existing one uses it.
     async fn ensure_collection(&self) -> Result<()> {
         let collections = self
             .client
@@ -334,13 +449,41 @@ impl QdrantStore {
             .await
             .context("Failed to check collection")?;
 
-        if !collections {
+        let distance = match self.metric {
+            DistanceMetric::Cosine => Distance::Cosine,
+            DistanceMetric::Dot => Distance::Dot,
+            DistanceMetric::Euclidean => Distance::Euclid,
+        };
+        if collections {
+            let info = self
+                .client
+                .collection_info(&self.collection_name)
+                .await
+                .context("Failed to get collection info")?;
+            let vectors = info
+                .result
+                .and_then(|info| info.config)
+                .and_then(|config| config.params)
+                .and_then(|params| params.vectors_config)
+                .and_then(|vectors| vectors.config);
+            if let Some(Config::Params(params)) = vectors {
+                if params.distance() != distance {
+                    anyhow::bail!(
+                        "Qdrant collection '{}' compares embeddings by {} distance but {} is configured; \
+                         configure the collection's metric or clear it and re-index",
+                        self.collection_name,
+                        params.distance().as_str_name(),
+                        self.metric.as_str()
+                    );
+                }
+            }
+        } else {
             self.client
                 .create_collection(
                     CreateCollectionBuilder::new(&self.collection_name)
                         .vectors_config(VectorsConfig {
                             config: Some(Config::Params(
-                                VectorParamsBuilder::new(self.vector_size, Distance::Cosine).build()
+                                VectorParamsBuilder::new(self.vector_size, distance).build()
                             )),
                         })
                 )
@@ -352,6 +495,23 @@ impl QdrantStore {
     }
 }
 
+/// Translates `filter` to a Qdrant payload filter.
+///
+/// Qdrant has no `LIKE`; a pattern becomes a match on its longest literal
+/// part, which is a substring match on fields without a full-text index.
+/// That admits more documents than the pattern, so results are checked
+/// against the filter afterwards.
+fn qdrant_filter(filter: &SearchFilter) -> Filter {
+    Filter::must(filter.conditions().iter().filter_map(|condition| match condition {
+        MetadataCondition::Equals { key, value } => Some(Condition::matches(key.as_str(), value.clone())),
+        MetadataCondition::Like { key, pattern } => pattern
+            .split(['%', '_'])
+            .max_by_key(|part| part.len())
+            .filter(|literal| !literal.is_empty())
+            .map(|literal| Condition::matches_text(key.as_str(), literal)),
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,124 @@
+//! Files mentioned in chat messages (`rag.read_through`).
+//!
+//! Asking about a file ("why does core/src/server.rs block here?") retrieves
+//! whatever the index holds for it, which is nothing for a file outside the
+//! indexed directories and outdated chunks for one changed since. Paths
+//! mentioned in a message are picked out with [`mentioned_paths`]; the
+//! existing files among them that are not indexed, or whose content no
+//! longer matches the stored hash, are read and chunked for the request by
+//! [`RagEngine::read_through`](super::RagEngine::read_through), and
+//! [`merge`] puts their chunks ahead of the retrieved ones.
+
+use super::types::SearchResult;
+use std::path::{Path, PathBuf};
+
+/// Quotes and brackets around a mentioned path.
+const ENCLOSING: [char; 11] = ['`', '"', '\'', '(', ')', '[', ']', '{', '}', '<', '>'];
+
+/// Punctuation after a mentioned path.
+const TRAILING: [char; 6] = ['.', ',', ';', ':', '!', '?'];
+
+/// Paths mentioned in `text`, in order and without duplicates.
+///
+/// A word is taken for a path if it contains a separator or ends in a file
+/// extension, once quotes, brackets, trailing punctuation and a `:line` or
+/// `:line:col` suffix are stripped. URLs are skipped, `~` is expanded to
+/// `home`, and relative paths are resolved against `cwd`, or skipped
+/// without one. Whether the paths exist is left to the caller.
+pub fn mentioned_paths(text: &str, cwd: Option<&Path>, home: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for word in text.split_whitespace() {
+        let word = word
+            .trim_start_matches(ENCLOSING)
+            .trim_end_matches(|c| ENCLOSING.contains(&c) || TRAILING.contains(&c));
+        let word = strip_location(word);
+        if word.is_empty() || word.contains("://") || !looks_like_path(word) {
+            continue;
+        }
+
+        let path = crate::dotfiles::expand_home(word, home);
+        let path = match cwd {
+            _ if path.is_absolute() => path,
+            Some(cwd) => cwd.join(path),
+            None => continue,
+        };
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// `word` without a trailing `:line` or `:line:col`.
+fn strip_location(mut word: &str) -> &str {
+    for _ in 0..2 {
+        match word.rsplit_once(':') {
+            Some((rest, number)) if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) => word = rest,
+            _ => break,
+        }
+    }
+    word
+}
+
+fn looks_like_path(word: &str) -> bool {
+    if word.contains('/') || word.contains('\\') {
+        return true;
+    }
+    let path = Path::new(word);
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    !stem.is_empty()
+        && !stem.bytes().all(|b| b.is_ascii_digit())
+        && (1..=10).contains(&extension.len())
+        && extension.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// Chunks of the files read through, followed by the retrieved results from
+/// other sources; the stored chunks of those files are missing or outdated.
+pub fn merge(read: Vec<SearchResult>, retrieved: Vec<SearchResult>) -> Vec<SearchResult> {
+    let sources: Vec<&String> = read.iter().filter_map(|result| result.document.metadata.get("source")).collect();
+    let retrieved: Vec<SearchResult> = retrieved
+        .into_iter()
+        .filter(|result| result.document.metadata.get("source").is_none_or(|source| !sources.contains(&source)))
+        .collect();
+    read.into_iter().chain(retrieved).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag::Document;
+
+    #[test]
+    fn test_mentioned_paths() {
+        let cwd = Path::new("/work/repo");
+        let home = Path::new("/home/me");
+        let text = "Look at `core/src/server.rs:42:7`, then (README.md) and ~/notes/todo.txt. \
+                    See https://example.com/a.rs, version 1.5; again: core/src/server.rs!";
+        assert_eq!(
+            mentioned_paths(text, Some(cwd), home),
+            [
+                PathBuf::from("/work/repo/core/src/server.rs"),
+                PathBuf::from("/work/repo/README.md"),
+                PathBuf::from("/home/me/notes/todo.txt"),
+            ]
+        );
+
+        // Relative paths need a working directory
+        assert_eq!(mentioned_paths("compare src/a.rs and /etc/hosts", None, home), [PathBuf::from("/etc/hosts")]);
+    }
+
+    #[test]
+    fn test_merge_replaces_outdated_chunks() {
+        let result = |id: &str, source: &str| SearchResult {
+            document: Document::new(id.to_string(), String::new(), vec![]).with_metadata("source", source),
+            score: 0.5,
+        };
+        let merged = merge(
+            vec![result("a#new", "src/a.rs")],
+            vec![result("a#old", "src/a.rs"), result("b#1", "src/b.rs")],
+        );
+        let ids: Vec<&str> = merged.iter().map(|result| result.document.id.as_str()).collect();
+        assert_eq!(ids, ["a#new", "b#1"]);
+    }
+}
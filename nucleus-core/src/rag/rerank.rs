@@ -0,0 +1,284 @@
+//! Lightweight lexical reranking of vector search results.
+//!
+//! Vector similarity is good at finding related chunks but can rank a chunk
+//! that merely shares a topic above one that contains the exact identifiers
+//! from the query. The reranker blends the vector score with the fraction of
+//! query terms that appear in each chunk.
+//!
+//! Results from indexed files can also be boosted by recency, so answers
+//! about actively changing code prefer the newest versions of files.
+//!
+//! With `rag.reranker_model`, a [`CrossEncoder`] rescores the best
+//! candidates by reading the query and each chunk together, which is slower
+//! but more accurate than either.
+//!
+//! With `rag.mmr_lambda`, [`diversify`] picks the final results by maximal
+//! marginal relevance, so five overlapping chunks of one file don't crowd
+//! out everything else.
+
+use super::types::SearchResult;
+use crate::provider::{Provider, ProviderError};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// How many candidates to fetch per requested result when reranking.
+pub(crate) const CANDIDATE_MULTIPLIER: usize = 3;
+
+/// Weight of the vector similarity score in the blended score.
+const VECTOR_WEIGHT: f32 = 0.7;
+
+/// Reorders `results` by blended score and keeps the best `top_k`.
+///
+/// The blended score replaces each result's `score`.
+pub(crate) fn rerank(query: &str, mut results: Vec<SearchResult>, top_k: usize) -> Vec<SearchResult> {
+    let query_terms = terms(query);
+
+    if !query_terms.is_empty() {
+        for result in &mut results {
+            let document_terms = terms(&result.document.content);
+            let matched = query_terms.intersection(&document_terms).count();
+            let overlap = matched as f32 / query_terms.len() as f32;
+
+            result.score = VECTOR_WEIGHT * result.score + (1.0 - VECTOR_WEIGHT) * overlap;
+        }
+
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    }
+
+    results.truncate(top_k);
+    results
+}
+
+/// Blends how recently each file was modified into the score of `results`
+/// and sorts them by the blended score.
+///
+/// Freshness halves every `half_life_days` since the `mtime` metadata (as of
+/// `now`, seconds since the Unix epoch) and is blended in with `weight`.
+/// Results without a modification time, e.g. added notes, keep their score.
+pub(crate) fn boost_recent(results: &mut [SearchResult], weight: f32, half_life_days: f32, now: u64) {
+    if weight <= 0.0 || half_life_days <= 0.0 {
+        return;
+    }
+
+    for result in results.iter_mut() {
+        let modified = result.document.metadata.get("mtime").and_then(|mtime| mtime.parse::<u64>().ok());
+        if let Some(modified) = modified.filter(|modified| *modified > 0) {
+            let age_days = now.saturating_sub(modified) as f32 / 86_400.0;
+            let freshness = 0.5_f32.powf(age_days / half_life_days);
+            result.score = (1.0 - weight) * result.score + weight * freshness;
+        }
+    }
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+}
+
+/// Picks `top_k` of `results` by maximal marginal relevance.
+///
+/// Each pick maximizes `lambda * relevance - (1 - lambda) * redundancy`,
+/// where relevance is the score rescaled to [0, 1] among the candidates and
+/// redundancy the highest cosine similarity of the result's embedding (one
+/// per result in `embeddings`) to those already picked. Results keep their
+/// score and are returned in the order picked.
+pub(crate) fn diversify(results: Vec<SearchResult>, embeddings: &[Vec<f32>], top_k: usize, lambda: f32) -> Vec<SearchResult> {
+    if embeddings.len() != results.len() {
+        return results.into_iter().take(top_k).collect();
+    }
+
+    let (min, max) = results.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), result| {
+        (min.min(result.score), max.max(result.score))
+    });
+    let relevance: Vec<f32> = results.iter()
+        .map(|result| if max > min { (result.score - min) / (max - min) } else { 1.0 })
+        .collect();
+
+    let mut remaining: Vec<usize> = (0..results.len()).collect();
+    let mut picked: Vec<usize> = Vec::with_capacity(top_k);
+    while picked.len() < top_k && !remaining.is_empty() {
+        let marginal = |i: usize| {
+            let redundancy = picked.iter().map(|&j| cosine(&embeddings[i], &embeddings[j])).fold(0.0, f32::max);
+            lambda * relevance[i] - (1.0 - lambda) * redundancy
+        };
+        // Ties go to the higher-ranked result
+        let (position, _) = remaining.iter()
+            .enumerate()
+            .map(|(position, &i)| (position, marginal(i)))
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+            .expect("remaining is not empty");
+        picked.push(remaining.remove(position));
+    }
+
+    let mut results: Vec<Option<SearchResult>> = results.into_iter().map(Some).collect();
+    picked.into_iter().filter_map(|i| results[i].take()).collect()
+}
+
+/// Cosine similarity of two vectors, 0 if either is zero.
+pub(super) fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norms > 0.0 { dot / norms } else { 0.0 }
+}
+
+/// Rescores search results with a reranking model through the provider.
+#[derive(Clone)]
+pub(crate) struct CrossEncoder {
+    provider: Arc<dyn Provider>,
+    model: String,
+    /// Results rescored per search
+    pub(crate) candidates: usize,
+}
+
+impl CrossEncoder {
+    pub(crate) fn new(provider: Arc<dyn Provider>, model: impl Into<String>, candidates: usize) -> Self {
+        Self {
+            provider,
+            model: model.into(),
+            candidates: candidates.max(1),
+        }
+    }
+
+    /// Replaces the score of the first `candidates` results with their
+    /// relevance to `query`, drops the rest, and sorts by the new score.
+    ///
+    /// `results` are left unchanged if the model fails.
+    pub(crate) async fn rerank(&self, query: &str, results: &mut Vec<SearchResult>) -> Result<(), ProviderError> {
+        let candidates = &results[..results.len().min(self.candidates)];
+        let documents: Vec<&str> = candidates.iter().map(|result| result.document.content.as_str()).collect();
+        let scores = self.provider.rerank(query, &documents, &self.model).await?;
+        if scores.len() != documents.len() {
+            return Err(ProviderError::Other(format!(
+                "Reranker returned {} scores for {} documents",
+                scores.len(),
+                documents.len()
+            )));
+        }
+
+        results.truncate(scores.len());
+        for (result, score) in results.iter_mut().zip(scores) {
+            result.score = score;
+        }
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(())
+    }
+}
+
+/// Lowercased words and identifiers of at least three characters.
+pub(crate) fn terms(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|term| term.len() >= 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EmbeddingModel;
+    use crate::provider::{ChatRequest, ChatResponse, Message};
+    use crate::rag::Document;
+    use async_trait::async_trait;
+
+    fn result(content: &str, score: f32) -> SearchResult {
+        SearchResult {
+            document: Document::new(content, content, vec![]),
+            score,
+        }
+    }
+
+    #[test]
+    fn test_rerank_prefers_matching_terms() {
+        let results = vec![
+            result("general notes about configuration files", 0.80),
+            result("fn load_config reads config.yaml", 0.75),
+        ];
+
+        let reranked = rerank("where is load_config defined", results, 1);
+        assert_eq!(reranked.len(), 1);
+        assert_eq!(reranked[0].document.content, "fn load_config reads config.yaml");
+    }
+
+    #[test]
+    fn test_boost_recent_prefers_new_files() {
+        const DAY: u64 = 86_400;
+        let now = 100 * DAY;
+        let mut results = vec![
+            SearchResult {
+                document: Document::new("old", "old", vec![]).with_metadata("mtime", (now - 60 * DAY).to_string()),
+                score: 0.80,
+            },
+            SearchResult {
+                document: Document::new("new", "new", vec![]).with_metadata("mtime", (now - DAY).to_string()),
+                score: 0.75,
+            },
+            result("note", 0.78),
+        ];
+
+        boost_recent(&mut results, 0.3, 14.0, now);
+        let order: Vec<&str> = results.iter().map(|result| result.document.id.as_str()).collect();
+        assert_eq!(order, vec!["new", "note", "old"]);
+        assert_eq!(results[1].score, 0.78);
+    }
+
+    /// Grades passages mentioning `load_config` highly.
+    struct GradingProvider;
+
+    #[async_trait]
+    impl Provider for GradingProvider {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> crate::provider::Result<()> {
+            let prompt = &request.messages.last().unwrap().content;
+            let grade = if prompt.contains("fn load_config") { "9" } else { "2" };
+            callback(ChatResponse {
+                model: request.model,
+                content: grade.to_string(),
+                done: true,
+                truncated: false,
+                message: Message::assistant(None, grade),
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cross_encoder_rescores_candidates() {
+        let encoder = CrossEncoder::new(Arc::new(GradingProvider), "reranker", 2);
+        let mut results = vec![
+            result("configuration overview", 0.9),
+            result("fn load_config reads config.yaml", 0.8),
+            result("unrelated", 0.7),
+        ];
+
+        encoder.rerank("where is load_config", &mut results).await.unwrap();
+        let order: Vec<&str> = results.iter().map(|result| result.document.id.as_str()).collect();
+        assert_eq!(order, ["fn load_config reads config.yaml", "configuration overview"]);
+        assert_eq!(results[0].score, 0.9);
+    }
+
+    #[test]
+    fn test_rerank_keeps_order_without_terms() {
+        let results = vec![result("first", 0.9), result("second", 0.5)];
+
+        let reranked = rerank("?", results, 5);
+        assert_eq!(reranked[0].document.content, "first");
+        assert_eq!(reranked[0].score, 0.9);
+    }
+
+    #[test]
+    fn test_diversify_skips_near_duplicates() {
+        let results = vec![result("a", 0.9), result("a again", 0.88), result("b", 0.7)];
+        let embeddings = vec![vec![1.0, 0.0], vec![0.99, 0.05], vec![0.0, 1.0]];
+
+        let picked = diversify(results.clone(), &embeddings, 2, 0.5);
+        let contents: Vec<&str> = picked.iter().map(|result| result.document.content.as_str()).collect();
+        assert_eq!(contents, ["a", "b"]);
+
+        // Relevance only keeps the original ranking
+        let picked = diversify(results, &embeddings, 2, 1.0);
+        assert_eq!(picked[1].document.content, "a again");
+    }
+}
@@ -0,0 +1,257 @@
+//! Snapshots of what the knowledge base holds (`storage.snapshots_path`).
+//!
+//! A snapshot records the sources of a collection and a hash of each
+//! source's content, one JSON file per snapshot. [`KnowledgeDiff`] compares
+//! a snapshot with the collection as it is now, listing the sources added,
+//! removed, and changed since, e.g. to check what the assistant knows
+//! before a demo.
+
+use super::trash::TrashOrigin;
+use super::types::Document;
+use crate::feedback::unix_timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use thiserror::Error;
+
+const EXTENSION: &str = "json";
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid snapshot: {0}")]
+    Format(#[from] serde_json::Error),
+
+    #[error("No snapshot '{0}'; `nucleus kb snapshots` lists them")]
+    NotFound(String),
+
+    #[error("Invalid snapshot name '{0}'; use letters, digits, '-', '_' and '.'")]
+    InvalidName(String),
+}
+
+pub type Result<T> = std::result::Result<T, SnapshotError>;
+
+/// Sources of a collection and the hashes of their content at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    /// Unix timestamp (seconds) the snapshot was taken
+    pub timestamp: u64,
+    pub origin: TrashOrigin,
+    /// Source -> content hash
+    pub sources: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    /// Snapshot of `documents`, named after the time if `name` is `None`.
+    pub(crate) fn new(name: Option<&str>, origin: TrashOrigin, documents: &[Document]) -> Self {
+        let timestamp = unix_timestamp();
+        Self {
+            name: name.map_or_else(|| format!("s{:x}", timestamp), str::to_string),
+            timestamp,
+            origin,
+            sources: manifest(documents),
+        }
+    }
+
+    pub fn summary(&self) -> SnapshotSummary {
+        SnapshotSummary {
+            name: self.name.clone(),
+            timestamp: self.timestamp,
+            origin: self.origin.clone(),
+            sources: self.sources.len(),
+        }
+    }
+}
+
+/// A snapshot without its sources, as listed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotSummary {
+    pub name: String,
+    pub timestamp: u64,
+    pub origin: TrashOrigin,
+    /// Number of sources recorded
+    pub sources: usize,
+}
+
+/// Sources added, removed, and changed since a snapshot, each sorted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnowledgeDiff {
+    pub snapshot: SnapshotSummary,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    /// Number of sources the same as in the snapshot
+    pub unchanged: usize,
+}
+
+impl KnowledgeDiff {
+    /// Compares `snapshot` with the `current` sources and hashes.
+    pub fn new(snapshot: &Snapshot, current: &BTreeMap<String, String>) -> Self {
+        let mut diff = Self {
+            snapshot: snapshot.summary(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+            unchanged: 0,
+        };
+        for (source, hash) in current {
+            match snapshot.sources.get(source) {
+                None => diff.added.push(source.clone()),
+                Some(before) if before != hash => diff.changed.push(source.clone()),
+                Some(_) => diff.unchanged += 1,
+            }
+        }
+        diff.removed = snapshot
+            .sources
+            .keys()
+            .filter(|source| !current.contains_key(*source))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The sources of `documents` and a hash of each one's content.
+///
+/// The file hash recorded at indexing is used when every chunk of a source
+/// agrees on it; other sources (added text, packs, partial updates) are
+/// hashed from their chunks in ID order.
+pub(crate) fn manifest(documents: &[Document]) -> BTreeMap<String, String> {
+    let mut sources: BTreeMap<&str, Vec<&Document>> = BTreeMap::new();
+    for document in documents {
+        if let Some(source) = document.metadata.get("source") {
+            sources.entry(source).or_default().push(document);
+        }
+    }
+
+    sources
+        .into_iter()
+        .map(|(source, mut chunks)| {
+            let first = chunks[0].metadata.get("hash");
+            let hash = match first {
+                Some(hash) if chunks.iter().all(|chunk| chunk.metadata.get("hash") == first) => hash.clone(),
+                _ => {
+                    chunks.sort_by(|a, b| a.id.cmp(&b.id));
+                    let content: Vec<&str> = chunks.iter().map(|chunk| chunk.content.as_str()).collect();
+                    super::indexer::content_hash(&content.join("\0"))
+                }
+            };
+            (source.to_string(), hash)
+        })
+        .collect()
+}
+
+/// The snapshot directory.
+#[derive(Debug, Clone)]
+pub(crate) struct Snapshots {
+    path: PathBuf,
+}
+
+impl Snapshots {
+    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Writes `snapshot`, replacing one of the same name.
+    pub(crate) fn save(&self, snapshot: &Snapshot) -> Result<()> {
+        let file = self.file(&snapshot.name)?;
+        std::fs::create_dir_all(&self.path)?;
+        let partial = file.with_extension("partial");
+        std::fs::write(&partial, serde_json::to_vec(snapshot)?)?;
+        std::fs::rename(&partial, &file)?;
+        Ok(())
+    }
+
+    pub(crate) fn load(&self, name: &str) -> Result<Snapshot> {
+        let file = self.file(name)?;
+        match std::fs::read(&file) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(SnapshotError::NotFound(name.to_string())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The snapshots, newest first.
+    pub(crate) fn list(&self) -> Result<Vec<SnapshotSummary>> {
+        let entries = match std::fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == EXTENSION) {
+                if let Ok(snapshot) = serde_json::from_slice::<Snapshot>(&std::fs::read(&path)?) {
+                    snapshots.push(snapshot.summary());
+                }
+            }
+        }
+        snapshots.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| a.name.cmp(&b.name)));
+        Ok(snapshots)
+    }
+
+    fn file(&self, name: &str) -> Result<PathBuf> {
+        // Names end up in file names
+        let valid = name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if name.is_empty() || name.starts_with('.') || !valid {
+            return Err(SnapshotError::InvalidName(name.to_string()));
+        }
+        Ok(self.path.join(format!("{}.{}", name, EXTENSION)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &str, source: &str, hash: Option<&str>, content: &str) -> Document {
+        let document = Document::new(id, content, vec![]).with_metadata("source", source);
+        match hash {
+            Some(hash) => document.with_metadata("hash", hash),
+            None => document,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_and_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = Snapshots::new(dir.path().join("snapshots"));
+        assert!(snapshots.list().unwrap().is_empty());
+
+        let before = [
+            chunk("main#0", "/src/main.rs", Some("h1"), "fn main() {"),
+            chunk("main#1", "/src/main.rs", Some("h1"), "}"),
+            chunk("notes#0", "/docs/notes.md", Some("h2"), "# Notes"),
+            chunk("added#0", "pasted", None, "remember this"),
+        ];
+        let origin = TrashOrigin::Collection("default".to_string());
+        let snapshot = Snapshot::new(Some("before-demo"), origin.clone(), &before);
+        assert_eq!(snapshot.sources["/src/main.rs"], "h1");
+        snapshots.save(&snapshot).unwrap();
+        assert_eq!(snapshots.load("before-demo").unwrap(), snapshot);
+        assert_eq!(snapshots.list().unwrap(), vec![snapshot.summary()]);
+
+        let after = [
+            chunk("main#0", "/src/main.rs", Some("h3"), "fn main() { run() }"),
+            chunk("added#0", "pasted", None, "remember this"),
+            chunk("lib#0", "/src/lib.rs", Some("h4"), "pub mod a;"),
+        ];
+        let diff = KnowledgeDiff::new(&snapshot, &manifest(&after));
+        assert_eq!(diff.added, ["/src/lib.rs"]);
+        assert_eq!(diff.removed, ["/docs/notes.md"]);
+        assert_eq!(diff.changed, ["/src/main.rs"]);
+        assert_eq!(diff.unchanged, 1);
+        assert!(KnowledgeDiff::new(&snapshot, &snapshot.sources).is_empty());
+
+        assert!(matches!(snapshots.load("missing"), Err(SnapshotError::NotFound(_))));
+        assert!(matches!(snapshots.load("../collections"), Err(SnapshotError::InvalidName(_))));
+    }
+}
@@ -0,0 +1,553 @@
+//! SQLite vector storage using the sqlite-vec extension.
+//!
+//! A lighter embedded alternative to LanceDB: all collections live in a
+//! single database file. Each collection is a table of documents and a `vec0`
+//! virtual table of their embeddings, joined by row ID. Requires the `sqlite`
+//! feature.
+
+use crate::config::StorageConfig;
+
+use super::filter::{Condition, SearchFilter};
+use super::store::{dir_size, source_matches, VectorStore};
+use super::types::{Document, SearchResult};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Once};
+
+/// Names of the tables backing a collection.
+#[derive(Debug, Clone)]
+struct Tables {
+    documents: String,
+    vectors: String,
+    source_index: String,
+}
+
+impl Tables {
+    fn new(collection_name: &str) -> Self {
+        // Table names cannot be bound as parameters
+        let base: String = collection_name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        Self {
+            documents: format!("\"{}_documents\"", base),
+            vectors: format!("\"{}_vectors\"", base),
+            source_index: format!("\"{}_documents_source\"", base),
+        }
+    }
+}
+
+/// SQLite-based vector store for lightweight embedded deployment.
+///
+/// Documents are upserted by ID and searched by cosine distance, like the
+/// other stores. The connection is shared behind a mutex and used from
+/// blocking tasks.
+pub struct SqliteVecStore {
+    conn: Arc<Mutex<Connection>>,
+    tables: Tables,
+    vector_size: u64,
+    /// The database file, shared by all collections
+    path: PathBuf,
+}
+
+#[async_trait]
+impl VectorStore for SqliteVecStore {
+    async fn add(&self, documents: Vec<Document>) -> Result<()> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+
+        for doc in &documents {
+            if doc.embedding.len() != self.vector_size as usize {
+                anyhow::bail!(
+                    "Document {} has embedding size {} but expected {}",
+                    doc.id,
+                    doc.embedding.len(),
+                    self.vector_size
+                );
+            }
+        }
+
+        self.with_connection(move |conn, tables| {
+            let tx = conn.transaction()?;
+            for doc in &documents {
+                let metadata = serde_json::to_string(&doc.metadata)?;
+                let source = doc.metadata.get("source");
+
+                let existing: Option<i64> = tx
+                    .query_row(
+                        &format!("SELECT rowid FROM {} WHERE id = ?1", tables.documents),
+                        [&doc.id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let rowid = match existing {
+                    Some(rowid) => {
+                        tx.execute(
+                            &format!(
+                                "UPDATE {} SET content = ?2, source = ?3, metadata = ?4 WHERE rowid = ?1",
+                                tables.documents
+                            ),
+                            params![rowid, doc.content, source, metadata],
+                        )?;
+                        tx.execute(&format!("DELETE FROM {} WHERE rowid = ?1", tables.vectors), [rowid])?;
+                        rowid
+                    }
+                    None => {
+                        tx.execute(
+                            &format!(
+                                "INSERT INTO {} (id, content, source, metadata) VALUES (?1, ?2, ?3, ?4)",
+                                tables.documents
+                            ),
+                            params![doc.id, doc.content, source, metadata],
+                        )?;
+                        tx.last_insert_rowid()
+                    }
+                };
+                tx.execute(
+                    &format!("INSERT INTO {} (rowid, embedding) VALUES (?1, ?2)", tables.vectors),
+                    params![rowid, to_blob(&doc.embedding)],
+                )?;
+            }
+            tx.commit().context("Failed to add documents to SQLite")
+        })
+        .await
+    }
+
+    async fn search(&self, query_embedding: &[f32], top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        if top_k == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut values = vec![Value::Blob(to_blob(query_embedding)), Value::Integer(top_k as i64)];
+        let conditions = filter.map(|filter| sql_conditions(filter, &mut values));
+        self.with_connection(move |conn, tables| {
+            let sql = match conditions {
+                // vec0 picks the neighbours before a join could filter them,
+                // so filtered searches compare against the matching documents
+                Some(conditions) => format!(
+                    "SELECT d.id, d.content, d.metadata, vec_distance_cosine(v.embedding, ?1) AS distance
+                     FROM {} d JOIN {} v ON v.rowid = d.rowid
+                     WHERE {}
+                     ORDER BY distance LIMIT ?2",
+                    tables.documents, tables.vectors, conditions
+                ),
+                None => format!(
+                    "SELECT d.id, d.content, d.metadata, v.distance
+                     FROM (SELECT rowid, distance FROM {} WHERE embedding MATCH ?1 AND k = ?2) v
+                     JOIN {} d ON d.rowid = v.rowid
+                     ORDER BY v.distance",
+                    tables.vectors, tables.documents
+                ),
+            };
+            let mut statement = conn.prepare(&sql)?;
+            let rows = statement.query_map(params_from_iter(values), |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, f64>(3)?,
+                ))
+            })?;
+
+            let mut results = Vec::new();
+            for row in rows {
+                let (id, content, metadata, distance) = row?;
+                // Don't return embeddings in search results; cosine distance is 1 - similarity
+                results.push(SearchResult {
+                    document: document(id, content, &metadata, Vec::new())?,
+                    score: 1.0 - distance as f32,
+                });
+            }
+            Ok(results)
+        })
+        .await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.with_connection(|conn, tables| {
+            let count: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM {}", tables.documents), [], |row| row.get(0))?;
+            Ok(count as usize)
+        })
+        .await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.with_connection(|conn, tables| {
+            let tx = conn.transaction()?;
+            tx.execute(&format!("DELETE FROM {}", tables.vectors), [])?;
+            tx.execute(&format!("DELETE FROM {}", tables.documents), [])?;
+            tx.commit().context("Failed to clear SQLite collection")
+        })
+        .await
+    }
+
+    async fn get_indexed_paths(&self) -> Result<Vec<String>> {
+        self.with_connection(|conn, tables| {
+            let mut statement = conn.prepare(&format!(
+                "SELECT DISTINCT source FROM {} WHERE source IS NOT NULL",
+                tables.documents
+            ))?;
+            let paths = statement
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(paths)
+        })
+        .await
+    }
+
+    async fn remove_by_source(&self, source_path: &str) -> Result<usize> {
+        let source_path = source_path.to_string();
+        self.with_connection(move |conn, tables| {
+            let tx = conn.transaction()?;
+            let rowids: Vec<i64> = {
+                let mut statement = tx.prepare(&format!(
+                    "SELECT rowid, source FROM {} WHERE source IS NOT NULL",
+                    tables.documents
+                ))?;
+                let rows = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+                let mut rowids = Vec::new();
+                for row in rows {
+                    let (rowid, source) = row?;
+                    if source_matches(&source, &source_path) {
+                        rowids.push(rowid);
+                    }
+                }
+                rowids
+            };
+
+            for rowid in &rowids {
+                tx.execute(&format!("DELETE FROM {} WHERE rowid = ?1", tables.vectors), [rowid])?;
+                tx.execute(&format!("DELETE FROM {} WHERE rowid = ?1", tables.documents), [rowid])?;
+            }
+            tx.commit().context("Failed to delete documents by source")?;
+            Ok(rowids.len())
+        })
+        .await
+    }
+
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>> {
+        let source_path = source_path.map(String::from);
+        self.with_connection(move |conn, tables| {
+            let mut statement = conn.prepare(&format!(
+                "SELECT d.id, d.content, d.metadata, d.source, v.embedding
+                 FROM {} d JOIN {} v ON v.rowid = d.rowid",
+                tables.documents, tables.vectors
+            ))?;
+            let rows = statement.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Vec<u8>>(4)?,
+                ))
+            })?;
+
+            let mut documents = Vec::new();
+            for row in rows {
+                let (id, content, metadata, source, embedding) = row?;
+                if let Some(source_path) = &source_path {
+                    if !source.is_some_and(|s| source_matches(&s, source_path)) {
+                        continue;
+                    }
+                }
+                documents.push(document(id, content, &metadata, from_blob(&embedding))?);
+            }
+            Ok(documents)
+        })
+        .await
+    }
+
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>> {
+        let source_path = source_path.to_string();
+        self.with_connection(move |conn, tables| {
+            let mut statement = conn.prepare(&format!(
+                "SELECT source, metadata FROM {} WHERE source IS NOT NULL",
+                tables.documents
+            ))?;
+            let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+            let mut hashes = Vec::new();
+            for row in rows {
+                let (source, metadata) = row?;
+                if source_matches(&source, &source_path) {
+                    let mut metadata: HashMap<String, String> = serde_json::from_str(&metadata)
+                        .context("Invalid document metadata")?;
+                    hashes.push((source, metadata.remove("hash")));
+                }
+            }
+            Ok(hashes)
+        })
+        .await
+    }
+
+    /// Size of the whole database file and its write-ahead log, which
+    /// collections share.
+    async fn disk_usage(&self) -> Result<Option<u64>> {
+        let mut wal = self.path.clone().into_os_string();
+        wal.push("-wal");
+        Ok(Some(dir_size(&self.path) + dir_size(&PathBuf::from(wal))))
+    }
+}
+
+impl SqliteVecStore {
+    /// Opens (or creates) the database and ensures the collection's tables exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage_config` - Storage configuration including collection name
+    /// * `path` - Path of the SQLite database file
+    /// * `vector_size` - Dimension of the embedding vectors
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened, or if the collection
+    /// stores vectors of another dimension (the embedding model changed).
+    pub async fn new(storage_config: StorageConfig, path: &str, vector_size: u64) -> Result<Self> {
+        register_extension();
+
+        let collection_name = storage_config.vector_db.collection_name.clone();
+        let tables = Tables::new(&collection_name);
+        let path = path.to_string();
+        let file = PathBuf::from(&path);
+
+        let conn = {
+            let tables = tables.clone();
+            tokio::task::spawn_blocking(move || -> Result<Connection> {
+                if let Some(parent) = std::path::Path::new(&path).parent().filter(|p| !p.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(parent)
+                        .with_context(|| format!("Failed to create {}", parent.display()))?;
+                }
+                let conn = Connection::open(&path)
+                    .with_context(|| format!("Failed to open SQLite database {}", path))?;
+                conn.execute_batch(&format!(
+                    "PRAGMA journal_mode = WAL;
+                     CREATE TABLE IF NOT EXISTS nucleus_collections (
+                         name TEXT PRIMARY KEY,
+                         dimension INTEGER NOT NULL
+                     );
+                     CREATE TABLE IF NOT EXISTS {} (
+                         rowid INTEGER PRIMARY KEY,
+                         id TEXT NOT NULL UNIQUE,
+                         content TEXT NOT NULL,
+                         source TEXT,
+                         metadata TEXT NOT NULL
+                     );
+                     CREATE INDEX IF NOT EXISTS {} ON {} (source);
+                     CREATE VIRTUAL TABLE IF NOT EXISTS {} USING vec0(
+                         embedding float[{}] distance_metric=cosine
+                     );",
+                    tables.documents,
+                    tables.source_index,
+                    tables.documents,
+                    tables.vectors,
+                    vector_size
+                ))
+                .context("Failed to create SQLite tables")?;
+
+                conn.execute(
+                    "INSERT OR IGNORE INTO nucleus_collections (name, dimension) VALUES (?1, ?2)",
+                    params![collection_name, vector_size as i64],
+                )?;
+                let dimension: i64 = conn.query_row(
+                    "SELECT dimension FROM nucleus_collections WHERE name = ?1",
+                    [&collection_name],
+                    |row| row.get(0),
+                )?;
+                if dimension as u64 != vector_size {
+                    anyhow::bail!(
+                        "Collection '{}' stores {}-dimensional vectors but the embedding model produces {}; \
+                         clear it or use another collection",
+                        collection_name, dimension, vector_size
+                    );
+                }
+                Ok(conn)
+            })
+            .await
+            .context("SQLite task failed")??
+        };
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            tables,
+            vector_size,
+            path: file,
+        })
+    }
+
+    /// Runs `f` with the connection on a blocking thread.
+    async fn with_connection<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Connection, &Tables) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        let tables = self.tables.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().map_err(|_| anyhow::anyhow!("SQLite connection lock poisoned"))?;
+            f(&mut conn, &tables)
+        })
+        .await
+        .context("SQLite task failed")?
+    }
+}
+
+/// Registers sqlite-vec with every connection opened afterwards.
+fn register_extension() {
+    static REGISTERED: Once = Once::new();
+
+    REGISTERED.call_once(|| {
+        // SAFETY: sqlite3_vec_init has the signature of an SQLite extension
+        // entry point, which is what sqlite3_auto_extension expects.
+        unsafe {
+            rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute::<*const (), unsafe extern "C" fn(
+                *mut rusqlite::ffi::sqlite3,
+                *mut *mut std::os::raw::c_char,
+                *const rusqlite::ffi::sqlite3_api_routines,
+            ) -> std::os::raw::c_int>(
+                sqlite_vec::sqlite3_vec_init as *const (),
+            )));
+        }
+    });
+}
+
+/// Encodes an embedding the way sqlite-vec stores `float[]` columns.
+fn to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Rebuilds a document from a row written by [`SqliteVecStore::add`].
+fn document(id: String, content: String, metadata: &str, embedding: Vec<f32>) -> Result<Document> {
+    let metadata: HashMap<String, String> = serde_json::from_str(metadata)
+        .context("Invalid document metadata")?;
+    Ok(Document {
+        id,
+        content,
+        embedding,
+        metadata,
+    })
+}
+
+/// Translates a search filter to SQL conditions on the documents table
+/// (`d`), appending the values they bind to `values`.
+///
+/// `LIKE` patterns become `GLOB` patterns, which are case-sensitive like
+/// the other stores' matching.
+fn sql_conditions(filter: &SearchFilter, values: &mut Vec<Value>) -> String {
+    let mut conditions = Vec::new();
+    for condition in filter.conditions() {
+        let column = match condition.key() {
+            "source" => "d.source".to_string(),
+            key => {
+                values.push(Value::Text(format!("$.\"{}\"", key.replace('"', "\\\""))));
+                format!("json_extract(d.metadata, ?{})", values.len())
+            }
+        };
+        let (operator, value) = match condition {
+            Condition::Equals { value, .. } => ("=", value.clone()),
+            Condition::Like { pattern, .. } => ("GLOB", like_to_glob(pattern)),
+        };
+        values.push(Value::Text(value));
+        conditions.push(format!("{} {} ?{}", column, operator, values.len()));
+    }
+    if conditions.is_empty() {
+        "1".to_string()
+    } else {
+        conditions.join(" AND ")
+    }
+}
+
+/// Translates a `LIKE` pattern to a `GLOB` pattern.
+fn like_to_glob(pattern: &str) -> String {
+    pattern.chars()
+        .map(|c| match c {
+            '%' => "*".to_string(),
+            '_' => "?".to_string(),
+            '*' | '?' | '[' => format!("[{}]", c),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(id: &str, content: &str, embedding: Vec<f32>, source: &str) -> Document {
+        Document::new(id, content, embedding).with_metadata("source", source)
+    }
+
+    #[test]
+    fn test_blob_roundtrip() {
+        let embedding = vec![0.5, -1.25, 3.0];
+        assert_eq!(to_blob(&embedding).len(), 12);
+        assert_eq!(from_blob(&to_blob(&embedding)), embedding);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage_config = StorageConfig::default();
+        storage_config.vector_db.collection_name = "test-collection".to_string();
+        let path = dir.path().join("nucleus.db").to_string_lossy().to_string();
+        let store = SqliteVecStore::new(storage_config.clone(), &path, 3).await.unwrap();
+
+        store.add(vec![
+            sample("a_0", "alpha", vec![1.0, 0.0, 0.0], "/src/a.rs").with_metadata("chunk", "0"),
+            sample("b_0", "beta", vec![0.0, 1.0, 0.0], "/src/b.rs").with_metadata("hash", "b1"),
+            sample("it's", "quoted", vec![0.0, 0.0, 1.0], "/other/it's.rs"),
+        ]).await.unwrap();
+        // Re-adding an ID replaces the document
+        store.add(vec![
+            sample("a_0", "alpha v2", vec![1.0, 0.0, 0.0], "/src/a.rs").with_metadata("chunk", "0"),
+        ]).await.unwrap();
+        assert_eq!(store.count().await.unwrap(), 3);
+
+        let results = store.search(&[0.9, 0.1, 0.0], 2, None).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].document.content, "alpha v2");
+        assert_eq!(results[0].document.metadata.get("chunk").map(String::as_str), Some("0"));
+        assert!(results[0].score > results[1].score);
+        assert!(results[0].score > 0.9);
+
+        // Filters pick the neighbours among matching documents only
+        let other = SearchFilter::new().with_like("source", "/other/%");
+        let results = store.search(&[0.9, 0.1, 0.0], 1, Some(&other)).await.unwrap();
+        assert_eq!(results[0].document.content, "quoted");
+        assert!(results[0].score < 0.5);
+        let chunk = SearchFilter::new().with_equals("chunk", "0").with_like("source", "/SRC/%");
+        assert!(store.search(&[1.0, 0.0, 0.0], 3, Some(&chunk)).await.unwrap().is_empty());
+
+        let mut paths = store.get_indexed_paths().await.unwrap();
+        paths.sort();
+        assert_eq!(paths, vec!["/other/it's.rs", "/src/a.rs", "/src/b.rs"]);
+        let documents = store.get_documents(Some("/src")).await.unwrap();
+        assert_eq!(documents.len(), 2);
+        assert!(documents.iter().all(|doc| doc.embedding.len() == 3));
+        let mut hashes = store.source_hashes("/src").await.unwrap();
+        hashes.sort();
+        assert_eq!(hashes, vec![("/src/a.rs".to_string(), None), ("/src/b.rs".to_string(), Some("b1".to_string()))]);
+
+        assert_eq!(store.remove_by_source("/src").await.unwrap(), 2);
+        assert_eq!(store.count().await.unwrap(), 1);
+        store.clear().await.unwrap();
+        assert_eq!(store.count().await.unwrap(), 0);
+        store.add(vec![sample("c_0", "gamma", vec![0.0, 1.0, 0.0], "/c.rs")]).await.unwrap();
+
+        // Reopening keeps the documents and rejects another dimension
+        let reopened = SqliteVecStore::new(storage_config.clone(), &path, 3).await.unwrap();
+        assert_eq!(reopened.count().await.unwrap(), 1);
+        assert!(SqliteVecStore::new(storage_config, &path, 4).await.is_err());
+    }
+}
@@ -0,0 +1,96 @@
+//! Starter packs: context packs of general knowledge, downloaded on request.
+//!
+//! A fresh install has nothing indexed, so retrieval has nothing to add to
+//! a question. Starter packs are pre-embedded [`ContextPack`](super::ContextPack)
+//! files published at `storage.starter_packs_url` as `<name>.pack`;
+//! `nucleus pack install <name>` downloads one and imports it into a
+//! collection of the same name, which is searched alongside the collection
+//! a request uses (weighted by `rag.collection_weights.starter`).
+//!
+//! The URL may also be a local directory, e.g. a mirror on a machine
+//! without internet access.
+
+use reqwest::header::USER_AGENT;
+use std::path::Path;
+use std::time::Duration;
+
+/// Largest pack file downloaded.
+const MAX_PACK_BYTES: u64 = 256 * 1024 * 1024;
+
+/// A starter pack that can be installed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StarterPack {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// The starter packs published with nucleus.
+pub const STARTER_PACKS: [StarterPack; 3] = [
+    StarterPack {
+        name: "rust-std",
+        description: "Summaries of the Rust standard library modules, types, and traits",
+    },
+    StarterPack {
+        name: "posix",
+        description: "Reference for the POSIX shell and utilities (sh, find, sed, awk, grep, ...)",
+    },
+    StarterPack {
+        name: "git",
+        description: "Reference for git commands, options, and common workflows",
+    },
+];
+
+/// The starter pack named `name`.
+pub fn find(name: &str) -> Option<&'static StarterPack> {
+    STARTER_PACKS.iter().find(|pack| pack.name == name)
+}
+
+/// Names of the starter packs, e.g. for error messages.
+pub fn names() -> String {
+    STARTER_PACKS.iter().map(|pack| pack.name).collect::<Vec<_>>().join(", ")
+}
+
+/// Where pack `name` is published under `base`.
+pub(crate) fn location(base: &str, name: &str) -> String {
+    format!("{}/{}.pack", base.trim_end_matches('/'), name)
+}
+
+/// Reads the pack file at `location`, an http(s) URL or a local path.
+pub(crate) async fn fetch(location: &str) -> Result<Vec<u8>, String> {
+    if !location.starts_with("http://") && !location.starts_with("https://") {
+        let path = location.strip_prefix("file://").unwrap_or(location);
+        return tokio::fs::read(Path::new(path)).await.map_err(|e| e.to_string());
+    }
+
+    let response = reqwest::Client::new()
+        .get(location)
+        .header(USER_AGENT, format!("nucleus/{}", crate::VERSION))
+        .timeout(Duration::from_secs(300))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    if response.content_length().is_some_and(|len| len > MAX_PACK_BYTES) {
+        return Err(format!("the pack is larger than {} MB", MAX_PACK_BYTES / 1024 / 1024));
+    }
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    Ok(bytes.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fetch_from_mirror_directory() {
+        assert_eq!(find("git").map(|pack| pack.name), Some("git"));
+        assert!(find("python").is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("posix.pack"), b"pack").unwrap();
+        let base = format!("{}/", dir.path().display());
+        assert_eq!(fetch(&location(&base, "posix")).await.unwrap(), b"pack");
+        assert_eq!(fetch(&format!("file://{}", location(&base, "posix"))).await.unwrap(), b"pack");
+        assert!(fetch(&location(&base, "git")).await.is_err());
+    }
+}
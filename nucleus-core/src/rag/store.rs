@@ -2,21 +2,31 @@
 //!
 //! This module provides a unified interface for different vector database implementations.
 
+use super::filter::SearchFilter;
 use super::types::{Document, SearchResult};
 use super::qdrant_store::QdrantStore;
 use super::lancedb_store::LanceDbStore;
-use crate::config::{StorageConfig, StorageMode};
+use super::hnsw::HnswStore;
+use crate::circuit::CircuitBreaker;
+use crate::config::{DistanceMetric, HnswConfig, ResilienceConfig, StorageConfig, StorageMode};
+use crate::memory::{self, Subsystem};
+use crate::warmup::Warmup;
 use anyhow::Result;
 use async_trait::async_trait;
+use std::path::Path;
 use std::sync::Arc;
 
 /// Unified interface for vector database operations.
 ///
 /// Implementations handle document storage, similarity search, and metadata queries
-/// across different vector database backends (LanceDB for embedded, Qdrant for gRPC).
+/// across different vector database backends (LanceDB or SQLite for embedded, Qdrant for gRPC,
+/// pgvector for Postgres, and Chroma).
 #[async_trait]
 pub trait VectorStore: Send + Sync {
     /// Adds or updates multiple documents in the store.
+    ///
+    /// A document replaces the stored one with the same ID, so re-adding a
+    /// chunk (see [`ChunkIds`](super::indexer::ChunkIds)) never duplicates it.
     async fn add(&self, documents: Vec<Document>) -> Result<()>;
     /// Searches for the most similar documents using vector similarity.
     ///
@@ -24,11 +34,17 @@ pub trait VectorStore: Send + Sync {
     ///
     /// * `query_embedding` - The embedding vector to search for
     /// * `top_k` - Maximum number of results to return
+    /// * `filter` - If set, only documents whose metadata matches it are searched
     ///
     /// # Returns
     ///
     /// A vector of search results, sorted by descending similarity score.
-    async fn search(&self, query_embedding: &[f32]) -> Result<Vec<SearchResult>>;
+    async fn search(
+        &self,
+        query_embedding: &[f32],
+        top_k: usize,
+        filter: Option<&SearchFilter>,
+    ) -> Result<Vec<SearchResult>>;
 
     /// Returns the total number of documents in the store.
     async fn count(&self) -> Result<usize>;
@@ -49,17 +65,77 @@ pub trait VectorStore: Send + Sync {
     ///
     /// The number of documents removed.
     async fn remove_by_source(&self, source_path: &str) -> Result<usize>;
+
+    /// Returns stored documents, including their embeddings.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_path` - If set, only documents from this source (file or directory) are returned
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>>;
+
+    /// Returns the source and content hash of each document under
+    /// `source_path` (file or directory), without loading content or
+    /// embeddings. The hash is `None` for documents stored without one.
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>>;
+
+    /// Checks that the backing database is reachable.
+    ///
+    /// Embedded stores are always reachable.
+    async fn health_check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Bytes the store takes up on disk; `None` for stores on a server.
+    async fn disk_usage(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+/// Total size of the files under `path`, 0 if it doesn't exist.
+pub(crate) fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+/// Returns true if a document's source is `source_path` or lies under it.
+///
+/// Path separators are normalized so Windows and Unix paths compare equal.
+pub(crate) fn source_matches(document_source: &str, source_path: &str) -> bool {
+    let document_source = document_source.replace('\\', "/");
+    let source_path = source_path.replace('\\', "/");
+    let source_path = source_path.trim_end_matches('/');
+
+    document_source == source_path || document_source.starts_with(&format!("{}/", source_path))
 }
 
 /// Creates a vector store instance based on the storage mode.
 ///
 /// - `Embedded` mode uses LanceDB for zero-setup, in-process storage
-/// - `Grpc` mode uses Qdrant for remote server connectivity
+/// - `Grpc` mode uses Qdrant for remote server connectivity, with retries
+///   and a circuit breaker (`resilience`)
+/// - `Sqlite` mode uses a single SQLite file with sqlite-vec (`sqlite` feature)
+/// - Both embedded modes are searched through an in-memory HNSW index with
+///   `hnsw.enabled`
+/// - `Postgres` mode uses pgvector in a Postgres database (`postgres`
+///   feature), with the same resilience as `Grpc`
+/// - `Chroma` mode uses a Chroma server over HTTP, also with retries and a
+///   circuit breaker
+///
+/// Collections are compared with their `vector_db` distance metric, which
+/// the `Sqlite` and `Postgres` stores only support as cosine.
 ///
 /// # Arguments
 ///
 /// * `storage_config` - Storage configuration including storage mode and top_k
 /// * `vector_size` - Dimension of the embedding vectors
+/// * `resilience` - Retry and circuit breaker settings for remote stores
 ///
 /// # Returns
 ///
@@ -67,19 +143,263 @@ pub trait VectorStore: Send + Sync {
 pub async fn create_vector_store(
     storage_config: StorageConfig,
     vector_size: u64,
+    resilience: &ResilienceConfig,
 ) -> Result<Arc<dyn VectorStore>> {
-    match storage_config.storage_mode.clone() {
+    let store: Arc<dyn VectorStore> = match storage_config.storage_mode.clone() {
         StorageMode::Embedded { path } => {
+            let hnsw = storage_config.hnsw.clone();
+            let metric = storage_config.vector_db.metric();
             let store = LanceDbStore::new(
                 storage_config,
                 &path,
                 vector_size.into(),
             ).await?;
-            Ok(Arc::new(store))
+            with_hnsw(Arc::new(store), hnsw, metric).await?
         }
         StorageMode::Grpc { .. } => {
-            let store = QdrantStore::new(storage_config, vector_size).await?;
-            Ok(Arc::new(store))
+            let service = format!("Qdrant collection '{}'", storage_config.vector_db.collection_name);
+            let store: Arc<dyn VectorStore> = Arc::new(QdrantStore::new(storage_config, vector_size).await?);
+            Arc::new(ResilientStore::new(store, service, resilience))
+        }
+        #[cfg(feature = "sqlite")]
+        StorageMode::Sqlite { path } => {
+            only_cosine(&storage_config, "sqlite")?;
+            let hnsw = storage_config.hnsw.clone();
+            let store = super::sqlite_store::SqliteVecStore::new(
+                storage_config,
+                &path,
+                vector_size,
+            ).await?;
+            with_hnsw(Arc::new(store), hnsw, DistanceMetric::Cosine).await?
+        }
+        #[cfg(not(feature = "sqlite"))]
+        StorageMode::Sqlite { .. } => {
+            anyhow::bail!("storage_mode 'sqlite' requires building with the `sqlite` feature")
+        }
+        StorageMode::Chroma { .. } => {
+            let service = format!("Chroma collection '{}'", storage_config.vector_db.collection_name);
+            let store: Arc<dyn VectorStore> =
+                Arc::new(super::chroma_store::ChromaStore::new(storage_config, vector_size).await?);
+            Arc::new(ResilientStore::new(store, service, resilience))
+        }
+        #[cfg(feature = "postgres")]
+        StorageMode::Postgres { .. } => {
+            only_cosine(&storage_config, "postgres")?;
+            let service = format!("Postgres collection '{}'", storage_config.vector_db.collection_name);
+            let store: Arc<dyn VectorStore> =
+                Arc::new(super::pgvector_store::PgVectorStore::new(storage_config, vector_size).await?);
+            Arc::new(ResilientStore::new(store, service, resilience))
         }
+        #[cfg(not(feature = "postgres"))]
+        StorageMode::Postgres { .. } => {
+            anyhow::bail!("storage_mode 'postgres' requires building with the `postgres` feature")
+        }
+    };
+    Ok(Arc::new(TrackedStore(store)))
+}
+
+/// Puts an in-memory HNSW index in front of an embedded store if enabled.
+async fn with_hnsw(store: Arc<dyn VectorStore>, config: HnswConfig, metric: DistanceMetric) -> Result<Arc<dyn VectorStore>> {
+    if !config.enabled {
+        return Ok(store);
+    }
+    Ok(Arc::new(HnswStore::new(store, config, metric).await?))
+}
+
+/// Fails for a collection configured with another metric than cosine, the
+/// only one the `mode` store searches by.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn only_cosine(storage_config: &StorageConfig, mode: &str) -> Result<()> {
+    let metric = storage_config.vector_db.metric();
+    if metric != DistanceMetric::Cosine {
+        anyhow::bail!(
+            "storage_mode '{}' only supports cosine distance, but collection '{}' is configured for {}",
+            mode, storage_config.vector_db.collection_name, metric.as_str()
+        );
+    }
+    Ok(())
+}
+
+/// Retries and circuit breaking around a remote store, see [`crate::circuit`].
+///
+/// Every store error counts as transient: against a database server they
+/// are overwhelmingly connection problems.
+struct ResilientStore {
+    inner: Arc<dyn VectorStore>,
+    breaker: CircuitBreaker,
+}
+
+impl ResilientStore {
+    fn new(inner: Arc<dyn VectorStore>, service: String, config: &ResilienceConfig) -> Self {
+        let probed = Arc::clone(&inner);
+        let breaker = CircuitBreaker::new(service, config).with_probe(Arc::new(move || {
+            let store = Arc::clone(&probed);
+            Box::pin(async move { store.health_check().await.is_ok() })
+        }));
+        Self { inner, breaker }
+    }
+}
+
+fn is_transient(_: &anyhow::Error) -> bool {
+    true
+}
+
+#[async_trait]
+impl VectorStore for ResilientStore {
+    async fn add(&self, documents: Vec<Document>) -> Result<()> {
+        self.breaker.call(is_transient, || self.inner.add(documents.clone())).await
+    }
+
+    async fn search(&self, query_embedding: &[f32], top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        self.breaker.call(is_transient, || self.inner.search(query_embedding, top_k, filter)).await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.breaker.call(is_transient, || self.inner.count()).await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.breaker.call(is_transient, || self.inner.clear()).await
+    }
+
+    async fn get_indexed_paths(&self) -> Result<Vec<String>> {
+        self.breaker.call(is_transient, || self.inner.get_indexed_paths()).await
+    }
+
+    async fn remove_by_source(&self, source_path: &str) -> Result<usize> {
+        self.breaker.call(is_transient, || self.inner.remove_by_source(source_path)).await
+    }
+
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>> {
+        self.breaker.call(is_transient, || self.inner.get_documents(source_path)).await
+    }
+
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>> {
+        self.breaker.call(is_transient, || self.inner.source_hashes(source_path)).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.inner.health_check().await
+    }
+
+    async fn disk_usage(&self) -> Result<Option<u64>> {
+        self.inner.disk_usage().await
+    }
+}
+
+/// A store that connects in the background; operations wait until it has.
+pub(crate) struct DeferredStore(Warmup<Arc<dyn VectorStore>>);
+
+impl DeferredStore {
+    /// Starts creating the store like [`create_vector_store`], naming it
+    /// `name` in the error if that fails.
+    pub(crate) fn spawn(
+        storage_config: StorageConfig,
+        vector_size: u64,
+        resilience: ResilienceConfig,
+        name: &'static str,
+    ) -> Self {
+        Self(Warmup::spawn(async move {
+            create_vector_store(storage_config, vector_size, &resilience)
+                .await
+                .map_err(|e| format!("{}: {}", name, e))
+        }))
+    }
+
+    pub(crate) fn warmup(&self) -> &Warmup<Arc<dyn VectorStore>> {
+        &self.0
+    }
+
+    async fn store(&self) -> Result<Arc<dyn VectorStore>> {
+        self.0.get().await.map_err(anyhow::Error::msg)
+    }
+}
+
+#[async_trait]
+impl VectorStore for DeferredStore {
+    async fn add(&self, documents: Vec<Document>) -> Result<()> {
+        self.store().await?.add(documents).await
+    }
+
+    async fn search(&self, query_embedding: &[f32], top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        self.store().await?.search(query_embedding, top_k, filter).await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.store().await?.count().await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.store().await?.clear().await
+    }
+
+    async fn get_indexed_paths(&self) -> Result<Vec<String>> {
+        self.store().await?.get_indexed_paths().await
+    }
+
+    async fn remove_by_source(&self, source_path: &str) -> Result<usize> {
+        self.store().await?.remove_by_source(source_path).await
+    }
+
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>> {
+        self.store().await?.get_documents(source_path).await
+    }
+
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>> {
+        self.store().await?.source_hashes(source_path).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.store().await?.health_check().await
+    }
+
+    async fn disk_usage(&self) -> Result<Option<u64>> {
+        self.store().await?.disk_usage().await
+    }
+}
+
+/// Attributes a store's allocations to [`Subsystem::Store`] for memory stats.
+struct TrackedStore(Arc<dyn VectorStore>);
+
+#[async_trait]
+impl VectorStore for TrackedStore {
+    async fn add(&self, documents: Vec<Document>) -> Result<()> {
+        memory::track(Subsystem::Store, self.0.add(documents)).await
+    }
+
+    async fn search(&self, query_embedding: &[f32], top_k: usize, filter: Option<&SearchFilter>) -> Result<Vec<SearchResult>> {
+        memory::track(Subsystem::Store, self.0.search(query_embedding, top_k, filter)).await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        memory::track(Subsystem::Store, self.0.count()).await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        memory::track(Subsystem::Store, self.0.clear()).await
+    }
+
+    async fn get_indexed_paths(&self) -> Result<Vec<String>> {
+        memory::track(Subsystem::Store, self.0.get_indexed_paths()).await
+    }
+
+    async fn remove_by_source(&self, source_path: &str) -> Result<usize> {
+        memory::track(Subsystem::Store, self.0.remove_by_source(source_path)).await
+    }
+
+    async fn get_documents(&self, source_path: Option<&str>) -> Result<Vec<Document>> {
+        memory::track(Subsystem::Store, self.0.get_documents(source_path)).await
+    }
+
+    async fn source_hashes(&self, source_path: &str) -> Result<Vec<(String, Option<String>)>> {
+        memory::track(Subsystem::Store, self.0.source_hashes(source_path)).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.0.health_check().await
+    }
+
+    async fn disk_usage(&self) -> Result<Option<u64>> {
+        self.0.disk_usage().await
     }
 }
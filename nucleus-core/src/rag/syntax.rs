@@ -0,0 +1,150 @@
+//! Syntax-aware chunking of source files (`tree-sitter` feature).
+//!
+//! Byte-offset chunking splits functions mid-body. Here a file is parsed
+//! and split on syntax node boundaries instead: top-level items (functions,
+//! structs, classes) are kept whole and packed together up to the chunk
+//! size, items too large for one chunk are split on their children (methods
+//! of an impl block, statements of a function), and only a single token
+//! larger than a chunk falls back to byte-offset chunking.
+//!
+//! Rust, Python, JavaScript, TypeScript, and Go are supported; other files
+//! use [`chunk_text`].
+
+use super::indexer::chunk_text;
+use std::ops::Range;
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser};
+
+/// Extensions of the files [`language`] has a grammar for.
+pub(crate) const EXTENSIONS: &[&str] = &["rs", "py", "pyi", "js", "jsx", "mjs", "cjs", "ts", "mts", "cts", "tsx", "go"];
+
+/// Grammar for the file at `path`, by extension.
+fn language(path: &Path) -> Option<Language> {
+    let language = match path.extension()?.to_str()? {
+        "rs" => tree_sitter_rust::LANGUAGE,
+        "py" | "pyi" => tree_sitter_python::LANGUAGE,
+        "js" | "jsx" | "mjs" | "cjs" => tree_sitter_javascript::LANGUAGE,
+        "ts" | "mts" | "cts" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+        "tsx" => tree_sitter_typescript::LANGUAGE_TSX,
+        "go" => tree_sitter_go::LANGUAGE,
+        _ => return None,
+    };
+    Some(language.into())
+}
+
+/// Splits the source file at `path` on syntax boundaries.
+///
+/// Returns `None` for unsupported languages or if the file cannot be
+/// parsed, so the caller can fall back to [`chunk_text`].
+pub(crate) fn chunk_source(path: &Path, text: &str, chunk_size: usize, overlap: usize) -> Option<Vec<String>> {
+    let language = language(path)?;
+    if text.len() <= chunk_size {
+        return Some(vec![text.to_string()]);
+    }
+
+    let mut parser = Parser::new();
+    parser.set_language(&language).ok()?;
+    let tree = parser.parse(text, None)?;
+
+    let mut pieces = Vec::new();
+    split(tree.root_node(), chunk_size, &mut pieces);
+    Some(pack(text, pieces, chunk_size, overlap))
+}
+
+/// Collects byte ranges of nodes that fit in a chunk, descending into the
+/// children of larger nodes.
+fn split(node: Node<'_>, chunk_size: usize, pieces: &mut Vec<Range<usize>>) {
+    if node.byte_range().len() <= chunk_size || node.child_count() == 0 {
+        pieces.push(node.byte_range());
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        split(child, chunk_size, pieces);
+    }
+}
+
+/// Packs consecutive pieces into chunks of at most `chunk_size` bytes.
+///
+/// A chunk spans from its first piece to its last, including whitespace
+/// between them.
+fn pack(text: &str, pieces: Vec<Range<usize>>, chunk_size: usize, overlap: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+
+    let flush = |range: Option<Range<usize>>, chunks: &mut Vec<String>| {
+        if let Some(range) = range {
+            let chunk = &text[range];
+            if !chunk.trim().is_empty() {
+                chunks.push(chunk.to_string());
+            }
+        }
+    };
+
+    for piece in pieces {
+        if piece.len() > chunk_size {
+            flush(current.take(), &mut chunks);
+            chunks.extend(chunk_text(&text[piece], chunk_size, overlap));
+            continue;
+        }
+
+        match &mut current {
+            Some(range) if piece.end - range.start <= chunk_size => range.end = piece.end,
+            _ => {
+                flush(current.replace(piece), &mut chunks);
+            }
+        }
+    }
+    flush(current, &mut chunks);
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_items_stay_whole() {
+        let source = "\
+/// Adds numbers.
+fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+fn main() {
+    println!(\"{}\", add(1, 2));
+}
+";
+        let chunks = chunk_source(Path::new("main.rs"), source, 80, 10).unwrap();
+
+        assert_eq!(chunks[0], "/// Adds numbers.\nfn add(a: i32, b: i32) -> i32 {\n    a + b\n}");
+        assert!(chunks.iter().any(|chunk| chunk.starts_with("struct Point {") && chunk.ends_with('}')));
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 80));
+    }
+
+    #[test]
+    fn test_large_function_splits_on_statements() {
+        let body: String = (0..20).map(|i| format!("    value = value * {} + {}\n", i, i)).collect();
+        let source = format!("def compute(value):\n{}    return value\n", body);
+
+        let chunks = chunk_source(Path::new("compute.py"), &source, 120, 10).unwrap();
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 120));
+        // Statements are never cut in half
+        for line in source.lines() {
+            assert!(chunks.iter().any(|chunk| chunk.contains(line.trim())), "split: {}", line);
+        }
+    }
+
+    #[test]
+    fn test_unknown_language() {
+        assert!(chunk_source(Path::new("notes.md"), "# Notes", 10, 2).is_none());
+    }
+}
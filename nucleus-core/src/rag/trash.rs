@@ -0,0 +1,265 @@
+//! Trash for documents removed from the knowledge base (`storage.trash`).
+//!
+//! Clearing a knowledge base, removing a source, and deleting a collection
+//! first copy the documents they remove, embeddings included, into the
+//! trash: one gzip-compressed JSONL file per removal, holding a
+//! [`TrashEntry`] line and then one line per document. Restoring an entry
+//! adds the documents back as they were, so nothing is re-embedded. Entries
+//! older than `retention_days` are purged whenever the trash is written to
+//! or listed.
+
+use super::types::Document;
+use crate::config::TrashConfig;
+use crate::feedback::unix_timestamp;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use thiserror::Error;
+
+const EXTENSION: &str = "jsonl.gz";
+
+#[derive(Debug, Error)]
+pub enum TrashError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid trash entry: {0}")]
+    Format(#[from] serde_json::Error),
+
+    #[error("No trash entry '{0}'; `nucleus trash list` shows the entries")]
+    NotFound(String),
+
+    #[error("The trash is disabled (storage.trash.enabled)")]
+    Disabled,
+}
+
+pub type Result<T> = std::result::Result<T, TrashError>;
+
+/// Where trashed documents came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashOrigin {
+    /// A collection of the local knowledge base, by name
+    Collection(String),
+    /// The shared team knowledge base
+    Team,
+}
+
+impl fmt::Display for TrashOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Collection(name) => write!(f, "collection '{}'", name),
+            Self::Team => f.write_str("shared knowledge base"),
+        }
+    }
+}
+
+/// A removal kept in the trash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    /// Unix timestamp (seconds) of the removal
+    pub timestamp: u64,
+    pub origin: TrashOrigin,
+    /// Removed file or directory; `None` if everything was removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Project directories of a deleted collection, which restoring recreates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roots: Option<Vec<PathBuf>>,
+    /// Number of documents removed
+    pub documents: usize,
+}
+
+impl TrashEntry {
+    pub(crate) fn new(origin: TrashOrigin, source: Option<&str>, documents: usize) -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let timestamp = unix_timestamp();
+        let sequence = COUNTER.fetch_add(1, Ordering::Relaxed) & 0xff;
+        Self {
+            id: format!("t{:x}{:02x}", timestamp, sequence),
+            timestamp,
+            origin,
+            source: source.map(str::to_string),
+            roots: None,
+            documents,
+        }
+    }
+
+    /// Records the roots of a collection deleted along with its documents.
+    pub(crate) fn with_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.roots = Some(roots);
+        self
+    }
+}
+
+/// The trash directory.
+#[derive(Debug, Clone)]
+pub(crate) struct Trash {
+    path: PathBuf,
+    retention_secs: u64,
+}
+
+impl Trash {
+    pub(crate) fn new(config: &TrashConfig) -> Self {
+        Self {
+            path: PathBuf::from(&config.path),
+            retention_secs: config.retention_days.saturating_mul(24 * 60 * 60),
+        }
+    }
+
+    /// Writes `documents` to the trash under `entry`.
+    pub(crate) fn put(&self, entry: &TrashEntry, documents: &[Document]) -> Result<()> {
+        self.expire()?;
+        std::fs::create_dir_all(&self.path)?;
+
+        // Written aside first, so a crash never leaves half an entry
+        let file = self.file(&entry.id)?;
+        let partial = file.with_extension("partial");
+        let mut writer = BufWriter::new(GzEncoder::new(File::create(&partial)?, Compression::fast()));
+        serde_json::to_writer(&mut writer, entry)?;
+        writer.write_all(b"\n")?;
+        for document in documents {
+            serde_json::to_writer(&mut writer, document)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.into_inner().map_err(|e| e.into_error())?.finish()?;
+        std::fs::rename(&partial, &file)?;
+        Ok(())
+    }
+
+    /// The entries in the trash, newest first.
+    pub(crate) fn list(&self) -> Result<Vec<TrashEntry>> {
+        self.expire()?;
+        let mut entries: Vec<TrashEntry> = self
+            .files()?
+            .iter()
+            .filter_map(|path| read_entry(path).ok())
+            .collect();
+        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| b.id.cmp(&a.id)));
+        Ok(entries)
+    }
+
+    /// Entry `id` and its documents.
+    pub(crate) fn read(&self, id: &str) -> Result<(TrashEntry, Vec<Document>)> {
+        let file = self.existing(id)?;
+        let mut lines = BufReader::new(GzDecoder::new(File::open(file)?)).lines();
+        let entry = serde_json::from_str(&lines.next().transpose()?.unwrap_or_default())?;
+        let documents = lines
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect::<Result<Vec<Document>>>()?;
+        Ok((entry, documents))
+    }
+
+    /// Deletes entry `id`, or every entry if `None`, returning how many were deleted.
+    pub(crate) fn purge(&self, id: Option<&str>) -> Result<usize> {
+        let files = match id {
+            Some(id) => vec![self.existing(id)?],
+            None => self.files()?,
+        };
+        for file in &files {
+            std::fs::remove_file(file)?;
+        }
+        Ok(files.len())
+    }
+
+    /// Deletes the entries older than the retention period.
+    fn expire(&self) -> Result<()> {
+        let cutoff = unix_timestamp().saturating_sub(self.retention_secs);
+        for file in self.files()? {
+            if read_entry(&file).is_ok_and(|entry| entry.timestamp < cutoff) {
+                std::fs::remove_file(file)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        let entries = match std::fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut files = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.to_string_lossy().ends_with(EXTENSION) {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    fn file(&self, id: &str) -> Result<PathBuf> {
+        // IDs end up in file names, so anything else is not an entry
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(TrashError::NotFound(id.to_string()));
+        }
+        Ok(self.path.join(format!("{}.{}", id, EXTENSION)))
+    }
+
+    fn existing(&self, id: &str) -> Result<PathBuf> {
+        let file = self.file(id)?;
+        if !file.exists() {
+            return Err(TrashError::NotFound(id.to_string()));
+        }
+        Ok(file)
+    }
+}
+
+/// Reads just the entry line of a trash file.
+fn read_entry(path: &Path) -> Result<TrashEntry> {
+    let mut line = String::new();
+    BufReader::new(GzDecoder::new(File::open(path)?)).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_put_restore_and_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash = Trash::new(&TrashConfig {
+            enabled: true,
+            path: dir.path().join("trash").to_string_lossy().to_string(),
+            retention_days: 7,
+        });
+        assert!(trash.list().unwrap().is_empty());
+
+        let documents = vec![
+            Document::new("a", "fn main() {}", vec![0.25, -1.0]).with_metadata("source", "/src/main.rs"),
+            Document::new("b", "# Notes", vec![0.5, 0.5]).with_metadata("source", "/src/notes.md"),
+        ];
+        let cleared = TrashEntry::new(TrashOrigin::Collection("default".to_string()), None, documents.len());
+        trash.put(&cleared, &documents).unwrap();
+        let removed = TrashEntry::new(TrashOrigin::Team, Some("/src/main.rs"), 1);
+        trash.put(&removed, &documents[..1]).unwrap();
+
+        let entries = trash.list().unwrap();
+        assert_eq!(entries, vec![removed.clone(), cleared.clone()]);
+        let (entry, restored) = trash.read(&cleared.id).unwrap();
+        assert_eq!(entry, cleared);
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored[0].embedding, vec![0.25, -1.0]);
+        assert_eq!(restored[1].metadata.get("source").map(String::as_str), Some("/src/notes.md"));
+
+        assert!(matches!(trash.read("../collections"), Err(TrashError::NotFound(_))));
+        assert_eq!(trash.purge(Some(&removed.id)).unwrap(), 1);
+        assert!(matches!(trash.purge(Some(&removed.id)), Err(TrashError::NotFound(_))));
+
+        let old = TrashEntry {
+            timestamp: unix_timestamp() - 8 * 24 * 60 * 60,
+            ..TrashEntry::new(TrashOrigin::Collection("docs".to_string()), None, 0)
+        };
+        trash.put(&old, &[]).unwrap();
+        assert_eq!(trash.list().unwrap(), vec![cleared]);
+        assert_eq!(trash.purge(None).unwrap(), 1);
+    }
+}
@@ -0,0 +1,144 @@
+//! Trust levels of sources (`rag.source_trust`).
+//!
+//! When official documentation, your own notes, and a random web page
+//! disagree, answers should follow the documentation. Each result's score is
+//! multiplied by the weight of the first rule matching its source or
+//! collection, and levels other than `normal` are recorded as `trust`
+//! metadata, which [`format_context`](super::format_context) shows to the
+//! model along with which sources to prefer.
+
+use super::filter::glob;
+use super::types::SearchResult;
+use crate::config::{SourceTrustRule, TrustLevel};
+use crate::dotfiles::expand_home;
+use std::path::Path;
+
+/// The configured trust rules.
+#[derive(Debug, Clone, Default)]
+pub struct SourceTrust {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    /// Source pattern with `~` expanded
+    source: Option<String>,
+    collection: Option<String>,
+    trust: TrustLevel,
+    weight: f32,
+}
+
+impl SourceTrust {
+    /// Rules from `rag.source_trust`, with a leading `~` in source patterns
+    /// expanded to `home`, written with `/` separators like indexed sources.
+    pub fn new(rules: &[SourceTrustRule], home: &Path) -> Self {
+        let rules = rules
+            .iter()
+            .map(|rule| Rule {
+                source: rule
+                    .source
+                    .as_deref()
+                    .map(|pattern| super::indexer::source_key(&expand_home(pattern, home))),
+                collection: rule.collection.clone(),
+                trust: rule.trust,
+                weight: rule.weight.unwrap_or_else(|| rule.trust.default_weight()).max(0.0),
+            })
+            .collect();
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Trust level and score weight of `result`, from the first matching rule.
+    pub fn of(&self, result: &SearchResult) -> Option<(TrustLevel, f32)> {
+        let metadata = &result.document.metadata;
+        let matches = |pattern: &Option<String>, key: &str, test: fn(&str, &str) -> bool| match pattern {
+            Some(pattern) => metadata.get(key).is_some_and(|value| test(value, pattern)),
+            None => true,
+        };
+        self.rules
+            .iter()
+            .find(|rule| matches(&rule.source, "source", glob) && matches(&rule.collection, "collection", |a, b| a == b))
+            .map(|rule| (rule.trust, rule.weight))
+    }
+
+    /// Multiplies the scores of `results` by their weights, records their
+    /// trust levels, and sorts them by the weighted score.
+    pub fn weigh(&self, results: &mut [SearchResult]) {
+        if self.is_empty() {
+            return;
+        }
+        for result in results.iter_mut() {
+            if let Some((trust, weight)) = self.of(result) {
+                result.score *= weight;
+                if trust != TrustLevel::Normal {
+                    result.document.metadata.insert("trust".to_string(), trust.as_str().to_string());
+                }
+            }
+        }
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    }
+}
+
+/// Trust level recorded on `result` by [`SourceTrust::weigh`], if not `normal`.
+pub fn trust_label(result: &SearchResult) -> Option<&str> {
+    result.document.metadata.get("trust").map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag::Document;
+
+    fn result(source: &str, collection: Option<&str>, score: f32) -> SearchResult {
+        let mut document = Document::new(source, "content", Vec::new()).with_metadata("source", source);
+        if let Some(collection) = collection {
+            document = document.with_metadata("collection", collection);
+        }
+        SearchResult { document, score }
+    }
+
+    fn rule(source: Option<&str>, collection: Option<&str>, trust: TrustLevel) -> SourceTrustRule {
+        SourceTrustRule {
+            source: source.map(str::to_string),
+            collection: collection.map(str::to_string),
+            trust,
+            weight: None,
+        }
+    }
+
+    #[test]
+    fn test_weigh_by_first_matching_rule() {
+        let trust = SourceTrust::new(
+            &[
+                rule(Some("https://docs.rs/*"), None, TrustLevel::Authoritative),
+                rule(Some("~/notes/*"), None, TrustLevel::Trusted),
+                rule(None, Some("team"), TrustLevel::Normal),
+                rule(Some("http*://*"), None, TrustLevel::Low),
+            ],
+            Path::new("/home/me"),
+        );
+        let mut results = vec![
+            result("https://blog.example.com/tokio", None, 0.9),
+            result("/home/me/notes/tokio.md", None, 0.8),
+            result("https://docs.rs/tokio", None, 0.75),
+            result("/src/main.rs", Some("team"), 0.7),
+        ];
+        trust.weigh(&mut results);
+
+        let ranked: Vec<(&str, Option<&str>)> = results
+            .iter()
+            .map(|result| (result.document.id.as_str(), trust_label(result)))
+            .collect();
+        assert_eq!(ranked, [
+            ("https://docs.rs/tokio", Some("authoritative")),
+            ("/home/me/notes/tokio.md", Some("trusted")),
+            ("https://blog.example.com/tokio", Some("low")),
+            ("/src/main.rs", None),
+        ]);
+        assert!((results[2].score - 0.9 * 0.8).abs() < 1e-6);
+        assert!(SourceTrust::default().of(&results[0]).is_none());
+    }
+}
@@ -64,3 +64,81 @@ pub struct SearchResult {
     pub document: Document,
     pub score: f32,
 }
+
+/// A file (or URL) and chunk that context for a response came from.
+///
+/// Displayed as `path:chunk`, e.g. `src/main.rs:3`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Source {
+    /// The document's `source` metadata
+    pub path: String,
+    /// Index of the chunk within the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<usize>,
+    /// Page the chunk is on, for paged documents such as PDFs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+}
+
+impl Source {
+    /// The source of a search result; `None` for documents without a `source`.
+    pub fn of(result: &SearchResult) -> Option<Self> {
+        let metadata = &result.document.metadata;
+        Some(Self {
+            path: metadata.get("source")?.clone(),
+            chunk: metadata.get("chunk").and_then(|chunk| chunk.parse().ok()),
+            page: metadata.get("page").and_then(|page| page.parse().ok()),
+        })
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(chunk) = self.chunk {
+            write!(f, ":{}", chunk)?;
+        }
+        if let Some(page) = self.page {
+            write!(f, " (page {})", page)?;
+        }
+        Ok(())
+    }
+}
+
+/// The chunks stored for one file, URL, or note, see
+/// [`RagEngine::source_stats`](super::RagEngine::source_stats).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceStats {
+    /// The documents' `source` metadata
+    pub source: String,
+    pub chunks: usize,
+    /// When the source was last indexed, in seconds since the Unix epoch;
+    /// `None` if it was indexed before this was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexed_at: Option<u64>,
+    /// Modification time of the file when it was indexed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+}
+
+/// How far [`RagEngine::index_directory_with`](super::RagEngine::index_directory_with) has got.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexProgress {
+    /// Files stored, unchanged, or skipped so far
+    pub files_done: usize,
+    pub files_total: usize,
+    /// File whose chunks were embedded last
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
+    /// Files whose last chunks were stored since the previous update
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub finished: Vec<String>,
+}
+
+/// Context for an LLM prompt together with the sources it was built from.
+#[derive(Debug, Clone, Default)]
+pub struct RetrievedContext {
+    /// Formatted as by [`format_context`](super::format_context)
+    pub text: String,
+    pub sources: Vec<Source>,
+}
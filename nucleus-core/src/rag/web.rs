@@ -0,0 +1,398 @@
+//! Fetching web pages and extracting their readable text, for indexing URLs.
+//!
+//! Indexing a page as-is buries its content in navigation, scripts, and
+//! footers. In the spirit of readability tools, [`readable_text`] keeps the
+//! page's `<main>` element, or its longest `<article>`, or else its
+//! `<body>`, drops elements that never hold content (scripts, styles, menus,
+//! headers, footers, sidebars, forms), and turns the rest into plain text
+//! with a line per block. Whitespace in `<pre>` blocks is kept, so code
+//! samples stay intact.
+//!
+//! The tokenizer is deliberately forgiving rather than a full HTML parser:
+//! unclosed and stray tags are tolerated, and only the entities commonly
+//! found in text are decoded.
+
+use reqwest::header::{CONTENT_TYPE, USER_AGENT};
+use std::ops::Range;
+use std::time::Duration;
+
+/// Largest page fetched, so a huge download cannot stall indexing.
+const MAX_PAGE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Elements whose content is never part of the readable text.
+const SKIPPED: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "canvas", "iframe",
+    "nav", "header", "footer", "aside", "form", "button", "select",
+];
+
+/// Elements whose content is separated from its surroundings by a blank line.
+const PARAGRAPHS: &[&str] = &["p", "h1", "h2", "h3", "h4", "h5", "h6", "pre", "blockquote", "table", "ul", "ol", "dl"];
+
+/// Elements whose content starts on a new line.
+const BLOCKS: &[&str] = &[
+    "br", "div", "section", "article", "main", "li", "tr", "dt", "dd", "hr", "figure", "figcaption", "details", "summary",
+];
+
+/// Elements without content or closing tag.
+const VOID: &[&str] = &["br", "hr", "img", "input", "meta", "link", "source", "wbr", "area", "base", "col", "embed", "track"];
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Text(&'a str),
+    Open(String),
+    Close(String),
+}
+
+/// Fetches `url` and returns its text: the readable text of HTML pages,
+/// other text (plain text, Markdown, JSON) as it is.
+///
+/// Fails with a message for anything but a successful text response over
+/// HTTP(S).
+pub(crate) async fn fetch_text(url: &str) -> Result<String, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("only http and https URLs can be indexed, not {}", parsed.scheme()));
+    }
+
+    let response = reqwest::Client::new()
+        .get(parsed)
+        .header(USER_AGENT, format!("nucleus/{}", crate::VERSION))
+        .timeout(Duration::from_secs(30))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    if response.content_length().is_some_and(|len| len > MAX_PAGE_BYTES) {
+        return Err(format!("the page is larger than {} MB", MAX_PAGE_BYTES / 1024 / 1024));
+    }
+
+    // Servers that do not say are most likely serving HTML
+    let content_type = response.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .unwrap_or("text/html")
+        .trim()
+        .to_ascii_lowercase();
+    let body = response.text().await.map_err(|e| e.to_string())?;
+
+    if content_type.contains("html") {
+        Ok(readable_text(&body))
+    } else if content_type.starts_with("text/") || content_type.ends_with("json") || content_type.ends_with("xml") {
+        Ok(body)
+    } else {
+        Err(format!("{} content cannot be indexed", content_type))
+    }
+}
+
+/// The title and main content of `html` as plain text.
+pub(crate) fn readable_text(html: &str) -> String {
+    let tokens = tokenize(html);
+
+    let title = element_ranges(&tokens, "title").into_iter().next().map(|range| {
+        let title: String = tokens[range].iter()
+            .filter_map(|token| match token {
+                Token::Text(text) => Some(decode_entities(text)),
+                _ => None,
+            })
+            .collect();
+        collapse_whitespace(&title).trim().to_string()
+    });
+
+    let longest = |name: &str| {
+        element_ranges(&tokens, name).into_iter().max_by_key(|range| text_len(&tokens[range.clone()]))
+    };
+    let content = longest("main")
+        .or_else(|| longest("article"))
+        .or_else(|| longest("body"))
+        .unwrap_or(0..tokens.len());
+    let body = render(&tokens[content]);
+
+    match title.filter(|title| !title.is_empty() && !body.starts_with(title.as_str())) {
+        Some(title) => format!("{}\n\n{}", title, body).trim_end().to_string(),
+        None => body,
+    }
+}
+
+/// Splits `html` into text and tags, dropping comments, doctypes, and the
+/// raw content of scripts and styles.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+
+        let closing = rest[1..].starts_with('/');
+        let name: String = rest[1 + closing as usize..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if name.is_empty() {
+            // A lone `<` in text
+            tokens.push(Token::Text("<"));
+            rest = &rest[1..];
+            continue;
+        }
+
+        let end = tag_end(rest);
+        let self_closing = rest[..end].ends_with("/>");
+        rest = &rest[end..];
+
+        if closing {
+            tokens.push(Token::Close(name));
+        } else if name == "script" || name == "style" {
+            // Raw text may contain `<`; skip to the closing tag
+            let lower = rest.to_ascii_lowercase();
+            let close = format!("</{}", name);
+            rest = lower.find(&close).map_or("", |at| {
+                let after = &rest[at..];
+                after.find('>').map_or("", |end| &after[end + 1..])
+            });
+        } else {
+            let void = self_closing || VOID.contains(&name.as_str());
+            tokens.push(Token::Open(name.clone()));
+            if void {
+                tokens.push(Token::Close(name));
+            }
+        }
+    }
+    tokens
+}
+
+/// Length of the tag at the start of `html`, honoring quoted attribute values.
+fn tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in html.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return i + 1,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+/// Token ranges of the outermost `name` elements, including their tags.
+fn element_ranges(tokens: &[Token], name: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Open(open) if open == name => {
+                if depth == 0 {
+                    start = i;
+                }
+                depth += 1;
+            }
+            Token::Close(close) if close == name && depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    ranges.push(start..i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        ranges.push(start..tokens.len());
+    }
+    ranges
+}
+
+/// Length of the readable text in `tokens`, to compare candidate elements.
+fn text_len(tokens: &[Token]) -> usize {
+    render(tokens).len()
+}
+
+/// Plain text of `tokens`, without the content of [`SKIPPED`] elements.
+fn render(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let mut skipped: Vec<&str> = Vec::new();
+    let mut pre = 0usize;
+
+    for token in tokens {
+        match token {
+            Token::Open(name) if SKIPPED.contains(&name.as_str()) => skipped.push(name),
+            Token::Close(name) if skipped.last() == Some(&name.as_str()) => {
+                skipped.pop();
+            }
+            _ if !skipped.is_empty() => {}
+            Token::Open(name) | Token::Close(name) => {
+                let opening = matches!(token, Token::Open(_));
+                if name == "pre" {
+                    pre = if opening { pre + 1 } else { pre.saturating_sub(1) };
+                }
+                if PARAGRAPHS.contains(&name.as_str()) {
+                    break_line(&mut out, 2);
+                } else if BLOCKS.contains(&name.as_str()) && (opening || name != "br") {
+                    break_line(&mut out, 1);
+                } else if name == "td" || name == "th" {
+                    push_text(&mut out, " ");
+                }
+            }
+            Token::Text(text) => {
+                let text = decode_entities(text);
+                if pre > 0 {
+                    out.push_str(&text);
+                } else {
+                    push_text(&mut out, &collapse_whitespace(&text));
+                }
+            }
+        }
+    }
+    out.trim().to_string()
+}
+
+/// Appends collapsed text, without leading space at the start of a line.
+fn push_text(out: &mut String, text: &str) {
+    let text = if out.is_empty() || out.ends_with('\n') || out.ends_with(' ') {
+        text.trim_start()
+    } else {
+        text
+    };
+    out.push_str(text);
+}
+
+/// Ends the current line, leaving `lines - 1` blank lines before the next.
+fn break_line(out: &mut String, lines: usize) {
+    let trimmed = out.trim_end_matches([' ', '\t']).len();
+    out.truncate(trimmed);
+    if out.is_empty() {
+        return;
+    }
+    let existing = out.len() - out.trim_end_matches('\n').len();
+    for _ in existing..lines {
+        out.push('\n');
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+        } else {
+            if space {
+                collapsed.push(' ');
+                space = false;
+            }
+            collapsed.push(c);
+        }
+    }
+    if space {
+        collapsed.push(' ');
+    }
+    collapsed
+}
+
+/// Decodes the named entities common in text and numeric character references.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..].find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..end + 1])?, end + 2)));
+        match entity {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "copy" => '©',
+        "rsquo" => '’',
+        "lsquo" => '‘',
+        "rdquo" => '”',
+        "ldquo" => '“',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readable_text_keeps_main_content() {
+        let html = r#"<!DOCTYPE html>
+<html><head><title>Config &amp; setup</title><style>p { color: red }</style></head>
+<body>
+  <header><nav><a href="/">Home</a> | <a href="/docs">Docs</a></nav></header>
+  <main>
+    <h1>Configuration</h1>
+    <p>Settings are read from
+       <code>config.yaml</code> at startup.</p>
+    <!-- TODO: document team mode -->
+    <pre>storage:
+  top_k: 5</pre>
+    <ul><li>Fast &lt;1ms</li><li>Local&#8209;first</li></ul>
+    <script>if (a < b) { track(); }</script>
+  </main>
+  <footer>&copy; 2024 Example</footer>
+</body></html>"#;
+
+        assert_eq!(
+            readable_text(html),
+            "Config & setup\n\nConfiguration\n\nSettings are read from config.yaml at startup.\n\nstorage:\n  top_k: 5\n\nFast <1ms\nLocal\u{2011}first"
+        );
+    }
+
+    #[test]
+    fn test_readable_text_without_main_uses_longest_article() {
+        let html = "<body><p>Menu</p><article>Short</article><article><p>The real story, \
+            which is longer.</p></article><div class=\"ad\">Buy now</div></body>";
+        assert_eq!(readable_text(html), "The real story, which is longer.");
+        assert_eq!(readable_text("Just text, 1 < 2 & 3 > 2"), "Just text, 1 < 2 & 3 > 2");
+    }
+}
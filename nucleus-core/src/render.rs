@@ -0,0 +1,534 @@
+//! Rendering of tables and JSON in streamed responses.
+//!
+//! Models answer with markdown tables and fenced JSON, which a terminal
+//! shows as the model wrote them: wrapped table rows lose their columns in
+//! a narrow terminal, and JSON on one line is hard to read. A
+//! [`BlockRenderer`] passes prose through a [`LineWrapper`] as it streams
+//! and holds back each table and ```` ```json ```` block until it is
+//! complete, then draws tables with aligned borders (wrapping cells to fit
+//! the width, or listing each row as `header: value` lines when even that
+//! does not fit) and pretty-prints JSON with syntax highlighting. The
+//! blocks as written are kept, for saving them to a file.
+
+use crate::text::{display_width, LineWrapper};
+
+const BOLD: &str = "\x1b[1m";
+const KEY: &str = "\x1b[36m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const LITERAL: &str = "\x1b[35m";
+const RESET: &str = "\x1b[0m";
+
+/// Narrowest a table column is wrapped to before rows are listed instead.
+const MIN_COLUMN_WIDTH: usize = 4;
+
+/// What a structured block holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    Table,
+    Json,
+}
+
+impl BlockKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Table => "table",
+            Self::Json => "JSON",
+        }
+    }
+
+    /// File extension for the block as written.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Table => "md",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// A block as the model wrote it, without code fences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawBlock {
+    pub kind: BlockKind,
+    pub raw: String,
+}
+
+/// The block a line belongs to.
+#[derive(Debug)]
+enum Block {
+    None,
+    /// A code block other than JSON, passed through
+    Fence,
+    Table(Vec<String>),
+    Json { fence: String, lines: Vec<String> },
+}
+
+/// Renders streamed text, drawing tables and JSON blocks once complete.
+#[derive(Debug)]
+pub struct BlockRenderer {
+    wrapper: LineWrapper,
+    width: usize,
+    color: bool,
+    structured: bool,
+    block: Block,
+    /// Start of the current line, held until it shows whether it belongs to a block
+    line: String,
+    /// Whether the current line is held until its end
+    holding: bool,
+    /// Whether the rest of the current line is prose
+    passing: bool,
+    /// Prose not yet handed to the wrapper
+    prose: String,
+    blocks: Vec<RawBlock>,
+}
+
+impl BlockRenderer {
+    /// Creates a renderer wrapping at `width` columns (0 disables wrapping),
+    /// highlighting with ANSI colors if `color` is set.
+    pub fn new(width: usize, color: bool) -> Self {
+        Self {
+            wrapper: LineWrapper::new(width),
+            width,
+            color,
+            structured: true,
+            block: Block::None,
+            line: String::new(),
+            holding: false,
+            passing: false,
+            prose: String::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Whether tables and JSON are drawn; if not, text is only wrapped.
+    pub fn with_structured(mut self, structured: bool) -> Self {
+        self.structured = structured;
+        self
+    }
+
+    /// Takes the next piece of text, returning what can be printed so far.
+    pub fn push(&mut self, text: &str) -> String {
+        if !self.structured {
+            return self.wrapper.push(text);
+        }
+        let mut out = String::new();
+        for c in text.chars() {
+            if self.passing {
+                self.prose.push(c);
+                self.passing = c != '\n';
+                continue;
+            }
+            if c == '\n' {
+                let line = std::mem::take(&mut self.line);
+                self.end_line(line, &mut out);
+                continue;
+            }
+            self.line.push(c);
+            if self.holding || c.is_whitespace() {
+                continue;
+            }
+
+            // The first character of the line decides whether it can be part of a block
+            self.holding = match self.block {
+                Block::Json { .. } => true,
+                Block::Fence => c == '`',
+                _ => c == '|' || c == '`',
+            };
+            if !self.holding {
+                self.close_table(&mut out);
+                self.prose.push_str(&std::mem::take(&mut self.line));
+                self.passing = true;
+            }
+        }
+        self.flush_prose(&mut out);
+        out
+    }
+
+    /// Returns the text held back at the end of the stream.
+    pub fn finish(&mut self) -> String {
+        if !self.structured {
+            return self.wrapper.finish();
+        }
+        let mut out = String::new();
+        // The last line ends with the stream, not a line break
+        let unterminated = !self.line.is_empty();
+        if unterminated {
+            let line = std::mem::take(&mut self.line);
+            self.end_line(line, &mut out);
+        }
+        self.close_table(&mut out);
+        if let Block::Json { fence, lines } = std::mem::replace(&mut self.block, Block::None) {
+            self.close_json(fence, lines, None, &mut out);
+        }
+        self.flush_prose(&mut out);
+        out.push_str(&self.wrapper.finish());
+        if unterminated && out.ends_with('\n') {
+            out.pop();
+        }
+        out
+    }
+
+    /// The tables and JSON blocks drawn so far, in order.
+    pub fn blocks(&self) -> &[RawBlock] {
+        &self.blocks
+    }
+
+    fn end_line(&mut self, line: String, out: &mut String) {
+        self.holding = false;
+        let trimmed = line.trim();
+        match &mut self.block {
+            Block::Json { .. } if trimmed.starts_with("```") => {
+                if let Block::Json { fence, lines } = std::mem::replace(&mut self.block, Block::None) {
+                    self.close_json(fence, lines, Some(line), out);
+                }
+            }
+            Block::Json { lines, .. } => lines.push(line),
+            Block::Fence => {
+                if trimmed.starts_with("```") {
+                    self.block = Block::None;
+                }
+                self.prose.push_str(&line);
+                self.prose.push('\n');
+            }
+            Block::Table(rows) if trimmed.starts_with('|') => rows.push(line),
+            Block::Table(_) => {
+                self.close_table(out);
+                self.end_line(line, out);
+            }
+            Block::None => {
+                if let Some(info) = trimmed.strip_prefix("```") {
+                    if info.trim().eq_ignore_ascii_case("json") {
+                        self.block = Block::Json { fence: line, lines: Vec::new() };
+                        return;
+                    }
+                    self.block = Block::Fence;
+                } else if trimmed.starts_with('|') {
+                    self.block = Block::Table(vec![line]);
+                    return;
+                }
+                self.prose.push_str(&line);
+                self.prose.push('\n');
+            }
+        }
+    }
+
+    fn close_table(&mut self, out: &mut String) {
+        if !matches!(self.block, Block::Table(_)) {
+            return;
+        }
+        let Block::Table(rows) = std::mem::replace(&mut self.block, Block::None) else {
+            unreachable!();
+        };
+        let cells: Vec<Vec<String>> = rows.iter().map(|row| split_row(row)).collect();
+        let aligns = cells.get(1).and_then(|separator| alignments(separator));
+        match aligns {
+            Some(aligns) if !cells[0].is_empty() => {
+                let mut body: Vec<Vec<String>> = cells.into_iter().enumerate().filter(|(i, _)| *i != 1).map(|(_, row)| row).collect();
+                for row in &mut body {
+                    row.resize(aligns.len(), String::new());
+                }
+                self.emit(render_table(&body, &aligns, self.width, self.color), out);
+                self.blocks.push(RawBlock { kind: BlockKind::Table, raw: rows.join("\n") });
+            }
+            // Not a table after all
+            _ => {
+                for row in rows {
+                    self.prose.push_str(&row);
+                    self.prose.push('\n');
+                }
+            }
+        }
+    }
+
+    fn close_json(&mut self, fence: String, lines: Vec<String>, closing: Option<String>, out: &mut String) {
+        let raw = lines.join("\n");
+        match format_json(&raw, self.color) {
+            Some(formatted) => {
+                self.emit(formatted, out);
+                self.blocks.push(RawBlock { kind: BlockKind::Json, raw });
+            }
+            None => {
+                for line in std::iter::once(fence).chain(lines).chain(closing) {
+                    self.prose.push_str(&line);
+                    self.prose.push('\n');
+                }
+            }
+        }
+    }
+
+    /// Writes a drawn block after the prose before it.
+    fn emit(&mut self, block: String, out: &mut String) {
+        self.flush_prose(out);
+        out.push_str(&block);
+    }
+
+    fn flush_prose(&mut self, out: &mut String) {
+        if !self.prose.is_empty() {
+            out.push_str(&self.wrapper.push(&std::mem::take(&mut self.prose)));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Cells of a `| a | b |` row, unescaping `\|`.
+fn split_row(row: &str) -> Vec<String> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = match row.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => row,
+    };
+
+    let mut cells = vec![String::new()];
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cells.last_mut().unwrap().push('|');
+                chars.next();
+            }
+            '|' => cells.push(String::new()),
+            _ => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+/// Column alignments of a `|---|:---:|` separator row.
+fn alignments(cells: &[String]) -> Option<Vec<Align>> {
+    cells
+        .iter()
+        .map(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.bytes().all(|b| b == b'-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Align::Center,
+                (false, true) => Align::Right,
+                _ => Align::Left,
+            })
+        })
+        .collect()
+}
+
+/// Draws `rows` (the header first) in a box at most `width` columns wide.
+fn render_table(rows: &[Vec<String>], aligns: &[Align], width: usize, color: bool) -> String {
+    let columns = aligns.len();
+    let mut widths: Vec<usize> = (0..columns)
+        .map(|i| rows.iter().map(|row| display_width(&row[i])).max().unwrap_or(0).max(1))
+        .collect();
+    let frame = 3 * columns + 1;
+    if width > 0 && widths.iter().sum::<usize>() + frame > width {
+        let available = width.saturating_sub(frame);
+        if available < columns * MIN_COLUMN_WIDTH {
+            return render_records(rows, width, color);
+        }
+        // Narrow the widest columns first
+        while widths.iter().sum::<usize>() > available {
+            let widest = (0..columns).max_by_key(|&i| widths[i]).unwrap_or_default();
+            widths[widest] -= 1;
+        }
+    }
+
+    let border = |left: &str, middle: &str, right: &str| {
+        let segments: Vec<String> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+        format!("{}{}{}\n", left, segments.join(middle), right)
+    };
+    let mut out = border("┌", "┬", "┐");
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<Vec<String>> = row.iter().zip(&widths).map(|(cell, &width)| wrap(cell, width)).collect();
+        let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+        for line in 0..height {
+            out.push('│');
+            for (column, cell) in cells.iter().enumerate() {
+                let text = cell.get(line).map(String::as_str).unwrap_or_default();
+                let padded = pad(text, widths[column], aligns[column]);
+                if i == 0 && color {
+                    out.push_str(&format!(" {}{}{} │", BOLD, padded, RESET));
+                } else {
+                    out.push_str(&format!(" {} │", padded));
+                }
+            }
+            out.push('\n');
+        }
+        if i == 0 {
+            out.push_str(&border("├", "┼", "┤"));
+        }
+    }
+    out.push_str(&border("└", "┴", "┘"));
+    out
+}
+
+/// Lists each row as `header: value` lines, for tables too wide to draw.
+fn render_records(rows: &[Vec<String>], width: usize, color: bool) -> String {
+    let (header, body) = rows.split_first().expect("a table has a header");
+    let records: Vec<String> = body
+        .iter()
+        .map(|row| {
+            let mut record = String::new();
+            for (name, value) in header.iter().zip(row) {
+                let name = if color { format!("{}{}:{}", BOLD, name, RESET) } else { format!("{}:", name) };
+                let mut wrapper = LineWrapper::new(width);
+                record.push_str(&wrapper.push(&format!("{} {}", name, value)));
+                record.push_str(&wrapper.finish());
+                record.push('\n');
+            }
+            record
+        })
+        .collect();
+    records.join("\n")
+}
+
+/// Lines of `text` wrapped at `width` columns.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut wrapper = LineWrapper::new(width);
+    let wrapped = wrapper.push(text) + &wrapper.finish();
+    wrapped.split('\n').map(str::to_string).collect()
+}
+
+fn pad(text: &str, width: usize, align: Align) -> String {
+    let space = width.saturating_sub(display_width(text));
+    let (before, after) = match align {
+        Align::Left => (0, space),
+        Align::Right => (space, 0),
+        Align::Center => (space / 2, space - space / 2),
+    };
+    format!("{}{}{}", " ".repeat(before), text, " ".repeat(after))
+}
+
+/// `raw` indented two spaces per level, keys in their original order, or
+/// `None` if it is not valid JSON.
+fn format_json(raw: &str, color: bool) -> Option<String> {
+    serde_json::from_str::<serde::de::IgnoredAny>(raw).ok()?;
+
+    let paint = |out: &mut String, text: &str, style: &str| {
+        if color {
+            out.push_str(&format!("{}{}{}", style, text, RESET));
+        } else {
+            out.push_str(text);
+        }
+    };
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+
+    let mut out = String::new();
+    let mut depth = 0;
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut string = String::from('"');
+                while let Some(c) = chars.next() {
+                    string.push(c);
+                    match c {
+                        '\\' => string.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+                let is_key = chars.clone().find(|c| !c.is_whitespace()) == Some(':');
+                paint(&mut out, &string, if is_key { KEY } else { STRING });
+            }
+            '{' | '[' => {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next_if(|&next| next == if c == '{' { '}' } else { ']' }).is_some() {
+                    out.push_str(if c == '{' { "{}" } else { "[]" });
+                } else {
+                    out.push(c);
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            '}' | ']' => {
+                depth -= 1;
+                newline(&mut out, depth);
+                out.push(c);
+            }
+            ',' => {
+                out.push(',');
+                newline(&mut out, depth);
+            }
+            ':' => out.push_str(": "),
+            c if c.is_whitespace() => {}
+            _ => {
+                let mut literal = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, ',' | ']' | '}')) {
+                    literal.push(c);
+                }
+                let style = if matches!(literal.as_str(), "true" | "false" | "null") { LITERAL } else { NUMBER };
+                paint(&mut out, &literal, style);
+            }
+        }
+    }
+    out.push('\n');
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(renderer: &mut BlockRenderer, pieces: &[&str]) -> String {
+        let mut out: String = pieces.iter().map(|piece| renderer.push(piece)).collect();
+        out.push_str(&renderer.finish());
+        out
+    }
+
+    #[test]
+    fn test_tables() {
+        let text = "Sizes:\n| Name | Size |\n|:-----|-----:|\n| a.rs | 10 |\n| lib\\|x | 2048 |\nDone.";
+        let mut renderer = BlockRenderer::new(0, false);
+        // Split mid-row, as tokens arrive
+        assert_eq!(
+            render(&mut renderer, &[&text[..20], &text[20..41], &text[41..]]),
+            "Sizes:\n\
+             ┌───────┬──────┐\n\
+             │ Name  │ Size │\n\
+             ├───────┼──────┤\n\
+             │ a.rs  │   10 │\n\
+             │ lib|x │ 2048 │\n\
+             └───────┴──────┘\n\
+             Done."
+        );
+        assert_eq!(renderer.blocks(), [RawBlock { kind: BlockKind::Table, raw: text[7..text.len() - 6].to_string() }]);
+
+        // Cells are wrapped to fit, or rows listed when that is too narrow
+        let table = "| Key | Description |\n|---|---|\n| a | first letter of the alphabet |\n";
+        let narrow = render(&mut BlockRenderer::new(24, false), &[table]);
+        assert!(narrow.lines().all(|line| display_width(line) <= 24), "{}", narrow);
+        assert!(narrow.contains("│ a   │ first letter   │\n│     │ of the         │"), "{}", narrow);
+        assert_eq!(
+            render(&mut BlockRenderer::new(12, false), &[table]),
+            "Key: a\nDescription:\nfirst letter\nof the\nalphabet\n"
+        );
+
+        // Pipes that are not a table, and code blocks, are left alone
+        let text = "| not a table |\nor this\n```sh\n| grep x\n```";
+        assert_eq!(render(&mut BlockRenderer::new(0, false), &[text]), text);
+    }
+
+    #[test]
+    fn test_json_blocks() {
+        let text = "Result:\n```json\n{\"name\": \"a, b\", \"ok\": true, \"sizes\": [1, 2.5], \"tags\": {}}\n```\nbroken:\n```json\n{\"a\":\n```\n";
+        let mut renderer = BlockRenderer::new(0, false);
+        assert_eq!(
+            render(&mut renderer, &[text]),
+            "Result:\n{\n  \"name\": \"a, b\",\n  \"ok\": true,\n  \"sizes\": [\n    1,\n    2.5\n  ],\n  \"tags\": {}\n}\nbroken:\n```json\n{\"a\":\n```\n"
+        );
+        assert_eq!(renderer.blocks().len(), 1);
+        assert_eq!(renderer.blocks()[0].kind, BlockKind::Json);
+
+        let colored = format_json("{\"a\": null}", true).unwrap();
+        assert_eq!(colored, format!("{{\n  {KEY}\"a\"{RESET}: {LITERAL}null{RESET}\n}}\n"));
+        assert_eq!(render(&mut BlockRenderer::new(0, false).with_structured(false), &[text]), text);
+    }
+}
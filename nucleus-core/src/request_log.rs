@@ -0,0 +1,146 @@
+//! Recent raw requests, kept for replay (`request_log`).
+//!
+//! When enabled, the server appends every chat-like request it receives to a
+//! JSONL file exactly as the client sent it. `nucleus replay <id>` sends one
+//! again, so an answer that was fine yesterday can be reproduced against
+//! today's configuration and model. Only the `keep` most recent requests
+//! are kept. A request shares its ID with its entry in the response log
+//! (see [`crate::response_log`]), so the original answer can be compared.
+
+use crate::config::RequestLogConfig;
+use crate::feedback::unix_timestamp;
+use crate::server::Request;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Error)]
+pub enum RequestLogError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, RequestLogError>;
+
+/// A request as recorded in the log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedRequest {
+    pub id: String,
+    /// Unix timestamp (seconds) the request was received
+    pub timestamp: u64,
+    pub request: Request,
+}
+
+/// Appends requests to the request log, dropping the oldest ones.
+#[derive(Debug)]
+pub struct RequestLog {
+    config: RequestLogConfig,
+    /// Lines in the file, counted on the first write
+    lines: Mutex<Option<usize>>,
+}
+
+impl RequestLog {
+    pub fn new(config: RequestLogConfig) -> Self {
+        Self {
+            config,
+            lines: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Appends `request` under `id`.
+    ///
+    /// Once the file holds twice as many requests as are kept, it is
+    /// rewritten with just the most recent ones.
+    pub async fn record(&self, id: &str, request: &Request) -> Result<()> {
+        let path = Path::new(&self.config.path);
+        let mut line = serde_json::to_string(&LoggedRequest {
+            id: id.to_string(),
+            timestamp: unix_timestamp(),
+            request: request.clone(),
+        })?;
+        line.push('\n');
+
+        let mut lines = self.lines.lock().await;
+        let count = match *lines {
+            Some(count) => count,
+            None => match tokio::fs::read_to_string(path).await {
+                Ok(content) => content.lines().count(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+                Err(e) => return Err(e.into()),
+            },
+        };
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        let mut count = count + 1;
+
+        if count > self.config.keep.max(1) * 2 {
+            let content = tokio::fs::read_to_string(path).await?;
+            let recent: Vec<&str> = content.lines().collect();
+            let recent = &recent[recent.len().saturating_sub(self.config.keep)..];
+            let mut pruned = recent.join("\n");
+            pruned.push('\n');
+
+            let temporary = path.with_extension("jsonl.tmp");
+            tokio::fs::write(&temporary, pruned).await?;
+            tokio::fs::rename(&temporary, path).await?;
+            count = recent.len();
+        }
+        *lines = Some(count);
+        Ok(())
+    }
+}
+
+/// Reads the requests in the log at `path`, oldest first.
+///
+/// Lines that cannot be parsed (e.g. one cut off by a crash) are skipped.
+pub fn read_requests(path: &Path) -> Result<Vec<LoggedRequest>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::RequestType;
+
+    #[tokio::test]
+    async fn test_record_keeps_recent_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = RequestLogConfig {
+            enabled: true,
+            path: dir.path().join("requests.jsonl").to_string_lossy().to_string(),
+            keep: 3,
+        };
+        let log = RequestLog::new(config.clone());
+
+        for n in 0..10 {
+            let request = Request::new(RequestType::Chat, format!("question {}", n)).with_pwd("/src/app");
+            log.record(&format!("r{}", n), &request).await.unwrap();
+        }
+
+        let requests = read_requests(Path::new(&config.path)).unwrap();
+        assert!((3..=6).contains(&requests.len()));
+        let last = requests.last().unwrap();
+        assert_eq!(last.id, "r9");
+        assert_eq!(last.request.content, "question 9");
+        assert_eq!(last.request.pwd.as_deref(), Some("/src/app"));
+    }
+}
@@ -0,0 +1,428 @@
+//! Write-through log of streamed responses (`response_log`).
+//!
+//! When enabled, the server appends every chat-like response to a JSONL file
+//! while it streams: a `start` event with the request, one `content` event
+//! per streamed piece, and an `end` event once the response is done, failed,
+//! or was cut off. Because pieces are written as they arrive, an answer the
+//! terminal scrollback already lost, or one whose client disconnected
+//! halfway, can still be read back with [`read_responses`].
+//!
+//! The file is rotated once it would grow past `max_bytes`; the `keep` most
+//! recent rotated files are kept as `path.1` (newest) to `path.{keep}`.
+
+use crate::config::ResponseLogConfig;
+use crate::feedback::unix_timestamp;
+use crate::server::{ChunkType, Request, RequestType, StreamChunk};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, Mutex};
+use tracing::warn;
+
+#[derive(Debug, Error)]
+pub enum ResponseLogError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, ResponseLogError>;
+
+/// How a logged response ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Done,
+    Error,
+    /// The request finished without a "done" or "error" chunk
+    Incomplete,
+}
+
+/// One line of the response log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum LogEvent {
+    Start {
+        id: String,
+        /// Unix timestamp (seconds)
+        timestamp: u64,
+        request_type: RequestType,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pwd: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        collection: Option<String>,
+        prompt: String,
+    },
+    Content {
+        id: String,
+        text: String,
+    },
+    End {
+        id: String,
+        /// Unix timestamp (seconds)
+        timestamp: u64,
+        outcome: Outcome,
+        elapsed_ms: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        /// ID to rate the response with, from the "done" chunk
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        response_id: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
+        /// Complete text of the "done" chunk, when it differs from the
+        /// streamed pieces (e.g. a sources footer was added)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        response: Option<String>,
+    },
+}
+
+/// A response reassembled from the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoggedResponse {
+    pub id: String,
+    /// Unix timestamp (seconds) of the request
+    pub timestamp: u64,
+    pub request_type: RequestType,
+    pub session_id: Option<String>,
+    pub pwd: Option<String>,
+    pub collection: Option<String>,
+    pub prompt: String,
+    pub response: String,
+    /// `None` while the response is still streaming, or if the server
+    /// stopped before it ended
+    pub outcome: Option<Outcome>,
+    pub error: Option<String>,
+    pub response_id: Option<String>,
+    pub truncated: bool,
+    pub elapsed_ms: Option<u64>,
+}
+
+#[derive(Debug)]
+struct OpenFile {
+    file: tokio::fs::File,
+    len: u64,
+}
+
+/// Appends events to the response log, rotating it as it grows.
+#[derive(Debug)]
+pub struct ResponseLog {
+    config: ResponseLogConfig,
+    file: Mutex<Option<OpenFile>>,
+}
+
+impl ResponseLog {
+    pub fn new(config: ResponseLogConfig) -> Self {
+        Self {
+            config,
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Appends one event, rotating the file first if it would grow past
+    /// `max_bytes`.
+    pub async fn write(&self, event: &LogEvent) -> Result<()> {
+        let mut line = serde_json::to_string(event)?;
+        line.push('\n');
+
+        let path = Path::new(&self.config.path);
+        let mut file = self.file.lock().await;
+        if file
+            .as_ref()
+            .is_some_and(|open| open.len > 0 && open.len + line.len() as u64 > self.config.max_bytes)
+        {
+            *file = None;
+            rotate(path, self.config.keep).await?;
+        }
+        let open = match file.as_mut() {
+            Some(open) => open,
+            None => file.insert(open_log(path).await?),
+        };
+        open.file.write_all(line.as_bytes()).await?;
+        // Readers look at the file while responses stream
+        open.file.flush().await?;
+        open.len += line.len() as u64;
+        Ok(())
+    }
+
+    /// Returns a sender that logs every chunk of the response to `request`
+    /// under `id` before passing it on to `sender`.
+    ///
+    /// Chunks are still logged after the client disconnects, and the
+    /// response is ended as incomplete if the request finishes without a
+    /// "done" or "error" chunk.
+    pub fn tee(
+        self: &Arc<Self>,
+        id: String,
+        request: &Request,
+        sender: mpsc::UnboundedSender<StreamChunk>,
+    ) -> mpsc::UnboundedSender<StreamChunk> {
+        let start = LogEvent::Start {
+            id: id.clone(),
+            timestamp: unix_timestamp(),
+            request_type: request.request_type,
+            session_id: request.session_id.clone(),
+            pwd: request.pwd.clone(),
+            collection: request.collection.clone(),
+            prompt: request.content.clone(),
+        };
+        let (tee, mut chunks) = mpsc::unbounded_channel::<StreamChunk>();
+        let log = self.clone();
+
+        tokio::spawn(async move {
+            let started = Instant::now();
+            log.log(&start).await;
+            let mut streamed = String::new();
+            let mut end = None;
+            while let Some(chunk) = chunks.recv().await {
+                match chunk.chunk_type {
+                    ChunkType::Chunk if !chunk.content.is_empty() => {
+                        streamed.push_str(&chunk.content);
+                        log.log(&LogEvent::Content {
+                            id: id.clone(),
+                            text: chunk.content.clone(),
+                        })
+                        .await;
+                    }
+                    ChunkType::Chunk => {}
+                    ChunkType::Done => {
+                        end = Some((
+                            Outcome::Done,
+                            None,
+                            chunk.response_id.clone(),
+                            chunk.truncated,
+                            (chunk.content != streamed).then(|| chunk.content.clone()),
+                        ));
+                    }
+                    ChunkType::Error => end = Some((Outcome::Error, chunk.error.clone(), None, false, None)),
+                }
+                let _ = sender.send(chunk);
+            }
+
+            let (outcome, error, response_id, truncated, response) =
+                end.unwrap_or((Outcome::Incomplete, None, None, false, None));
+            log.log(&LogEvent::End {
+                id,
+                timestamp: unix_timestamp(),
+                outcome,
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                error,
+                response_id,
+                truncated,
+                response,
+            })
+            .await;
+        });
+
+        tee
+    }
+
+    async fn log(&self, event: &LogEvent) {
+        if let Err(e) = self.write(event).await {
+            warn!("Failed to write the response log {}: {}", self.config.path, e);
+        }
+    }
+}
+
+async fn open_log(path: &Path) -> Result<OpenFile> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+    let len = file.metadata().await?.len();
+    Ok(OpenFile { file, len })
+}
+
+/// `path.{n}`, the `n`th most recent rotated file.
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+async fn rotate(path: &Path, keep: usize) -> Result<()> {
+    if keep == 0 {
+        return remove_if_exists(path).await;
+    }
+    remove_if_exists(&rotated(path, keep)).await?;
+    for n in (1..keep).rev() {
+        rename_if_exists(&rotated(path, n), &rotated(path, n + 1)).await?;
+    }
+    rename_if_exists(path, &rotated(path, 1)).await
+}
+
+async fn remove_if_exists(path: &Path) -> Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+async fn rename_if_exists(from: &Path, to: &Path) -> Result<()> {
+    match tokio::fs::rename(from, to).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Reads the responses in the log at `path` and its rotated files, oldest
+/// first.
+///
+/// Lines that cannot be parsed (e.g. one cut off by a crash) are skipped, as
+/// are pieces of responses whose start was rotated away.
+pub fn read_responses(path: &Path, keep: usize) -> Result<Vec<LoggedResponse>> {
+    let mut responses: Vec<LoggedResponse> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    let files = (1..=keep).rev().map(|n| rotated(path, n)).chain([path.to_path_buf()]);
+    for file in files {
+        let content = match std::fs::read_to_string(&file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        for event in content.lines().filter_map(|line| serde_json::from_str::<LogEvent>(line).ok()) {
+            match event {
+                LogEvent::Start {
+                    id,
+                    timestamp,
+                    request_type,
+                    session_id,
+                    pwd,
+                    collection,
+                    prompt,
+                } => {
+                    index.insert(id.clone(), responses.len());
+                    responses.push(LoggedResponse {
+                        id,
+                        timestamp,
+                        request_type,
+                        session_id,
+                        pwd,
+                        collection,
+                        prompt,
+                        response: String::new(),
+                        outcome: None,
+                        error: None,
+                        response_id: None,
+                        truncated: false,
+                        elapsed_ms: None,
+                    });
+                }
+                LogEvent::Content { id, text } => {
+                    if let Some(&i) = index.get(&id) {
+                        responses[i].response.push_str(&text);
+                    }
+                }
+                LogEvent::End {
+                    id,
+                    outcome,
+                    elapsed_ms,
+                    error,
+                    response_id,
+                    truncated,
+                    response,
+                    ..
+                } => {
+                    if let Some(&i) = index.get(&id) {
+                        let logged = &mut responses[i];
+                        logged.outcome = Some(outcome);
+                        logged.elapsed_ms = Some(elapsed_ms);
+                        logged.error = error;
+                        logged.response_id = response_id;
+                        logged.truncated = truncated;
+                        if let Some(response) = response {
+                            logged.response = response;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(responses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feedback::new_response_id;
+
+    fn config(dir: &Path, max_bytes: u64) -> ResponseLogConfig {
+        ResponseLogConfig {
+            enabled: true,
+            path: dir.join("responses.jsonl").to_string_lossy().to_string(),
+            max_bytes,
+            keep: 2,
+        }
+    }
+
+    async fn respond(log: &Arc<ResponseLog>, prompt: &str, chunks: Vec<StreamChunk>) {
+        let (sender, mut received) = mpsc::unbounded_channel();
+        let tee = log.tee(new_response_id(), &Request::new(RequestType::Chat, prompt), sender);
+        for chunk in chunks {
+            tee.send(chunk).unwrap();
+        }
+        drop(tee);
+        while received.recv().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn test_tee_and_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path(), 1024 * 1024);
+        let log = Arc::new(ResponseLog::new(config.clone()));
+
+        respond(&log, "list files", vec![
+            StreamChunk::chunk("Use "),
+            StreamChunk::chunk("ls"),
+            StreamChunk::done("Use ls\n\nSources: notes"),
+        ])
+        .await;
+        respond(&log, "and hidden ones?", vec![StreamChunk::chunk("Use ls -a")]).await;
+        respond(&log, "why?", vec![StreamChunk::error("model unavailable")]).await;
+
+        let responses = read_responses(Path::new(&config.path), config.keep).unwrap();
+        let summary: Vec<(&str, &str, Option<Outcome>)> = responses
+            .iter()
+            .map(|r| (r.prompt.as_str(), r.response.as_str(), r.outcome))
+            .collect();
+        assert_eq!(summary, [
+            ("list files", "Use ls\n\nSources: notes", Some(Outcome::Done)),
+            ("and hidden ones?", "Use ls -a", Some(Outcome::Incomplete)),
+            ("why?", "", Some(Outcome::Error)),
+        ]);
+        assert_eq!(responses[2].error.as_deref(), Some("model unavailable"));
+    }
+
+    #[tokio::test]
+    async fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path(), 200);
+        let log = Arc::new(ResponseLog::new(config.clone()));
+
+        for n in 0..20 {
+            respond(&log, &format!("question {}", n), vec![StreamChunk::done(format!("answer {}", n))]).await;
+        }
+
+        let path = Path::new(&config.path);
+        assert!(rotated(path, 2).exists());
+        assert!(!rotated(path, 3).exists());
+        let responses = read_responses(path, config.keep).unwrap();
+        assert!(responses.len() < 20);
+        assert_eq!(responses.last().unwrap().response, "answer 19");
+    }
+}
@@ -0,0 +1,172 @@
+//! Crash isolation and reporting for the daemon.
+//!
+//! Every request runs in a task of its own, so a panic while handling one
+//! ends only that request: its client gets an error saying the daemon
+//! recovered, and other connections carry on. The panic hook installed by
+//! [`install_panic_hook`] writes a crash report (message, location, backtrace,
+//! the request type being handled, versions) for every panic.
+
+use super::handler::ChunkSender;
+use super::types::{RequestType, StreamChunk};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+
+/// Error sent to a client whose request panicked.
+pub const RECOVERED_MESSAGE: &str =
+    "The nucleus daemon recovered from an internal error while handling this request; a crash report was saved";
+
+tokio::task_local! {
+    /// Type of the request handled by the current task.
+    static CURRENT_REQUEST: RequestType;
+}
+
+/// What was known when the daemon panicked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// Unix timestamp in seconds
+    pub timestamp: u64,
+    /// nucleus version
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    /// Request being handled, if the panic happened in a request
+    pub request_type: Option<RequestType>,
+    pub message: String,
+    /// `file:line:column` of the panic
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: String,
+}
+
+impl CrashReport {
+    fn from_panic(info: &PanicHookInfo<'_>) -> Self {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".to_string());
+
+        Self {
+            timestamp: crate::feedback::unix_timestamp(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            request_type: current_request(),
+            message,
+            location: info.location().map(|location| location.to_string()),
+            thread: std::thread::current().name().map(String::from),
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+        }
+    }
+
+    /// Writes the report as JSON into `dir`, returning the file's path.
+    pub fn write(&self, dir: &Path) -> std::io::Result<PathBuf> {
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!(
+            "crash-{}-{}-{}.json",
+            self.timestamp,
+            std::process::id(),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, serde_json::to_vec_pretty(self)?)?;
+        Ok(path)
+    }
+}
+
+/// Type of the request handled by the current task, if any.
+pub fn current_request() -> Option<RequestType> {
+    CURRENT_REQUEST.try_with(|request_type| *request_type).ok()
+}
+
+/// Installs a panic hook writing crash reports into `dir`.
+///
+/// The previous hook still runs afterwards, so panics are printed as before.
+/// Only the first call installs the hook.
+pub fn install_panic_hook(dir: impl Into<PathBuf>) {
+    static INSTALLED: Once = Once::new();
+
+    let dir = dir.into();
+    INSTALLED.call_once(move || {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let report = CrashReport::from_panic(info);
+            match report.write(&dir) {
+                Ok(path) => eprintln!("Crash report written to {}", path.display()),
+                Err(e) => eprintln!("Failed to write crash report: {}", e),
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Runs the handling of a request in a task of its own.
+///
+/// If `handling` panics, `sender` receives [`RECOVERED_MESSAGE`] as an
+/// error chunk instead of the stream ending without a response.
+pub(super) async fn isolate<F>(request_type: RequestType, sender: ChunkSender, handling: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let task = tokio::spawn(CURRENT_REQUEST.scope(request_type, handling));
+    if let Err(e) = task.await {
+        if e.is_panic() {
+            let _ = sender.send(StreamChunk::error(RECOVERED_MESSAGE));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ChunkType;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_isolate_reports_panics() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let handler_sender = sender.clone();
+        isolate(RequestType::Chat, sender, async move {
+            assert_eq!(current_request(), Some(RequestType::Chat));
+            let _ = handler_sender.send(StreamChunk::chunk("partial"));
+            panic!("handler bug");
+        })
+        .await;
+
+        assert_eq!(receiver.recv().await.unwrap().content, "partial");
+        let error = receiver.recv().await.unwrap();
+        assert_eq!(error.chunk_type, ChunkType::Error);
+        assert_eq!(error.error.as_deref(), Some(RECOVERED_MESSAGE));
+        assert!(receiver.recv().await.is_none());
+        assert_eq!(current_request(), None);
+    }
+
+    #[test]
+    fn test_write_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = CrashReport {
+            timestamp: 1,
+            version: "0.1.0".to_string(),
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            request_type: Some(RequestType::IndexCommands),
+            message: "boom".to_string(),
+            location: Some("src/lib.rs:1:1".to_string()),
+            thread: None,
+            backtrace: String::new(),
+        };
+
+        let first = report.write(&dir.path().join("crashes")).unwrap();
+        let second = report.write(&dir.path().join("crashes")).unwrap();
+        assert_ne!(first, second);
+
+        let written: CrashReport = serde_json::from_slice(&std::fs::read(&first).unwrap()).unwrap();
+        assert_eq!(written, report);
+    }
+}
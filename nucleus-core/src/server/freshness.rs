@@ -0,0 +1,21 @@
+//! Periodic checks for stale sources (`rag.freshness`).
+//!
+//! At startup and every `rag.freshness.interval_hours`, each collection of
+//! the local knowledge base is checked for files that changed on disk and
+//! web pages that were not fetched for too long (see
+//! [`crate::rag::freshness`]). They are logged, or re-indexed with
+//! `auto_refresh`; `nucleus stats` lists them either way.
+
+use super::handler::RequestHandler;
+use crate::config::FreshnessConfig;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Checks for stale sources until the server stops.
+pub(super) async fn run(handler: Arc<RequestHandler>, config: FreshnessConfig) {
+    let interval = Duration::from_secs(config.interval_hours.max(1) * 3600);
+    loop {
+        handler.check_freshness().await;
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,303 @@
+//! gRPC API served alongside the socket protocol.
+//!
+//! The service is defined in `proto/nucleus/v1/nucleus.proto`. Chat and team
+//! administration go through the same [`RequestHandler`] as socket requests,
+//! so private mode, egress checks, and notifications apply unchanged.
+
+mod proto;
+
+pub use proto::nucleus_client::NucleusClient;
+pub use proto::*;
+
+use super::crash;
+use super::handler::RequestHandler;
+use super::types::{ChunkType, Message, Request, RequestType, StreamChunk};
+use crate::config::Verbosity;
+use futures::Stream;
+use proto::nucleus_server::{Nucleus, NucleusServer};
+use std::{net::SocketAddr, path::Path, pin::Pin, sync::Arc};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tonic::{Response, Status};
+
+#[derive(Debug, Error)]
+pub enum GrpcError {
+    #[error("Invalid gRPC listen address: {0}")]
+    InvalidAddress(#[from] std::net::AddrParseError),
+
+    #[error("gRPC transport error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+}
+
+pub type Result<T> = std::result::Result<T, GrpcError>;
+
+/// Serves the gRPC API on `address` until the server fails.
+pub(super) async fn serve(handler: Arc<RequestHandler>, address: &str) -> Result<()> {
+    let address: SocketAddr = address.parse()?;
+    tonic::transport::Server::builder()
+        .add_service(NucleusServer::new(NucleusService { handler }))
+        .serve(address)
+        .await?;
+    Ok(())
+}
+
+struct NucleusService {
+    handler: Arc<RequestHandler>,
+}
+
+impl NucleusService {
+    /// Runs a request through the handler, streaming its chunks.
+    fn dispatch(&self, request: Request) -> mpsc::UnboundedReceiver<StreamChunk> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let handler = Arc::clone(&self.handler);
+        let request_type = request.request_type;
+        tokio::spawn(crash::isolate(request_type, sender.clone(), async move {
+            handler.handle(request, sender).await;
+        }));
+        receiver
+    }
+
+    /// Runs a request through the handler and returns the final message.
+    async fn run(&self, request: Request) -> std::result::Result<String, Status> {
+        let mut receiver = self.dispatch(request);
+        while let Some(chunk) = receiver.recv().await {
+            match chunk.chunk_type {
+                ChunkType::Chunk => continue,
+                ChunkType::Done => return Ok(chunk.content),
+                ChunkType::Error => return Err(error_status(chunk)),
+            }
+        }
+        Err(Status::internal("Request ended without a response"))
+    }
+}
+
+type ChatStream = Pin<Box<dyn Stream<Item = std::result::Result<ChatChunk, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Nucleus for NucleusService {
+    type ChatStream = ChatStream;
+
+    async fn chat(&self, request: tonic::Request<ChatRequest>) -> std::result::Result<Response<ChatStream>, Status> {
+        let receiver = self.dispatch(request.into_inner().into());
+        let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+            let chunk = receiver.recv().await?;
+            Some((chat_chunk(chunk), receiver))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn embed(&self, request: tonic::Request<EmbedRequest>) -> std::result::Result<Response<EmbedResponse>, Status> {
+        let request = request.into_inner();
+        let texts: Vec<&str> = request.texts.iter().map(String::as_str).collect();
+        let embeddings = self.handler.embed(&texts).await.map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(EmbedResponse {
+            embeddings: embeddings.into_iter().map(|values| Embedding { values }).collect(),
+            model: self.handler.config().rag.embedding_model.name.clone(),
+        }))
+    }
+
+    async fn search(&self, request: tonic::Request<SearchRequest>) -> std::result::Result<Response<SearchResponse>, Status> {
+        let request = request.into_inner();
+        let mut query = Request::new(RequestType::Search, request.query);
+        if !request.session_id.is_empty() {
+            query = query.with_session_id(request.session_id);
+        }
+        if request.limit > 0 {
+            query = query.with_limit(request.limit as usize);
+        }
+        if request.all_collections {
+            query = query.with_all_collections();
+        }
+
+        let results = self.handler.search(&query).await.map_err(|e| Status::internal(e.to_string()))?;
+        let hits = results
+            .into_iter()
+            .map(|result| SearchHit {
+                id: result.document.id,
+                content: result.document.content,
+                score: result.score,
+                metadata: result.document.metadata,
+            })
+            .collect();
+        Ok(Response::new(SearchResponse { hits }))
+    }
+
+    async fn index(&self, request: tonic::Request<IndexRequest>) -> std::result::Result<Response<IndexResponse>, Status> {
+        let path = request.into_inner().path;
+        if !Path::new(&path).is_absolute() {
+            return Err(Status::invalid_argument("path must be absolute"));
+        }
+
+        let count = self.handler.index(Path::new(&path), None).await.map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(IndexResponse { files_indexed: count as u64 }))
+    }
+
+    async fn stats(&self, request: tonic::Request<StatsRequest>) -> std::result::Result<Response<StatsResponse>, Status> {
+        let collection = request.into_inner().collection;
+        let mut request = Request::new(RequestType::Stats, "");
+        if !collection.is_empty() {
+            request = request.with_collection(collection);
+        }
+        let stats = self.handler.stats(&request).await.map_err(|e| Status::internal(e.to_string()))?;
+        let (team_namespace, team_documents) = match stats.team {
+            Some(team) => (team.namespace, team.chunks as u64),
+            None => (String::new(), 0),
+        };
+
+        Ok(Response::new(StatsResponse {
+            documents: stats.chunks as u64,
+            team_namespace,
+            team_documents,
+            collection: stats.collection,
+            disk_bytes: stats.disk_bytes,
+            embedding_model: stats.embedding_model,
+            embedding_dim: stats.embedding_dim as u32,
+            last_indexed: stats.last_indexed,
+            sources: stats
+                .sources
+                .into_iter()
+                .map(|source| SourceStats {
+                    source: source.source,
+                    chunks: source.chunks as u64,
+                    indexed_at: source.indexed_at,
+                    modified: source.modified,
+                })
+                .collect(),
+            indexing_jobs: stats.indexing.len() as u32,
+            stale: stats
+                .stale
+                .into_iter()
+                .map(|stale| StaleSource {
+                    source: stale.source,
+                    reason: stale.reason.to_string(),
+                    indexed_at: stale.indexed_at,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn team_admin(
+        &self,
+        request: tonic::Request<TeamAdminRequest>,
+    ) -> std::result::Result<Response<TeamAdminResponse>, Status> {
+        let request = request.into_inner();
+        let request_type = match request.action() {
+            TeamAction::Index => RequestType::TeamIndex,
+            TeamAction::Remove => RequestType::TeamRemove,
+            TeamAction::Clear => RequestType::TeamClear,
+            TeamAction::Unspecified => return Err(Status::invalid_argument("action is required")),
+        };
+        if request_type != RequestType::TeamClear && !Path::new(&request.path).is_absolute() {
+            return Err(Status::invalid_argument("path must be absolute"));
+        }
+
+        let message = self.run(Request::new(request_type, request.path)).await?;
+        Ok(Response::new(TeamAdminResponse { message }))
+    }
+}
+
+impl From<ChatRequest> for Request {
+    fn from(chat: ChatRequest) -> Self {
+        let request_type = if chat.edit { RequestType::Edit } else { RequestType::Chat };
+        let mut request = Request::new(request_type, chat.content);
+
+        if !chat.pwd.is_empty() {
+            request = request.with_pwd(chat.pwd);
+        }
+        if !chat.history.is_empty() {
+            request.history = Some(
+                chat.history
+                    .into_iter()
+                    .map(|message| Message {
+                        role: message.role,
+                        content: message.content,
+                    })
+                    .collect(),
+            );
+        }
+        if let Some(max_time_ms) = chat.max_time_ms {
+            request = request.with_max_time_ms(max_time_ms);
+        }
+        if let Some(max_tokens) = chat.max_tokens {
+            request = request.with_max_tokens(max_tokens);
+        }
+        if chat.include_tree {
+            request = request.with_tree();
+        }
+        if !chat.session_id.is_empty() {
+            request = request.with_session_id(chat.session_id);
+        }
+        if chat.all_collections {
+            request = request.with_all_collections();
+        }
+        if let Some(verbosity) = Verbosity::from_name(&chat.verbosity) {
+            request = request.with_verbosity(verbosity);
+        }
+        request
+    }
+}
+
+fn chat_chunk(chunk: StreamChunk) -> std::result::Result<ChatChunk, Status> {
+    match chunk.chunk_type {
+        ChunkType::Chunk => Ok(ChatChunk {
+            content: chunk.content,
+            ..ChatChunk::default()
+        }),
+        ChunkType::Done => Ok(ChatChunk {
+            content: chunk.content,
+            done: true,
+            response_id: chunk.response_id.unwrap_or_default(),
+            truncated: chunk.truncated,
+        }),
+        ChunkType::Error => Err(error_status(chunk)),
+    }
+}
+
+fn error_status(chunk: StreamChunk) -> Status {
+    let message = chunk.error.unwrap_or(chunk.content);
+    if message.starts_with("Private mode:") {
+        Status::failed_precondition(message)
+    } else {
+        Status::internal(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_request_conversion() {
+        let request: Request = ChatRequest {
+            content: "why does this fail?".to_string(),
+            pwd: "/src/app".to_string(),
+            edit: true,
+            max_tokens: Some(200),
+            session_id: "tty1".to_string(),
+            all_collections: true,
+            verbosity: "terse".to_string(),
+            ..ChatRequest::default()
+        }
+        .into();
+
+        assert_eq!(request.request_type, RequestType::Edit);
+        assert_eq!(request.pwd.as_deref(), Some("/src/app"));
+        assert_eq!(request.max_tokens, Some(200));
+        assert_eq!(request.max_time_ms, None);
+        assert!(request.history.is_none());
+        assert_eq!(request.session_id.as_deref(), Some("tty1"));
+        assert!(request.all_collections);
+        assert_eq!(request.verbosity, Some(Verbosity::Terse));
+    }
+
+    #[test]
+    fn test_chat_chunk_conversion() {
+        let chunk = chat_chunk(StreamChunk::done("answer").with_response_id("r1")).unwrap();
+        assert!(chunk.done);
+        assert_eq!(chunk.response_id, "r1");
+
+        let status = chat_chunk(StreamChunk::error("Private mode: knowledge base writes are disabled")).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    }
+}
@@ -0,0 +1,190 @@
+//! Messages of the `nucleus.v1` package.
+//!
+//! Mirrors `proto/nucleus/v1/nucleus.proto`; field tags must match it.
+
+use std::collections::HashMap;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HistoryMessage {
+    #[prost(string, tag = "1")]
+    pub role: String,
+    #[prost(string, tag = "2")]
+    pub content: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ChatRequest {
+    #[prost(string, tag = "1")]
+    pub content: String,
+    #[prost(string, tag = "2")]
+    pub pwd: String,
+    #[prost(message, repeated, tag = "3")]
+    pub history: Vec<HistoryMessage>,
+    #[prost(bool, tag = "4")]
+    pub edit: bool,
+    #[prost(uint64, optional, tag = "5")]
+    pub max_time_ms: Option<u64>,
+    #[prost(uint32, optional, tag = "6")]
+    pub max_tokens: Option<u32>,
+    #[prost(bool, tag = "7")]
+    pub include_tree: bool,
+    #[prost(string, tag = "8")]
+    pub session_id: String,
+    #[prost(bool, tag = "9")]
+    pub all_collections: bool,
+    #[prost(string, tag = "10")]
+    pub verbosity: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ChatChunk {
+    #[prost(string, tag = "1")]
+    pub content: String,
+    #[prost(bool, tag = "2")]
+    pub done: bool,
+    #[prost(string, tag = "3")]
+    pub response_id: String,
+    #[prost(bool, tag = "4")]
+    pub truncated: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EmbedRequest {
+    #[prost(string, repeated, tag = "1")]
+    pub texts: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Embedding {
+    #[prost(float, repeated, tag = "1")]
+    pub values: Vec<f32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EmbedResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub embeddings: Vec<Embedding>,
+    #[prost(string, tag = "2")]
+    pub model: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SearchRequest {
+    #[prost(string, tag = "1")]
+    pub query: String,
+    #[prost(uint32, tag = "2")]
+    pub limit: u32,
+    #[prost(string, tag = "3")]
+    pub session_id: String,
+    #[prost(bool, tag = "4")]
+    pub all_collections: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SearchHit {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub content: String,
+    #[prost(float, tag = "3")]
+    pub score: f32,
+    #[prost(map = "string, string", tag = "4")]
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SearchResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub hits: Vec<SearchHit>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IndexRequest {
+    #[prost(string, tag = "1")]
+    pub path: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IndexResponse {
+    #[prost(uint64, tag = "1")]
+    pub files_indexed: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StatsRequest {
+    #[prost(string, tag = "1")]
+    pub collection: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StatsResponse {
+    #[prost(uint64, tag = "1")]
+    pub documents: u64,
+    #[prost(string, tag = "2")]
+    pub team_namespace: String,
+    #[prost(uint64, tag = "3")]
+    pub team_documents: u64,
+    #[prost(string, tag = "4")]
+    pub collection: String,
+    #[prost(uint64, optional, tag = "5")]
+    pub disk_bytes: Option<u64>,
+    #[prost(string, tag = "6")]
+    pub embedding_model: String,
+    #[prost(uint32, tag = "7")]
+    pub embedding_dim: u32,
+    #[prost(uint64, optional, tag = "8")]
+    pub last_indexed: Option<u64>,
+    #[prost(message, repeated, tag = "9")]
+    pub sources: Vec<SourceStats>,
+    #[prost(uint32, tag = "10")]
+    pub indexing_jobs: u32,
+    #[prost(message, repeated, tag = "11")]
+    pub stale: Vec<StaleSource>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SourceStats {
+    #[prost(string, tag = "1")]
+    pub source: String,
+    #[prost(uint64, tag = "2")]
+    pub chunks: u64,
+    #[prost(uint64, optional, tag = "3")]
+    pub indexed_at: Option<u64>,
+    #[prost(uint64, optional, tag = "4")]
+    pub modified: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StaleSource {
+    #[prost(string, tag = "1")]
+    pub source: String,
+    #[prost(string, tag = "2")]
+    pub reason: String,
+    #[prost(uint64, optional, tag = "3")]
+    pub indexed_at: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum TeamAction {
+    Unspecified = 0,
+    Index = 1,
+    Remove = 2,
+    Clear = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TeamAdminRequest {
+    #[prost(enumeration = "TeamAction", tag = "1")]
+    pub action: i32,
+    #[prost(string, tag = "2")]
+    pub path: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TeamAdminResponse {
+    #[prost(string, tag = "1")]
+    pub message: String,
+}
+
+include!(concat!(env!("OUT_DIR"), "/nucleus.v1.Nucleus.rs"));
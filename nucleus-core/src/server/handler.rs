@@ -1,120 +1,2304 @@
-use super::types::{Request, RequestType, StreamChunk};
-use crate::{config::Config, provider::Provider, rag};
-use std::{path::Path, sync::Arc};
+use super::jobs::{Job, Jobs};
+use super::session::{self, Sessions};
+use super::suggest::{self, SuggestState};
+use super::types::{
+    ClientVersion, Component, IndexStats, JobOutcome, Request, RequestType, SearchHit, ServerStatus, StreamChunk, TeamIndexStats,
+    Timeout, VersionMismatch, WarmingUp, PROTOCOL_VERSION,
+};
+use super::watch::DirWatcher;
+use crate::{
+    attachment::{self, ResolvedAttachment},
+    changes::{self, Changes},
+    chat::{ChatManager, Orchestrator},
+    command_docs,
+    config::{Config, OperationClass, Verbosity},
+    conversations,
+    diff,
+    events::{EventBus, EventKind},
+    dotfiles,
+    egress::{EgressClassifier, EgressError},
+    experiment::ExperimentRouter,
+    expression::ExpressionGenerator,
+    feedback::{self, FeedbackStore, Interaction, Rating, RetrievedChunk},
+    guardrails::Guardrails,
+    log_analysis::{self, LogAnalyzer},
+    notify::{headline, Notifier, OperationEvent, OperationKind},
+    plugins::Plugins,
+    project_tree::{self, TreeOptions},
+    provider::Provider,
+    rag::{self, ContextPack},
+    request_log::RequestLog,
+    response_log::ResponseLog,
+    todos::{self, TodoFile},
+    update::UpdateNotice,
+    warmup::Warmup,
+};
+use nucleus_plugin::{Permission, PluginRegistry};
+use std::{path::{Path, PathBuf}, sync::Arc, time::{Duration, Instant}};
 use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
 
 pub type ChunkSender = mpsc::UnboundedSender<StreamChunk>;
 
+/// How often a request waiting for a subsystem to start reports progress.
+const WARM_UP_PROGRESS: Duration = Duration::from_secs(5);
+
 /// Handles different request types and sends responses via channel.
 pub struct RequestHandler {
     config: Config,
     provider: Arc<dyn Provider>,
+    /// The provider behind `provider` while it starts
+    model: Warmup<Arc<dyn Provider>>,
     rag_manager: rag::RagEngine,
+    notifier: Notifier,
+    feedback: FeedbackStore,
+    responses: Arc<ResponseLog>,
+    requests: RequestLog,
+    experiments: ExperimentRouter,
+    suggestions: SuggestState,
+    sessions: Sessions,
+    jobs: Jobs,
+    egress: EgressClassifier,
+    guardrails: Guardrails,
+    /// Answers chats, running tools from the enabled plugins found; its
+    /// scheduler hands out the slots for model requests of every transport
+    chat: ChatManager,
+    watcher: Option<DirWatcher>,
+    updates: UpdateNotice,
+    events: EventBus,
+    started: Instant,
 }
 
 impl RequestHandler {
-    pub async fn new(config: Config, provider: Arc<dyn Provider>) -> Result<Self, rag::RagError> {
-        let rag_manager = rag::RagEngine::new(&config, provider.clone()).await?;
+    /// Creates the handler; its vector stores connect in the background.
+    pub fn new(
+        config: Config,
+        provider: Arc<dyn Provider>,
+        model: Warmup<Arc<dyn Provider>>,
+        watcher: Option<DirWatcher>,
+    ) -> Self {
+        let rag_manager = rag::RagEngine::deferred(&config, provider.clone());
+        let notifier = Notifier::new(config.notifications.clone());
+        let feedback = FeedbackStore::new(&config.storage.feedback_path);
+        let responses = Arc::new(ResponseLog::new(config.response_log.clone()));
+        let requests = RequestLog::new(config.request_log.clone());
+        let experiments = ExperimentRouter::new(config.experiments.clone());
+        let egress = EgressClassifier::new(&config.egress);
+        let guardrails = Guardrails::new(&config.suggest.guardrails);
+        let updates = UpdateNotice::start(&config.updates);
+        let sessions = Sessions::new(config.sessions.clone());
+        let events = EventBus::from_config(&config.events);
+        let plugins = Plugins::new(&config.plugins, PluginRegistry::new(default_grant(&config)));
+        let chat = ChatManager::from_parts(config.clone(), provider.clone(), Arc::new(rag_manager.clone()), plugins)
+            .with_events(events.clone());
         
-        Ok(Self {
+        Self {
             config,
             provider,
+            model,
             rag_manager,
-        })
+            notifier,
+            feedback,
+            responses,
+            requests,
+            experiments,
+            suggestions: SuggestState::default(),
+            sessions,
+            jobs: Jobs::default(),
+            egress,
+            guardrails,
+            chat,
+            watcher,
+            updates,
+            events,
+            started: Instant::now(),
+        }
     }
     
     /// Routes request to appropriate handler based on type.
-    pub async fn handle(&self, request: Request, sender: ChunkSender) {
+    ///
+    /// A request running past its `timeouts` limit is dropped where it stands
+    /// (work already handed to blocking threads finishes in the background)
+    /// and answered with a timeout error; time spent waiting for the model or
+    /// knowledge base to start does not count. Requests from clients speaking
+    /// another protocol version are refused. Chat-like requests outside
+    /// private sessions are written to the request log, and their responses
+    /// to the response log as they stream, if enabled.
+    ///
+    /// Chat-like requests wait for a slot from the chat manager's scheduler
+    /// first: one at a time per session, with sessions taking turns. The
+    /// wait does not count toward the timeout either.
+    pub async fn handle(self: &Arc<Self>, mut request: Request, sender: ChunkSender) {
+        if let Some(client) = request.client.as_ref().filter(|client| client.protocol != PROTOCOL_VERSION) {
+            warn!("Refusing a request from nucleus {} (protocol {})", client.version, client.protocol);
+            let _ = sender.send(StreamChunk::incompatible(VersionMismatch {
+                client: client.clone(),
+                server: ClientVersion::current(),
+            }));
+            return;
+        }
+        
+        if request.request_type == RequestType::Chat {
+            if let Some(argument) = session::private_command(&request.content) {
+                request.content = argument.to_string();
+                request.request_type = RequestType::Privacy;
+            } else if session::is_new_command(&request.content) {
+                request.request_type = RequestType::SessionReset;
+            }
+        }
+        
+        if self.sessions.is_private(&request) {
+            if let Some(reason) = session::private_violation(request.request_type, &self.config) {
+                let _ = sender.send(StreamChunk::error(format!("Private mode: {}", reason)));
+                return;
+            }
+        } else {
+            self.warm_standby(&request);
+        }
+        
+        if let Err(e) = self.warm_up(request.request_type, &sender).await {
+            let _ = sender.send(StreamChunk::error(e));
+            return;
+        }
+
+        let class = request.request_type.operation_class();
+        let _slot = match class {
+            Some(OperationClass::Chat) => Some(self.chat.acquire(request.session_id.as_deref()).await),
+            _ => None,
+        };
+        let sender = if class == Some(OperationClass::Chat) && !self.sessions.is_private(&request) {
+            self.log(&request, sender).await
+        } else {
+            sender
+        };
+        match class.and_then(|class| Some((class, self.config.timeouts.limit(class)?))) {
+            Some((operation, limit)) => {
+                let dispatch = self.dispatch(request, sender.clone());
+                if tokio::time::timeout(limit, dispatch).await.is_err() {
+                    warn!("{} request timed out after {}s", operation.as_str(), limit.as_secs());
+                    let _ = sender.send(StreamChunk::timed_out(Timeout {
+                        operation,
+                        limit_secs: limit.as_secs(),
+                    }));
+                }
+            }
+            None => self.dispatch(request, sender).await,
+        }
+    }
+    
+    /// Records `request` in the request log and tees its response into the
+    /// response log, whichever are enabled, under one ID.
+    async fn log(&self, request: &Request, sender: ChunkSender) -> ChunkSender {
+        let id = feedback::new_response_id();
+        if self.requests.enabled() {
+            if let Err(e) = self.requests.record(&id, request).await {
+                warn!("Failed to write the request log {}: {}", self.config.request_log.path, e);
+            }
+        }
+        if self.responses.enabled() {
+            return self.responses.tee(id, request, sender);
+        }
+        sender
+    }
+
+    /// Waits for the subsystems `request_type` uses to start, sending a
+    /// progress chunk every [`WARM_UP_PROGRESS`] until they have.
+    async fn warm_up(&self, request_type: RequestType, sender: &ChunkSender) -> Result<(), String> {
+        for &component in request_type.components() {
+            let ready = self.component_ready(component);
+            tokio::pin!(ready);
+            let mut progress = tokio::time::interval(WARM_UP_PROGRESS);
+            loop {
+                tokio::select! {
+                    biased;
+                    result = &mut ready => {
+                        result.map_err(|e| format!("The {} failed to start: {}", component.as_str(), e))?;
+                        break;
+                    }
+                    _ = progress.tick() => {
+                        let _ = sender.send(StreamChunk::warming_up(WarmingUp {
+                            component,
+                            elapsed_secs: self.started.elapsed().as_secs(),
+                        }));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn component_ready(&self, component: Component) -> Result<(), String> {
+        match component {
+            Component::Model => self.model.get().await.map(drop),
+            Component::KnowledgeBase => self.rag_manager.ready().await.map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Whether `component` has finished starting, successfully or not.
+    fn is_started(&self, component: Component) -> bool {
+        match component {
+            Component::Model => self.model.is_done(),
+            Component::KnowledgeBase => self.rag_manager.is_ready(),
+        }
+    }
+
+    async fn dispatch(self: &Arc<Self>, request: Request, sender: ChunkSender) {
         match request.request_type {
-            RequestType::Chat | RequestType::Edit => {
+            RequestType::Chat | RequestType::Edit | RequestType::Generate => {
                 self.handle_chat(request, sender).await
             }
             RequestType::Add => self.handle_add(request, sender).await,
             RequestType::Index => self.handle_index(request, sender).await,
-            RequestType::Stats => self.handle_stats(sender).await,
+            RequestType::Stats => self.handle_stats(request, sender).await,
+            RequestType::Debate => self.handle_debate(request, sender).await,
+            RequestType::PackExport => self.handle_pack_export(request, sender).await,
+            RequestType::PackImport => self.handle_pack_import(request, sender).await,
+            RequestType::PackList => self.handle_pack_list(sender).await,
+            RequestType::PackInstall => self.handle_pack_install(request, sender).await,
+            RequestType::TeamIndex
+            | RequestType::TeamRemove
+            | RequestType::TeamClear => self.handle_team_admin(request, sender).await,
+            RequestType::TeamStats => self.handle_team_stats(sender).await,
+            RequestType::Feedback => self.handle_feedback(request, sender).await,
+            RequestType::FeedbackExport => self.handle_feedback_export(request, sender).await,
+            RequestType::FeedbackStats => self.handle_feedback_stats(sender).await,
+            RequestType::Tree => self.handle_tree(request, sender).await,
+            RequestType::Suggest => self.handle_suggest(request, sender).await,
+            RequestType::Privacy => self.handle_privacy(request, sender),
+            RequestType::SessionReset => self.handle_session_reset(request, sender),
+            RequestType::SessionList => self.handle_session_list(request, sender),
+            RequestType::SessionResume => self.handle_session_resume(request, sender),
+            RequestType::SessionDelete => self.handle_session_delete(request, sender),
+            RequestType::Search => self.handle_search(request, sender).await,
+            RequestType::Diff => self.handle_diff(request, sender).await,
+            RequestType::AnalyzeLog => self.handle_analyze_log(request, sender).await,
+            RequestType::GenerateExpression => self.handle_generate_expression(request, sender).await,
+            RequestType::IndexCommands => self.handle_index_commands(sender).await,
+            RequestType::IndexDotfiles => self.handle_index_dotfiles(sender).await,
+            RequestType::IndexUrl => self.handle_index_url(request, sender).await,
+            RequestType::Remember => self.handle_remember(request, sender).await,
+            RequestType::WhatsChanged => self.handle_whats_changed(request, sender).await,
+            RequestType::Todos => self.handle_todos(request, sender).await,
+            RequestType::TodoSummary => self.handle_todo_summary(request, sender).await,
+            RequestType::Status => self.handle_status(sender).await,
+            RequestType::JobsList => self.handle_jobs_list(sender),
+            RequestType::JobsCancel => self.handle_jobs_cancel(request, sender),
+            RequestType::JobsStatus => self.handle_jobs_status(request, sender),
+            RequestType::CollectionList => self.handle_collection_list(sender),
+            RequestType::CollectionCreate => self.handle_collection_create(request, sender),
+            RequestType::CollectionDelete => self.handle_collection_delete(request, sender).await,
+            RequestType::CollectionSwitch => self.handle_collection_switch(request, sender),
+            RequestType::TrashList => self.handle_trash_list(sender).await,
+            RequestType::TrashRestore => self.handle_trash_restore(request, sender).await,
+            RequestType::TrashPurge => self.handle_trash_purge(request, sender).await,
+            RequestType::SnapshotCreate => self.handle_snapshot_create(request, sender).await,
+            RequestType::SnapshotList => self.handle_snapshot_list(sender).await,
+            RequestType::SnapshotDiff => self.handle_snapshot_diff(request, sender).await,
+            RequestType::Subscribe => self.handle_subscribe(request, sender).await,
+            RequestType::PluginsList => self.handle_plugins_list(sender).await,
+            RequestType::PluginsEnable => self.handle_plugins_enable(request, true, sender).await,
+            RequestType::PluginsDisable => self.handle_plugins_enable(request, false, sender).await,
         }
     }
     
-    async fn handle_chat(&self, request: Request, sender: ChunkSender) {
+    async fn handle_chat(&self, mut request: Request, sender: ChunkSender) {
         use crate::provider::ChatRequest;
         
-        let messages = self.build_messages(request);
+        let started = Instant::now();
+        // `/shorter` asks the last question again, and its answer replaces the earlier one
+        let shorter = request.request_type == RequestType::Chat && session::is_shorter_command(&request.content);
+        if shorter {
+            let mut history = request.history.take().unwrap_or_else(|| self.sessions.history(&request));
+            let Some(last) = history.len().checked_sub(2).filter(|&last| history[last].role == "user") else {
+                let _ = sender.send(StreamChunk::error("There is no earlier question in this conversation to answer more briefly"));
+                return;
+            };
+            request.content = history.split_off(last).swap_remove(0).content;
+            request.history = Some(history);
+            request.verbosity = Some(Verbosity::Terse);
+        }
+        // Continue the session's conversation unless the client keeps its own
+        if request.history.is_none() {
+            request.history = Some(self.sessions.history(&request)).filter(|history| !history.is_empty());
+        }
+        let deadline = request.max_time_ms
+            .map(|ms| tokio::time::Instant::from_std(started) + Duration::from_millis(ms));
+        let verbosity = request.verbosity.unwrap_or(self.config.verbosity.default);
+        let max_tokens = request.max_tokens.or(self.config.verbosity.max_tokens(verbosity));
+        let private = self.sessions.is_private(&request);
         
-        let chat_request = ChatRequest::new(&self.config.llm.model, messages)
-            .with_temperature(self.config.llm.temperature);
+        let mut variant = self.experiments.assign(&self.config, self.rag_manager.retrieval_options());
+        debug!(variant = %variant.name, "Assigned experiment variant");
+        if private {
+            variant.retrieval.include_team = false;
+        }
+        
+        let attachments = match self.resolve_attachments(&request).await {
+            Ok(attachments) => attachments,
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                return;
+            }
+        };
+        
+        let knowledge = match self.knowledge(&request).await {
+            Ok(knowledge) => knowledge,
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                return;
+            }
+        };
+        
+        let prompt = request.content.clone();
+        let all_collections = request.all_collections;
+        // Generate requests bring their own context
+        let retrieve = request.request_type != RequestType::Generate;
+        let retrieval = async {
+            if !retrieve {
+                Ok(Vec::new())
+            } else if all_collections {
+                knowledge.retrieve_all(&prompt, variant.retrieval).await
+            } else {
+                knowledge.retrieve_with(&prompt, variant.retrieval).await
+            }
+        };
+        let retrieved = match within_deadline(deadline, retrieval).await {
+            Some(Ok(results)) => results,
+            Some(Err(e)) => {
+                debug!("Could not retrieve RAG context: {}", e);
+                Vec::new()
+            }
+            None => {
+                debug!("Time budget exhausted during retrieval, continuing without context");
+                Vec::new()
+            }
+        };
+        // Files the message mentions, read as they are now if the index is missing or behind
+        let read_through = if retrieve {
+            match within_deadline(deadline, knowledge.read_through(&prompt, request.pwd.as_deref().map(Path::new))).await {
+                Some(Ok(results)) => results,
+                Some(Err(e)) => {
+                    debug!("Could not read mentioned files: {}", e);
+                    Vec::new()
+                }
+                None => Vec::new(),
+            }
+        } else {
+            Vec::new()
+        };
+        let mut read_sources: Vec<String> = read_through.iter()
+            .filter_map(|result| result.document.metadata.get("source").cloned())
+            .collect();
+        read_sources.dedup();
+        let retrieved = rag::read_through::merge(read_through, retrieved);
+        let command_docs = match request.last_command.as_ref().filter(|_| retrieve) {
+            Some(command) => match within_deadline(deadline, self.rag_manager.command_docs(&command.command)).await {
+                Some(Ok(results)) => results,
+                Some(Err(e)) => {
+                    debug!("Could not retrieve command documentation: {}", e);
+                    Vec::new()
+                }
+                None => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+        // Searching all collections already covered dotfiles and conversations
+        let dotfile_context = if retrieve && self.config.dotfiles.enabled && !all_collections {
+            match within_deadline(deadline, self.rag_manager.dotfile_context(&prompt)).await {
+                Some(Ok(results)) => results,
+                Some(Err(e)) => {
+                    debug!("Could not retrieve dotfiles: {}", e);
+                    Vec::new()
+                }
+                None => Vec::new(),
+            }
+        } else {
+            Vec::new()
+        };
+        let conversation_context = if all_collections || !retrieve {
+            Vec::new()
+        } else {
+            match within_deadline(deadline, self.rag_manager.conversation_context(&prompt)).await {
+                Some(Ok(results)) => results,
+                Some(Err(e)) => {
+                    debug!("Could not retrieve past conversations: {}", e);
+                    Vec::new()
+                }
+                None => Vec::new(),
+            }
+        };
+        let tree = match request.pwd.as_deref().filter(|_| request.include_tree) {
+            Some(pwd) => match within_deadline(deadline, render_tree(PathBuf::from(pwd))).await {
+                Some(Ok(tree)) => Some(tree),
+                Some(Err(e)) => {
+                    debug!("Could not summarize project tree: {}", e);
+                    None
+                }
+                None => None,
+            },
+            None => None,
+        };
+        let supplied = request.context.clone().unwrap_or_default();
+        let other_context = format!(
+            "{}{}{}{}{}",
+            rag::format_conversations(&conversation_context),
+            request.last_command.as_ref().map(|command| command.to_prompt()).unwrap_or_default(),
+            rag::format_command_docs(&command_docs),
+            rag::format_dotfiles(&dotfile_context),
+            attachment::format_context(&prompt, &attachments, &self.config.attachments)
+        );
+        
+        // Keep the retrieved chunks that fit next to the rest of the prompt and the answer
+        let mut budget = rag::ContextBudget::new(&self.config.llm);
+        if let Some(max_tokens) = max_tokens {
+            budget = budget.with_answer_tokens(max_tokens as usize);
+        }
+        let rest = self.build_messages(&request, &format!("{}{}", supplied, other_context), tree.as_deref());
+        let rest: Vec<&str> = rest.iter().map(|message| message.content.as_str()).collect();
+        let retrieved = budget.fit(retrieved, &rest);
+        
+        let context = format!("{}{}{}", supplied, rag::format_context(&retrieved, self.rag_manager.context_format()), other_context);
+        let history = match request.history.as_ref().filter(|_| self.config.conversations.auto && !private) {
+            Some(history) => history.clone(),
+            None => Vec::new(),
+        };
+        let messages = self.build_messages(&request, &context, tree.as_deref());
+        let outgoing: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        if let Err(e) = self.check_egress(&outgoing) {
+            let _ = sender.send(StreamChunk::error(e.to_string()));
+            return;
+        }
+        let checked = messages.len();
+        
+        let mut chat_request = ChatRequest::new(&variant.model, messages)
+            .with_temperature(variant.temperature);
+        if let Some(max_tokens) = max_tokens {
+            chat_request = chat_request.with_max_tokens(max_tokens);
+        }
+        
+        let (result, full_response, truncated) = stream_answer(&self.chat, chat_request, checked, deadline, &sender).await;
+        
+        // Cite the knowledge base context after the answer
+        let sources = rag::cite(&retrieved);
+        let footer = rag::format_sources(&sources);
+        if result.is_ok() && !footer.is_empty() {
+            let _ = sender.send(StreamChunk::chunk(&footer));
+        }
+        let cited = format!("{}{}", full_response, footer);
+        if result.is_ok() && shorter {
+            self.sessions.replace_last(&request, &prompt, &full_response);
+        } else if result.is_ok() {
+            self.sessions.record(&request, &prompt, &full_response);
+        }
+        
+        let mut remembered = None;
+        let event = match result {
+            // Private sessions are neither remembered for feedback nor announced
+            Ok(_) if private => {
+                let _ = sender.send(StreamChunk::done(cited).with_sources(sources).with_truncated(truncated));
+                return;
+            }
+            Ok(_) => {
+                let response_id = feedback::new_response_id();
+                let _ = sender.send(
+                    StreamChunk::done(cited)
+                        .with_response_id(&response_id)
+                        .with_sources(sources)
+                        .with_truncated(truncated),
+                );
+                self.events.publish(EventKind::ChatCompleted {
+                    response_id: Some(response_id.clone()),
+                    session_id: request.session_id.clone(),
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    truncated,
+                });
+                let event = OperationEvent::new(OperationKind::Generation, true, started.elapsed(), headline(&full_response, 200));
+                if self.config.conversations.auto {
+                    remembered = Some((response_id.clone(), prompt.clone(), full_response.clone()));
+                }
+                
+                self.feedback.record(Interaction {
+                    response_id,
+                    timestamp: feedback::unix_timestamp(),
+                    prompt,
+                    response: full_response,
+                    model: variant.model,
+                    temperature: variant.temperature,
+                    embedding_model: self.config.rag.embedding_model.id.clone(),
+                    retrieved: retrieved.iter().map(RetrievedChunk::from).collect(),
+                    variant: variant.name,
+                });
+                event
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                OperationEvent::new(OperationKind::Generation, false, started.elapsed(), e.to_string())
+            }
+        };
+        if !private {
+            self.spawn_notification(event);
+        }
+        
+        // The response is already complete for the client; summarizing runs after it
+        if let Some((response_id, prompt, response)) = remembered {
+            let turns = history.iter()
+                .map(|message| (message.role.as_str(), message.content.as_str()))
+                .chain([("user", prompt.as_str()), ("assistant", response.as_str())]);
+            if let Err(e) = self.remember(&response_id, turns).await {
+                warn!("Failed to remember conversation: {:#}", e);
+            }
+        }
+        
+        // Private sessions returned above, so the files read may be indexed
+        if knowledge.persists_read_through() {
+            for source in read_sources {
+                match knowledge.refresh_path(Path::new(&source)).await {
+                    Ok(true) => self.events.publish(EventKind::FileIndexed { source }),
+                    Ok(false) => {}
+                    Err(e) => warn!("Failed to index {}: {}", source, e),
+                }
+            }
+        }
+    }
+    
+    /// Summarizes an exchange and indexes the summary into the conversation collection.
+    async fn remember<'a>(
+        &self,
+        response_id: &str,
+        turns: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> anyhow::Result<usize> {
+        use crate::provider::{ChatRequest, Message};
+        
+        let settings = &self.config.conversations;
+        let transcript = conversations::transcript(turns, settings.max_transcript_chars);
+        let messages = vec![
+            Message::system(None, conversations::SYSTEM_PROMPT),
+            Message::user(None, conversations::summary_prompt(&transcript)),
+        ];
+        let outgoing: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        self.check_egress(&outgoing)?;
+        
+        let model = settings.model.clone().unwrap_or_else(|| self.config.llm.model.clone());
+        let mut summary = String::new();
+        self.provider.chat(ChatRequest::new(model, messages).with_temperature(0.2), Box::new(|response| {
+            summary.push_str(&response.message.content);
+        })).await?;
+        
+        let summary = summary.trim();
+        anyhow::ensure!(!summary.is_empty(), "the model returned an empty summary");
+        Ok(self.rag_manager.index_conversation(response_id, summary, feedback::unix_timestamp()).await?)
+    }
+    
+    async fn handle_remember(&self, request: Request, sender: ChunkSender) {
+        let interaction = match self.feedback.find(request.content.trim()) {
+            Ok(interaction) => interaction,
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                return;
+            }
+        };
+        
+        let started = Instant::now();
+        let history = request.history.unwrap_or_default();
+        let turns = history.iter()
+            .map(|message| (message.role.as_str(), message.content.as_str()))
+            .chain([("user", interaction.prompt.as_str()), ("assistant", interaction.response.as_str())]);
+        
+        let event = match self.remember(&interaction.response_id, turns).await {
+            Ok(_) => {
+                let message = "Saved a summary of the conversation to the knowledge base";
+                let _ = sender.send(StreamChunk::done(message));
+                OperationEvent::new(OperationKind::Index, true, started.elapsed(), message)
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to remember the conversation: {:#}", e)));
+                OperationEvent::new(OperationKind::Index, false, started.elapsed(), e.to_string())
+            }
+        };
+        self.spawn_notification(event);
+    }
+    
+    async fn handle_suggest(&self, request: Request, sender: ChunkSender) {
+        use crate::provider::ChatRequest;
+        
+        let settings = &self.config.suggest;
+        let session = request.session_id.clone().unwrap_or_default();
+        let (suggestion_id, cancelled) = self.suggestions.begin(&session);
+        let budget = Duration::from_millis(request.max_time_ms.unwrap_or(settings.max_time_ms));
+        let deadline = tokio::time::Instant::now() + budget;
+        
+        let mut context = match (&request.pwd, settings.use_rag) {
+            (Some(pwd), true) => {
+                let private = self.sessions.is_private(&request);
+                self.suggestion_context(&request, pwd, private, deadline).await
+            }
+            _ => String::new(),
+        };
+        if self.config.commands.suggest {
+            if let Some(Ok(results)) = within_deadline(Some(deadline), self.rag_manager.command_docs(&request.content)).await {
+                context.push_str(&rag::format_command_docs(&results));
+            }
+        }
+        if self.config.dotfiles.enabled && self.config.dotfiles.suggest {
+            if let Some(Ok(results)) = within_deadline(Some(deadline), self.rag_manager.dotfile_context(&request.content)).await {
+                context.push_str(&rag::format_dotfiles(&results));
+            }
+        }
+        
+        let messages = suggest::build_messages(&request, &context);
+        let outgoing: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        if let Err(e) = self.check_egress(&outgoing) {
+            self.suggestions.end(&session, suggestion_id);
+            let _ = sender.send(StreamChunk::error(e.to_string()));
+            return;
+        }
+        
+        let model = settings.model.clone().unwrap_or_else(|| self.config.llm.model.clone());
+        let chat_request = ChatRequest::new(model, messages)
+            .with_temperature(settings.temperature)
+            .with_max_tokens(request.max_tokens.unwrap_or(settings.max_tokens));
+        
+        let mut reply = String::new();
+        let generation = self.provider.chat(chat_request, Box::new(|response| {
+            reply.push_str(&response.message.content);
+        }));
+        let outcome = tokio::select! {
+            result = tokio::time::timeout_at(deadline, generation) => Some(result),
+            _ = cancelled => None,
+        };
+        self.suggestions.end(&session, suggestion_id);
+        
+        let chunk = match outcome {
+            None => StreamChunk::error("Suggestion superseded by newer input"),
+            Some(Err(_)) => {
+                debug!(budget_ms = budget.as_millis() as u64, "Suggestion exceeded its time budget");
+                StreamChunk::done("").with_truncated(true)
+            }
+            Some(Ok(Err(e))) => StreamChunk::error(e.to_string()),
+            Some(Ok(Ok(()))) => {
+                let suggestion = suggest::clean_suggestion(&reply);
+                let warnings = self.guardrails.check(&suggestion);
+                StreamChunk::done(suggestion).with_guardrails(warnings)
+            }
+        };
+        let _ = sender.send(chunk);
+    }
+    
+    /// Knowledge base context for suggestions, reused per working directory.
+    ///
+    /// Input a standby embedding of the session stands in for is searched
+    /// with that embedding, see [`suggest::Standby`]. Private sessions
+    /// search only the local knowledge base and bypass the cache.
+    async fn suggestion_context(
+        &self,
+        request: &Request,
+        pwd: &str,
+        private: bool,
+        deadline: tokio::time::Instant,
+    ) -> String {
+        let query = request.content.as_str();
+        let mut options = self.rag_manager.retrieval_options();
+        options.include_team = !private;
+        // Too slow for the suggestion deadline
+        options.cross_encoder = false;
+        options.diversify = false;
+
+        let standby = request.session_id.as_deref().and_then(|session| self.suggestions.standby(session, pwd));
+        if let Some((text, embedding)) = standby.as_ref().and_then(|standby| standby.find(query)).filter(|_| !private) {
+            debug!("Searching with the standby embedding of '{}'", text);
+            let retrieval = async {
+                self.knowledge_for(None, Some(Path::new(pwd))).await?.retrieve_embedded(query, embedding, options).await
+            };
+            return match within_deadline(Some(deadline), retrieval).await {
+                Some(Ok(results)) => rag::format_context(&results, self.rag_manager.context_format()),
+                _ => String::new(),
+            };
+        }
+
+        if !private {
+            if let Some(context) = self.suggestions.cached_context(pwd) {
+                return context;
+            }
+        }
+        
+        let retrieval = async {
+            self.knowledge_for(None, Some(Path::new(pwd))).await?.retrieve_with(query, options).await
+        };
+        match within_deadline(Some(deadline), retrieval).await {
+            Some(Ok(results)) => {
+                let context = rag::format_context(&results, self.rag_manager.context_format());
+                if !private {
+                    self.suggestions.cache_context(pwd, context.clone());
+                }
+                context
+            }
+            _ => String::new(),
+        }
+    }
+    
+    /// Computes the standby embeddings for suggestions in the background
+    /// when a session starts, or changes directory, with `suggest.use_rag`.
+    ///
+    /// Commands that look like they hold secrets are left out.
+    fn warm_standby(self: &Arc<Self>, request: &Request) {
+        let (Some(session), Some(pwd)) = (&request.session_id, &request.pwd) else {
+            return;
+        };
+        if !self.config.suggest.use_rag || !self.suggestions.needs_standby(session, pwd) {
+            return;
+        }
+
+        let handler = Arc::clone(self);
+        let (session, pwd) = (session.clone(), pwd.clone());
+        let shell = request.environment.as_ref().and_then(|environment| environment.shell.clone());
+        tokio::spawn(async move {
+            let texts = {
+                let (settings, pwd) = (handler.config.suggest.clone(), PathBuf::from(&pwd));
+                tokio::task::spawn_blocking(move || suggest::standby_texts(&settings, shell.as_deref(), &pwd)).await
+            };
+            let (mut commands, files) = texts.unwrap_or_default();
+            commands.retain(|command| handler.egress.scan(command).is_empty());
+
+            let texts: Vec<&str> = commands.iter().chain(&files).map(String::as_str).collect();
+            let embedded = async {
+                if texts.is_empty() {
+                    return Ok(Vec::new());
+                }
+                handler.knowledge_for(None, Some(Path::new(&pwd))).await?.embed_queries(&texts).await
+            };
+            let standby = match embedded.await {
+                Ok(embeddings) => suggest::Standby::new(&pwd, commands, files, embeddings),
+                Err(e) => {
+                    warn!("Failed to embed commands and files for suggestions: {}", e);
+                    suggest::Standby::new(&pwd, Vec::new(), Vec::new(), Vec::new())
+                }
+            };
+            debug!("Standby embeddings ready for session '{}'", session);
+            handler.suggestions.set_standby(&session, standby);
+        });
+    }
+
+    fn handle_privacy(&self, request: Request, sender: ChunkSender) {
+        let chunk = match request.content.trim() {
+            "on" => {
+                self.sessions.set_private(&request, true);
+                StreamChunk::done("Private mode on: nothing from this session leaves the machine or is stored")
+            }
+            "off" => {
+                self.sessions.set_private(&request, false);
+                StreamChunk::done("Private mode off")
+            }
+            "" | "status" => {
+                let state = if self.sessions.is_private(&request) { "on" } else { "off" };
+                StreamChunk::done(format!("Private mode is {}", state))
+            }
+            other => StreamChunk::error(format!("Unknown privacy setting '{}'; use on, off, or status", other)),
+        };
+        let _ = sender.send(chunk);
+    }
+    
+    fn handle_session_reset(&self, request: Request, sender: ChunkSender) {
+        let chunk = match (&request.session_id, self.sessions.reset(&request)) {
+            (None, _) => StreamChunk::error("Requests without a session ID have no conversation history"),
+            (Some(_), 0) => StreamChunk::done("Started a new conversation"),
+            (Some(_), turns) if self.config.sessions.persist => StreamChunk::done(format!(
+                "Started a new conversation; the earlier one ({} exchanges) is kept, see `nucleus sessions list`",
+                turns
+            )),
+            (Some(_), turns) => StreamChunk::done(format!("Started a new conversation; forgot {} earlier exchanges", turns)),
+        };
+        let _ = sender.send(chunk);
+    }
+
+    fn handle_session_list(&self, request: Request, sender: ChunkSender) {
+        let _ = sender.send(match self.sessions.list(&request) {
+            Ok(sessions) => match serde_json::to_string(&sessions) {
+                Ok(json) => StreamChunk::done(json),
+                Err(e) => StreamChunk::error(format!("Failed to encode the conversations: {}", e)),
+            },
+            Err(e) => StreamChunk::error(format!("Failed to list conversations: {}", e)),
+        });
+    }
+
+    fn handle_session_resume(&self, request: Request, sender: ChunkSender) {
+        let id = request.content.trim();
+        let _ = sender.send(match self.sessions.resume(&request, id) {
+            Ok(turns) => StreamChunk::done(format!("Resumed '{}' ({} recent exchanges)", id, turns)),
+            Err(e) => StreamChunk::error(format!("Failed to resume: {}", e)),
+        });
+    }
+
+    fn handle_session_delete(&self, request: Request, sender: ChunkSender) {
+        let id = request.content.trim();
+        let _ = sender.send(match self.sessions.delete(id) {
+            Ok(()) => StreamChunk::done(format!("Deleted conversation '{}'", id)),
+            Err(e) => StreamChunk::error(format!("Failed to delete: {}", e)),
+        });
+    }
+    
+    /// Checks outgoing text for secrets if the provider is remote.
+    ///
+    /// Clients cannot be asked for confirmation, so `confirm` mode blocks like `block`.
+    fn check_egress(&self, outgoing: &[&str]) -> Result<(), EgressError> {
+        if !self.provider.is_remote() {
+            return Ok(());
+        }
+        self.egress.check(self.config.egress.mode, &outgoing.join("\n"), None)
+    }
+    
+    /// Reads and validates the request's attachments.
+    async fn resolve_attachments(&self, request: &Request) -> attachment::Result<Vec<ResolvedAttachment>> {
+        let pwd = request.pwd.as_deref().map(Path::new);
+        let mut resolved = Vec::with_capacity(request.attachments.len());
+        for attachment in &request.attachments {
+            resolved.push(attachment.resolve(pwd, &self.config.attachments).await?);
+        }
+        Ok(resolved)
+    }
+    
+    async fn handle_debate(&self, request: Request, sender: ChunkSender) {
+        let orchestrator = Orchestrator::new(self.provider.clone(), &self.config);
+        let started = Instant::now();
+        let private = self.sessions.is_private(&request);
+        if let Err(e) = self.check_egress(&[&request.content]) {
+            let _ = sender.send(StreamChunk::error(e.to_string()));
+            return;
+        }
+        
+        let result = orchestrator.review_loop(&request.content, |chunk| {
+            if !chunk.is_empty() {
+                let _ = sender.send(StreamChunk::chunk(chunk));
+            }
+        }).await;
+        
+        let event = match result {
+            Ok(outcome) => {
+                let _ = sender.send(StreamChunk::done(&outcome.final_answer));
+                let summary = format!(
+                    "Review loop {} after {} round(s)",
+                    if outcome.approved { "approved" } else { "merged" },
+                    outcome.rounds.len()
+                );
+                OperationEvent::new(OperationKind::Agent, true, started.elapsed(), summary)
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Review loop failed: {:#}", e)));
+                OperationEvent::new(OperationKind::Agent, false, started.elapsed(), e.to_string())
+            }
+        };
+        if !private {
+            self.spawn_notification(event);
+        }
+    }
+    
+    async fn handle_whats_changed(&self, request: Request, sender: ChunkSender) {
+        use crate::provider::{ChatRequest, Message};
+        
+        let started = Instant::now();
+        let private = self.sessions.is_private(&request);
+        let Some(pwd) = request.pwd.clone() else {
+            let _ = sender.send(StreamChunk::error("whats-changed needs the working directory (pwd)"));
+            return;
+        };
+        let commits = request.limit.unwrap_or(changes::RECENT_COMMITS);
+        
+        let collected = tokio::task::spawn_blocking(move || Changes::collect(Path::new(&pwd), commits)).await;
+        let changes = match collected {
+            Ok(Ok(changes)) => changes,
+            Ok(Err(e)) => {
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                return;
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to collect changes: {}", e)));
+                return;
+            }
+        };
+        if changes.is_empty() {
+            let _ = sender.send(StreamChunk::done("No uncommitted changes or commits yet"));
+            return;
+        }
         
-        let mut full_response = String::new();
+        let messages = vec![
+            Message::system(None, &self.config.system_prompt),
+            Message::user(None, changes.summary_prompt(&request.content)),
+        ];
+        let outgoing: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        if let Err(e) = self.check_egress(&outgoing) {
+            let _ = sender.send(StreamChunk::error(e.to_string()));
+            return;
+        }
         
+        let chat_request = ChatRequest::new(&self.config.llm.model, messages)
+            .with_temperature(self.config.llm.temperature);
+        let mut summary = String::new();
         let result = self.provider.chat(chat_request, Box::new(|response| {
             if !response.message.content.is_empty() {
-                full_response.push_str(&response.message.content);
+                summary.push_str(&response.message.content);
                 let _ = sender.send(StreamChunk::chunk(&response.message.content));
             }
         })).await;
         
-        match result {
+        let event = match result {
             Ok(_) => {
-                let _ = sender.send(StreamChunk::done(&full_response));
+                let _ = sender.send(StreamChunk::done(&summary));
+                let title = match changes.branch.as_str() {
+                    "" => "Summarized changes".to_string(),
+                    branch => format!("Summarized changes on {}", branch),
+                };
+                OperationEvent::new(OperationKind::Generation, true, started.elapsed(), title)
             }
             Err(e) => {
                 let _ = sender.send(StreamChunk::error(e.to_string()));
+                OperationEvent::new(OperationKind::Generation, false, started.elapsed(), e.to_string())
             }
+        };
+        if !private {
+            self.spawn_notification(event);
         }
     }
     
-    async fn handle_add(&self, request: Request, sender: ChunkSender) {
-        match self.rag_manager.add_knowledge(&request.content, "user_input").await {
+    async fn handle_todos(&self, request: Request, sender: ChunkSender) {
+        let files = match self.collect_todos(&request).await {
+            Ok(files) => files,
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(e));
+                return;
+            }
+        };
+        
+        match serde_json::to_string(&files) {
+            Ok(json) => {
+                let _ = sender.send(StreamChunk::done(json));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to encode results: {}", e)));
+            }
+        }
+    }
+    
+    async fn handle_todo_summary(&self, request: Request, sender: ChunkSender) {
+        use crate::provider::{ChatRequest, Message};
+        
+        let started = Instant::now();
+        let private = self.sessions.is_private(&request);
+        let files = match self.collect_todos(&request).await {
+            Ok(files) => files,
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(e));
+                return;
+            }
+        };
+        if files.is_empty() {
+            let _ = sender.send(StreamChunk::done("No TODO, FIXME, or HACK comments in the indexed files"));
+            return;
+        }
+        
+        let messages = vec![
+            Message::system(None, &self.config.system_prompt),
+            Message::user(None, todos::summary_prompt(&files, feedback::unix_timestamp())),
+        ];
+        let outgoing: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        if let Err(e) = self.check_egress(&outgoing) {
+            let _ = sender.send(StreamChunk::error(e.to_string()));
+            return;
+        }
+        
+        let chat_request = ChatRequest::new(&self.config.llm.model, messages)
+            .with_temperature(self.config.llm.temperature);
+        let mut summary = String::new();
+        let result = self.provider.chat(chat_request, Box::new(|response| {
+            if !response.message.content.is_empty() {
+                summary.push_str(&response.message.content);
+                let _ = sender.send(StreamChunk::chunk(&response.message.content));
+            }
+        })).await;
+        
+        let count: usize = files.iter().map(|file| file.todos.len()).sum();
+        let event = match result {
             Ok(_) => {
-                let _ = sender.send(StreamChunk::done("Added to knowledge base"));
+                let _ = sender.send(StreamChunk::done(&summary));
+                let title = format!("Summarized {} TODOs in {} files", count, files.len());
+                OperationEvent::new(OperationKind::Generation, true, started.elapsed(), title)
             }
             Err(e) => {
-                let _ = sender.send(StreamChunk::error(format!("Failed to add: {}", e)));
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                OperationEvent::new(OperationKind::Generation, false, started.elapsed(), e.to_string())
             }
+        };
+        if !private {
+            self.spawn_notification(event);
         }
     }
     
-    async fn handle_index(&self, request: Request, sender: ChunkSender) {
-        let dir = request.pwd.clone().expect("Invalid directory");
-        let path_dir = Path::new(&dir);
-        match self.rag_manager.index_directory(&path_dir).await {
-            Ok(count) => {
-                let _ = sender.send(StreamChunk::done(format!(
-                    "Indexed {} files from: {}",
-                    count, request.content
+    /// Scans the indexed files under the request's directory for marker comments.
+    ///
+    /// Files are read from disk, so markers added since indexing are found too.
+    async fn collect_todos(&self, request: &Request) -> Result<Vec<TodoFile>, String> {
+        let scope = if request.content.trim().is_empty() {
+            request.pwd.as_ref().map(PathBuf::from)
+        } else {
+            Some(resolve_path(request))
+        };
+        let scope = scope.map(|dir| dir.canonicalize().unwrap_or(dir));
+        
+        let knowledge = self.knowledge(request).await.map_err(|e| e.to_string())?;
+        let sources = knowledge.get_indexed_paths().await
+            .map_err(|e| format!("Failed to list indexed files: {}", e))?;
+        let paths: Vec<PathBuf> = sources.into_iter()
+            .map(PathBuf::from)
+            .filter(|path| path.is_file())
+            .filter(|path| match &scope {
+                Some(scope) => path.canonicalize().is_ok_and(|path| path.starts_with(scope)),
+                None => true,
+            })
+            .collect();
+        
+        tokio::task::spawn_blocking(move || {
+            let mut files: Vec<TodoFile> = paths.iter().filter_map(|path| todos::scan_file(path)).collect();
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            files
+        })
+        .await
+        .map_err(|e| format!("Failed to scan files: {}", e))
+    }
+    
+    async fn handle_diff(&self, request: Request, sender: ChunkSender) {
+        use crate::provider::{ChatRequest, Message};
+        
+        let started = Instant::now();
+        let private = self.sessions.is_private(&request);
+        let (old, new) = match self.resolve_attachments(&request).await.as_deref() {
+            Ok([old, new]) => (old.clone(), new.clone()),
+            Ok(attachments) => {
+                let _ = sender.send(StreamChunk::error(format!(
+                    "Diff needs exactly two attachments (old and new), got {}",
+                    attachments.len()
                 )));
+                return;
             }
             Err(e) => {
-                let _ = sender.send(StreamChunk::error(format!("Failed to index: {}", e)));
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                return;
+            }
+        };
+        
+        let unified = diff::unified(&old.name, &new.name, &old.content, &new.content);
+        if unified.is_empty() {
+            let _ = sender.send(StreamChunk::done(format!("No differences between {} and {}", old.name, new.name)));
+            return;
+        }
+        
+        let messages = vec![
+            Message::system(None, &self.config.system_prompt),
+            Message::user(None, diff::explain_prompt(&unified, &request.content)),
+        ];
+        let outgoing: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        if let Err(e) = self.check_egress(&outgoing) {
+            let _ = sender.send(StreamChunk::error(e.to_string()));
+            return;
+        }
+        
+        let chat_request = ChatRequest::new(&self.config.llm.model, messages)
+            .with_temperature(self.config.llm.temperature);
+        let mut explanation = String::new();
+        let result = self.provider.chat(chat_request, Box::new(|response| {
+            if !response.message.content.is_empty() {
+                explanation.push_str(&response.message.content);
+                let _ = sender.send(StreamChunk::chunk(&response.message.content));
+            }
+        })).await;
+        
+        let event = match result {
+            Ok(_) => {
+                let _ = sender.send(StreamChunk::done(&explanation));
+                let summary = format!("Compared {} and {}", old.name, new.name);
+                OperationEvent::new(OperationKind::Generation, true, started.elapsed(), summary)
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                OperationEvent::new(OperationKind::Generation, false, started.elapsed(), e.to_string())
             }
+        };
+        if !private {
+            self.spawn_notification(event);
         }
     }
     
-    async fn handle_stats(&self, sender: ChunkSender) {
-        let count = self.rag_manager.count().await;
-        let _ = sender.send(StreamChunk::done(format!(
-            "Knowledge base contains {} documents",
-            count
-        )));
+    async fn handle_analyze_log(&self, request: Request, sender: ChunkSender) {
+        let started = Instant::now();
+        let private = self.sessions.is_private(&request);
+        let path = resolve_path(&request);
+        let content = match tokio::fs::read(&path).await {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to read {}: {}", path.display(), e)));
+                return;
+            }
+        };
+        
+        let plan = log_analysis::plan(&content, &self.config.log_analysis);
+        drop(content);
+        let outgoing: Vec<&str> = plan.chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        if let Err(e) = self.check_egress(&outgoing) {
+            let _ = sender.send(StreamChunk::error(e.to_string()));
+            return;
+        }
+        
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string());
+        let analyzer = LogAnalyzer::new(self.provider.clone(), &self.config);
+        let result = analyzer.analyze(&name, &plan, |chunk| {
+            if !chunk.is_empty() {
+                let _ = sender.send(StreamChunk::chunk(chunk));
+            }
+        }).await;
+        
+        let event = match result {
+            Ok(report) => {
+                let _ = sender.send(StreamChunk::done(&report));
+                let summary = format!("Analyzed {} ({} lines, {} error clusters)", name, plan.total_lines, plan.clusters.len());
+                OperationEvent::new(OperationKind::Agent, true, started.elapsed(), summary)
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Log analysis failed: {:#}", e)));
+                OperationEvent::new(OperationKind::Agent, false, started.elapsed(), e.to_string())
+            }
+        };
+        if !private {
+            self.spawn_notification(event);
+        }
     }
     
-    fn build_messages(&self, request: Request) -> Vec<crate::provider::Message> {
-        use crate::provider::Message;
+    async fn handle_generate_expression(&self, request: Request, sender: ChunkSender) {
+        let started = Instant::now();
+        let private = self.sessions.is_private(&request);
+        let kind = request.expression_kind.unwrap_or_default();
+        let sample = match self.resolve_attachments(&request).await.as_deref() {
+            Ok([sample]) => sample.content.clone(),
+            Ok(attachments) => {
+                let _ = sender.send(StreamChunk::error(format!(
+                    "Expression generation needs exactly one attachment (the sample), got {}",
+                    attachments.len()
+                )));
+                return;
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                return;
+            }
+        };
+        if let Err(e) = self.check_egress(&[&request.content, &sample]) {
+            let _ = sender.send(StreamChunk::error(e.to_string()));
+            return;
+        }
         
-        let mut messages = vec![Message::system(None, &self.config.system_prompt)];
+        let generator = ExpressionGenerator::new(self.provider.clone(), &self.config);
+        let result = generator.generate(kind, &request.content, &sample, |expression, reason| {
+            let _ = sender.send(StreamChunk::chunk(format!("Rejected `{}`: {}\n", expression, reason)));
+        }).await;
         
-        if let Some(history) = request.history {
-            for msg in history {
-                messages.push(Message {
-                    role: "user".to_string(),
-                    context: None,
-                    content: msg.content.clone(),
-                    images: None,
-                    tool_calls: None,
-                });
+        let event = match result {
+            Ok(generated) => {
+                let outputs: String = generated.outputs.iter().map(|output| format!("  {}\n", output)).collect();
+                let _ = sender.send(StreamChunk::chunk(format!("Output on the sample:\n{}", outputs)));
+                let _ = sender.send(StreamChunk::done(&generated.expression));
+                let summary = format!("Generated {} in {} attempt(s)", kind, generated.attempts);
+                OperationEvent::new(OperationKind::Generation, true, started.elapsed(), summary)
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("{:#}", e)));
+                OperationEvent::new(OperationKind::Generation, false, started.elapsed(), e.to_string())
             }
+        };
+        if !private {
+            self.spawn_notification(event);
         }
+    }
+    
+    async fn handle_index_commands(&self, sender: ChunkSender) {
+        let started = Instant::now();
+        let result = command_docs::refresh(&self.rag_manager, &self.config.commands, |line| {
+            let _ = sender.send(StreamChunk::chunk(line));
+        }).await;
         
-        messages.push(Message::user(None, &request.content));
-        messages
+        let event = match result {
+            Ok(summary) => {
+                let message = format!(
+                    "Indexed documentation of {} command(s) ({} unchanged, {} removed, {} without documentation)",
+                    summary.indexed, summary.unchanged, summary.removed, summary.undocumented
+                );
+                let _ = sender.send(StreamChunk::done(&message));
+                OperationEvent::new(OperationKind::Index, true, started.elapsed(), message)
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to index commands: {:#}", e)));
+                OperationEvent::new(OperationKind::Index, false, started.elapsed(), e.to_string())
+            }
+        };
+        self.spawn_notification(event);
+    }
+    
+    async fn handle_index_dotfiles(&self, sender: ChunkSender) {
+        if !self.config.dotfiles.enabled {
+            let _ = sender.send(StreamChunk::error("Dotfile indexing is disabled; set dotfiles.enabled: true to opt in"));
+            return;
+        }
+        
+        let started = Instant::now();
+        let result = dotfiles::refresh(&self.rag_manager, &self.config.dotfiles, &self.egress, |line| {
+            let _ = sender.send(StreamChunk::chunk(line));
+        }).await;
+        
+        let event = match result {
+            Ok(summary) => {
+                let message = format!(
+                    "Indexed {} dotfile(s) ({} unchanged, {} removed, {} secret(s) redacted)",
+                    summary.indexed, summary.unchanged, summary.removed, summary.redactions
+                );
+                let _ = sender.send(StreamChunk::done(&message));
+                OperationEvent::new(OperationKind::Index, true, started.elapsed(), message)
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to index dotfiles: {:#}", e)));
+                OperationEvent::new(OperationKind::Index, false, started.elapsed(), e.to_string())
+            }
+        };
+        self.spawn_notification(event);
+    }
+    
+    async fn handle_add(&self, request: Request, sender: ChunkSender) {
+        let added = async { self.knowledge(&request).await?.add_knowledge(&request.content, "user_input").await };
+        match added.await {
+            Ok(_) => {
+                let _ = sender.send(StreamChunk::done("Added to knowledge base"));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to add: {}", e)));
+            }
+        }
+    }
+    
+    /// Indexes `pwd` as a job, streaming its progress until it is done; with
+    /// `detach`, answers with the job ID and indexes in the background,
+    /// beyond the request's time limit.
+    async fn handle_index(self: &Arc<Self>, request: Request, sender: ChunkSender) {
+        let Some(dir) = request.pwd.clone().map(PathBuf::from) else {
+            let _ = sender.send(StreamChunk::error("index requires a working directory"));
+            return;
+        };
+        let job = self.jobs.start(format!("index {}", dir.display()));
+        let id = job.id();
+
+        if request.detach {
+            let handler = Arc::clone(self);
+            let description = format!("Indexing {} as job {}", dir.display(), id);
+            tokio::spawn(async move {
+                let _ = handler.run_index(job, &dir, request.collection.as_deref(), |_| {}).await;
+            });
+            let _ = sender.send(StreamChunk::done(description).with_job(id));
+            return;
+        }
+
+        // The first chunk tells the client which job to cancel
+        let _ = sender.send(StreamChunk::progress(id, rag::IndexProgress::default()));
+        let on_progress = |progress: &rag::IndexProgress| {
+            let _ = sender.send(StreamChunk::progress(id, progress.clone()));
+        };
+        match self.run_index(job, &dir, request.collection.as_deref(), on_progress).await {
+            Ok(count) => {
+                let _ = sender.send(StreamChunk::done(format!("Indexed {} files from: {}", count, request.content)).with_job(id));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to index: {}", e)));
+            }
+        }
+    }
+    
+    async fn handle_index_url(&self, request: Request, sender: ChunkSender) {
+        let url = request.content.trim();
+        let started = Instant::now();
+        let result = async { self.knowledge(&request).await?.index_url(url).await }.await;
+
+        let (success, summary) = match &result {
+            Ok(0) => (true, format!("{} is already up to date", url)),
+            Ok(count) => (true, format!("Indexed {} ({} chunks)", url, count)),
+            Err(e) => (false, format!("Failed to index: {}", e)),
+        };
+        if matches!(result, Ok(count) if count > 0) {
+            self.events.publish(EventKind::FileIndexed { source: url.to_string() });
+        }
+        self.spawn_notification(OperationEvent::new(OperationKind::Index, success, started.elapsed(), summary.clone()));
+
+        let _ = sender.send(if success { StreamChunk::done(summary) } else { StreamChunk::error(summary) });
+    }
+
+    /// Indexes `dir` into the local knowledge base and notifies about the outcome.
+    ///
+    /// The directory goes into the `collection` named, or else the one it
+    /// belongs to. Runs as a job that a jobs-cancel request can stop. With
+    /// `watch.enabled`, the directory is watched for changes afterwards.
+    pub(super) async fn index(&self, dir: &Path, collection: Option<&str>) -> rag::Result<usize> {
+        let job = self.jobs.start(format!("index {}", dir.display()));
+        self.run_index(job, dir, collection, |_| {}).await
+    }
+
+    /// Runs [`index`](Self::index) as `job`, passing its progress to
+    /// `on_progress`; the outcome stays available to jobs-status requests.
+    async fn run_index<F>(&self, job: Job, dir: &Path, collection: Option<&str>, on_progress: F) -> rag::Result<usize>
+    where
+        F: Fn(&rag::IndexProgress) + Sync,
+    {
+        let started = Instant::now();
+        info!("Indexing {} as job {}", dir.display(), job.id());
+        let result = match self.knowledge_for(collection, Some(dir)).await {
+            Ok(knowledge) => {
+                let on_progress = |progress: &rag::IndexProgress| {
+                    job.report(progress);
+                    for source in &progress.finished {
+                        self.events.publish(EventKind::FileIndexed { source: source.clone() });
+                    }
+                    on_progress(progress);
+                };
+                knowledge.index_directory_with(dir, job.token(), on_progress).await
+            }
+            Err(e) => Err(e),
+        };
+        
+        let (success, summary) = match &result {
+            Ok(count) => (true, format!("Indexed {} files from: {}", count, dir.display())),
+            Err(e) => (false, format!("Failed to index: {}", e)),
+        };
+        job.finish(JobOutcome {
+            success,
+            summary: summary.clone(),
+        });
+        self.spawn_notification(OperationEvent::new(OperationKind::Index, success, started.elapsed(), summary));
+        
+        if let (Ok(_), Some(watcher)) = (&result, &self.watcher) {
+            match watcher.watch(dir) {
+                Ok(true) => info!("Watching {} for changes", dir.display()),
+                Ok(false) => {}
+                Err(e) => warn!("Failed to watch {}: {}", dir.display(), e),
+            }
+        }
+        result
+    }
+    
+    /// Looks for stale sources in every collection, see [`super::freshness`].
+    pub(super) async fn check_freshness(&self) {
+        for collection in self.rag_manager.list_collections() {
+            if let Err(e) = self.refresh_stale(&collection.name).await {
+                warn!("Failed to check collection '{}' for stale sources: {}", collection.name, e);
+            }
+        }
+    }
+
+    /// Logs the stale sources in `collection`, or with `rag.freshness.auto_refresh`
+    /// re-indexes them as a job that a jobs-cancel request can stop.
+    async fn refresh_stale(&self, collection: &str) -> rag::Result<()> {
+        let knowledge = self.knowledge_for(Some(collection), None).await?;
+        let sources = knowledge.source_stats().await?;
+        let stale = rag::freshness::find_stale(sources, self.config.rag.freshness.url_max_age()).await;
+        if stale.is_empty() {
+            return Ok(());
+        }
+        if !self.config.rag.freshness.auto_refresh {
+            warn!(
+                "{} sources in collection '{}' changed since they were indexed, see `nucleus stats`",
+                stale.len(),
+                collection
+            );
+            return Ok(());
+        }
+
+        let job = self.jobs.start(format!("refresh {} stale sources in {}", stale.len(), collection));
+        let started = Instant::now();
+        let mut failed = 0;
+        for (i, source) in stale.iter().enumerate() {
+            if job.token().is_cancelled() {
+                break;
+            }
+            match knowledge.refresh_source(&source.source).await {
+                Ok(true) if rag::freshness::is_url(&source.source) || Path::new(&source.source).is_file() => {
+                    self.events.publish(EventKind::FileIndexed { source: source.source.clone() });
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Failed to refresh {}: {}", source.source, e);
+                    failed += 1;
+                }
+            }
+            job.report(&rag::IndexProgress {
+                files_done: i + 1,
+                files_total: stale.len(),
+                current: Some(source.source.clone()),
+                ..rag::IndexProgress::default()
+            });
+        }
+
+        let (success, summary) = if job.token().is_cancelled() {
+            (false, format!("Cancelled refreshing stale sources in collection '{}'", collection))
+        } else {
+            let refreshed = stale.len() - failed;
+            (failed == 0, format!("Refreshed {} of {} stale sources in collection '{}'", refreshed, stale.len(), collection))
+        };
+        info!("{}", summary);
+        job.finish(JobOutcome {
+            success,
+            summary: summary.clone(),
+        });
+        self.spawn_notification(OperationEvent::new(OperationKind::Index, success, started.elapsed(), summary));
+        Ok(())
+    }
+
+    fn handle_jobs_list(&self, sender: ChunkSender) {
+        let _ = sender.send(match serde_json::to_string(&self.jobs.list()) {
+            Ok(json) => StreamChunk::done(json),
+            Err(e) => StreamChunk::error(format!("Failed to encode jobs: {}", e)),
+        });
+    }
+
+    fn handle_jobs_cancel(&self, request: Request, sender: ChunkSender) {
+        let id = request.content.trim();
+        let _ = sender.send(match id.parse::<u64>().ok().and_then(|id| self.jobs.cancel(id)) {
+            Some(description) => StreamChunk::done(format!("Cancelling job {} ({})", id, description)),
+            None => StreamChunk::error(format!("No running job with ID '{}'", id)),
+        });
+    }
+
+    fn handle_jobs_status(&self, request: Request, sender: ChunkSender) {
+        let id = request.content.trim();
+        let _ = sender.send(match id.parse::<u64>().ok().and_then(|id| self.jobs.status(id)) {
+            Some(job) => match serde_json::to_string(&job) {
+                Ok(json) => StreamChunk::done(json),
+                Err(e) => StreamChunk::error(format!("Failed to encode job: {}", e)),
+            },
+            None => StreamChunk::error(format!("No running or recently finished job with ID '{}'", id)),
+        });
+    }
+
+    /// The knowledge base a request searches or writes to: the collection it
+    /// names, or else the one for its working directory.
+    async fn knowledge(&self, request: &Request) -> rag::Result<rag::RagEngine> {
+        self.knowledge_for(request.collection.as_deref(), request.pwd.as_deref().map(Path::new)).await
+    }
+
+    async fn knowledge_for(&self, requested: Option<&str>, cwd: Option<&Path>) -> rag::Result<rag::RagEngine> {
+        let name = self.rag_manager.resolve_collection(requested, cwd);
+        if name == rag::DEFAULT_COLLECTION {
+            return Ok(self.rag_manager.clone());
+        }
+        self.rag_manager.collection(&name).await
+    }
+
+    fn handle_collection_list(&self, sender: ChunkSender) {
+        let _ = sender.send(match serde_json::to_string(&self.rag_manager.list_collections()) {
+            Ok(json) => StreamChunk::done(json),
+            Err(e) => StreamChunk::error(format!("Failed to encode collections: {}", e)),
+        });
+    }
+
+    fn handle_collection_create(&self, request: Request, sender: ChunkSender) {
+        let name = request.content.trim();
+        let roots: Vec<PathBuf> = request.pwd.iter().map(PathBuf::from).collect();
+        let _ = sender.send(match self.rag_manager.create_collection(name, roots.clone()) {
+            Ok(()) => match roots.first() {
+                Some(root) => StreamChunk::done(format!("Created collection '{}' for {}", name, root.display())),
+                None => StreamChunk::done(format!("Created collection '{}'", name)),
+            },
+            Err(e) => StreamChunk::error(format!("Failed to create collection: {}", e)),
+        });
+    }
+
+    async fn handle_collection_delete(&self, request: Request, sender: ChunkSender) {
+        let name = request.content.trim();
+        let _ = sender.send(match self.rag_manager.delete_collection(name).await {
+            Ok(()) => StreamChunk::done(format!("Deleted collection '{}'{}", name, self.trash_note())),
+            Err(e) => StreamChunk::error(format!("Failed to delete collection: {}", e)),
+        });
+    }
+
+    fn handle_collection_switch(&self, request: Request, sender: ChunkSender) {
+        let name = request.content.trim();
+        let _ = sender.send(match self.rag_manager.switch_collection(name) {
+            Ok(()) => StreamChunk::done(format!("Switched to collection '{}'", name)),
+            Err(e) => StreamChunk::error(format!("Failed to switch collection: {}", e)),
+        });
+    }
+
+    /// Where removed documents went, for messages about removals.
+    fn trash_note(&self) -> &'static str {
+        if self.config.storage.trash.enabled {
+            " (moved to the trash, see `nucleus trash list`)"
+        } else {
+            ""
+        }
+    }
+
+    async fn handle_trash_list(&self, sender: ChunkSender) {
+        let _ = sender.send(match self.rag_manager.list_trash().await {
+            Ok(entries) => match serde_json::to_string(&entries) {
+                Ok(json) => StreamChunk::done(json),
+                Err(e) => StreamChunk::error(format!("Failed to encode the trash: {}", e)),
+            },
+            Err(e) => StreamChunk::error(format!("Failed to list the trash: {}", e)),
+        });
+    }
+
+    async fn handle_trash_restore(&self, request: Request, sender: ChunkSender) {
+        let id = request.content.trim();
+        let _ = sender.send(match self.rag_manager.restore_trash(id).await {
+            Ok(entry) => {
+                let what = entry.source.as_deref().map(|source| format!(" from {}", source)).unwrap_or_default();
+                StreamChunk::done(format!("Restored {} documents{} to the {}", entry.documents, what, entry.origin))
+            }
+            Err(e) => StreamChunk::error(format!("Failed to restore: {}", e)),
+        });
+    }
+
+    async fn handle_trash_purge(&self, request: Request, sender: ChunkSender) {
+        let id = Some(request.content.trim()).filter(|id| !id.is_empty());
+        let _ = sender.send(match self.rag_manager.purge_trash(id).await {
+            Ok(purged) => StreamChunk::done(format!("Deleted {} trash entries", purged)),
+            Err(e) => StreamChunk::error(format!("Failed to purge the trash: {}", e)),
+        });
+    }
+
+    async fn handle_snapshot_create(&self, request: Request, sender: ChunkSender) {
+        let name = Some(request.content.trim()).filter(|name| !name.is_empty());
+        let snapshot = async { self.knowledge(&request).await?.snapshot(name).await };
+        let _ = sender.send(match snapshot.await {
+            Ok(snapshot) => StreamChunk::done(format!(
+                "Saved snapshot '{}' of {} sources in the {}",
+                snapshot.name, snapshot.sources, snapshot.origin
+            )),
+            Err(e) => StreamChunk::error(format!("Failed to snapshot the knowledge base: {}", e)),
+        });
+    }
+
+    async fn handle_snapshot_list(&self, sender: ChunkSender) {
+        let _ = sender.send(match self.rag_manager.list_snapshots().await {
+            Ok(snapshots) => match serde_json::to_string(&snapshots) {
+                Ok(json) => StreamChunk::done(json),
+                Err(e) => StreamChunk::error(format!("Failed to encode snapshots: {}", e)),
+            },
+            Err(e) => StreamChunk::error(format!("Failed to list snapshots: {}", e)),
+        });
+    }
+
+    async fn handle_snapshot_diff(&self, request: Request, sender: ChunkSender) {
+        let _ = sender.send(match self.rag_manager.diff_snapshot(request.content.trim()).await {
+            Ok(diff) => match serde_json::to_string(&diff) {
+                Ok(json) => StreamChunk::done(json),
+                Err(e) => StreamChunk::error(format!("Failed to encode the diff: {}", e)),
+            },
+            Err(e) => StreamChunk::error(format!("Failed to compare with the snapshot: {}", e)),
+        });
+    }
+
+    /// Applies changes reported by the directory watcher to the knowledge base.
+    ///
+    /// Each file is updated in the collection it belongs to. Not announced
+    /// through notifications, which would fire on every save.
+    pub(super) async fn refresh(&self, paths: impl IntoIterator<Item = PathBuf>) {
+        for path in paths {
+            let refreshed = async { self.knowledge_for(None, Some(&path)).await?.refresh_path(&path).await };
+            match refreshed.await {
+                Ok(true) => {
+                    info!("Updated {} in the knowledge base", path.display());
+                    // Removed files and re-indexed directories aren't announced
+                    if path.is_file() {
+                        self.events.publish(EventKind::FileIndexed {
+                            source: rag::source_key(&path),
+                        });
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => warn!("Failed to update {} in the knowledge base: {}", path.display(), e),
+            }
+        }
+    }
+    
+    async fn handle_search(&self, request: Request, sender: ChunkSender) {
+        let hits: Vec<SearchHit> = match self.search(&request).await {
+            Ok(results) => results.into_iter().map(SearchHit::from).collect(),
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Search failed: {}", e)));
+                return;
+            }
+        };
+        
+        match serde_json::to_string(&hits) {
+            Ok(json) => {
+                let _ = sender.send(StreamChunk::done(json));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to encode results: {}", e)));
+            }
+        }
+    }
+    
+    /// Searches the knowledge base for `request.content` without asking the LLM.
+    ///
+    /// Private sessions only search the local knowledge base, and only
+    /// rerank with a local model.
+    pub(super) async fn search(&self, request: &Request) -> rag::Result<Vec<rag::SearchResult>> {
+        let mut options = self.rag_manager.retrieval_options();
+        if let Some(limit) = request.limit {
+            options.top_k = limit;
+        }
+        // Private sessions keep the knowledge base on this machine
+        let private = self.sessions.is_private(request);
+        options.include_team &= !private;
+        options.cross_encoder &= !private || self.config.llm.is_local();
+        
+        let knowledge = self.knowledge(request).await?;
+        if request.all_collections {
+            knowledge.retrieve_all(&request.content, options).await
+        } else {
+            knowledge.retrieve_with(&request.content, options).await
+        }
+    }
+    
+    async fn handle_pack_export(&self, request: Request, sender: ChunkSender) {
+        let path = resolve_path(&request);
+        let pack = request.pack.clone().unwrap_or_else(|| ContextPack::new(pack_name_from_path(&path)));
+        let name = pack.name.clone();
+        
+        let exported = async { self.knowledge(&request).await?.export_pack(pack, &path).await };
+        match exported.await {
+            Ok(count) => {
+                let _ = sender.send(StreamChunk::done(format!(
+                    "Exported pack '{}' with {} documents to {}",
+                    name, count, path.display()
+                )));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to export pack: {}", e)));
+            }
+        }
+    }
+    
+    async fn handle_pack_import(&self, request: Request, sender: ChunkSender) {
+        let path = resolve_path(&request);
+        
+        let imported = async { self.knowledge(&request).await?.import_pack(&path).await };
+        match imported.await {
+            Ok(pack) => {
+                let _ = sender.send(StreamChunk::done(format!(
+                    "Imported pack '{}' ({} sources, {} prompts, {} memories)",
+                    pack.name, pack.sources.len(), pack.prompts.len(), pack.memories.len()
+                )));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to import pack: {}", e)));
+            }
+        }
+    }
+    
+    async fn handle_pack_install(&self, request: Request, sender: ChunkSender) {
+        let name = request.content.trim();
+        let _ = sender.send(match self.rag_manager.install_starter_pack(name).await {
+            Ok(pack) => StreamChunk::done(format!(
+                "Installed starter pack '{}' into collection '{}'; it is searched with every question",
+                pack.name, name
+            )),
+            Err(e) => StreamChunk::error(format!("Failed to install starter pack: {}", e)),
+        });
+    }
+    
+    async fn handle_pack_list(&self, sender: ChunkSender) {
+        match self.rag_manager.list_packs().await {
+            Ok(packs) if packs.is_empty() => {
+                let _ = sender.send(StreamChunk::done("No context packs imported"));
+            }
+            Ok(packs) => {
+                let lines: Vec<String> = packs
+                    .iter()
+                    .map(|pack| match &pack.description {
+                        Some(description) => format!("{} - {}", pack.name, description),
+                        None => pack.name.clone(),
+                    })
+                    .collect();
+                let _ = sender.send(StreamChunk::done(lines.join("\n")));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to list packs: {}", e)));
+            }
+        }
+    }
+    
+    async fn handle_team_admin(&self, request: Request, sender: ChunkSender) {
+        let team = match self.rag_manager.team_admin() {
+            Ok(team) => team,
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                return;
+            }
+        };
+        let path = resolve_path(&request);
+        
+        let result = match request.request_type {
+            RequestType::TeamIndex => {
+                let started = Instant::now();
+                let result = team.index_directory(&path).await
+                    .map(|count| format!("Indexed {} files from {} into the shared knowledge base", count, path.display()));
+                
+                let (success, summary) = match &result {
+                    Ok(summary) => (true, summary.clone()),
+                    Err(e) => (false, e.to_string()),
+                };
+                self.spawn_notification(OperationEvent::new(OperationKind::Index, success, started.elapsed(), summary));
+                result
+            }
+            RequestType::TeamRemove => team
+                .remove_from_knowledge_base(&path.to_string_lossy())
+                .await
+                .map(|count| format!("Removed {} documents from the shared knowledge base{}", count, self.trash_note())),
+            _ => team
+                .clear()
+                .await
+                .map(|_| format!("Cleared the shared knowledge base{}", self.trash_note())),
+        };
+        
+        match result {
+            Ok(message) => {
+                let _ = sender.send(StreamChunk::done(message));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Shared knowledge base operation failed: {}", e)));
+            }
+        }
+    }
+    
+    async fn handle_team_stats(&self, sender: ChunkSender) {
+        match self.rag_manager.team_namespace() {
+            Some(namespace) => {
+                let count = self.rag_manager.team_count().await;
+                let _ = sender.send(StreamChunk::done(format!(
+                    "Shared knowledge base for team '{}' contains {} documents",
+                    namespace, count
+                )));
+            }
+            None => {
+                let _ = sender.send(StreamChunk::error(rag::RagError::TeamNotConfigured.to_string()));
+            }
+        }
+    }
+    
+    async fn handle_feedback(&self, request: Request, sender: ChunkSender) {
+        let (Some(response_id), Some(rating)) = (&request.response_id, request.rating) else {
+            let _ = sender.send(StreamChunk::error("Feedback requires a response_id and a rating"));
+            return;
+        };
+        
+        let comment = Some(request.content.clone());
+        match self.feedback.submit(response_id, rating, comment).await {
+            Ok(_) => {
+                let label = match rating {
+                    Rating::Up => "👍",
+                    Rating::Down => "👎",
+                };
+                let _ = sender.send(StreamChunk::done(format!("Recorded {} for response {}", label, response_id)));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to record feedback: {}", e)));
+            }
+        }
+    }
+    
+    async fn handle_feedback_export(&self, request: Request, sender: ChunkSender) {
+        let path = resolve_path(&request);
+        
+        match self.feedback.export(&path, request.rating).await {
+            Ok(count) => {
+                let _ = sender.send(StreamChunk::done(format!(
+                    "Exported {} rated responses to {}",
+                    count, path.display()
+                )));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to export feedback: {}", e)));
+            }
+        }
+    }
+    
+    async fn handle_feedback_stats(&self, sender: ChunkSender) {
+        match self.feedback.summarize().await {
+            Ok(summaries) if summaries.is_empty() => {
+                let _ = sender.send(StreamChunk::done("No feedback recorded yet"));
+            }
+            Ok(summaries) => {
+                let lines: Vec<String> = summaries
+                    .iter()
+                    .map(|s| format!(
+                        "{}: {} up, {} down ({:.0}% approval)",
+                        s.variant, s.up, s.down, s.approval_rate() * 100.0
+                    ))
+                    .collect();
+                let _ = sender.send(StreamChunk::done(lines.join("\n")));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to summarize feedback: {}", e)));
+            }
+        }
+    }
+    
+    /// Streams workspace events to the client until it disconnects.
+    async fn handle_subscribe(&self, request: Request, sender: ChunkSender) {
+        let mut subscription = match self.events.subscribe(&request.content) {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(e.to_string()));
+                return;
+            }
+        };
+        loop {
+            let event = tokio::select! {
+                event = subscription.next() => event,
+                // Noticed once writing an event to the client failed
+                _ = sender.closed() => None,
+            };
+            let Some(event) = event else {
+                return;
+            };
+            let json = serde_json::to_string(&event).unwrap_or_default();
+            if sender.send(StreamChunk::chunk(json)).is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn handle_plugins_list(&self, sender: ChunkSender) {
+        let plugins = self.chat.plugins().list().await;
+        let _ = sender.send(match serde_json::to_string(&plugins) {
+            Ok(json) => StreamChunk::done(json),
+            Err(e) => StreamChunk::error(format!("Failed to encode plugins: {}", e)),
+        });
+    }
+
+    async fn handle_plugins_enable(&self, request: Request, enabled: bool, sender: ChunkSender) {
+        let name = request.content.trim();
+        let action = if enabled { "enable" } else { "disable" };
+        let _ = sender.send(match self.chat.plugins().set_enabled(name, enabled).await {
+            Ok(()) => StreamChunk::done(format!("Plugin '{}' is {}d", name, action)),
+            Err(e) => StreamChunk::error(format!("Failed to {} the plugin: {}", action, e)),
+        });
+    }
+
+    /// Workspace events, see [`crate::events`].
+    pub(super) fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Delivers a completion notification without delaying the client response.
+    fn spawn_notification(&self, event: OperationEvent) {
+        if !self.notifier.should_notify(&event) {
+            return;
+        }
+
+        let notifier = self.notifier.clone();
+        tokio::spawn(async move {
+            if let Err(e) = notifier.notify(&event).await {
+                warn!("Failed to send completion notification: {}", e);
+            }
+        });
+    }
+    
+    async fn handle_tree(&self, request: Request, sender: ChunkSender) {
+        let path = resolve_path(&request);
+        match render_tree(path).await {
+            Ok(tree) => {
+                let _ = sender.send(StreamChunk::done(tree));
+            }
+            Err(e) => {
+                let _ = sender.send(StreamChunk::error(format!("Failed to read directory: {}", e)));
+            }
+        }
+    }
+    
+    async fn handle_stats(&self, request: Request, sender: ChunkSender) {
+        let _ = sender.send(match self.stats(&request).await {
+            Ok(stats) => match serde_json::to_string(&stats) {
+                Ok(json) => StreamChunk::done(json),
+                Err(e) => StreamChunk::error(format!("Failed to encode stats: {}", e)),
+            },
+            Err(e) => StreamChunk::error(e.to_string()),
+        });
+    }
+
+    /// Statistics of the collection `request` uses, see [`IndexStats`].
+    pub(super) async fn stats(&self, request: &Request) -> rag::Result<IndexStats> {
+        let knowledge = self.knowledge(request).await?;
+        let collection = self.rag_manager.resolve_collection(request.collection.as_deref(), request.pwd.as_deref().map(Path::new));
+        let sources = knowledge.source_stats().await?;
+        let stale = rag::freshness::find_stale(sources.clone(), self.config.rag.freshness.url_max_age()).await;
+        let team = match self.rag_manager.team_namespace() {
+            Some(namespace) => Some(TeamIndexStats {
+                namespace: namespace.to_string(),
+                chunks: self.rag_manager.team_count().await,
+            }),
+            None => None,
+        };
+        let model = &self.config.rag.embedding_model;
+
+        Ok(IndexStats {
+            collection,
+            chunks: knowledge.count().await,
+            last_indexed: sources.iter().filter_map(|source| source.indexed_at).max(),
+            sources,
+            stale,
+            disk_bytes: knowledge.disk_usage().await,
+            embedding_model: model.name.clone(),
+            embedding_dim: model.embedding_dim,
+            indexing: self.jobs.list(),
+            team,
+            memory: crate::memory::snapshot(),
+            latest_version: self.updates.available().map(|release| release.version().to_string()),
+        })
+    }
+    
+    /// Reports the version and whether the embedding model and store work, for `nucleus doctor`.
+    ///
+    /// Subsystems that are still starting are listed instead of checked.
+    async fn handle_status(&self, sender: ChunkSender) {
+        let model = &self.config.rag.embedding_model;
+        let warming_up: Vec<Component> = [Component::Model, Component::KnowledgeBase]
+            .into_iter()
+            .filter(|&component| !self.is_started(component))
+            .collect();
+
+        let (model_dim, embedding_error, capabilities) = if warming_up.contains(&Component::Model) {
+            (None, None, None)
+        } else {
+            let capabilities = Some(self.provider.capabilities(&self.config.llm.model).await);
+            match self.provider.embed("nucleus status", model).await {
+                Ok(embedding) => (Some(embedding.len()), None, capabilities),
+                Err(e) => (None, Some(e.to_string()), capabilities),
+            }
+        };
+        let (documents, store_error) = if warming_up.contains(&Component::KnowledgeBase) {
+            (None, None)
+        } else {
+            match self.rag_manager.check_store().await {
+                Ok(count) => (Some(count), None),
+                Err(e) => (None, Some(e.to_string())),
+            }
+        };
+        
+        let status = ServerStatus {
+            version: crate::VERSION.to_string(),
+            llm_model: self.config.llm.model.clone(),
+            embedding_model: model.name.clone(),
+            store_dim: model.embedding_dim,
+            model_dim,
+            embedding_error,
+            documents,
+            store_error,
+            warming_up,
+            capabilities,
+        };
+        let _ = sender.send(match serde_json::to_string(&status) {
+            Ok(json) => StreamChunk::done(json),
+            Err(e) => StreamChunk::error(format!("Failed to encode status: {}", e)),
+        });
+    }
+    
+    /// Builds the conversation, prefixing the user message with any RAG `context`.
+    ///
+    /// The client environment and project `tree`, if present, are added to the system prompt.
+    fn build_messages(&self, request: &Request, context: &str, tree: Option<&str>) -> Vec<crate::provider::Message> {
+        use crate::provider::Message;
+        
+        let mut system_prompt = self.config.system_prompt.clone();
+        if let Some(environment) = &request.environment {
+            system_prompt.push_str(&format!("\n\n{}", environment.to_prompt()));
+        }
+        if let Some(tree) = tree {
+            system_prompt.push_str(&format!("\n\nProject layout:\n{}", tree));
+        }
+        if let Some(instructions) = request.verbosity.unwrap_or(self.config.verbosity.default).instructions() {
+            system_prompt.push_str(&format!("\n\n{}", instructions));
+        }
+        let mut messages = vec![Message::system(None, system_prompt)];
+        
+        if let Some(history) = &request.history {
+            for msg in history {
+                messages.push(Message {
+                    role: msg.role.clone(),
+                    context: None,
+                    content: msg.content.clone(),
+                    images: None,
+                    tool_calls: None,
+                });
+            }
+        }
+        
+        if context.is_empty() {
+            messages.push(Message::user(None, &request.content));
+        } else {
+            let enhanced_message = format!("{}{}", context, request.content);
+            messages.push(Message::user(Some(context.to_string()), &enhanced_message));
+        }
+        messages
+    }
+}
+
+/// Direct access used by the gRPC service.
+#[cfg(feature = "grpc")]
+impl RequestHandler {
+    /// Embeds `texts` with the configured embedding model.
+    pub(super) async fn embed(&self, texts: &[&str]) -> crate::provider::Result<Vec<Vec<f32>>> {
+        self.provider.embed_batch(texts, &self.config.rag.embedding_model).await
+    }
+    
+    pub(super) fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+/// Runs `future` until `deadline`, returning `None` if the deadline passes first.
+///
+/// Without a deadline the future always runs to completion.
+async fn within_deadline<F: std::future::Future>(
+    deadline: Option<tokio::time::Instant>,
+    future: F,
+) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Streams the answer to `request` to `sender`, running the tools the model
+/// calls, and stopping at `deadline`. The first `checked` messages already
+/// passed the egress check.
+///
+/// Returns the result, the text generated (also what came before the
+/// deadline), and whether the answer was cut short: by the deadline, or by
+/// the provider stopping at the request's `max_tokens`.
+async fn stream_answer(
+    chat: &ChatManager,
+    request: crate::provider::ChatRequest,
+    checked: usize,
+    deadline: Option<tokio::time::Instant>,
+    sender: &ChunkSender,
+) -> (Result<(), String>, String, bool) {
+    let mut text = String::new();
+    let mut on_chunk = |chunk: &str| {
+        text.push_str(chunk);
+        let _ = sender.send(StreamChunk::chunk(chunk));
+    };
+    let (result, timed_out, stopped_at_limit) = match within_deadline(deadline, chat.complete(request, checked, &mut on_chunk)).await {
+        Some(Ok(response)) => (Ok(()), false, response.truncated),
+        Some(Err(e)) => (Err(format!("{:#}", e)), false, false),
+        None => (Ok(()), true, false),
+    };
+    if timed_out || stopped_at_limit {
+        debug!(timed_out, stopped_at_limit, "Generation stopped at request budget");
+    }
+    (result, text, timed_out || stopped_at_limit)
+}
+
+/// Grant of plugins not in `plugins.grants`: the configured permissions,
+/// without network access.
+fn default_grant(config: &Config) -> Permission {
+    Permission {
+        read: config.permission.read,
+        write: config.permission.write,
+        network: false,
+        execute: config.permission.command,
+    }
+}
+
+/// Builds and renders the project tree for `root` off the async runtime.
+async fn render_tree(root: PathBuf) -> std::io::Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let options = TreeOptions::default();
+        project_tree::build(&root, &options).map(|tree| project_tree::render(&tree, &options))
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+/// Resolves the request's path content against its working directory.
+fn resolve_path(request: &Request) -> PathBuf {
+    let path = PathBuf::from(&request.content);
+    match &request.pwd {
+        Some(pwd) if path.is_relative() => Path::new(pwd).join(path),
+        _ => path,
+    }
+}
+
+/// Derives a pack name from a file name like `onboarding.pack`.
+fn pack_name_from_path(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "pack".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EmbeddingModel;
+    use crate::provider::{ChatRequest, ChatResponse, Message, ToolCall, ToolCallFunction};
+    use async_trait::async_trait;
+
+    /// Streams one chunk per word of `answer`, pausing before each, and
+    /// ends like a provider stopping at `max_tokens` if `truncated`.
+    struct Words {
+        answer: &'static str,
+        pause: Duration,
+        truncated: bool,
+    }
+
+    #[async_trait]
+    impl Provider for Words {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> crate::provider::Result<()> {
+            let chunk = |content: &str, done, truncated| ChatResponse {
+                model: request.model.clone(),
+                content: content.to_string(),
+                done,
+                truncated,
+                message: Message::assistant(None, content),
+            };
+            for word in self.answer.split_inclusive(' ') {
+                tokio::time::sleep(self.pause).await;
+                callback(chunk(word, false, false));
+            }
+            callback(chunk("", true, self.truncated));
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    /// Asks for the `clock` tool, then answers with what it returned.
+    struct AsksTime;
+
+    #[async_trait]
+    impl Provider for AsksTime {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> crate::provider::Result<()> {
+            let last = request.messages.last().unwrap();
+            let mut message = Message::assistant(None, "");
+            if last.role == "tool" {
+                message.content = last.content.clone();
+            } else {
+                message.tool_calls = Some(vec![ToolCall {
+                    function: ToolCallFunction { name: "clock".to_string(), arguments: serde_json::json!({}) },
+                }]);
+            }
+            callback(ChatResponse {
+                model: request.model,
+                content: message.content.clone(),
+                done: true,
+                truncated: false,
+                message,
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+            Ok(vec![])
+        }
+    }
+
+    struct Clock;
+
+    #[async_trait]
+    impl nucleus_plugin::Plugin for Clock {
+        fn name(&self) -> &str {
+            "clock"
+        }
+
+        fn description(&self) -> &str {
+            "Tells the time"
+        }
+
+        fn parameter_schema(&self) -> serde_json::Value {
+            serde_json::json!({})
+        }
+
+        fn required_permission(&self) -> Permission {
+            Permission::NONE
+        }
+
+        async fn execute(&self, _input: serde_json::Value) -> nucleus_plugin::Result<nucleus_plugin::PluginOutput> {
+            Ok(nucleus_plugin::PluginOutput::new("noon"))
+        }
+    }
+
+    fn chat(provider: Arc<dyn Provider>, registry: PluginRegistry) -> ChatManager {
+        let config = Config::default();
+        let rag = Arc::new(rag::RagEngine::deferred(&config, provider.clone()));
+        let plugins_config = crate::config::PluginsConfig { enabled: false, ..Default::default() };
+        ChatManager::from_parts(config, provider, rag, Plugins::new(&plugins_config, registry))
+    }
+
+    async fn answer(provider: Words, max_tokens: u32, deadline: Option<Duration>) -> (String, bool, usize) {
+        let (sender, mut chunks) = mpsc::unbounded_channel();
+        let deadline = deadline.map(|limit| tokio::time::Instant::now() + limit);
+        let request = ChatRequest::new("m", vec![Message::user(None, "hi")]).with_max_tokens(max_tokens);
+        let chat = chat(Arc::new(provider), PluginRegistry::new(Permission::NONE));
+        let (result, text, truncated) = stream_answer(&chat, request, 1, deadline, &sender).await;
+        result.unwrap();
+        drop(sender);
+        let mut streamed = 0;
+        while chunks.recv().await.is_some() {
+            streamed += 1;
+        }
+        (text, truncated, streamed)
+    }
+
+    #[tokio::test]
+    async fn test_within_deadline() {
+        assert_eq!(within_deadline(None, async { 1 }).await, Some(1));
+        let later = tokio::time::Instant::now() + Duration::from_secs(60);
+        assert_eq!(within_deadline(Some(later), async { 1 }).await, Some(1));
+        let passed = tokio::time::Instant::now();
+        assert_eq!(within_deadline(Some(passed), std::future::pending::<()>()).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_truncation() {
+        // Deadline reached: what came before it is kept
+        let slow = Words { answer: "one two three", pause: Duration::from_millis(100), truncated: false };
+        let (text, truncated, streamed) = answer(slow, 100, Some(Duration::from_millis(250))).await;
+        assert_eq!((text.as_str(), truncated, streamed), ("one two ", true, 2));
+
+        // Token limit reached, as the provider reports it
+        let cut = Words { answer: "one two", pause: Duration::ZERO, truncated: true };
+        assert_eq!(answer(cut, 2, None).await, ("one two".to_string(), true, 2));
+
+        // Natural stop exactly at the limit
+        let exact = Words { answer: "one two", pause: Duration::ZERO, truncated: false };
+        assert_eq!(answer(exact, 2, None).await, ("one two".to_string(), false, 2));
+    }
+
+    #[tokio::test]
+    async fn test_answer_runs_plugins() {
+        let mut registry = PluginRegistry::new(Permission::NONE);
+        assert!(registry.register(Arc::new(Clock)));
+        let chat = chat(Arc::new(AsksTime), registry);
+        let (sender, _chunks) = mpsc::unbounded_channel();
+        let request = ChatRequest::new("m", vec![Message::user(None, "What time is it?")]);
+        let (result, text, _) = stream_answer(&chat, request, 1, None, &sender).await;
+        assert_eq!((result, text.as_str()), (Ok(()), "noon"));
+
+        // Once disabled, e.g. over the socket, the model's call is refused
+        chat.plugins().registry().write().await.unregister("clock");
+        let request = ChatRequest::new("m", vec![Message::user(None, "What time is it?")]);
+        let (result, _, _) = stream_answer(&chat, request, 1, None, &sender).await;
+        assert!(result.unwrap_err().contains("Unknown plugin: clock"));
     }
 }
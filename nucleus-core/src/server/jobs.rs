@@ -0,0 +1,207 @@
+//! Long-running requests that can be listed, queried, and cancelled by ID.
+//!
+//! Indexing a directory registers a job for as long as it runs; a
+//! `jobs-cancel` request cancels its token, and the job stops at the next
+//! point where it can leave the knowledge base consistent. The outcome of
+//! the last [`FINISHED_KEPT`] jobs stays available to `jobs-status`
+//! requests, so clients that detached from a job can learn how it ended.
+
+use super::types::{JobInfo, JobOutcome};
+use crate::rag::IndexProgress;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Finished jobs whose outcome is kept.
+const FINISHED_KEPT: usize = 16;
+
+/// The jobs running on the server.
+#[derive(Debug, Default)]
+pub(super) struct Jobs {
+    last_id: AtomicU64,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    running: BTreeMap<u64, Running>,
+    /// Most recently finished last
+    finished: VecDeque<JobInfo>,
+}
+
+#[derive(Debug)]
+struct Running {
+    description: String,
+    started: Instant,
+    cancel: CancellationToken,
+    progress: Option<IndexProgress>,
+}
+
+impl Running {
+    fn info(&self, id: u64) -> JobInfo {
+        JobInfo {
+            id,
+            description: self.description.clone(),
+            elapsed_secs: self.started.elapsed().as_secs(),
+            cancelling: self.cancel.is_cancelled(),
+            progress: self.progress.clone(),
+            outcome: None,
+        }
+    }
+}
+
+/// A registered job; dropping it removes the job from the list.
+///
+/// The job owns its registration, so it can move to a task that outlives
+/// the request which started it.
+#[derive(Debug)]
+pub(super) struct Job {
+    id: u64,
+    cancel: CancellationToken,
+    state: Arc<Mutex<State>>,
+}
+
+impl Jobs {
+    /// Registers a job described as `description`, e.g. `index /src/app`.
+    pub(super) fn start(&self, description: impl Into<String>) -> Job {
+        let id = self.last_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let cancel = CancellationToken::new();
+        self.state.lock().unwrap().running.insert(id, Running {
+            description: description.into(),
+            started: Instant::now(),
+            cancel: cancel.clone(),
+            progress: None,
+        });
+        Job {
+            id,
+            cancel,
+            state: Arc::clone(&self.state),
+        }
+    }
+
+    /// Running jobs, oldest first.
+    pub(super) fn list(&self) -> Vec<JobInfo> {
+        self.state
+            .lock()
+            .unwrap()
+            .running
+            .iter()
+            .map(|(&id, job)| job.info(id))
+            .collect()
+    }
+
+    /// Job `id`, running or recently finished.
+    pub(super) fn status(&self, id: u64) -> Option<JobInfo> {
+        let state = self.state.lock().unwrap();
+        match state.running.get(&id) {
+            Some(job) => Some(job.info(id)),
+            None => state.finished.iter().find(|job| job.id == id).cloned(),
+        }
+    }
+
+    /// Asks job `id` to stop, returning its description; `None` if no such
+    /// job is running.
+    pub(super) fn cancel(&self, id: u64) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        let job = state.running.get(&id)?;
+        job.cancel.cancel();
+        Some(job.description.clone())
+    }
+}
+
+impl Job {
+    pub(super) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(super) fn token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Records how far the job has got, for `jobs-status` requests.
+    pub(super) fn report(&self, progress: &IndexProgress) {
+        if let Some(job) = self.state.lock().unwrap().running.get_mut(&self.id) {
+            job.progress = Some(progress.clone());
+        }
+    }
+
+    /// Removes the job from the running ones, keeping `outcome` for
+    /// `jobs-status` requests.
+    pub(super) fn finish(self, outcome: JobOutcome) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(job) = state.running.remove(&self.id) {
+            let info = JobInfo {
+                outcome: Some(outcome),
+                ..job.info(self.id)
+            };
+            if state.finished.len() == FINISHED_KEPT {
+                state.finished.pop_front();
+            }
+            state.finished.push_back(info);
+        }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().running.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_and_finish() {
+        let jobs = Jobs::default();
+        let first = jobs.start("index /a");
+        let second = jobs.start("index /b");
+        assert_eq!((first.id(), second.id()), (1, 2));
+
+        assert_eq!(jobs.cancel(2).as_deref(), Some("index /b"));
+        assert!(second.token().is_cancelled());
+        assert!(!first.token().is_cancelled());
+        let listed = jobs.list();
+        assert_eq!(listed.iter().map(|job| (job.id, job.cancelling)).collect::<Vec<_>>(), [(1, false), (2, true)]);
+
+        drop(second);
+        assert_eq!(jobs.list().len(), 1);
+        assert_eq!(jobs.cancel(2), None);
+    }
+
+    #[test]
+    fn test_status_of_running_and_finished_jobs() {
+        let jobs = Jobs::default();
+        let job = jobs.start("index /a");
+        let progress = IndexProgress {
+            files_done: 3,
+            files_total: 10,
+            current: Some("/a/main.rs".to_string()),
+            ..IndexProgress::default()
+        };
+        job.report(&progress);
+        assert_eq!(jobs.status(1).unwrap().progress, Some(progress.clone()));
+
+        job.finish(JobOutcome {
+            success: true,
+            summary: "Indexed 10 files".to_string(),
+        });
+        assert!(jobs.list().is_empty());
+        let status = jobs.status(1).unwrap();
+        assert_eq!(status.outcome.map(|outcome| outcome.summary).as_deref(), Some("Indexed 10 files"));
+        assert_eq!(status.progress, Some(progress));
+        assert_eq!(jobs.cancel(1), None);
+
+        for _ in 0..FINISHED_KEPT {
+            jobs.start("index /b").finish(JobOutcome {
+                success: false,
+                summary: "Cancelled".to_string(),
+            });
+        }
+        assert!(jobs.status(1).is_none());
+        assert!(jobs.status(2).is_some());
+    }
+}
@@ -2,56 +2,170 @@
 //!
 //! The server is organized into separate concerns:
 //! - `types`: Protocol types for requests and responses
+//! - `crash`: Panic isolation per request and crash reports
+//! - `freshness`: Periodic checks for sources that changed since they were indexed
+//! - `grpc`: gRPC API over the same handler (`grpc` feature)
 //! - `handler`: Business logic for processing requests
+//! - `jobs`: Long-running requests that can be listed and cancelled
+//! - `scheduler`: Fair scheduling of model requests across sessions and transports
+//! - `session`: Per-session state such as private mode
+//! - `suggest`: Low-latency path for inline command suggestions
 //! - `transport`: IPC communication layer (Unix sockets on Unix, Named Pipes on Windows)
+//! - `watch`: Live index updates for indexed directories
 
+mod crash;
+mod freshness;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 mod handler;
+mod jobs;
+mod session;
+mod suggest;
 mod transport;
 mod types;
+mod watch;
 
 // Re-export types for external use
 #[allow(unused)]
-pub use types::{ChunkType, Message, Request, RequestType, StreamChunk};
+pub use types::{
+    ChunkType, ClientVersion, Component, IndexStats, JobInfo, JobOutcome, Message, Request, RequestType, SearchHit, ServerStatus,
+    SessionInfo, StreamChunk, TeamIndexStats, Timeout, VersionMismatch, WarmingUp, PROTOCOL_VERSION,
+};
 
-use crate::{config::Config, detection, provider::{OllamaProvider, Provider}};
+use crate::{
+    config::{Config, EventsConfig, GrpcConfig, ProviderKind},
+    detection,
+    events,
+    memory::{self, Subsystem},
+    models::EmbeddingModel,
+    provider::{Capabilities, ChatRequest, ChatResponse, OllamaProvider, OpenAiProvider, Provider, ProviderError, ResilientProvider},
+    warmup::Warmup,
+};
+use async_trait::async_trait;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
 use tokio::sync::mpsc;
 
+/// IPC endpoint the server listens on.
 #[cfg(unix)]
-const SOCKET_PATH: &str = "/tmp/llm-workspace.sock";
+pub const SOCKET_PATH: &str = "/tmp/llm-workspace.sock";
 
 #[cfg(windows)]
-const SOCKET_PATH: &str = r"\\.\pipe\llm-workspace";
+pub const SOCKET_PATH: &str = r"\\.\pipe\llm-workspace";
 
 /// Main server coordinating transport and request handling.
 pub struct Server {
     handler: Arc<handler::RequestHandler>,
     transport: transport::IpcTransport,
+    grpc: GrpcConfig,
+    events: EventsConfig,
+    crash_reports_path: String,
 }
 
 impl Server {
     /// Creates a new server instance.
-    /// 
-    /// This will check if Ollama is installed and running.
-    /// If not, helpful installation/startup instructions will be printed.
+    ///
+    /// Uses the provider selected by `llm.provider` (Ollama by default). For
+    /// Ollama, this will check it is installed and running; if not, helpful
+    /// installation/startup instructions will be printed.
     /// Connects to Qdrant for persistent vector storage.
+    /// Calls to Ollama, OpenAI-compatible APIs, and Qdrant are retried and
+    /// circuit-broken as configured in `resilience`.
+    ///
+    /// Vector stores connect, and a llama.cpp model loads, in the background:
+    /// the server listens at once, and requests that need them wait with
+    /// progress chunks (see [`WarmingUp`]).
+    ///
+    /// With `watch.enabled`, indexes `watch.paths` in the background and
+    /// keeps indexed directories up to date as files change.
     pub async fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
-        detection::detect_ollama()?;
+        let provider = match config.llm.provider {
+            Some(ProviderKind::OpenAi) => StartingProvider::ready(Arc::new(ResilientProvider::new(
+                Arc::new(OpenAiProvider::new(&config)),
+                "OpenAI-compatible API",
+                &config.resilience,
+            ))),
+            Some(ProviderKind::MistralRs) => {
+                return Err("llm.provider 'mistralrs' is not supported by the server; use ChatManager instead".into());
+            }
+            #[cfg(feature = "llama-cpp")]
+            Some(ProviderKind::LlamaCpp) => {
+                let config = config.clone();
+                // Loading (and first downloading) the model can take minutes
+                let provider = Warmup::spawn(async move {
+                    match crate::provider::LlamaCppProvider::new(&config).await {
+                        Ok(provider) => Ok(Arc::new(provider) as Arc<dyn Provider>),
+                        Err(e) => {
+                            eprintln!("Failed to load {}: {}", config.llm.model, e);
+                            Err(e.to_string())
+                        }
+                    }
+                });
+                StartingProvider { provider, remote: false }
+            }
+            #[cfg(not(feature = "llama-cpp"))]
+            Some(ProviderKind::LlamaCpp) => {
+                return Err("llm.provider 'llamacpp' requires building with the `llama-cpp` feature".into());
+            }
+            None | Some(ProviderKind::Ollama) => {
+                detection::detect_ollama()?;
+                StartingProvider::ready(Arc::new(
+                    ResilientProvider::new(Arc::new(OllamaProvider::new(&config)), "Ollama", &config.resilience)
+                ))
+            }
+        };
+        let grpc = config.grpc.clone();
+        let events = config.events.clone();
+        let crash_reports_path = config.storage.crash_reports_path.clone();
+        let model = provider.provider.clone();
+        let provider = Arc::new(TrackedProvider(Arc::new(provider)));
+        
+        let watch_config = config.watch.clone();
+        let freshness_config = config.rag.freshness.clone();
+        let (watcher, changes) = match watch_config.enabled.then(watch::DirWatcher::new).transpose() {
+            Ok(Some((watcher, changes))) => (Some(watcher), Some(changes)),
+            Ok(None) => (None, None),
+            Err(e) => {
+                eprintln!("Failed to start the file watcher, indexed directories will not update live: {}", e);
+                (None, None)
+            }
+        };
+        let handler = Arc::new(handler::RequestHandler::new(config, provider, model, watcher));
+        if let Some(changes) = changes {
+            let debounce = Duration::from_millis(watch_config.debounce_ms);
+            tokio::spawn(watch::run(Arc::clone(&handler), watch_config.paths, changes, debounce));
+        }
+        if freshness_config.interval_hours > 0 {
+            tokio::spawn(freshness::run(Arc::clone(&handler), freshness_config));
+        }
         
-        let provider: Arc<dyn Provider> = Arc::new(OllamaProvider::new(&config));
-        let handler = Arc::new(handler::RequestHandler::new(config, provider).await?);
         let transport = transport::IpcTransport::new(SOCKET_PATH);
         
-        Ok(Self { handler, transport })
+        Ok(Self { handler, transport, grpc, events, crash_reports_path })
     }
     
     /// Starts the server and listens for connections.
+    ///
+    /// A request that panics ends with an error for its client while the
+    /// server keeps running; a crash report is written to
+    /// `storage.crash_reports_path`.
+    ///
+    /// With `events.sse_enabled`, workspace events are also served as
+    /// Server-Sent Events (see [`crate::events`]).
     pub async fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
+        crash::install_panic_hook(&self.crash_reports_path);
         let listener = self.transport.bind().await?;
         
         println!("AI Server listening on {}", SOCKET_PATH);
         
+        if self.grpc.enabled {
+            self.start_grpc();
+        }
+        if self.events.sse_enabled {
+            self.start_events();
+        }
+        
         let shutdown = signal::ctrl_c();
         tokio::pin!(shutdown);
         
@@ -75,6 +189,36 @@ impl Server {
         
         Ok(())
     }
+    
+    #[cfg(feature = "grpc")]
+    fn start_grpc(&self) {
+        let handler = Arc::clone(&self.handler);
+        let address = self.grpc.address.clone();
+        println!("gRPC API listening on {}", address);
+        
+        tokio::spawn(async move {
+            if let Err(e) = grpc::serve(handler, &address).await {
+                eprintln!("gRPC server error: {}", e);
+            }
+        });
+    }
+    
+    #[cfg(not(feature = "grpc"))]
+    fn start_grpc(&self) {
+        eprintln!("grpc.enabled is set, but nucleus was built without the `grpc` feature");
+    }
+    
+    fn start_events(&self) {
+        let bus = self.handler.events().clone();
+        let address = self.events.sse_address.clone();
+        println!("Workspace events at http://{}/events", address);
+        
+        tokio::spawn(async move {
+            if let Err(e) = events::serve_sse(bus, &address).await {
+                eprintln!("Event stream server error: {}", e);
+            }
+        });
+    }
 }
 
 /// Handles a single client connection.
@@ -86,9 +230,10 @@ async fn handle_connection(
     
     let (sender, receiver) = mpsc::unbounded_channel();
     
-    let handle_task = tokio::spawn(async move {
+    let request_type = request.request_type;
+    let handle_task = tokio::spawn(crash::isolate(request_type, sender.clone(), async move {
         handler.handle(request, sender).await;
-    });
+    }));
     
     let write_task = tokio::spawn(async move {
         transport::write_chunks(&mut stream, receiver).await
@@ -98,3 +243,100 @@ async fn handle_connection(
     
     Ok(())
 }
+
+/// A provider that may still be starting; calls wait until it has.
+struct StartingProvider {
+    provider: Warmup<Arc<dyn Provider>>,
+    /// Whether the provider talks to another machine, known before it starts
+    remote: bool,
+}
+
+impl StartingProvider {
+    fn ready(provider: Arc<dyn Provider>) -> Self {
+        Self {
+            remote: provider.is_remote(),
+            provider: Warmup::ready(provider),
+        }
+    }
+
+    async fn get(&self) -> crate::provider::Result<Arc<dyn Provider>> {
+        self.provider.get().await.map_err(ProviderError::Other)
+    }
+}
+
+#[async_trait]
+impl Provider for StartingProvider {
+    async fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+        callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+    ) -> crate::provider::Result<()> {
+        self.get().await?.chat(request, callback).await
+    }
+
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+        self.get().await?.embed(text, model).await
+    }
+
+    async fn embed_batch(&self, texts: &[&str], model: &EmbeddingModel) -> crate::provider::Result<Vec<Vec<f32>>> {
+        self.get().await?.embed_batch(texts, model).await
+    }
+
+    async fn rerank(&self, query: &str, documents: &[&str], model: &str) -> crate::provider::Result<Vec<f32>> {
+        self.get().await?.rerank(query, documents, model).await
+    }
+
+    async fn health_check(&self) -> crate::provider::Result<()> {
+        self.get().await?.health_check().await
+    }
+
+    fn is_remote(&self) -> bool {
+        self.remote
+    }
+
+    /// The defaults if the model failed to start.
+    async fn capabilities(&self, model: &str) -> Capabilities {
+        match self.get().await {
+            Ok(provider) => provider.capabilities(model).await,
+            Err(_) => Capabilities::default(),
+        }
+    }
+}
+
+/// Attributes a provider's allocations to [`Subsystem::Model`] for memory stats.
+struct TrackedProvider(Arc<dyn Provider>);
+
+#[async_trait]
+impl Provider for TrackedProvider {
+    async fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+        callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+    ) -> crate::provider::Result<()> {
+        memory::track(Subsystem::Model, self.0.chat(request, callback)).await
+    }
+
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> crate::provider::Result<Vec<f32>> {
+        memory::track(Subsystem::Model, self.0.embed(text, model)).await
+    }
+
+    async fn embed_batch(&self, texts: &[&str], model: &EmbeddingModel) -> crate::provider::Result<Vec<Vec<f32>>> {
+        memory::track(Subsystem::Model, self.0.embed_batch(texts, model)).await
+    }
+
+    async fn rerank(&self, query: &str, documents: &[&str], model: &str) -> crate::provider::Result<Vec<f32>> {
+        memory::track(Subsystem::Model, self.0.rerank(query, documents, model)).await
+    }
+
+    async fn health_check(&self) -> crate::provider::Result<()> {
+        self.0.health_check().await
+    }
+
+    fn is_remote(&self) -> bool {
+        self.0.is_remote()
+    }
+
+    async fn capabilities(&self, model: &str) -> Capabilities {
+        self.0.capabilities(model).await
+    }
+}
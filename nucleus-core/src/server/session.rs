@@ -0,0 +1,636 @@
+//! Per-session state kept by the server.
+//!
+//! Sessions are identified by the request's `session_id`; requests without
+//! one share a default session.
+//!
+//! Each session with an ID keeps its recent exchanges (`sessions`), which
+//! chat requests that bring no `history` of their own get as theirs. `/new`
+//! in chat (a `session-reset` request) forgets them. `/shorter` asks the
+//! last question again for a terse answer, which replaces the earlier one.
+//!
+//! With `sessions.persist`, every exchange is also appended to a JSONL file
+//! per session under `sessions.path`, so a session picks up where it left
+//! off after a server restart (unless it has been idle too long). `/new`
+//! keeps the earlier conversation stored under a new ID; stored
+//! conversations can be listed, resumed into the requesting session, and
+//! deleted.
+//!
+//! A session in private mode (`/private on`) is restricted so nothing it
+//! discusses leaves the machine or outlives the session: requests that need a
+//! remote LLM are refused, the shared team knowledge base is not queried,
+//! responses are not remembered for feedback, requests and responses are not
+//! written to the request and response logs, completion notifications are
+//! not sent, and knowledge base writes are rejected.
+
+use super::types::{Message, Request, RequestType, SessionInfo};
+use crate::config::{Config, SessionsConfig};
+use crate::feedback::unix_timestamp;
+use crate::memory::{self, Subsystem};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::warn;
+
+#[derive(Debug, Error)]
+pub(super) enum SessionError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("No stored conversation '{0}'; `nucleus sessions list` shows them")]
+    NotFound(String),
+
+    #[error("Conversations are not stored (sessions.persist)")]
+    Disabled,
+
+    #[error("Requests without a session ID have no conversation to resume into")]
+    NoSession,
+}
+
+pub(super) type Result<T> = std::result::Result<T, SessionError>;
+
+/// Recent messages of a session, oldest first.
+#[derive(Debug)]
+struct History {
+    messages: VecDeque<Message>,
+    used: Instant,
+}
+
+/// Tracks which sessions are in private mode, and their conversation history.
+#[derive(Debug, Default)]
+pub(super) struct Sessions {
+    config: SessionsConfig,
+    private: Mutex<HashSet<String>>,
+    histories: Mutex<HashMap<String, History>>,
+    /// Stored conversations, unless `sessions.persist` is off
+    store: Option<SessionStore>,
+}
+
+impl Sessions {
+    pub(super) fn new(config: SessionsConfig) -> Self {
+        Self {
+            store: config.persist.then(|| SessionStore { path: PathBuf::from(&config.path) }),
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub(super) fn is_private(&self, request: &Request) -> bool {
+        self.private.lock().unwrap().contains(session_key(request))
+    }
+
+    pub(super) fn set_private(&self, request: &Request, private: bool) {
+        let _scope = memory::enter(Subsystem::Sessions);
+        let mut sessions = self.private.lock().unwrap();
+        let changed = if private {
+            sessions.insert(session_key(request).to_string())
+        } else {
+            sessions.remove(session_key(request))
+        };
+        // Nothing said in private mode is sent anywhere once it is off
+        if changed {
+            self.reset(request);
+        }
+    }
+
+    /// The recent exchanges of the request's session, oldest first; none
+    /// for requests without a `session_id`.
+    ///
+    /// A session not in memory, e.g. after a restart, continues its stored
+    /// conversation if that was used within `idle_minutes`.
+    pub(super) fn history(&self, request: &Request) -> Vec<Message> {
+        let Some(session) = request.session_id.as_deref() else {
+            return Vec::new();
+        };
+        let mut histories = self.histories.lock().unwrap();
+        if let Some(history) = histories.get_mut(session) {
+            history.used = Instant::now();
+            return history.messages.iter().cloned().collect();
+        }
+
+        let idle = self.config.idle_minutes.saturating_mul(60);
+        let stored = match self.store.as_ref().map(|store| store.read(session)) {
+            Some(Ok(Some(stored))) if stored.last().is_some_and(|m| m.timestamp + idle >= unix_timestamp()) => stored,
+            Some(Err(e)) => {
+                warn!("Failed to read stored conversation '{}': {}", session, e);
+                return Vec::new();
+            }
+            _ => return Vec::new(),
+        };
+        let _scope = memory::enter(Subsystem::Sessions);
+        let history = self.loaded(stored);
+        let messages = history.messages.iter().cloned().collect();
+        histories.insert(session.to_string(), history);
+        messages
+    }
+
+    /// Adds an exchange to the request's session, dropping the oldest
+    /// beyond `max_turns`, and forgets sessions that have been idle too long.
+    pub(super) fn record(&self, request: &Request, prompt: &str, response: &str) {
+        let Some(session) = request.session_id.as_deref().filter(|_| self.config.max_turns > 0) else {
+            return;
+        };
+        let _scope = memory::enter(Subsystem::Sessions);
+        let idle = Duration::from_secs(self.config.idle_minutes.saturating_mul(60));
+        let mut histories = self.histories.lock().unwrap();
+        histories.retain(|_, history| history.used.elapsed() < idle);
+
+        let history = histories.entry(session.to_string()).or_insert_with(|| History {
+            messages: VecDeque::new(),
+            used: Instant::now(),
+        });
+        let exchange = [
+            Message {
+                role: "user".to_string(),
+                content: prompt.to_string(),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: response.to_string(),
+            },
+        ];
+        history.used = Instant::now();
+        history.messages.extend(exchange.iter().cloned());
+        while history.messages.len() > self.config.max_turns * 2 {
+            history.messages.pop_front();
+        }
+        drop(histories);
+
+        // Nothing said in private mode outlives the session
+        if let Some(store) = self.store.as_ref().filter(|_| !self.is_private(request)) {
+            if let Err(e) = store.append(session, &exchange) {
+                warn!("Failed to store conversation '{}': {}", session, e);
+            }
+        }
+    }
+
+    /// Replaces the last exchange of the request's session, e.g. with a
+    /// shorter answer to the same question.
+    pub(super) fn replace_last(&self, request: &Request, prompt: &str, response: &str) {
+        let Some(session) = request.session_id.as_deref() else {
+            return;
+        };
+        if let Some(history) = self.histories.lock().unwrap().get_mut(session) {
+            let kept = history.messages.len().saturating_sub(2);
+            history.messages.truncate(kept);
+        }
+        if let Some(store) = self.store.as_ref().filter(|_| !self.is_private(request)) {
+            if let Err(e) = store.drop_last(session, 2) {
+                warn!("Failed to store conversation '{}': {}", session, e);
+            }
+        }
+        self.record(request, prompt, response);
+    }
+
+    /// Forgets the history of the request's session, returning the number
+    /// of exchanges it had. Its stored conversation is kept under a new ID.
+    pub(super) fn reset(&self, request: &Request) -> usize {
+        let Some(session) = request.session_id.as_deref() else {
+            return 0;
+        };
+        if let Some(store) = &self.store {
+            if let Err(e) = store.archive(session) {
+                warn!("Failed to keep conversation '{}': {}", session, e);
+            }
+        }
+        let removed = self.histories.lock().unwrap().remove(session);
+        removed.map_or(0, |history| history.messages.len() / 2)
+    }
+
+    /// The stored conversations, most recently used first.
+    pub(super) fn list(&self, request: &Request) -> Result<Vec<SessionInfo>> {
+        let store = self.store.as_ref().ok_or(SessionError::Disabled)?;
+        let mut sessions = store.list()?;
+        for session in &mut sessions {
+            session.current = request.session_id.as_deref() == Some(session.id.as_str());
+        }
+        Ok(sessions)
+    }
+
+    /// Continues stored conversation `id` in the request's session, which
+    /// takes it over; the session's own conversation is kept like on
+    /// [`reset`](Self::reset). Returns the number of exchanges resumed.
+    pub(super) fn resume(&self, request: &Request, id: &str) -> Result<usize> {
+        let store = self.store.as_ref().ok_or(SessionError::Disabled)?;
+        let session = request.session_id.as_deref().ok_or(SessionError::NoSession)?;
+        if store.read(id)?.is_none() {
+            return Err(SessionError::NotFound(id.to_string()));
+        }
+        if id != session {
+            self.reset(request);
+            store.rename(id, session)?;
+        }
+
+        let _scope = memory::enter(Subsystem::Sessions);
+        let history = self.loaded(store.read(session)?.unwrap_or_default());
+        let turns = history.messages.len() / 2;
+        self.histories.lock().unwrap().insert(session.to_string(), history);
+        Ok(turns)
+    }
+
+    /// Deletes stored conversation `id`, and forgets its history if it is a
+    /// session's current one.
+    pub(super) fn delete(&self, id: &str) -> Result<()> {
+        let store = self.store.as_ref().ok_or(SessionError::Disabled)?;
+        if !store.delete(id)? {
+            return Err(SessionError::NotFound(id.to_string()));
+        }
+        self.histories.lock().unwrap().remove(id);
+        Ok(())
+    }
+
+    /// The in-memory history of a stored conversation: its last `max_turns`.
+    fn loaded(&self, stored: Vec<StoredMessage>) -> History {
+        let skip = stored.len().saturating_sub(self.config.max_turns * 2);
+        History {
+            messages: stored.into_iter().skip(skip).map(Message::from).collect(),
+            used: Instant::now(),
+        }
+    }
+}
+
+/// A message in a stored conversation.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredMessage {
+    role: String,
+    content: String,
+    /// Unix timestamp (seconds) the message was stored
+    timestamp: u64,
+}
+
+impl From<StoredMessage> for Message {
+    fn from(message: StoredMessage) -> Self {
+        Self {
+            role: message.role,
+            content: message.content,
+        }
+    }
+}
+
+/// Conversations on disk, one JSONL file of [`StoredMessage`]s per session.
+#[derive(Debug)]
+struct SessionStore {
+    path: PathBuf,
+}
+
+impl SessionStore {
+    fn append(&self, id: &str, messages: &[Message]) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.path)?;
+        let timestamp = unix_timestamp();
+        let mut lines = String::new();
+        for message in messages {
+            let stored = StoredMessage {
+                role: message.role.clone(),
+                content: message.content.clone(),
+                timestamp,
+            };
+            lines.push_str(&serde_json::to_string(&stored)?);
+            lines.push('\n');
+        }
+        let mut file = File::options().create(true).append(true).open(self.file(id))?;
+        file.write_all(lines.as_bytes())
+    }
+
+    /// The messages of conversation `id`, `None` if there is none.
+    ///
+    /// Lines that cannot be parsed (e.g. one cut off by a crash) are skipped.
+    fn read(&self, id: &str) -> std::io::Result<Option<Vec<StoredMessage>>> {
+        let file = match File::open(self.file(id)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut messages = Vec::new();
+        for line in BufReader::new(file).lines() {
+            if let Ok(message) = serde_json::from_str(&line?) {
+                messages.push(message);
+            }
+        }
+        Ok(Some(messages))
+    }
+
+    /// Removes the last `count` messages of conversation `id`, if there is one.
+    fn drop_last(&self, id: &str, count: usize) -> std::io::Result<()> {
+        let Some(mut messages) = self.read(id)? else {
+            return Ok(());
+        };
+        messages.truncate(messages.len().saturating_sub(count));
+        let mut lines = String::new();
+        for message in &messages {
+            lines.push_str(&serde_json::to_string(message)?);
+            lines.push('\n');
+        }
+        std::fs::write(self.file(id), lines)
+    }
+
+    fn list(&self) -> std::io::Result<Vec<SessionInfo>> {
+        let entries = match std::fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut sessions = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            let Some(id) = name.strip_suffix(".jsonl").and_then(decode) else {
+                continue;
+            };
+            let Some(messages) = self.read(&id)?.filter(|messages| !messages.is_empty()) else {
+                continue;
+            };
+            let title = messages.iter().find(|m| m.role == "user").map_or("", |m| m.content.as_str());
+            sessions.push(SessionInfo {
+                turns: messages.iter().filter(|m| m.role == "user").count(),
+                created: messages[0].timestamp,
+                updated: messages[messages.len() - 1].timestamp,
+                title: shorten(title),
+                current: false,
+                id,
+            });
+        }
+        sessions.sort_by(|a, b| b.updated.cmp(&a.updated).then_with(|| a.id.cmp(&b.id)));
+        Ok(sessions)
+    }
+
+    /// Moves conversation `id` aside to `<id>~<timestamp>`, if there is one.
+    fn archive(&self, id: &str) -> std::io::Result<()> {
+        let timestamp = unix_timestamp();
+        let mut archived = format!("{}~{}", id, timestamp);
+        let mut n = 1;
+        while self.file(&archived).exists() {
+            n += 1;
+            archived = format!("{}~{}.{}", id, timestamp, n);
+        }
+        match self.rename(id, &archived) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> std::io::Result<()> {
+        std::fs::rename(self.file(from), self.file(to))
+    }
+
+    /// Deletes conversation `id`, returning whether there was one.
+    fn delete(&self, id: &str) -> std::io::Result<bool> {
+        match std::fs::remove_file(self.file(id)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn file(&self, id: &str) -> PathBuf {
+        self.path.join(format!("{}.jsonl", encode(id)))
+    }
+}
+
+/// Session IDs come from clients, so anything but letters, digits, `-`, and
+/// `_` is percent-encoded in file names.
+fn encode(id: &str) -> String {
+    let mut name = String::with_capacity(id.len());
+    for byte in id.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            name.push(char::from(byte));
+        } else {
+            name.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    name
+}
+
+fn decode(name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The first line of `text`, cut to at most 60 characters.
+fn shorten(text: &str) -> String {
+    let line = text.trim().lines().next().unwrap_or_default();
+    match line.char_indices().nth(60) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+fn session_key(request: &Request) -> &str {
+    request.session_id.as_deref().unwrap_or_default()
+}
+
+/// Parses a `/private [on|off]` chat command, returning its argument.
+pub(super) fn private_command(content: &str) -> Option<&str> {
+    let rest = content.trim().strip_prefix("/private")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.trim())
+}
+
+/// Whether `content` is the `/new` chat command, which starts a new conversation.
+pub(super) fn is_new_command(content: &str) -> bool {
+    content.trim() == "/new"
+}
+
+/// Whether `content` is the `/shorter` chat command, which asks the last
+/// question again for a terse answer.
+pub(super) fn is_shorter_command(content: &str) -> bool {
+    content.trim() == "/shorter"
+}
+
+/// Explains why a request is not allowed in private mode, if it isn't.
+pub(super) fn private_violation(request_type: RequestType, config: &Config) -> Option<String> {
+    match request_type {
+        RequestType::Chat
+        | RequestType::Edit
+        | RequestType::Debate
+        | RequestType::Suggest
+        | RequestType::Diff
+        | RequestType::AnalyzeLog
+        | RequestType::GenerateExpression
+        | RequestType::Remember
+        | RequestType::WhatsChanged
+        | RequestType::TodoSummary
+        | RequestType::Generate
+            if !config.llm.is_local() =>
+        {
+            Some(format!(
+                "the LLM at {} is not local; point llm.base_url at this machine or turn private mode off",
+                config.llm.base_url
+            ))
+        }
+        RequestType::Add
+        | RequestType::Index
+        | RequestType::PackImport
+        | RequestType::PackInstall
+        | RequestType::TeamIndex
+        | RequestType::TeamRemove
+        | RequestType::TeamClear
+        | RequestType::IndexCommands
+        | RequestType::IndexDotfiles
+        | RequestType::IndexUrl
+        | RequestType::Remember
+        | RequestType::CollectionCreate
+        | RequestType::CollectionDelete
+        | RequestType::TrashRestore
+        | RequestType::TrashPurge => Some("knowledge base writes are disabled".to_string()),
+        RequestType::Feedback => Some("responses are not stored, so they cannot be rated".to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_command() {
+        assert_eq!(private_command("/private on"), Some("on"));
+        assert_eq!(private_command("  /private  "), Some(""));
+        assert_eq!(private_command("/privately"), None);
+        assert_eq!(private_command("what is /private on?"), None);
+        assert!(is_new_command(" /new\n"));
+        assert!(!is_new_command("/news"));
+        assert!(is_shorter_command("/shorter "));
+        assert!(!is_shorter_command("/shorter please"));
+    }
+
+    #[test]
+    fn test_private_violation() {
+        let mut config = Config::default();
+        config.llm.base_url = "http://localhost:11434".to_string();
+        assert!(private_violation(RequestType::Chat, &config).is_none());
+        assert!(private_violation(RequestType::Index, &config).is_some());
+        assert!(private_violation(RequestType::Stats, &config).is_none());
+        assert!(private_violation(RequestType::Remember, &config).is_some());
+        for request_type in [RequestType::CollectionCreate, RequestType::TrashRestore, RequestType::TrashPurge] {
+            assert!(private_violation(request_type, &config).is_some());
+        }
+        assert!(private_violation(RequestType::TrashList, &config).is_none());
+
+        config.llm.base_url = "https://llm.example.com".to_string();
+        assert!(private_violation(RequestType::Chat, &config).is_some());
+    }
+
+    #[test]
+    fn test_sessions_are_independent() {
+        let sessions = Sessions::default();
+        let tty1 = Request::new(RequestType::Chat, "").with_session_id("tty1");
+        let tty2 = Request::new(RequestType::Chat, "").with_session_id("tty2");
+
+        sessions.set_private(&tty1, true);
+        assert!(sessions.is_private(&tty1));
+        assert!(!sessions.is_private(&tty2));
+
+        sessions.set_private(&tty1, false);
+        assert!(!sessions.is_private(&tty1));
+    }
+
+    #[test]
+    fn test_history_keeps_recent_turns() {
+        let sessions = Sessions::new(SessionsConfig {
+            max_turns: 2,
+            persist: false,
+            ..SessionsConfig::default()
+        });
+        let tty1 = Request::new(RequestType::Chat, "").with_session_id("tty1");
+        let tty2 = Request::new(RequestType::Chat, "").with_session_id("tty2");
+        let anonymous = Request::new(RequestType::Chat, "");
+
+        for n in 0..3 {
+            sessions.record(&tty1, &format!("question {}", n), &format!("answer {}", n));
+        }
+        sessions.record(&anonymous, "question", "answer");
+
+        let history = sessions.history(&tty1);
+        let contents: Vec<&str> = history.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(contents, ["question 1", "answer 1", "question 2", "answer 2"]);
+        assert_eq!(history[0].role, "user");
+        assert_eq!(history[1].role, "assistant");
+        assert!(sessions.history(&tty2).is_empty());
+        assert!(sessions.history(&anonymous).is_empty());
+
+        // Turning private mode on or off starts over
+        sessions.set_private(&tty1, true);
+        assert!(sessions.history(&tty1).is_empty());
+        sessions.record(&tty1, "secret", "kept");
+        assert_eq!(sessions.reset(&tty1), 1);
+        assert!(sessions.history(&tty1).is_empty());
+    }
+
+    #[test]
+    fn test_stored_conversations() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SessionsConfig {
+            max_turns: 2,
+            path: dir.path().join("sessions").to_string_lossy().to_string(),
+            ..SessionsConfig::default()
+        };
+        let tty1 = Request::new(RequestType::Chat, "").with_session_id("tty/1");
+        let tty2 = Request::new(RequestType::Chat, "").with_session_id("tty2");
+
+        let sessions = Sessions::new(config.clone());
+        for n in 0..3 {
+            sessions.record(&tty1, &format!("question {}", n), &format!("answer {}", n));
+        }
+        sessions.set_private(&tty2, true);
+        sessions.record(&tty2, "secret", "not stored");
+
+        // After a restart the session picks up where it left off
+        let sessions = Sessions::new(config);
+        assert_eq!(sessions.history(&tty1).len(), 4);
+        let listed = sessions.list(&tty1).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!((listed[0].id.as_str(), listed[0].turns, listed[0].current), ("tty/1", 3, true));
+        assert_eq!(listed[0].title, "question 0");
+
+        // `/new` keeps the conversation, which another session can take over
+        sessions.reset(&tty1);
+        assert!(sessions.history(&tty1).is_empty());
+        let archived = sessions.list(&tty1).unwrap().remove(0);
+        assert!(archived.id.starts_with("tty/1~") && !archived.current);
+        assert_eq!(sessions.resume(&tty2, &archived.id).unwrap(), 2);
+        assert_eq!(sessions.history(&tty2)[3].content, "answer 2");
+        assert_eq!(sessions.list(&tty2).unwrap()[0].id, "tty2");
+
+        assert!(matches!(sessions.resume(&tty2, "tty3"), Err(SessionError::NotFound(_))));
+        sessions.delete("tty2").unwrap();
+        assert!(sessions.history(&tty2).is_empty());
+        assert!(sessions.list(&tty1).unwrap().is_empty());
+        assert_eq!(decode(&encode("pid-12/ü~3")).as_deref(), Some("pid-12/ü~3"));
+    }
+
+    #[test]
+    fn test_replace_last() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SessionsConfig {
+            path: dir.path().to_string_lossy().to_string(),
+            ..SessionsConfig::default()
+        };
+        let tty1 = Request::new(RequestType::Chat, "").with_session_id("tty1");
+
+        let sessions = Sessions::new(config.clone());
+        sessions.record(&tty1, "question 0", "answer 0");
+        sessions.record(&tty1, "question 1", "a very long answer");
+        sessions.replace_last(&tty1, "question 1", "short");
+
+        let contents = |history: Vec<Message>| history.into_iter().map(|message| message.content).collect::<Vec<_>>();
+        let expected = ["question 0", "answer 0", "question 1", "short"];
+        assert_eq!(contents(sessions.history(&tty1)), expected);
+        assert_eq!(contents(Sessions::new(config).history(&tty1)), expected);
+    }
+}
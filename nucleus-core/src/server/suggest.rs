@@ -0,0 +1,345 @@
+//! Low-latency inline command suggestions.
+//!
+//! Suggestions run on every keystroke pause, so they skip the regular chat
+//! path: a dedicated (usually smaller) model, a strict time budget, optional
+//! knowledge base context cached per working directory, and cancellation of
+//! in-flight suggestions once newer input arrives for the same session.
+//! Finished suggestions are checked by [`crate::guardrails`] before they
+//! are sent.
+//!
+//! When a session starts, its most frequent shell commands and the
+//! top-level files of its working directory are embedded in the background
+//! ([`Standby`]). Input starting one of those commands, or naming one of
+//! those files, is then searched for with the embedding at hand rather than
+//! one computed on the hot path.
+
+use super::types::Request;
+use crate::config::SuggestConfig;
+use crate::memory::{self, Subsystem};
+use crate::provider::Message;
+use crate::shell_integration::Shell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// How long retrieved context is reused for a working directory.
+const CONTEXT_TTL: Duration = Duration::from_secs(60);
+
+/// Sessions whose standby embeddings are kept.
+const MAX_STANDBY_SESSIONS: usize = 32;
+
+const SYSTEM_PROMPT: &str = "You complete shell commands. Given the partial command the user is typing, \
+    reply with the complete command on a single line. No explanation, no code fences.";
+
+/// Tracks in-flight suggestions and cached context.
+#[derive(Debug, Default)]
+pub(super) struct SuggestState {
+    next_id: AtomicU64,
+    /// Session -> (suggestion ID, handle whose drop cancels it)
+    in_flight: Mutex<HashMap<String, (u64, oneshot::Sender<()>)>>,
+    /// Working directory -> (retrieved at, formatted context)
+    context: Mutex<HashMap<String, (Instant, String)>>,
+    /// Session -> embeddings computed ahead of time
+    standby: Mutex<HashMap<String, StandbySlot>>,
+}
+
+#[derive(Debug)]
+enum StandbySlot {
+    /// Being computed for a working directory
+    Warming(String),
+    Ready(Arc<Standby>),
+}
+
+/// Embeddings of a session's frequent commands and of the top-level files
+/// of its working directory.
+#[derive(Debug)]
+pub(super) struct Standby {
+    pwd: String,
+    /// Most frequent first
+    commands: Vec<(String, Vec<f32>)>,
+    files: Vec<(String, Vec<f32>)>,
+    warmed_at: Instant,
+}
+
+impl Standby {
+    /// Pairs `commands` and then `files` with their `embeddings`.
+    pub(super) fn new(pwd: &str, commands: Vec<String>, files: Vec<String>, embeddings: Vec<Vec<f32>>) -> Self {
+        let mut embeddings = embeddings.into_iter();
+        Self {
+            pwd: pwd.to_string(),
+            commands: commands.into_iter().zip(embeddings.by_ref()).collect(),
+            files: files.into_iter().zip(embeddings).collect(),
+            warmed_at: Instant::now(),
+        }
+    }
+
+    /// The text and embedding best standing in for `input`: a file the
+    /// word being typed starts, else the most frequent command starting
+    /// with `input`, else one running the same program.
+    pub(super) fn find(&self, input: &str) -> Option<(&str, &[f32])> {
+        let input = input.trim_start();
+        let program = input.split_whitespace().next()?;
+        let word = input.rsplit_once(char::is_whitespace).map(|(_, word)| word).unwrap_or_default();
+        let file = self.files.iter().find(|(name, _)| !word.is_empty() && name.starts_with(word));
+        file.or_else(|| self.commands.iter().find(|(command, _)| command.starts_with(input)))
+            .or_else(|| {
+                self.commands
+                    .iter()
+                    .find(|(command, _)| command.split_whitespace().next() == Some(program))
+            })
+            .map(|(text, embedding)| (text.as_str(), embedding.as_slice()))
+    }
+}
+
+impl SuggestState {
+    /// Registers a suggestion for `session`, cancelling the previous one.
+    ///
+    /// The returned receiver resolves when a newer suggestion replaces this one.
+    pub(super) fn begin(&self, session: &str) -> (u64, oneshot::Receiver<()>) {
+        let _scope = memory::enter(Subsystem::Sessions);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (cancel, cancelled) = oneshot::channel();
+
+        // Dropping the previous sender wakes its receiver
+        self.in_flight.lock().unwrap().insert(session.to_string(), (id, cancel));
+        (id, cancelled)
+    }
+
+    /// Unregisters a suggestion if it is still the latest for `session`.
+    pub(super) fn end(&self, session: &str, id: u64) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.get(session).is_some_and(|(current, _)| *current == id) {
+            in_flight.remove(session);
+        }
+    }
+
+    pub(super) fn cached_context(&self, pwd: &str) -> Option<String> {
+        let cache = self.context.lock().unwrap();
+        cache
+            .get(pwd)
+            .filter(|(retrieved_at, _)| retrieved_at.elapsed() < CONTEXT_TTL)
+            .map(|(_, context)| context.clone())
+    }
+
+    pub(super) fn cache_context(&self, pwd: &str, context: String) {
+        let _scope = memory::enter(Subsystem::Sessions);
+        let mut cache = self.context.lock().unwrap();
+        cache.retain(|_, (retrieved_at, _)| retrieved_at.elapsed() < CONTEXT_TTL);
+        cache.insert(pwd.to_string(), (Instant::now(), context));
+    }
+
+    /// Whether standby embeddings need computing for `session` in `pwd`,
+    /// i.e. the session is new or has changed directory. If so, the caller
+    /// computes them and hands them to [`set_standby`](Self::set_standby).
+    pub(super) fn needs_standby(&self, session: &str, pwd: &str) -> bool {
+        let _scope = memory::enter(Subsystem::Sessions);
+        let mut standby = self.standby.lock().unwrap();
+        let current = match standby.get(session) {
+            Some(StandbySlot::Warming(warming)) => warming,
+            Some(StandbySlot::Ready(ready)) => &ready.pwd,
+            None => {
+                if standby.len() >= MAX_STANDBY_SESSIONS {
+                    let oldest = standby
+                        .iter()
+                        .filter_map(|(session, slot)| match slot {
+                            StandbySlot::Ready(ready) => Some((session, ready.warmed_at)),
+                            StandbySlot::Warming(_) => None,
+                        })
+                        .min_by_key(|(_, warmed_at)| *warmed_at)
+                        .map(|(session, _)| session.clone());
+                    if let Some(oldest) = oldest {
+                        standby.remove(&oldest);
+                    }
+                }
+                ""
+            }
+        };
+        if current == pwd {
+            return false;
+        }
+        standby.insert(session.to_string(), StandbySlot::Warming(pwd.to_string()));
+        true
+    }
+
+    pub(super) fn set_standby(&self, session: &str, ready: Standby) {
+        let _scope = memory::enter(Subsystem::Sessions);
+        self.standby.lock().unwrap().insert(session.to_string(), StandbySlot::Ready(Arc::new(ready)));
+    }
+
+    /// The standby embeddings of `session`, if they are ready for `pwd`.
+    pub(super) fn standby(&self, session: &str, pwd: &str) -> Option<Arc<Standby>> {
+        match self.standby.lock().unwrap().get(session) {
+            Some(StandbySlot::Ready(ready)) if ready.pwd == pwd => Some(Arc::clone(ready)),
+            _ => None,
+        }
+    }
+}
+
+/// The commands and file names to embed ahead of time for a session in
+/// `pwd`, see [`Standby`].
+///
+/// Does blocking file I/O; call it off the async runtime.
+pub(super) fn standby_texts(settings: &SuggestConfig, shell: Option<&str>, pwd: &Path) -> (Vec<String>, Vec<String>) {
+    let commands = match history_file(settings.history_path.as_deref(), shell) {
+        Some(path) if settings.standby_commands > 0 => match std::fs::read(&path) {
+            Ok(history) => frequent_commands(&String::from_utf8_lossy(&history), settings.standby_commands),
+            Err(e) => {
+                tracing::debug!("Failed to read shell history {}: {}", path.display(), e);
+                Vec::new()
+            }
+        },
+        _ => Vec::new(),
+    };
+    let mut files: Vec<String> = std::fs::read_dir(pwd)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    files.sort();
+    files.truncate(settings.standby_files);
+    (commands, files)
+}
+
+/// The shell history to read: `configured`, `$HISTFILE`, or the history
+/// file of `shell` (the first one found if unknown).
+fn history_file(configured: Option<&str>, shell: Option<&str>) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    if let Some(path) = configured {
+        return Some(crate::dotfiles::expand_home(path, &home));
+    }
+    if let Some(path) = std::env::var_os("HISTFILE") {
+        return Some(PathBuf::from(path));
+    }
+    let candidates = [
+        (Shell::Zsh, ".zsh_history"),
+        (Shell::Bash, ".bash_history"),
+        (Shell::Fish, ".local/share/fish/fish_history"),
+    ];
+    let shell = shell.and_then(Shell::from_name);
+    candidates
+        .iter()
+        .filter(|(candidate, _)| shell.is_none_or(|shell| shell == *candidate))
+        .map(|(_, file)| home.join(file))
+        .find(|path| path.is_file())
+}
+
+/// The `limit` most frequent commands in a bash, zsh, or fish `history`,
+/// the more recent first among equally frequent ones.
+fn frequent_commands(history: &str, limit: usize) -> Vec<String> {
+    // Command -> (uses, last line used on)
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (line_number, line) in history.lines().enumerate() {
+        let command = if let Some(command) = line.strip_prefix("- cmd: ") {
+            command
+        } else if line.starts_with(": ") {
+            // zsh extended history, ": <start>:<duration>;<command>"
+            match line.split_once(';') {
+                Some((_, command)) => command,
+                None => continue,
+            }
+        } else if line.starts_with([' ', '#']) {
+            // Bash timestamps and fish metadata
+            continue;
+        } else {
+            line
+        };
+        let command = command.trim();
+        if !command.is_empty() {
+            let count = counts.entry(command).or_default();
+            *count = (count.0 + 1, line_number);
+        }
+    }
+
+    let mut commands: Vec<_> = counts.into_iter().collect();
+    commands.sort_by(|(_, a), (_, b)| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+    commands.into_iter().take(limit).map(|(command, _)| command.to_string()).collect()
+}
+
+/// Builds the prompt for completing `request.content`.
+pub(super) fn build_messages(request: &Request, context: &str) -> Vec<Message> {
+    let mut system_prompt = SYSTEM_PROMPT.to_string();
+    if let Some(environment) = &request.environment {
+        system_prompt.push_str(&format!("\n\n{}", environment.to_prompt()));
+    }
+    if let Some(pwd) = &request.pwd {
+        system_prompt.push_str(&format!("\nWorking directory: {}", pwd));
+    }
+    system_prompt.push_str(context);
+
+    vec![Message::system(None, system_prompt), Message::user(None, &request.content)]
+}
+
+/// Reduces a model reply to a single command line.
+pub(super) fn clean_suggestion(reply: &str) -> String {
+    reply
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("```"))
+        .map(|line| line.trim_start_matches("$ ").trim_matches('`').to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_newer_suggestion_cancels_previous() {
+        let state = SuggestState::default();
+        let (first, first_cancelled) = state.begin("tty1");
+        let (second, mut second_cancelled) = state.begin("tty1");
+
+        assert!(first_cancelled.await.is_err());
+        assert!(second_cancelled.try_recv().is_err_and(|e| e == oneshot::error::TryRecvError::Empty));
+
+        // Finishing a stale suggestion must not unregister the newer one
+        state.end("tty1", first);
+        assert!(state.in_flight.lock().unwrap().contains_key("tty1"));
+        state.end("tty1", second);
+        assert!(state.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_standby_stands_in_for_input() {
+        let history = "\
+: 1700000000:0;git status
+: 1700000005:0;cargo test
+#1700000010
+git status
+- cmd: cargo build --release
+  when: 1700000020
+cargo test
+git status
+";
+        let commands = frequent_commands(history, 3);
+        assert_eq!(commands, ["git status", "cargo test", "cargo build --release"]);
+
+        let files = vec!["Cargo.toml".to_string(), "README.md".to_string()];
+        let embeddings = (0..5).map(|i| vec![i as f32]).collect();
+        let standby = Standby::new("/project", commands, files, embeddings);
+        assert_eq!(standby.find("git st"), Some(("git status", [0.0].as_slice())));
+        assert_eq!(standby.find("cargo clippy"), Some(("cargo test", [1.0].as_slice())));
+        assert_eq!(standby.find("less READ"), Some(("README.md", [4.0].as_slice())));
+        assert_eq!(standby.find("ls "), None);
+        assert_eq!(standby.find(""), None);
+
+        let state = SuggestState::default();
+        assert!(state.needs_standby("tty1", "/project"));
+        assert!(!state.needs_standby("tty1", "/project"));
+        assert!(state.standby("tty1", "/project").is_none());
+        state.set_standby("tty1", standby);
+        assert!(state.standby("tty1", "/project").is_some());
+        assert!(state.needs_standby("tty1", "/elsewhere"));
+    }
+
+    #[test]
+    fn test_clean_suggestion() {
+        assert_eq!(clean_suggestion("```bash\n$ git status --short\n```"), "git status --short");
+        assert_eq!(clean_suggestion("`ls -la`\nLists all files."), "ls -la");
+        assert_eq!(clean_suggestion(""), "");
+    }
+}
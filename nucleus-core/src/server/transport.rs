@@ -1,7 +1,20 @@
-use super::types::{Request, StreamChunk};
+use super::types::{ChunkType, Request, StreamChunk};
+use std::io::IoSlice;
+use std::time::Duration;
 use thiserror::Error;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// How long to wait for more chunks before writing a batch.
+#[cfg(not(test))]
+const FLUSH_INTERVAL: Duration = Duration::from_millis(4);
+/// Long enough that a test only sees a batch early if it was ended early.
+#[cfg(test)]
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Batch size at which chunks are written without waiting.
+const BATCH_BYTES: usize = 16 * 1024;
 
 #[cfg(unix)]
 use tokio::net::{UnixListener, UnixStream};
@@ -10,6 +23,8 @@ use std::path::Path;
 
 #[cfg(windows)]
 use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+#[cfg(windows)]
+use tokio::sync::Mutex;
 
 #[derive(Debug, Error)]
 pub enum TransportError {
@@ -32,20 +47,31 @@ pub type IpcStream = NamedPipeServer;
 #[cfg(unix)]
 pub type IpcListener = UnixListener;
 
+/// Accepts clients on a named pipe.
+///
+/// A pipe name only exists while one of its instances is open, so an idle
+/// instance is always kept waiting: clients connecting between two accepts
+/// find the pipe instead of `ERROR_FILE_NOT_FOUND`.
 #[cfg(windows)]
 pub struct WindowsPipeListener {
     path: String,
+    /// Instance the next client connects to
+    pending: Mutex<NamedPipeServer>,
 }
 
 #[cfg(windows)]
 impl WindowsPipeListener {
     pub async fn accept(&self) -> Result<(NamedPipeServer, ())> {
-        let server = ServerOptions::new()
+        let mut pending = self.pending.lock().await;
+        let connected = pending.connect().await;
+
+        // Replace the instance either way; one that failed to connect can't be reused
+        let next = ServerOptions::new()
             .first_pipe_instance(false)
             .create(&self.path)?;
-        
-        server.connect().await?;
-        
+        let server = std::mem::replace(&mut *pending, next);
+        connected?;
+
         Ok((server, ()))
     }
 }
@@ -81,15 +107,18 @@ impl IpcTransport {
         Ok(listener)
     }
     
+    /// Creates the first instance of the named pipe.
+    ///
+    /// Fails if another process already serves the pipe.
     #[cfg(windows)]
     pub async fn bind(&self) -> Result<IpcListener> {
-        // Create the first instance of the named pipe
         let server = ServerOptions::new()
             .first_pipe_instance(true)
             .create(&self.socket_path)?;
         
         Ok(WindowsPipeListener {
             path: self.socket_path.clone(),
+            pending: Mutex::new(server),
         })
     }
     
@@ -106,19 +135,6 @@ impl IpcTransport {
 }
 
 /// Reads a request from the stream.
-#[cfg(unix)]
-pub async fn read_request(stream: &mut IpcStream) -> Result<Request> {
-    let (reader, _) = stream.split();
-    let mut reader = BufReader::new(reader);
-    let mut line = String::new();
-    
-    reader.read_line(&mut line).await?;
-    let request = serde_json::from_str(&line)?;
-    
-    Ok(request)
-}
-
-#[cfg(windows)]
 pub async fn read_request(stream: &mut IpcStream) -> Result<Request> {
     let mut reader = BufReader::new(stream);
     let mut line = String::new();
@@ -130,16 +146,125 @@ pub async fn read_request(stream: &mut IpcStream) -> Result<Request> {
 }
 
 /// Writes stream chunks to the client.
+///
+/// Chunks arriving within [`FLUSH_INTERVAL`] of each other are serialized
+/// into reused line buffers and sent with a single vectored write, so fast
+/// generations don't cost a syscall and an allocation per token. The final
+/// `done` or `error` chunk is written without waiting.
 pub async fn write_chunks(
     stream: &mut IpcStream,
     mut receiver: mpsc::UnboundedReceiver<StreamChunk>,
 ) -> Result<()> {
+    // Line buffers kept across batches; the first `count` hold the batch
+    let mut lines: Vec<Vec<u8>> = Vec::new();
+    
     while let Some(chunk) = receiver.recv().await {
-        let json = serde_json::to_string(&chunk)?;
-        stream.write_all(json.as_bytes()).await?;
-        stream.write_all(b"\n").await?;
+        let mut last = encode_chunk(&chunk, &mut lines, 0)?;
+        let mut count = 1;
+        let mut bytes = lines[0].len();
+        let deadline = Instant::now() + FLUSH_INTERVAL;
+        
+        while !last && bytes < BATCH_BYTES {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(chunk)) => {
+                    last = encode_chunk(&chunk, &mut lines, count)?;
+                    bytes += lines[count].len();
+                    count += 1;
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
+        
+        write_lines(stream, &lines[..count]).await?;
         stream.flush().await?;
     }
     
     Ok(())
 }
+
+/// Serializes `chunk` as a JSON line into the reused buffer at `index`,
+/// returning true if it ends the response.
+fn encode_chunk(chunk: &StreamChunk, lines: &mut Vec<Vec<u8>>, index: usize) -> Result<bool> {
+    if index == lines.len() {
+        lines.push(Vec::new());
+    }
+    let line = &mut lines[index];
+    line.clear();
+    serde_json::to_writer(&mut *line, chunk)?;
+    line.push(b'\n');
+    Ok(chunk.chunk_type != ChunkType::Chunk)
+}
+
+/// Writes all of `lines`, resuming vectored writes the stream cut short.
+async fn write_lines(stream: &mut IpcStream, lines: &[Vec<u8>]) -> Result<()> {
+    let mut slices: Vec<IoSlice> = lines.iter().map(|line| IoSlice::new(line)).collect();
+    let mut remaining = &mut slices[..];
+    while !remaining.is_empty() {
+        let written = stream.write_vectored(remaining).await?;
+        if written == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+        }
+        IoSlice::advance_slices(&mut remaining, written);
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::server::RequestType;
+
+    #[tokio::test]
+    async fn test_request_and_chunks_roundtrip() {
+        let (mut server, client) = UnixStream::pair().unwrap();
+        let (client_reader, mut client_writer) = client.into_split();
+
+        let request = Request::new(RequestType::Chat, "hello");
+        let json = serde_json::to_string(&request).unwrap();
+        client_writer.write_all(format!("{}\n", json).as_bytes()).await.unwrap();
+
+        let received = read_request(&mut server).await.unwrap();
+        assert_eq!(received.request_type, RequestType::Chat);
+        assert_eq!(received.content, "hello");
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        sender.send(StreamChunk::chunk("hel")).unwrap();
+        sender.send(StreamChunk::done("hello")).unwrap();
+        drop(sender);
+        write_chunks(&mut server, receiver).await.unwrap();
+        drop(server);
+
+        let mut lines = BufReader::new(client_reader).lines();
+        let mut chunks = Vec::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            chunks.push(serde_json::from_str::<StreamChunk>(&line).unwrap().content);
+        }
+        assert_eq!(chunks, ["hel", "hello"]);
+    }
+
+    #[tokio::test]
+    async fn test_done_is_written_without_waiting() {
+        let (mut server, client) = UnixStream::pair().unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let writer = tokio::spawn(async move { write_chunks(&mut server, receiver).await });
+
+        for token in ["a", "b", "c"] {
+            sender.send(StreamChunk::chunk(token)).unwrap();
+        }
+        sender.send(StreamChunk::done("abc")).unwrap();
+
+        // The sender stays open, so only the done chunk can end the batch
+        // before the flush interval
+        let mut lines = BufReader::new(client).lines();
+        let mut chunks = Vec::new();
+        for _ in 0..4 {
+            let line = tokio::time::timeout(FLUSH_INTERVAL / 10, lines.next_line()).await.unwrap().unwrap().unwrap();
+            let chunk: StreamChunk = serde_json::from_str(&line).unwrap();
+            chunks.push((chunk.chunk_type, chunk.content));
+        }
+        assert_eq!(chunks.last(), Some(&(ChunkType::Done, "abc".to_string())));
+
+        drop(sender);
+        writer.await.unwrap().unwrap();
+    }
+}
@@ -1,4 +1,22 @@
+use crate::attachment::Attachment;
+use crate::config::{OperationClass, Verbosity};
+use crate::environment::EnvironmentContext;
+use crate::expression::ExpressionKind;
+use crate::feedback::Rating;
+use crate::guardrails::{self, GuardrailWarning};
+use crate::memory::MemoryStats;
+use crate::provider::Capabilities;
+use crate::rag::{ContextPack, IndexProgress, SearchResult, Source, SourceStats, StaleSource};
+use crate::shell_integration::CommandCapture;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Version of the request/response protocol.
+///
+/// Bumped when a change would make older clients or servers misread
+/// messages. A server only answers clients that speak the same version;
+/// differing nucleus versions with the same protocol work together.
+pub const PROTOCOL_VERSION: u32 = 1;
 
 /// Type of request being made to the server.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -14,6 +32,295 @@ pub enum RequestType {
     Index,
     /// Get knowledge base statistics
     Stats,
+    /// Implementer/reviewer review loop producing a merged answer (streaming response)
+    #[serde(alias = "review-loop", alias = "review_loop")]
+    Debate,
+    /// Export a context pack to a file
+    #[serde(rename = "pack-export")]
+    PackExport,
+    /// Import a context pack file into the knowledge base
+    #[serde(rename = "pack-import")]
+    PackImport,
+    /// List imported context packs
+    #[serde(rename = "pack-list")]
+    PackList,
+    /// Download the starter pack named by the content into its own collection
+    #[serde(rename = "pack-install")]
+    PackInstall,
+    /// Index a directory into the shared team knowledge base (admin only)
+    #[serde(rename = "team-index")]
+    TeamIndex,
+    /// Remove a file or directory from the shared team knowledge base (admin only)
+    #[serde(rename = "team-remove")]
+    TeamRemove,
+    /// Remove everything from the shared team knowledge base (admin only)
+    #[serde(rename = "team-clear")]
+    TeamClear,
+    /// Get shared team knowledge base statistics
+    #[serde(rename = "team-stats")]
+    TeamStats,
+    /// Rate a previous response (thumbs up/down)
+    Feedback,
+    /// Export rated responses as an eval set
+    #[serde(rename = "feedback-export")]
+    FeedbackExport,
+    /// Summarize ratings per experiment variant
+    #[serde(rename = "feedback-stats")]
+    FeedbackStats,
+    /// Summarize the project's directory layout
+    Tree,
+    /// Complete a partially typed shell command (low-latency path)
+    Suggest,
+    /// Turn private mode on or off for the session (also `/private on|off` in chat)
+    Privacy,
+    /// Forget the session's conversation history (also `/new` in chat)
+    #[serde(rename = "session-reset")]
+    SessionReset,
+    /// List the stored conversations, most recently used first (JSON response)
+    #[serde(rename = "session-list")]
+    SessionList,
+    /// Continue the stored conversation whose ID is the content in the session
+    #[serde(rename = "session-resume")]
+    SessionResume,
+    /// Delete the stored conversation whose ID is the content
+    #[serde(rename = "session-delete")]
+    SessionDelete,
+    /// Search the knowledge base without asking the LLM (JSON response); the
+    /// retrieval half of a chat request, see `generate`
+    #[serde(alias = "retrieve")]
+    Search,
+    /// Explain the meaningful differences between two attachments (streaming response)
+    Diff,
+    /// Summarize a large log file and cluster its errors (streaming response)
+    #[serde(rename = "analyze-log")]
+    AnalyzeLog,
+    /// Generate a regex or jq expression and validate it against a sample
+    #[serde(rename = "generate-expression")]
+    GenerateExpression,
+    /// Index man pages and `--help` output of the commands on the server's PATH
+    #[serde(rename = "index-commands")]
+    IndexCommands,
+    /// Index the configuration files listed in `dotfiles.paths` (requires `dotfiles.enabled`)
+    #[serde(rename = "index-dotfiles")]
+    IndexDotfiles,
+    /// Fetch a web page and index its readable text, with the URL as source
+    #[serde(rename = "index-url")]
+    IndexUrl,
+    /// Summarize a recent exchange into the conversation collection
+    Remember,
+    /// Summarize uncommitted changes and recent commits of the repository at `pwd` (streaming response)
+    #[serde(rename = "whats-changed")]
+    WhatsChanged,
+    /// List TODO, FIXME, and HACK comments in indexed files, by file (JSON response)
+    Todos,
+    /// Summarize and prioritize the TODO, FIXME, and HACK comments in indexed files (streaming response)
+    #[serde(rename = "todo-summary")]
+    TodoSummary,
+    /// Report the server version and the health of the model and store (JSON response)
+    Status,
+    /// List running jobs such as directory indexing (JSON response)
+    #[serde(rename = "jobs-list")]
+    JobsList,
+    /// Cancel the job whose ID is the content
+    #[serde(rename = "jobs-cancel")]
+    JobsCancel,
+    /// Progress or outcome of the running or recently finished job whose ID
+    /// is the content (JSON response)
+    #[serde(rename = "jobs-status")]
+    JobsStatus,
+    /// List the named knowledge base collections (JSON response)
+    #[serde(rename = "collection-list")]
+    CollectionList,
+    /// Create the collection named by the content, used from within `pwd` if set
+    #[serde(rename = "collection-create")]
+    CollectionCreate,
+    /// Delete the collection named by the content and everything in it
+    #[serde(rename = "collection-delete")]
+    CollectionDelete,
+    /// Make the collection named by the content the one used outside every project
+    #[serde(rename = "collection-switch")]
+    CollectionSwitch,
+    /// List the removals kept in the trash, newest first (JSON response)
+    #[serde(rename = "trash-list")]
+    TrashList,
+    /// Restore the trash entry whose ID is the content
+    #[serde(rename = "trash-restore")]
+    TrashRestore,
+    /// Delete the trash entry whose ID is the content for good, or every
+    /// entry if the content is empty
+    #[serde(rename = "trash-purge")]
+    TrashPurge,
+    /// Record the sources of the collection and hashes of their content as
+    /// the snapshot named by the content, or one named after the time
+    #[serde(rename = "snapshot-create")]
+    SnapshotCreate,
+    /// List the knowledge base snapshots, newest first (JSON response)
+    #[serde(rename = "snapshot-list")]
+    SnapshotList,
+    /// Sources added, removed, and changed since the snapshot named by the
+    /// content (JSON response)
+    #[serde(rename = "snapshot-diff")]
+    SnapshotDiff,
+    /// Stream workspace events as they happen, one JSON event per chunk, until
+    /// the client disconnects (see [`crate::events`])
+    Subscribe,
+    /// List the plugins found in `plugins.path` (JSON response)
+    #[serde(rename = "plugins-list")]
+    PluginsList,
+    /// Enable the plugin named by the content
+    #[serde(rename = "plugins-enable")]
+    PluginsEnable,
+    /// Disable the plugin named by the content
+    #[serde(rename = "plugins-disable")]
+    PluginsDisable,
+    /// Answer like a chat request, from the request's `context` instead of
+    /// searching the knowledge base (streaming response)
+    Generate,
+}
+
+impl RequestType {
+    /// The `timeouts` limit that applies to the request, if any.
+    ///
+    /// Quick local requests have none, and neither does `suggest`, which
+    /// keeps to its own `suggest.max_time_ms` budget, or `subscribe`, which
+    /// streams until the client disconnects.
+    pub fn operation_class(self) -> Option<OperationClass> {
+        match self {
+            Self::Chat
+            | Self::Edit
+            | Self::Debate
+            | Self::Diff
+            | Self::AnalyzeLog
+            | Self::GenerateExpression
+            | Self::WhatsChanged
+            | Self::TodoSummary
+            | Self::Generate => Some(OperationClass::Chat),
+            Self::Add | Self::Search | Self::Remember | Self::Status => Some(OperationClass::Embed),
+            Self::Index
+            | Self::PackExport
+            | Self::PackImport
+            | Self::PackInstall
+            | Self::TeamIndex
+            | Self::TeamRemove
+            | Self::TeamClear
+            | Self::IndexCommands
+            | Self::IndexDotfiles
+            | Self::IndexUrl
+            | Self::CollectionDelete
+            | Self::TrashRestore => Some(OperationClass::Index),
+            Self::Stats
+            | Self::PackList
+            | Self::TeamStats
+            | Self::Feedback
+            | Self::FeedbackExport
+            | Self::FeedbackStats
+            | Self::Tree
+            | Self::Suggest
+            | Self::Privacy
+            | Self::SessionReset
+            | Self::SessionList
+            | Self::SessionResume
+            | Self::SessionDelete
+            | Self::Todos
+            | Self::JobsList
+            | Self::JobsCancel
+            | Self::JobsStatus
+            | Self::CollectionList
+            | Self::CollectionCreate
+            | Self::CollectionSwitch
+            | Self::TrashList
+            | Self::TrashPurge
+            | Self::SnapshotCreate
+            | Self::SnapshotList
+            | Self::SnapshotDiff
+            | Self::Subscribe
+            | Self::PluginsList
+            | Self::PluginsEnable
+            | Self::PluginsDisable => None,
+        }
+    }
+
+    /// The subsystems the request uses, which it waits for while they start.
+    ///
+    /// `status` and `suggest` never wait: one reports what is still starting,
+    /// the other keeps to its latency budget.
+    pub fn components(self) -> &'static [Component] {
+        match self {
+            Self::Chat
+            | Self::Edit
+            | Self::Add
+            | Self::Index
+            | Self::Debate
+            | Self::PackImport
+            | Self::PackInstall
+            | Self::TeamIndex
+            | Self::Search
+            | Self::IndexCommands
+            | Self::IndexDotfiles
+            | Self::IndexUrl
+            | Self::Remember
+            | Self::TodoSummary => &[Component::Model, Component::KnowledgeBase],
+            Self::Diff
+            | Self::AnalyzeLog
+            | Self::GenerateExpression
+            | Self::WhatsChanged
+            | Self::Generate => &[Component::Model],
+            Self::Stats
+            | Self::PackExport
+            | Self::TeamRemove
+            | Self::TeamClear
+            | Self::TeamStats
+            | Self::Todos
+            | Self::CollectionDelete
+            | Self::TrashRestore
+            | Self::SnapshotCreate
+            | Self::SnapshotDiff => &[Component::KnowledgeBase],
+            Self::PackList
+            | Self::Feedback
+            | Self::FeedbackExport
+            | Self::FeedbackStats
+            | Self::Tree
+            | Self::Suggest
+            | Self::Privacy
+            | Self::SessionReset
+            | Self::SessionList
+            | Self::SessionResume
+            | Self::SessionDelete
+            | Self::Status
+            | Self::JobsList
+            | Self::JobsCancel
+            | Self::JobsStatus
+            | Self::CollectionList
+            | Self::CollectionCreate
+            | Self::CollectionSwitch
+            | Self::TrashList
+            | Self::TrashPurge
+            | Self::SnapshotList
+            | Self::Subscribe
+            | Self::PluginsList
+            | Self::PluginsEnable
+            | Self::PluginsDisable => &[],
+        }
+    }
+}
+
+/// A subsystem the server starts in the background after it begins listening.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Component {
+    /// The LLM provider, which may have to load a model
+    Model,
+    /// The vector stores behind every collection
+    KnowledgeBase,
+}
+
+impl Component {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Model => "model",
+            Self::KnowledgeBase => "knowledge base",
+        }
+    }
 }
 
 /// Type of streaming response chunk.
@@ -59,7 +366,25 @@ pub struct Request {
     /// For chat/edit: the user's message
     /// For add: the text to add to knowledge base
     /// For index: the directory path to index
-    /// For stats: ignored
+    /// For index-url: the URL of the page to index
+    /// For debate: the task for the implementer and reviewer
+    /// For pack-export/pack-import: the pack file path (relative to `pwd`)
+    /// For team-index/team-remove: the directory or file path (relative to `pwd`)
+    /// For feedback: an optional comment
+    /// For feedback-export: the output file path (relative to `pwd`)
+    /// For tree: the project directory (defaults to `pwd`)
+    /// For suggest: the partially typed command
+    /// For privacy: "on", "off", or "status"
+    /// For session-reset: ignored
+    /// For search: the query
+    /// For diff: an optional question about the differences (the texts are the two attachments)
+    /// For analyze-log: the log file path (relative to `pwd`)
+    /// For generate-expression: what the expression should do (the sample is the attachment)
+    /// For remember: the response ID of the exchange to keep
+    /// For whats-changed: optionally, what to pay particular attention to
+    /// For todos/todo-summary: the directory to look in (relative to `pwd`, defaults to `pwd`)
+    /// For subscribe: comma-separated event types to receive (all if empty)
+    /// For stats/status/pack-list/team-stats/team-clear/index-commands/index-dotfiles: ignored
     pub content: String,
 
     /// Optional working directory context.
@@ -73,6 +398,379 @@ pub struct Request {
     /// Allows maintaining context across multiple interactions.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub history: Option<Vec<Message>>,
+
+    /// Pack manifest describing what to export, for pack-export requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pack: Option<ContextPack>,
+
+    /// Response being rated, for feedback requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_id: Option<String>,
+
+    /// Rating for feedback requests, or a filter for feedback-export requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<Rating>,
+
+    /// Time budget for chat/edit requests, in milliseconds.
+    ///
+    /// When exceeded, generation stops and the partial answer is returned
+    /// with `truncated` set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_time_ms: Option<u64>,
+
+    /// Token budget for chat/edit requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+
+    /// Answer length for chat/edit requests (defaults to `verbosity.default`).
+    ///
+    /// Adds the verbosity's instructions to the system prompt, and its
+    /// token budget unless `max_tokens` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verbosity: Option<Verbosity>,
+
+    /// Client environment (OS, shell, git state, toolchains) for chat/edit requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<EnvironmentContext>,
+
+    /// Add a summarized tree of `pwd` to the context of chat/edit requests.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub include_tree: bool,
+
+    /// Files or inline snippets added to the context of chat/edit requests,
+    /// the old and new versions for diff requests, or the sample for
+    /// generate-expression requests.
+    ///
+    /// Attachments apply to this request only and are not indexed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+
+    /// Last command and its output captured by the terminal client, for chat/edit requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_command: Option<CommandCapture>,
+
+    /// Client session (e.g. a terminal) the request belongs to.
+    ///
+    /// A new suggest request cancels the in-flight suggestion of the same session,
+    /// private mode applies per session, and chat requests without `history`
+    /// continue the session's conversation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+
+    /// Maximum number of results for search requests (defaults to `storage.top_k`),
+    /// or of recent commits for whats-changed requests (defaults to 3).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+
+    /// Search every collection (knowledge base, team, command documentation,
+    /// dotfiles, conversations) for chat/edit and search requests, merging
+    /// the results by weighted score and labeling their origin.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub all_collections: bool,
+
+    /// Context for generate requests, e.g. search results reranked and
+    /// formatted by the client; added to the prompt of chat requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+
+    /// Named collection to search or index instead of the one for `pwd`
+    /// (the collection whose root contains it, else the active one).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+
+    /// Language to generate for generate-expression requests (defaults to regex).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression_kind: Option<ExpressionKind>,
+
+    /// Answer an index request with its job ID right away and index in the
+    /// background, instead of streaming progress until indexing is done.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub detach: bool,
+
+    /// Version of the client that sent the request.
+    ///
+    /// Set by [`Request::new`]; requests without it are answered as before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<ClientVersion>,
+}
+
+/// nucleus and protocol version of a client or server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientVersion {
+    pub version: String,
+    pub protocol: u32,
+}
+
+impl ClientVersion {
+    /// The version of this build.
+    pub fn current() -> Self {
+        Self {
+            version: crate::VERSION.to_string(),
+            protocol: PROTOCOL_VERSION,
+        }
+    }
+}
+
+impl Request {
+    pub fn new(request_type: RequestType, content: impl Into<String>) -> Self {
+        Self {
+            request_type,
+            content: content.into(),
+            pwd: None,
+            history: None,
+            pack: None,
+            response_id: None,
+            rating: None,
+            max_time_ms: None,
+            max_tokens: None,
+            verbosity: None,
+            environment: None,
+            include_tree: false,
+            attachments: Vec::new(),
+            last_command: None,
+            session_id: None,
+            limit: None,
+            all_collections: false,
+            context: None,
+            collection: None,
+            expression_kind: None,
+            detach: false,
+            client: Some(ClientVersion::current()),
+        }
+    }
+
+    pub fn with_pwd(mut self, pwd: impl Into<String>) -> Self {
+        self.pwd = Some(pwd.into());
+        self
+    }
+
+    pub fn with_pack(mut self, pack: ContextPack) -> Self {
+        self.pack = Some(pack);
+        self
+    }
+
+    pub fn with_response_id(mut self, response_id: impl Into<String>) -> Self {
+        self.response_id = Some(response_id.into());
+        self
+    }
+
+    pub fn with_rating(mut self, rating: Rating) -> Self {
+        self.rating = Some(rating);
+        self
+    }
+
+    pub fn with_max_time_ms(mut self, max_time_ms: u64) -> Self {
+        self.max_time_ms = Some(max_time_ms);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = Some(verbosity);
+        self
+    }
+
+    pub fn with_environment(mut self, environment: EnvironmentContext) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    pub fn with_tree(mut self) -> Self {
+        self.include_tree = true;
+        self
+    }
+
+    pub fn with_attachment(mut self, attachment: Attachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    pub fn with_last_command(mut self, command: CommandCapture) -> Self {
+        self.last_command = Some(command);
+        self
+    }
+
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn with_all_collections(mut self) -> Self {
+        self.all_collections = true;
+        self
+    }
+
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    pub fn with_collection(mut self, collection: impl Into<String>) -> Self {
+        self.collection = Some(collection.into());
+        self
+    }
+
+    pub fn with_expression_kind(mut self, kind: ExpressionKind) -> Self {
+        self.expression_kind = Some(kind);
+        self
+    }
+
+    pub fn with_detach(mut self, detach: bool) -> Self {
+        self.detach = detach;
+        self
+    }
+}
+
+/// Server version and health, the response to a status request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerStatus {
+    /// nucleus version the server was built from
+    pub version: String,
+    pub llm_model: String,
+    pub embedding_model: String,
+    /// Embedding dimension the vector store is configured for
+    pub store_dim: usize,
+    /// Dimension of the embeddings the model returned, if it answered
+    #[serde(default)]
+    pub model_dim: Option<usize>,
+    #[serde(default)]
+    pub embedding_error: Option<String>,
+    /// Documents in the local knowledge base, if the store answered
+    #[serde(default)]
+    pub documents: Option<usize>,
+    #[serde(default)]
+    pub store_error: Option<String>,
+    /// Subsystems still starting, which were not checked
+    #[serde(default)]
+    pub warming_up: Vec<Component>,
+    /// What the chat model supports, so clients can hide what it can't do
+    /// (e.g. attaching images); `None` while the model is starting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Capabilities>,
+}
+
+/// Knowledge base statistics, the response to a stats request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexStats {
+    /// Collection the statistics are for
+    pub collection: String,
+    /// Stored chunks, one vector each
+    pub chunks: usize,
+    /// Chunks by source (file, URL, or note), sorted by source
+    #[serde(default)]
+    pub sources: Vec<SourceStats>,
+    /// Bytes the knowledge base takes up on disk; `None` for a store on a server
+    #[serde(default)]
+    pub disk_bytes: Option<u64>,
+    pub embedding_model: String,
+    pub embedding_dim: usize,
+    /// When a source was last indexed, in seconds since the Unix epoch
+    #[serde(default)]
+    pub last_indexed: Option<u64>,
+    /// Sources that changed since they were indexed (`rag.freshness`)
+    #[serde(default)]
+    pub stale: Vec<StaleSource>,
+    /// Indexing jobs still running
+    #[serde(default)]
+    pub indexing: Vec<JobInfo>,
+    /// Shared team knowledge base, if team mode is configured
+    #[serde(default)]
+    pub team: Option<TeamIndexStats>,
+    /// Heap usage by subsystem (`memory-stats` feature)
+    #[serde(default)]
+    pub memory: Option<MemoryStats>,
+    /// Version of a newer release, if one is available
+    #[serde(default)]
+    pub latest_version: Option<String>,
+}
+
+/// The shared team knowledge base in [`IndexStats`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TeamIndexStats {
+    pub namespace: String,
+    pub chunks: usize,
+}
+
+/// A running job, as listed by a jobs-list request, or a finished one, as
+/// reported by a jobs-status request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobInfo {
+    /// ID to pass to a jobs-cancel or jobs-status request
+    pub id: u64,
+    /// What the job does, e.g. `index /src/app`
+    pub description: String,
+    /// Seconds the job has been running, or ran for once finished
+    pub elapsed_secs: u64,
+    /// Set once the job has been asked to stop
+    #[serde(default)]
+    pub cancelling: bool,
+    /// Last progress the job reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<IndexProgress>,
+    /// How the job ended; unset while it runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<JobOutcome>,
+}
+
+/// A stored conversation, as listed by a session-list request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    /// ID to pass to a session-resume or session-delete request
+    pub id: String,
+    /// Question and answer pairs
+    pub turns: usize,
+    /// Unix timestamp (seconds) of the first message
+    pub created: u64,
+    /// Unix timestamp (seconds) of the last message
+    pub updated: u64,
+    /// The start of the first question
+    pub title: String,
+    /// Whether this is the requesting session's conversation
+    #[serde(default)]
+    pub current: bool,
+}
+
+/// How a finished job ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobOutcome {
+    pub success: bool,
+    /// e.g. `Indexed 12 files from: /src/app`
+    pub summary: String,
+}
+
+/// Knowledge base search result.
+///
+/// The "done" chunk of a search request carries a JSON array of these.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchHit {
+    /// Document ID
+    pub id: String,
+    pub content: String,
+    /// Similarity to the query (higher is closer)
+    pub score: f32,
+    /// Document metadata such as `source`
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+impl From<SearchResult> for SearchHit {
+    fn from(result: SearchResult) -> Self {
+        Self {
+            id: result.document.id,
+            content: result.document.content,
+            score: result.score,
+            metadata: result.document.metadata,
+        }
+    }
 }
 
 /// Streaming response chunk sent to client.
@@ -94,6 +792,115 @@ pub struct StreamChunk {
     /// Error message if chunk_type is "error".
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+
+    /// ID of the response, set on the "done" chunk of chat/edit requests.
+    ///
+    /// Pass it back in a feedback request to rate the response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_id: Option<String>,
+
+    /// Set on the "done" chunk when generation stopped at the request's budget.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+
+    /// Set on the "error" chunk when the request ran past its time limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Timeout>,
+
+    /// Set on the "error" chunk when the client speaks another protocol version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incompatible: Option<VersionMismatch>,
+
+    /// Set on content-less "chunk" chunks while the request waits for a
+    /// subsystem that is still starting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warming_up: Option<WarmingUp>,
+
+    /// Files and chunks the knowledge base context came from, set on the
+    /// "done" chunk of chat/edit requests; the content ends in a matching
+    /// `Sources:` footer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<Source>,
+
+    /// ID of the job an index request runs as, set on its progress chunks
+    /// and "done" chunk; pass it to jobs-cancel or jobs-status requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<u64>,
+
+    /// Set on content-less "chunk" chunks of index requests as files are
+    /// indexed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<IndexProgress>,
+
+    /// Guardrail rules the suggested command matched, set on the "done"
+    /// chunk of suggest requests.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub guardrails: Vec<GuardrailWarning>,
+
+    /// Set with `guardrails` when the client should ask for an extra
+    /// keystroke before accepting the suggestion.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub confirm: bool,
+}
+
+/// A request the server stopped at its `timeouts` limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timeout {
+    /// Which limit was hit
+    pub operation: OperationClass,
+    pub limit_secs: u64,
+}
+
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The {} request timed out after {}s (raise `timeouts.{}_secs` to allow more time)",
+            self.operation.as_str(), self.limit_secs, self.operation.as_str()
+        )
+    }
+}
+
+/// Progress of a request waiting for a subsystem that is still starting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WarmingUp {
+    pub component: Component,
+    /// Seconds since the server started
+    pub elapsed_secs: u64,
+}
+
+impl std::fmt::Display for WarmingUp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Waiting for the server to start the {} ({}s)...", self.component.as_str(), self.elapsed_secs)
+    }
+}
+
+/// A client and server that cannot talk to each other.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionMismatch {
+    pub client: ClientVersion,
+    pub server: ClientVersion,
+}
+
+impl VersionMismatch {
+    /// How to make the two compatible again.
+    pub fn fix(&self) -> &'static str {
+        if self.client.protocol < self.server.protocol {
+            "Upgrade the client so it runs the same version as the server"
+        } else {
+            "Restart the server after upgrading it so it runs the same version as the client"
+        }
+    }
+}
+
+impl std::fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "nucleus {} (protocol {}) cannot talk to server {} (protocol {}). {}",
+            self.client.version, self.client.protocol, self.server.version, self.server.protocol, self.fix()
+        )
+    }
 }
 
 impl StreamChunk {
@@ -102,6 +909,16 @@ impl StreamChunk {
             chunk_type: ChunkType::Chunk,
             content: content.into(),
             error: None,
+            response_id: None,
+            truncated: false,
+            timeout: None,
+            incompatible: None,
+            warming_up: None,
+            sources: Vec::new(),
+            job: None,
+            progress: None,
+            guardrails: Vec::new(),
+            confirm: false,
         }
     }
 
@@ -110,6 +927,16 @@ impl StreamChunk {
             chunk_type: ChunkType::Done,
             content: content.into(),
             error: None,
+            response_id: None,
+            truncated: false,
+            timeout: None,
+            incompatible: None,
+            warming_up: None,
+            sources: Vec::new(),
+            job: None,
+            progress: None,
+            guardrails: Vec::new(),
+            confirm: false,
         }
     }
 
@@ -118,6 +945,76 @@ impl StreamChunk {
             chunk_type: ChunkType::Error,
             content: String::new(),
             error: Some(error.into()),
+            response_id: None,
+            truncated: false,
+            timeout: None,
+            incompatible: None,
+            warming_up: None,
+            sources: Vec::new(),
+            job: None,
+            progress: None,
+            guardrails: Vec::new(),
+            confirm: false,
+        }
+    }
+
+    /// Progress chunk for a request waiting for a subsystem to start.
+    pub fn warming_up(warming_up: WarmingUp) -> Self {
+        Self {
+            warming_up: Some(warming_up),
+            ..Self::chunk("")
+        }
+    }
+
+    /// Progress chunk for job `job`.
+    pub fn progress(job: u64, progress: IndexProgress) -> Self {
+        Self {
+            job: Some(job),
+            progress: Some(progress),
+            ..Self::chunk("")
+        }
+    }
+
+    /// Error chunk for a request stopped at its time limit.
+    pub fn timed_out(timeout: Timeout) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..Self::error(timeout.to_string())
+        }
+    }
+
+    /// Error chunk for a request from a client with another protocol version.
+    pub fn incompatible(mismatch: VersionMismatch) -> Self {
+        Self {
+            incompatible: Some(mismatch.clone()),
+            ..Self::error(mismatch.to_string())
         }
     }
+
+    pub fn with_response_id(mut self, response_id: impl Into<String>) -> Self {
+        self.response_id = Some(response_id.into());
+        self
+    }
+
+    pub fn with_sources(mut self, sources: Vec<Source>) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    pub fn with_truncated(mut self, truncated: bool) -> Self {
+        self.truncated = truncated;
+        self
+    }
+
+    pub fn with_job(mut self, job: u64) -> Self {
+        self.job = Some(job);
+        self
+    }
+
+    /// Attaches guardrail warnings, asking for confirmation if one says so.
+    pub fn with_guardrails(mut self, guardrails: Vec<GuardrailWarning>) -> Self {
+        self.confirm = guardrails::requires_confirmation(&guardrails);
+        self.guardrails = guardrails;
+        self
+    }
 }
@@ -0,0 +1,152 @@
+//! Live index updates for indexed directories.
+//!
+//! [`DirWatcher`] receives file system events for every directory the server
+//! indexes. Changed paths are collected until the tree has been quiet for the
+//! debounce interval, then refreshed in the knowledge base (see
+//! [`RagEngine::refresh_path`](crate::rag::RagEngine::refresh_path)), so
+//! edited files are re-indexed and deleted ones removed while you work.
+
+use super::handler::RequestHandler;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// Changes are applied after this long even if files keep changing.
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// A watched directory.
+struct Root {
+    /// Path the directory was indexed under, the prefix of its sources
+    indexed: PathBuf,
+    /// Path events are reported under
+    canonical: PathBuf,
+}
+
+/// Recursive file system watcher for indexed directories.
+pub(super) struct DirWatcher {
+    watcher: Mutex<RecommendedWatcher>,
+    roots: Arc<Mutex<Vec<Root>>>,
+}
+
+impl DirWatcher {
+    /// Creates a watcher, returning it with the stream of changed paths.
+    ///
+    /// Paths are reported as they were indexed, so they match the `source`
+    /// of the documents indexed from them.
+    pub(super) fn new() -> notify::Result<(Self, mpsc::UnboundedReceiver<PathBuf>)> {
+        let (sender, changes) = mpsc::unbounded_channel();
+        let roots = Arc::new(Mutex::new(Vec::<Root>::new()));
+
+        let event_roots = Arc::clone(&roots);
+        let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            let event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("File watcher error: {}", e);
+                    return;
+                }
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+
+            let roots = event_roots.lock().unwrap();
+            for path in event.paths {
+                if let Some(path) = indexed_path(&roots, &path) {
+                    let _ = sender.send(path);
+                }
+            }
+        })?;
+
+        Ok((Self { watcher: Mutex::new(watcher), roots }, changes))
+    }
+
+    /// Watches `dir` recursively, unless it is already watched.
+    ///
+    /// Returns whether a new watch was added.
+    pub(super) fn watch(&self, dir: &Path) -> notify::Result<bool> {
+        let canonical = dir.canonicalize().map_err(notify::Error::io)?;
+        if self.roots.lock().unwrap().iter().any(|root| canonical.starts_with(&root.canonical)) {
+            return Ok(false);
+        }
+
+        // The roots lock is not held here: the backend may deliver events
+        // (which take it) while a watch is being added
+        self.watcher.lock().unwrap().watch(&canonical, RecursiveMode::Recursive)?;
+        self.roots.lock().unwrap().push(Root { indexed: dir.to_path_buf(), canonical });
+        Ok(true)
+    }
+}
+
+/// Maps a path reported by the watcher to the path it was indexed under.
+fn indexed_path(roots: &[Root], path: &Path) -> Option<PathBuf> {
+    roots.iter().find_map(|root| {
+        let relative = path.strip_prefix(&root.canonical).ok()?;
+        Some(if relative.as_os_str().is_empty() {
+            root.indexed.clone()
+        } else {
+            root.indexed.join(relative)
+        })
+    })
+}
+
+/// Indexes and watches `paths`, then applies changes until the watcher is dropped.
+pub(super) async fn run(
+    handler: Arc<RequestHandler>,
+    paths: Vec<String>,
+    mut changes: mpsc::UnboundedReceiver<PathBuf>,
+    debounce: Duration,
+) {
+    for path in paths {
+        if let Err(e) = handler.index(Path::new(&path), None).await {
+            warn!("Failed to index watched directory {}: {}", path, e);
+        }
+    }
+
+    while let Some(first) = changes.recv().await {
+        let deadline = Instant::now() + MAX_DELAY;
+        let mut changed = BTreeSet::from([first]);
+        while let Ok(Some(path)) = tokio::time::timeout(debounce.min(deadline - Instant::now()), changes.recv()).await {
+            changed.insert(path);
+        }
+        handler.refresh(changed).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indexed_path() {
+        let roots = vec![Root {
+            indexed: PathBuf::from("./project"),
+            canonical: PathBuf::from("/home/user/project"),
+        }];
+
+        assert_eq!(
+            indexed_path(&roots, Path::new("/home/user/project/src/main.rs")),
+            Some(PathBuf::from("./project/src/main.rs"))
+        );
+        assert_eq!(indexed_path(&roots, Path::new("/home/user/project")), Some(PathBuf::from("./project")));
+        assert_eq!(indexed_path(&roots, Path::new("/home/user/other/main.rs")), None);
+    }
+
+    #[tokio::test]
+    async fn test_reports_changes_under_indexed_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let (watcher, mut changes) = DirWatcher::new().unwrap();
+        assert!(watcher.watch(dir.path()).unwrap());
+        assert!(!watcher.watch(&dir.path().join(".")).unwrap());
+
+        std::fs::write(dir.path().join("notes.md"), "# Notes").unwrap();
+
+        let changed = tokio::time::timeout(Duration::from_secs(5), changes.recv()).await.unwrap().unwrap();
+        assert_eq!(changed, dir.path().join("notes.md"));
+    }
+}
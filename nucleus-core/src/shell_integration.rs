@@ -0,0 +1,597 @@
+//! Command capture via OSC 133 shell integration markers.
+//!
+//! Shells with semantic prompt support (or the hooks from [`shell_hook`])
+//! wrap each prompt, command line, and command output in OSC 133 escape
+//! sequences:
+//!
+//! ```text
+//! ESC]133;A BEL   prompt starts
+//! ESC]133;B BEL   prompt ends, user types the command
+//! ESC]133;C BEL   command runs, output starts
+//! ESC]133;D;0 BEL command finished with exit code 0
+//! ```
+//!
+//! A terminal client feeds everything the shell writes into a
+//! [`CommandTracker`], which yields one [`CommandCapture`] per finished
+//! command. The capture can be attached to a chat request so "explain the
+//! last command's output" gets exactly that output rather than a guess from
+//! the scrollback.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Start of an OSC 133 sequence.
+const OSC_133: &[u8] = b"\x1b]133;";
+
+/// Markers longer than this are treated as plain output.
+const MAX_MARKER_LEN: usize = 256;
+
+/// Finished commands kept for lookup.
+const MAX_HISTORY: usize = 20;
+
+/// A finished command and its output, with terminal escapes removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandCapture {
+    pub command: String,
+    pub output: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Set if the beginning of the output was dropped to stay within the limit
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+impl CommandCapture {
+    /// Formats the command as a prompt section.
+    pub fn to_prompt(&self) -> String {
+        let mut prompt = format!("\n\nLast command run in the terminal:\n$ {}\n", self.command);
+        if let Some(exit_code) = self.exit_code {
+            prompt.push_str(&format!("(exit code {})\n", exit_code));
+        }
+        prompt.push_str("Output");
+        if self.truncated {
+            prompt.push_str(" (beginning omitted)");
+        }
+        prompt.push_str(&format!(":\n{}\n", self.output.trim_end()));
+        prompt
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Idle,
+    Prompt,
+    Input,
+    Output,
+}
+
+/// Splits a terminal byte stream into commands using OSC 133 markers.
+#[derive(Debug)]
+pub struct CommandTracker {
+    phase: Phase,
+    /// Bytes that may be the start of a marker split across reads
+    pending: Vec<u8>,
+    command: Vec<u8>,
+    output: Vec<u8>,
+    truncated: bool,
+    /// Set once the shell sends C markers; until then a newline ends the command line
+    marks_output: bool,
+    max_output_bytes: usize,
+    history: VecDeque<CommandCapture>,
+}
+
+impl CommandTracker {
+    /// Creates a tracker keeping at most the last `max_output_bytes` of each command's output.
+    pub fn new(max_output_bytes: usize) -> Self {
+        Self {
+            phase: Phase::Idle,
+            pending: Vec::new(),
+            command: Vec::new(),
+            output: Vec::new(),
+            truncated: false,
+            marks_output: false,
+            max_output_bytes,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Processes bytes written by the shell.
+    ///
+    /// # Returns
+    ///
+    /// Commands that finished within these bytes, oldest first.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<CommandCapture> {
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(bytes);
+
+        let mut finished = Vec::new();
+        let mut rest = &data[..];
+
+        while let Some(start) = find(rest, OSC_133) {
+            self.push_text(&rest[..start]);
+
+            let after = &rest[start + OSC_133.len()..];
+            match find_terminator(after) {
+                Some((end, terminator_len)) => {
+                    let params = String::from_utf8_lossy(&after[..end]).to_string();
+                    if let Some(capture) = self.handle_marker(&params) {
+                        finished.push(capture);
+                    }
+                    rest = &after[end + terminator_len..];
+                }
+                None if after.len() > MAX_MARKER_LEN => {
+                    // Not a real marker; keep it as text
+                    self.push_text(&rest[start..start + OSC_133.len()]);
+                    rest = after;
+                }
+                None => {
+                    self.pending = rest[start..].to_vec();
+                    return finished;
+                }
+            }
+        }
+
+        let keep = partial_prefix_len(rest, OSC_133);
+        self.push_text(&rest[..rest.len() - keep]);
+        self.pending = rest[rest.len() - keep..].to_vec();
+        finished
+    }
+
+    /// The most recently finished command.
+    pub fn last_command(&self) -> Option<&CommandCapture> {
+        self.history.back()
+    }
+
+    /// Recently finished commands, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &CommandCapture> {
+        self.history.iter()
+    }
+
+    fn push_text(&mut self, text: &[u8]) {
+        match self.phase {
+            Phase::Input if self.marks_output => self.command.extend_from_slice(text),
+            // cmd.exe can't mark where output starts: the command line is what
+            // was typed up to the first newline
+            Phase::Input => match text.iter().position(|&b| b == b'\n') {
+                Some(newline) => {
+                    self.command.extend_from_slice(&text[..newline]);
+                    if self.command.last() == Some(&b'\r') {
+                        self.command.pop();
+                    }
+                    if clean_command(&self.command).is_empty() {
+                        self.command.clear();
+                    } else {
+                        self.output.clear();
+                        self.truncated = false;
+                        self.phase = Phase::Output;
+                    }
+                    self.push_text(&text[newline + 1..]);
+                }
+                None => self.command.extend_from_slice(text),
+            },
+            Phase::Output => {
+                self.output.extend_from_slice(text);
+                if self.output.len() > self.max_output_bytes {
+                    let excess = self.output.len() - self.max_output_bytes;
+                    self.output.drain(..excess);
+                    self.truncated = true;
+                }
+            }
+            Phase::Idle | Phase::Prompt => {}
+        }
+    }
+
+    fn handle_marker(&mut self, params: &str) -> Option<CommandCapture> {
+        let mut parts = params.split(';');
+        match parts.next()? {
+            "A" => {
+                // A new prompt without a D marker still ends the previous command
+                let capture = (self.phase == Phase::Output).then(|| self.finish(None));
+                self.phase = Phase::Prompt;
+                capture
+            }
+            "B" => {
+                self.command.clear();
+                self.phase = Phase::Input;
+                None
+            }
+            "C" => {
+                self.marks_output = true;
+                self.output.clear();
+                self.truncated = false;
+                self.phase = Phase::Output;
+                None
+            }
+            "D" if self.phase == Phase::Output => {
+                let exit_code = parts.next().and_then(|code| code.trim().parse().ok());
+                self.phase = Phase::Idle;
+                Some(self.finish(exit_code))
+            }
+            _ => None,
+        }
+    }
+
+    fn finish(&mut self, exit_code: Option<i32>) -> CommandCapture {
+        let capture = CommandCapture {
+            command: clean_command(&self.command),
+            output: clean_text(&self.output),
+            exit_code,
+            truncated: self.truncated,
+        };
+
+        self.command.clear();
+        self.output.clear();
+        self.truncated = false;
+
+        if self.history.len() >= MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(capture.clone());
+        capture
+    }
+}
+
+/// Shells [`shell_hook`] has hooks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+    Cmd,
+}
+
+impl Shell {
+    pub const ALL: [Shell; 5] = [Self::Bash, Self::Zsh, Self::Fish, Self::PowerShell, Self::Cmd];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bash => "bash",
+            Self::Zsh => "zsh",
+            Self::Fish => "fish",
+            Self::PowerShell => "powershell",
+            Self::Cmd => "cmd",
+        }
+    }
+
+    /// Parses a shell name or executable path, e.g. `zsh`, `/bin/bash`, `-zsh`
+    /// (login shell), `pwsh.exe`, or `C:\Windows\System32\cmd.exe`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+        let name = name.trim_start_matches('-').to_ascii_lowercase();
+        match name.strip_suffix(".exe").unwrap_or(&name) {
+            "bash" => Some(Self::Bash),
+            "zsh" => Some(Self::Zsh),
+            "fish" => Some(Self::Fish),
+            "pwsh" | "powershell" => Some(Self::PowerShell),
+            "cmd" => Some(Self::Cmd),
+            _ => None,
+        }
+    }
+
+    /// Detects the user's shell from the environment.
+    ///
+    /// `SHELL` wins where set (Unix, Git Bash, MSYS2). On Windows, PowerShell
+    /// is recognized by the per-user module path it adds to `PSModulePath`
+    /// (the system-wide value is set for every process), otherwise `ComSpec`
+    /// names the shell, usually cmd.exe.
+    pub fn detect() -> Option<Self> {
+        detect_from(|name| std::env::var(name).ok(), cfg!(windows))
+    }
+
+    /// Line that installs the hooks when added to the shell's startup file
+    /// (see [`hook`](Self::hook)), or `None` for cmd, which has none.
+    pub fn install_line(&self) -> Option<&'static str> {
+        match self {
+            Self::Bash => Some("eval \"$(nucleus shell-init bash)\""),
+            Self::Zsh => Some("eval \"$(nucleus shell-init zsh)\""),
+            Self::Fish => Some("nucleus shell-init fish | source"),
+            Self::PowerShell => Some("nucleus shell-init powershell | Out-String | Invoke-Expression"),
+            Self::Cmd => None,
+        }
+    }
+
+    /// Startup file relative to the home directory, for shells that have a
+    /// fixed one (PowerShell's `$PROFILE` depends on the version and host).
+    pub fn startup_file(&self) -> Option<&'static str> {
+        match self {
+            Self::Bash => Some(".bashrc"),
+            Self::Zsh => Some(".zshrc"),
+            Self::Fish => Some(".config/fish/config.fish"),
+            Self::PowerShell | Self::Cmd => None,
+        }
+    }
+
+    /// Snippet that emits OSC 133 markers from this shell's prompt.
+    ///
+    /// Install it from the shell's startup file:
+    ///
+    /// - bash, zsh: `eval "$(nucleus shell-init bash)"`
+    /// - fish: `nucleus shell-init fish | source`
+    /// - PowerShell (`$PROFILE`): `nucleus shell-init powershell | Out-String | Invoke-Expression`
+    /// - cmd: run the printed `PROMPT` command, e.g. from the `AutoRun` registry value
+    ///
+    /// cmd can't run code when a command starts, so its prompt only marks
+    /// where commands end; [`CommandTracker`] then takes the first line
+    /// after the prompt as the command and the rest as its output. The same
+    /// applies to PowerShell without PSReadLine.
+    ///
+    /// Except in cmd, the snippet also binds Alt+S to `nucleus shorter`,
+    /// which asks the terminal's last question again for a terse answer.
+    pub fn hook(&self) -> &'static str {
+        match self {
+            Self::Bash => concat!(
+                "__nucleus_precmd() { local ec=$?; printf '\\e]133;D;%s\\a\\e]133;A\\a' \"$ec\"; }\n",
+                "PROMPT_COMMAND=\"__nucleus_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}\"\n",
+                "PS1=\"$PS1\"'\\[\\e]133;B\\a\\]'\n",
+                "PS0=\"$PS0\"'\\e]133;C\\a'\n",
+                "[[ $- == *i* ]] && bind -x '\"\\es\": nucleus shorter'\n",
+            ),
+            Self::Zsh => concat!(
+                "__nucleus_precmd() { local ec=$?; printf '\\e]133;D;%s\\a\\e]133;A\\a' \"$ec\"; }\n",
+                "__nucleus_preexec() { printf '\\e]133;C\\a'; }\n",
+                "precmd_functions=(__nucleus_precmd $precmd_functions)\n",
+                "preexec_functions+=(__nucleus_preexec)\n",
+                "PS1=\"$PS1\"$'%{\\e]133;B\\a%}'\n",
+                "__nucleus_shorter() { zle -I; nucleus shorter </dev/tty; }\n",
+                "zle -N __nucleus_shorter\n",
+                "bindkey '\\es' __nucleus_shorter\n",
+            ),
+            Self::Fish => concat!(
+                "function __nucleus_preexec --on-event fish_preexec\n",
+                "    printf '\\e]133;C\\a'\n",
+                "end\n",
+                "function __nucleus_postexec --on-event fish_postexec\n",
+                "    printf '\\e]133;D;%s\\a\\e]133;A\\a' $status\n",
+                "end\n",
+                "functions -q fish_prompt; and functions -c fish_prompt __nucleus_fish_prompt\n",
+                "function fish_prompt\n",
+                "    __nucleus_fish_prompt\n",
+                "    printf '\\e]133;B\\a'\n",
+                "end\n",
+                "bind \\es 'nucleus shorter; commandline -f repaint'\n",
+            ),
+            // `e only exists in PowerShell 7, so escapes are built with [char]
+            Self::PowerShell => concat!(
+                "$global:__nucleus_prompt = $function:prompt\n",
+                "function global:prompt {\n",
+                "    $ec = if ($?) { 0 } elseif ($LASTEXITCODE) { $LASTEXITCODE } else { 1 }\n",
+                "    $e = [char]27; $a = [char]7\n",
+                "    \"$e]133;D;$ec$a$e]133;A$a\" + (& $global:__nucleus_prompt) + \"$e]133;B$a\"\n",
+                "}\n",
+                "if (Get-Module PSReadLine) {\n",
+                "    Set-PSReadLineKeyHandler -Key Enter -ScriptBlock {\n",
+                "        [Microsoft.PowerShell.PSConsoleReadLine]::AcceptLine()\n",
+                "        [Console]::Write(\"$([char]27)]133;C$([char]7)\")\n",
+                "    }\n",
+                "    Set-PSReadLineKeyHandler -Chord Alt+s -ScriptBlock {\n",
+                "        nucleus shorter | Out-Host\n",
+                "        [Microsoft.PowerShell.PSConsoleReadLine]::InvokePrompt()\n",
+                "    }\n",
+                "}\n",
+            ),
+            // PROMPT has no BEL code, so markers end with ST (ESC \)
+            Self::Cmd => "PROMPT $E]133;D$E\\$E]133;A$E\\$P$G$E]133;B$E\\\n",
+        }
+    }
+}
+
+impl std::fmt::Display for Shell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn detect_from(var: impl Fn(&str) -> Option<String>, windows: bool) -> Option<Shell> {
+    if let Some(shell) = var("SHELL").filter(|shell| !shell.is_empty()) {
+        return Shell::from_name(&shell);
+    }
+    if !windows {
+        return None;
+    }
+
+    let user_modules = var("PSModulePath").is_some_and(|path| {
+        let user = var("USERPROFILE").unwrap_or_default().to_ascii_lowercase();
+        !user.is_empty() && path.split(';').any(|entry| entry.to_ascii_lowercase().starts_with(&user))
+    });
+    if user_modules {
+        return Some(Shell::PowerShell);
+    }
+    var("ComSpec").and_then(|comspec| Shell::from_name(&comspec))
+}
+
+/// Shell snippet that emits OSC 133 markers, for shells without built-in support.
+///
+/// `shell` is anything [`Shell::from_name`] accepts; see [`Shell::hook`].
+pub fn shell_hook(shell: &str) -> Option<&'static str> {
+    Shell::from_name(shell).map(|shell| shell.hook())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Finds the BEL or ST (`ESC \`) ending a marker, as `(position, length)`.
+fn find_terminator(bytes: &[u8]) -> Option<(usize, usize)> {
+    bytes.iter().enumerate().find_map(|(i, &b)| match b {
+        0x07 => Some((i, 1)),
+        0x1b if bytes.get(i + 1) == Some(&b'\\') => Some((i, 2)),
+        _ => None,
+    })
+}
+
+/// Length of the longest suffix of `bytes` that is a proper prefix of `needle`.
+fn partial_prefix_len(bytes: &[u8], needle: &[u8]) -> usize {
+    (1..needle.len().min(bytes.len() + 1))
+        .rev()
+        .find(|&len| bytes.ends_with(&needle[..len]))
+        .unwrap_or(0)
+}
+
+/// Removes escape sequences and applies backspaces and carriage returns.
+fn clean_text(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters, then a final byte in @..~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: until BEL or ST
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\x08' => {
+                line.pop();
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            // A lone carriage return redraws the line (progress bars)
+            '\r' => line.clear(),
+            '\n' => lines.push(std::mem::take(&mut line)),
+            c if c.is_control() && c != '\t' => {}
+            c => line.push(c),
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+/// Cleans the echoed command line; line editors may redraw it several times.
+fn clean_command(bytes: &[u8]) -> String {
+    clean_text(bytes)
+        .lines()
+        .map(str::trim)
+        .rev()
+        .find(|line| !line.is_empty())
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION: &[u8] = b"\x1b]133;A\x07~/app $ \x1b]133;B\x07cargo build\r\n\x1b]133;C\x07\
+        \x1b[1;31merror\x1b[0m: could not compile `app`\r\n\x1b]133;D;101\x07\x1b]133;A\x07~/app $ ";
+
+    #[test]
+    fn test_captures_command_and_output() {
+        let mut tracker = CommandTracker::new(4096);
+        let finished = tracker.feed(SESSION);
+
+        assert_eq!(
+            finished,
+            vec![CommandCapture {
+                command: "cargo build".to_string(),
+                output: "error: could not compile `app`".to_string(),
+                exit_code: Some(101),
+                truncated: false,
+            }]
+        );
+        assert_eq!(tracker.last_command(), finished.first());
+    }
+
+    #[test]
+    fn test_markers_split_across_reads() {
+        let mut tracker = CommandTracker::new(4096);
+        let mut finished = Vec::new();
+        for chunk in SESSION.chunks(3) {
+            finished.extend(tracker.feed(chunk));
+        }
+
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].command, "cargo build");
+        assert_eq!(finished[0].exit_code, Some(101));
+    }
+
+    #[test]
+    fn test_output_keeps_tail() {
+        let mut tracker = CommandTracker::new(6);
+        let finished = tracker.feed(b"\x1b]133;B\x07seq 5\n\x1b]133;C\x071\n2\n3\n4\n5\n\x1b]133;D;0\x1b\\");
+
+        assert_eq!(finished[0].output, "3\n4\n5");
+        assert!(finished[0].truncated);
+    }
+
+    #[test]
+    fn test_command_without_output_marker() {
+        // cmd.exe prompt: markers end with ST, no C marker, no exit code
+        let mut tracker = CommandTracker::new(4096);
+        let finished = tracker.feed(
+            b"\x1b]133;D\x1b\\\x1b]133;A\x1b\\C:\\app>\x1b]133;B\x1b\\\r\n\
+            \x1b]133;D\x1b\\\x1b]133;A\x1b\\C:\\app>\x1b]133;B\x1b\\dir /b\r\nCargo.toml\r\nsrc\r\n\r\n\
+            \x1b]133;D\x1b\\\x1b]133;A\x1b\\C:\\app>",
+        );
+
+        assert_eq!(
+            finished,
+            vec![CommandCapture {
+                command: "dir /b".to_string(),
+                output: "Cargo.toml\nsrc\n".to_string(),
+                exit_code: None,
+                truncated: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_shell_from_name() {
+        assert_eq!(Shell::from_name("/usr/bin/zsh"), Some(Shell::Zsh));
+        assert_eq!(Shell::from_name("-bash"), Some(Shell::Bash));
+        assert_eq!(Shell::from_name("C:\\Program Files\\PowerShell\\7\\pwsh.exe"), Some(Shell::PowerShell));
+        assert_eq!(Shell::from_name("C:\\WINDOWS\\system32\\CMD.EXE"), Some(Shell::Cmd));
+        assert_eq!(Shell::from_name("nu"), None);
+        for shell in Shell::ALL {
+            assert_eq!(Shell::from_name(shell.name()), Some(shell));
+        }
+    }
+
+    #[test]
+    fn test_detect_shell() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+        };
+        const WINDOWS: &[(&str, &str)] = &[
+            ("USERPROFILE", "C:\\Users\\ana"),
+            ("ComSpec", "C:\\WINDOWS\\system32\\cmd.exe"),
+            ("PSModulePath", "C:\\Program Files\\WindowsPowerShell\\Modules;C:\\WINDOWS\\system32\\WindowsPowerShell\\v1.0\\Modules"),
+        ];
+        const POWERSHELL: &[(&str, &str)] = &[
+            ("USERPROFILE", "C:\\Users\\ana"),
+            ("ComSpec", "C:\\WINDOWS\\system32\\cmd.exe"),
+            ("PSModulePath", "C:\\Users\\ana\\Documents\\PowerShell\\Modules;C:\\Program Files\\PowerShell\\Modules"),
+        ];
+
+        assert_eq!(detect_from(env(&[("SHELL", "/bin/zsh")]), false), Some(Shell::Zsh));
+        assert_eq!(detect_from(env(&[]), false), None);
+        assert_eq!(detect_from(env(WINDOWS), true), Some(Shell::Cmd));
+        assert_eq!(detect_from(env(POWERSHELL), true), Some(Shell::PowerShell));
+        assert_eq!(detect_from(env(&[("SHELL", "/usr/bin/bash")]), true), Some(Shell::Bash));
+    }
+
+    #[test]
+    fn test_clean_text() {
+        assert_eq!(clean_text(b"10%\r50%\r100%\r\ndone"), "100%\ndone");
+        assert_eq!(clean_text(b"lss\x08 -la"), "ls -la");
+        assert_eq!(clean_text(b"\x1b]0;title\x07ok"), "ok");
+    }
+}
@@ -0,0 +1,285 @@
+//! Unicode-aware splitting and layout of text.
+//!
+//! Chunks and wrapped lines have to break between grapheme clusters (what a
+//! reader sees as one character), not inside a multi-byte character, an
+//! emoji sequence, or a letter and its accents. Terminals also give CJK
+//! characters and emoji two columns, so counting `char`s misplaces line
+//! breaks.
+//!
+//! The rules here approximate UAX #29 and East Asian Width for what shows up
+//! in code and chat: combining marks, joiners, variation selectors, emoji
+//! modifiers and tags, flags, and the wide CJK and emoji blocks.
+
+const ZWJ: char = '\u{200D}';
+const EMOJI_PRESENTATION: char = '\u{FE0F}';
+
+/// Whether a grapheme cluster may end before byte `index` of `text`.
+///
+/// The start and end of `text` are boundaries; positions inside a `char` are not.
+pub fn is_grapheme_boundary(text: &str, index: usize) -> bool {
+    if index == 0 || index >= text.len() {
+        return true;
+    }
+    if !text.is_char_boundary(index) {
+        return false;
+    }
+    let (before, after) = text.split_at(index);
+    let (Some(prev), Some(next)) = (before.chars().next_back(), after.chars().next()) else {
+        return true;
+    };
+
+    if prev == '\r' && next == '\n' {
+        return false;
+    }
+    if extends(next) || (prev == ZWJ && is_pictographic(next)) {
+        return false;
+    }
+    // Regional indicators pair up into flags
+    if is_regional_indicator(prev) && is_regional_indicator(next) {
+        let preceding = before.chars().rev().take_while(|&c| is_regional_indicator(c)).count();
+        return preceding % 2 == 0;
+    }
+    true
+}
+
+/// The last grapheme boundary at or before byte `index`.
+pub fn floor_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !is_grapheme_boundary(text, index) {
+        index -= 1;
+    }
+    index
+}
+
+/// The first grapheme boundary at or after byte `index`.
+pub fn ceil_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !is_grapheme_boundary(text, index) {
+        index += 1;
+    }
+    index
+}
+
+/// The grapheme clusters of `text`.
+pub fn graphemes(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let end = ceil_boundary(rest, first.len_utf8());
+        let (cluster, remaining) = rest.split_at(end);
+        rest = remaining;
+        Some(cluster)
+    })
+}
+
+/// Terminal columns taken by `text`, which should not contain line breaks.
+pub fn display_width(text: &str) -> usize {
+    graphemes(text).map(cluster_width).sum()
+}
+
+fn cluster_width(cluster: &str) -> usize {
+    let mut chars = cluster.chars();
+    let Some(first) = chars.next() else {
+        return 0;
+    };
+    if is_regional_indicator(first) || (char_width(first) == 1 && cluster.contains(EMOJI_PRESENTATION)) {
+        return 2;
+    }
+    char_width(first)
+}
+
+/// Terminal columns taken by `c` on its own: 0, 1, or 2.
+pub fn char_width(c: char) -> usize {
+    if c.is_control() || extends(c) {
+        0
+    } else if is_wide(c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Characters that attach to the one before them.
+fn extends(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'     // combining diacritical marks
+        | '\u{0483}'..='\u{0489}'   // Cyrillic
+        | '\u{0591}'..='\u{05BD}'   // Hebrew points
+        | '\u{064B}'..='\u{065F}'   // Arabic
+        | '\u{0E31}' | '\u{0E34}'..='\u{0E3A}' | '\u{0E47}'..='\u{0E4E}' // Thai
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{200C}' | ZWJ
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{3099}'..='\u{309A}'   // kana voicing marks
+        | '\u{FE00}'..='\u{FE0F}'   // variation selectors
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{1F3FB}'..='\u{1F3FF}' // skin tone modifiers
+        | '\u{E0020}'..='\u{E007F}' // tags
+        | '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c, '\u{1F1E6}'..='\u{1F1FF}')
+}
+
+fn is_pictographic(c: char) -> bool {
+    matches!(c, '\u{2600}'..='\u{27BF}' | '\u{1F000}'..='\u{1FAFF}')
+}
+
+/// East Asian Wide and Fullwidth characters, and emoji shown as such.
+fn is_wide(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{115F}'     // Hangul Jamo
+        | '\u{231A}'..='\u{231B}' | '\u{23E9}'..='\u{23EC}' | '\u{23F0}' | '\u{23F3}'
+        | '\u{25FD}'..='\u{25FE}' | '\u{2614}'..='\u{2615}' | '\u{26A1}' | '\u{26AA}'..='\u{26AB}'
+        | '\u{26BD}'..='\u{26BE}' | '\u{26C4}'..='\u{26C5}' | '\u{26D4}' | '\u{26EA}'
+        | '\u{26F2}'..='\u{26F5}' | '\u{26FA}' | '\u{26FD}' | '\u{2705}' | '\u{270A}'..='\u{270B}'
+        | '\u{2728}' | '\u{274C}' | '\u{2753}'..='\u{2755}' | '\u{2757}' | '\u{2795}'..='\u{2797}'
+        | '\u{2B1B}'..='\u{2B1C}' | '\u{2B50}' | '\u{2B55}'
+        | '\u{2E80}'..='\u{303E}'   // CJK radicals and punctuation
+        | '\u{3041}'..='\u{33FF}'   // kana, CJK compatibility
+        | '\u{3400}'..='\u{4DBF}'   // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK unified ideographs
+        | '\u{A000}'..='\u{A4CF}'   // Yi
+        | '\u{AC00}'..='\u{D7A3}'   // Hangul syllables
+        | '\u{F900}'..='\u{FAFF}'   // CJK compatibility ideographs
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'   // fullwidth forms
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F004}' | '\u{1F0CF}' | '\u{1F18E}' | '\u{1F191}'..='\u{1F19A}'
+        | '\u{1F200}'..='\u{1F251}'
+        | '\u{1F300}'..='\u{1F64F}' // pictographs, emoticons
+        | '\u{1F680}'..='\u{1F6FF}' // transport and map symbols
+        | '\u{1F7E0}'..='\u{1F7EB}'
+        | '\u{1F90C}'..='\u{1F9FF}'
+        | '\u{1FA70}'..='\u{1FAFF}'
+        | '\u{20000}'..='\u{3FFFD}' // CJK extensions B and later
+    )
+}
+
+/// Wraps streamed text at a terminal width.
+///
+/// Text is handed to [`push`](Self::push) as it arrives; words are held back
+/// until the whitespace after them shows whether they still fit on the
+/// line, so a word split across pushes is measured whole. Words wider than
+/// the line are broken between grapheme clusters.
+#[derive(Debug, Clone)]
+pub struct LineWrapper {
+    width: usize,
+    column: usize,
+    spaces: String,
+    word: String,
+}
+
+impl LineWrapper {
+    /// Creates a wrapper breaking lines at `width` columns; 0 disables wrapping.
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            column: 0,
+            spaces: String::new(),
+            word: String::new(),
+        }
+    }
+
+    /// Takes the next piece of text, returning what can be printed so far.
+    pub fn push(&mut self, text: &str) -> String {
+        if self.width == 0 {
+            return text.to_string();
+        }
+        let mut out = String::new();
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    self.flush_word(&mut out);
+                    self.spaces.clear();
+                    out.push('\n');
+                    self.column = 0;
+                }
+                ' ' | '\t' => {
+                    self.flush_word(&mut out);
+                    self.spaces.push(c);
+                }
+                _ => self.word.push(c),
+            }
+        }
+        out
+    }
+
+    /// Returns the text held back at the end of the stream.
+    pub fn finish(&mut self) -> String {
+        let mut out = String::new();
+        self.flush_word(&mut out);
+        self.spaces.clear();
+        out
+    }
+
+    fn flush_word(&mut self, out: &mut String) {
+        if self.word.is_empty() {
+            return;
+        }
+        let word = std::mem::take(&mut self.word);
+        let spaces = self.spaces_width();
+        let width = display_width(&word);
+
+        if self.column > 0 && self.column + spaces + width > self.width {
+            out.push('\n');
+            self.column = 0;
+        } else {
+            out.push_str(&self.spaces);
+            self.column += spaces;
+        }
+        self.spaces.clear();
+
+        for cluster in graphemes(&word) {
+            let cluster_width = cluster_width(cluster);
+            if self.column > 0 && self.column + cluster_width > self.width {
+                out.push('\n');
+                self.column = 0;
+            }
+            out.push_str(cluster);
+            self.column += cluster_width;
+        }
+    }
+
+    /// Columns taken by the pending whitespace, with tab stops every 8 columns.
+    fn spaces_width(&self) -> usize {
+        self.spaces
+            .chars()
+            .fold(self.column, |column, c| if c == '\t' { (column / 8 + 1) * 8 } else { column + 1 })
+            - self.column
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graphemes_and_width() {
+        let text = "e\u{301}日本👩\u{200D}💻🇯🇵👍🏽";
+        assert_eq!(graphemes(text).collect::<Vec<_>>(), ["e\u{301}", "日", "本", "👩\u{200D}💻", "🇯🇵", "👍🏽"]);
+        assert_eq!(display_width(text), 1 + 4 + 2 + 2 + 2);
+        assert_eq!(display_width("❤\u{FE0F}"), 2);
+        assert!(!is_grapheme_boundary("日", 1));
+        assert_eq!(floor_boundary(text, 2), 0);
+        assert_eq!(ceil_boundary(text, 1), 3);
+    }
+
+    #[test]
+    fn test_line_wrapper() {
+        let mut wrapper = LineWrapper::new(10);
+        let mut out = String::new();
+        // Words and clusters split across pushes are measured whole
+        for piece in ["日本語の", "テキ", "スト and more👩", "\u{200D}💻 text\nnext", " line"] {
+            out.push_str(&wrapper.push(piece));
+        }
+        out.push_str(&wrapper.finish());
+
+        assert_eq!(out, "日本語のテ\nキスト and\nmore👩\u{200D}💻\ntext\nnext line");
+        assert!(out.lines().all(|line| display_width(line) <= 10));
+        assert_eq!(LineWrapper::new(0).push("no wrapping at all"), "no wrapping at all");
+    }
+}
@@ -0,0 +1,308 @@
+//! TODO, FIXME, and HACK comments across indexed sources.
+//!
+//! Markers are found by exact match in the files on disk (not through
+//! embeddings, which would also return chunks that merely talk about work
+//! left to do) and dated with `git blame`, so a `todos` request can list
+//! them by file and age. A `todo-summary` request hands the list to the LLM
+//! for a prioritized summary.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+/// Comment markers, most urgent first.
+pub const MARKERS: [&str; 3] = ["FIXME", "HACK", "TODO"];
+
+/// Comment leaders a marker must follow on its line.
+const COMMENT_LEADERS: [&str; 6] = ["//", "#", "/*", "--", ";", "<!--"];
+
+/// Items included in a summary prompt; the oldest are dropped first.
+const MAX_SUMMARY_ITEMS: usize = 300;
+
+/// Characters of a comment kept.
+const MAX_TEXT_CHARS: usize = 200;
+
+/// A marker comment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Todo {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// `TODO`, `FIXME`, or `HACK`
+    pub marker: String,
+    /// Comment text after the marker
+    pub text: String,
+    /// Who last changed the line, if committed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// When the line was last changed (seconds since the Unix epoch), if committed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub committed_at: Option<u64>,
+}
+
+/// The marker comments of one file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoFile {
+    pub path: String,
+    pub todos: Vec<Todo>,
+}
+
+/// Age group of a marker, from its blame date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Age {
+    Uncommitted,
+    Week,
+    Month,
+    Year,
+    Older,
+}
+
+impl Age {
+    pub const ALL: [Age; 5] = [Self::Uncommitted, Self::Week, Self::Month, Self::Year, Self::Older];
+
+    /// Age of a line committed at `committed_at`, as of `now`.
+    pub fn of(committed_at: Option<u64>, now: u64) -> Self {
+        const DAY: u64 = 86_400;
+        let Some(committed_at) = committed_at else {
+            return Self::Uncommitted;
+        };
+        match now.saturating_sub(committed_at) / DAY {
+            0..7 => Self::Week,
+            7..31 => Self::Month,
+            31..366 => Self::Year,
+            _ => Self::Older,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Uncommitted => "uncommitted",
+            Self::Week => "this week",
+            Self::Month => "this month",
+            Self::Year => "this year",
+            Self::Older => "over a year ago",
+        }
+    }
+}
+
+/// Finds marker comments in `text`, without blame information.
+pub fn scan(text: &str) -> Vec<Todo> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let (marker, text) = find_marker(line)?;
+            Some(Todo {
+                line: index + 1,
+                marker: marker.to_string(),
+                text,
+                author: None,
+                committed_at: None,
+            })
+        })
+        .collect()
+}
+
+/// Returns the first marker in a comment on `line` and the text after it.
+fn find_marker(line: &str) -> Option<(&'static str, String)> {
+    let comment = COMMENT_LEADERS.iter().filter_map(|leader| line.find(leader)).min()?;
+
+    let (start, marker) = MARKERS.iter()
+        .flat_map(|marker| {
+            line[comment..].match_indices(marker).map(move |(start, _)| (comment + start, *marker))
+        })
+        .filter(|(start, marker)| {
+            let before = line[..*start].chars().next_back();
+            let after = line[start + marker.len()..].chars().next();
+            !before.is_some_and(|c| c.is_alphanumeric() || c == '_')
+                && !after.is_some_and(|c| c.is_alphanumeric() || c == '_')
+        })
+        .min_by_key(|(start, _)| *start)?;
+
+    // `TODO(name): text`, `FIXME - text`
+    let mut rest = &line[start + marker.len()..];
+    if rest.starts_with('(') {
+        if let Some(end) = rest.find(')') {
+            rest = &rest[end + 1..];
+        }
+    }
+    let text = rest.trim_start_matches([':', '-', ' ', '\t'])
+        .trim_end_matches("*/")
+        .trim_end_matches("-->")
+        .trim();
+    Some((marker, text.chars().take(MAX_TEXT_CHARS).collect()))
+}
+
+/// Reads the file at `path` and finds its marker comments, with blame
+/// information if it is tracked by git.
+///
+/// Returns `None` if the file cannot be read as text or has no markers.
+/// Runs `git`, so call it off the async runtime.
+pub fn scan_file(path: &Path) -> Option<TodoFile> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let mut todos = scan(&text);
+    if todos.is_empty() {
+        return None;
+    }
+
+    let lines: Vec<usize> = todos.iter().map(|todo| todo.line).collect();
+    let blamed = blame(path, &lines);
+    for todo in &mut todos {
+        if let Some((author, committed_at)) = blamed.get(&todo.line) {
+            todo.author = Some(author.clone());
+            todo.committed_at = Some(*committed_at);
+        }
+    }
+
+    Some(TodoFile {
+        path: path.display().to_string(),
+        todos,
+    })
+}
+
+/// Author and commit time of `lines` of the file at `path`.
+///
+/// Lines that are not committed, and files outside a repository, are left out.
+fn blame(path: &Path, lines: &[usize]) -> HashMap<usize, (String, u64)> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return HashMap::new();
+    };
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+
+    let mut command = Command::new("git");
+    command.arg("blame").arg("--line-porcelain").current_dir(dir);
+    for line in lines {
+        command.arg("-L").arg(format!("{},{}", line, line));
+    }
+    match command.arg("--").arg(name).output() {
+        Ok(output) if output.status.success() => parse_blame(&String::from_utf8_lossy(&output.stdout)),
+        _ => HashMap::new(),
+    }
+}
+
+/// Parses `git blame --line-porcelain` output into line number, author, and commit time.
+fn parse_blame(output: &str) -> HashMap<usize, (String, u64)> {
+    const UNCOMMITTED: &str = "0000000000000000000000000000000000000000";
+
+    let mut blamed = HashMap::new();
+    let mut current: Option<(usize, bool)> = None;
+    let mut author = String::new();
+
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            current = None;
+        } else if let Some(name) = line.strip_prefix("author ") {
+            author = name.to_string();
+        } else if let Some(time) = line.strip_prefix("author-time ") {
+            if let (Some((number, true)), Ok(time)) = (current, time.parse()) {
+                blamed.insert(number, (author.clone(), time));
+            }
+        } else if current.is_none() {
+            // Header: <commit> <original line> <final line> [<lines in group>]
+            let mut fields = line.split(' ');
+            let commit = fields.next().unwrap_or_default();
+            if let Some(number) = fields.nth(1).and_then(|number| number.parse().ok()) {
+                current = Some((number, commit != UNCOMMITTED));
+            }
+        }
+    }
+    blamed
+}
+
+/// Prompt asking for a prioritized summary of `files`, dated as of `now`.
+pub fn summary_prompt(files: &[TodoFile], now: u64) -> String {
+    let mut items: Vec<(&TodoFile, &Todo)> = files.iter()
+        .flat_map(|file| file.todos.iter().map(move |todo| (file, todo)))
+        .collect();
+    let total = items.len();
+    if total > MAX_SUMMARY_ITEMS {
+        // Keep the newest; uncommitted markers count as the newest of all
+        items.sort_by_key(|(_, todo)| std::cmp::Reverse(todo.committed_at.unwrap_or(u64::MAX)));
+        items.truncate(MAX_SUMMARY_ITEMS);
+        items.sort_by_key(|(file, todo)| (&file.path, todo.line));
+    }
+
+    let mut prompt = String::from(
+        "Below are the TODO, FIXME, and HACK comments of a code base, by file, with when each line \
+         was last changed. Write a prioritized summary: group related items into themes, put \
+         likely bugs (FIXME) and workarounds (HACK) before plain TODOs, call out stale items \
+         that have been open for a long time, and reference items as `path:line`.\n",
+    );
+
+    let mut path = "";
+    for (file, todo) in &items {
+        if file.path != path {
+            path = &file.path;
+            prompt.push_str(&format!("\n{}\n", path));
+        }
+        let age = Age::of(todo.committed_at, now).as_str();
+        prompt.push_str(&format!("- line {} {} ({}): {}\n", todo.line, todo.marker, age, todo.text));
+    }
+    if total > items.len() {
+        prompt.push_str(&format!("\n[{} older items not shown]\n", total - items.len()));
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_finds_comment_markers() {
+        let source = "\
+fn main() {
+    // TODO(alice): handle errors
+    let todo_list = vec![]; // not a marker: TODOS
+    /* FIXME - leaks the handle */
+    println!(\"TODO: inside a string\");
+}
+# HACK: pin the version
+";
+        let todos = scan(source);
+        let found: Vec<(usize, &str, &str)> = todos.iter()
+            .map(|todo| (todo.line, todo.marker.as_str(), todo.text.as_str()))
+            .collect();
+        assert_eq!(found, [
+            (2, "TODO", "handle errors"),
+            (4, "FIXME", "leaks the handle"),
+            (7, "HACK", "pin the version"),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_blame() {
+        let output = "\
+1111111111111111111111111111111111111111 3 2 1
+author Alice
+author-time 1700000000
+summary Add main
+\t    // TODO: handle errors
+0000000000000000000000000000000000000000 5 5 1
+author Not Committed Yet
+author-time 1700500000
+\t    // FIXME: new
+";
+        let blamed = parse_blame(output);
+        assert_eq!(blamed.get(&2), Some(&("Alice".to_string(), 1_700_000_000)));
+        assert!(!blamed.contains_key(&5));
+    }
+
+    #[test]
+    fn test_age() {
+        let now = 400 * 86_400;
+        assert_eq!(Age::of(None, now), Age::Uncommitted);
+        assert_eq!(Age::of(Some(now - 86_400), now), Age::Week);
+        assert_eq!(Age::of(Some(now - 20 * 86_400), now), Age::Month);
+        assert_eq!(Age::of(Some(0), now), Age::Older);
+    }
+
+    #[test]
+    fn test_summary_prompt() {
+        let files = vec![TodoFile {
+            path: "src/main.rs".to_string(),
+            todos: scan("// FIXME: crashes on empty input"),
+        }];
+        let prompt = summary_prompt(&files, 0);
+        assert!(prompt.contains("src/main.rs\n- line 1 FIXME (uncommitted): crashes on empty input"));
+    }
+}
@@ -0,0 +1,116 @@
+//! Checks for newer nucleus releases (`updates.check`).
+//!
+//! With checks on, the server asks the release feed at startup and every
+//! `updates.interval_hours`, and mentions a newer release in `stats`;
+//! `nucleus doctor` asks the feed itself. Failed checks are only logged.
+
+use crate::config::UpdateConfig;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// A published release.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Release {
+    /// Git tag, e.g. `v0.2.0`
+    pub tag_name: String,
+    /// Release notes page
+    #[serde(default)]
+    pub html_url: String,
+}
+
+impl Release {
+    /// The version the release was tagged with, without a leading `v`.
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    pub fn is_newer_than(&self, version: &str) -> bool {
+        is_newer(self.version(), version)
+    }
+}
+
+/// Compares `major.minor.patch` versions; pre-release and build suffixes
+/// are ignored, and versions that do not parse are never newer.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    fn parse(version: &str) -> Option<Vec<u64>> {
+        version.split(['-', '+']).next()?.split('.').map(|part| part.parse().ok()).collect()
+    }
+
+    match (parse(candidate), parse(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => false,
+    }
+}
+
+/// Fetches the latest release from `updates.url`.
+pub async fn latest_release(config: &UpdateConfig) -> reqwest::Result<Release> {
+    reqwest::Client::new()
+        .get(&config.url)
+        // GitHub rejects requests without a user agent
+        .header(reqwest::header::USER_AGENT, format!("nucleus/{}", crate::VERSION))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+/// The latest release, if newer than this build, kept up to date in the background.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateNotice {
+    available: Arc<RwLock<Option<Release>>>,
+}
+
+impl UpdateNotice {
+    /// Starts checking for releases if `updates.check` is on.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn start(config: &UpdateConfig) -> Self {
+        let notice = Self::default();
+        if config.check {
+            tokio::spawn(check_periodically(config.clone(), Arc::clone(&notice.available)));
+        }
+        notice
+    }
+
+    /// The newer release found by the last check, if any.
+    pub fn available(&self) -> Option<Release> {
+        self.available.read().unwrap().clone()
+    }
+}
+
+async fn check_periodically(config: UpdateConfig, available: Arc<RwLock<Option<Release>>>) {
+    let interval = Duration::from_secs(config.interval_hours.max(1) * 3600);
+    loop {
+        match latest_release(&config).await {
+            Ok(release) if release.is_newer_than(crate::VERSION) => {
+                tracing::info!("nucleus {} is available (running {})", release.version(), crate::VERSION);
+                *available.write().unwrap() = Some(release);
+            }
+            Ok(_) => *available.write().unwrap() = None,
+            Err(e) => tracing::debug!("Update check failed: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.0", "0.1.9"));
+        assert!(is_newer("1.0.0", "0.10.0"));
+        assert!(is_newer("0.1.10", "0.1.9"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.2.0-rc.1", "0.2.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+
+        let release = Release { tag_name: "v0.3.1".to_string(), html_url: String::new() };
+        assert_eq!(release.version(), "0.3.1");
+        assert!(release.is_newer_than("0.3.0"));
+    }
+}
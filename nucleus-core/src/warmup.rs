@@ -0,0 +1,101 @@
+//! Values built in the background while the server is already serving.
+//!
+//! Connecting to the vector stores and loading a model can take seconds to
+//! minutes, so the server starts them with [`Warmup::spawn`] and accepts
+//! connections straight away. Requests that need one wait on it with
+//! [`Warmup::get`], and are told they are waiting (see `server::handler`).
+
+use std::future::Future;
+use tokio::sync::watch;
+
+#[derive(Clone)]
+enum State<T> {
+    Starting,
+    Ready(T),
+    Failed(String),
+}
+
+/// A value that is being built in the background, or already has been.
+///
+/// Clones share the value.
+#[derive(Clone)]
+pub struct Warmup<T> {
+    state: watch::Receiver<State<T>>,
+}
+
+impl<T: Clone + Send + Sync + 'static> Warmup<T> {
+    /// Starts building the value on a new task.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn spawn<F>(init: F) -> Self
+    where
+        F: Future<Output = Result<T, String>> + Send + 'static,
+    {
+        let (sender, state) = watch::channel(State::Starting);
+        tokio::spawn(async move {
+            let _ = sender.send(match init.await {
+                Ok(value) => State::Ready(value),
+                Err(e) => State::Failed(e),
+            });
+        });
+        Self { state }
+    }
+
+    /// A value that is ready from the start.
+    pub fn ready(value: T) -> Self {
+        let (_, state) = watch::channel(State::Ready(value));
+        Self { state }
+    }
+
+    /// Whether building the value has finished, successfully or not.
+    pub fn is_done(&self) -> bool {
+        !matches!(*self.state.borrow(), State::Starting)
+    }
+
+    /// Waits for the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error building the value failed with.
+    pub async fn get(&self) -> Result<T, String> {
+        let mut state = self.state.clone();
+        let result = match state.wait_for(|state| !matches!(state, State::Starting)).await {
+            Ok(state) => match &*state {
+                State::Ready(value) => Ok(value.clone()),
+                State::Failed(e) => Err(e.clone()),
+                State::Starting => unreachable!("waited until started"),
+            },
+            // The task building the value panicked
+            Err(_) => Err("startup was interrupted".to_string()),
+        };
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_waits_for_the_value() {
+        let (start, started) = tokio::sync::oneshot::channel::<()>();
+        let warmup = Warmup::spawn(async move {
+            let _ = started.await;
+            Ok(42)
+        });
+        assert!(!warmup.is_done());
+
+        let waiting = tokio::spawn({
+            let warmup = warmup.clone();
+            async move { warmup.get().await }
+        });
+        start.send(()).unwrap();
+        assert_eq!(waiting.await.unwrap(), Ok(42));
+        assert!(warmup.is_done());
+        assert_eq!(Warmup::ready(7).get().await, Ok(7));
+
+        let failed = Warmup::<u32>::spawn(async { Err("no model".to_string()) });
+        assert_eq!(failed.get().await, Err("no model".to_string()));
+        assert!(failed.is_done());
+    }
+}
@@ -0,0 +1,88 @@
+/*
+ * C API for embedding the nucleus engine.
+ *
+ * Link against libnucleus_ffi (cdylib or staticlib, built with
+ * `cargo build -p nucleus-ffi --release`).
+ *
+ * Conventions:
+ * - Functions return a NucleusStatus; on failure nucleus_last_error()
+ *   describes the error on the calling thread.
+ * - Input strings are NUL-terminated UTF-8 and borrowed for the call.
+ * - Strings returned through out-parameters belong to the caller and must be
+ *   released with nucleus_string_free().
+ * - A manager may be used from one thread at a time; calls block until done.
+ */
+
+#ifndef NUCLEUS_H
+#define NUCLEUS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Compare with nucleus_abi_version() to detect a mismatched library. */
+#define NUCLEUS_ABI_VERSION 1
+
+typedef enum NucleusStatus {
+    NUCLEUS_OK = 0,
+    /* A required pointer was NULL or a string was not valid UTF-8 */
+    NUCLEUS_INVALID_ARGUMENT = 1,
+    /* The operation failed; see nucleus_last_error() */
+    NUCLEUS_FAILED = 2,
+    /* A bug in nucleus; the manager should not be used further */
+    NUCLEUS_PANICKED = 3,
+} NucleusStatus;
+
+typedef struct NucleusManager NucleusManager;
+
+/* Receives partial chat output; `chunk` is only valid during the call. */
+typedef void (*NucleusChunkCallback)(const char *chunk, void *user_data);
+
+uint32_t nucleus_abi_version(void);
+
+/* Last error on this thread, or NULL. Valid until the next nucleus call on this thread. */
+const char *nucleus_last_error(void);
+
+/*
+ * Creates a manager from a YAML config file, or from config.yaml in the
+ * working directory (falling back to defaults) if `config_path` is NULL.
+ */
+NucleusStatus nucleus_manager_new(const char *config_path, NucleusManager **out);
+
+/* Releases a manager. NULL is ignored. */
+void nucleus_manager_free(NucleusManager *manager);
+
+/*
+ * Asks a question, with knowledge base context and tools.
+ *
+ * `on_chunk` (optional) is called on the calling thread with partial output.
+ * The complete answer is written to `response` (optional).
+ */
+NucleusStatus nucleus_chat(NucleusManager *manager,
+                           const char *prompt,
+                           NucleusChunkCallback on_chunk,
+                           void *user_data,
+                           char **response);
+
+/* Indexes a directory; the number of files is written to `files_indexed` (optional). */
+NucleusStatus nucleus_index(NucleusManager *manager, const char *path, size_t *files_indexed);
+
+/*
+ * Searches the knowledge base without asking the LLM.
+ *
+ * Writes a JSON array of {"id", "content", "score", "metadata"} objects,
+ * best match first. A `limit` of 0 uses the configured storage.top_k.
+ */
+NucleusStatus nucleus_search(NucleusManager *manager, const char *query, size_t limit, char **results_json);
+
+/* Releases a string returned by nucleus. NULL is ignored. */
+void nucleus_string_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* NUCLEUS_H */
@@ -0,0 +1,360 @@
+//! C ABI for embedding the nucleus engine.
+//!
+//! Lets non-Rust applications (e.g. an Electron or Tauri GUI) run the engine
+//! in-process instead of talking to the server socket. The declarations are in
+//! `include/nucleus.h`, which is the reference for callers.
+//!
+//! # Conventions
+//!
+//! - Functions return a [`NucleusStatus`]; on failure, [`nucleus_last_error`]
+//!   describes the error on the calling thread.
+//! - Strings passed in are NUL-terminated UTF-8 and borrowed for the call.
+//! - Strings returned through out-parameters are owned by the caller and must
+//!   be released with [`nucleus_string_free`].
+//! - A manager may be used from one thread at a time; calls block until done.
+//!
+//! Breaking changes bump [`NUCLEUS_ABI_VERSION`].
+
+use nucleus_core::server::SearchHit;
+use nucleus_core::{ChatManager, Config};
+use nucleus_plugin::{Permission, PluginRegistry};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use tokio::runtime::Runtime;
+
+/// Version of the C ABI described by `include/nucleus.h`.
+pub const NUCLEUS_ABI_VERSION: u32 = 1;
+
+/// Result of an FFI call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NucleusStatus {
+    Ok = 0,
+    /// A required pointer was NULL or a string was not valid UTF-8
+    InvalidArgument = 1,
+    /// The operation failed; see [`nucleus_last_error`]
+    Failed = 2,
+    /// A bug in nucleus; the manager should not be used further
+    Panicked = 3,
+}
+
+/// Called with each partial chunk of a chat response.
+///
+/// `chunk` is only valid for the duration of the call.
+pub type NucleusChunkCallback = Option<unsafe extern "C" fn(chunk: *const c_char, user_data: *mut c_void)>;
+
+/// Engine instance owning its own async runtime.
+pub struct NucleusManager {
+    runtime: Runtime,
+    manager: ChatManager,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+enum FfiError {
+    InvalidArgument(&'static str),
+    Failed(String),
+}
+
+type FfiResult<T> = Result<T, FfiError>;
+
+/// Runs `f`, recording its error and converting panics.
+fn guard<F>(f: F) -> NucleusStatus
+where
+    F: FnOnce() -> FfiResult<()>,
+{
+    let (status, message) = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => (NucleusStatus::Ok, None),
+        Ok(Err(FfiError::InvalidArgument(message))) => (NucleusStatus::InvalidArgument, Some(message.to_string())),
+        Ok(Err(FfiError::Failed(message))) => (NucleusStatus::Failed, Some(message)),
+        Err(_) => (NucleusStatus::Panicked, Some("nucleus panicked".to_string())),
+    };
+
+    set_last_error(message);
+    status
+}
+
+fn set_last_error(message: Option<String>) {
+    // Interior NULs would truncate the message; replace them instead
+    let message = message.map(|m| CString::new(m.replace('\0', " ")).unwrap_or_default());
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Borrows a C string argument.
+///
+/// # Safety
+///
+/// `value` must be NULL or point to a NUL-terminated string that outlives the call.
+unsafe fn str_arg<'a>(value: *const c_char, name: &'static str) -> FfiResult<&'a str> {
+    if value.is_null() {
+        return Err(FfiError::InvalidArgument(name));
+    }
+    CStr::from_ptr(value).to_str().map_err(|_| FfiError::InvalidArgument(name))
+}
+
+/// Hands a string to the caller through `out`, if `out` is not NULL.
+///
+/// # Safety
+///
+/// `out` must be NULL or valid for writes.
+unsafe fn write_string(out: *mut *mut c_char, value: String) {
+    if !out.is_null() {
+        *out = CString::new(value.replace('\0', " ")).unwrap_or_default().into_raw();
+    }
+}
+
+fn failed(error: impl std::fmt::Display) -> FfiError {
+    FfiError::Failed(format!("{:#}", error))
+}
+
+/// Returns [`NUCLEUS_ABI_VERSION`], for checking the library matches the header.
+#[no_mangle]
+pub extern "C" fn nucleus_abi_version() -> u32 {
+    NUCLEUS_ABI_VERSION
+}
+
+/// Describes the last error on this thread, or NULL.
+///
+/// The string stays valid until the next nucleus call on this thread.
+#[no_mangle]
+pub extern "C" fn nucleus_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Creates a manager from a YAML config file, or `config.yaml` in the
+/// working directory (falling back to defaults) if `config_path` is NULL.
+///
+/// Tools are the plugins found in `plugins.path`, limited to those that only
+/// read files or use the network; no built-in plugins are registered.
+/// Release with [`nucleus_manager_free`].
+///
+/// # Safety
+///
+/// `config_path` must be NULL or a valid C string; `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn nucleus_manager_new(config_path: *const c_char, out: *mut *mut NucleusManager) -> NucleusStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(FfiError::InvalidArgument("out"));
+        }
+        let config = if config_path.is_null() {
+            Config::load_or_default()
+        } else {
+            Config::load(str_arg(config_path, "config_path")?).map_err(failed)?
+        };
+
+        let runtime = Runtime::new().map_err(failed)?;
+        let registry = PluginRegistry::new(Permission {
+            network: true,
+            ..Permission::READ_ONLY
+        });
+        let manager = runtime.block_on(ChatManager::new(config, registry)).map_err(failed)?;
+
+        *out = Box::into_raw(Box::new(NucleusManager { runtime, manager }));
+        Ok(())
+    })
+}
+
+/// Releases a manager. NULL is ignored.
+///
+/// # Safety
+///
+/// `manager` must be NULL or come from [`nucleus_manager_new`], and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn nucleus_manager_free(manager: *mut NucleusManager) {
+    if !manager.is_null() {
+        drop(Box::from_raw(manager));
+    }
+}
+
+/// Caller-provided chunk callback.
+struct ChunkSink {
+    callback: unsafe extern "C" fn(*const c_char, *mut c_void),
+    user_data: *mut c_void,
+}
+
+// SAFETY: chat runs via `block_on`, which polls the query on the calling
+// thread, so the callback and `user_data` never leave the caller's thread.
+unsafe impl Send for ChunkSink {}
+
+impl ChunkSink {
+    fn emit(&self, chunk: &str) {
+        if let Ok(chunk) = CString::new(chunk) {
+            // SAFETY: the caller guarantees the callback accepts `user_data`
+            unsafe { (self.callback)(chunk.as_ptr(), self.user_data) }
+        }
+    }
+}
+
+/// Asks a question, with knowledge base context and tools.
+///
+/// `on_chunk` (optional) receives partial output as it is generated. The
+/// complete answer is written to `response` (optional).
+///
+/// # Safety
+///
+/// `manager` must be a live manager and `prompt` a valid C string.
+/// `response` must be NULL or valid for writes. `on_chunk` is called on the
+/// calling thread with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn nucleus_chat(
+    manager: *mut NucleusManager,
+    prompt: *const c_char,
+    on_chunk: NucleusChunkCallback,
+    user_data: *mut c_void,
+    response: *mut *mut c_char,
+) -> NucleusStatus {
+    guard(|| {
+        let nucleus = manager.as_ref().ok_or(FfiError::InvalidArgument("manager"))?;
+        let prompt = str_arg(prompt, "prompt")?;
+        let sink = on_chunk.map(|callback| ChunkSink { callback, user_data });
+
+        let answer = nucleus
+            .runtime
+            .block_on(nucleus.manager.query_stream(prompt, move |chunk| {
+                if let Some(sink) = &sink {
+                    sink.emit(chunk);
+                }
+            }))
+            .map_err(failed)?;
+
+        write_string(response, answer);
+        Ok(())
+    })
+}
+
+/// Indexes a directory into the knowledge base.
+///
+/// The number of indexed files is written to `files_indexed` (optional).
+///
+/// # Safety
+///
+/// `manager` must be a live manager and `path` a valid C string.
+/// `files_indexed` must be NULL or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn nucleus_index(
+    manager: *mut NucleusManager,
+    path: *const c_char,
+    files_indexed: *mut usize,
+) -> NucleusStatus {
+    guard(|| {
+        let nucleus = manager.as_ref().ok_or(FfiError::InvalidArgument("manager"))?;
+        let path = str_arg(path, "path")?;
+
+        let count = nucleus
+            .runtime
+            .block_on(nucleus.manager.index_directory(Path::new(path)))
+            .map_err(failed)?;
+
+        if !files_indexed.is_null() {
+            *files_indexed = count;
+        }
+        Ok(())
+    })
+}
+
+/// Searches the knowledge base without asking the LLM.
+///
+/// Results are written to `results_json` as a JSON array of
+/// `{"id", "content", "score", "metadata"}` objects, best match first.
+/// A `limit` of 0 uses the configured `storage.top_k`.
+///
+/// # Safety
+///
+/// `manager` must be a live manager and `query` a valid C string.
+/// `results_json` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn nucleus_search(
+    manager: *mut NucleusManager,
+    query: *const c_char,
+    limit: usize,
+    results_json: *mut *mut c_char,
+) -> NucleusStatus {
+    guard(|| {
+        let nucleus = manager.as_ref().ok_or(FfiError::InvalidArgument("manager"))?;
+        let query = str_arg(query, "query")?;
+        if results_json.is_null() {
+            return Err(FfiError::InvalidArgument("results_json"));
+        }
+
+        let limit = (limit > 0).then_some(limit);
+        let results = nucleus.runtime.block_on(nucleus.manager.search(query, limit)).map_err(failed)?;
+        let hits: Vec<SearchHit> = results.into_iter().map(SearchHit::from).collect();
+
+        write_string(results_json, serde_json::to_string(&hits).map_err(failed)?);
+        Ok(())
+    })
+}
+
+/// Releases a string returned by nucleus. NULL is ignored.
+///
+/// # Safety
+///
+/// `value` must be NULL or a string returned through a nucleus out-parameter,
+/// and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn nucleus_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        let error = nucleus_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_null_arguments() {
+        let query = CString::new("caching").unwrap();
+        let mut results = ptr::null_mut();
+
+        let status = unsafe { nucleus_search(ptr::null_mut(), query.as_ptr(), 0, &mut results) };
+        assert_eq!(status, NucleusStatus::InvalidArgument);
+        assert_eq!(last_error(), "manager");
+        assert!(results.is_null());
+
+        let status = unsafe { nucleus_manager_new(ptr::null(), ptr::null_mut()) };
+        assert_eq!(status, NucleusStatus::InvalidArgument);
+        assert_eq!(last_error(), "out");
+    }
+
+    #[test]
+    fn test_error_is_cleared_on_success() {
+        let missing = CString::new("/nonexistent/nucleus.yaml").unwrap();
+        let mut manager = ptr::null_mut();
+
+        let status = unsafe { nucleus_manager_new(missing.as_ptr(), &mut manager) };
+        assert_eq!(status, NucleusStatus::Failed);
+        assert!(manager.is_null());
+        assert!(!last_error().is_empty());
+
+        assert_eq!(guard(|| Ok(())), NucleusStatus::Ok);
+        assert!(nucleus_last_error().is_null());
+    }
+
+    #[test]
+    fn test_panics_are_contained() {
+        let status = guard(|| panic!("boom"));
+        assert_eq!(status, NucleusStatus::Panicked);
+        assert_eq!(last_error(), "nucleus panicked");
+    }
+
+    #[test]
+    fn test_returned_strings() {
+        let mut value = ptr::null_mut();
+        unsafe { write_string(&mut value, "a\0b".to_string()) };
+        assert_eq!(unsafe { CStr::from_ptr(value) }.to_str().unwrap(), "a b");
+        unsafe { nucleus_string_free(value) };
+    }
+}
@@ -1,5 +1,5 @@
 mod plugin;
 mod registry;
 
-pub use plugin::{Permission, Plugin, PluginError, PluginOutput, Result};
+pub use plugin::{Capability, Permission, Plugin, PluginError, PluginOutput, Result};
 pub use registry::PluginRegistry;
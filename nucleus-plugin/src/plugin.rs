@@ -1,4 +1,5 @@
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fmt;
 use thiserror::Error;
@@ -14,17 +15,53 @@ pub enum PluginError {
     #[error("Permission denied: {0}")]
     PermissionDenied(String),
     
+    /// The plugin needs more than it is granted, see [`PluginRegistry`](crate::PluginRegistry).
+    #[error("Plugin '{plugin}' is not granted {missing}")]
+    Denied { plugin: String, missing: Permission },
+    
     #[error("Plugin error: {0}")]
     Other(String),
 }
 
 pub type Result<T> = std::result::Result<T, PluginError>;
 
-/// Permissions required by a plugin.
+/// One thing a plugin may be allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    /// Read files and directories
+    Read,
+    /// Create, change, or delete files
+    Write,
+    /// Connect to other machines
+    Network,
+    /// Start processes
+    Execute,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [Self::Read, Self::Write, Self::Network, Self::Execute];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Network => "network",
+            Self::Execute => "execute",
+        }
+    }
+}
+
+/// Permissions required by a plugin, or granted to one.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Permission {
+    /// Read files and directories
     pub read: bool,
+    /// Create, change, or delete files
     pub write: bool,
+    /// Connect to other machines
+    pub network: bool,
+    /// Start processes
     pub execute: bool,
 }
 
@@ -32,32 +69,92 @@ impl Permission {
     pub const READ_ONLY: Self = Self {
         read: true,
         write: false,
+        network: false,
         execute: false,
     };
     
     pub const READ_WRITE: Self = Self {
         read: true,
         write: true,
+        network: false,
+        execute: false,
+    };
+    
+    pub const NETWORK: Self = Self {
+        read: false,
+        write: false,
+        network: true,
         execute: false,
     };
     
     pub const ALL: Self = Self {
         read: true,
         write: true,
+        network: true,
         execute: true,
     };
     
     pub const NONE: Self = Self {
         read: false,
         write: false,
+        network: false,
         execute: false,
     };
     
     /// Check if this permission allows the required permission.
     pub fn allows(&self, required: &Permission) -> bool {
-        (!required.read || self.read)
-            && (!required.write || self.write)
-            && (!required.execute || self.execute)
+        self.missing(required) == Self::NONE
+    }
+    
+    /// What of `required` this permission does not allow.
+    pub fn missing(&self, required: &Permission) -> Permission {
+        Self {
+            read: required.read && !self.read,
+            write: required.write && !self.write,
+            network: required.network && !self.network,
+            execute: required.execute && !self.execute,
+        }
+    }
+    
+    pub fn has(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Read => self.read,
+            Capability::Write => self.write,
+            Capability::Network => self.network,
+            Capability::Execute => self.execute,
+        }
+    }
+    
+    /// The capabilities this permission allows.
+    pub fn capabilities(&self) -> Vec<Capability> {
+        Capability::ALL.into_iter().filter(|capability| self.has(*capability)).collect()
+    }
+}
+
+impl FromIterator<Capability> for Permission {
+    fn from_iter<I: IntoIterator<Item = Capability>>(capabilities: I) -> Self {
+        let mut permission = Self::NONE;
+        for capability in capabilities {
+            match capability {
+                Capability::Read => permission.read = true,
+                Capability::Write => permission.write = true,
+                Capability::Network => permission.network = true,
+                Capability::Execute => permission.execute = true,
+            }
+        }
+        permission
+    }
+}
+
+/// Lists the capabilities, e.g. "read, network", or "nothing".
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let capabilities: Vec<&str> = self.capabilities().iter().map(Capability::as_str).collect();
+        if capabilities.is_empty() {
+            write!(f, "nothing")
+        } else {
+            write!(f, "{}", capabilities.join(", "))
+        }
     }
 }
 
@@ -110,6 +207,15 @@ pub trait Plugin: Send + Sync {
     /// Used to enforce security boundaries.
     fn required_permission(&self) -> Permission;
     
+    /// Permissions a call with `input` requires, checked against the
+    /// plugin's grant before every call.
+    /// Defaults to [`required_permission`](Self::required_permission);
+    /// plugins whose needs depend on the input (e.g. on whether a path is
+    /// read or written) can ask for more or less per call.
+    fn permission_for(&self, _input: &Value) -> Permission {
+        self.required_permission()
+    }
+    
     /// Execute the plugin with given input parameters.
     /// The input should match the parameter schema.
     ///
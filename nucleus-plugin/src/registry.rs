@@ -10,26 +10,60 @@ use std::sync::Arc;
 /// - Looking up plugins by name
 /// - Executing plugins
 /// - Providing plugin specifications to the LLM
+///
+/// Each plugin is granted permissions of its own ([`with_grant`](Self::with_grant)),
+/// or else the registry's default grant. A plugin is only registered if its
+/// grant allows its [`required_permission`](Plugin::required_permission),
+/// and every call is checked against the grant as it is then, so a call
+/// needing more, or made after the grant was narrowed, fails with
+/// [`PluginError::Denied`].
 pub struct PluginRegistry {
     plugins: HashMap<String, Arc<dyn Plugin>>,
-    granted_permissions: Permission,
+    /// Grant of plugins without one of their own
+    default_grant: Permission,
+    grants: HashMap<String, Permission>,
 }
 
 impl PluginRegistry {
-    /// Create a new plugin registry with the given permissions.
-    pub fn new(granted_permissions: Permission) -> Self {
+    /// Create a new plugin registry granting plugins without a grant of
+    /// their own `default_grant`.
+    pub fn new(default_grant: Permission) -> Self {
         Self {
             plugins: HashMap::new(),
-            granted_permissions,
+            default_grant,
+            grants: HashMap::new(),
         }
     }
     
-    /// Register a plugin if permissions allow.
-    /// Returns true if the plugin was registered, false if denied by permissions.
+    /// Grants the plugin named `plugin` exactly `grant`, instead of the default.
+    pub fn with_grant(mut self, plugin: impl Into<String>, grant: Permission) -> Self {
+        self.set_grant(plugin, grant);
+        self
+    }
+    
+    /// Like [`with_grant`](Self::with_grant); applies to the next call of
+    /// an already registered plugin.
+    pub fn set_grant(&mut self, plugin: impl Into<String>, grant: Permission) {
+        self.grants.insert(plugin.into(), grant);
+    }
+    
+    /// The permissions the plugin named `plugin` is granted.
+    pub fn grant(&self, plugin: &str) -> Permission {
+        self.grants.get(plugin).copied().unwrap_or(self.default_grant)
+    }
+    
+    /// Register a plugin if its grant allows what it requires.
+    /// Returns true if the plugin was registered, false (with a warning
+    /// naming what the grant lacks) if denied by permissions.
     pub fn register(&mut self, plugin: Arc<dyn Plugin>) -> bool {
-        let required = plugin.required_permission();
+        let missing = self.grant(plugin.name()).missing(&plugin.required_permission());
         
-        if !self.granted_permissions.allows(&required) {
+        if missing != Permission::NONE {
+            tracing::warn!(
+                "Plugin '{}' not registered: it needs {} permission, which is not granted",
+                plugin.name(),
+                missing
+            );
             return false;
         }
         
@@ -37,6 +71,11 @@ impl PluginRegistry {
         true
     }
 
+    /// Removes the plugin named `name`, returning it if it was registered.
+    pub fn unregister(&mut self, name: &str) -> Option<Arc<dyn Plugin>> {
+        self.plugins.remove(name)
+    }
+
     /// Get the number plugins that exist in the registry
     pub fn get_count(&self) -> usize {
         self.plugins.iter().count()
@@ -52,20 +91,36 @@ impl PluginRegistry {
         self.plugins.values().collect()
     }
     
-    /// Execute a plugin by name.
-    pub async fn execute(&self, name: &str, input: Value) -> Result<PluginOutput, PluginError> {
+    /// Get the plugin named `name`, if its grant allows calling it with
+    /// `input`.
+    ///
+    /// The plugin is returned by itself so the call can run without holding
+    /// on to the registry (or a lock around it).
+    pub fn authorize(&self, name: &str, input: &Value) -> Result<Arc<dyn Plugin>, PluginError> {
         let plugin = self
             .get(name)
             .ok_or_else(|| PluginError::Other(format!("Unknown plugin: {}", name)))?;
         
-        plugin.execute(input).await
+        let missing = self.grant(name).missing(&plugin.permission_for(input));
+        if missing != Permission::NONE {
+            return Err(PluginError::Denied { plugin: name.to_string(), missing });
+        }
+        
+        Ok(plugin.clone())
+    }
+    
+    /// Execute a plugin by name, if its grant allows the call.
+    pub async fn execute(&self, name: &str, input: Value) -> Result<PluginOutput, PluginError> {
+        self.authorize(name, &input)?.execute(input).await
     }
     
     /// Get plugin specifications for the LLM.
-    /// Returns a list of tool definitions in a format the LLM can understand.
+    /// Returns a list of tool definitions in a format the LLM can understand,
+    /// leaving out plugins whose grant no longer allows what they require.
     pub fn plugin_specs(&self) -> Vec<Value> {
         self.plugins
             .values()
+            .filter(|plugin| self.grant(plugin.name()).allows(&plugin.required_permission()))
             .map(|plugin| {
                 serde_json::json!({
                     "name": plugin.name(),
@@ -124,4 +179,23 @@ mod tests {
         assert!(!registry.register(plugin));
         assert!(registry.get("test").is_none());
     }
+    
+    #[tokio::test]
+    async fn test_per_plugin_grants() {
+        let mut registry = PluginRegistry::new(Permission::NONE).with_grant("test", Permission::READ_ONLY);
+        assert!(registry.register(Arc::new(TestPlugin)));
+        assert_eq!(registry.grant("other"), Permission::NONE);
+        assert!(registry.execute("test", Value::Null).await.is_ok());
+        assert!(registry.authorize("other", &Value::Null).is_err());
+        
+        registry.set_grant("test", Permission::NETWORK);
+        assert!(registry.plugin_specs().is_empty());
+        match registry.execute("test", Value::Null).await {
+            Err(PluginError::Denied { plugin, missing }) => {
+                assert_eq!(plugin, "test");
+                assert_eq!(missing.to_string(), "read");
+            }
+            other => panic!("expected a denial, got {:?}", other.map(|output| output.content)),
+        }
+    }
 }
@@ -0,0 +1,184 @@
+//! Python bindings for the nucleus server.
+//!
+//! A thin wrapper over [`AiClient`], so notebooks and scripts can use the
+//! same local server and knowledge base as the CLI:
+//!
+//! ```python
+//! import nucleus
+//!
+//! client = nucleus.Client()
+//! client.index("/path/to/project")
+//! for hit in client.search("how are embeddings cached?", limit=3):
+//!     print(hit.score, hit.metadata.get("source"))
+//! print(client.chat("Summarize the caching strategy", on_chunk=lambda c: print(c, end="")))
+//! ```
+//!
+//! Build with `maturin develop` (or `maturin build --release`) from this directory.
+
+use nucleus_core::client::{AiClient, ClientError};
+use nucleus_core::server::{IndexStats as CoreIndexStats, SearchHit as CoreSearchHit};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyConnectionError, PyException, PyTimeoutError};
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+create_exception!(nucleus, NucleusError, PyException, "Request failed on the nucleus server.");
+
+fn to_py_err(error: ClientError) -> PyErr {
+    match error {
+        ClientError::Connect { .. } => PyConnectionError::new_err(error.to_string()),
+        ClientError::Timeout(_) => PyTimeoutError::new_err(error.to_string()),
+        error => NucleusError::new_err(error.to_string()),
+    }
+}
+
+/// Knowledge base search result.
+#[pyclass(frozen, get_all, module = "nucleus")]
+pub struct SearchHit {
+    id: String,
+    content: String,
+    score: f32,
+    metadata: HashMap<String, String>,
+}
+
+#[pymethods]
+impl SearchHit {
+    fn __repr__(&self) -> String {
+        format!("SearchHit(id={:?}, score={:.3})", self.id, self.score)
+    }
+}
+
+impl From<CoreSearchHit> for SearchHit {
+    fn from(hit: CoreSearchHit) -> Self {
+        Self {
+            id: hit.id,
+            content: hit.content,
+            score: hit.score,
+            metadata: hit.metadata,
+        }
+    }
+}
+
+/// Statistics of the active knowledge base collection.
+#[pyclass(frozen, get_all, module = "nucleus")]
+pub struct IndexStats {
+    collection: String,
+    chunks: usize,
+    /// Chunks by source (file, URL, or note)
+    sources: HashMap<String, usize>,
+    /// `None` for a store on a server
+    disk_bytes: Option<u64>,
+    embedding_model: String,
+    embedding_dim: usize,
+    /// Seconds since the Unix epoch
+    last_indexed: Option<u64>,
+    /// Whether a directory is being indexed
+    indexing: bool,
+    /// Sources that changed since they were indexed, with why (`modified`,
+    /// `missing`, or `expired`)
+    stale: HashMap<String, String>,
+}
+
+#[pymethods]
+impl IndexStats {
+    fn __repr__(&self) -> String {
+        format!("IndexStats(collection={:?}, chunks={}, sources={})", self.collection, self.chunks, self.sources.len())
+    }
+}
+
+impl From<CoreIndexStats> for IndexStats {
+    fn from(stats: CoreIndexStats) -> Self {
+        Self {
+            collection: stats.collection,
+            chunks: stats.chunks,
+            sources: stats.sources.into_iter().map(|source| (source.source, source.chunks)).collect(),
+            disk_bytes: stats.disk_bytes,
+            embedding_model: stats.embedding_model,
+            embedding_dim: stats.embedding_dim,
+            last_indexed: stats.last_indexed,
+            indexing: !stats.indexing.is_empty(),
+            stale: stats.stale.into_iter().map(|stale| (stale.source, stale.reason.to_string())).collect(),
+        }
+    }
+}
+
+/// Client for a running nucleus server.
+///
+/// Each call opens a new connection; the client itself holds no state.
+#[pyclass(frozen, module = "nucleus")]
+pub struct Client {
+    inner: AiClient,
+}
+
+#[pymethods]
+impl Client {
+    #[new]
+    #[pyo3(signature = (socket_path=None))]
+    fn new(socket_path: Option<String>) -> Self {
+        let mut inner = AiClient::new();
+        if let Some(socket_path) = socket_path {
+            inner = inner.with_socket_path(socket_path);
+        }
+        Self { inner }
+    }
+
+    /// Asks a question and returns the complete answer.
+    ///
+    /// `on_chunk` is called with each partial chunk as it arrives.
+    #[pyo3(signature = (prompt, pwd=None, on_chunk=None))]
+    fn chat(&self, py: Python<'_>, prompt: &str, pwd: Option<&str>, on_chunk: Option<Py<PyAny>>) -> PyResult<String> {
+        let Some(on_chunk) = on_chunk else {
+            return py.detach(|| self.inner.chat(prompt, pwd, |_| {})).map_err(to_py_err);
+        };
+
+        // Errors raised by the callback stop further calls and are re-raised afterwards
+        let mut callback_error = None;
+        let answer = py.detach(|| {
+            self.inner.chat(prompt, pwd, |chunk| {
+                if callback_error.is_none() {
+                    callback_error = Python::attach(|py| on_chunk.call1(py, (chunk,)).err());
+                }
+            })
+        });
+
+        match callback_error {
+            Some(error) => Err(error),
+            None => answer.map_err(to_py_err),
+        }
+    }
+
+    /// Searches the knowledge base without asking the LLM.
+    #[pyo3(signature = (query, limit=None))]
+    fn search(&self, py: Python<'_>, query: &str, limit: Option<usize>) -> PyResult<Vec<SearchHit>> {
+        let hits = py.detach(|| self.inner.search(query, limit)).map_err(to_py_err)?;
+        Ok(hits.into_iter().map(SearchHit::from).collect())
+    }
+
+    /// Indexes a directory into the knowledge base.
+    fn index(&self, py: Python<'_>, path: &str) -> PyResult<String> {
+        py.detach(|| self.inner.index(path)).map_err(to_py_err)
+    }
+
+    /// Fetches a web page and indexes its readable text into the knowledge base.
+    fn index_url(&self, py: Python<'_>, url: &str) -> PyResult<String> {
+        py.detach(|| self.inner.index_url(url)).map_err(to_py_err)
+    }
+
+    /// Returns statistics of the active knowledge base collection.
+    fn stats(&self, py: Python<'_>) -> PyResult<IndexStats> {
+        py.detach(|| self.inner.stats()).map(IndexStats::from).map_err(to_py_err)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Client(socket_path={:?})", self.inner.socket_path().display().to_string())
+    }
+}
+
+#[pymodule]
+fn nucleus(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Client>()?;
+    m.add_class::<SearchHit>()?;
+    m.add_class::<IndexStats>()?;
+    m.add("NucleusError", m.py().get_type::<NucleusError>())?;
+    Ok(())
+}
@@ -1,12 +1,29 @@
+use nucleus_core::config::PluginsConfig;
+use nucleus_core::dotfiles::expand_home;
 use nucleus_plugin::{Plugin, PluginError, PluginOutput, Permission, Result};
 use async_trait::async_trait;
 use serde::Deserialize;
 use serde_json::Value;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// Entries listed by [`ListDirectoryPlugin`] before the rest are counted.
+const DEFAULT_MAX_ENTRIES: usize = 200;
 
 /// Plugin for reading file contents.
-pub struct ReadFilePlugin;
-pub struct WriteFilePlugin;
+pub struct ReadFilePlugin {
+    workspace: Workspace,
+}
+
+/// Plugin for writing file contents, creating missing parent directories.
+pub struct WriteFilePlugin {
+    workspace: Workspace,
+}
+
+/// Plugin listing the entries of a directory.
+pub struct ListDirectoryPlugin {
+    workspace: Workspace,
+}
 
 #[derive(Debug, Deserialize)]
 struct ReadFileParams {
@@ -19,9 +36,109 @@ struct WriteFileParams {
     content: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct ListDirectoryParams {
+    path: Option<String>,
+    max_entries: Option<usize>,
+}
+
+/// Directory the file plugins are confined to.
+///
+/// Relative paths are taken from the root. Paths leading outside it, through
+/// `..` or a symlink, are refused.
+#[derive(Debug, Clone)]
+struct Workspace {
+    root: PathBuf,
+}
+
+impl Workspace {
+    fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn current() -> Self {
+        Self::new(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+    }
+
+    /// `plugins.workspace_root`, or the current directory if unset.
+    fn from_config(config: &PluginsConfig) -> Self {
+        match &config.workspace_root {
+            Some(root) => {
+                let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+                Self::new(expand_home(root, &home))
+            }
+            None => Self::current(),
+        }
+    }
+
+    /// Resolves `path` to the real path it names inside the workspace.
+    ///
+    /// The part that exists has its symlinks resolved (a dangling symlink
+    /// fails to resolve); the part that does not (a file about to be
+    /// written) is appended as is.
+    fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let root = std::fs::canonicalize(&self.root).map_err(|e| {
+            PluginError::ExecutionFailed(format!("Workspace {} is not accessible: {}", self.root.display(), e))
+        })?;
+        let requested = normalize(&root.join(path));
+        let existing = requested
+            .ancestors()
+            .find(|ancestor| ancestor.symlink_metadata().is_ok())
+            .unwrap_or(&root);
+        let mut resolved = std::fs::canonicalize(existing)
+            .map_err(|e| PluginError::ExecutionFailed(format!("Failed to resolve {}: {}", path, e)))?;
+        // Joining an empty path would add a trailing separator
+        let missing = requested.strip_prefix(existing).unwrap_or(Path::new(""));
+        if !missing.as_os_str().is_empty() {
+            resolved.push(missing);
+        }
+
+        if !resolved.starts_with(&root) {
+            return Err(PluginError::PermissionDenied(format!(
+                "{} is outside the workspace {}",
+                path,
+                root.display()
+            )));
+        }
+        Ok(resolved)
+    }
+}
+
+/// `path` with `.` and `..` components removed, without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// The read_file, write_file and list_dir plugins, confined to
+/// `plugins.workspace_root`.
+pub fn file_plugins(config: &PluginsConfig) -> [Arc<dyn Plugin>; 3] {
+    let workspace = Workspace::from_config(config);
+    [
+        Arc::new(ReadFilePlugin { workspace: workspace.clone() }),
+        Arc::new(WriteFilePlugin { workspace: workspace.clone() }),
+        Arc::new(ListDirectoryPlugin { workspace }),
+    ]
+}
+
 impl ReadFilePlugin {
+    /// Reads files under the current directory.
     pub fn new() -> Self {
-        Self
+        Self { workspace: Workspace::current() }
+    }
+
+    /// Reads files under `root`.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self { workspace: Workspace::new(root) }
     }
 
     pub async fn read(&self, path: &Path) -> Result<PluginOutput> {
@@ -33,8 +150,32 @@ impl ReadFilePlugin {
 }
 
 impl WriteFilePlugin {
+    /// Writes files under the current directory.
     pub fn new() -> Self {
-        Self
+        Self { workspace: Workspace::current() }
+    }
+
+    /// Writes files under `root`.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self { workspace: Workspace::new(root) }
+    }
+}
+
+impl ListDirectoryPlugin {
+    /// Lists directories under the current directory.
+    pub fn new() -> Self {
+        Self { workspace: Workspace::current() }
+    }
+
+    /// Lists directories under `root`.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self { workspace: Workspace::new(root) }
+    }
+}
+
+impl Default for ListDirectoryPlugin {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -55,7 +196,7 @@ impl Plugin for ReadFilePlugin {
             "properties": {
                 "path": {
                     "type": "string",
-                    "description": "Absolute or relative path to the file to read"
+                    "description": "Path to the file to read, relative to the workspace root"
                 }
             }
         })
@@ -69,7 +210,7 @@ impl Plugin for ReadFilePlugin {
         let params: ReadFileParams = serde_json::from_value(input)
             .map_err(|e| PluginError::InvalidInput(format!("Invalid parameters: {}", e)))?;
         
-        let path = PathBuf::from(&params.path);
+        let path = self.workspace.resolve(&params.path)?;
         
         // Read file
         let content = tokio::fs::read_to_string(&path)
@@ -100,7 +241,7 @@ impl Plugin for WriteFilePlugin {
             "properties": {
                 "path": {
                     "type": "string",
-                    "description": "Absolute or relative path to the file to write to"
+                    "description": "Path to the file to write to, relative to the workspace root"
                 },
                 "content": {
                     "type": "string",
@@ -118,8 +259,13 @@ impl Plugin for WriteFilePlugin {
         let params: WriteFileParams = serde_json::from_value(input)
             .map_err(|e| PluginError::InvalidInput(format!("Invalid parameters: {}", e)))?;
         
-        let path = PathBuf::from(&params.path);
+        let path = self.workspace.resolve(&params.path)?;
         
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| PluginError::ExecutionFailed(format!("Failed to create {}: {}", parent.display(), e)))?;
+        }
         tokio::fs::write(&path, &params.content)
             .await
             .map_err(|e| PluginError::ExecutionFailed(format!("Failed to write file: {}", e)))?;
@@ -130,6 +276,76 @@ impl Plugin for WriteFilePlugin {
     }
 }
 
+#[async_trait]
+impl Plugin for ListDirectoryPlugin {
+    fn name(&self) -> &str {
+        "list_dir"
+    }
+
+    fn description(&self) -> &str {
+        "List the files and subdirectories of a directory, with file sizes"
+    }
+
+    fn parameter_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Directory to list, relative to the workspace root (defaults to the root)"
+                },
+                "max_entries": {
+                    "type": "number",
+                    "description": "Entries listed before the rest are counted",
+                    "default": DEFAULT_MAX_ENTRIES
+                }
+            }
+        })
+    }
+
+    fn required_permission(&self) -> Permission {
+        Permission::READ_ONLY
+    }
+
+    async fn execute(&self, input: Value) -> Result<PluginOutput> {
+        let params: ListDirectoryParams = serde_json::from_value(input)
+            .map_err(|e| PluginError::InvalidInput(format!("Invalid parameters: {}", e)))?;
+
+        let path = self.workspace.resolve(params.path.as_deref().unwrap_or("."))?;
+        let max_entries = params.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES);
+
+        let mut reader = tokio::fs::read_dir(&path)
+            .await
+            .map_err(|e| PluginError::ExecutionFailed(format!("Failed to read directory: {}", e)))?;
+        let mut entries = Vec::new();
+        while let Some(entry) = reader
+            .next_entry()
+            .await
+            .map_err(|e| PluginError::ExecutionFailed(format!("Failed to read directory: {}", e)))?
+        {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let line = match entry.metadata().await {
+                Ok(metadata) if metadata.is_dir() => format!("{}/", name),
+                Ok(metadata) => format!("{} ({} bytes)", name, metadata.len()),
+                Err(_) => name,
+            };
+            entries.push(line);
+        }
+        entries.sort();
+
+        let total = entries.len();
+        let mut content = entries.into_iter().take(max_entries).collect::<Vec<_>>().join("\n");
+        if total > max_entries {
+            content.push_str(&format!("\n... {} more entries", total - max_entries));
+        }
+        if total == 0 {
+            content = "(empty directory)".to_string();
+        }
+
+        Ok(PluginOutput::new(content).with_metadata(serde_json::json!({ "entries": total })))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,7 +360,7 @@ mod tests {
         std::fs::write(&test_file, test_content).unwrap();
         
         // Test reading it
-        let plugin = ReadFilePlugin::new();
+        let plugin = ReadFilePlugin::with_root(&temp_dir);
         let input = serde_json::json!({
             "path": test_file.to_str().unwrap()
         });
@@ -173,7 +389,7 @@ mod tests {
         let test_file = temp_dir.join("nucleus_test_write.txt");
         let test_content = "Written by nucleus!";
         
-        let plugin = WriteFilePlugin::new();
+        let plugin = WriteFilePlugin::with_root(&temp_dir);
         let input = serde_json::json!({
             "path": test_file.to_str().unwrap(),
             "content": test_content
@@ -195,7 +411,7 @@ mod tests {
         
         std::fs::remove_file(&test_file).ok();
         
-        let plugin = WriteFilePlugin::new();
+        let plugin = WriteFilePlugin::with_root(&temp_dir);
         let input = serde_json::json!({
             "path": test_file.to_str().unwrap(),
             "content": "New file content"
@@ -206,4 +422,69 @@ mod tests {
         
         std::fs::remove_file(test_file).ok();
     }
+    
+    #[tokio::test]
+    async fn test_paths_outside_workspace_are_denied() {
+        let root = std::env::temp_dir().join("nucleus_test_workspace_sandbox");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub fn f() {}").unwrap();
+        
+        let read = ReadFilePlugin::with_root(&root);
+        let content = read.execute(serde_json::json!({ "path": "src/../src/lib.rs" })).await.unwrap();
+        assert_eq!(content.content, "pub fn f() {}");
+        
+        let outside = std::env::temp_dir().join("nucleus_test_outside.txt");
+        for path in ["../nucleus_test_outside.txt", outside.to_str().unwrap(), "/etc/hosts"] {
+            let result = read.execute(serde_json::json!({ "path": path })).await;
+            assert!(matches!(result, Err(PluginError::PermissionDenied(_))), "{}", path);
+        }
+        
+        let write = WriteFilePlugin::with_root(&root);
+        let result = write.execute(serde_json::json!({ "path": "new/../../escape.txt", "content": "x" })).await;
+        assert!(matches!(result, Err(PluginError::PermissionDenied(_))));
+        write.execute(serde_json::json!({ "path": "docs/notes.md", "content": "notes" })).await.unwrap();
+        assert!(root.join("docs/notes.md").exists());
+        
+        #[cfg(unix)]
+        {
+            let link = root.join("escape");
+            std::fs::remove_file(&link).ok();
+            std::os::unix::fs::symlink(std::env::temp_dir(), &link).unwrap();
+            let result = write.execute(serde_json::json!({ "path": "escape/nucleus_test_outside.txt", "content": "x" })).await;
+            assert!(matches!(result, Err(PluginError::PermissionDenied(_))));
+        }
+        
+        std::fs::remove_dir_all(root).ok();
+    }
+    
+    #[tokio::test]
+    async fn test_list_dir() {
+        let root = std::env::temp_dir().join("nucleus_test_workspace_list");
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+        std::fs::write(root.join("README.md"), "").unwrap();
+        
+        let plugin = ListDirectoryPlugin::with_root(&root);
+        let result = plugin.execute(serde_json::json!({})).await.unwrap();
+        assert_eq!(result.content, "Cargo.toml (9 bytes)\nREADME.md (0 bytes)\nsrc/");
+        
+        let result = plugin.execute(serde_json::json!({ "max_entries": 1 })).await.unwrap();
+        assert_eq!(result.content, "Cargo.toml (9 bytes)\n... 2 more entries");
+        
+        let result = plugin.execute(serde_json::json!({ "path": "src" })).await.unwrap();
+        assert_eq!(result.content, "(empty directory)");
+        assert!(plugin.execute(serde_json::json!({ "path": ".." })).await.is_err());
+        
+        // The configured root applies to every file plugin
+        let config = PluginsConfig { workspace_root: Some(root.display().to_string()), ..PluginsConfig::default() };
+        let plugins = file_plugins(&config);
+        let names: Vec<&str> = plugins.iter().map(|plugin| plugin.name()).collect();
+        assert_eq!(names, ["read_file", "write_file", "list_dir"]);
+        let result = plugins[2].execute(serde_json::json!({})).await.unwrap();
+        assert!(result.content.starts_with("Cargo.toml"));
+        assert!(plugins[0].execute(serde_json::json!({ "path": "../x" })).await.is_err());
+        
+        std::fs::remove_dir_all(root).ok();
+    }
 }
@@ -2,15 +2,20 @@
 //!
 //! The standard library is a collection of built-in plugins that are typical in most use-cases.
 //! Provides essential plugins that work out of the box:
-//! - File operations (read, write, list)
+//! - File operations (read, write, list), confined to a workspace root
 //! - Search (text and code search)
+//! - Project tree summaries
+//! - Web search (DuckDuckGo)
 //! - Execution (safe command execution)
 
 mod commands;
 mod files;
 mod search;
+mod tree;
+mod web_search;
 
-pub use files::{ReadFilePlugin, WriteFilePlugin};
+pub use files::{file_plugins, ListDirectoryPlugin, ReadFilePlugin, WriteFilePlugin};
 pub use search::SearchPlugin;
-// TODO: Implement ListDirectoryPlugin
+pub use tree::ProjectTreePlugin;
+pub use web_search::WebSearchPlugin;
 // TODO: Implement command execution
@@ -0,0 +1,90 @@
+use nucleus_core::project_tree::{self, TreeOptions};
+use nucleus_plugin::{Permission, Plugin, PluginError, PluginOutput, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Plugin that summarizes a project's directory layout.
+#[derive(Default)]
+pub struct ProjectTreePlugin;
+
+#[derive(Debug, Deserialize)]
+struct ProjectTreeParams {
+    path: Option<String>,
+    depth: Option<usize>,
+    max_entries: Option<usize>,
+}
+
+impl ProjectTreePlugin {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Plugin for ProjectTreePlugin {
+    fn name(&self) -> &str {
+        "project_tree"
+    }
+
+    fn description(&self) -> &str {
+        "Show a depth-limited tree of a project with sizes, languages, and notable files"
+    }
+
+    fn parameter_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Project directory (defaults to current directory)"
+                },
+                "depth": {
+                    "type": "number",
+                    "description": "Directory levels to expand",
+                    "default": 3
+                },
+                "max_entries": {
+                    "type": "number",
+                    "description": "Entries shown per directory before the rest are summarized",
+                    "default": 25
+                }
+            }
+        })
+    }
+
+    fn required_permission(&self) -> Permission {
+        Permission::READ_ONLY
+    }
+
+    async fn execute(&self, input: Value) -> Result<PluginOutput> {
+        let params: ProjectTreeParams = serde_json::from_value(input)
+            .map_err(|e| PluginError::InvalidInput(format!("Invalid parameters: {}", e)))?;
+
+        let root = params.path
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("."));
+
+        let defaults = TreeOptions::default();
+        let options = TreeOptions {
+            max_depth: params.depth.unwrap_or(defaults.max_depth),
+            max_entries: params.max_entries.unwrap_or(defaults.max_entries),
+            ..defaults
+        };
+
+        // Walking a large tree is blocking filesystem work
+        let (tree, options) = tokio::task::spawn_blocking(move || {
+            project_tree::build(&root, &options).map(|tree| (tree, options))
+        })
+        .await
+        .map_err(|e| PluginError::ExecutionFailed(format!("Tree task failed: {}", e)))?
+        .map_err(|e| PluginError::ExecutionFailed(format!("Failed to read directory: {}", e)))?;
+
+        Ok(PluginOutput::new(project_tree::render(&tree, &options))
+            .with_metadata(serde_json::json!({
+                "files": tree.file_count,
+                "bytes": tree.size
+            })))
+    }
+}
@@ -0,0 +1,356 @@
+use async_trait::async_trait;
+use nucleus_plugin::{Permission, Plugin, PluginError, PluginOutput, Result};
+use regex::Regex;
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// DuckDuckGo's HTML results page, which needs no API key or JavaScript.
+const DEFAULT_ENDPOINT: &str = "https://html.duckduckgo.com/html/";
+
+/// Most results returned for one search; the results page holds about 30.
+const MAX_RESULTS: usize = 25;
+
+/// DuckDuckGo turns away requests without a browser-like user agent.
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; nucleus web search)";
+
+static ANCHOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<a\s([^>]*)>(.*?)</a>").unwrap());
+static CLASS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"class="([^"]*)""#).unwrap());
+static HREF: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"href="([^"]*)""#).unwrap());
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// Plugin that searches the web with DuckDuckGo.
+pub struct WebSearchPlugin {
+    client: reqwest::Client,
+    endpoint: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebSearchParams {
+    query: String,
+    #[serde(default = "default_max_results")]
+    max_results: usize,
+    /// DuckDuckGo region, e.g. `us-en` or `de-de`
+    region: Option<String>,
+}
+
+fn default_max_results() -> usize {
+    5
+}
+
+/// A search result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct WebResult {
+    title: String,
+    url: String,
+    snippet: String,
+}
+
+impl WebSearchPlugin {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+        }
+    }
+
+    /// Searches a DuckDuckGo-compatible results page at `endpoint` instead.
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+}
+
+impl Default for WebSearchPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Plugin for WebSearchPlugin {
+    fn name(&self) -> &str {
+        "web_search"
+    }
+
+    fn description(&self) -> &str {
+        "Search the web with DuckDuckGo and return the titles, URLs, and snippets of the top results"
+    }
+
+    fn parameter_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "required": ["query"],
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "What to search for"
+                },
+                "max_results": {
+                    "type": "number",
+                    "description": "Maximum number of results to return (at most 25)",
+                    "default": 5
+                },
+                "region": {
+                    "type": "string",
+                    "description": "Region for the results, e.g. 'us-en' or 'de-de'"
+                }
+            }
+        })
+    }
+
+    fn required_permission(&self) -> Permission {
+        Permission::NETWORK
+    }
+
+    async fn execute(&self, input: Value) -> Result<PluginOutput> {
+        let params: WebSearchParams = serde_json::from_value(input)
+            .map_err(|e| PluginError::InvalidInput(format!("Invalid parameters: {}", e)))?;
+        let query = params.query.trim();
+        if query.is_empty() {
+            return Err(PluginError::InvalidInput("Query is empty".to_string()));
+        }
+        if params.max_results == 0 {
+            return Err(PluginError::InvalidInput("max_results must be at least 1".to_string()));
+        }
+
+        let mut request = self.client
+            .get(&self.endpoint)
+            .query(&[("q", query)])
+            .header(reqwest::header::USER_AGENT, USER_AGENT)
+            .timeout(Duration::from_secs(15));
+        if let Some(region) = &params.region {
+            request = request.query(&[("kl", region)]);
+        }
+
+        let response = request.send().await.map_err(|e| {
+            if e.is_timeout() {
+                PluginError::ExecutionFailed("Web search timed out".to_string())
+            } else {
+                PluginError::ExecutionFailed(format!("Web search failed: {}", e))
+            }
+        })?;
+        let status = response.status();
+        // DuckDuckGo answers throttled clients with 202 and an empty page
+        if matches!(status, StatusCode::ACCEPTED | StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS) {
+            return Err(PluginError::ExecutionFailed(format!(
+                "DuckDuckGo is rate limiting searches (HTTP {}); try again later",
+                status.as_u16()
+            )));
+        }
+        if !status.is_success() {
+            return Err(PluginError::ExecutionFailed(format!("DuckDuckGo returned HTTP {}", status.as_u16())));
+        }
+        let html = response
+            .text()
+            .await
+            .map_err(|e| PluginError::ExecutionFailed(format!("Failed to read search results: {}", e)))?;
+
+        let results = parse_results(&html, params.max_results.min(MAX_RESULTS));
+        if results.is_empty() && html.contains("anomaly-modal") {
+            return Err(PluginError::ExecutionFailed(
+                "DuckDuckGo asked to confirm a human is searching; try again later".to_string(),
+            ));
+        }
+
+        let result_json = serde_json::json!({
+            "summary": format!("Found {} results for '{}'", results.len(), query),
+            "results": results
+        });
+
+        Ok(PluginOutput::new(serde_json::to_string_pretty(&result_json).unwrap()))
+    }
+}
+
+/// The first `max_results` organic results on a DuckDuckGo HTML results
+/// page; ads are skipped.
+fn parse_results(html: &str, max_results: usize) -> Vec<WebResult> {
+    let mut results: Vec<WebResult> = Vec::new();
+    // Whether the last result link was kept, so an ad's snippet isn't taken
+    let mut kept = false;
+    for anchor in ANCHOR.captures_iter(html) {
+        let attributes = &anchor[1];
+        let class = CLASS.captures(attributes).map_or("", |class| class.get(1).unwrap().as_str());
+        let classes: Vec<&str> = class.split_whitespace().collect();
+
+        if classes.contains(&"result__a") {
+            if results.len() == max_results {
+                break;
+            }
+            let href = HREF.captures(attributes).map(|href| decode_entities(&href[1]));
+            kept = false;
+            let Some(url) = href.as_deref().and_then(result_url) else {
+                continue;
+            };
+            results.push(WebResult {
+                title: text(&anchor[2]),
+                url,
+                snippet: String::new(),
+            });
+            kept = true;
+        } else if classes.contains(&"result__snippet") && kept {
+            if let Some(result) = results.last_mut().filter(|result| result.snippet.is_empty()) {
+                result.snippet = text(&anchor[2]);
+            }
+        }
+    }
+    results
+}
+
+/// The target of a result link, which DuckDuckGo routes through a redirect
+/// (`//duckduckgo.com/l/?uddg=<url>`); `None` for ads.
+fn result_url(href: &str) -> Option<String> {
+    let href = if href.starts_with("//") { format!("https:{}", href) } else { href.to_string() };
+    let url = Url::parse(&href).ok()?;
+    if !url.host_str().is_some_and(|host| host == "duckduckgo.com" || host.ends_with(".duckduckgo.com")) {
+        return Some(href);
+    }
+    match url.path() {
+        "/l/" => url.query_pairs().find(|(key, _)| key == "uddg").map(|(_, target)| target.into_owned()),
+        _ => None,
+    }
+}
+
+/// The text of an HTML fragment, whitespace collapsed.
+fn text(html: &str) -> String {
+    let text = decode_entities(&TAG.replace_all(html, ""));
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').filter(|&end| end <= 10).and_then(|end| {
+            let name = &rest[1..end + 1];
+            let c = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                    None => name.strip_prefix('#').and_then(|dec| dec.parse().ok()).and_then(char::from_u32),
+                },
+            };
+            c.map(|c| (c, end + 2))
+        });
+        match entity {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const RESULTS_PAGE: &str = r#"
+        <div class="result results_links results_links_deep result--ad">
+          <h2 class="result__title">
+            <a rel="nofollow" class="result__a" href="https://duckduckgo.com/y.js?ad_domain=example.com&amp;u3=1">Sponsored</a>
+          </h2>
+          <a class="result__snippet" href="https://duckduckgo.com/y.js?ad_domain=example.com">Buy now</a>
+        </div>
+        <div class="result results_links results_links_deep web-result">
+          <h2 class="result__title">
+            <a rel="nofollow" class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fdoc.rust%2Dlang.org%2Fbook%2F&amp;rut=abc">The Rust
+              Programming Language</a>
+          </h2>
+          <a class="result__snippet" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fdoc.rust%2Dlang.org%2Fbook%2F">An <b>introductory</b> book about Rust &amp; its &#x27;ownership&#x27;.</a>
+        </div>
+        <div class="result results_links results_links_deep web-result">
+          <h2 class="result__title"><a class="result__a" href="https://www.rust-lang.org/">Rust</a></h2>
+          <a class="result__snippet" href="https://www.rust-lang.org/">Reliable software.</a>
+        </div>
+    "#;
+
+    #[test]
+    fn test_registers_only_with_network() {
+        use nucleus_plugin::PluginRegistry;
+        use std::sync::Arc;
+
+        let mut registry = PluginRegistry::new(Permission::READ_ONLY);
+        assert!(!registry.register(Arc::new(WebSearchPlugin::new())));
+        // The default registry of embedders (see nucleus-ffi)
+        let mut registry = PluginRegistry::new(Permission {
+            network: true,
+            ..Permission::READ_ONLY
+        });
+        assert!(registry.register(Arc::new(WebSearchPlugin::new())));
+        let mut registry = PluginRegistry::new(Permission::READ_ONLY).with_grant("web_search", Permission::NETWORK);
+        assert!(registry.register(Arc::new(WebSearchPlugin::new())));
+    }
+
+    #[test]
+    fn test_parse_results() {
+        let results = parse_results(RESULTS_PAGE, 5);
+        assert_eq!(
+            results,
+            [
+                WebResult {
+                    title: "The Rust Programming Language".to_string(),
+                    url: "https://doc.rust-lang.org/book/".to_string(),
+                    snippet: "An introductory book about Rust & its 'ownership'.".to_string(),
+                },
+                WebResult {
+                    title: "Rust".to_string(),
+                    url: "https://www.rust-lang.org/".to_string(),
+                    snippet: "Reliable software.".to_string(),
+                },
+            ]
+        );
+        assert_eq!(parse_results(RESULTS_PAGE, 1).len(), 1);
+    }
+
+    /// Serves one HTTP response to the first request on a local port.
+    async fn serve_once(status: &'static str, body: &'static str) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).await;
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+        format!("http://{}/html/", address)
+    }
+
+    #[tokio::test]
+    async fn test_execute() {
+        let plugin = WebSearchPlugin::new().with_endpoint(serve_once("200 OK", RESULTS_PAGE).await);
+        let output = plugin.execute(serde_json::json!({ "query": "rust book", "max_results": 1 })).await.unwrap();
+        let output: Value = serde_json::from_str(&output.content).unwrap();
+        assert_eq!(output["summary"], "Found 1 results for 'rust book'");
+        assert_eq!(output["results"][0]["url"], "https://doc.rust-lang.org/book/");
+
+        let plugin = WebSearchPlugin::new().with_endpoint(serve_once("202 Accepted", "").await);
+        let error = plugin.execute(serde_json::json!({ "query": "rust" })).await.unwrap_err();
+        assert!(matches!(error, PluginError::ExecutionFailed(message) if message.contains("rate limiting")));
+
+        let error = plugin.execute(serde_json::json!({ "query": " " })).await.unwrap_err();
+        assert!(matches!(error, PluginError::InvalidInput(_)));
+    }
+}